@@ -0,0 +1,112 @@
+//! Processes the notifiers start for every message (like `curl`), which run in the
+//! background so that a slow server doesn't hold up the wipe.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+
+/// Running processes along with the files to remove once they exit, and the number of
+/// the ones which failed.
+#[derive(Default)]
+pub(crate) struct BackgroundProcesses {
+    pending: Vec<(Child, Option<PathBuf>)>,
+    failed: usize,
+}
+
+impl BackgroundProcesses {
+    /// Starts the command with the output discarded and the input written to its standard
+    /// input. The file is removed once the process exits, or right away if it can't be
+    /// started.
+    pub fn spawn(&mut self, command: &mut Command, input: Option<&[u8]>, file: Option<PathBuf>) {
+        let spawned = command
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match spawned {
+            Ok(c) => c,
+            Err(_) => {
+                if let Some(path) = &file {
+                    let _ = std::fs::remove_file(path);
+                }
+                self.failed += 1;
+                return;
+            }
+        };
+
+        if let Some(input) = input {
+            let written = child
+                .stdin
+                .take()
+                .map(|mut stdin| stdin.write_all(input).is_ok())
+                .unwrap_or(false);
+            if !written {
+                self.failed += 1;
+            }
+        }
+        self.pending.push((child, file));
+    }
+
+    /// Collects the processes which have exited, or waits for all of them.
+    pub fn reap(&mut self, wait: bool) {
+        let mut failed = 0;
+        self.pending.retain_mut(|(child, file)| {
+            let status = if wait {
+                child.wait().map(Some)
+            } else {
+                child.try_wait()
+            };
+            let done = match status {
+                Ok(Some(s)) => {
+                    if !s.success() {
+                        failed += 1;
+                    }
+                    true
+                }
+                Ok(None) => false,
+                Err(_) => {
+                    failed += 1;
+                    true
+                }
+            };
+            if let (true, Some(path)) = (done, file) {
+                let _ = std::fs::remove_file(path);
+            }
+            !done
+        });
+        self.failed += failed;
+    }
+
+    /// Counts a message which couldn't be sent before any process was started for it.
+    pub fn add_failure(&mut self) {
+        self.failed += 1;
+    }
+
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_failures_are_counted() {
+        let mut processes = BackgroundProcesses::default();
+        processes.spawn(&mut Command::new("true"), None, None);
+        processes.spawn(&mut Command::new("false"), None, None);
+        processes.spawn(&mut Command::new("cat"), Some(b"input"), None);
+        processes.spawn(&mut Command::new("/nonexistent/lethe"), None, None);
+        assert_eq!(processes.failed(), 1);
+
+        processes.reap(true);
+        assert_eq!(processes.failed(), 2);
+        assert!(processes.pending.is_empty());
+    }
+}
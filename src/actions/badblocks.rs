@@ -0,0 +1,104 @@
+use crate::actions::{WipeRange, WipeState, WipeTask};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Bad blocks found during a wipe, saved as byte ranges so the map can be used with
+/// a different block size later.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BadBlockMap {
+    pub size: u64,
+    pub block_size: usize,
+    pub ranges: Vec<WipeRange>,
+}
+
+impl BadBlockMap {
+    pub fn new(total_size: u64, block_size: usize, state: &WipeState) -> Self {
+        BadBlockMap {
+            size: total_size,
+            block_size,
+            ranges: WipeRange::from_blocks(
+                &state.bad_blocks.borrow().all_marked(),
+                block_size,
+                total_size,
+            ),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = std::fs::read(path).context("Unable to read bad block map")?;
+        serde_json::from_slice(&content).context("Corrupted bad block map")
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .context("Unable to save bad block map")
+    }
+
+    /// Marks the blocks of the task overlapping the bad ranges so the wipe skips them
+    /// right away. Returns the number of blocks marked.
+    pub fn apply(&self, task: &WipeTask, state: &mut WipeState) -> Result<u32> {
+        if self.size != task.total_size {
+            return Err(anyhow!(
+                "Bad block map is for a device of {} bytes, this one has {}",
+                self.size,
+                task.total_size
+            ));
+        }
+
+        let block_size = task.block_size as u64;
+        let mut marker = state.bad_blocks.borrow_mut();
+        let before = marker.total_marked();
+        for r in self.ranges.iter().filter(|r| !r.is_empty()) {
+            if r.end > self.size {
+                return Err(anyhow!(
+                    "Bad block range {} is beyond the end of the device",
+                    r
+                ));
+            }
+            for block in r.start / block_size..r.end.div_ceil(block_size) {
+                marker.mark(block as u32);
+            }
+        }
+        Ok(marker.total_marked() - before)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+
+    fn task(block_size: usize) -> WipeTask {
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        WipeTask::new(scheme, Verify::No, 100000, block_size).unwrap()
+    }
+
+    #[test]
+    fn test_bad_block_map_round_trip_with_other_block_size() {
+        let state = WipeState::default();
+        for b in &[2, 3, 12] {
+            state.bad_blocks.borrow_mut().mark(*b);
+        }
+        let map = BadBlockMap::new(100000, 8192, &state);
+        assert_eq!(
+            map.ranges,
+            vec![WipeRange::new(16384, 32768), WipeRange::new(98304, 100000)]
+        );
+
+        let path = std::env::temp_dir().join(format!("lethe-badblocks-{}", std::process::id()));
+        map.save(&path).unwrap();
+        let loaded = BadBlockMap::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, map);
+
+        let mut state = WipeState::default();
+        assert_eq!(loaded.apply(&task(32768), &mut state).unwrap(), 2);
+        assert_eq!(state.bad_blocks.borrow().all_marked(), vec![0, 3]);
+
+        let mut other = task(8192);
+        other.total_size = 200000;
+        assert!(loaded.apply(&other, &mut WipeState::default()).is_err());
+    }
+}
@@ -0,0 +1,444 @@
+//! Wiping all devices matching a selection in one run, e.g. a pile of USB sticks, or each
+//! one as soon as it's attached.
+
+use crate::actions::mirror::{MirrorAccess, MirrorTarget};
+use crate::actions::{
+    CancellationToken, Verify, WipeEventReceiver, WipeOutcome, WipeState, WipeTask,
+};
+use crate::sanitization::Scheme;
+use crate::storage::{
+    DeviceFilter, StorageAccess, StorageError, StorageRef, StorageRegistry, StorageType,
+};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Devices picked by `wipe-all`. The system drive and the devices of the providers (like
+/// image files) are never selected, neither are the partitions unless asked for by type.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceSelector {
+    pub storage_type: Option<StorageType>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+}
+
+impl DeviceSelector {
+    pub fn matches(&self, device: &dyn StorageRef) -> bool {
+        let details = device.details();
+        if details.is_boot || device.id().contains("://") || details.size == 0 {
+            return false;
+        }
+        let type_matches = match self.storage_type {
+            Some(t) => details.storage_type == t,
+            None => details.storage_type != StorageType::Partition,
+        };
+        type_matches
+            && self.min_size.is_none_or(|s| details.size >= s)
+            && self.max_size.is_none_or(|s| details.size <= s)
+    }
+
+    /// Narrows the selection to the removable devices unless a type was picked explicitly,
+    /// as wiping every drive that happens to be attached is rarely what was meant.
+    pub fn removable_by_default(self) -> Self {
+        DeviceSelector {
+            storage_type: self.storage_type.or(Some(StorageType::Removable)),
+            ..self
+        }
+    }
+
+    pub fn select<'a, T: StorageRef>(&self, devices: &'a [T]) -> Vec<&'a T> {
+        devices.iter().filter(|d| self.matches(*d)).collect()
+    }
+}
+
+/// Settings shared by the wipes of the selected devices. Each device is looked up and
+/// opened again by the thread wiping it, as the devices can't be shared between threads.
+#[derive(Debug, Clone)]
+pub struct BatchWipe {
+    pub scheme: Scheme,
+    pub verify: Verify,
+    /// The preferred block size of each device when not set.
+    pub block_size: Option<usize>,
+    pub retries: u32,
+    pub dry_run: bool,
+    pub unmount: bool,
+    pub filter: DeviceFilter,
+    /// Shared by all the wipes, cancelling it stops them all.
+    pub cancellation: CancellationToken,
+}
+
+impl BatchWipe {
+    pub fn run(
+        &self,
+        device_id: &str,
+        receiver: &mut dyn WipeEventReceiver,
+    ) -> Result<WipeOutcome> {
+        let mut registry = StorageRegistry::default();
+        registry.set_filter(self.filter.clone());
+        let devices = registry.devices()?;
+        let device = find_attached(&devices, device_id)?;
+        // the system drive is never wiped in a batch, not even in a dry run
+        registry.prepare_for_wipe(device, false, self.unmount)?;
+
+        let mut task = WipeTask::new(
+            self.scheme.clone(),
+            self.verify,
+            device.details().size,
+            self.block_size
+                .unwrap_or_else(|| device.details().preferred_block_size()),
+        )?;
+        task.dry_run = self.dry_run;
+        task.trim_supported = device.details().trim_supported;
+        task.cancellation = self.cancellation.clone();
+        let mut access = registry.access(device)?;
+        if let Some(g) = access.geometry().ok().flatten() {
+            task.align_to(&g)
+                .context(format!("Unable to align the blocks of {}", device_id))?;
+        }
+
+        Ok(task.run(&mut access, &mut self.state(), receiver))
+    }
+
+    /// Wipes all the devices in one run, writing the same data to each of them, see
+    /// [`MirrorAccess`]. The devices should be of the same size. A device failing during
+    /// the wipe fails alone, any other error stops the whole run before it starts.
+    pub fn run_mirrored(
+        &self,
+        device_ids: &[String],
+        receiver: &mut dyn WipeEventReceiver,
+    ) -> Result<Vec<BatchResult>> {
+        let started = Instant::now();
+        let mut registry = StorageRegistry::default();
+        registry.set_filter(self.filter.clone());
+        let devices = registry.devices()?;
+        let mut selected = Vec::new();
+        for device_id in device_ids {
+            let device = find_attached(&devices, device_id)?;
+            registry.prepare_for_wipe(device, false, self.unmount)?;
+            selected.push(device);
+        }
+
+        let size = selected.first().map(|d| d.details().size).unwrap_or(0);
+        if let Some(d) = selected.iter().find(|d| d.details().size != size) {
+            return Err(anyhow!(
+                "Unable to mirror devices of different sizes, {} is {} bytes and {} is {}",
+                selected[0].id(),
+                size,
+                d.id(),
+                d.details().size
+            ));
+        }
+
+        let mut task = WipeTask::new(
+            self.scheme.clone(),
+            self.verify,
+            size,
+            self.block_size.unwrap_or_else(|| {
+                selected
+                    .iter()
+                    .map(|d| d.details().preferred_block_size())
+                    .max()
+                    .unwrap_or_default()
+            }),
+        )?;
+        task.dry_run = self.dry_run;
+        task.trim_supported = selected.iter().all(|d| d.details().trim_supported);
+        task.cancellation = self.cancellation.clone();
+        let mut targets = Vec::new();
+        for device in &selected {
+            let filter = self.filter.clone();
+            let device_id = device.id().to_string();
+            // opened again by the thread of the target, as the devices can't be shared
+            targets.push(MirrorTarget::open(device.id(), move || {
+                let mut registry = StorageRegistry::default();
+                registry.set_filter(filter);
+                let devices = registry.devices()?;
+                registry.access(find_attached(&devices, &device_id)?)
+            })?);
+        }
+        let mut access = MirrorAccess::new(targets)?;
+        if let Some(g) = access.geometry().ok().flatten() {
+            task.align_to(&g)
+                .context("Unable to align the blocks of the mirrored devices")?;
+        }
+
+        let outcome = task.run(&mut access, &mut self.state(), receiver);
+        Ok(access
+            .into_targets()
+            .into_iter()
+            .map(|t| {
+                let result = match t.error {
+                    Some(err) => Err(err),
+                    None if !outcome.is_success() => Ok(outcome),
+                    None if !t.mismatched_blocks.is_empty() => Ok(WipeOutcome::VerificationFailed),
+                    None if !t.bad_blocks.is_empty() => Ok(WipeOutcome::CompletedWithBadBlocks),
+                    None => Ok(outcome),
+                };
+                BatchResult::new(&t.device_id, started, result)
+            })
+            .collect())
+    }
+
+    fn state(&self) -> WipeState {
+        WipeState {
+            retries_left: self.retries,
+            verify_retries_left: self.retries,
+            ..Default::default()
+        }
+    }
+}
+
+fn find_attached<'a>(
+    devices: &'a [Box<dyn StorageRef>],
+    device_id: &str,
+) -> Result<&'a dyn StorageRef> {
+    Ok(devices
+        .iter()
+        .find(|d| d.id() == device_id)
+        .ok_or_else(|| StorageError::NotFound(std::io::ErrorKind::NotFound.into()))
+        .context(format!("Device {} is no longer attached", device_id))?
+        .as_ref())
+}
+
+/// Tells which of the selected devices were attached since the last check. The devices
+/// present when it's created are not reported, a device detached and attached again is.
+#[derive(Debug)]
+pub struct AttachWatcher {
+    selector: DeviceSelector,
+    known: HashSet<String>,
+}
+
+impl AttachWatcher {
+    pub fn new<T: StorageRef>(selector: DeviceSelector, devices: &[T]) -> AttachWatcher {
+        AttachWatcher {
+            selector,
+            known: devices.iter().map(|d| d.id().to_string()).collect(),
+        }
+    }
+
+    /// The newly attached devices matching the selection, in the order they are listed.
+    pub fn poll<T: StorageRef>(&mut self, devices: &[T]) -> Vec<String> {
+        let present: HashSet<String> = devices.iter().map(|d| d.id().to_string()).collect();
+        let attached = devices
+            .iter()
+            .filter(|d| !self.known.contains(d.id()) && self.selector.matches(*d))
+            .map(|d| d.id().to_string())
+            .collect();
+        self.known = present;
+        attached
+    }
+}
+
+/// How the wipe of one of the devices ended.
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    pub device_id: String,
+    pub outcome: WipeOutcome,
+    /// The error which stopped the wipe before it started, like a mounted device.
+    pub error: Option<String>,
+    pub elapsed: Duration,
+}
+
+impl BatchResult {
+    pub fn new(device_id: &str, started: Instant, result: Result<WipeOutcome>) -> BatchResult {
+        let (outcome, error) = match result {
+            Ok(outcome) => (outcome, None),
+            Err(err) => (WipeOutcome::of_error(&err), Some(format!("{:#}", err))),
+        };
+        BatchResult {
+            device_id: device_id.to_string(),
+            outcome,
+            error,
+            elapsed: started.elapsed(),
+        }
+    }
+}
+
+/// Wipes the devices with up to `parallel` of them at once and returns the results in
+/// the order of the devices. A failed wipe doesn't stop the others.
+pub fn run_batch<F>(device_ids: &[String], parallel: usize, wipe: F) -> Vec<BatchResult>
+where
+    F: Fn(&str) -> Result<WipeOutcome> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<BatchResult>>> = Mutex::new(vec![None; device_ids.len()]);
+
+    let worker = || loop {
+        let i = next.fetch_add(1, Ordering::SeqCst);
+        let device_id = match device_ids.get(i) {
+            Some(id) => id,
+            None => break,
+        };
+        let started = Instant::now();
+        let result = BatchResult::new(device_id, started, wipe(device_id));
+        results.lock().unwrap()[i] = Some(result);
+    };
+
+    std::thread::scope(|scope| {
+        for _ in 1..parallel.clamp(1, device_ids.len().max(1)) {
+            scope.spawn(worker);
+        }
+        worker();
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::platform::MockDevice;
+    use crate::storage::StorageDetails;
+    use std::sync::atomic::AtomicUsize;
+
+    fn device(id: &str, storage_type: StorageType, size: u64, is_boot: bool) -> MockDevice {
+        MockDevice {
+            id: id.to_string(),
+            details: StorageDetails {
+                size,
+                storage_type,
+                is_boot,
+                ..Default::default()
+            },
+            users: Vec::new(),
+            mounts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_device_selector() {
+        let gb = 1u64 << 30;
+        let devices = vec![
+            device("/dev/sda", StorageType::Fixed, 512 * gb, true),
+            device("/dev/sdb", StorageType::Removable, 8 * gb, false),
+            device("/dev/sdb1", StorageType::Partition, 8 * gb, false),
+            device("/dev/sdc", StorageType::Removable, 32 * gb, false),
+            device("/dev/sdd", StorageType::Fixed, 1024 * gb, false),
+            device("/dev/sde", StorageType::Removable, 0, false),
+            device("file:///tmp/image", StorageType::File, gb, false),
+        ];
+        let ids = |selector: DeviceSelector| -> Vec<String> {
+            selector
+                .select(&devices)
+                .iter()
+                .map(|d| d.id.clone())
+                .collect()
+        };
+
+        assert_eq!(
+            ids(DeviceSelector::default()),
+            vec!["/dev/sdb", "/dev/sdc", "/dev/sdd"]
+        );
+        assert_eq!(
+            ids(DeviceSelector {
+                storage_type: Some(StorageType::Removable),
+                min_size: Some(16 * gb),
+                ..Default::default()
+            }),
+            vec!["/dev/sdc"]
+        );
+        assert_eq!(
+            ids(DeviceSelector {
+                storage_type: Some(StorageType::Partition),
+                max_size: Some(8 * gb),
+                ..Default::default()
+            }),
+            vec!["/dev/sdb1"]
+        );
+        assert_eq!(
+            ids(DeviceSelector::default().removable_by_default()),
+            vec!["/dev/sdb", "/dev/sdc"]
+        );
+        assert_eq!(
+            ids(DeviceSelector {
+                storage_type: Some(StorageType::Fixed),
+                ..Default::default()
+            }
+            .removable_by_default()),
+            vec!["/dev/sdd"]
+        );
+    }
+
+    #[test]
+    fn test_attach_watcher() {
+        let gb = 1u64 << 30;
+        let boot = device("/dev/sda", StorageType::Fixed, 512 * gb, true);
+        let stick = device("/dev/sdb", StorageType::Removable, 8 * gb, false);
+        let partition = device("/dev/sdb1", StorageType::Partition, 8 * gb, false);
+        let other = device("/dev/sdc", StorageType::Removable, 16 * gb, false);
+
+        let mut watcher = AttachWatcher::new(
+            DeviceSelector {
+                storage_type: Some(StorageType::Removable),
+                ..Default::default()
+            },
+            &[boot.clone(), other.clone()],
+        );
+        assert!(watcher.poll(&[boot.clone(), other.clone()]).is_empty());
+        assert_eq!(
+            watcher.poll(&[
+                boot.clone(),
+                stick.clone(),
+                partition.clone(),
+                other.clone()
+            ]),
+            vec!["/dev/sdb"]
+        );
+        // still attached, e.g. while it's being wiped
+        assert!(watcher
+            .poll(&[
+                boot.clone(),
+                stick.clone(),
+                partition.clone(),
+                other.clone()
+            ])
+            .is_empty());
+
+        assert!(watcher.poll(std::slice::from_ref(&boot)).is_empty());
+        assert_eq!(
+            watcher.poll(&[boot, stick, other]),
+            vec!["/dev/sdb", "/dev/sdc"]
+        );
+    }
+
+    #[test]
+    fn test_run_batch() {
+        let ids: Vec<String> = (0..5).map(|i| format!("mem://{}", i)).collect();
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+
+        let results = run_batch(&ids, 2, |id| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            running.fetch_sub(1, Ordering::SeqCst);
+            match id {
+                "mem://1" => Err(anyhow!("Device is mounted")),
+                "mem://3" => Ok(WipeOutcome::CompletedWithBadBlocks),
+                _ => Ok(WipeOutcome::Completed),
+            }
+        });
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            results
+                .iter()
+                .map(|r| r.device_id.as_str())
+                .collect::<Vec<_>>(),
+            ids.iter().map(|id| id.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(results[1].outcome, WipeOutcome::Failed);
+        assert_eq!(results[1].error.as_deref(), Some("Device is mounted"));
+        assert_eq!(results[3].outcome, WipeOutcome::CompletedWithBadBlocks);
+        assert!(results[0].error.is_none());
+
+        assert!(run_batch(&[], 4, |_| Ok(WipeOutcome::Completed)).is_empty());
+    }
+}
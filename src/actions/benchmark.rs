@@ -0,0 +1,55 @@
+use crate::sanitization::mem::AlignedBuffer;
+use crate::storage::StorageAccess;
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_BENCHMARK_DURATION: Duration = Duration::from_secs(3);
+
+/// Measures the sequential read throughput (in bytes per second) by reading from the beginning
+/// of the device for the given time. Nothing is written, as it runs before the wipe is
+/// confirmed. The buffer is aligned as the device requires for direct I/O.
+pub fn read_throughput(
+    access: &mut dyn StorageAccess,
+    total_size: u64,
+    block_size: usize,
+    alignment: usize,
+    duration: Duration,
+) -> Result<u64> {
+    let mut buf = AlignedBuffer::new(block_size, alignment);
+    let started = Instant::now();
+    let mut position = 0;
+
+    access.seek(0)?;
+    while position < total_size && started.elapsed() < duration {
+        let len = (total_size - position).min(block_size as u64) as usize;
+        match access.read(&mut buf.as_mut_slice()[..len])? {
+            0 => break,
+            n => position += n as u64,
+        }
+    }
+
+    Ok(per_second(position, started.elapsed()))
+}
+
+fn per_second(bytes: u64, elapsed: Duration) -> u64 {
+    match elapsed.as_micros() {
+        0 => bytes,
+        us => (bytes as u128 * 1_000_000 / us) as u64,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::memory::InMemoryStorage;
+
+    #[test]
+    fn test_read_throughput() {
+        let mut storage = InMemoryStorage::with_data(vec![0; 100000]);
+        let throughput =
+            read_throughput(&mut storage, 100000, 4096, 512, Duration::from_secs(1)).unwrap();
+
+        assert!(throughput > 0);
+        assert_eq!(storage.file.position(), 100000);
+    }
+}
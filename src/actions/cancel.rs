@@ -0,0 +1,192 @@
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Exit code after a wipe is stopped with Ctrl+C, the one shells report for SIGINT.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+const INTERRUPT_MESSAGE: &[u8] =
+    b"\nStopping after the current block. Press Ctrl+C again to exit right away.\n";
+
+/// Flag of the token cancelled on Ctrl+C, set once and never freed.
+static INTERRUPT_TARGET: AtomicPtr<AtomicBool> = AtomicPtr::new(std::ptr::null_mut());
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Terminal settings to put back before exiting on a second Ctrl+C, never freed either.
+#[cfg(unix)]
+static SAVED_TERMINAL: AtomicPtr<libc::termios> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Stops a running wipe from another thread. The wipe checks the token between blocks,
+/// so it stops shortly after the cancellation is requested.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Cancels the token on Ctrl+C instead of killing the process, so that the wipe stops
+    /// between blocks and saves its checkpoint. Pressing it again exits right away.
+    pub fn cancel_on_interrupt(&self) {
+        let target = Arc::into_raw(Arc::clone(&self.cancelled)) as *mut AtomicBool;
+        let previous = INTERRUPT_TARGET.swap(target, Ordering::SeqCst);
+        if previous.is_null() {
+            install_interrupt_handler();
+        }
+    }
+}
+
+/// Whether the wipe was cancelled with Ctrl+C, see `CancellationToken::cancel_on_interrupt`.
+pub fn was_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Sets the terminal settings of the standard input to restore if the process exits right
+/// away on Ctrl+C, as the code which changed them doesn't get to run. `None` forgets them.
+#[cfg(unix)]
+pub fn restore_terminal_on_interrupt(saved: Option<libc::termios>) {
+    let saved = saved.map_or(std::ptr::null_mut(), |s| Box::into_raw(Box::new(s)));
+    SAVED_TERMINAL.store(saved, Ordering::SeqCst);
+}
+
+/// Puts back the terminal settings set with `restore_terminal_on_interrupt`, for the code
+/// which exits the process without unwinding. Safe to call in a signal handler.
+#[cfg(unix)]
+pub fn restore_terminal() {
+    let saved = SAVED_TERMINAL.load(Ordering::SeqCst);
+    if !saved.is_null() {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved) };
+    }
+}
+
+/// Only does what's safe in a signal handler.
+fn on_interrupt() {
+    let target = INTERRUPT_TARGET.load(Ordering::SeqCst);
+    if INTERRUPTED.swap(true, Ordering::SeqCst) || target.is_null() {
+        #[cfg(unix)]
+        unsafe {
+            restore_terminal();
+            libc::_exit(INTERRUPTED_EXIT_CODE)
+        };
+        #[cfg(windows)]
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    unsafe { (*target).store(true, Ordering::SeqCst) };
+    write_interrupt_message();
+}
+
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn handler(_: libc::c_int) {
+        on_interrupt();
+    }
+
+    unsafe {
+        libc::signal(libc::SIGINT, handler as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(unix)]
+fn write_interrupt_message() {
+    unsafe {
+        libc::write(
+            libc::STDERR_FILENO,
+            INTERRUPT_MESSAGE.as_ptr() as *const libc::c_void,
+            INTERRUPT_MESSAGE.len(),
+        );
+    }
+}
+
+#[cfg(windows)]
+fn install_interrupt_handler() {
+    use winapi::shared::minwindef::{BOOL, DWORD, FALSE, TRUE};
+    use winapi::um::wincon::{CTRL_BREAK_EVENT, CTRL_C_EVENT};
+
+    // called on a separate thread, unlike the signal handler on Unix
+    unsafe extern "system" fn handler(event: DWORD) -> BOOL {
+        match event {
+            CTRL_C_EVENT | CTRL_BREAK_EVENT => {
+                on_interrupt();
+                TRUE
+            }
+            _ => FALSE,
+        }
+    }
+
+    unsafe {
+        winapi::um::consoleapi::SetConsoleCtrlHandler(Some(handler), TRUE);
+    }
+}
+
+#[cfg(windows)]
+fn write_interrupt_message() {
+    use std::io::Write;
+    let _ = std::io::stderr().write_all(INTERRUPT_MESSAGE);
+}
+
+/// Pauses a running wipe from another thread. The wipe flushes the written data and waits
+/// between blocks with the device kept open until it's resumed or cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct PauseToken {
+    paused: Arc<AtomicBool>,
+}
+
+impl PauseToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+}
+
+/// Unwinds the wipe engine once the cancellation is noticed.
+#[derive(Error, Debug)]
+#[error("wipe was cancelled")]
+pub(crate) struct Cancelled;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_is_shared_between_clones() {
+        let token = CancellationToken::new();
+        let other = token.clone();
+        assert!(!other.is_cancelled());
+
+        std::thread::spawn(move || token.cancel()).join().unwrap();
+        assert!(other.is_cancelled());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_interrupt_cancels_token() {
+        let token = CancellationToken::new();
+        token.cancel_on_interrupt();
+        assert!(!was_interrupted());
+
+        unsafe { libc::raise(libc::SIGINT) };
+        assert!(token.is_cancelled());
+        assert!(was_interrupted());
+    }
+}
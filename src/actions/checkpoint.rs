@@ -0,0 +1,718 @@
+use crate::actions::{VerificationJournal, Verify, WipeEvent, WipeRange, WipeState, WipeTask};
+use crate::sanitization::Scheme;
+use crate::storage::StorageDetails;
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+pub const DEFAULT_MAX_COUNT: usize = 16;
+
+/// Identifies a device across runs so that a checkpoint is never applied to a different one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeviceFingerprint {
+    pub id: String,
+    pub size: u64,
+    #[serde(default)]
+    pub serial: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+impl DeviceFingerprint {
+    pub fn new(id: &str, size: u64) -> Self {
+        DeviceFingerprint {
+            id: id.to_string(),
+            size,
+            serial: None,
+            model: None,
+        }
+    }
+
+    /// Adds the serial number and the model of the drive, which tell apart the drives of
+    /// the same size attached at the same path one after another.
+    pub fn with_identity(mut self, details: &StorageDetails) -> Self {
+        self.serial = details.serial.clone();
+        self.model = details.model.clone();
+        self
+    }
+
+    /// Whether it's the same device. The serial number and the model are only compared
+    /// when both fingerprints have them, not all the drives report them.
+    pub fn matches(&self, other: &DeviceFingerprint) -> bool {
+        let same = |a: &Option<String>, b: &Option<String>| match (a, b) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+        self.id == other.id
+            && self.size == other.size
+            && same(&self.serial, &other.serial)
+            && same(&self.model, &other.model)
+    }
+
+    pub(crate) fn file_name(&self) -> String {
+        let id: String = self
+            .id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("{}-{}.json", id.trim_matches('_'), self.size)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    pub fingerprint: DeviceFingerprint,
+    /// Includes the seeds of the random stages, which reproduce the data written, so the
+    /// checkpoints are only readable by the user.
+    pub scheme: Scheme,
+    pub verify: Verify,
+    pub block_size: usize,
+    #[serde(default)]
+    pub ranges: Vec<WipeRange>,
+    pub stage: usize,
+    pub at_verification: bool,
+    pub position: u64,
+    pub bad_blocks: Vec<u32>,
+    #[serde(default)]
+    pub mismatched_blocks: Vec<u32>,
+    /// Blocks verified before the interruption, when it happened during the verification.
+    #[serde(default)]
+    pub verified_blocks: u64,
+    #[serde(default)]
+    pub journal: Option<VerificationJournal>,
+    pub updated: u64,
+}
+
+impl Checkpoint {
+    pub fn capture(fingerprint: &DeviceFingerprint, task: &WipeTask, state: &WipeState) -> Self {
+        Checkpoint {
+            fingerprint: fingerprint.clone(),
+            scheme: task.scheme.clone(),
+            verify: task.verify,
+            block_size: task.block_size,
+            ranges: task.ranges.clone(),
+            stage: state.stage,
+            at_verification: state.at_verification,
+            position: state.position,
+            bad_blocks: state.bad_blocks.borrow().all_marked(),
+            mismatched_blocks: state.mismatched_blocks.borrow().all_marked(),
+            verified_blocks: if state.at_verification {
+                state.verified_blocks
+            } else {
+                0
+            },
+            journal: if state.at_verification {
+                Some(state.journal.clone())
+            } else {
+                None
+            },
+            updated: unix_now(),
+        }
+    }
+
+    /// Names the first parameter the task differs from the interrupted run in, if any. The
+    /// seeds of random stages aren't compared, as they are generated anew for every run.
+    pub fn mismatch(&self, task: &WipeTask) -> Option<&'static str> {
+        if self.scheme.describe("") != task.scheme.describe("") {
+            Some("scheme")
+        } else if self.verify != task.verify {
+            Some("verification")
+        } else if self.block_size != task.block_size {
+            Some("block size")
+        } else if self.ranges != task.ranges {
+            Some("ranges")
+        } else {
+            None
+        }
+    }
+
+    /// Builds a task and a state to continue the interrupted run from.
+    pub fn restore(&self) -> Result<(WipeTask, WipeState)> {
+        let mut task = WipeTask::new(
+            self.scheme.clone(),
+            self.verify,
+            self.fingerprint.size,
+            self.block_size,
+        )?;
+
+        if !self.ranges.is_empty() {
+            task.set_ranges(self.ranges.clone())?;
+        }
+
+        let state = WipeState {
+            stage: self.stage,
+            at_verification: self.at_verification,
+            position: self.position,
+            verified_blocks: self.verified_blocks,
+            journal: self.journal.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+
+        for b in &self.bad_blocks {
+            state.bad_blocks.borrow_mut().mark(*b);
+        }
+
+        for b in &self.mismatched_blocks {
+            state.mismatched_blocks.borrow_mut().mark(*b);
+        }
+
+        Ok((task, state))
+    }
+}
+
+/// Defines which checkpoints are considered stale and can be removed.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub max_age: Option<Duration>,
+    pub max_count: Option<usize>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            max_age: Some(DEFAULT_MAX_AGE),
+            max_count: Some(DEFAULT_MAX_COUNT),
+        }
+    }
+}
+
+/// Outcome of [`CheckpointStore::prune`].
+#[derive(Debug, Default)]
+pub struct Pruned {
+    /// Number of removed files.
+    pub removed: usize,
+    /// Checkpoints which couldn't be read and were kept.
+    pub unreadable: Vec<PathBuf>,
+}
+
+pub struct CheckpointStore {
+    root: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        CheckpointStore {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// `checkpoints` in the data directory, see [`StateDir`](crate::actions::state::StateDir).
+    pub fn default_location() -> PathBuf {
+        crate::actions::state::StateDir::default_location().join("checkpoints")
+    }
+
+    pub(crate) fn path_for(&self, fingerprint: &DeviceFingerprint) -> PathBuf {
+        self.root.join(fingerprint.file_name())
+    }
+
+    pub fn find(&self, fingerprint: &DeviceFingerprint) -> Result<Option<Checkpoint>> {
+        if !self.root.exists() {
+            return Ok(None);
+        }
+
+        let _lock = StoreLock::shared(&self.root)?;
+
+        let file = match File::open(self.path_for(fingerprint)) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("Unable to open checkpoint"),
+        };
+        let checkpoint: Checkpoint =
+            serde_json::from_reader(BufReader::new(file)).context("Corrupted checkpoint")?;
+
+        if !checkpoint.fingerprint.matches(fingerprint) {
+            return Ok(None);
+        }
+
+        Ok(Some(checkpoint))
+    }
+
+    /// Writes the checkpoint to a temporary file first and then atomically replaces
+    /// the previous version, so readers never observe a partially written checkpoint.
+    /// The directory and the files are only accessible by the user (0700 and 0600).
+    pub fn flush(&self, checkpoint: &Checkpoint) -> Result<()> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder
+            .create(&self.root)
+            .context("Unable to create checkpoint directory")?;
+
+        let _lock = StoreLock::exclusive(&self.root)?;
+
+        let path = self.path_for(&checkpoint.fingerprint);
+        let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+
+        let written = Self::write_to(&tmp_path, checkpoint).and_then(|_| {
+            std::fs::rename(&tmp_path, &path).context("Unable to replace checkpoint")
+        });
+
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+        written
+    }
+
+    fn write_to(path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+        // a leftover of a crashed run may have been created with other permissions
+        let _ = std::fs::remove_file(path);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options.open(path).context("Unable to create checkpoint")?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, checkpoint).context("Unable to write checkpoint")?;
+        writer.flush()?;
+        writer
+            .get_ref()
+            .sync_all()
+            .context("Unable to write checkpoint")?;
+        Ok(())
+    }
+
+    pub fn remove(&self, fingerprint: &DeviceFingerprint) -> Result<()> {
+        if !self.root.exists() {
+            return Ok(());
+        }
+
+        let _lock = StoreLock::exclusive(&self.root)?;
+
+        match std::fs::remove_file(self.path_for(fingerprint)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(e).context("Unable to remove checkpoint")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl CheckpointStore {
+    /// Returns all readable checkpoints, most recently updated first.
+    pub fn list(&self) -> Result<Vec<Checkpoint>> {
+        if !self.root.exists() {
+            return Ok(vec![]);
+        }
+
+        let _lock = StoreLock::shared(&self.root)?;
+
+        let mut checkpoints: Vec<_> = self.entries()?.into_iter().filter_map(|(_, c)| c).collect();
+        checkpoints.sort_by_key(|c| std::cmp::Reverse(c.updated));
+        Ok(checkpoints)
+    }
+
+    /// Removes checkpoints not matching the retention policy along with leftover temporary
+    /// files. Unreadable checkpoints are kept and returned for the caller to warn about, as
+    /// they may be written by a newer version or recoverable by hand.
+    pub fn prune(&self, policy: &RetentionPolicy) -> Result<Pruned> {
+        let mut pruned = Pruned::default();
+        if !self.root.exists() {
+            return Ok(pruned);
+        }
+
+        let _lock = StoreLock::exclusive(&self.root)?;
+
+        let now = unix_now();
+        let mut stale = Vec::new();
+        let mut valid = Vec::new();
+
+        for (path, checkpoint) in self.entries()? {
+            match checkpoint {
+                Some(c)
+                    if policy
+                        .max_age
+                        .map(|age| now.saturating_sub(c.updated) <= age.as_secs())
+                        .unwrap_or(true) =>
+                {
+                    valid.push((path, c.updated))
+                }
+                Some(_) => stale.push(path),
+                None => pruned.unreadable.push(path),
+            }
+        }
+
+        if let Some(max_count) = policy.max_count {
+            valid.sort_by_key(|v| std::cmp::Reverse(v.1));
+            stale.extend(valid.into_iter().skip(max_count).map(|(p, _)| p));
+        }
+
+        // temporary files are only present while a writer holds the lock
+        stale.extend(
+            std::fs::read_dir(&self.root)?
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map(|e| e == "tmp").unwrap_or(false)),
+        );
+
+        for path in stale {
+            match std::fs::remove_file(&path) {
+                Ok(()) => pruned.removed += 1,
+                Err(e) if e.kind() == ErrorKind::NotFound => (),
+                Err(e) => return Err(e).context("Unable to remove checkpoint"),
+            }
+        }
+        Ok(pruned)
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, Option<Checkpoint>)>> {
+        let entries = std::fs::read_dir(&self.root)
+            .context("Unable to read checkpoint directory")?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().map(|e| e == "json").unwrap_or(false))
+            .map(|p| {
+                let checkpoint = File::open(&p)
+                    .ok()
+                    .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok());
+                (p, checkpoint)
+            })
+            .collect();
+        Ok(entries)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Advisory lock guarding the whole store against concurrently running instances.
+struct StoreLock {
+    file: File,
+}
+
+impl StoreLock {
+    fn open(root: &Path) -> Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(root.join(".lock"))
+            .context("Unable to open checkpoint store lock")
+    }
+
+    fn shared(root: &Path) -> Result<StoreLock> {
+        let file = Self::open(root)?;
+        file.lock_shared()
+            .context("Unable to lock checkpoint store")?;
+        Ok(StoreLock { file })
+    }
+
+    fn exclusive(root: &Path) -> Result<StoreLock> {
+        let file = Self::open(root)?;
+        file.lock_exclusive()
+            .context("Unable to lock checkpoint store")?;
+        Ok(StoreLock { file })
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Periodically persists the progress of a running wipe.
+pub struct Checkpointer {
+    store: CheckpointStore,
+    fingerprint: DeviceFingerprint,
+    interval: Duration,
+    last_flush: Option<Instant>,
+}
+
+impl Checkpointer {
+    pub fn new(store: CheckpointStore, fingerprint: DeviceFingerprint, interval: Duration) -> Self {
+        Checkpointer {
+            store,
+            fingerprint,
+            interval,
+            last_flush: None,
+        }
+    }
+
+    /// The progress is saved with the next `Progress` event.
+    pub fn is_due(&self) -> bool {
+        self.last_flush
+            .map(|t| t.elapsed() >= self.interval)
+            .unwrap_or(true)
+    }
+
+    pub fn update(&mut self, task: &WipeTask, state: &WipeState, event: &WipeEvent) -> Result<()> {
+        match event {
+            WipeEvent::Progress(_) if !self.is_due() => Ok(()),
+            WipeEvent::Progress(_) => self.flush(task, state),
+            WipeEvent::StageStarted | WipeEvent::Retrying(_) => self.flush(task, state),
+            WipeEvent::Completed(None) => self.store.remove(&self.fingerprint),
+            WipeEvent::Completed(Some(_)) => self.flush(task, state),
+            WipeEvent::Paused | WipeEvent::PausedUntil(_) | WipeEvent::Aborted => {
+                self.flush(task, state)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn flush(&mut self, task: &WipeTask, state: &WipeState) -> Result<()> {
+        self.last_flush = Some(Instant::now());
+        self.store
+            .flush(&Checkpoint::capture(&self.fingerprint, task, state))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sanitization::SchemeRepo;
+
+    fn temp_store(name: &str) -> CheckpointStore {
+        CheckpointStore::new(std::env::temp_dir().join(format!(
+            "lethe-checkpoints-{}-{}",
+            name,
+            std::process::id()
+        )))
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let store = temp_store("roundtrip");
+        let fingerprint = DeviceFingerprint::new("/dev/test", 100000);
+
+        assert!(store.find(&fingerprint).unwrap().is_none());
+
+        let schemes = SchemeRepo::default();
+        let task = WipeTask::new(
+            schemes.find("random2x").unwrap().clone(),
+            Verify::Last,
+            100000,
+            4096,
+        )
+        .unwrap();
+        let state = WipeState {
+            stage: 1,
+            position: 8192,
+            ..Default::default()
+        };
+        state.bad_blocks.borrow_mut().mark(3);
+
+        store
+            .flush(&Checkpoint::capture(&fingerprint, &task, &state))
+            .unwrap();
+
+        let restored = store.find(&fingerprint).unwrap().unwrap();
+        let (restored_task, restored_state) = restored.restore().unwrap();
+
+        assert_eq!(restored_task.total_size, task.total_size);
+        assert_eq!(restored_task.block_size, task.block_size);
+        assert_eq!(
+            serde_json::to_string(&restored_task.scheme.stages).unwrap(),
+            serde_json::to_string(&task.scheme.stages).unwrap()
+        );
+        assert_eq!(restored_state.stage, 1);
+        assert_eq!(restored_state.position, 8192);
+        assert!(restored_state.bad_blocks.borrow().is_marked(3));
+
+        assert!(store
+            .find(&DeviceFingerprint::new("/dev/test", 100001))
+            .unwrap()
+            .is_none());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&store.path_for(&fingerprint)), 0o600);
+            assert_eq!(mode(&store.root), 0o700);
+        }
+
+        store.remove(&fingerprint).unwrap();
+        assert!(store.find(&fingerprint).unwrap().is_none());
+
+        std::fs::remove_dir_all(&store.root).unwrap();
+    }
+
+    #[test]
+    fn test_fingerprint_identity() {
+        let drive = |serial: Option<&str>| {
+            DeviceFingerprint::new("/dev/sdz", 1000).with_identity(&StorageDetails {
+                serial: serial.map(|s| s.to_string()),
+                model: Some("Drive".to_string()),
+                ..Default::default()
+            })
+        };
+        assert!(drive(Some("A")).matches(&drive(Some("A"))));
+        assert!(!drive(Some("A")).matches(&drive(Some("B"))));
+        assert!(drive(Some("A")).matches(&drive(None)));
+        assert!(DeviceFingerprint::new("/dev/sdz", 1000).matches(&drive(Some("A"))));
+        assert!(!DeviceFingerprint::new("/dev/sdz", 1001).matches(&drive(Some("A"))));
+
+        let store = temp_store("identity");
+        let schemes = SchemeRepo::default();
+        let task =
+            WipeTask::new(schemes.find("zero").unwrap().clone(), Verify::No, 1000, 100).unwrap();
+        store
+            .flush(&Checkpoint::capture(
+                &drive(Some("A")),
+                &task,
+                &WipeState::default(),
+            ))
+            .unwrap();
+        assert!(store.find(&drive(Some("A"))).unwrap().is_some());
+        assert!(store.find(&drive(Some("B"))).unwrap().is_none());
+
+        std::fs::remove_dir_all(&store.root).unwrap();
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let schemes = SchemeRepo::default();
+        let task = |name: &str, verify: Verify, block_size: usize| {
+            WipeTask::new(
+                schemes.find(name).unwrap().clone(),
+                verify,
+                100000,
+                block_size,
+            )
+            .unwrap()
+        };
+        let checkpoint = Checkpoint::capture(
+            &DeviceFingerprint::new("/dev/test", 100000),
+            &task("random2x", Verify::Last, 4096),
+            &WipeState::default(),
+        );
+
+        // seeds of the same scheme differ between runs
+        assert_eq!(
+            checkpoint.mismatch(&task("random2x", Verify::Last, 4096)),
+            None
+        );
+        assert_eq!(
+            checkpoint.mismatch(&task("zero", Verify::Last, 4096)),
+            Some("scheme")
+        );
+        assert_eq!(
+            checkpoint.mismatch(&task("random2x", Verify::No, 4096)),
+            Some("verification")
+        );
+        assert_eq!(
+            checkpoint.mismatch(&task("random2x", Verify::Last, 8192)),
+            Some("block size")
+        );
+        let mut ranged = task("random2x", Verify::Last, 4096);
+        ranged.set_ranges(vec![WipeRange::new(0, 8192)]).unwrap();
+        assert_eq!(checkpoint.mismatch(&ranged), Some("ranges"));
+    }
+
+    #[test]
+    fn test_prune() {
+        let store = temp_store("prune");
+        let schemes = SchemeRepo::default();
+        let task = WipeTask::new(
+            schemes.find("zero").unwrap().clone(),
+            Verify::No,
+            100000,
+            4096,
+        )
+        .unwrap();
+
+        for (i, age) in [0, 10, 20, 1000].iter().enumerate() {
+            let mut checkpoint = Checkpoint::capture(
+                &DeviceFingerprint::new(&format!("/dev/test{}", i), 100000),
+                &task,
+                &WipeState::default(),
+            );
+            checkpoint.updated -= age;
+            store.flush(&checkpoint).unwrap();
+        }
+        std::fs::write(store.root.join("garbage.json"), "{").unwrap();
+        std::fs::write(store.root.join("leftover.123.tmp"), "{").unwrap();
+
+        assert_eq!(store.list().unwrap().len(), 4);
+
+        let pruned = store
+            .prune(&RetentionPolicy {
+                max_age: Some(Duration::from_secs(100)),
+                max_count: Some(2),
+            })
+            .unwrap();
+        assert_eq!(pruned.removed, 3);
+        assert_eq!(pruned.unreadable, vec![store.root.join("garbage.json")]);
+        assert!(store.root.join("garbage.json").exists());
+
+        let ids: Vec<_> = store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|c| c.fingerprint.id)
+            .collect();
+        assert_eq!(ids, vec!["/dev/test0", "/dev/test1"]);
+
+        std::fs::remove_dir_all(&store.root).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let store = std::sync::Arc::new(temp_store("concurrent"));
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap().clone();
+
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let store = std::sync::Arc::clone(&store);
+                let scheme = scheme.clone();
+                std::thread::spawn(move || {
+                    let task = WipeTask::new(scheme, Verify::No, 100000, 4096).unwrap();
+                    for position in 0..50 {
+                        let state = WipeState {
+                            position,
+                            ..Default::default()
+                        };
+                        for id in &["/dev/shared", &format!("/dev/own{}", i)] {
+                            let fingerprint = DeviceFingerprint::new(id, 100000);
+                            store
+                                .flush(&Checkpoint::capture(&fingerprint, &task, &state))
+                                .unwrap();
+                            assert!(store.find(&fingerprint).unwrap().is_some());
+                        }
+                    }
+                    store
+                        .remove(&DeviceFingerprint::new(&format!("/dev/own{}", i), 100000))
+                        .unwrap();
+                })
+            })
+            .collect();
+
+        for w in workers {
+            w.join().unwrap();
+        }
+
+        let shared = DeviceFingerprint::new("/dev/shared", 100000);
+        assert_eq!(store.find(&shared).unwrap().unwrap().position, 49);
+        store.remove(&shared).unwrap();
+        store.remove(&shared).unwrap();
+
+        let leftovers: Vec<_> = std::fs::read_dir(&store.root)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name())
+            .filter(|n| n != ".lock")
+            .collect();
+        assert!(leftovers.is_empty());
+
+        std::fs::remove_dir_all(&store.root).unwrap();
+    }
+}
@@ -0,0 +1,205 @@
+use crate::actions::eventlog::describe;
+use crate::actions::{WipeEvent, WipeEventReceiver, WipeState, WipeTask};
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Minimum time between the progress lines sent to the attached clients and the log.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+static HANGUP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_hangup(_: libc::c_int) {
+    HANGUP.store(true, Ordering::SeqCst);
+}
+
+/// Keeps the process running when the controlling terminal is closed, `detach`
+/// receivers switch to the log file when it happens.
+pub fn survive_hangup() {
+    unsafe {
+        libc::signal(libc::SIGHUP, on_hangup as *const () as libc::sighandler_t);
+    }
+}
+
+fn file_stem(device_id: &str) -> String {
+    let name: String = device_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("lethe-{}", name.trim_matches('_'))
+}
+
+/// Control socket of a detachable wipe of the device.
+pub fn socket_path(device_id: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}.sock", file_stem(device_id)))
+}
+
+/// Log the output of a detachable wipe of the device goes to after a hangup.
+pub fn log_path(device_id: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}.log", file_stem(device_id)))
+}
+
+/// Passes the events through to the terminal frontend until the terminal is closed, after
+/// which the output goes to the log file instead. The progress is also sent to the clients
+/// attached through the control socket.
+pub struct DetachableReceiver {
+    inner: Option<Box<dyn WipeEventReceiver>>,
+    socket: PathBuf,
+    listener: UnixListener,
+    clients: Vec<UnixStream>,
+    log: PathBuf,
+    last_progress: Option<Duration>,
+}
+
+impl DetachableReceiver {
+    pub fn new(inner: Box<dyn WipeEventReceiver>, device_id: &str) -> Result<Self> {
+        let socket = socket_path(device_id);
+        if UnixStream::connect(&socket).is_ok() {
+            return Err(anyhow!("{} is already being wiped", device_id));
+        }
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).context("Unable to create control socket")?;
+        std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(DetachableReceiver {
+            inner: Some(inner),
+            socket,
+            listener,
+            clients: Vec::new(),
+            log: log_path(device_id),
+            last_progress: None,
+        })
+    }
+
+    pub fn log_path(&self) -> &Path {
+        &self.log
+    }
+
+    /// Redirects the standard output and error to the log file, writing to the closed
+    /// terminal would fail.
+    fn detach(&mut self) -> Result<()> {
+        self.inner = None;
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.log)?;
+        unsafe {
+            libc::dup2(log.as_raw_fd(), libc::STDOUT_FILENO);
+            libc::dup2(log.as_raw_fd(), libc::STDERR_FILENO);
+        }
+        println!("Terminal closed, continuing in the background.");
+        Ok(())
+    }
+
+    fn send(&mut self, line: &str) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            // a client not keeping up is dropped rather than holding the wipe back
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(stream);
+            }
+        }
+        self.clients
+            .retain_mut(|c| writeln!(c, "{}", line).and_then(|_| c.flush()).is_ok());
+        if self.inner.is_none() {
+            println!("{}", line);
+        }
+    }
+}
+
+impl Drop for DetachableReceiver {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+impl WipeEventReceiver for DetachableReceiver {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        if self.inner.is_some() && HANGUP.load(Ordering::SeqCst) && self.detach().is_err() {
+            // nowhere to report to, the wipe still goes on
+            self.inner = None;
+        }
+
+        if let WipeEvent::Progress(_) = event {
+            let elapsed = state.stamp.elapsed;
+            match self.last_progress {
+                Some(last) if elapsed < last + PROGRESS_INTERVAL => {}
+                _ => {
+                    self.last_progress = Some(elapsed);
+                    if let Some(line) = describe(task, state, &event) {
+                        self.send(&line);
+                    }
+                }
+            }
+        } else if let Some(line) = describe(task, state, &event) {
+            self.send(&line);
+        }
+
+        if let Some(inner) = &mut self.inner {
+            inner.handle(task, state, event);
+        }
+    }
+}
+
+/// Prints the progress of a detachable wipe of the device until it ends.
+pub fn attach(device_id: &str) -> Result<()> {
+    let stream = UnixStream::connect(socket_path(device_id)).context(format!(
+        "No detachable wipe of {} is running (see --detach of wipe)",
+        device_id
+    ))?;
+    for line in BufReader::new(stream).lines() {
+        println!("{}", line?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+
+    struct Nothing;
+
+    impl WipeEventReceiver for Nothing {
+        fn handle(&mut self, _task: &WipeTask, _state: &WipeState, _event: WipeEvent) {}
+    }
+
+    #[test]
+    fn test_attached_client_receives_progress() {
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::No, 1000, 100).unwrap();
+        let mut state = WipeState::default();
+
+        let device_id = format!("/dev/test{}", std::process::id());
+        let mut receiver = DetachableReceiver::new(Box::new(Nothing), &device_id).unwrap();
+        assert!(DetachableReceiver::new(Box::new(Nothing), &device_id).is_err());
+
+        let client = UnixStream::connect(socket_path(&device_id)).unwrap();
+        receiver.handle(&task, &state, WipeEvent::StageStarted);
+        state.stamp.elapsed = Duration::from_millis(100);
+        receiver.handle(&task, &state, WipeEvent::Progress(250));
+        // too soon after the previous one
+        state.stamp.elapsed = Duration::from_millis(200);
+        receiver.handle(&task, &state, WipeEvent::Progress(300));
+        receiver.handle(&task, &state, WipeEvent::Completed(None));
+        drop(receiver);
+        assert!(!socket_path(&device_id).exists());
+
+        let lines: Vec<String> = BufReader::new(client).lines().map(|l| l.unwrap()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Stage 1/1: fill with 0x00",
+                "Stage 1/1: 25.0% (250 of 1000 bytes)",
+                "Wipe completed"
+            ]
+        );
+    }
+}
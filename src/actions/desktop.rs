@@ -0,0 +1,198 @@
+//! Native desktop notifications about the stages and the outcome of a wipe, for the
+//! interactive users who don't keep an eye on the console during long wipes.
+
+use crate::actions::background::BackgroundProcesses;
+use crate::actions::timing::format_duration;
+use crate::actions::{WipeEvent, WipeEventReceiver, WipeState, WipeTask};
+use std::process::Command;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesktopNotification {
+    pub title: String,
+    pub message: String,
+}
+
+/// Shows a desktop notification when a stage or the whole wipe is completed, using
+/// `notify-send`, `osascript` or a PowerShell toast depending on the platform. The
+/// notifications are best effort: if they can't be shown, the wipe goes on and a single
+/// warning is printed at the end.
+pub struct DesktopNotifier {
+    inner: Box<dyn WipeEventReceiver>,
+    device_id: String,
+    processes: BackgroundProcesses,
+}
+
+impl DesktopNotifier {
+    pub fn new(inner: Box<dyn WipeEventReceiver>, device_id: &str) -> Self {
+        DesktopNotifier {
+            inner,
+            device_id: device_id.to_string(),
+            processes: BackgroundProcesses::default(),
+        }
+    }
+
+    /// The notification to show for the event, if any.
+    pub fn notification(
+        &self,
+        task: &WipeTask,
+        state: &WipeState,
+        event: &WipeEvent,
+    ) -> Option<DesktopNotification> {
+        let stages = task.scheme.stages.len();
+        let elapsed = format_duration(state.stamp.elapsed);
+        let (title, message) = match event {
+            // the last stage is covered by the notification about the whole wipe
+            WipeEvent::StageCompleted(None) if state.stage + 1 < stages => (
+                format!(
+                    "Stage {}/{} {}",
+                    state.stage + 1,
+                    stages,
+                    if state.at_verification {
+                        "verified"
+                    } else {
+                        "written"
+                    }
+                ),
+                format!("Wiping {}, elapsed {}", self.device_id, elapsed),
+            ),
+            WipeEvent::Completed(None) => (
+                "Wipe completed".to_string(),
+                format!("{} was wiped in {}", self.device_id, elapsed),
+            ),
+            WipeEvent::Completed(Some(err)) | WipeEvent::Fatal(err) => (
+                "Wipe failed".to_string(),
+                format!("{}: {:#}", self.device_id, err),
+            ),
+            WipeEvent::Aborted => (
+                "Wipe aborted".to_string(),
+                format!("{} was not wiped completely", self.device_id),
+            ),
+            _ => return None,
+        };
+        Some(DesktopNotification {
+            title: format!("lethe: {}", title),
+            message,
+        })
+    }
+}
+
+// the title and the message are passed as separate arguments (or the environment), so
+// they never have to be quoted for the script
+#[cfg(target_os = "macos")]
+fn command(notification: &DesktopNotification) -> Command {
+    let mut command = Command::new("osascript");
+    command
+        .args(["-e", "on run argv"])
+        .args([
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+        ])
+        .args(["-e", "end run"])
+        .arg(&notification.title)
+        .arg(&notification.message);
+    command
+}
+
+#[cfg(windows)]
+fn command(notification: &DesktopNotification) -> Command {
+    const SCRIPT: &str = "\
+        $m = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]; \
+        $t = $m::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+        $x = $t.GetElementsByTagName('text'); \
+        $x.Item(0).AppendChild($t.CreateTextNode($env:LETHE_TITLE)) > $null; \
+        $x.Item(1).AppendChild($t.CreateTextNode($env:LETHE_MESSAGE)) > $null; \
+        $m::CreateToastNotifier('lethe').Show([Windows.UI.Notifications.ToastNotification]::new($t))";
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .env("LETHE_TITLE", &notification.title)
+        .env("LETHE_MESSAGE", &notification.message);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn command(notification: &DesktopNotification) -> Command {
+    let mut command = Command::new("notify-send");
+    command
+        .args(["--app-name=lethe", "--"])
+        .arg(&notification.title)
+        .arg(&notification.message);
+    command
+}
+
+impl WipeEventReceiver for DesktopNotifier {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        if let Some(n) = self.notification(task, state, &event) {
+            self.processes.spawn(&mut command(&n), None, None);
+        }
+
+        let finished = matches!(
+            event,
+            WipeEvent::Completed(_) | WipeEvent::Aborted | WipeEvent::Fatal(_)
+        );
+        self.processes.reap(finished);
+        if finished && self.processes.failed() > 0 {
+            eprintln!(
+                "Unable to show {} desktop notification(s).",
+                self.processes.failed()
+            );
+        }
+
+        self.inner.handle(task, state, event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    struct Nothing;
+
+    impl WipeEventReceiver for Nothing {
+        fn handle(&mut self, _task: &WipeTask, _state: &WipeState, _event: WipeEvent) {}
+    }
+
+    #[test]
+    fn test_stages_and_outcome_are_notified() {
+        let scheme = SchemeRepo::default().find("dod").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::All, 1000, 100).unwrap();
+        let mut state = WipeState::default();
+        state.stamp.elapsed = Duration::from_secs(90);
+        let notifier = DesktopNotifier::new(Box::new(Nothing), "/dev/sdx");
+        let title = |state: &WipeState, event: WipeEvent| {
+            notifier.notification(&task, state, &event).map(|n| n.title)
+        };
+
+        assert_eq!(title(&state, WipeEvent::Started), None);
+        assert_eq!(
+            title(&state, WipeEvent::StageCompleted(None)),
+            Some("lethe: Stage 1/3 written".to_string())
+        );
+        state.at_verification = true;
+        assert_eq!(
+            title(&state, WipeEvent::StageCompleted(None)),
+            Some("lethe: Stage 1/3 verified".to_string())
+        );
+        let err = Rc::new(anyhow!("Mocked IO failure"));
+        assert_eq!(
+            title(&state, WipeEvent::StageCompleted(Some(err.clone()))),
+            None
+        );
+        state.stage = 2;
+        assert_eq!(title(&state, WipeEvent::StageCompleted(None)), None);
+
+        let completed = notifier
+            .notification(&task, &state, &WipeEvent::Completed(None))
+            .unwrap();
+        assert_eq!(completed.title, "lethe: Wipe completed");
+        assert_eq!(completed.message, "/dev/sdx was wiped in 00:01:30");
+        let failed = notifier
+            .notification(&task, &state, &WipeEvent::Fatal(err))
+            .unwrap();
+        assert_eq!(failed.message, "/dev/sdx: Mocked IO failure");
+    }
+}
@@ -0,0 +1,228 @@
+use crate::actions::{WipeEvent, WipeState, WipeTask};
+use crate::storage::StorageDetails;
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+pub const DEFAULT_EVENTS_CAPACITY: usize = 200;
+
+/// Keeps a bounded history of wipe events so that a redacted diagnostics bundle
+/// can be produced when something goes wrong.
+pub struct DiagnosticsRecorder {
+    device_id: String,
+    details: StorageDetails,
+    capacity: usize,
+    events: VecDeque<String>,
+}
+
+impl DiagnosticsRecorder {
+    pub fn new(device_id: &str, details: &StorageDetails, capacity: usize) -> Self {
+        DiagnosticsRecorder {
+            device_id: device_id.to_string(),
+            details: details.clone(),
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn record(&mut self, state: &WipeState, event: &WipeEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+
+        let elapsed = state.stamp.elapsed;
+        self.events.push_back(format!(
+            "[{:>6}.{:03}] #{} stage={} verifying={} position={} retries_left={} verify_retries_left={} {}",
+            elapsed.as_secs(),
+            elapsed.subsec_millis(),
+            state.stamp.sequence,
+            state.stage,
+            state.at_verification,
+            state.position,
+            state.retries_left,
+            state.verify_retries_left,
+            redact(&format!("{:?}", event))
+        ));
+    }
+
+    pub fn write_bundle<P: AsRef<Path>>(
+        &self,
+        path: P,
+        task: Option<&WipeTask>,
+        failure: &anyhow::Error,
+    ) -> Result<()> {
+        let file = File::create(path.as_ref()).context(format!(
+            "Unable to create diagnostics bundle {}",
+            path.as_ref().to_string_lossy()
+        ))?;
+
+        let mut zip = ZipWriter::new(file);
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        zip.start_file("system.txt", options)?;
+        zip.write_all(self.describe_system().as_bytes())?;
+
+        zip.start_file("device.txt", options)?;
+        zip.write_all(self.describe_device().as_bytes())?;
+
+        if let Some(t) = task {
+            zip.start_file("config.txt", options)?;
+            zip.write_all(describe_task(t).as_bytes())?;
+        }
+
+        zip.start_file("events.log", options)?;
+        for e in &self.events {
+            zip.write_all(e.as_bytes())?;
+            zip.write_all(b"\n")?;
+        }
+
+        zip.start_file("failure.txt", options)?;
+        zip.write_all(redact(&format!("{:#}\n\n{:?}\n", failure, failure)).as_bytes())?;
+
+        zip.finish()?;
+        Ok(())
+    }
+
+    fn describe_system(&self) -> String {
+        let mut s = String::new();
+        s.push_str(&format!("lethe: {}\n", env!("CARGO_PKG_VERSION")));
+        s.push_str(&format!("os: {}\n", std::env::consts::OS));
+        s.push_str(&format!("family: {}\n", std::env::consts::FAMILY));
+        s.push_str(&format!("arch: {}\n", std::env::consts::ARCH));
+
+        if cfg!(target_os = "linux") {
+            if let Ok(v) = std::fs::read_to_string("/proc/version") {
+                s.push_str(&format!("kernel: {}", v));
+            }
+        }
+        s
+    }
+
+    fn describe_device(&self) -> String {
+        format!(
+            "id: {}\nsize: {}\nblock size: {}\ntype: {}\nmounted: {}\n",
+            redact(&self.device_id),
+            self.details.size,
+            self.details.block_size,
+            self.details.storage_type,
+            self.details.mount_point.is_some()
+        )
+    }
+}
+
+fn describe_task(task: &WipeTask) -> String {
+    let mut s = String::new();
+    s.push_str(&format!("scheme: {}\n", task.scheme.description));
+    for stage in &task.scheme.stages {
+        s.push_str(&format!("- {}\n", stage));
+    }
+    s.push_str(&format!("verify: {}\n", task.verify));
+    s.push_str(&format!("total size: {}\n", task.total_size));
+    s.push_str(&format!("block size: {}\n", task.block_size));
+    for range in &task.ranges {
+        s.push_str(&format!("range: {}\n", range));
+    }
+    if task.operator.is_some() {
+        s.push_str("operator: <redacted>\n");
+    }
+    s
+}
+
+/// Strips user-specific details (home directory, user name) from free-form text.
+fn redact(s: &str) -> String {
+    let mut result = s.to_string();
+
+    for var in &["HOME", "USERPROFILE"] {
+        if let Ok(v) = std::env::var(var) {
+            if v.len() > 1 {
+                result = result.replace(&v, "~");
+            }
+        }
+    }
+
+    for var in &["USER", "USERNAME"] {
+        if let Ok(v) = std::env::var(var) {
+            if v.len() > 2 {
+                result = result.replace(&v, "<user>");
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+    use std::io::Read;
+
+    #[test]
+    fn test_recorder_keeps_last_events() {
+        let mut recorder = DiagnosticsRecorder::new("/dev/test", &StorageDetails::default(), 3);
+        let state = WipeState::default();
+
+        for i in 0..5 {
+            recorder.record(&state, &WipeEvent::Progress(i));
+        }
+
+        let events: Vec<_> = recorder.events.iter().collect();
+        assert_eq!(events.len(), 3);
+        assert!(events[0].ends_with("Progress(2)"));
+        assert!(events[2].ends_with("Progress(4)"));
+    }
+
+    #[test]
+    fn test_bundle_contents() {
+        let schemes = SchemeRepo::default();
+        let task = WipeTask::new(
+            schemes.find("zero").unwrap().clone(),
+            Verify::Last,
+            4096,
+            1024,
+        )
+        .unwrap();
+
+        let mut recorder = DiagnosticsRecorder::new("/dev/test", &StorageDetails::default(), 10);
+        recorder.record(&WipeState::default(), &WipeEvent::Started);
+
+        let path =
+            std::env::temp_dir().join(format!("lethe-diagnostics-test-{}.zip", std::process::id()));
+        recorder
+            .write_bundle(&path, Some(&task), &anyhow!("Mocked failure"))
+            .unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut names: Vec<_> = archive.file_names().map(|n| n.to_owned()).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "config.txt",
+                "device.txt",
+                "events.log",
+                "failure.txt",
+                "system.txt"
+            ]
+        );
+
+        let mut failure = String::new();
+        archive
+            .by_name("failure.txt")
+            .unwrap()
+            .read_to_string(&mut failure)
+            .unwrap();
+        assert!(failure.contains("Mocked failure"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use std::collections::HashMap;
+
+/// Number of bytes covered by a single digest.
+const GROUP_SIZE: u64 = 16 << 20;
+
+const KEY_SIZE: usize = 32;
+
+type GroupMac = Hmac<Sha256>;
+
+/// Digest of a group of blocks which is still being computed.
+struct PendingGroup {
+    group: u64,
+    start: u64,
+    end: u64,
+    mac: GroupMac,
+}
+
+/// Keyed digests (HMAC-SHA256) of the data written in a stage, one per group of blocks.
+/// Verification can compare the data read back against them instead of generating
+/// the stage data again.
+///
+/// Each digest covers the blocks of a group fed to it starting from some position, so it's
+/// only comparable when the data is read back starting from the same position.
+pub struct StageDigests {
+    key: [u8; KEY_SIZE],
+    group_size: u64,
+    /// Digests by the position of the first byte they cover.
+    groups: HashMap<u64, [u8; 32]>,
+    pending: Option<PendingGroup>,
+}
+
+/// Outcome of checking a group read back against the recorded digest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupCheck {
+    Match,
+    Mismatch,
+    /// No digest was recorded for the data read, it has to be checked some other way.
+    Unknown,
+}
+
+impl StageDigests {
+    /// Groups are aligned to the block size. The key is random so the digests can't be
+    /// forged by a device which recognizes the written data.
+    pub fn new(block_size: usize) -> Self {
+        let block_size = block_size as u64;
+        StageDigests {
+            key: rand::random(),
+            group_size: (GROUP_SIZE / block_size).max(1) * block_size,
+            groups: HashMap::new(),
+            pending: None,
+        }
+    }
+
+    pub fn group_size(&self) -> u64 {
+        self.group_size
+    }
+
+    /// Prepares for writing starting from the given position. Digests of the data written
+    /// past it are dropped, unless the writing simply continues after an interruption.
+    pub fn rewind(&mut self, position: u64) {
+        let group = position / self.group_size;
+        match &self.pending {
+            Some(p) if p.group == group && p.end <= position => return,
+            _ => self.pending = None,
+        }
+        let group_start = group * self.group_size;
+        self.groups.retain(|start, _| *start < group_start);
+    }
+
+    /// Records the data written at the given position.
+    pub fn record(&mut self, position: u64, data: &[u8]) {
+        let group = position / self.group_size;
+        if self.pending.as_ref().map(|p| p.group) != Some(group) {
+            self.finish();
+        }
+        let key = &self.key;
+        let pending = self
+            .pending
+            .get_or_insert_with(|| PendingGroup::new(key, group, position));
+        pending.update(position, data);
+    }
+
+    /// Completes the digest of the last group written, the next data recorded starts
+    /// a new digest even within the same group.
+    pub fn finish(&mut self) {
+        if let Some(p) = self.pending.take() {
+            self.groups.insert(p.start, p.digest());
+        }
+    }
+
+    /// Starts checking the data read back from the given position.
+    pub fn checker(&self, position: u64) -> GroupChecker {
+        GroupChecker(PendingGroup::new(
+            &self.key,
+            position / self.group_size,
+            position,
+        ))
+    }
+
+    pub fn check(&self, checker: GroupChecker) -> GroupCheck {
+        let GroupChecker(p) = checker;
+        match self.groups.get(&p.start) {
+            Some(digest) if p.digest() == *digest => GroupCheck::Match,
+            Some(_) => GroupCheck::Mismatch,
+            None => GroupCheck::Unknown,
+        }
+    }
+}
+
+/// Digest of a group being read back, see [`StageDigests::checker`].
+pub struct GroupChecker(PendingGroup);
+
+impl GroupChecker {
+    pub fn group(&self) -> u64 {
+        self.0.group
+    }
+
+    /// Position of the first byte fed to the checker.
+    pub fn start(&self) -> u64 {
+        self.0.start
+    }
+
+    /// Position after the last byte fed to the checker.
+    pub fn end(&self) -> u64 {
+        self.0.end
+    }
+
+    pub fn update(&mut self, position: u64, data: &[u8]) {
+        self.0.update(position, data)
+    }
+}
+
+impl PendingGroup {
+    fn new(key: &[u8], group: u64, start: u64) -> Self {
+        PendingGroup {
+            group,
+            start,
+            end: start,
+            mac: GroupMac::new_varkey(key).unwrap(),
+        }
+    }
+
+    fn update(&mut self, position: u64, data: &[u8]) {
+        // blocks skipped as bad are left out, the positions keep the rest in place
+        self.mac.update(&position.to_le_bytes());
+        self.mac.update(data);
+        self.end = position + data.len() as u64;
+    }
+
+    fn digest(self) -> [u8; 32] {
+        self.mac.finalize().into_bytes().into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BLOCK: usize = 1 << 20;
+
+    fn record_range(digests: &mut StageDigests, from: u64, to: u64, value: u8) {
+        let data = vec![value; BLOCK];
+        for position in (from..to).step_by(BLOCK) {
+            digests.record(position, &data);
+        }
+    }
+
+    fn check_range(digests: &StageDigests, from: u64, to: u64, value: u8) -> Vec<GroupCheck> {
+        let data = vec![value; BLOCK];
+        let mut results = Vec::new();
+        let mut checker = digests.checker(from);
+        for position in (from..to).step_by(BLOCK) {
+            if position / digests.group_size() != checker.group() {
+                results.push(digests.check(checker));
+                checker = digests.checker(position);
+            }
+            checker.update(position, &data);
+        }
+        results.push(digests.check(checker));
+        results
+    }
+
+    #[test]
+    fn test_digests_detect_changed_groups() {
+        let group = GROUP_SIZE;
+        let mut digests = StageDigests::new(BLOCK);
+        record_range(&mut digests, 0, 3 * group, 0);
+        digests.finish();
+
+        assert_eq!(
+            check_range(&digests, 0, 3 * group, 0),
+            vec![GroupCheck::Match; 3]
+        );
+        assert_eq!(
+            check_range(&digests, 0, group, 1),
+            vec![GroupCheck::Mismatch]
+        );
+        // data read back from a different position can't be compared
+        assert_eq!(
+            check_range(&digests, BLOCK as u64, group, 0),
+            vec![GroupCheck::Unknown]
+        );
+    }
+
+    #[test]
+    fn test_digests_rewind() {
+        let group = GROUP_SIZE;
+        let middle = group + 4 * BLOCK as u64;
+        let mut digests = StageDigests::new(BLOCK);
+
+        // interrupted and continued from the same position
+        record_range(&mut digests, 0, middle, 0);
+        digests.rewind(middle);
+        record_range(&mut digests, middle, 3 * group, 0);
+        digests.finish();
+        assert_eq!(
+            check_range(&digests, 0, 3 * group, 0),
+            vec![GroupCheck::Match; 3]
+        );
+
+        // rewritten from the middle of a group
+        digests.rewind(middle);
+        record_range(&mut digests, middle, 3 * group, 0);
+        digests.finish();
+        assert_eq!(check_range(&digests, 0, group, 0), vec![GroupCheck::Match]);
+        assert_eq!(
+            check_range(&digests, middle, 3 * group, 0),
+            vec![GroupCheck::Match; 2]
+        );
+    }
+}
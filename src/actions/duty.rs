@@ -0,0 +1,166 @@
+use anyhow::Result;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_BURST: Duration = Duration::from_secs(4);
+
+/// Time worth of bytes a rate limited wipe may go ahead by, short so the pace stays even.
+const RATE_LIMIT_BURST: Duration = Duration::from_millis(250);
+
+/// Share of time the device is kept busy. The rest is spent in pauses between bursts
+/// of I/O, which lets slow-to-cool enclosures avoid thermal throttling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DutyCycle {
+    percent: u8,
+    burst: Duration,
+}
+
+impl DutyCycle {
+    pub fn new(percent: u8) -> Result<Self> {
+        if percent == 0 || percent > 100 {
+            return Err(anyhow!("Duty cycle should be between 1 and 100 percent"));
+        }
+        Ok(DutyCycle {
+            percent,
+            burst: DEFAULT_BURST,
+        })
+    }
+
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+
+    /// Length of the pause required after being busy for the given time.
+    pub fn pause_after(&self, active: Duration) -> Duration {
+        active * (100 - self.percent as u32) / self.percent as u32
+    }
+}
+
+/// Keeps track of the current burst and pauses when it's over.
+pub(crate) struct Throttle {
+    duty: DutyCycle,
+    burst_started: Instant,
+}
+
+impl Throttle {
+    pub fn new(duty: DutyCycle) -> Self {
+        Throttle {
+            duty,
+            burst_started: Instant::now(),
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let active = self.burst_started.elapsed();
+        if active >= self.duty.burst {
+            sleep(self.duty.pause_after(active));
+            self.burst_started = Instant::now();
+        }
+    }
+}
+
+/// Paces the I/O to the given number of bytes per second. Each block takes its size in
+/// tokens from a bucket refilled at that rate, and waits when the bucket runs dry.
+pub(crate) struct TokenBucket {
+    rate: u64,
+    burst: Duration,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u64) -> Self {
+        Self::with_burst(rate, RATE_LIMIT_BURST)
+    }
+
+    fn with_burst(rate: u64, burst: Duration) -> Self {
+        TokenBucket {
+            rate,
+            burst,
+            tokens: rate as f64 * burst.as_secs_f64(),
+            refilled: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let capacity = self.rate as f64 * self.burst.as_secs_f64();
+        self.tokens = (self.tokens
+            + now.duration_since(self.refilled).as_secs_f64() * self.rate as f64)
+            .min(capacity);
+        self.refilled = now;
+    }
+
+    pub fn take(&mut self, bytes: u64) {
+        self.refill();
+        self.tokens -= bytes as f64;
+        if self.tokens < 0.0 {
+            // blocks larger than the bucket are simply waited for in full
+            sleep(Duration::from_secs_f64(-self.tokens / self.rate as f64));
+            self.refill();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::*;
+
+    #[test]
+    fn test_duty_cycle_validation() {
+        assert_matches!(DutyCycle::new(0), Err(_));
+        assert_matches!(DutyCycle::new(101), Err(_));
+        assert_matches!(DutyCycle::new(100), Ok(_));
+    }
+
+    #[test]
+    fn test_pause_length() {
+        let active = Duration::from_secs(4);
+        assert_eq!(
+            DutyCycle::new(80).unwrap().pause_after(active),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            DutyCycle::new(50).unwrap().pause_after(active),
+            Duration::from_secs(4)
+        );
+        assert_eq!(
+            DutyCycle::new(100).unwrap().pause_after(active),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn test_throttle_pauses_after_burst() {
+        let mut throttle = Throttle::new(DutyCycle {
+            percent: 50,
+            burst: Duration::from_millis(20),
+        });
+
+        let started = Instant::now();
+        throttle.tick();
+        assert!(started.elapsed() < Duration::from_millis(20));
+
+        sleep(Duration::from_millis(20));
+        throttle.tick();
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_token_bucket_paces_to_rate() {
+        let mut bucket = TokenBucket::with_burst(1 << 20, Duration::from_millis(50));
+
+        let started = Instant::now();
+        // the burst goes through right away
+        bucket.take(32 << 10);
+        assert!(started.elapsed() < Duration::from_millis(25));
+
+        for _ in 0..8 {
+            bucket.take(32 << 10);
+        }
+        // 288k at 1M/s with 50ms worth of a head start
+        assert!(started.elapsed() >= Duration::from_millis(225));
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+}
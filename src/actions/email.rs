@@ -0,0 +1,429 @@
+//! Emails about the outcome of a wipe and the troubles on the way, sent over SMTP with `curl`.
+
+use crate::actions::background::BackgroundProcesses;
+use crate::actions::report::format_timestamp;
+use crate::actions::timing::format_duration;
+use crate::actions::{WipeEvent, WipeEventReceiver, WipeState, WipeTask};
+use anyhow::Result;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// Minimum time between the emails about the same kind of trouble.
+pub const DEFAULT_THROTTLE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Retries in a row, without any progress in between, considered a loop worth an email.
+pub const DEFAULT_RETRY_THRESHOLD: u32 = 3;
+
+/// SMTP server the emails are sent through, the `[email]` table of the config.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SmtpSettings {
+    /// `smtps://host:465` for TLS or `smtp://host:587`, which is upgraded with STARTTLS.
+    pub server: String,
+    pub from: String,
+    pub username: Option<String>,
+    #[serde(skip_serializing)]
+    pub password: Option<String>,
+    /// Sends over an `smtp://` connection even if the server doesn't support STARTTLS.
+    pub allow_plaintext: bool,
+}
+
+impl SmtpSettings {
+    pub fn validate(&self) -> Result<()> {
+        if !self.server.starts_with("smtp://") && !self.server.starts_with("smtps://") {
+            return Err(anyhow!(
+                "The email server should be an smtp:// or smtps:// URL, not \"{}\"",
+                self.server
+            ));
+        }
+        if self.from.is_empty() {
+            return Err(anyhow!("The email sender (from) is missing"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub subject: String,
+    pub body: Vec<String>,
+}
+
+/// Emails the outcome of the wipe, and the first bad block or a loop of retries, before
+/// handing the events to the inner receiver. The same trouble is emailed again only after
+/// the throttle interval, with the number of the events left out in between.
+pub struct EmailNotifier {
+    inner: Box<dyn WipeEventReceiver>,
+    device_id: String,
+    smtp: SmtpSettings,
+    to: Vec<String>,
+    throttle: Duration,
+    retry_threshold: u32,
+    retries: u32,
+    last_sent: HashMap<&'static str, Duration>,
+    suppressed: HashMap<&'static str, usize>,
+    /// Running `curl` processes with their configs.
+    processes: BackgroundProcesses,
+    /// Private directory for the configs with the credentials, created for the first one.
+    config_dir: Option<PathBuf>,
+    sent: usize,
+}
+
+impl EmailNotifier {
+    pub fn new(
+        inner: Box<dyn WipeEventReceiver>,
+        device_id: &str,
+        smtp: SmtpSettings,
+        to: Vec<String>,
+    ) -> Self {
+        EmailNotifier {
+            inner,
+            device_id: device_id.to_string(),
+            smtp,
+            to,
+            throttle: DEFAULT_THROTTLE_INTERVAL,
+            retry_threshold: DEFAULT_RETRY_THRESHOLD,
+            retries: 0,
+            last_sent: HashMap::new(),
+            suppressed: HashMap::new(),
+            processes: BackgroundProcesses::default(),
+            config_dir: None,
+            sent: 0,
+        }
+    }
+
+    pub fn with_throttle(mut self, throttle: Duration) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// The email to send about the event, if any.
+    pub fn email(
+        &mut self,
+        task: &WipeTask,
+        state: &WipeState,
+        event: &WipeEvent,
+    ) -> Option<Email> {
+        let (kind, subject, error) = match event {
+            WipeEvent::Progress(_) | WipeEvent::StageCompleted(None) => {
+                self.retries = 0;
+                return None;
+            }
+            WipeEvent::Retrying(_) => {
+                self.retries += 1;
+                if self.retries < self.retry_threshold {
+                    return None;
+                }
+                ("retrying", "keeps retrying", None)
+            }
+            WipeEvent::MarkBlockAsBad(_) => ("bad_block", "is skipping bad blocks", None),
+            WipeEvent::Completed(None) => ("completed", "completed", None),
+            WipeEvent::Completed(Some(err)) | WipeEvent::Fatal(err) => {
+                ("failed", "failed", Some(format!("{:#}", err)))
+            }
+            WipeEvent::Aborted => ("aborted", "was aborted", None),
+            _ => return None,
+        };
+
+        let elapsed = state.stamp.elapsed;
+        let repeated = matches!(kind, "retrying" | "bad_block");
+        if repeated {
+            match self.last_sent.get(kind) {
+                Some(last) if elapsed < *last + self.throttle => {
+                    *self.suppressed.entry(kind).or_default() += 1;
+                    return None;
+                }
+                _ => {
+                    self.last_sent.insert(kind, elapsed);
+                }
+            }
+        }
+
+        let mut body = vec![
+            format!("Device: {}", self.device_id),
+            format!("Scheme: {}", task.scheme.description),
+            format!(
+                "Stage: {}/{}{}",
+                state.stage + 1,
+                task.scheme.stages.len(),
+                if state.at_verification {
+                    " (verifying)"
+                } else {
+                    ""
+                }
+            ),
+            format!(
+                "Progress: {} of {} bytes",
+                task.progress_at(state.position),
+                task.wiped_size()
+            ),
+            format!("Elapsed: {}", format_duration(elapsed)),
+            format!("Bad blocks: {}", state.bad_blocks.borrow().total_marked()),
+            format!("Time: {}", format_timestamp(state.stamp.timestamp)),
+        ];
+        if let Some(e) = error {
+            body.push(format!("Error: {}", e));
+        }
+        let suppressed = if repeated {
+            self.suppressed.remove(kind).unwrap_or(0)
+        } else {
+            self.suppressed.drain().map(|(_, n)| n).sum()
+        };
+        if suppressed > 0 {
+            body.push(format!(
+                "Events not emailed since the previous email: {}",
+                suppressed
+            ));
+        }
+
+        Some(Email {
+            subject: format!("Wipe of {} {}", self.device_id, subject),
+            body,
+        })
+    }
+
+    fn message(&self, email: &Email) -> String {
+        let mut lines = vec![
+            format!("From: {}", self.smtp.from),
+            format!("To: {}", self.to.join(", ")),
+            format!("Subject: {}", email.subject),
+            "Content-Type: text/plain; charset=utf-8".to_string(),
+            String::new(),
+        ];
+        lines.extend(email.body.iter().cloned());
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    fn send(&mut self, email: &Email) -> Result<()> {
+        let mut command = Command::new("curl");
+        command
+            .args(["--silent", "--show-error", "--max-time", "60", "--crlf"])
+            .arg("--url")
+            .arg(&self.smtp.server)
+            .arg("--mail-from")
+            .arg(&self.smtp.from);
+        if !self.smtp.allow_plaintext {
+            command.arg("--ssl-reqd");
+        }
+        for to in &self.to {
+            command.arg("--mail-rcpt").arg(to);
+        }
+
+        // the credentials are kept out of the process list, in a config only the user can read
+        let config = match self.smtp.username.clone() {
+            Some(user) => {
+                let name = format!("smtp-{}.conf", self.sent);
+                let path = self.config_dir()?.join(name);
+                let credentials =
+                    format!("{}:{}", user, self.smtp.password.as_deref().unwrap_or(""));
+                write_private(&path, &format!("user = \"{}\"\n", escape(&credentials)))?;
+                command.arg("--config").arg(&path);
+                Some(path)
+            }
+            None => None,
+        };
+        self.sent += 1;
+
+        command.args(["--upload-file", "-"]);
+        let message = self.message(email);
+        self.processes
+            .spawn(&mut command, Some(message.as_bytes()), config);
+        Ok(())
+    }
+
+    fn config_dir(&mut self) -> Result<&Path> {
+        if self.config_dir.is_none() {
+            self.config_dir = Some(create_private_dir()?);
+        }
+        Ok(self.config_dir.as_deref().unwrap())
+    }
+
+    /// Number of emails which couldn't be sent.
+    pub fn failed(&self) -> usize {
+        self.processes.failed()
+    }
+}
+
+impl Drop for EmailNotifier {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.config_dir {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Creates a directory only the user can access, under a random name so that nobody can
+/// create it (or a link with its name) beforehand.
+fn create_private_dir() -> Result<PathBuf> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    let mut attempts = 0;
+    loop {
+        let path = std::env::temp_dir().join(format!(
+            "lethe-smtp-{}-{:016x}",
+            std::process::id(),
+            rand::thread_rng().gen::<u64>()
+        ));
+        match builder.create(&path) {
+            Ok(()) => return Ok(path),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && attempts < 8 => {
+                attempts += 1
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    let _ = std::fs::remove_file(path);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Escapes a value for a double quoted string of the curl config, which ends at a line
+/// break, so the line breaks are written as escapes too.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl WipeEventReceiver for EmailNotifier {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        if let Some(email) = self.email(task, state, &event) {
+            if self.send(&email).is_err() {
+                self.processes.add_failure();
+            }
+        }
+
+        let finished = matches!(
+            event,
+            WipeEvent::Completed(_) | WipeEvent::Aborted | WipeEvent::Fatal(_)
+        );
+        self.processes.reap(finished);
+        if finished && self.failed() > 0 {
+            eprintln!("Unable to send {} email(s).", self.failed());
+        }
+
+        self.inner.handle(task, state, event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+    use std::rc::Rc;
+
+    struct Nothing;
+
+    impl WipeEventReceiver for Nothing {
+        fn handle(&mut self, _task: &WipeTask, _state: &WipeState, _event: WipeEvent) {}
+    }
+
+    #[test]
+    fn test_repeated_events_are_throttled() {
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::No, 1000, 100).unwrap();
+        let mut state = WipeState::default();
+        let mut notifier = EmailNotifier::new(
+            Box::new(Nothing),
+            "/dev/sdx",
+            SmtpSettings::default(),
+            vec!["ops@example.com".to_string()],
+        )
+        .with_throttle(Duration::from_secs(600));
+        let mut at = |secs: u64, event: WipeEvent| {
+            state.stamp.elapsed = Duration::from_secs(secs);
+            notifier.email(&task, &state, &event)
+        };
+
+        let retrying = || WipeEvent::Retrying(Duration::from_secs(1));
+        assert!(at(0, retrying()).is_none());
+        assert!(at(1, retrying()).is_none());
+        assert!(at(2, WipeEvent::Progress(100)).is_none());
+        assert!(at(3, retrying()).is_none());
+        assert!(at(4, retrying()).is_none());
+        let email = at(5, retrying()).unwrap();
+        assert_eq!(email.subject, "Wipe of /dev/sdx keeps retrying");
+        assert!(email.body.contains(&"Elapsed: 00:00:05".to_string()));
+
+        assert!(at(6, WipeEvent::MarkBlockAsBad(200)).is_some());
+        assert!(at(7, WipeEvent::MarkBlockAsBad(300)).is_none());
+        assert!(at(8, retrying()).is_none());
+        assert!(at(9, retrying()).is_none());
+
+        let email = at(700, retrying()).unwrap();
+        assert!(email
+            .body
+            .contains(&"Events not emailed since the previous email: 2".to_string()));
+
+        let err = Rc::new(anyhow!("Mocked IO failure"));
+        let email = at(701, WipeEvent::Completed(Some(err))).unwrap();
+        assert_eq!(email.subject, "Wipe of /dev/sdx failed");
+        assert!(email.body.contains(&"Error: Mocked IO failure".to_string()));
+        assert!(email
+            .body
+            .contains(&"Events not emailed since the previous email: 1".to_string()));
+    }
+
+    #[test]
+    fn test_smtp_settings_validation() {
+        let mut smtp = SmtpSettings {
+            server: "mail.example.com".to_string(),
+            from: "lethe@example.com".to_string(),
+            ..Default::default()
+        };
+        assert!(smtp.validate().is_err());
+        smtp.server = "smtps://mail.example.com:465".to_string();
+        assert!(smtp.validate().is_ok());
+        smtp.from.clear();
+        assert!(smtp.validate().is_err());
+        assert_eq!(escape(r#"user:p"a\ss"#), r#"user:p\"a\\ss"#);
+        assert_eq!(
+            escape("user:pass\nurl = \"evil\"\r"),
+            r#"user:pass\nurl = \"evil\"\r"#
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_config_dir_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = create_private_dir().unwrap();
+        let other = create_private_dir().unwrap();
+        assert_ne!(dir, other);
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        std::fs::remove_dir(&dir).unwrap();
+        std::fs::remove_dir(&other).unwrap();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
@@ -0,0 +1,71 @@
+/// Blocks shorter than this can't tell random data from a pattern reliably and pass as is.
+pub const MIN_CHECKED_SIZE: usize = 1024;
+
+/// Deviation of the chi-square statistic from its mean, in standard deviations, beyond
+/// which the data isn't considered random. Random data gets there about once in 10^11
+/// blocks, so a false mismatch is practically impossible.
+const MAX_DEVIATION: f64 = 7.0;
+
+const DEGREES_OF_FREEDOM: f64 = 255.0;
+
+/// Chi-square statistic of the byte value counts against the uniform distribution.
+fn chi_square(counts: &[u64; 256], total: u64) -> f64 {
+    let expected = total as f64 / 256.0;
+    counts
+        .iter()
+        .map(|&c| {
+            let d = c as f64 - expected;
+            d * d / expected
+        })
+        .sum()
+}
+
+fn is_uniform(counts: &[u64; 256], total: u64) -> bool {
+    let deviation =
+        (chi_square(counts, total) - DEGREES_OF_FREEDOM) / (2.0 * DEGREES_OF_FREEDOM).sqrt();
+    deviation.abs() <= MAX_DEVIATION
+}
+
+/// Checks that the data read back from a random stage looks statistically random, without
+/// knowing the seed. Both the byte values and the differences between the neighbouring
+/// bytes have to be uniformly distributed, so counters and other simple sequences with
+/// a flat histogram are caught too. Much weaker than comparing with the generated data,
+/// but doesn't depend on the generator.
+pub fn looks_random(data: &[u8]) -> bool {
+    if data.len() < MIN_CHECKED_SIZE {
+        return true;
+    }
+
+    let mut values = [0u64; 256];
+    let mut differences = [0u64; 256];
+    for b in data {
+        values[*b as usize] += 1;
+    }
+    for pair in data.windows(2) {
+        differences[pair[1].wrapping_sub(pair[0]) as usize] += 1;
+    }
+
+    is_uniform(&values, data.len() as u64) && is_uniform(&differences, data.len() as u64 - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sanitization::{Stage, StreamingIterator};
+
+    #[test]
+    fn test_random_data_is_told_from_patterns() {
+        let mut stream = Stage::random().stream(1 << 20, 1 << 16, 1 << 16, 0);
+        while let Some(block) = stream.next() {
+            assert!(looks_random(block));
+        }
+
+        let counter: Vec<u8> = (0..65536).map(|i| i as u8).collect();
+        assert!(!looks_random(&counter));
+
+        assert!(!looks_random(&[0; 65536]));
+        assert!(!looks_random(&[0x55, 0xaa].repeat(32768)));
+        assert!(!looks_random(&b"Lorem ipsum dolor sit amet. ".repeat(2000)));
+        assert!(looks_random(&[0; 100]));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::actions::report::format_timestamp;
+use crate::actions::{WipeEvent, WipeEventReceiver, WipeState, WipeTask};
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// Minimum time between the progress lines written to the log.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Code of the OS error behind the error, if there is one.
+pub fn os_error_code(error: &anyhow::Error) -> Option<i32> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .and_then(|e| e.raw_os_error())
+}
+
+/// Single line description of the event, if it's worth reporting.
+pub fn describe(task: &WipeTask, state: &WipeState, event: &WipeEvent) -> Option<String> {
+    let stage = format!(
+        "Stage {}/{}{}",
+        state.stage + 1,
+        task.scheme.stages.len(),
+        if state.at_verification {
+            " (verifying)"
+        } else {
+            ""
+        }
+    );
+    let line = match event {
+        WipeEvent::Started => format!("Wiping with {}", task.scheme.description),
+        WipeEvent::StageStarted => format!("{}: {}", stage, task.scheme.stages[state.stage]),
+        WipeEvent::StageSkipped => format!("{}: skipped", stage),
+        WipeEvent::Progress(position) => {
+            let done = task.progress_at(*position);
+            format!(
+                "{}: {:.1}% ({} of {} bytes)",
+                stage,
+                done as f64 * 100.0 / task.wiped_size().max(1) as f64,
+                done,
+                task.wiped_size()
+            )
+        }
+        WipeEvent::MarkBlockAsBad(position) => format!("Bad block at {}", position),
+        WipeEvent::MarkBlockAsMismatched(position) => format!("Mismatch at {}", position),
+        WipeEvent::StageCompleted(Some(err)) => format!("{}: failed. {:#}", stage, err),
+        WipeEvent::StageCompleted(None) => format!("{}: done", stage),
+        WipeEvent::Retrying(_) => "Retrying".to_string(),
+        WipeEvent::Completed(Some(err)) => format!("Wipe failed. {:#}", err),
+        WipeEvent::Completed(None) => "Wipe completed".to_string(),
+        WipeEvent::Paused => format!("{}: paused at {}", stage, state.position),
+        WipeEvent::PausedUntil(until) => format!(
+            "{}: paused at {} until {}",
+            stage,
+            state.position,
+            format_timestamp(*until)
+        ),
+        WipeEvent::Resumed => format!("{}: resumed", stage),
+        WipeEvent::Aborted => "Wipe aborted".to_string(),
+        WipeEvent::Fatal(err) => format!("Fatal error. {:#}", err),
+        _ => return None,
+    };
+    Some(line)
+}
+
+/// Appends a timestamped line per wipe event to a plain text log, see `--log-format`.
+pub struct TextLogReceiver {
+    device_id: String,
+    out: Box<dyn Write>,
+    last_progress: Option<Duration>,
+}
+
+impl TextLogReceiver {
+    pub fn new(device_id: &str, out: Box<dyn Write>) -> Self {
+        TextLogReceiver {
+            device_id: device_id.to_string(),
+            out,
+            last_progress: None,
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(device_id: &str, path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())
+            .context(format!(
+                "Unable to open log file {}",
+                path.as_ref().to_string_lossy()
+            ))?;
+        Ok(TextLogReceiver::new(device_id, Box::new(file)))
+    }
+
+    fn write(&mut self, state: &WipeState, line: &str) {
+        let _ = writeln!(
+            self.out,
+            "{} +{:.3}s #{} {}",
+            format_timestamp(state.stamp.timestamp),
+            state.stamp.elapsed.as_secs_f64(),
+            state.stamp.sequence,
+            line
+        );
+        let _ = self.out.flush();
+    }
+}
+
+impl WipeEventReceiver for TextLogReceiver {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        match &event {
+            WipeEvent::Progress(position) => {
+                let elapsed = state.stamp.elapsed;
+                let due = match self.last_progress {
+                    Some(last) => elapsed >= last + PROGRESS_INTERVAL,
+                    None => true,
+                };
+                if !due && task.progress_at(*position) < task.wiped_size() {
+                    return;
+                }
+                self.last_progress = Some(elapsed);
+            }
+            WipeEvent::StageStarted => self.last_progress = None,
+            _ => {}
+        }
+
+        // the errors of the system calls include the OS error code already
+        let mut line = match describe(task, state, &event) {
+            Some(line) => line,
+            None => format!("{:?}", event),
+        };
+        match &event {
+            WipeEvent::Started => line.push_str(&format!(
+                " on {} ({} bytes), block size {}, verification: {}",
+                self.device_id, task.total_size, task.block_size, task.verify
+            )),
+            WipeEvent::Retrying(delay) => line.push_str(&format!(
+                " at {} in {}s, {} retries and {} verification retries left",
+                state.position,
+                delay.as_secs(),
+                state.retries_left,
+                state.verify_retries_left
+            )),
+            _ => {}
+        }
+        self.write(state, &line);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+    use crate::storage::StorageError;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_text_log_has_a_line_per_event() {
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::No, 1000, 100).unwrap();
+        let mut state = WipeState::default();
+
+        let path = std::env::temp_dir().join(format!("lethe-log-test-{}.log", std::process::id()));
+        let mut receiver = TextLogReceiver::open("/dev/sdx", &path).unwrap();
+        receiver.handle(&task, &state, WipeEvent::Started);
+        receiver.handle(&task, &state, WipeEvent::StageStarted);
+        receiver.handle(&task, &state, WipeEvent::Progress(100));
+        // too soon after the previous one
+        receiver.handle(&task, &state, WipeEvent::Progress(200));
+        receiver.handle(&task, &state, WipeEvent::MarkBlockAsBad(300));
+        let err = anyhow::Error::new(StorageError::Other(std::io::Error::from_raw_os_error(5)))
+            .context("Unable to write at 400");
+        receiver.handle(&task, &state, WipeEvent::StageCompleted(Some(Rc::new(err))));
+        state.retries_left = 2;
+        receiver.handle(&task, &state, WipeEvent::Retrying(Duration::from_secs(3)));
+        drop(receiver);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<_> = content
+            .lines()
+            .map(|l| l.splitn(4, ' ').nth(3).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 6, "{:?}", lines);
+        assert!(lines[0].starts_with("Wiping with Single zeroes fill on /dev/sdx (1000 bytes)"));
+        assert_eq!(lines[2], "Stage 1/1: 10.0% (100 of 1000 bytes)");
+        assert_eq!(lines[3], "Bad block at 300");
+        assert!(lines[4].ends_with("(os error 5)"));
+        assert!(lines[5].starts_with("Retrying at 0 in 3s, 2 retries"));
+    }
+}
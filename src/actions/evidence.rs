@@ -0,0 +1,300 @@
+use crate::actions::report::sha256_hex;
+use crate::actions::{WipeRange, WipeState, WipeTask};
+use crate::sanitization::mem::AlignedBuffer;
+use crate::sanitization::StreamingIterator;
+use crate::storage::StorageAccess;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Size of a sampled sector, aligned for direct access on any device.
+pub const SECTOR_SIZE: usize = 4096;
+
+pub const DEFAULT_SAMPLES: usize = 100;
+
+pub const NONCE_SIZE: usize = 16;
+
+/// A sector read back after the wipe.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SectorSample {
+    pub offset: u64,
+    pub sha256: String,
+    /// The sector holds the data written by the last stage of the scheme.
+    pub matches_final_stage: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EvidenceManifest {
+    pub tool: String,
+    pub device: String,
+    pub size: u64,
+    pub sector_size: usize,
+    /// Hex encoded nonce the offsets are derived from, also recorded in the wipe report.
+    pub nonce: String,
+    pub final_stage: String,
+    pub samples: Vec<SectorSample>,
+}
+
+/// Sectors sampled from the wiped ranges, to be checked independently of the tool.
+///
+/// The offsets are derived from the nonce (see [`sample_offsets`]), so they can't be picked
+/// to hide the sectors which weren't wiped.
+pub struct Evidence {
+    pub manifest: EvidenceManifest,
+    /// Content of the sampled sectors, in the order of the manifest.
+    pub data: Vec<u8>,
+}
+
+pub fn generate_nonce() -> [u8; NONCE_SIZE] {
+    rand::random()
+}
+
+/// Offsets of up to `count` distinct sectors fully within the ranges. The n-th candidate is
+/// `SHA-256("lethe-evidence" || nonce || n)` (with n as a little-endian u32), its first 8 bytes
+/// taken as a little-endian index of a sector counted across the ranges. Candidates rejected
+/// by the filter (or already taken) are skipped.
+pub fn sample_offsets(
+    nonce: &[u8],
+    ranges: &[WipeRange],
+    count: usize,
+    accept: impl Fn(u64) -> bool,
+) -> Vec<u64> {
+    let sector_size = SECTOR_SIZE as u64;
+    let first_sector = |r: &WipeRange| r.start.div_ceil(sector_size);
+    let sectors_in = |r: &WipeRange| (r.end / sector_size).saturating_sub(first_sector(r));
+    let total: u64 = ranges.iter().map(sectors_in).sum();
+
+    let mut offsets = Vec::new();
+    let count = count.min(total.min(usize::MAX as u64) as usize);
+    // bounded in case most of the sectors are rejected
+    for n in 0..(count as u32).saturating_mul(16) {
+        if offsets.len() == count {
+            break;
+        }
+
+        let digest = Sha256::new()
+            .chain(b"lethe-evidence")
+            .chain(nonce)
+            .chain(n.to_le_bytes())
+            .finalize();
+        let mut index = u64::from_le_bytes(digest[..8].try_into().unwrap()) % total;
+
+        let offset = ranges
+            .iter()
+            .find_map(|r| {
+                let sectors = sectors_in(r);
+                if index < sectors {
+                    Some((first_sector(r) + index) * sector_size)
+                } else {
+                    index -= sectors;
+                    None
+                }
+            })
+            .unwrap();
+
+        if !offsets.contains(&offset) && accept(offset) {
+            offsets.push(offset);
+        }
+    }
+    offsets
+}
+
+impl Evidence {
+    /// Reads the sampled sectors back and compares them with the last stage of the task.
+    /// Sectors in the blocks skipped as bad are left out.
+    pub fn collect(
+        access: &mut dyn StorageAccess,
+        device_id: &str,
+        task: &WipeTask,
+        state: &WipeState,
+        nonce: &[u8],
+        count: usize,
+    ) -> Result<Evidence> {
+        let final_stage = task
+            .scheme
+            .stages
+            .iter()
+            .rev()
+            .find(|s| s.is_verifiable())
+            .ok_or_else(|| anyhow!("The scheme doesn't write any data to sample"))?;
+
+        let block_size = task.block_size as u64;
+        let bad_blocks = state.bad_blocks.borrow();
+        let offsets = sample_offsets(nonce, &task.ranges, count, |o| {
+            let first = (o / block_size) as u32;
+            let last = ((o + SECTOR_SIZE as u64 - 1) / block_size) as u32;
+            (first..=last).all(|b| !bad_blocks.is_marked(b))
+        });
+        if offsets.is_empty() {
+            return Err(anyhow!("No sectors to sample"));
+        }
+
+        let mut buf = AlignedBuffer::new(SECTOR_SIZE, SECTOR_SIZE);
+        let mut data = Vec::with_capacity(offsets.len() * SECTOR_SIZE);
+        let mut samples = Vec::with_capacity(offsets.len());
+        for offset in offsets {
+            access.seek(offset)?;
+            let sector = buf.as_mut_slice();
+            let mut filled = 0;
+            while filled < SECTOR_SIZE {
+                match access.read(&mut sector[filled..])? {
+                    0 => return Err(anyhow!("Unexpected end of device at {}", offset)),
+                    n => filled += n,
+                }
+            }
+
+            let mut expected = final_stage.stream(
+                offset + SECTOR_SIZE as u64,
+                SECTOR_SIZE,
+                SECTOR_SIZE,
+                offset,
+            );
+            samples.push(SectorSample {
+                offset,
+                sha256: sha256_hex(sector),
+                matches_final_stage: expected.next() == Some(&*sector),
+            });
+            data.extend_from_slice(sector);
+        }
+
+        Ok(Evidence {
+            manifest: EvidenceManifest {
+                tool: format!("lethe {}", env!("CARGO_PKG_VERSION")),
+                device: device_id.to_string(),
+                size: task.total_size,
+                sector_size: SECTOR_SIZE,
+                nonce: hex(nonce),
+                final_stage: final_stage.to_string(),
+                samples,
+            },
+            data,
+        })
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.manifest.samples.iter().all(|s| s.matches_final_stage)
+    }
+
+    /// Writes a zip archive with `manifest.json` and the raw sectors in `sectors.bin`.
+    /// As with reports, a SHA-256 digest of the archive is saved next to it.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+        let file = File::create(path).context(format!(
+            "Unable to create evidence archive {}",
+            path.to_string_lossy()
+        ))?;
+
+        let mut zip = ZipWriter::new(file);
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        zip.start_file("manifest.json", options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&self.manifest)?)?;
+
+        zip.start_file("sectors.bin", options)?;
+        zip.write_all(&self.data)?;
+        zip.finish()?;
+
+        let content = std::fs::read(path)?;
+        let mut digest_path = path.as_os_str().to_owned();
+        digest_path.push(".sha256");
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        std::fs::write(
+            &digest_path,
+            format!("{}  {}\n", sha256_hex(&content), file_name),
+        )
+        .context("Unable to write evidence archive digest")?;
+
+        Ok(PathBuf::from(digest_path))
+    }
+}
+
+pub fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+    use crate::storage::memory::InMemoryStorage;
+    use std::io::Read;
+
+    #[test]
+    fn test_sample_offsets_are_stable() {
+        let ranges = vec![WipeRange::new(0, 40960), WipeRange::new(81920, 102400)];
+        let offsets = sample_offsets(b"nonce", &ranges, 8, |_| true);
+        assert_eq!(offsets, sample_offsets(b"nonce", &ranges, 8, |_| true));
+        assert_ne!(offsets, sample_offsets(b"other", &ranges, 8, |_| true));
+        assert_eq!(offsets.len(), 8);
+        assert!(offsets.iter().all(|o| o % SECTOR_SIZE as u64 == 0
+            && ranges.iter().any(|r| *o >= r.start && o + 4096 <= r.end)));
+
+        // all sectors are taken when there are fewer of them
+        let mut all = sample_offsets(b"nonce", &ranges[1..], 100, |_| true);
+        all.sort();
+        assert_eq!(all, vec![81920, 86016, 90112, 94208, 98304]);
+
+        assert!(sample_offsets(b"nonce", &ranges, 8, |o| o < 81920)
+            .iter()
+            .all(|o| *o < 81920));
+    }
+
+    #[test]
+    fn test_evidence_checks_final_stage() {
+        let scheme = SchemeRepo::default().find("random").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::No, 1 << 20, 65536).unwrap();
+
+        let mut data = vec![0u8; 1 << 20];
+        let mut stream = task.scheme.stages[0].stream(1 << 20, 65536, task.io_alignment, 0);
+        let mut position = 0;
+        while let Some(chunk) = stream.next() {
+            data[position..position + chunk.len()].copy_from_slice(chunk);
+            position += chunk.len();
+        }
+
+        let nonce = generate_nonce();
+        let mut storage = InMemoryStorage::with_data(data);
+        let state = WipeState::default();
+        let evidence =
+            Evidence::collect(&mut storage, "/dev/test", &task, &state, &nonce, 10).unwrap();
+        assert_eq!(evidence.manifest.samples.len(), 10);
+        assert_eq!(evidence.data.len(), 10 * SECTOR_SIZE);
+        assert!(evidence.is_consistent());
+
+        // a sector which wasn't wiped
+        let offset = evidence.manifest.samples[3].offset as usize;
+        storage.file.get_mut()[offset + 100] ^= 0xff;
+        let evidence =
+            Evidence::collect(&mut storage, "/dev/test", &task, &state, &nonce, 10).unwrap();
+        assert!(!evidence.is_consistent());
+
+        // the bad blocks are never sampled
+        state.bad_blocks.borrow_mut().mark(0);
+        let evidence =
+            Evidence::collect(&mut storage, "/dev/test", &task, &state, &nonce, 10).unwrap();
+        assert!(evidence.manifest.samples.iter().all(|s| s.offset >= 65536));
+
+        let path = std::env::temp_dir().join(format!("lethe-evidence-{}.zip", std::process::id()));
+        let digest_path = evidence.write(&path).unwrap();
+        let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut sectors = Vec::new();
+        archive
+            .by_name("sectors.bin")
+            .unwrap()
+            .read_to_end(&mut sectors)
+            .unwrap();
+        assert_eq!(sectors, evidence.data);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&digest_path).unwrap();
+    }
+}
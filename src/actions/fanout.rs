@@ -0,0 +1,70 @@
+use crate::actions::{WipeEvent, WipeEventReceiver, WipeState, WipeTask};
+
+/// Passes every event to all of the receivers in order, so that e.g. the terminal
+/// frontend and a log can observe the same wipe.
+#[derive(Default)]
+pub struct FanOutReceiver {
+    receivers: Vec<Box<dyn WipeEventReceiver>>,
+}
+
+impl FanOutReceiver {
+    pub fn new(receivers: Vec<Box<dyn WipeEventReceiver>>) -> Self {
+        FanOutReceiver { receivers }
+    }
+
+    pub fn with(mut self, receiver: Box<dyn WipeEventReceiver>) -> Self {
+        self.receivers.push(receiver);
+        self
+    }
+}
+
+impl WipeEventReceiver for FanOutReceiver {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        if let Some((last, others)) = self.receivers.split_last_mut() {
+            for receiver in others {
+                receiver.handle(task, state, event.clone());
+            }
+            last.handle(task, state, event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Recorder(Rc<RefCell<Vec<String>>>, &'static str);
+
+    impl WipeEventReceiver for Recorder {
+        fn handle(&mut self, _task: &WipeTask, _state: &WipeState, event: WipeEvent) {
+            self.0.borrow_mut().push(format!("{}: {:?}", self.1, event));
+        }
+    }
+
+    #[test]
+    fn test_all_receivers_get_every_event() {
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::No, 1000, 100).unwrap();
+        let state = WipeState::default();
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut receiver = FanOutReceiver::new(vec![Box::new(Recorder(log.clone(), "a"))])
+            .with(Box::new(Recorder(log.clone(), "b")));
+        receiver.handle(&task, &state, WipeEvent::Started);
+        receiver.handle(&task, &state, WipeEvent::Progress(100));
+
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "a: Started",
+                "b: Started",
+                "a: Progress(100)",
+                "b: Progress(100)"
+            ]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use crate::sanitization::{Scheme, Stage, StreamingIterator};
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Size of each temporary file, small enough for any filesystem (FAT32 files are limited
+/// to 4 GiB).
+pub const MAX_FILE_SIZE: u64 = 1 << 30;
+
+/// Smallest write tried once the filesystem is full, so the last clusters are filled too.
+const MIN_WRITE_SIZE: usize = 512;
+
+/// Overwrites the free space of a mounted filesystem, and with it the data of the deleted
+/// files, by filling temporary files with each stage of the scheme until the filesystem
+/// is full. The files are removed at the end.
+pub struct FreeSpaceTask {
+    pub scheme: Scheme,
+    /// Directory on the filesystem, the temporary files are created in a hidden
+    /// directory inside it.
+    pub directory: PathBuf,
+    pub block_size: usize,
+    /// Free space at the start, only used to show the progress.
+    pub free_space: Option<u64>,
+    /// Stops after writing this many bytes instead of filling the filesystem.
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum FreeSpaceEvent {
+    Started,
+    StageStarted(usize),
+    /// The stage can't be applied to files (like discarding).
+    StageSkipped(usize),
+    /// Bytes written in the current stage.
+    Progress(u64),
+    StageCompleted(u64),
+    Completed(Option<Rc<anyhow::Error>>),
+}
+
+pub trait FreeSpaceEventReceiver {
+    fn handle(&mut self, task: &FreeSpaceTask, event: FreeSpaceEvent);
+}
+
+impl FreeSpaceTask {
+    pub fn new<P: AsRef<Path>>(scheme: Scheme, directory: P, block_size: usize) -> Result<Self> {
+        let directory = directory.as_ref();
+        if !directory.is_dir() {
+            return Err(anyhow!(
+                "{} is not a directory",
+                directory.to_string_lossy()
+            ));
+        }
+        Ok(FreeSpaceTask {
+            scheme,
+            directory: directory.to_path_buf(),
+            block_size,
+            free_space: fs2::available_space(directory).ok(),
+            limit: None,
+        })
+    }
+
+    pub fn run(&self, frontend: &mut dyn FreeSpaceEventReceiver) -> bool {
+        frontend.handle(self, FreeSpaceEvent::Started);
+        let result = self.fill_stages(frontend).err().map(Rc::from);
+        let success = result.is_none();
+        frontend.handle(self, FreeSpaceEvent::Completed(result));
+        success
+    }
+
+    fn fill_stages(&self, frontend: &mut dyn FreeSpaceEventReceiver) -> Result<()> {
+        let dir = TempDir::create(&self.directory)?;
+        // declared after the directory so the files are closed before it's removed
+        let mut files: Vec<(File, u64)> = Vec::new();
+
+        for (i, stage) in self.scheme.stages.iter().enumerate() {
+            if let Stage::Discard = stage {
+                frontend.handle(self, FreeSpaceEvent::StageSkipped(i));
+                continue;
+            }
+
+            frontend.handle(self, FreeSpaceEvent::StageStarted(i));
+            let written = if files.is_empty() {
+                self.fill_new(&dir, stage, &mut files, frontend)?
+            } else {
+                self.rewrite(stage, &mut files, frontend)?
+            };
+            for (file, _) in &files {
+                file.sync_all()
+                    .context("Unable to flush the temporary files")?;
+            }
+            frontend.handle(self, FreeSpaceEvent::StageCompleted(written));
+        }
+
+        Ok(())
+    }
+
+    /// Creates the files and writes to them until the filesystem is full.
+    fn fill_new(
+        &self,
+        dir: &TempDir,
+        stage: &Stage,
+        files: &mut Vec<(File, u64)>,
+        frontend: &mut dyn FreeSpaceEventReceiver,
+    ) -> Result<u64> {
+        let limit = self.limit.unwrap_or(u64::MAX);
+        let mut stream = stage.stream(u64::MAX, self.block_size, self.block_size, 0);
+        let mut write_size = self.block_size;
+        let mut written = 0;
+
+        while written < limit {
+            let mut file = match dir.create_file(files.len()) {
+                Ok(f) => f,
+                Err(err) if is_full(&err) => break,
+                Err(err) => return Err(err).context("Unable to create a temporary file"),
+            };
+            let mut size = 0;
+            let mut full = false;
+
+            while size < MAX_FILE_SIZE && written < limit {
+                let chunk = match stream.next() {
+                    Some(c) => c,
+                    None => {
+                        full = true;
+                        break;
+                    }
+                };
+                let len = (chunk.len().min(write_size) as u64)
+                    .min(MAX_FILE_SIZE - size)
+                    .min(limit - written) as usize;
+                match file.write(&chunk[..len]) {
+                    Ok(0) => {
+                        full = true;
+                        break;
+                    }
+                    Ok(n) => {
+                        size += n as u64;
+                        written += n as u64;
+                        frontend.handle(self, FreeSpaceEvent::Progress(written));
+                    }
+                    Err(err) if err.kind() == ErrorKind::FileTooLarge => break,
+                    Err(err) if is_full(&err) => {
+                        if write_size <= MIN_WRITE_SIZE {
+                            full = true;
+                            break;
+                        }
+                        write_size /= 2;
+                    }
+                    Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(err) => return Err(err).context("Unable to write a temporary file"),
+                }
+            }
+
+            files.push((file, size));
+            if full {
+                break;
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Overwrites the files filled by the previous stages.
+    fn rewrite(
+        &self,
+        stage: &Stage,
+        files: &mut [(File, u64)],
+        frontend: &mut dyn FreeSpaceEventReceiver,
+    ) -> Result<u64> {
+        let mut stream = stage.stream(u64::MAX, self.block_size, self.block_size, 0);
+        let mut written = 0;
+
+        for (file, size) in files.iter_mut() {
+            file.seek(SeekFrom::Start(0))?;
+            let mut left = *size;
+            while left > 0 {
+                let chunk = match stream.next() {
+                    Some(c) => c,
+                    None => break,
+                };
+                let len = (chunk.len() as u64).min(left) as usize;
+                file.write_all(&chunk[..len])
+                    .context("Unable to write a temporary file")?;
+                left -= len as u64;
+                written += len as u64;
+                frontend.handle(self, FreeSpaceEvent::Progress(written));
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+fn is_full(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::StorageFull | ErrorKind::QuotaExceeded
+    )
+}
+
+/// Hidden directory holding the temporary files, removed with everything in it.
+struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    fn create(parent: &Path) -> Result<TempDir> {
+        let path = parent.join(format!(".lethe-free-{}", std::process::id()));
+        std::fs::create_dir(&path).context(format!(
+            "Unable to create a temporary directory in {}",
+            parent.to_string_lossy()
+        ))?;
+        Ok(TempDir { path })
+    }
+
+    /// The file is unlinked right away, so the space is given back even if the process
+    /// is killed.
+    #[cfg(unix)]
+    fn create_file(&self, index: usize) -> std::io::Result<File> {
+        let path = self.path.join(format!("{}.tmp", index));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        std::fs::remove_file(&path)?;
+        Ok(file)
+    }
+
+    /// The file is deleted by the system once closed, even if the process is killed.
+    #[cfg(windows)]
+    fn create_file(&self, index: usize) -> std::io::Result<File> {
+        use std::os::windows::fs::OpenOptionsExt;
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .custom_flags(winapi::um::winbase::FILE_FLAG_DELETE_ON_CLOSE)
+            .open(self.path.join(format!("{}.tmp", index)))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        stages: Vec<u64>,
+        skipped: usize,
+        error: Option<String>,
+    }
+
+    impl FreeSpaceEventReceiver for Recorder {
+        fn handle(&mut self, _task: &FreeSpaceTask, event: FreeSpaceEvent) {
+            match event {
+                FreeSpaceEvent::StageCompleted(written) => self.stages.push(written),
+                FreeSpaceEvent::StageSkipped(_) => self.skipped += 1,
+                FreeSpaceEvent::Completed(result) => self.error = result.map(|e| e.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_free_space_is_filled_and_released() {
+        let dir = std::env::temp_dir().join(format!("lethe-free-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let scheme = Scheme {
+            description: "test".to_string(),
+            stages: vec![Stage::random(), Stage::discard(), Stage::zero()],
+        };
+        let mut task = FreeSpaceTask::new(scheme, &dir, 4096).unwrap();
+        task.limit = Some(10000);
+
+        let mut recorder = Recorder::default();
+        assert!(task.run(&mut recorder));
+        let left = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(recorder.error, None);
+        assert_eq!(recorder.stages, vec![10000, 10000]);
+        assert_eq!(recorder.skipped, 1);
+        assert_eq!(left, 0);
+
+        assert!(FreeSpaceTask::new(task.scheme.clone(), &dir, 4096).is_err());
+    }
+}
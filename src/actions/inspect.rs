@@ -0,0 +1,234 @@
+//! A read-only look at a device before it's wiped, to make sure it's the right one.
+
+use crate::actions::entropy;
+use crate::actions::probe::{self, Signature};
+use crate::storage::StorageAccess;
+use anyhow::Result;
+use std::fmt::{Display, Formatter};
+
+pub const SECTOR_SIZE: usize = 512;
+
+pub const DEFAULT_SECTORS: usize = 1;
+
+/// Number of blocks read across the device to tell whether it looks blank.
+const SAMPLES: u64 = 64;
+
+/// Large enough to tell random data from a pattern, see `entropy::MIN_CHECKED_SIZE`.
+const SAMPLE_SIZE: usize = 4096;
+
+const BYTES_PER_LINE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Content {
+    Zeroes,
+    Ones,
+    Random,
+    Data,
+}
+
+impl Display for Content {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Content::Zeroes => f.write_str("all zeroes"),
+            Content::Ones => f.write_str("all ones (0xff)"),
+            Content::Random => f.write_str("random data"),
+            Content::Data => f.write_str("data"),
+        }
+    }
+}
+
+impl Content {
+    fn of(data: &[u8]) -> Content {
+        if data.iter().all(|b| *b == 0) {
+            Content::Zeroes
+        } else if data.iter().all(|b| *b == 0xff) {
+            Content::Ones
+        } else if data.len() >= entropy::MIN_CHECKED_SIZE && entropy::looks_random(data) {
+            Content::Random
+        } else {
+            Content::Data
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Inspection {
+    pub head: Vec<u8>,
+    pub tail_offset: u64,
+    pub tail: Vec<u8>,
+    pub signatures: Vec<Signature>,
+    /// Content of all the sampled blocks, `Data` unless they are alike.
+    pub content: Content,
+    pub samples: usize,
+}
+
+impl Inspection {
+    /// No partition tables or filesystems, and the same filler everywhere it was sampled,
+    /// like after a wipe.
+    pub fn looks_blank(&self) -> bool {
+        self.signatures.is_empty() && self.content != Content::Data
+    }
+}
+
+/// Reads the first and the last `sectors` sectors, probes for known structures and samples
+/// blocks evenly spread across the device. Nothing is written.
+pub fn inspect(
+    access: &mut dyn StorageAccess,
+    total_size: u64,
+    sectors: usize,
+) -> Result<Inspection> {
+    let length = (sectors * SECTOR_SIZE).min(total_size as usize);
+    let head = probe::read_at(access, total_size, 0, length)?;
+    let tail_offset = total_size - length as u64;
+    let tail = probe::read_at(access, total_size, tail_offset, length)?;
+
+    let signatures = probe::probe(access, total_size)?;
+
+    let positions: Vec<u64> = (0..SAMPLES)
+        .map(|i| (total_size / SAMPLES * i) / SAMPLE_SIZE as u64 * SAMPLE_SIZE as u64)
+        .filter(|p| p + SAMPLE_SIZE as u64 <= total_size)
+        .fold(Vec::new(), |mut positions, p| {
+            if positions.last() != Some(&p) {
+                positions.push(p);
+            }
+            positions
+        });
+    let mut content = None;
+    for p in &positions {
+        let sample = Content::of(&probe::read_at(access, total_size, *p, SAMPLE_SIZE)?);
+        content = match content {
+            None => Some(sample),
+            Some(c) if c == sample => Some(c),
+            Some(_) => Some(Content::Data),
+        };
+    }
+
+    Ok(Inspection {
+        samples: positions.len(),
+        // devices smaller than a sample are judged by their first sectors
+        content: content.unwrap_or_else(|| Content::of(&head)),
+        head,
+        tail_offset,
+        tail,
+        signatures,
+    })
+}
+
+/// Lines of a canonical hex dump (like `hexdump -C`), repeated lines are collapsed into `*`.
+pub fn hexdump(data: &[u8], offset: u64) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut previous: Option<&[u8]> = None;
+    let mut collapsed = false;
+
+    for (i, line) in data.chunks(BYTES_PER_LINE).enumerate() {
+        if previous == Some(line) && line.len() == BYTES_PER_LINE {
+            if !collapsed {
+                lines.push("*".to_string());
+                collapsed = true;
+            }
+            continue;
+        }
+        previous = Some(line);
+        collapsed = false;
+
+        let bytes: Vec<String> = (0..BYTES_PER_LINE)
+            .map(|j| match line.get(j) {
+                Some(b) => format!("{:02x}", b),
+                None => "  ".to_string(),
+            })
+            .collect();
+        let text: String = line
+            .iter()
+            .map(|b| match b {
+                0x20..=0x7e => *b as char,
+                _ => '.',
+            })
+            .collect();
+        lines.push(format!(
+            "{:08x}  {}  {}  |{}|",
+            offset + (i * BYTES_PER_LINE) as u64,
+            bytes[..8].join(" "),
+            bytes[8..].join(" "),
+            text
+        ));
+    }
+    if collapsed {
+        lines.push(format!("{:08x}", offset + data.len() as u64));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::memory::InMemoryStorage;
+    use rand::RngCore;
+
+    fn inspect_data(data: Vec<u8>, sectors: usize) -> Inspection {
+        let size = data.len() as u64;
+        inspect(&mut InMemoryStorage::with_data(data), size, sectors).unwrap()
+    }
+
+    #[test]
+    fn test_inspect() {
+        let size = 1 << 20;
+
+        let zeroes = inspect_data(vec![0; size], 2);
+        assert_eq!(zeroes.head.len(), 1024);
+        assert_eq!(zeroes.tail_offset, size as u64 - 1024);
+        assert_eq!(zeroes.content, Content::Zeroes);
+        assert_eq!(zeroes.samples, 64);
+        assert!(zeroes.looks_blank());
+
+        let mut random = vec![0; size];
+        rand::thread_rng().fill_bytes(&mut random);
+        assert_eq!(inspect_data(random.clone(), 1).content, Content::Random);
+
+        // a partition table on an otherwise wiped device
+        let mut partitioned = random;
+        partitioned[510] = 0x55;
+        partitioned[511] = 0xaa;
+        let inspection = inspect_data(partitioned, 1);
+        assert_eq!(inspection.head[510..], [0x55, 0xaa]);
+        assert_eq!(inspection.signatures.len(), 1);
+        assert!(!inspection.looks_blank());
+
+        let mut used = vec![0xff; size];
+        used[size / 2..].iter_mut().for_each(|b| *b = 0);
+        let inspection = inspect_data(used, 1);
+        assert_eq!(inspection.content, Content::Data);
+        assert!(!inspection.looks_blank());
+        assert_eq!(inspection.head, vec![0xff; 512]);
+        assert_eq!(inspection.tail, vec![0; 512]);
+
+        let tiny = inspect_data(b"lethe".to_vec(), 1);
+        assert_eq!(tiny.samples, 0);
+        assert_eq!(tiny.head, b"lethe");
+        assert_eq!(tiny.content, Content::Data);
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let mut data = b"LETHE wipe label".to_vec();
+        data.extend_from_slice(&[0; 48]);
+        data.extend_from_slice(&[0x55, 0xaa]);
+
+        assert_eq!(
+            hexdump(&data, 0x1000),
+            vec![
+                "00001000  4c 45 54 48 45 20 77 69  70 65 20 6c 61 62 65 6c  |LETHE wipe label|",
+                "00001010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|",
+                "*",
+                "00001040  55 aa                                             |U.|",
+            ]
+        );
+        assert_eq!(
+            hexdump(&[0; 64], 0),
+            vec![
+                "00000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|",
+                "*",
+                "00000040",
+            ]
+        );
+    }
+}
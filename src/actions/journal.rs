@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Checksum of a verified block, so it can be read again and compared.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BlockChecksum {
+    pub position: u64,
+    pub length: usize,
+    pub sha256: [u8; 32],
+}
+
+/// Progress of the verification of a stage, saved with the checkpoints so an interrupted
+/// verification continues where it stopped instead of reading the stage again.
+///
+/// The blocks read back are chained into a rolling hash, which ends up the same whether
+/// the verification was interrupted or not. The checksum of the last block is kept to make
+/// sure the device still holds the same data when the verification is resumed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VerificationJournal {
+    /// Position the verification of the stage started from, it starts over from here when
+    /// the journal doesn't match the device.
+    pub start: u64,
+    pub blocks: u64,
+    pub hash: [u8; 32],
+    pub last_block: Option<BlockChecksum>,
+}
+
+impl VerificationJournal {
+    pub fn new(start: u64) -> Self {
+        VerificationJournal {
+            start,
+            blocks: 0,
+            hash: [0; 32],
+            last_block: None,
+        }
+    }
+
+    pub fn record(&mut self, position: u64, data: &[u8]) {
+        let block = BlockChecksum {
+            position,
+            length: data.len(),
+            sha256: Sha256::digest(data).into(),
+        };
+        self.hash = Sha256::new()
+            .chain(self.hash)
+            .chain(position.to_le_bytes())
+            .chain(block.sha256)
+            .finalize()
+            .into();
+        self.blocks += 1;
+        self.last_block = Some(block);
+    }
+
+    /// Whether the data read from the last recorded block is still the same.
+    pub fn matches_last_block(&self, data: &[u8]) -> bool {
+        match &self.last_block {
+            Some(b) => data.len() == b.length && Sha256::digest(data)[..] == b.sha256[..],
+            None => true,
+        }
+    }
+
+    pub fn hash_hex(&self) -> String {
+        self.hash.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl Default for VerificationJournal {
+    fn default() -> Self {
+        VerificationJournal::new(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_journal_rolling_hash() {
+        let blocks: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i; 512]).collect();
+
+        let mut whole = VerificationJournal::new(0);
+        for (i, b) in blocks.iter().enumerate() {
+            whole.record(i as u64 * 512, b);
+        }
+
+        let mut first = VerificationJournal::new(0);
+        first.record(0, &blocks[0]);
+        first.record(512, &blocks[1]);
+        let saved = serde_json::to_string(&first).unwrap();
+        let mut resumed: VerificationJournal = serde_json::from_str(&saved).unwrap();
+        assert!(resumed.matches_last_block(&blocks[1]));
+        assert!(!resumed.matches_last_block(&blocks[2]));
+        assert!(!resumed.matches_last_block(&blocks[1][..256]));
+        resumed.record(1024, &blocks[2]);
+        resumed.record(1536, &blocks[3]);
+
+        assert_eq!(resumed, whole);
+        assert_eq!(whole.blocks, 4);
+        assert_eq!(whole.hash_hex().len(), 64);
+
+        // the same data at other positions gives another hash
+        let mut shifted = VerificationJournal::new(0);
+        for (i, b) in blocks.iter().enumerate() {
+            shifted.record(i as u64 * 512 + 512, b);
+        }
+        assert_ne!(shifted.hash, whole.hash);
+        assert!(VerificationJournal::default().matches_last_block(&blocks[0]));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::sanitization::mem::AlignedBuffer;
+use crate::storage::StorageAccess;
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac, NewMac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::convert::TryInto;
+
+/// Size of the label, a single block aligned for direct access on any device.
+pub const LABEL_SIZE: usize = 4096;
+
+const MAGIC: &[u8; 16] = b"LETHE-WIPE-LABEL";
+const HEADER_SIZE: usize = MAGIC.len() + 4;
+const MAC_SIZE: usize = 32;
+
+/// Partition tables written by post-wipe actions are kept within this distance from
+/// either end of the device.
+const PARTITION_TABLE_AREA: u64 = 1 << 20;
+
+type LabelMac = Hmac<Sha256>;
+
+/// A record of the wipe left on the device, written only on request since it's data
+/// written after the wipe.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WipeLabel {
+    pub tool: String,
+    /// RFC 3339 timestamp of the completion.
+    pub wiped_at: String,
+    pub scheme: String,
+    pub verify: String,
+    pub operator: Option<String>,
+    /// The label is signed with a key (HMAC-SHA256), otherwise the MAC is computed with
+    /// an empty key and only guards against corruption.
+    pub keyed: bool,
+}
+
+/// Outcome of checking the signature of a label read from a device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelSignature {
+    Valid,
+    Invalid,
+    /// The label is keyed, but no key was given to check it.
+    Unchecked,
+}
+
+impl WipeLabel {
+    /// Layout: magic, JSON length (u32, little-endian), JSON, MAC of the JSON, zero padding.
+    pub fn encode(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(self)?;
+        if HEADER_SIZE + json.len() + MAC_SIZE > LABEL_SIZE {
+            return Err(anyhow!("The label doesn't fit into {} bytes", LABEL_SIZE));
+        }
+
+        let mut data = Vec::with_capacity(LABEL_SIZE);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        data.extend_from_slice(&json);
+        data.extend_from_slice(&mac(key, &json));
+        data.resize(LABEL_SIZE, 0);
+        Ok(data)
+    }
+
+    /// Returns `None` if the data doesn't start with a label.
+    pub fn decode(data: &[u8], key: Option<&[u8]>) -> Result<Option<(WipeLabel, LabelSignature)>> {
+        if data.len() < HEADER_SIZE || &data[..MAGIC.len()] != MAGIC {
+            return Ok(None);
+        }
+        let length = u32::from_le_bytes(data[MAGIC.len()..HEADER_SIZE].try_into().unwrap());
+        let json_end = HEADER_SIZE + length as usize;
+        if json_end + MAC_SIZE > data.len() {
+            return Err(anyhow!("The label is truncated"));
+        }
+
+        let json = &data[HEADER_SIZE..json_end];
+        let label: WipeLabel = serde_json::from_slice(json).context("The label is corrupted")?;
+        let key = match (label.keyed, key) {
+            (true, None) => return Ok(Some((label, LabelSignature::Unchecked))),
+            (true, Some(k)) => k,
+            (false, _) => &[],
+        };
+        let signature = if mac(key, json)[..] == data[json_end..json_end + MAC_SIZE] {
+            LabelSignature::Valid
+        } else {
+            LabelSignature::Invalid
+        };
+        Ok(Some((label, signature)))
+    }
+
+    pub fn write(&self, access: &mut dyn StorageAccess, offset: u64, key: &[u8]) -> Result<()> {
+        let data = self.encode(key)?;
+        let mut buf = AlignedBuffer::new(LABEL_SIZE, LABEL_SIZE);
+        buf.as_mut_slice().copy_from_slice(&data);
+        access.seek(offset)?;
+        access
+            .write(buf.as_mut_slice())
+            .context(format!("Unable to write the label at {}", offset))?;
+        access.flush()
+    }
+
+    pub fn read(
+        access: &mut dyn StorageAccess,
+        offset: u64,
+        key: Option<&[u8]>,
+    ) -> Result<Option<(WipeLabel, LabelSignature)>> {
+        let mut buf = AlignedBuffer::new(LABEL_SIZE, LABEL_SIZE);
+        let data = buf.as_mut_slice();
+        access.seek(offset)?;
+        let mut filled = 0;
+        while filled < LABEL_SIZE {
+            match access.read(&mut data[filled..])? {
+                0 => return Err(anyhow!("Unexpected end of device at {}", offset)),
+                n => filled += n,
+            }
+        }
+        WipeLabel::decode(data, key)
+    }
+}
+
+/// Checks that a label fits on the device at the given offset.
+pub fn check_offset(offset: u64, total_size: u64) -> Result<()> {
+    if !offset.is_multiple_of(LABEL_SIZE as u64) {
+        return Err(anyhow!(
+            "The label offset must be a multiple of {}",
+            LABEL_SIZE
+        ));
+    }
+    if offset.saturating_add(LABEL_SIZE as u64) > total_size {
+        return Err(anyhow!("The label offset is past the end of the device"));
+    }
+    Ok(())
+}
+
+/// The label would be overwritten by an empty partition table written after the wipe.
+pub fn overlaps_partition_table(offset: u64, total_size: u64) -> bool {
+    offset < PARTITION_TABLE_AREA
+        || offset + LABEL_SIZE as u64 > total_size.saturating_sub(PARTITION_TABLE_AREA)
+}
+
+fn mac(key: &[u8], data: &[u8]) -> [u8; MAC_SIZE] {
+    let mut mac = LabelMac::new_varkey(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn label(keyed: bool) -> WipeLabel {
+        WipeLabel {
+            tool: "lethe 0.5.2".to_string(),
+            wiped_at: "2024-05-01T12:00:00Z".to_string(),
+            scheme: "dod".to_string(),
+            verify: "last".to_string(),
+            operator: Some("Jane Doe".to_string()),
+            keyed,
+        }
+    }
+
+    #[test]
+    fn test_label_roundtrip() {
+        let data = label(true).encode(b"secret").unwrap();
+        assert_eq!(data.len(), LABEL_SIZE);
+        assert_eq!(
+            WipeLabel::decode(&data, Some(b"secret")).unwrap(),
+            Some((label(true), LabelSignature::Valid))
+        );
+        assert_eq!(
+            WipeLabel::decode(&data, Some(b"other")).unwrap(),
+            Some((label(true), LabelSignature::Invalid))
+        );
+        assert_eq!(
+            WipeLabel::decode(&data, None).unwrap(),
+            Some((label(true), LabelSignature::Unchecked))
+        );
+
+        let mut data = label(false).encode(&[]).unwrap();
+        assert_eq!(
+            WipeLabel::decode(&data, None).unwrap(),
+            Some((label(false), LabelSignature::Valid))
+        );
+        let length = u32::from_le_bytes(data[16..20].try_into().unwrap()) as usize;
+        data[HEADER_SIZE + length] ^= 0xff;
+        assert_eq!(
+            WipeLabel::decode(&data, None).unwrap().map(|(_, s)| s),
+            Some(LabelSignature::Invalid)
+        );
+
+        assert_eq!(WipeLabel::decode(&[0u8; LABEL_SIZE], None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_label_offset() {
+        assert!(check_offset(0, 1 << 20).is_ok());
+        assert!(check_offset(100, 1 << 20).is_err());
+        assert!(check_offset(1 << 20, 1 << 20).is_err());
+
+        assert!(overlaps_partition_table(0, 16 << 20));
+        assert!(!overlaps_partition_table(1 << 20, 16 << 20));
+        assert!(overlaps_partition_table((16 << 20) - 4096, 16 << 20));
+    }
+}
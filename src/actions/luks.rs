@@ -0,0 +1,208 @@
+use crate::actions::probe::{probe, Signature, SignatureKind};
+use crate::actions::WipeRange;
+use crate::sanitization::mem::AlignedBuffer;
+use crate::storage::StorageAccess;
+use anyhow::Result;
+use serde::Serialize;
+
+const IO_ALIGNMENT: u64 = 4096;
+const LUKS_MAGIC: &[u8] = b"LUKS\xba\xbe";
+const LUKS2_SECONDARY_MAGIC: &[u8] = b"SKUL\xba\xbe";
+
+/// Offsets (relative to the volume start) where LUKS2 can keep the secondary header copy,
+/// one for each of the allowed header sizes.
+const LUKS2_SECONDARY_OFFSETS: [u64; 9] = [
+    0x4000, 0x8000, 0x10000, 0x20000, 0x40000, 0x80000, 0x100000, 0x200000, 0x400000,
+];
+
+/// Outcome of erasing the LUKS headers of the wiped device.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct LuksErasure {
+    /// Offsets of the LUKS volumes whose headers were erased.
+    pub volumes: Vec<u64>,
+    /// Offsets where a LUKS header magic is still present after the erasure.
+    pub remaining_headers: Vec<u64>,
+    /// Device mapper names of the closed crypt mappings.
+    pub closed_mappings: Vec<String>,
+}
+
+impl LuksErasure {
+    /// Without any header copy the volume key can't be recovered, even with the passphrase.
+    pub fn is_unrecoverable(&self) -> bool {
+        !self.volumes.is_empty() && self.remaining_headers.is_empty()
+    }
+}
+
+/// LUKS volumes on the device itself or inside its partitions.
+pub fn find_volumes(access: &mut dyn StorageAccess, total_size: u64) -> Result<Vec<Signature>> {
+    Ok(probe(access, total_size)?
+        .into_iter()
+        .filter(|s| s.kind == SignatureKind::Luks)
+        .collect())
+}
+
+/// Overwrites both header copies and the keyslots area of the volumes with zeroes
+/// and checks that no header magic is left behind.
+pub fn erase_headers(
+    access: &mut dyn StorageAccess,
+    total_size: u64,
+    volumes: &[Signature],
+) -> Result<LuksErasure> {
+    let ranges = volumes
+        .iter()
+        .flat_map(|v| {
+            v.regions.iter().cloned().chain(
+                LUKS2_SECONDARY_OFFSETS
+                    .iter()
+                    .map(move |o| WipeRange::new(v.offset + o, v.offset + o + IO_ALIGNMENT)),
+            )
+        })
+        .filter(|r| r.start < total_size)
+        .map(|r| r.expand_to_blocks(IO_ALIGNMENT as usize, total_size))
+        .collect();
+
+    let mut buf = AlignedBuffer::new(IO_ALIGNMENT as usize, IO_ALIGNMENT as usize);
+    buf.fill(0);
+    for range in WipeRange::normalize(ranges, total_size, IO_ALIGNMENT as usize)? {
+        access.seek(range.start)?;
+        let mut position = range.start;
+        while position < range.end {
+            let len = (range.end - position).min(IO_ALIGNMENT) as usize;
+            access.write(&buf.as_mut_slice()[..len])?;
+            position += len as u64;
+        }
+    }
+    access.flush()?;
+
+    let mut remaining_headers = Vec::new();
+    for v in volumes {
+        if has_magic(access, total_size, v.offset, LUKS_MAGIC)? {
+            remaining_headers.push(v.offset);
+        }
+        for o in LUKS2_SECONDARY_OFFSETS.iter().map(|o| v.offset + o) {
+            if has_magic(access, total_size, o, LUKS2_SECONDARY_MAGIC)? {
+                remaining_headers.push(o);
+            }
+        }
+    }
+
+    Ok(LuksErasure {
+        volumes: volumes.iter().map(|v| v.offset).collect(),
+        remaining_headers,
+        closed_mappings: Vec::new(),
+    })
+}
+
+fn has_magic(
+    access: &mut dyn StorageAccess,
+    total_size: u64,
+    offset: u64,
+    magic: &[u8],
+) -> Result<bool> {
+    if offset + magic.len() as u64 > total_size {
+        return Ok(false);
+    }
+
+    let aligned_start = offset - offset % IO_ALIGNMENT;
+    let aligned_end = ((offset + magic.len() as u64).div_ceil(IO_ALIGNMENT) * IO_ALIGNMENT)
+        .min(total_size.div_ceil(IO_ALIGNMENT) * IO_ALIGNMENT);
+    let mut buf = AlignedBuffer::new(
+        (aligned_end - aligned_start) as usize,
+        IO_ALIGNMENT as usize,
+    );
+    let data = buf.as_mut_slice();
+
+    access.seek(aligned_start)?;
+    let mut filled = 0;
+    while filled < data.len() {
+        match access.read(&mut data[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+
+    let skip = (offset - aligned_start) as usize;
+    Ok(filled >= skip + magic.len() && &data[skip..skip + magic.len()] == magic)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::memory::InMemoryStorage;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    #[test]
+    fn test_erase_luks2_headers() {
+        let size = 32 * MB;
+        let mut data = vec![0xaau8; size as usize];
+        // LUKS2 with 16k headers, the secondary copy follows the primary one
+        put(&mut data, 0, LUKS_MAGIC);
+        put(&mut data, 6, &2u16.to_be_bytes());
+        put(&mut data, 8, &0x4000u64.to_be_bytes());
+        put(&mut data, 4096, &[0u8; 0x3000]);
+        put(&mut data, 4096, br#"{"config":{"keyslots_size":"65536"}}"#);
+        put(&mut data, 0x4000, LUKS2_SECONDARY_MAGIC);
+
+        let mut storage = InMemoryStorage::with_data(data);
+        let volumes = find_volumes(&mut storage, size).unwrap();
+        assert_eq!(volumes.len(), 1);
+
+        let erasure = erase_headers(&mut storage, size, &volumes).unwrap();
+        assert_eq!(erasure.volumes, vec![0]);
+        assert!(erasure.remaining_headers.is_empty());
+        assert!(erasure.is_unrecoverable());
+
+        let data = storage.file.into_inner();
+        // both headers and the keyslots area are zeroed, the data after them is untouched
+        assert!(data[..2 * 0x4000 + 65536].iter().all(|b| *b == 0));
+        assert_eq!(data[2 * 0x4000 + 65536], 0xaa);
+        assert_eq!(data[5 * MB as usize], 0xaa);
+    }
+
+    #[test]
+    fn test_remaining_header_is_reported() {
+        struct ReadOnly(InMemoryStorage);
+
+        impl StorageAccess for ReadOnly {
+            fn position(&mut self) -> Result<u64> {
+                self.0.position()
+            }
+
+            fn seek(&mut self, position: u64) -> Result<u64> {
+                self.0.seek(position)
+            }
+
+            fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+                self.0.read(buffer)
+            }
+
+            fn write(&mut self, data: &[u8]) -> Result<()> {
+                // the device silently drops the writes
+                let position = self.0.position()?;
+                self.0.seek(position + data.len() as u64)?;
+                Ok(())
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let size = 8 * MB;
+        let mut data = vec![0u8; size as usize];
+        put(&mut data, 0, LUKS_MAGIC);
+        put(&mut data, 6, &1u16.to_be_bytes());
+
+        let mut storage = ReadOnly(InMemoryStorage::with_data(data));
+        let volumes = find_volumes(&mut storage, size).unwrap();
+        let erasure = erase_headers(&mut storage, size, &volumes).unwrap();
+
+        assert_eq!(erasure.remaining_headers, vec![0]);
+        assert!(!erasure.is_unrecoverable());
+    }
+}
@@ -5,6 +5,7 @@ pub trait BlockMarker {
     fn mark(&mut self, position: u32);
     fn is_marked(&self, position: u32) -> bool;
     fn total_marked(&self) -> u32;
+    fn all_marked(&self) -> Vec<u32>;
 }
 
 impl Debug for dyn BlockMarker {
@@ -37,6 +38,10 @@ impl BlockMarker for RoaringBlockMarker {
     fn total_marked(&self) -> u32 {
         self.store.len() as u32
     }
+
+    fn all_marked(&self) -> Vec<u32> {
+        self.store.iter().collect()
+    }
 }
 
 #[cfg(test)]
@@ -70,6 +75,8 @@ mod test {
         assert_eq!(2, marker.total_marked());
         assert!(marker.is_marked(13));
         assert!(marker.is_marked(133));
+
+        assert_eq!(vec![13, 133], marker.all_marked());
     }
 
     #[test]
@@ -82,9 +89,9 @@ mod test {
         assert!(marker.is_marked(0));
         assert!(!marker.is_marked(1));
 
-        marker.mark(u32::max_value());
+        marker.mark(u32::MAX);
         assert_eq!(2, marker.total_marked());
         assert!(marker.is_marked(0));
-        assert!(marker.is_marked(u32::max_value()));
+        assert!(marker.is_marked(u32::MAX));
     }
 }
@@ -0,0 +1,501 @@
+//! Writing a single sanitization stream to several devices at once, e.g. a batch of the same
+//! drives, so that the data is generated only once.
+
+use crate::sanitization::mem::AlignedBuffer;
+use crate::storage::{Geometry, StorageAccess};
+use anyhow::{Context, Result};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+const IO_ALIGNMENT: usize = 4096;
+
+type Job = Box<dyn FnOnce(&mut dyn StorageAccess) + Send>;
+
+/// One of the devices the stream is mirrored to. The device is opened and accessed by
+/// a thread of its own, so that all the devices are written at the same time.
+pub struct MirrorTarget {
+    pub device_id: String,
+    /// Bytes written to the device so far.
+    pub written: u64,
+    /// Positions of the blocks the device failed to write or read back.
+    pub bad_blocks: Vec<u64>,
+    /// Positions of the blocks read back from the device which differ from the written data.
+    pub mismatched_blocks: Vec<u64>,
+    /// Why the device was dropped from the mirror, the rest of the wipe skips it.
+    pub error: Option<anyhow::Error>,
+    worker: Option<Worker>,
+}
+
+impl MirrorTarget {
+    /// Opens the device on the thread of the target, fails if it can't be opened.
+    pub fn open<F>(device_id: &str, open: F) -> Result<Self>
+    where
+        F: FnOnce() -> Result<Box<dyn StorageAccess>> + Send + 'static,
+    {
+        Ok(MirrorTarget {
+            device_id: device_id.to_string(),
+            written: 0,
+            bad_blocks: Vec::new(),
+            mismatched_blocks: Vec::new(),
+            error: None,
+            worker: Some(Worker::start(open)?),
+        })
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    fn drop_with(&mut self, error: anyhow::Error) {
+        self.worker = None;
+        self.error = Some(error.context(format!("Dropped {} from the mirror", self.device_id)));
+    }
+}
+
+/// The thread running the operations on one of the devices, in the order they are sent.
+struct Worker {
+    jobs: Option<Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    fn start<F>(open: F) -> Result<Self>
+    where
+        F: FnOnce() -> Result<Box<dyn StorageAccess>> + Send + 'static,
+    {
+        let (jobs, received) = channel::<Job>();
+        let (opened, open_result) = channel();
+        let thread = std::thread::spawn(move || {
+            let mut access = match open() {
+                Ok(a) => {
+                    let _ = opened.send(Ok(()));
+                    a
+                }
+                Err(err) => {
+                    let _ = opened.send(Err(err));
+                    return;
+                }
+            };
+            for job in received {
+                job(access.as_mut());
+            }
+        });
+        open_result
+            .recv()
+            .unwrap_or_else(|_| Err(anyhow!("Unable to open the device")))?;
+        Ok(Worker {
+            jobs: Some(jobs),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // the thread stops once it runs out of jobs
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Passes every operation to all the devices which didn't fail yet, in parallel. A device
+/// failing to seek or flush is dropped and the wipe goes on with the others, it only sees
+/// an error once all of them failed. A block the device fails to write (or read back) is
+/// recorded as bad for that device alone, the others still get it.
+///
+/// The data read back for verification is compared with the written one for each device,
+/// the devices it differs on have the block recorded as mismatched. The verification only
+/// sees a mismatch found on all of them.
+pub struct MirrorAccess {
+    targets: Vec<MirrorTarget>,
+    position: u64,
+}
+
+impl MirrorAccess {
+    pub fn new(targets: Vec<MirrorTarget>) -> Result<Self> {
+        if targets.is_empty() {
+            return Err(anyhow!("Nothing to mirror to"));
+        }
+        Ok(MirrorAccess {
+            targets,
+            position: 0,
+        })
+    }
+
+    pub fn targets(&self) -> &[MirrorTarget] {
+        &self.targets
+    }
+
+    pub fn into_targets(self) -> Vec<MirrorTarget> {
+        self.targets
+    }
+
+    /// Runs the operation on all the live devices at once and waits for them to finish.
+    /// The results are in the order of the devices, the ones which stopped responding
+    /// are dropped.
+    fn run_all<T, F>(&mut self, op: F) -> Vec<(usize, Result<T>)>
+    where
+        T: Send + 'static,
+        F: Fn(&mut dyn StorageAccess) -> Result<T> + Send + Sync + 'static,
+    {
+        let op = Arc::new(op);
+        let (done, results) = channel();
+        for (i, target) in self.targets.iter().enumerate() {
+            if let Some(jobs) = target.worker.as_ref().and_then(|w| w.jobs.as_ref()) {
+                let op = op.clone();
+                let done = done.clone();
+                let _ = jobs.send(Box::new(move |access| {
+                    let _ = done.send((i, op(access)));
+                }));
+            }
+        }
+        drop(done);
+
+        let mut results: Vec<_> = results.iter().collect();
+        results.sort_by_key(|(i, _)| *i);
+        for (i, target) in self.targets.iter_mut().enumerate() {
+            if target.is_live() && !results.iter().any(|(r, _)| *r == i) {
+                target.drop_with(anyhow!("The device stopped responding"));
+            }
+        }
+        results
+    }
+
+    /// Runs the operation on all the live devices, dropping the ones it fails on. Fails with
+    /// the last error if no devices are left.
+    fn each<T, F>(&mut self, op: F) -> Result<Vec<(usize, T)>>
+    where
+        T: Send + 'static,
+        F: Fn(&mut dyn StorageAccess) -> Result<T> + Send + Sync + 'static,
+    {
+        let mut succeeded = Vec::new();
+        let mut last_error = None;
+        for (i, result) in self.run_all(op) {
+            match result {
+                Ok(r) => succeeded.push((i, r)),
+                Err(err) => {
+                    last_error = Some(format!("{:#}", err));
+                    self.targets[i].drop_with(err);
+                }
+            }
+        }
+        match (succeeded.is_empty(), last_error) {
+            (true, Some(err)) => Err(anyhow!("All the mirrored devices failed. {}", err)),
+            (true, None) => Err(anyhow!("All the mirrored devices failed")),
+            _ => Ok(succeeded),
+        }
+    }
+
+    /// Reads the block at the current position from each live device. A device failing
+    /// the read has the block recorded as bad, the error is returned only if all of them
+    /// failed.
+    fn read_each(&mut self, length: usize) -> Result<Vec<(usize, AlignedBuffer)>> {
+        let position = self.position;
+        let reads = self.each(move |a| {
+            let mut buf = AlignedBuffer::new(length, IO_ALIGNMENT);
+            let read = a.read(buf.as_mut_slice()).map(|r| r == length);
+            // the next block is read from the same place on all the devices
+            a.seek(position + length as u64)?;
+            Ok(match read {
+                Ok(true) => Ok(buf),
+                Ok(false) => Err(anyhow!("Unexpected end of the device")),
+                Err(err) => Err(err),
+            })
+        })?;
+        self.position += length as u64;
+
+        let mut succeeded = Vec::new();
+        let mut last_error = None;
+        for (i, read) in reads {
+            match read {
+                Ok(buf) => succeeded.push((i, buf)),
+                Err(err) => {
+                    self.targets[i].bad_blocks.push(position);
+                    last_error = Some(err);
+                }
+            }
+        }
+        match last_error {
+            Some(err) if succeeded.is_empty() => {
+                Err(err.context("All the mirrored devices failed"))
+            }
+            _ => Ok(succeeded),
+        }
+    }
+}
+
+impl StorageAccess for MirrorAccess {
+    fn position(&mut self) -> Result<u64> {
+        Ok(self.position)
+    }
+
+    fn seek(&mut self, position: u64) -> Result<u64> {
+        let positions = self.each(move |a| a.seek(position))?;
+        self.position = positions[0].1;
+        Ok(self.position)
+    }
+
+    /// Reads the data most of the devices agree on, without the data that was written the
+    /// devices can't be told apart otherwise.
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        let reads = self.read_each(buffer.len())?;
+
+        let agreeing = |data: &[u8]| reads.iter().filter(|(_, d)| d.as_slice() == data).count();
+        let (_, reference) = reads
+            .iter()
+            .fold(None, |best: Option<(usize, &[u8])>, (_, data)| {
+                let votes = agreeing(data.as_slice());
+                match best {
+                    Some((v, _)) if v >= votes => best,
+                    _ => Some((votes, data.as_slice())),
+                }
+            })
+            .unwrap();
+        buffer.copy_from_slice(reference);
+        Ok(buffer.len())
+    }
+
+    fn read_back(&mut self, buffer: &mut [u8], expected: &[u8]) -> Result<usize> {
+        let position = self.position;
+        let reads = self.read_each(buffer.len())?;
+
+        // a mismatch on all the devices is left to the verification, like a failed write
+        if reads.iter().all(|(_, data)| data.as_slice() != expected) {
+            buffer.copy_from_slice(reads[0].1.as_slice());
+            return Ok(buffer.len());
+        }
+        for (i, data) in &reads {
+            if data.as_slice() != expected {
+                self.targets[*i].mismatched_blocks.push(position);
+            }
+        }
+        buffer.copy_from_slice(expected);
+        Ok(buffer.len())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        let position = self.position;
+        let length = data.len();
+        let mut shared = AlignedBuffer::new(length, IO_ALIGNMENT);
+        shared.as_mut_slice().copy_from_slice(data);
+        let shared = Arc::new(shared);
+        let writes = self.each(move |a| match a.write(shared.as_slice()) {
+            Ok(()) => Ok(Ok(())),
+            Err(err) => {
+                // goes on with the next block, like the others
+                a.seek(position + length as u64)?;
+                Ok(Err(err))
+            }
+        })?;
+        self.position += length as u64;
+
+        let failed = writes.iter().filter(|(_, w)| w.is_err()).count();
+        if failed == writes.len() {
+            let (_, last) = writes.into_iter().last().unwrap();
+            return last.context("All the mirrored devices failed");
+        }
+        for (i, written) in writes {
+            match written {
+                Ok(()) => self.targets[i].written += length as u64,
+                Err(_) => self.targets[i].bad_blocks.push(position),
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.each(|a| a.flush()).map(|_| ())
+    }
+
+    /// Discards on all the devices, unlike the other operations an unsupported discard
+    /// doesn't drop the device.
+    fn discard(&mut self, position: u64, length: u64) -> Result<()> {
+        let mut result = Ok(());
+        for (i, discarded) in self.run_all(move |a| a.discard(position, length)) {
+            if let Err(err) = discarded {
+                result = Err(err).context(format!(
+                    "Unable to discard on {}",
+                    self.targets[i].device_id
+                ));
+            }
+        }
+        result
+    }
+
+    /// The smallest size and the largest sectors of the devices, none if any of them
+    /// doesn't report its geometry.
+    fn geometry(&mut self) -> Result<Option<Geometry>> {
+        let geometries = self.each(|a| a.geometry())?;
+        Ok(geometries
+            .into_iter()
+            .map(|(_, g)| g)
+            .reduce(|a, b| match (a, b) {
+                (Some(a), Some(b)) => Some(Geometry {
+                    size: a.size.min(b.size),
+                    sector_size: a.sector_size.max(b.sector_size),
+                    physical_sector_size: a.physical_sector_size.max(b.physical_sector_size),
+                }),
+                _ => None,
+            })
+            .flatten())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::{Verify, WipeEvent, WipeEventReceiver, WipeOutcome, WipeState, WipeTask};
+    use crate::sanitization::SchemeRepo;
+    use crate::storage::memory::InMemoryStorage;
+    use std::sync::Mutex;
+
+    /// Shares the storage with the test, so it can be checked after the mirror is gone.
+    struct SharedStorage(Arc<Mutex<InMemoryStorage>>);
+
+    impl SharedStorage {
+        fn new(size: usize) -> (Self, Arc<Mutex<InMemoryStorage>>) {
+            let storage = Arc::new(Mutex::new(InMemoryStorage::with_data(vec![0xaa; size])));
+            (SharedStorage(storage.clone()), storage)
+        }
+
+        fn target(self, device_id: &str) -> MirrorTarget {
+            MirrorTarget::open(device_id, move || {
+                Ok(Box::new(self) as Box<dyn StorageAccess>)
+            })
+            .unwrap()
+        }
+    }
+
+    impl StorageAccess for SharedStorage {
+        fn position(&mut self) -> Result<u64> {
+            self.0.lock().unwrap().position()
+        }
+
+        fn seek(&mut self, position: u64) -> Result<u64> {
+            self.0.lock().unwrap().seek(position)
+        }
+
+        fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+            self.0.lock().unwrap().read(buffer)
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<()> {
+            self.0.lock().unwrap().write(data)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.0.lock().unwrap().flush()
+        }
+    }
+
+    struct NoEvents;
+
+    impl WipeEventReceiver for NoEvents {
+        fn handle(&mut self, _task: &WipeTask, _state: &WipeState, _event: WipeEvent) {}
+    }
+
+    #[test]
+    fn test_mirroring_tracks_errors_per_device() {
+        let size = 100000;
+        let (first, first_data) = SharedStorage::new(size);
+        let (failing, failing_data) = SharedStorage::new(size);
+        for position in [32768, 65536, 98304] {
+            failing_data.lock().unwrap().fail_at(position);
+        }
+        let (corrupted, corrupted_data) = SharedStorage::new(size);
+        corrupted_data.lock().unwrap().corrupt_at(vec![65536]);
+        let (last, last_data) = SharedStorage::new(size);
+
+        let mut mirror = MirrorAccess::new(vec![
+            first.target("first"),
+            failing.target("failing"),
+            corrupted.target("corrupted"),
+            last.target("last"),
+        ])
+        .unwrap();
+
+        let scheme = SchemeRepo::default().find("random").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::Last, size as u64, 32768).unwrap();
+        let mut state = WipeState::default();
+        assert_eq!(
+            task.run(&mut mirror, &mut state, &mut NoEvents),
+            WipeOutcome::Completed
+        );
+
+        let targets = mirror.into_targets();
+        assert!(targets.iter().all(|t| t.is_live() && t.error.is_none()));
+        assert_eq!(targets[0].written, size as u64);
+        assert_eq!(targets[1].written, 32768);
+        assert_eq!(targets[1].bad_blocks, vec![32768, 65536, 98304]);
+        // the blocks the device failed to write are still read back and differ
+        assert_eq!(targets[1].mismatched_blocks, vec![32768, 65536, 98304]);
+        assert!(targets[2].bad_blocks.is_empty());
+        assert_eq!(targets[2].mismatched_blocks, vec![65536]);
+        assert!(targets[3].bad_blocks.is_empty() && targets[3].mismatched_blocks.is_empty());
+
+        let first_data = first_data.lock().unwrap();
+        assert_eq!(
+            first_data.file.get_ref(),
+            last_data.lock().unwrap().file.get_ref()
+        );
+        assert_eq!(
+            first_data.file.get_ref()[..32768],
+            failing_data.lock().unwrap().file.get_ref()[..32768]
+        );
+        assert!(first_data.file.get_ref().iter().any(|b| *b != 0xaa));
+    }
+
+    #[test]
+    fn test_mirroring_compares_each_device_with_written_data() {
+        // with two devices neither of them is taken as the reference
+        let size = 65536;
+        let (corrupted, corrupted_data) = SharedStorage::new(size);
+        corrupted_data.lock().unwrap().corrupt_at(vec![0]);
+        let (good, _) = SharedStorage::new(size);
+        let mut mirror =
+            MirrorAccess::new(vec![corrupted.target("corrupted"), good.target("good")]).unwrap();
+
+        let scheme = SchemeRepo::default().find("random").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::Last, size as u64, 32768).unwrap();
+        assert!(task
+            .run(&mut mirror, &mut WipeState::default(), &mut NoEvents)
+            .is_success());
+        assert_eq!(mirror.targets()[0].mismatched_blocks, vec![0]);
+        assert!(mirror.targets()[1].mismatched_blocks.is_empty());
+
+        // a mismatch on all the devices fails the verification
+        let (first, first_data) = SharedStorage::new(size);
+        first_data.lock().unwrap().corrupt_at(vec![32768]);
+        let (second, second_data) = SharedStorage::new(size);
+        second_data.lock().unwrap().corrupt_at(vec![32768]);
+        let mut mirror =
+            MirrorAccess::new(vec![first.target("first"), second.target("second")]).unwrap();
+
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::Last, size as u64, 32768).unwrap();
+        assert_eq!(
+            task.run(&mut mirror, &mut WipeState::default(), &mut NoEvents),
+            WipeOutcome::VerificationFailed
+        );
+        assert!(mirror
+            .targets()
+            .iter()
+            .all(|t| t.mismatched_blocks.is_empty()));
+    }
+
+    #[test]
+    fn test_mirroring_fails_when_all_devices_fail() {
+        let (storage, data) = SharedStorage::new(1000);
+        data.lock().unwrap().fail_at(0);
+        let mut mirror = MirrorAccess::new(vec![storage.target("only")]).unwrap();
+
+        assert!(mirror.write(&[0; 100]).is_err());
+        assert!(mirror.targets()[0].bad_blocks.is_empty());
+        assert!(mirror.seek(0).is_ok());
+        assert!(MirrorAccess::new(Vec::new()).is_err());
+        assert!(MirrorTarget::open("missing", || Err(anyhow!("Not found"))).is_err());
+    }
+}
@@ -1,4 +1,57 @@
+//! Wiping and related operations.
+//!
+//! A [`WipeTask`] applies a scheme to a device and reports the progress to a
+//! [`WipeEventReceiver`] as [`WipeEvent`]s. A running wipe can be stopped through the
+//! task's [`CancellationToken`].
+
+mod background;
+pub mod badblocks;
+pub mod batch;
+pub mod benchmark;
+mod cancel;
+pub mod checkpoint;
+#[cfg(unix)]
+pub mod control;
+pub mod desktop;
+pub mod diagnostics;
+mod digest;
+mod duty;
+pub mod email;
+mod entropy;
+pub mod eventlog;
+pub mod evidence;
+mod fanout;
+pub mod freespace;
+pub mod inspect;
+mod journal;
+pub mod label;
+pub mod luks;
 mod marker;
+pub mod mirror;
+pub mod notify;
+mod operator;
+pub mod postwipe;
+pub mod privacy;
+pub mod probe;
+mod range;
+pub mod report;
+pub mod resume;
+mod retry;
+pub mod sanitize;
+pub mod schedule;
+pub mod soak;
+pub mod state;
+pub mod timing;
+pub mod warnings;
 mod wipe;
 
+#[cfg(unix)]
+pub use cancel::{restore_terminal, restore_terminal_on_interrupt};
+pub use cancel::{was_interrupted, CancellationToken, PauseToken, INTERRUPTED_EXIT_CODE};
+pub use duty::DutyCycle;
+pub use fanout::FanOutReceiver;
+pub use journal::{BlockChecksum, VerificationJournal};
+pub use operator::*;
+pub use range::*;
+pub use retry::{RetryBackoff, RetryScope};
 pub use wipe::*;
@@ -0,0 +1,251 @@
+use crate::actions::background::BackgroundProcesses;
+use crate::actions::{WipeEvent, WipeEventReceiver, WipeState, WipeTask};
+use serde::Serialize;
+use std::process::Command;
+use std::time::Duration;
+
+/// Minimum time between the progress notifications.
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where the notifications are delivered, each one as a JSON document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotifyTarget {
+    /// POSTed to the URL with `curl`.
+    Url(String),
+    /// Passed to the standard input of the shell command, the event name is also set
+    /// in the `LETHE_EVENT` environment variable.
+    Command(String),
+}
+
+impl NotifyTarget {
+    fn command(&self, event: &str) -> Command {
+        let mut command = match self {
+            NotifyTarget::Url(url) => {
+                let mut c = Command::new("curl");
+                c.args([
+                    "--fail",
+                    "--silent",
+                    "--show-error",
+                    "--max-time",
+                    "30",
+                    "-H",
+                    "Content-Type: application/json",
+                    "--data-binary",
+                    "@-",
+                ])
+                .arg(url);
+                c
+            }
+            NotifyTarget::Command(cmd) if cfg!(windows) => {
+                let mut c = Command::new("cmd");
+                c.arg("/C").arg(cmd);
+                c
+            }
+            NotifyTarget::Command(cmd) => {
+                let mut c = Command::new("sh");
+                c.arg("-c").arg(cmd);
+                c
+            }
+        };
+        command.env("LETHE_EVENT", event);
+        command
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub event: &'static str,
+    pub device: String,
+    pub scheme: String,
+    pub stage: usize,
+    pub stages: usize,
+    pub verifying: bool,
+    pub done: u64,
+    pub total: u64,
+    pub elapsed_secs: u64,
+    pub error: Option<String>,
+}
+
+/// Sends notifications about the main events, and about the progress from time to time,
+/// to the targets, then hands the events to the inner receiver. Notifications are sent
+/// in the background, only the last one is waited for.
+pub struct NotifyingReceiver {
+    inner: Box<dyn WipeEventReceiver>,
+    device_id: String,
+    targets: Vec<NotifyTarget>,
+    interval: Duration,
+    last_progress: Option<Duration>,
+    processes: BackgroundProcesses,
+}
+
+impl NotifyingReceiver {
+    pub fn new(
+        inner: Box<dyn WipeEventReceiver>,
+        device_id: &str,
+        targets: Vec<NotifyTarget>,
+    ) -> Self {
+        NotifyingReceiver {
+            inner,
+            device_id: device_id.to_string(),
+            targets,
+            interval: DEFAULT_PROGRESS_INTERVAL,
+            last_progress: None,
+            processes: BackgroundProcesses::default(),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn notification(
+        &self,
+        task: &WipeTask,
+        state: &WipeState,
+        event: &WipeEvent,
+    ) -> Option<Notification> {
+        let (name, error) = match event {
+            WipeEvent::Started => ("started", None),
+            WipeEvent::StageStarted => ("stage_started", None),
+            WipeEvent::Progress(_) => ("progress", None),
+            WipeEvent::StageCompleted(result) => (
+                "stage_completed",
+                result.as_ref().map(|e| format!("{:#}", e)),
+            ),
+            WipeEvent::Completed(result) => {
+                ("completed", result.as_ref().map(|e| format!("{:#}", e)))
+            }
+            WipeEvent::Aborted => ("aborted", None),
+            WipeEvent::Fatal(err) => ("fatal", Some(format!("{:#}", err))),
+            _ => return None,
+        };
+
+        Some(Notification {
+            event: name,
+            device: self.device_id.clone(),
+            scheme: task.scheme.description.clone(),
+            stage: state.stage + 1,
+            stages: task.scheme.stages.len(),
+            verifying: state.at_verification,
+            done: task.progress_at(state.position),
+            total: task.wiped_size(),
+            elapsed_secs: state.stamp.elapsed.as_secs(),
+            error,
+        })
+    }
+
+    fn send(&mut self, notification: &Notification) {
+        let payload = match serde_json::to_vec(notification) {
+            Ok(p) => p,
+            Err(_) => return,
+        };
+        for target in &self.targets {
+            self.processes.spawn(
+                &mut target.command(notification.event),
+                Some(&payload),
+                None,
+            );
+        }
+    }
+
+    /// Number of notifications which couldn't be delivered.
+    pub fn failed(&self) -> usize {
+        self.processes.failed()
+    }
+}
+
+impl WipeEventReceiver for NotifyingReceiver {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        let due = match event {
+            WipeEvent::Progress(_) => match self.last_progress {
+                Some(last) if state.stamp.elapsed < last + self.interval => false,
+                _ => {
+                    self.last_progress = Some(state.stamp.elapsed);
+                    true
+                }
+            },
+            _ => true,
+        };
+
+        if due {
+            if let Some(n) = self.notification(task, state, &event) {
+                self.send(&n);
+            }
+        }
+
+        let finished = matches!(
+            event,
+            WipeEvent::Completed(_) | WipeEvent::Aborted | WipeEvent::Fatal(_)
+        );
+        self.processes.reap(finished);
+        if finished && self.failed() > 0 {
+            eprintln!("Unable to deliver {} notification(s).", self.failed());
+        }
+
+        self.inner.handle(task, state, event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+
+    struct Nothing;
+
+    impl WipeEventReceiver for Nothing {
+        fn handle(&mut self, _task: &WipeTask, _state: &WipeState, _event: WipeEvent) {}
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_notifications_are_sent_to_command() {
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::No, 1000, 100).unwrap();
+        let mut state = WipeState::default();
+
+        let dir = std::env::temp_dir().join(format!("lethe-notify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let command = format!("cat > '{}'/\"$LETHE_EVENT-$$\"", dir.to_string_lossy());
+        let mut receiver = NotifyingReceiver::new(
+            Box::new(Nothing),
+            "/dev/sdx",
+            vec![NotifyTarget::Command(command)],
+        )
+        .with_interval(Duration::from_secs(10));
+
+        receiver.handle(&task, &state, WipeEvent::Started);
+        receiver.handle(&task, &state, WipeEvent::MarkBlockAsBad(0));
+        for (i, position) in [100u64, 200, 300].iter().enumerate() {
+            state.position = *position;
+            state.stamp.elapsed = Duration::from_secs(i as u64 * 6);
+            receiver.handle(&task, &state, WipeEvent::Progress(*position));
+        }
+        receiver.handle(&task, &state, WipeEvent::Completed(None));
+        assert_eq!(receiver.failed(), 0);
+
+        let mut events = Vec::new();
+        let mut completed = None;
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let event = name.split('-').next().unwrap().to_string();
+            if event == "completed" {
+                let content = std::fs::read(&path).unwrap();
+                completed = Some(serde_json::from_slice::<serde_json::Value>(&content).unwrap());
+            }
+            events.push(event);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        events.sort_unstable();
+        assert_eq!(events, vec!["completed", "progress", "progress", "started"]);
+
+        let completed = completed.unwrap();
+        assert_eq!(completed["device"], "/dev/sdx");
+        assert_eq!(completed["done"], 300);
+        assert_eq!(completed["total"], 1000);
+        assert!(completed["error"].is_null());
+    }
+}
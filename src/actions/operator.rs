@@ -0,0 +1,67 @@
+use anyhow::Result;
+use regex::Regex;
+use std::fmt::{Display, Formatter};
+
+/// The person responsible for a wipe, as recorded in session output and reports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operator {
+    pub name: String,
+    pub email: Option<String>,
+}
+
+impl Operator {
+    pub fn parse(s: &str) -> Result<Operator> {
+        let operator_regex =
+            Regex::new(r"^\s*(?P<name>[^<>]*?)\s*(<(?P<email>[^<>\s]+@[^<>\s]+)>)?\s*$").unwrap();
+
+        let captures = operator_regex.captures(s).ok_or(anyhow!(
+            "Use a name with optional email (e.g. \"Jane Doe <jane@example.com>\")."
+        ))?;
+
+        let name = captures["name"].to_string();
+        if name.is_empty() {
+            return Err(anyhow!("Operator name can't be empty."));
+        }
+
+        Ok(Operator {
+            name,
+            email: captures.name("email").map(|m| m.as_str().to_string()),
+        })
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.email {
+            Some(email) => write!(f, "{} <{}>", self.name, email),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::*;
+
+    #[test]
+    fn test_operator_parser_good() {
+        let o = Operator::parse("Jane Doe <jane@example.com>").unwrap();
+        assert_eq!(o.name, "Jane Doe");
+        assert_eq!(o.email, Some("jane@example.com".to_string()));
+        assert_eq!(o.to_string(), "Jane Doe <jane@example.com>");
+
+        let o = Operator::parse("  jdoe ").unwrap();
+        assert_eq!(o.name, "jdoe");
+        assert_eq!(o.email, None);
+        assert_eq!(o.to_string(), "jdoe");
+    }
+
+    #[test]
+    fn test_operator_parser_bad() {
+        assert_matches!(Operator::parse(""), Err(_));
+        assert_matches!(Operator::parse("<jane@example.com>"), Err(_));
+        assert_matches!(Operator::parse("Jane <not an email>"), Err(_));
+        assert_matches!(Operator::parse("Jane <jane@example.com"), Err(_));
+    }
+}
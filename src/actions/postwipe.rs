@@ -0,0 +1,272 @@
+use crate::sanitization::mem::AlignedBuffer;
+use crate::storage::{Platform, StorageAccess, StorageRef};
+use anyhow::{Context, Result};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const IO_ALIGNMENT: u64 = 4096;
+
+/// Maximum number of bytes discarded with a single request.
+const DISCARD_CHUNK_SIZE: u64 = 1 << 30;
+
+const GPT_ENTRIES: u32 = 128;
+const GPT_ENTRY_SIZE: u32 = 128;
+const GPT_HEADER_SIZE: usize = 92;
+
+/// Optional step taken after a successful wipe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostWipeAction {
+    None,
+    /// Discards the whole device so the controller knows all blocks are free.
+    Trim,
+    /// Writes an empty GPT (with a protective MBR).
+    Gpt,
+    /// Writes an empty MBR partition table.
+    Mbr,
+    /// Spins the drive down.
+    Standby,
+}
+
+impl PostWipeAction {
+    pub const NAMES: &'static [&'static str] = &["none", "trim", "gpt", "mbr", "standby"];
+
+    pub fn apply(
+        &self,
+        platform: &dyn Platform,
+        device: &dyn StorageRef,
+        access: &mut dyn StorageAccess,
+    ) -> Result<()> {
+        let total_size = device.details().size;
+        let sector_size = match device.details().block_size {
+            0 => 512,
+            s => s as u64,
+        };
+        match self {
+            PostWipeAction::None => Ok(()),
+            PostWipeAction::Trim => discard_all(access, total_size),
+            PostWipeAction::Gpt => write_gpt(access, total_size, sector_size),
+            PostWipeAction::Mbr => write_regions(access, total_size, &[(0, empty_mbr(None))]),
+            PostWipeAction::Standby => platform.standby(device.id()),
+        }
+    }
+}
+
+impl FromStr for PostWipeAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(PostWipeAction::None),
+            "trim" => Ok(PostWipeAction::Trim),
+            "gpt" => Ok(PostWipeAction::Gpt),
+            "mbr" => Ok(PostWipeAction::Mbr),
+            "standby" => Ok(PostWipeAction::Standby),
+            _ => Err(anyhow!("Use one of {}.", PostWipeAction::NAMES.join(", "))),
+        }
+    }
+}
+
+impl Display for PostWipeAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostWipeAction::None => f.write_str("nothing"),
+            PostWipeAction::Trim => f.write_str("discarded the whole device"),
+            PostWipeAction::Gpt => f.write_str("wrote an empty GPT partition table"),
+            PostWipeAction::Mbr => f.write_str("wrote an empty MBR partition table"),
+            PostWipeAction::Standby => f.write_str("put the drive to standby"),
+        }
+    }
+}
+
+fn discard_all(access: &mut dyn StorageAccess, total_size: u64) -> Result<()> {
+    let mut position = 0;
+    while position < total_size {
+        let length = (total_size - position).min(DISCARD_CHUNK_SIZE);
+        access.discard(position, length)?;
+        position += length;
+    }
+    Ok(())
+}
+
+/// MBR without partitions, or with a single protective partition covering the given
+/// number of sectors when used with GPT.
+fn empty_mbr(protective_sectors: Option<u64>) -> Vec<u8> {
+    let mut mbr = vec![0u8; 512];
+    mbr[440..444].copy_from_slice(&rand::random::<[u8; 4]>());
+    if let Some(sectors) = protective_sectors {
+        let entry = &mut mbr[446..462];
+        entry[1..4].copy_from_slice(&[0x00, 0x02, 0x00]);
+        entry[4] = 0xee;
+        entry[5..8].copy_from_slice(&[0xff, 0xff, 0xff]);
+        entry[8..12].copy_from_slice(&1u32.to_le_bytes());
+        entry[12..16].copy_from_slice(&(sectors.min(u32::MAX as u64) as u32).to_le_bytes());
+    }
+    mbr[510] = 0x55;
+    mbr[511] = 0xaa;
+    mbr
+}
+
+fn write_gpt(access: &mut dyn StorageAccess, total_size: u64, sector_size: u64) -> Result<()> {
+    let entries_len = (GPT_ENTRIES * GPT_ENTRY_SIZE) as u64;
+    let entries_sectors = entries_len.div_ceil(sector_size);
+    let last_lba = (total_size / sector_size)
+        .checked_sub(1)
+        .filter(|l| *l > 2 * (entries_sectors + 1) + 1)
+        .ok_or_else(|| anyhow!("The device is too small for GPT"))?;
+
+    let mut disk_guid: [u8; 16] = rand::random();
+    disk_guid[7] = (disk_guid[7] & 0x0f) | 0x40;
+    disk_guid[8] = (disk_guid[8] & 0x3f) | 0x80;
+    let entries_crc = crc32(&vec![0u8; entries_len as usize]);
+
+    let header = |my_lba: u64, alternate_lba: u64, entries_lba: u64| {
+        let mut h = vec![0u8; GPT_HEADER_SIZE];
+        h[0..8].copy_from_slice(b"EFI PART");
+        h[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        h[12..16].copy_from_slice(&(GPT_HEADER_SIZE as u32).to_le_bytes());
+        h[24..32].copy_from_slice(&my_lba.to_le_bytes());
+        h[32..40].copy_from_slice(&alternate_lba.to_le_bytes());
+        h[40..48].copy_from_slice(&(2 + entries_sectors).to_le_bytes());
+        h[48..56].copy_from_slice(&(last_lba - 1 - entries_sectors).to_le_bytes());
+        h[56..72].copy_from_slice(&disk_guid);
+        h[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+        h[80..84].copy_from_slice(&GPT_ENTRIES.to_le_bytes());
+        h[84..88].copy_from_slice(&GPT_ENTRY_SIZE.to_le_bytes());
+        h[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+        let crc = crc32(&h);
+        h[16..20].copy_from_slice(&crc.to_le_bytes());
+        h
+    };
+
+    // the entries are all empty, only the headers and the protective MBR need to be placed
+    let mut head = empty_mbr(Some(last_lba));
+    head.resize(sector_size as usize, 0);
+    head.extend(header(1, last_lba, 2));
+    write_regions(
+        access,
+        total_size,
+        &[
+            (0, head),
+            (
+                last_lba * sector_size,
+                header(last_lba, 1, last_lba - entries_sectors),
+            ),
+        ],
+    )
+}
+
+/// Writes the data at the given offsets, zeroing the rest of the aligned blocks around them,
+/// so the regions shouldn't share a block.
+fn write_regions(
+    access: &mut dyn StorageAccess,
+    total_size: u64,
+    regions: &[(u64, Vec<u8>)],
+) -> Result<()> {
+    for (offset, data) in regions {
+        let start = offset - offset % IO_ALIGNMENT;
+        let end = (offset + data.len() as u64)
+            .div_ceil(IO_ALIGNMENT)
+            .saturating_mul(IO_ALIGNMENT)
+            .min(total_size);
+        let mut buf = AlignedBuffer::new((end - start) as usize, IO_ALIGNMENT as usize);
+        let block = buf.as_mut_slice();
+        block.fill(0);
+        let skip = (offset - start) as usize;
+        block[skip..skip + data.len()].copy_from_slice(data);
+
+        access.seek(start)?;
+        access
+            .write(block)
+            .context(format!("Unable to write at {}", start))?;
+    }
+    access.flush()
+}
+
+/// CRC-32 (IEEE) as used by GPT.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::probe::{probe, SignatureKind};
+    use crate::storage::platform::{MockDevice, MockPlatform};
+    use crate::storage::StorageDetails;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn mock_device(size: u64, block_size: usize) -> MockDevice {
+        MockDevice {
+            id: "/dev/sdx".to_string(),
+            details: StorageDetails {
+                size,
+                block_size,
+                ..Default::default()
+            },
+            users: Vec::new(),
+            mounts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_write_empty_partition_tables() {
+        for block_size in &[512, 4096] {
+            let device = mock_device(16 * MB, *block_size);
+            let platform = MockPlatform::new(vec![device.clone()]);
+            let mut access = platform.access(&device).unwrap();
+            PostWipeAction::Gpt
+                .apply(&platform, &device, &mut access)
+                .unwrap();
+
+            let kinds: Vec<_> = probe(&mut access, device.details.size)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.kind)
+                .collect();
+            assert_eq!(kinds, vec![SignatureKind::Mbr, SignatureKind::Gpt]);
+
+            let data = platform.data("/dev/sdx").unwrap();
+            let ss = *block_size;
+            let last = data.len() - ss;
+            assert_eq!(&data[last..last + 8], b"EFI PART");
+            // header checksum covers the header with the checksum field zeroed
+            let mut header = data[ss..ss + GPT_HEADER_SIZE].to_vec();
+            let crc = u32::from_le_bytes([header[16], header[17], header[18], header[19]]);
+            header[16..20].fill(0);
+            assert_eq!(crc32(&header), crc);
+        }
+
+        let device = mock_device(MB, 512);
+        let platform = MockPlatform::new(vec![device.clone()]);
+        let mut access = platform.access(&device).unwrap();
+        PostWipeAction::Mbr
+            .apply(&platform, &device, &mut access)
+            .unwrap();
+        let data = platform.data("/dev/sdx").unwrap();
+        assert_eq!(&data[510..512], &[0x55, 0xaa]);
+        assert!(data[446..510].iter().all(|b| *b == 0));
+
+        assert!(PostWipeAction::Gpt
+            .apply(&platform, &mock_device(16384, 512), &mut access)
+            .is_err());
+        assert!("gpt".parse::<PostWipeAction>().is_ok());
+        assert!("raid".parse::<PostWipeAction>().is_err());
+    }
+}
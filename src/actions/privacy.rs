@@ -0,0 +1,79 @@
+use crate::actions::checkpoint::DeviceFingerprint;
+use crate::actions::state::StateDir;
+use std::path::PathBuf;
+
+/// Local file a wipe of the device may leave behind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub path: PathBuf,
+    pub kind: &'static str,
+    pub exists: bool,
+}
+
+/// Result of the self-audit after a private wipe. Only meant to be printed, storing it
+/// would be a trace itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfAudit {
+    /// Files written to by the wipe.
+    pub touched: Vec<String>,
+    /// Places where a regular wipe of the device leaves files.
+    pub traces: Vec<Trace>,
+}
+
+impl SelfAudit {
+    pub fn run(device_id: &str, state: &StateDir, fingerprint: &DeviceFingerprint) -> SelfAudit {
+        let mut places = vec![
+            (state.checkpoints().path_for(fingerprint), "checkpoint"),
+            (state.badblocks_path(fingerprint), "bad block map"),
+        ];
+        #[cfg(unix)]
+        {
+            use crate::actions::control;
+            places.push((control::socket_path(device_id), "control socket"));
+            places.push((control::log_path(device_id), "detached wipe log"));
+        }
+
+        SelfAudit {
+            touched: vec![device_id.to_string()],
+            traces: places
+                .into_iter()
+                .map(|(path, kind)| Trace {
+                    exists: path.exists(),
+                    path,
+                    kind,
+                })
+                .collect(),
+        }
+    }
+
+    /// Traces found on disk, left by earlier regular wipes of the device at most.
+    pub fn leftovers(&self) -> impl Iterator<Item = &Trace> {
+        self.traces.iter().filter(|t| t.exists)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_self_audit_finds_leftover_checkpoints() {
+        let root = std::env::temp_dir().join(format!("lethe-privacy-{}", std::process::id()));
+        let state = StateDir::new(&root);
+        let fingerprint = DeviceFingerprint::new("/dev/sdx", 1000);
+
+        let audit = SelfAudit::run("/dev/sdx", &state, &fingerprint);
+        assert_eq!(audit.touched, vec!["/dev/sdx"]);
+        assert_eq!(audit.traces[0].kind, "checkpoint");
+        assert_eq!(audit.traces[1].kind, "bad block map");
+        assert_eq!(audit.leftovers().count(), 0);
+
+        let checkpoint = state.checkpoints().path_for(&fingerprint);
+        std::fs::create_dir_all(checkpoint.parent().unwrap()).unwrap();
+        std::fs::write(checkpoint, "{}").unwrap();
+        let audit = SelfAudit::run("/dev/sdx", &state, &fingerprint);
+        let leftovers: Vec<_> = audit.leftovers().map(|t| t.kind).collect();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(leftovers, vec!["checkpoint"]);
+    }
+}
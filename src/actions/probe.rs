@@ -0,0 +1,895 @@
+use crate::actions::WipeRange;
+use crate::sanitization::mem::AlignedBuffer;
+use crate::storage::StorageAccess;
+use anyhow::Result;
+use std::convert::TryInto;
+use std::fmt::{Display, Formatter};
+
+const IO_ALIGNMENT: u64 = 4096;
+const MAX_GPT_ENTRIES: u32 = 1024;
+const MIN_GPT_ENTRY_SIZE: u64 = 128;
+const MAX_GPT_ENTRY_SIZE: u64 = 4096;
+const MAX_LUKS2_HEADER: u64 = 4 * 1024 * 1024;
+const DEFAULT_LUKS2_AREA: u64 = 16 * 1024 * 1024;
+const DEFAULT_LUKS1_AREA: u64 = 2 * 1024 * 1024;
+const NTFS_BOOT_AREA: u64 = 8192;
+const NTFS_MFT_AREA: u64 = 64 * 1024;
+const NTFS_MFT_MIRROR_AREA: u64 = 4096;
+const MD_MAGIC: u32 = 0xa92b_4efc;
+const MD_SUPERBLOCK_AREA: u64 = 4096;
+/// Reserved for the 0.90 superblock at the end of the member.
+const MD_LEGACY_AREA: u64 = 64 * 1024;
+/// Label and the default metadata area before the first physical extent.
+const LVM_METADATA_AREA: u64 = 1024 * 1024;
+/// Group descriptors read to find the inode tables, enough for 32T with the default groups.
+const MAX_EXT_GROUPS: u64 = 1 << 18;
+const MIN_NTFS_RECORD: usize = 1024;
+const MAX_NTFS_RECORD: usize = 4096;
+const APFS_MAGIC: &[u8] = b"NXSB";
+/// Set in the size of an APFS checkpoint area which isn't contiguous.
+const APFS_NONCONTIGUOUS: u32 = 0x8000_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureKind {
+    Mbr,
+    Gpt,
+    Ntfs,
+    Ext,
+    Luks,
+    /// Member of a Linux software RAID (md).
+    MdRaid,
+    /// LVM physical volume.
+    Lvm,
+    /// APFS container.
+    Apfs,
+}
+
+impl Display for SignatureKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureKind::Mbr => f.write_str("MBR partition table"),
+            SignatureKind::Gpt => f.write_str("GPT partition table"),
+            SignatureKind::Ntfs => f.write_str("NTFS"),
+            SignatureKind::Ext => f.write_str("ext2/3/4"),
+            SignatureKind::Luks => f.write_str("LUKS"),
+            SignatureKind::MdRaid => f.write_str("Linux RAID member"),
+            SignatureKind::Lvm => f.write_str("LVM physical volume"),
+            SignatureKind::Apfs => f.write_str("APFS container"),
+        }
+    }
+}
+
+/// A known on-disk structure and the regions holding its metadata.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub kind: SignatureKind,
+    pub offset: u64,
+    pub regions: Vec<WipeRange>,
+}
+
+/// Looks for partition tables and filesystem (or encryption) headers on the device,
+/// including the ones inside the partitions found.
+pub fn probe(access: &mut dyn StorageAccess, total_size: u64) -> Result<Vec<Signature>> {
+    let mut prober = Prober {
+        access,
+        total_size,
+        found: Vec::new(),
+    };
+
+    if !prober.probe_volume(0, total_size)? {
+        let partitions = prober.probe_partition_tables()?;
+        for (start, size) in partitions {
+            prober.probe_volume(start, size)?;
+        }
+    }
+
+    Ok(prober.found)
+}
+
+/// Combines the metadata regions of the signatures into ranges aligned to the block size.
+pub fn metadata_ranges(
+    signatures: &[Signature],
+    total_size: u64,
+    block_size: usize,
+) -> Result<Vec<WipeRange>> {
+    let ranges = signatures
+        .iter()
+        .flat_map(|s| s.regions.iter())
+        .map(|r| r.expand_to_blocks(block_size, total_size))
+        .collect();
+
+    WipeRange::normalize(ranges, total_size, block_size)
+}
+
+/// Ranges of the quick wipe: the first and the last `edge_size` bytes of the device, where the
+/// partition tables and most of the headers are, and the metadata regions of the signatures
+/// found elsewhere, like the backup superblocks spread over a filesystem.
+pub fn quick_ranges(
+    signatures: &[Signature],
+    total_size: u64,
+    edge_size: u64,
+    block_size: usize,
+) -> Result<Vec<WipeRange>> {
+    let edge_size = edge_size.min(total_size);
+    let ranges = [
+        WipeRange::new(0, edge_size),
+        WipeRange::new(total_size - edge_size, total_size),
+    ]
+    .iter()
+    .chain(signatures.iter().flat_map(|s| s.regions.iter()))
+    .filter(|r| !r.is_empty())
+    .map(|r| r.expand_to_blocks(block_size, total_size))
+    .collect();
+
+    WipeRange::normalize(ranges, total_size, block_size)
+}
+
+/// Reads `len` bytes at any offset with aligned reads, as the device may be opened for
+/// direct I/O. The bytes past the end of the device are left zeroed.
+pub(crate) fn read_at(
+    access: &mut dyn StorageAccess,
+    total_size: u64,
+    offset: u64,
+    len: usize,
+) -> Result<Vec<u8>> {
+    let mut result = vec![0u8; len];
+    if offset >= total_size {
+        return Ok(result);
+    }
+
+    let aligned_start = offset - offset % IO_ALIGNMENT;
+    let aligned_end = (offset + len as u64).div_ceil(IO_ALIGNMENT) * IO_ALIGNMENT;
+    let mut buf = AlignedBuffer::new(
+        (aligned_end - aligned_start) as usize,
+        IO_ALIGNMENT as usize,
+    );
+    let data = buf.as_mut_slice();
+
+    access.seek(aligned_start)?;
+    let mut filled = 0;
+    while filled < data.len() {
+        let n = access.read(&mut data[filled..])?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+
+    let skip = (offset - aligned_start) as usize;
+    let available = filled.saturating_sub(skip).min(len);
+    result[..available].copy_from_slice(&data[skip..skip + available]);
+    Ok(result)
+}
+
+struct Prober<'a> {
+    access: &'a mut dyn StorageAccess,
+    total_size: u64,
+    found: Vec<Signature>,
+}
+
+impl Prober<'_> {
+    fn read_at(&mut self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        read_at(self.access, self.total_size, offset, len)
+    }
+
+    fn add(&mut self, kind: SignatureKind, offset: u64, regions: Vec<(u64, u64)>) {
+        let regions = regions
+            .into_iter()
+            .map(|(start, end)| {
+                WipeRange::new(start.min(self.total_size), end.min(self.total_size))
+            })
+            .filter(|r| r.start < r.end)
+            .collect();
+        self.found.push(Signature {
+            kind,
+            offset,
+            regions,
+        });
+    }
+
+    /// Probes for RAID or volume manager metadata, the content of such volumes is not
+    /// probed further.
+    fn probe_volume_member(&mut self, start: u64, size: u64) -> Result<bool> {
+        let end = start + size;
+        let legacy = (size & !(MD_LEGACY_AREA - 1)).checked_sub(MD_LEGACY_AREA);
+        let superblocks = [
+            Some((0, MD_SUPERBLOCK_AREA)),
+            Some((4096, MD_SUPERBLOCK_AREA)),
+            size.checked_sub(8192)
+                .map(|o| (o & !4095, MD_SUPERBLOCK_AREA)),
+            legacy.map(|o| (o, MD_LEGACY_AREA)),
+        ];
+        for (offset, area) in superblocks.iter().flatten() {
+            let sb = self.read_at(start + offset, 4)?;
+            if le32(&sb, 0) == MD_MAGIC {
+                let sb_start = start + offset;
+                self.add(
+                    SignatureKind::MdRaid,
+                    sb_start,
+                    vec![(sb_start, (sb_start + area).min(end))],
+                );
+                return Ok(true);
+            }
+        }
+
+        let head = self.read_at(start, 2048)?;
+        for sector in 0..4 {
+            let label = &head[sector * 512..(sector + 1) * 512];
+            if &label[0..8] == b"LABELONE" && &label[24..32] == b"LVM2 001" {
+                self.add(
+                    SignatureKind::Lvm,
+                    start,
+                    vec![(start, (start + LVM_METADATA_AREA).min(end))],
+                );
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Probes for a filesystem or an encrypted volume starting at the given offset.
+    fn probe_volume(&mut self, start: u64, size: u64) -> Result<bool> {
+        if self.probe_volume_member(start, size)? {
+            return Ok(true);
+        }
+
+        let head = self.read_at(start, 4096)?;
+        let end = start + size;
+
+        if &head[0..6] == b"LUKS\xba\xbe" {
+            let area = match be16(&head, 6) {
+                1 => match be32(&head, 104) as u64 * 512 {
+                    0 => DEFAULT_LUKS1_AREA,
+                    a => a,
+                },
+                _ => self.luks2_area(start, be64(&head, 8))?,
+            };
+            self.add(
+                SignatureKind::Luks,
+                start,
+                vec![(start, (start + area).min(end))],
+            );
+            return Ok(true);
+        }
+
+        if &head[3..11] == b"NTFS    " {
+            let sector_size = le16(&head, 11) as u64;
+            let cluster_size = head[13] as u64 * sector_size;
+            let total_sectors = le64(&head, 40);
+            let mft = start + le64(&head, 48).saturating_mul(cluster_size);
+            let mft_mirror = start + le64(&head, 56).saturating_mul(cluster_size);
+            let backup = start + total_sectors.saturating_mul(sector_size);
+
+            let record_size = match head[64] as i8 {
+                c if c > 0 => (c as u64 * cluster_size) as usize,
+                c => 1usize << (-(c as i32)).min(12),
+            };
+            let mft_runs = if mft < end {
+                self.ntfs_mft_runs(start, mft, record_size, cluster_size)?
+            } else {
+                Vec::new()
+            };
+
+            let mut regions = vec![(start, start + NTFS_BOOT_AREA)];
+            if mft_runs.is_empty() {
+                regions.push((mft, mft + NTFS_MFT_AREA));
+            }
+            regions.extend(mft_runs);
+            regions.push((mft_mirror, mft_mirror + NTFS_MFT_MIRROR_AREA));
+            regions.push((backup, backup + sector_size));
+            let regions = regions
+                .into_iter()
+                .filter(|(offset, _)| *offset < end)
+                .map(|(offset, region_end)| (offset, region_end.min(end)))
+                .collect();
+            self.add(SignatureKind::Ntfs, start, regions);
+            return Ok(true);
+        }
+
+        if le16(&head, 1024 + 56) == 0xef53 {
+            let sb = &head[1024..2048];
+            let fs_block = 1024u64 << le32(sb, 24).min(16);
+            let first_data_block = le32(sb, 20) as u64;
+            let blocks_per_group = le32(sb, 32).max(1) as u64;
+            let mut blocks_count = le32(sb, 4) as u64;
+            if le32(sb, 96) & 0x80 != 0 {
+                blocks_count |= (le32(sb, 336) as u64) << 32;
+            }
+            let sparse = le32(sb, 100) & 0x1 != 0;
+            let groups = blocks_count
+                .saturating_sub(first_data_block)
+                .div_ceil(blocks_per_group);
+            let desc_size = match le32(sb, 96) & 0x80 {
+                0 => 32,
+                _ => (le16(sb, 254) as u64).max(32),
+            };
+            let gdt_blocks = (groups * desc_size).div_ceil(fs_block).max(1);
+
+            // superblock and the group descriptors table, in the first group and its backups
+            let gdt = start + (first_data_block + 1) * fs_block;
+            let mut regions = vec![(start, gdt + gdt_blocks * fs_block)];
+            for g in (1..groups).filter(|g| !sparse || is_sparse_group(*g)) {
+                let offset = start + (g * blocks_per_group + first_data_block) * fs_block;
+                if offset >= end {
+                    break;
+                }
+                regions.push((offset, (offset + (1 + gdt_blocks) * fs_block).min(end)));
+            }
+
+            let inode_size = match le32(sb, 76) {
+                0 => 128,
+                _ => le16(sb, 88) as u64,
+            };
+            let inode_table_len = le32(sb, 40) as u64 * inode_size;
+            if inode_table_len > 0 {
+                let descriptors =
+                    self.read_at(gdt, (groups.min(MAX_EXT_GROUPS) * desc_size) as usize)?;
+                for desc in descriptors.chunks(desc_size as usize) {
+                    let mut block = le32(desc, 8) as u64;
+                    if desc_size >= 64 {
+                        block |= (le32(desc, 40) as u64) << 32;
+                    }
+                    let offset = start + block.saturating_mul(fs_block);
+                    if block > 0 && offset < end {
+                        regions.push((offset, (offset + inode_table_len).min(end)));
+                    }
+                }
+            }
+            self.add(SignatureKind::Ext, start, regions);
+            return Ok(true);
+        }
+
+        if &head[32..36] == APFS_MAGIC {
+            let block = match le32(&head, 36) as u64 {
+                b if b.is_power_of_two() && (4096..=65536).contains(&b) => b,
+                _ => 4096,
+            };
+            let mut regions = vec![(start, start + block)];
+            // checkpoint descriptor and data areas, holding the copies of the superblock
+            // and the space manager
+            for (blocks_at, base_at) in &[(104, 112), (108, 120)] {
+                let blocks = le32(&head, *blocks_at);
+                let base = start + le64(&head, *base_at).saturating_mul(block);
+                if blocks & APFS_NONCONTIGUOUS == 0 && base < end {
+                    regions.push((base, base.saturating_add(blocks as u64 * block)));
+                }
+            }
+            // object map of the container and the root of its tree, locating the volumes
+            let omap = start + le64(&head, 160).saturating_mul(block);
+            if omap > start && omap < end {
+                regions.push((omap, omap + block));
+                let tree = start + le64(&self.read_at(omap, 56)?, 48).saturating_mul(block);
+                if tree > start && tree < end {
+                    regions.push((tree, tree + block));
+                }
+            }
+            let regions = regions
+                .into_iter()
+                .map(|(offset, region_end)| (offset, region_end.min(end)))
+                .collect();
+            self.add(SignatureKind::Apfs, start, regions);
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    /// Extents of the MFT, as listed by the data runs of its own record. Empty if the record
+    /// can't be parsed.
+    fn ntfs_mft_runs(
+        &mut self,
+        start: u64,
+        mft: u64,
+        record_size: usize,
+        cluster_size: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        // anything else comes from a corrupt boot sector
+        if !(MIN_NTFS_RECORD..=MAX_NTFS_RECORD).contains(&record_size) || cluster_size == 0 {
+            return Ok(Vec::new());
+        }
+        let mut record = self.read_at(mft, record_size)?;
+        if &record[0..4] != b"FILE" {
+            return Ok(Vec::new());
+        }
+
+        // the last two bytes of each sector are kept in the update sequence array
+        let usa = le16(&record, 4) as usize;
+        let usa_count = le16(&record, 6) as usize;
+        for i in 1..usa_count {
+            let (fixed, original) = (i * 512 - 2, usa + i * 2);
+            if fixed + 2 > record.len() || original + 2 > record.len() {
+                break;
+            }
+            let value = [record[original], record[original + 1]];
+            record[fixed..fixed + 2].copy_from_slice(&value);
+        }
+
+        let mut offset = le16(&record, 20) as usize;
+        while offset + 64 <= record.len() {
+            let kind = le32(&record, offset);
+            let len = le32(&record, offset + 4) as usize;
+            if kind == 0xffff_ffff || len == 0 || offset + len > record.len() {
+                break;
+            }
+            // unnamed non-resident $DATA attribute
+            if kind == 0x80 && record[offset + 8] == 1 && record[offset + 9] == 0 {
+                let runs = offset + le16(&record, offset + 32) as usize;
+                return Ok(data_runs(&record[runs.min(offset + len)..offset + len])
+                    .into_iter()
+                    .map(|(lcn, clusters)| {
+                        let run = start + lcn.saturating_mul(cluster_size);
+                        (
+                            run,
+                            run.saturating_add(clusters.saturating_mul(cluster_size)),
+                        )
+                    })
+                    .collect());
+            }
+            offset += len;
+        }
+        Ok(Vec::new())
+    }
+
+    fn luks2_area(&mut self, start: u64, header_size: u64) -> Result<u64> {
+        if header_size <= 4096 || header_size > MAX_LUKS2_HEADER {
+            return Ok(DEFAULT_LUKS2_AREA);
+        }
+
+        let json_area = self.read_at(start + 4096, (header_size - 4096) as usize)?;
+        let json_len = json_area
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(json_area.len());
+
+        let keyslots_size = serde_json::from_slice::<serde_json::Value>(&json_area[..json_len])
+            .ok()
+            .and_then(|v| {
+                v.pointer("/config/keyslots_size")
+                    .and_then(|s| s.as_str())
+                    .and_then(|s| s.parse::<u64>().ok())
+            });
+
+        Ok(match keyslots_size {
+            Some(k) => 2 * header_size + k,
+            None => DEFAULT_LUKS2_AREA,
+        })
+    }
+
+    /// Probes for MBR and GPT partition tables and returns the partitions found.
+    fn probe_partition_tables(&mut self) -> Result<Vec<(u64, u64)>> {
+        let mut partitions = Vec::new();
+
+        let mbr = self.read_at(0, 512)?;
+        if mbr[510] == 0x55 && mbr[511] == 0xaa {
+            self.add(SignatureKind::Mbr, 0, vec![(0, 512)]);
+
+            for entry in mbr[446..510].chunks(16) {
+                match entry[4] {
+                    // empty, extended and GPT protective entries don't hold a filesystem
+                    0x00 | 0x05 | 0x0f | 0x85 | 0xee => {}
+                    _ => {
+                        let start = le32(entry, 8) as u64 * 512;
+                        let size = le32(entry, 12) as u64 * 512;
+                        partitions.push((start, size));
+                    }
+                }
+            }
+        }
+
+        for sector_size in &[512u64, 4096u64] {
+            let header = self.read_at(*sector_size, 512)?;
+            if &header[0..8] == b"EFI PART" {
+                partitions = self.probe_gpt(*sector_size, &header)?;
+                break;
+            }
+        }
+
+        Ok(partitions
+            .into_iter()
+            .filter(|(start, size)| *size > 0 && *start < self.total_size)
+            .map(|(start, size)| (start, size.min(self.total_size - start)))
+            .collect())
+    }
+
+    fn probe_gpt(&mut self, sector_size: u64, header: &[u8]) -> Result<Vec<(u64, u64)>> {
+        let entries_count = le32(header, 80).min(MAX_GPT_ENTRIES) as u64;
+        let entry_size = le32(header, 84) as u64;
+        let entries_start = le64(header, 72)
+            .checked_mul(sector_size)
+            .filter(|s| *s < self.total_size);
+        let last_lba = (self.total_size / sector_size).checked_sub(1);
+        // anything else comes from a malformed header
+        let (entries_start, last_lba) = match (entries_start, last_lba) {
+            (Some(s), Some(l))
+                if entry_size.is_power_of_two()
+                    && (MIN_GPT_ENTRY_SIZE..=MAX_GPT_ENTRY_SIZE).contains(&entry_size) =>
+            {
+                (s, l)
+            }
+            _ => return Ok(Vec::new()),
+        };
+        let backup_lba = match le64(header, 32) {
+            lba if lba > 1 && lba <= last_lba => lba,
+            _ => last_lba,
+        };
+        let entries_len = entries_count * entry_size;
+
+        self.add(
+            SignatureKind::Gpt,
+            sector_size,
+            vec![
+                (0, entries_start + entries_len),
+                (
+                    (backup_lba * sector_size).saturating_sub(entries_len),
+                    (backup_lba + 1) * sector_size,
+                ),
+            ],
+        );
+
+        let entries = self.read_at(entries_start, entries_len as usize)?;
+        Ok(entries
+            .chunks(entry_size as usize)
+            .filter(|e| e[0..16].iter().any(|b| *b != 0))
+            .map(|e| {
+                let first = le64(e, 32);
+                let last = le64(e, 40);
+                (
+                    first.saturating_mul(sector_size),
+                    last.saturating_sub(first)
+                        .saturating_add(1)
+                        .saturating_mul(sector_size),
+                )
+            })
+            .collect())
+    }
+}
+
+/// Decodes the NTFS data runs into the (cluster, number of clusters) extents, skipping the
+/// sparse ones. Stops at the first malformed run.
+fn data_runs(runs: &[u8]) -> Vec<(u64, u64)> {
+    let mut extents = Vec::new();
+    let mut lcn: i64 = 0;
+    let mut p = 0;
+    while p < runs.len() && runs[p] != 0 {
+        let (len_size, offset_size) = ((runs[p] & 0xf) as usize, (runs[p] >> 4) as usize);
+        if len_size == 0
+            || len_size > 8
+            || offset_size > 8
+            || p + 1 + len_size + offset_size > runs.len()
+        {
+            break;
+        }
+        let clusters = le_var(&runs[p + 1..p + 1 + len_size]);
+        if offset_size > 0 {
+            let delta = le_var(&runs[p + 1 + len_size..p + 1 + len_size + offset_size]);
+            // sign-extended relative offset
+            let shift = 64 - 8 * offset_size as u32;
+            lcn += ((delta << shift) as i64) >> shift;
+            if lcn < 0 {
+                break;
+            }
+            extents.push((lcn as u64, clusters));
+        }
+        p += 1 + len_size + offset_size;
+    }
+    extents
+}
+
+fn le_var(data: &[u8]) -> u64 {
+    data.iter().rev().fold(0, |v, b| (v << 8) | *b as u64)
+}
+
+/// Groups holding backup superblocks when the `sparse_super` feature is enabled:
+/// 1 and powers of 3, 5 and 7.
+fn is_sparse_group(group: u64) -> bool {
+    if group <= 1 {
+        return true;
+    }
+    [3, 5, 7].iter().any(|base| {
+        let mut g = group;
+        while g.is_multiple_of(*base) {
+            g /= base;
+        }
+        g == 1
+    })
+}
+
+fn le16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn le32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn le64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn be16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn be32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn be64(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::memory::InMemoryStorage;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn ext4_superblock(data: &mut [u8], start: usize, blocks: u32) {
+        let sb = start + 1024;
+        put(data, sb + 4, &blocks.to_le_bytes());
+        put(data, sb + 20, &0u32.to_le_bytes());
+        put(data, sb + 24, &2u32.to_le_bytes()); // 4k blocks
+        put(data, sb + 32, &32u32.to_le_bytes()); // blocks per group
+        put(data, sb + 56, &0xef53u16.to_le_bytes());
+        put(data, sb + 100, &1u32.to_le_bytes()); // sparse_super
+    }
+
+    #[test]
+    fn test_probe_gpt_with_ext4() {
+        let size = 8 * MB;
+        let mut data = vec![0u8; size as usize];
+
+        // protective MBR
+        put(&mut data, 446 + 4, &[0xee]);
+        put(&mut data, 510, &[0x55, 0xaa]);
+
+        // GPT header and a single partition from 1M to 7M
+        put(&mut data, 512, b"EFI PART");
+        put(&mut data, 512 + 32, &(size / 512 - 1).to_le_bytes());
+        put(&mut data, 512 + 72, &2u64.to_le_bytes());
+        put(&mut data, 512 + 80, &128u32.to_le_bytes());
+        put(&mut data, 512 + 84, &128u32.to_le_bytes());
+        put(&mut data, 1024, &[1u8; 16]);
+        put(&mut data, 1024 + 32, &2048u64.to_le_bytes());
+        put(&mut data, 1024 + 40, &(14336u64 - 1).to_le_bytes());
+
+        ext4_superblock(&mut data, MB as usize, 1536);
+
+        let mut storage = InMemoryStorage::with_data(data);
+        let signatures = probe(&mut storage, size).unwrap();
+
+        let kinds: Vec<_> = signatures.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![SignatureKind::Mbr, SignatureKind::Gpt, SignatureKind::Ext]
+        );
+
+        assert_eq!(
+            signatures[1].regions,
+            vec![
+                WipeRange::new(0, 2 * 512 + 128 * 128),
+                WipeRange::new(size - 512 - 128 * 128, size)
+            ]
+        );
+
+        let ext = &signatures[2];
+        assert_eq!(ext.offset, MB);
+        assert_eq!(ext.regions[0], WipeRange::new(MB, MB + 8192));
+        // backups in groups 1, 3, 5, 7, 9, 25 and 27
+        assert_eq!(ext.regions.len(), 8);
+        assert_eq!(ext.regions[1].start, MB + 32 * 4096);
+
+        let ranges = metadata_ranges(&signatures, size, MB as usize).unwrap();
+        assert_eq!(
+            ranges,
+            vec![
+                WipeRange::new(0, 3 * MB),
+                WipeRange::new(4 * MB, 5 * MB),
+                WipeRange::new(7 * MB, 8 * MB)
+            ]
+        );
+        // a malformed header is skipped
+        let malformed: [(usize, &[u8]); 3] = [
+            (84, &u32::MAX.to_le_bytes()),
+            (84, &96u32.to_le_bytes()),
+            (72, &u64::MAX.to_le_bytes()),
+        ];
+        for (offset, bytes) in malformed.iter() {
+            let mut data = storage.file.into_inner();
+            put(&mut data, 512 + 72, &2u64.to_le_bytes());
+            put(&mut data, 512 + 84, &128u32.to_le_bytes());
+            put(&mut data, 512 + offset, bytes);
+            storage = InMemoryStorage::with_data(data);
+            let signatures = probe(&mut storage, size).unwrap();
+            assert!(signatures.iter().all(|s| s.kind != SignatureKind::Gpt));
+        }
+    }
+
+    #[test]
+    fn test_quick_ranges_cover_edges_and_backups() {
+        let size = 64 * MB;
+        let mut data = vec![0u8; size as usize];
+        ext4_superblock(&mut data, 0, 16384);
+        let mut storage = InMemoryStorage::with_data(data);
+        let signatures = probe(&mut storage, size).unwrap();
+        assert_eq!(signatures[0].kind, SignatureKind::Ext);
+
+        // groups of 128k, the backups up to group 9 are within the first 2M,
+        // the ones in groups 25, 27, 49, 81, 125, 243 and 343 are wiped on their own
+        let ranges = quick_ranges(&signatures, size, 2 * MB, 4096).unwrap();
+        assert_eq!(ranges.first(), Some(&WipeRange::new(0, 2 * MB)));
+        assert_eq!(ranges.last(), Some(&WipeRange::new(62 * MB, size)));
+        assert_eq!(ranges.len(), 9);
+        // a backup superblock followed by the 4 blocks of the group descriptors
+        assert_eq!(
+            ranges[1],
+            WipeRange::new(25 * 128 * 1024, 25 * 128 * 1024 + 5 * 4096)
+        );
+
+        let blank = quick_ranges(&[], MB, 2 * MB, 4096).unwrap();
+        assert_eq!(blank, vec![WipeRange::new(0, MB)]);
+    }
+
+    #[test]
+    fn test_probe_ext4_inode_tables() {
+        let size = 8 * MB;
+        let mut data = vec![0u8; size as usize];
+        ext4_superblock(&mut data, 0, 2048);
+        put(&mut data, 1024 + 40, &128u32.to_le_bytes()); // inodes per group
+        put(&mut data, 1024 + 76, &1u32.to_le_bytes()); // dynamic revision
+        put(&mut data, 1024 + 88, &256u16.to_le_bytes()); // inode size
+                                                          // descriptors of the 64 groups, the first one pointing past the descriptors table
+        put(&mut data, 4096 + 8, &3u32.to_le_bytes());
+        put(&mut data, 4096 + 63 * 32 + 8, &2000u32.to_le_bytes());
+
+        let mut storage = InMemoryStorage::with_data(data);
+        let signatures = probe(&mut storage, size).unwrap();
+        let regions = &signatures[0].regions;
+        assert_eq!(regions[0], WipeRange::new(0, 8192));
+        assert!(regions.contains(&WipeRange::new(3 * 4096, 3 * 4096 + 32 * 1024)));
+        assert!(regions.contains(&WipeRange::new(2000 * 4096, 2000 * 4096 + 32 * 1024)));
+    }
+
+    #[test]
+    fn test_probe_ntfs_mft_runs() {
+        let size = 8 * MB;
+        let mut data = vec![0u8; size as usize];
+        put(&mut data, 3, b"NTFS    ");
+        put(&mut data, 11, &512u16.to_le_bytes());
+        data[13] = 8; // 4k clusters
+        put(&mut data, 40, &(size / 512 - 1).to_le_bytes());
+        put(&mut data, 48, &16u64.to_le_bytes()); // MFT at 64k
+        put(&mut data, 56, &2u64.to_le_bytes());
+        data[64] = 0xf6; // 1k records
+
+        let mft = 16 * 4096;
+        put(&mut data, mft, b"FILE");
+        put(&mut data, mft + 4, &48u16.to_le_bytes());
+        put(&mut data, mft + 6, &3u16.to_le_bytes());
+        put(&mut data, mft + 48, &[0x01, 0x00, 0x11, 0x40, 0x00, 0x00]);
+        put(&mut data, mft + 20, &56u16.to_le_bytes());
+        // $DATA with 64 clusters at 16 and 32 clusters 500 clusters further, which
+        // crosses the end of the first sector patched by the fixup
+        let attr = mft + 56;
+        put(&mut data, attr, &0x80u32.to_le_bytes());
+        put(&mut data, attr + 4, &480u32.to_le_bytes());
+        data[attr + 8] = 1;
+        put(&mut data, attr + 32, &(454u16).to_le_bytes());
+        put(&mut data, attr + 454, &[0x11, 0x40, 0x10, 0x21, 0x20, 0xf4]);
+        put(&mut data, attr + 460, &[0x01, 0x00]);
+        put(&mut data, mft + 510, &[0x01, 0x00]);
+        put(&mut data, attr + 480, &0xffff_ffffu32.to_le_bytes());
+
+        let mut storage = InMemoryStorage::with_data(data);
+        let signatures = probe(&mut storage, size).unwrap();
+        assert_eq!(signatures[0].kind, SignatureKind::Ntfs);
+        let regions = &signatures[0].regions;
+        assert!(regions.contains(&WipeRange::new(16 * 4096, 80 * 4096)));
+        assert!(regions.contains(&WipeRange::new(516 * 4096, 548 * 4096)));
+
+        // a corrupt record size falls back to the start of the MFT
+        for (record_size, clusters) in [(0x00, 8), (0xff, 8), (0xfd, 8), (0x01, 0)] {
+            let mut data = storage.file.into_inner();
+            data[64] = record_size;
+            data[13] = clusters;
+            storage = InMemoryStorage::with_data(data);
+            let signatures = probe(&mut storage, size).unwrap();
+            assert_eq!(signatures[0].kind, SignatureKind::Ntfs);
+        }
+    }
+
+    #[test]
+    fn test_probe_apfs_container() {
+        let size = 8 * MB;
+        let mut data = vec![0u8; size as usize];
+        put(&mut data, 32, APFS_MAGIC);
+        put(&mut data, 36, &4096u32.to_le_bytes());
+        put(&mut data, 104, &8u32.to_le_bytes());
+        put(&mut data, 108, &(16 | APFS_NONCONTIGUOUS).to_le_bytes());
+        put(&mut data, 112, &1u64.to_le_bytes());
+        put(&mut data, 120, &100u64.to_le_bytes());
+        put(&mut data, 160, &200u64.to_le_bytes());
+        put(&mut data, 200 * 4096 + 48, &201u64.to_le_bytes());
+
+        let mut storage = InMemoryStorage::with_data(data);
+        let signatures = probe(&mut storage, size).unwrap();
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0].kind, SignatureKind::Apfs);
+        assert_eq!(
+            signatures[0].regions,
+            vec![
+                WipeRange::new(0, 4096),
+                WipeRange::new(4096, 9 * 4096),
+                WipeRange::new(200 * 4096, 201 * 4096),
+                WipeRange::new(201 * 4096, 202 * 4096),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_probe_luks_volume() {
+        let size = 4 * MB;
+        let mut data = vec![0u8; size as usize];
+        put(&mut data, 0, b"LUKS\xba\xbe");
+        put(&mut data, 6, &1u16.to_be_bytes());
+        put(&mut data, 104, &4096u32.to_be_bytes());
+
+        let mut storage = InMemoryStorage::with_data(data);
+        let signatures = probe(&mut storage, size).unwrap();
+
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0].kind, SignatureKind::Luks);
+        assert_eq!(signatures[0].regions, vec![WipeRange::new(0, 2 * MB)]);
+    }
+
+    #[test]
+    fn test_probe_raid_and_lvm_members() {
+        let size = 4 * MB;
+        let mut data = vec![0u8; size as usize];
+        put(&mut data, 4096, &MD_MAGIC.to_le_bytes());
+        let mut storage = InMemoryStorage::with_data(data);
+        let signatures = probe(&mut storage, size).unwrap();
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0].kind, SignatureKind::MdRaid);
+        assert_eq!(signatures[0].regions, vec![WipeRange::new(4096, 8192)]);
+
+        // legacy superblock near the end of a partition
+        let mut data = vec![0u8; size as usize];
+        put(
+            &mut data,
+            (size - 64 * 1024) as usize,
+            &MD_MAGIC.to_le_bytes(),
+        );
+        let mut storage = InMemoryStorage::with_data(data);
+        let signatures = probe(&mut storage, size).unwrap();
+        assert_eq!(signatures[0].kind, SignatureKind::MdRaid);
+        assert_eq!(signatures[0].offset, size - 64 * 1024);
+
+        let mut data = vec![0u8; size as usize];
+        put(&mut data, 512, b"LABELONE");
+        put(&mut data, 512 + 24, b"LVM2 001");
+        let mut storage = InMemoryStorage::with_data(data);
+        let signatures = probe(&mut storage, size).unwrap();
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0].kind, SignatureKind::Lvm);
+        assert_eq!(signatures[0].regions, vec![WipeRange::new(0, MB)]);
+    }
+
+    #[test]
+    fn test_probe_blank_device() {
+        let mut storage = InMemoryStorage::with_data(vec![0u8; MB as usize]);
+        assert!(probe(&mut storage, MB).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sparse_groups() {
+        let groups: Vec<u64> = (0..50).filter(|g| is_sparse_group(*g)).collect();
+        assert_eq!(groups, vec![0, 1, 3, 5, 7, 9, 25, 27, 49]);
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// A half-open byte range `[start, end)` of a device to be wiped.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WipeRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl WipeRange {
+    pub fn new(start: u64, end: u64) -> Self {
+        WipeRange { start, end }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    /// Widens the range to the block boundaries, without going past the end of the device.
+    pub fn expand_to_blocks(&self, block_size: usize, total_size: u64) -> WipeRange {
+        let block_size = block_size as u64;
+        WipeRange {
+            start: self.start - self.start % block_size,
+            end: self
+                .end
+                .div_ceil(block_size)
+                .saturating_mul(block_size)
+                .min(total_size),
+        }
+    }
+
+    /// Whether both ends are on the alignment boundary, except the end of the device.
+    pub fn is_aligned(&self, alignment: usize, total_size: u64) -> bool {
+        let alignment = alignment as u64;
+        self.start.is_multiple_of(alignment)
+            && (self.end.is_multiple_of(alignment) || self.end == total_size)
+    }
+
+    /// Byte ranges covered by the given (sorted) blocks, with adjacent blocks merged together.
+    pub fn from_blocks(blocks: &[u32], block_size: usize, total_size: u64) -> Vec<WipeRange> {
+        let block_size = block_size as u64;
+        let mut ranges: Vec<WipeRange> = Vec::new();
+        for b in blocks {
+            let start = *b as u64 * block_size;
+            let end = (start + block_size).min(total_size);
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(WipeRange::new(start, end)),
+            }
+        }
+        ranges
+    }
+
+    /// Sorts the ranges, merges the overlapping or adjacent ones and makes sure they fit
+    /// the device and are aligned, e.g. to the sectors. They don't have to be aligned to the
+    /// blocks, the last block of a range is shorter then.
+    pub fn normalize(
+        mut ranges: Vec<WipeRange>,
+        total_size: u64,
+        alignment: usize,
+    ) -> Result<Vec<WipeRange>> {
+        if ranges.is_empty() {
+            return Err(anyhow!("At least one range is required"));
+        }
+
+        for r in &ranges {
+            if r.is_empty() {
+                return Err(anyhow!("Range {} is empty", r));
+            }
+            if r.end > total_size {
+                return Err(anyhow!("Range {} is beyond the end of the device", r));
+            }
+            if !r.is_aligned(alignment, total_size) {
+                return Err(anyhow!("Range {} is not aligned to {} bytes", r, alignment));
+            }
+        }
+
+        ranges.sort_by_key(|r| r.start);
+
+        let mut merged: Vec<WipeRange> = Vec::with_capacity(ranges.len());
+        for r in ranges {
+            match merged.last_mut() {
+                Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                _ => merged.push(r),
+            }
+        }
+
+        Ok(merged)
+    }
+}
+
+impl Display for WipeRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::*;
+
+    #[test]
+    fn test_normalize_sorts_and_merges() {
+        let ranges = WipeRange::normalize(
+            vec![
+                WipeRange::new(4096, 8192),
+                WipeRange::new(0, 1024),
+                WipeRange::new(1024, 2048),
+                WipeRange::new(6144, 10000),
+            ],
+            10000,
+            1024,
+        )
+        .unwrap();
+
+        assert_eq!(
+            ranges,
+            vec![WipeRange::new(0, 2048), WipeRange::new(4096, 10000)]
+        );
+    }
+
+    #[test]
+    fn test_expand_to_blocks() {
+        assert_eq!(
+            WipeRange::new(1000, 1100).expand_to_blocks(1024, 10000),
+            WipeRange::new(0, 2048)
+        );
+        assert_eq!(
+            WipeRange::new(9300, 9500).expand_to_blocks(1024, 10000),
+            WipeRange::new(9216, 10000)
+        );
+    }
+
+    #[test]
+    fn test_normalize_rejects_invalid_ranges() {
+        assert_matches!(WipeRange::normalize(vec![], 10000, 1024), Err(_));
+        assert_matches!(
+            WipeRange::normalize(vec![WipeRange::new(1024, 1024)], 10000, 1024),
+            Err(_)
+        );
+        assert_matches!(
+            WipeRange::normalize(vec![WipeRange::new(0, 20000)], 10000, 1024),
+            Err(_)
+        );
+        assert_matches!(
+            WipeRange::normalize(vec![WipeRange::new(512, 2048)], 10000, 1024),
+            Err(_)
+        );
+        assert_matches!(
+            WipeRange::normalize(vec![WipeRange::new(0, 1500)], 10000, 1024),
+            Err(_)
+        );
+        assert_eq!(
+            WipeRange::normalize(vec![WipeRange::new(9728, 10000)], 10000, 512).unwrap(),
+            vec![WipeRange::new(9728, 10000)]
+        );
+    }
+}
@@ -0,0 +1,791 @@
+use crate::actions::probe::Signature;
+use crate::actions::timing::{format_duration, StageTimer, StageTiming};
+use crate::actions::{Verify, WipeEvent, WipeEventReceiver, WipeRange, WipeState, WipeTask};
+use crate::storage::StorageDetails;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Erasure report produced after the wipe is completed.
+#[derive(Serialize, Debug, Clone)]
+pub struct WipeReport {
+    pub tool: String,
+    pub device: String,
+    pub serial: Option<String>,
+    pub size: u64,
+    pub storage_type: String,
+    pub scheme: String,
+    pub stages: Vec<String>,
+    pub block_size: usize,
+    pub ranges: Option<Vec<WipeRange>>,
+    /// Partition tables and headers found on the device and wiped by a targeted scheme.
+    pub destroyed: Vec<String>,
+    pub operator: Option<String>,
+    pub started: String,
+    pub completed: String,
+    pub result: String,
+    pub error: Option<String>,
+    pub verification: String,
+    pub verified_stages: usize,
+    pub verified_blocks: u64,
+    pub skipped_stages: Vec<usize>,
+    pub bad_blocks: u32,
+    pub mismatched_ranges: Vec<WipeRange>,
+    pub stage_timings: Vec<StageTiming>,
+    /// Nonce the sectors of the evidence archive were sampled with, when one was exported.
+    pub evidence_nonce: Option<String>,
+    pub reverification: Option<Reverification>,
+}
+
+/// Sampled verification repeated after a cool-down, catching the drives which acknowledged
+/// writes that didn't persist.
+#[derive(Serialize, Debug, Clone)]
+pub struct Reverification {
+    pub started: String,
+    pub completed: String,
+    pub result: String,
+    pub error: Option<String>,
+    pub verification: String,
+    pub verified_blocks: u64,
+    pub mismatched_ranges: Vec<WipeRange>,
+}
+
+impl Reverification {
+    /// Takes the outcome from the report of the verification run.
+    pub fn new(report: &WipeReport) -> Self {
+        Reverification {
+            started: report.started.clone(),
+            completed: report.completed.clone(),
+            result: report.result.clone(),
+            error: report.error.clone(),
+            verification: report.verification.clone(),
+            verified_blocks: report.verified_blocks,
+            mismatched_ranges: report.mismatched_ranges.clone(),
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            "Re-verification after cool-down:".to_string(),
+            format!("  Started: {}", self.started),
+            format!("  Completed: {}", self.completed),
+            format!("  Result: {}", self.result),
+        ];
+        if let Some(e) = &self.error {
+            lines.push(format!("  Error: {}", e));
+        }
+        lines.push(format!(
+            "  Verification: {} ({} block(s) verified)",
+            self.verification, self.verified_blocks
+        ));
+        if !self.mismatched_ranges.is_empty() {
+            lines.push("  Mismatched ranges:".to_string());
+            for r in &self.mismatched_ranges {
+                lines.push(format!("    {} - {}", r.start, r.end));
+            }
+        }
+        lines
+    }
+}
+
+impl WipeReport {
+    /// Human readable representation of the report, one line per field.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            "Lethe Wipe Report".to_string(),
+            String::new(),
+            format!("Tool: {}", self.tool),
+            format!("Device: {}", self.device),
+            format!("Serial number: {}", self.serial.as_deref().unwrap_or("n/a")),
+            format!("Size: {} bytes", self.size),
+            format!("Type: {}", self.storage_type),
+            format!("Operator: {}", self.operator.as_deref().unwrap_or("n/a")),
+            String::new(),
+            format!("Scheme: {}", self.scheme),
+        ];
+        for (i, s) in self.stages.iter().enumerate() {
+            lines.push(format!("  {}. {}", i + 1, s));
+        }
+        lines.push(format!("Block size: {} bytes", self.block_size));
+        if let Some(ranges) = &self.ranges {
+            lines.push("Ranges:".to_string());
+            for r in ranges {
+                lines.push(format!("  {} - {}", r.start, r.end));
+            }
+        }
+        if !self.destroyed.is_empty() {
+            lines.push("Destroyed structures:".to_string());
+            for d in &self.destroyed {
+                lines.push(format!("  {}", d));
+            }
+        }
+        lines.push(String::new());
+        lines.push(format!("Started: {}", self.started));
+        lines.push(format!("Completed: {}", self.completed));
+        lines.push(format!("Result: {}", self.result));
+        if let Some(e) = &self.error {
+            lines.push(format!("Error: {}", e));
+        }
+        lines.push(format!(
+            "Verification: {} ({} stage(s), {} block(s) verified)",
+            self.verification, self.verified_stages, self.verified_blocks
+        ));
+        if !self.skipped_stages.is_empty() {
+            lines.push(format!(
+                "Skipped stages: {} (not supported by the device)",
+                self.skipped_stages
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        lines.push(format!("Skipped bad blocks: {}", self.bad_blocks));
+        if let Some(nonce) = &self.evidence_nonce {
+            lines.push(format!("Evidence nonce: {}", nonce));
+        }
+        if !self.mismatched_ranges.is_empty() {
+            lines.push("Mismatched ranges:".to_string());
+            for r in &self.mismatched_ranges {
+                lines.push(format!("  {} - {}", r.start, r.end));
+            }
+        }
+        if !self.stage_timings.is_empty() {
+            lines.push(String::new());
+            lines.push("Stage timings:".to_string());
+            for t in &self.stage_timings {
+                lines.push(format!("  {}. {}", t.stage, describe_timing(t)));
+            }
+        }
+        if let Some(r) = &self.reverification {
+            lines.push(String::new());
+            lines.extend(r.lines());
+        }
+        lines
+    }
+
+    /// Writes the report as PDF if the path has a `.pdf` extension or as JSON otherwise.
+    /// A SHA-256 digest of the written file is saved next to it (in `sha256sum` format).
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        write_document(path.as_ref(), self, &self.lines())
+    }
+}
+
+/// Reports of several schemes run back-to-back on the same device in one session.
+#[derive(Serialize, Debug, Clone)]
+pub struct CombinedReport {
+    pub tool: String,
+    pub device: String,
+    /// Number of schemes planned for the session, a failed run stops the rest.
+    pub planned_runs: usize,
+    pub result: String,
+    pub runs: Vec<WipeReport>,
+}
+
+impl CombinedReport {
+    pub fn new(runs: Vec<WipeReport>, planned_runs: usize) -> Self {
+        let success = runs.len() == planned_runs && runs.iter().all(|r| r.error.is_none());
+        CombinedReport {
+            tool: format!("lethe {}", env!("CARGO_PKG_VERSION")),
+            device: runs.first().map(|r| r.device.clone()).unwrap_or_default(),
+            planned_runs,
+            result: if success { "success" } else { "failed" }.to_string(),
+            runs,
+        }
+    }
+
+    /// Summary of the session followed by the lines of each run's report.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            "Lethe Combined Wipe Report".to_string(),
+            String::new(),
+            format!("Tool: {}", self.tool),
+            format!("Device: {}", self.device),
+            format!("Schemes: {} of {} run", self.runs.len(), self.planned_runs),
+            format!("Result: {}", self.result),
+        ];
+        for (i, run) in self.runs.iter().enumerate() {
+            lines.push(String::new());
+            lines.push(format!(
+                "Run {}/{}: {}",
+                i + 1,
+                self.planned_runs,
+                run.scheme
+            ));
+            // the title is replaced by the run header
+            lines.extend(run.lines().into_iter().skip(2).map(|l| format!("  {}", l)));
+        }
+        lines
+    }
+
+    /// Same as [`WipeReport::write`].
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        write_document(path.as_ref(), self, &self.lines())
+    }
+}
+
+fn write_document<T: Serialize>(path: &Path, value: &T, lines: &[String]) -> Result<PathBuf> {
+    let is_pdf = path
+        .extension()
+        .map(|e| e.eq_ignore_ascii_case("pdf"))
+        .unwrap_or(false);
+
+    let content = if is_pdf {
+        render_pdf(lines)
+    } else {
+        let mut json = serde_json::to_vec_pretty(value)?;
+        json.push(b'\n');
+        json
+    };
+
+    std::fs::write(path, &content)
+        .context(format!("Unable to write report {}", path.to_string_lossy()))?;
+
+    let digest_path = digest_path(path);
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    std::fs::write(
+        &digest_path,
+        format!("{}  {}\n", sha256_hex(&content), file_name),
+    )
+    .context(format!(
+        "Unable to write report digest {}",
+        digest_path.to_string_lossy()
+    ))?;
+
+    Ok(digest_path)
+}
+
+fn describe_timing(timing: &StageTiming) -> String {
+    if timing.skipped {
+        return format!("{}: skipped", timing.description);
+    }
+    let mut parts = vec![timing.description.clone()];
+    for (name, phase) in &[("write", timing.write), ("verify", timing.verify)] {
+        if let Some(p) = phase {
+            parts.push(format!(
+                "{} {} at {} bytes/s",
+                name,
+                format_duration(p.duration()),
+                p.throughput
+            ));
+        }
+    }
+    parts.push(format!("retries: {}", timing.retries));
+    parts.join(", ")
+}
+
+pub(crate) fn digest_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Collects the details required for a report from the wipe events.
+pub struct ReportRecorder {
+    device_id: String,
+    details: StorageDetails,
+    started: Option<SystemTime>,
+    verified_stages: usize,
+    verified_blocks: u64,
+    skipped_stages: Vec<usize>,
+    verification_failed: bool,
+    timer: StageTimer,
+    evidence_nonce: Option<String>,
+    destroyed: Vec<String>,
+}
+
+impl ReportRecorder {
+    pub fn new(device_id: &str, details: &StorageDetails) -> Self {
+        ReportRecorder {
+            device_id: device_id.to_string(),
+            details: details.clone(),
+            started: None,
+            verified_stages: 0,
+            verified_blocks: 0,
+            skipped_stages: Vec::new(),
+            verification_failed: false,
+            timer: StageTimer::new(),
+            evidence_nonce: None,
+            destroyed: Vec::new(),
+        }
+    }
+
+    /// Records the nonce of the evidence archive exported after the wipe.
+    pub fn with_evidence_nonce(mut self, nonce: &str) -> Self {
+        self.evidence_nonce = Some(nonce.to_string());
+        self
+    }
+
+    /// Records the structures the targeted scheme was applied to.
+    pub fn with_destroyed(mut self, signatures: &[Signature]) -> Self {
+        self.destroyed = signatures
+            .iter()
+            .map(|s| format!("{} at {}", s.kind, s.offset))
+            .collect();
+        self
+    }
+
+    /// Returns the report once the wipe is completed.
+    pub fn record(
+        &mut self,
+        task: &WipeTask,
+        state: &WipeState,
+        event: &WipeEvent,
+    ) -> Option<WipeReport> {
+        self.timer.record(task, state, event);
+        match event {
+            WipeEvent::Started => {
+                // the same recorder may be used for several runs
+                *self = ReportRecorder {
+                    evidence_nonce: self.evidence_nonce.take(),
+                    destroyed: std::mem::take(&mut self.destroyed),
+                    ..ReportRecorder::new(&self.device_id, &self.details)
+                };
+                self.started = Some(SystemTime::now());
+            }
+            WipeEvent::StageCompleted(result) if state.at_verification => {
+                self.verification_failed = result.is_some();
+                if result.is_none() {
+                    self.verified_stages += 1;
+                    self.verified_blocks += state.verified_blocks;
+                }
+            }
+            WipeEvent::StageSkipped => self.skipped_stages.push(state.stage + 1),
+            WipeEvent::Completed(result) => {
+                return Some(self.build(task, state, result.as_deref()))
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn build(
+        &self,
+        task: &WipeTask,
+        state: &WipeState,
+        error: Option<&anyhow::Error>,
+    ) -> WipeReport {
+        let verification = match task.verify {
+            Verify::No => "not performed".to_string(),
+            _ if self.verification_failed => "failed".to_string(),
+            _ if error.is_some() => "incomplete".to_string(),
+            Verify::Sample { percent, .. } => format!("passed ({}% sample)", percent),
+            _ => "passed".to_string(),
+        };
+
+        WipeReport {
+            tool: format!("lethe {}", env!("CARGO_PKG_VERSION")),
+            device: self.device_id.clone(),
+            serial: self.details.serial.clone(),
+            size: task.total_size,
+            storage_type: self.details.storage_type.to_string(),
+            scheme: task.scheme.description.clone(),
+            stages: task.scheme.stages.iter().map(|s| s.to_string()).collect(),
+            block_size: task.block_size,
+            ranges: if task.is_partial() {
+                Some(task.ranges.clone())
+            } else {
+                None
+            },
+            destroyed: self.destroyed.clone(),
+            operator: task.operator.as_ref().map(|o| o.to_string()),
+            started: format_timestamp(self.started.unwrap_or_else(SystemTime::now)),
+            completed: format_timestamp(SystemTime::now()),
+            result: if error.is_none() { "success" } else { "failed" }.to_string(),
+            error: error.map(|e| format!("{:#}", e)),
+            verification,
+            verified_stages: self.verified_stages,
+            verified_blocks: self.verified_blocks,
+            skipped_stages: self.skipped_stages.clone(),
+            bad_blocks: state.bad_blocks.borrow().total_marked(),
+            mismatched_ranges: task.block_ranges(&state.mismatched_blocks.borrow().all_marked()),
+            stage_timings: self.timer.stages().to_vec(),
+            evidence_nonce: self.evidence_nonce.clone(),
+            reverification: None,
+        }
+    }
+}
+
+/// Passes the events through to another receiver and writes a report when the wipe is completed.
+/// A verification-only run after that is added to the report as its re-verification.
+pub struct ReportingReceiver {
+    inner: Box<dyn WipeEventReceiver>,
+    recorder: ReportRecorder,
+    path: PathBuf,
+    runs: usize,
+    reports: Vec<WipeReport>,
+}
+
+impl ReportingReceiver {
+    pub fn new<P: AsRef<Path>>(
+        inner: Box<dyn WipeEventReceiver>,
+        recorder: ReportRecorder,
+        path: P,
+    ) -> Self {
+        ReportingReceiver {
+            inner,
+            recorder,
+            path: path.as_ref().to_path_buf(),
+            runs: 1,
+            reports: Vec::new(),
+        }
+    }
+
+    /// Expects the given number of runs and writes a combined report of all of them,
+    /// after the last one or after the first failed one.
+    pub fn with_runs(mut self, runs: usize) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    fn save(&self, result: Result<PathBuf>) {
+        match result {
+            Ok(digest) => eprintln!(
+                "Report saved to {} (digest in {}).",
+                self.path.to_string_lossy(),
+                digest.to_string_lossy()
+            ),
+            Err(e) => eprintln!("Unable to save the report. {:#}", e),
+        }
+    }
+
+    fn write(&self) -> Result<PathBuf> {
+        match self.reports.last() {
+            Some(_) if self.runs > 1 => {
+                CombinedReport::new(self.reports.clone(), self.runs).write(&self.path)
+            }
+            Some(report) => report.write(&self.path),
+            None => Err(anyhow!("Nothing to report")),
+        }
+    }
+}
+
+impl WipeEventReceiver for ReportingReceiver {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        let report = self.recorder.record(task, state, &event);
+        self.inner.handle(task, state, event);
+
+        let report = match report {
+            Some(r) => r,
+            None => return,
+        };
+
+        if task.verify_only {
+            if let Some(last) = self.reports.last_mut() {
+                last.reverification = Some(Reverification::new(&report));
+                return self.save(self.write());
+            }
+        }
+
+        let failed = report.error.is_some();
+        self.reports.push(report);
+        if failed || self.reports.len() == self.runs {
+            self.save(self.write());
+        }
+    }
+}
+
+/// Formats the time as an RFC 3339 timestamp in UTC.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Renders the lines as a plain A4 PDF document using a standard font.
+fn render_pdf(lines: &[String]) -> Vec<u8> {
+    const LINES_PER_PAGE: usize = 60;
+
+    let pages: Vec<&[String]> = lines.chunks(LINES_PER_PAGE).collect();
+    let first_page_obj = 4;
+
+    let mut objects: Vec<String> = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|i| format!("{} 0 R", first_page_obj + i * 2))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_string(),
+    ];
+
+    for (i, page) in pages.iter().enumerate() {
+        let mut content = String::from("BT /F1 10 Tf 12 TL 50 800 Td\n");
+        for line in page.iter() {
+            content.push_str(&format!("({}) '\n", pdf_escape(line)));
+        }
+        content.push_str("ET");
+
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            first_page_obj + i * 2 + 1
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            content.len(),
+            content
+        ));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, o) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, o));
+    }
+
+    let xref = pdf.len();
+    pdf.push_str(&format!(
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    ));
+    for o in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", o));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+
+    pdf.into_bytes()
+}
+
+fn pdf_escape(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::probe::SignatureKind;
+    use crate::sanitization::SchemeRepo;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    fn task() -> WipeTask {
+        let schemes = SchemeRepo::default();
+        WipeTask::new(
+            schemes.find("random2x").unwrap().clone(),
+            Verify::Last,
+            4096,
+            1024,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_recorder_builds_report() {
+        let task = task();
+        let details = StorageDetails {
+            size: 4096,
+            serial: Some("S3Z9NB0K".to_string()),
+            ..Default::default()
+        };
+        let mut recorder = ReportRecorder::new("/dev/test", &details);
+        let mut state = WipeState::default();
+
+        assert!(recorder
+            .record(&task, &state, &WipeEvent::Started)
+            .is_none());
+        state.at_verification = true;
+        let err = Rc::new(anyhow!("Verification failed!"));
+        recorder.record(&task, &state, &WipeEvent::StageCompleted(Some(err)));
+        recorder.record(&task, &state, &WipeEvent::StageCompleted(None));
+
+        let report = recorder
+            .record(&task, &state, &WipeEvent::Completed(None))
+            .unwrap();
+
+        assert_eq!(report.device, "/dev/test");
+        assert_eq!(report.serial.as_deref(), Some("S3Z9NB0K"));
+        assert_eq!(report.stages.len(), 2);
+        assert_eq!(report.result, "success");
+        assert_eq!(report.verification, "passed");
+        assert_eq!(report.verified_stages, 1);
+        assert!(report.ranges.is_none());
+        assert!(report.destroyed.is_empty());
+    }
+
+    #[test]
+    fn test_report_is_written_with_digest() {
+        let task = task();
+        let mut recorder = ReportRecorder::new("/dev/test", &StorageDetails::default())
+            .with_destroyed(&[Signature {
+                kind: SignatureKind::Gpt,
+                offset: 512,
+                regions: Vec::new(),
+            }]);
+        recorder.record(&task, &WipeState::default(), &WipeEvent::Started);
+        let report = recorder
+            .record(&task, &WipeState::default(), &WipeEvent::Completed(None))
+            .unwrap();
+        assert!(report
+            .lines()
+            .contains(&"  GPT partition table at 512".to_string()));
+
+        for ext in &["json", "pdf"] {
+            let path = std::env::temp_dir().join(format!(
+                "lethe-report-test-{}.{}",
+                std::process::id(),
+                ext
+            ));
+            let digest_path = report.write(&path).unwrap();
+
+            let content = std::fs::read(&path).unwrap();
+            let digest = std::fs::read_to_string(&digest_path).unwrap();
+            assert!(digest.starts_with(&sha256_hex(&content)));
+
+            if *ext == "pdf" {
+                assert!(content.starts_with(b"%PDF-1.4"));
+            } else {
+                let parsed: serde_json::Value = serde_json::from_slice(&content).unwrap();
+                assert_eq!(parsed["device"], "/dev/test");
+                assert_eq!(parsed["destroyed"][0], "GPT partition table at 512");
+            }
+
+            std::fs::remove_file(&path).unwrap();
+            std::fs::remove_file(&digest_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_combined_report_of_several_runs() {
+        struct Discard;
+
+        impl WipeEventReceiver for Discard {
+            fn handle(&mut self, _task: &WipeTask, _state: &WipeState, _event: WipeEvent) {}
+        }
+
+        let path = std::env::temp_dir().join(format!(
+            "lethe-combined-report-test-{}.json",
+            std::process::id()
+        ));
+        let recorder = ReportRecorder::new("/dev/test", &StorageDetails::default());
+        let mut receiver = ReportingReceiver::new(Box::new(Discard), recorder, &path).with_runs(3);
+
+        let task = task();
+        let mut state = WipeState::default();
+        receiver.handle(&task, &state, WipeEvent::Started);
+        state.at_verification = true;
+        receiver.handle(&task, &state, WipeEvent::StageCompleted(None));
+        receiver.handle(&task, &state, WipeEvent::Completed(None));
+        assert!(!path.exists());
+
+        let state = WipeState::default();
+        let err = Rc::new(anyhow!("Mocked IO failure"));
+        receiver.handle(&task, &state, WipeEvent::Started);
+        receiver.handle(&task, &state, WipeEvent::Completed(Some(err)));
+
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(digest_path(&path)).unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_slice(&content).unwrap();
+        assert_eq!(parsed["result"], "failed");
+        assert_eq!(parsed["planned_runs"], 3);
+        assert_eq!(parsed["runs"][0]["result"], "success");
+        assert_eq!(parsed["runs"][0]["verified_stages"], 1);
+        assert_eq!(parsed["runs"][1]["result"], "failed");
+        assert_eq!(parsed["runs"][1]["verified_stages"], 0);
+    }
+
+    #[test]
+    fn test_reverification_is_added_to_report() {
+        struct Discard;
+
+        impl WipeEventReceiver for Discard {
+            fn handle(&mut self, _task: &WipeTask, _state: &WipeState, _event: WipeEvent) {}
+        }
+
+        let path = std::env::temp_dir().join(format!(
+            "lethe-reverify-report-test-{}.json",
+            std::process::id()
+        ));
+        let recorder = ReportRecorder::new("/dev/test", &StorageDetails::default());
+        let mut receiver = ReportingReceiver::new(Box::new(Discard), recorder, &path);
+
+        let task = task();
+        let state = WipeState::default();
+        receiver.handle(&task, &state, WipeEvent::Started);
+        receiver.handle(&task, &state, WipeEvent::Completed(None));
+        let parsed: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert!(parsed["reverification"].is_null());
+
+        let mut reverify = task.clone();
+        reverify.verify = Verify::sample(10).unwrap();
+        reverify.verify_only = true;
+        let mut state = WipeState::default();
+        receiver.handle(&reverify, &state, WipeEvent::Started);
+        state.at_verification = true;
+        state.verified_blocks = 3;
+        let err = Rc::new(anyhow!("Mismatch at 1024"));
+        receiver.handle(&reverify, &state, WipeEvent::StageCompleted(Some(err)));
+        let err = Rc::new(anyhow!("Verification failed"));
+        receiver.handle(&reverify, &state, WipeEvent::Completed(Some(err)));
+
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(digest_path(&path)).unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_slice(&content).unwrap();
+        assert_eq!(parsed["result"], "success");
+        assert_eq!(parsed["reverification"]["result"], "failed");
+        assert_eq!(parsed["reverification"]["verification"], "failed");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1709251199)),
+            "2024-02-29T23:59:59Z"
+        );
+    }
+}
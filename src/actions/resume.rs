@@ -0,0 +1,188 @@
+use crate::actions::{WipeState, WipeTask};
+use crate::sanitization::mem::AlignedBuffer;
+use crate::sanitization::{Stage, StreamingIterator};
+use crate::storage::StorageAccess;
+use anyhow::Result;
+
+/// Writes may reach the device out of order within its caches, so the blocks right before
+/// the detected boundary are all checked rather than sampled.
+pub const TAIL_CHECK_SIZE: u64 = 256 << 20;
+
+/// Blocks sampled in the rest of the filled part to confirm the boundary.
+const PREFIX_SAMPLES: u64 = 64;
+
+/// Detects how far an interrupted wipe got by looking for the boundary between the blocks
+/// already holding the data of a stage and the rest of the device. Only works for fill and
+/// pattern stages. The stages are checked from the last one, as the data of a stage is
+/// overwritten by the following ones.
+pub fn detect_progress(access: &mut dyn StorageAccess, task: &WipeTask) -> Result<WipeState> {
+    if task.is_partial() {
+        return Err(anyhow!("The progress of a partial wipe can't be detected"));
+    }
+    if task
+        .scheme
+        .stages
+        .iter()
+        .any(|s| matches!(s, Stage::Random { .. } | Stage::Discard))
+    {
+        return Err(anyhow!(
+            "The progress of random or discard stages can't be detected from the data"
+        ));
+    }
+
+    let mut prober = BoundaryProber::new(access, task);
+    for (i, stage) in task.scheme.stages.iter().enumerate().rev() {
+        let filled = prober.filled_blocks(stage)?;
+        if filled > 0 {
+            return Ok(WipeState {
+                stage: i,
+                position: (filled * task.block_size as u64).min(task.total_size),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(WipeState::default())
+}
+
+struct BoundaryProber<'a> {
+    access: &'a mut dyn StorageAccess,
+    total_size: u64,
+    block_size: usize,
+    io_alignment: usize,
+    blocks: u64,
+    buf: AlignedBuffer,
+}
+
+impl<'a> BoundaryProber<'a> {
+    fn new(access: &'a mut dyn StorageAccess, task: &WipeTask) -> Self {
+        BoundaryProber {
+            access,
+            total_size: task.total_size,
+            block_size: task.block_size,
+            io_alignment: task.io_alignment,
+            blocks: task.total_size.div_ceil(task.block_size as u64),
+            buf: AlignedBuffer::new(task.block_size, task.io_alignment),
+        }
+    }
+
+    /// Number of blocks from the start holding the data of the stage. Found with a binary
+    /// search, then confirmed by checking the blocks before the boundary, and searching
+    /// again below the first block which turns out not to be filled.
+    fn filled_blocks(&mut self, stage: &Stage) -> Result<u64> {
+        let tail_blocks = TAIL_CHECK_SIZE.div_ceil(self.block_size as u64);
+        let mut limit = self.blocks;
+
+        'search: loop {
+            let (mut low, mut high) = (0, limit);
+            while low < high {
+                let middle = low + (high - low) / 2;
+                if self.is_filled(stage, middle)? {
+                    low = middle + 1;
+                } else {
+                    high = middle;
+                }
+            }
+
+            let tail_start = low.saturating_sub(tail_blocks);
+            for block in tail_start..low {
+                if !self.is_filled(stage, block)? {
+                    limit = block;
+                    continue 'search;
+                }
+            }
+
+            for sample in 0..PREFIX_SAMPLES.min(tail_start) {
+                let block = sample * tail_start / PREFIX_SAMPLES.min(tail_start);
+                if !self.is_filled(stage, block)? {
+                    limit = block;
+                    continue 'search;
+                }
+            }
+
+            return Ok(low);
+        }
+    }
+
+    fn is_filled(&mut self, stage: &Stage, block: u64) -> Result<bool> {
+        let position = block * self.block_size as u64;
+        let len = (self.total_size - position).min(self.block_size as u64) as usize;
+        let data = &mut self.buf.as_mut_slice()[..len];
+
+        self.access.seek(position)?;
+        let mut filled = 0;
+        while filled < len {
+            match self.access.read(&mut data[filled..])? {
+                0 => return Ok(false),
+                n => filled += n,
+            }
+        }
+
+        let mut stream = stage.stream(
+            self.total_size,
+            self.block_size,
+            self.io_alignment,
+            position,
+        );
+        Ok(stream.next().map(|expected| expected == &data[..]) == Some(true))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::{Scheme, SchemeRepo};
+    use crate::storage::memory::InMemoryStorage;
+
+    const BLOCK: usize = 4096;
+
+    fn task(stages: Vec<Stage>, size: usize) -> WipeTask {
+        let scheme = Scheme {
+            description: "test".to_string(),
+            stages,
+        };
+        WipeTask::new(scheme, Verify::No, size as u64, BLOCK).unwrap()
+    }
+
+    #[test]
+    fn test_detect_progress_of_constant_fill() {
+        let size = 1000 * BLOCK + 100;
+        let mut data = vec![0xaa; size];
+        data[..377 * BLOCK].fill(0);
+        // a block written after the boundary, e.g. unrelated zeros
+        data[900 * BLOCK..901 * BLOCK].fill(0);
+        let task = task(vec![Stage::zero()], size);
+
+        let state = detect_progress(&mut InMemoryStorage::with_data(data.clone()), &task).unwrap();
+        assert_eq!((state.stage, state.position), (0, 377 * BLOCK as u64));
+
+        // a hole before the boundary which didn't make it to the device
+        data[10 * BLOCK] = 1;
+        let state = detect_progress(&mut InMemoryStorage::with_data(data.clone()), &task).unwrap();
+        assert_eq!(state.position, 10 * BLOCK as u64);
+
+        data.fill(0);
+        let state = detect_progress(&mut InMemoryStorage::with_data(data), &task).unwrap();
+        assert_eq!(state.position, size as u64);
+    }
+
+    #[test]
+    fn test_detect_progress_of_later_stages() {
+        let size = 100 * BLOCK;
+        let mut data = vec![0xff; size];
+        data[..42 * BLOCK].fill(0);
+        let task = task(vec![Stage::one(), Stage::zero()], size);
+        let state = detect_progress(&mut InMemoryStorage::with_data(data), &task).unwrap();
+        assert_eq!((state.stage, state.position), (1, 42 * BLOCK as u64));
+
+        let data = vec![0x55; size];
+        let state = detect_progress(&mut InMemoryStorage::with_data(data), &task).unwrap();
+        assert_eq!((state.stage, state.position), (0, 0));
+
+        let random = SchemeRepo::default().find("random").unwrap().clone();
+        let task = WipeTask::new(random, Verify::No, size as u64, BLOCK).unwrap();
+        let data = vec![0; size];
+        assert!(detect_progress(&mut InMemoryStorage::with_data(data), &task).is_err());
+    }
+}
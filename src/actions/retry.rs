@@ -0,0 +1,125 @@
+use anyhow::Result;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(3);
+
+/// Exponential delays stop growing at this length.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(600);
+
+/// How long to wait before retrying after a failure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetryBackoff {
+    Fixed(Duration),
+    /// Doubles with each retry, starting from the given delay.
+    Exponential(Duration),
+}
+
+impl Default for RetryBackoff {
+    fn default() -> Self {
+        RetryBackoff::Fixed(DEFAULT_RETRY_DELAY)
+    }
+}
+
+impl RetryBackoff {
+    /// Parses `fixed` or `exponential`, optionally followed by the (initial) delay in
+    /// seconds, e.g. `exponential:5`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let (kind, seconds) = match s.split_once(':') {
+            Some((kind, seconds)) => (kind, Some(seconds)),
+            None => (s, None),
+        };
+        let delay = match seconds {
+            Some(s) => Duration::from_secs(
+                s.parse()
+                    .map_err(|_| anyhow!("The delay should be a number of seconds."))?,
+            ),
+            None => DEFAULT_RETRY_DELAY,
+        };
+        match kind {
+            "fixed" => Ok(RetryBackoff::Fixed(delay)),
+            "exponential" => Ok(RetryBackoff::Exponential(delay)),
+            _ => Err(anyhow!(
+                "Use fixed or exponential, optionally followed by :SECONDS."
+            )),
+        }
+    }
+
+    /// Delay before the given retry, counting from 0.
+    pub fn delay(&self, retry: u32) -> Duration {
+        match self {
+            RetryBackoff::Fixed(d) => *d,
+            RetryBackoff::Exponential(d) => d
+                .checked_mul(1 << retry.min(31))
+                .unwrap_or(MAX_RETRY_DELAY)
+                .min(MAX_RETRY_DELAY),
+        }
+    }
+}
+
+impl Display for RetryBackoff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RetryBackoff::Fixed(d) => write!(f, "{}s", d.as_secs()),
+            RetryBackoff::Exponential(d) => write!(f, "{}s, doubling", d.as_secs()),
+        }
+    }
+}
+
+/// What is written again after a verification mismatch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetryScope {
+    /// The whole stage, once rewriting the region around the mismatched block didn't help.
+    Stage,
+    /// Only the region around the mismatched block, as many times as the verification
+    /// retries allow.
+    Block,
+}
+
+impl RetryScope {
+    pub const NAMES: &'static [&'static str] = &["stage", "block"];
+}
+
+impl std::str::FromStr for RetryScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "stage" => Ok(RetryScope::Stage),
+            "block" => Ok(RetryScope::Block),
+            _ => Err(anyhow!("Use one of {}.", RetryScope::NAMES.join(", "))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::*;
+
+    #[test]
+    fn test_backoff_parsing() {
+        assert_eq!(
+            RetryBackoff::parse("fixed").unwrap(),
+            RetryBackoff::Fixed(DEFAULT_RETRY_DELAY)
+        );
+        assert_eq!(
+            RetryBackoff::parse("exponential:5").unwrap(),
+            RetryBackoff::Exponential(Duration::from_secs(5))
+        );
+        assert_matches!(RetryBackoff::parse("linear"), Err(_));
+        assert_matches!(RetryBackoff::parse("fixed:soon"), Err(_));
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        let fixed = RetryBackoff::Fixed(Duration::from_secs(3));
+        assert_eq!(fixed.delay(0), Duration::from_secs(3));
+        assert_eq!(fixed.delay(5), Duration::from_secs(3));
+
+        let exponential = RetryBackoff::Exponential(Duration::from_secs(3));
+        assert_eq!(exponential.delay(0), Duration::from_secs(3));
+        assert_eq!(exponential.delay(2), Duration::from_secs(12));
+        assert_eq!(exponential.delay(40), MAX_RETRY_DELAY);
+    }
+}
@@ -0,0 +1,147 @@
+//! Erasing a drive with its own SANITIZE command. The drive carries it out by itself, even
+//! across power cycles, so the task only starts it and follows the progress.
+
+use crate::storage::sanitize::{SanitizeMode, SanitizeStatus, Sanitizer};
+use anyhow::Result;
+use std::rc::Rc;
+use std::time::Duration;
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub struct SanitizeTask {
+    pub device_id: String,
+    pub mode: SanitizeMode,
+    pub poll_interval: Duration,
+}
+
+#[derive(Debug)]
+pub enum SanitizeEvent {
+    Started,
+    /// Done fraction, from 0 to 1.
+    Progress(f32),
+    Completed(Option<Rc<anyhow::Error>>),
+}
+
+pub trait SanitizeEventReceiver {
+    fn handle(&mut self, task: &SanitizeTask, event: SanitizeEvent);
+}
+
+impl SanitizeTask {
+    pub fn new(device_id: &str, mode: SanitizeMode) -> Self {
+        SanitizeTask {
+            device_id: device_id.to_string(),
+            mode,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    pub fn run(&self, sanitizer: &dyn Sanitizer, frontend: &mut dyn SanitizeEventReceiver) -> bool {
+        frontend.handle(self, SanitizeEvent::Started);
+        let result = self.sanitize(sanitizer, frontend).err().map(Rc::from);
+        let success = result.is_none();
+        frontend.handle(self, SanitizeEvent::Completed(result));
+        success
+    }
+
+    fn sanitize(
+        &self,
+        sanitizer: &dyn Sanitizer,
+        frontend: &mut dyn SanitizeEventReceiver,
+    ) -> Result<()> {
+        sanitizer.start(self.mode)?;
+        loop {
+            match sanitizer.status()? {
+                SanitizeStatus::InProgress(done) => {
+                    frontend.handle(self, SanitizeEvent::Progress(done))
+                }
+                SanitizeStatus::Completed => {
+                    frontend.handle(self, SanitizeEvent::Progress(1.0));
+                    return Ok(());
+                }
+                SanitizeStatus::Failed => {
+                    return Err(anyhow!("The drive reports the {} failed", self.mode))
+                }
+                SanitizeStatus::Idle => {
+                    return Err(anyhow!("The drive reports no {} in progress", self.mode))
+                }
+            }
+            std::thread::sleep(self.poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct ScriptedSanitizer {
+        started: RefCell<Option<SanitizeMode>>,
+        statuses: RefCell<Vec<SanitizeStatus>>,
+    }
+
+    impl ScriptedSanitizer {
+        fn new(statuses: Vec<SanitizeStatus>) -> Self {
+            ScriptedSanitizer {
+                started: RefCell::new(None),
+                statuses: RefCell::new(statuses),
+            }
+        }
+    }
+
+    impl Sanitizer for ScriptedSanitizer {
+        fn start(&self, mode: SanitizeMode) -> Result<()> {
+            *self.started.borrow_mut() = Some(mode);
+            Ok(())
+        }
+
+        fn status(&self) -> Result<SanitizeStatus> {
+            Ok(self.statuses.borrow_mut().remove(0))
+        }
+    }
+
+    #[derive(Default)]
+    struct Events {
+        progress: Vec<f32>,
+        error: Option<String>,
+    }
+
+    impl SanitizeEventReceiver for Events {
+        fn handle(&mut self, _task: &SanitizeTask, event: SanitizeEvent) {
+            match event {
+                SanitizeEvent::Progress(done) => self.progress.push(done),
+                SanitizeEvent::Completed(Some(err)) => self.error = Some(err.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_sanitize_is_followed_until_done() {
+        let mut task = SanitizeTask::new("/dev/nvme0n1", SanitizeMode::Crypto);
+        task.poll_interval = Duration::from_millis(0);
+
+        let sanitizer = ScriptedSanitizer::new(vec![
+            SanitizeStatus::InProgress(0.0),
+            SanitizeStatus::InProgress(0.5),
+            SanitizeStatus::Completed,
+        ]);
+        let mut events = Events::default();
+        assert!(task.run(&sanitizer, &mut events));
+        assert_eq!(*sanitizer.started.borrow(), Some(SanitizeMode::Crypto));
+        assert_eq!(events.progress, vec![0.0, 0.5, 1.0]);
+        assert!(events.error.is_none());
+
+        let sanitizer = ScriptedSanitizer::new(vec![
+            SanitizeStatus::InProgress(0.25),
+            SanitizeStatus::Failed,
+        ]);
+        let mut events = Events::default();
+        assert!(!task.run(&sanitizer, &mut events));
+        assert_eq!(
+            events.error.as_deref(),
+            Some("The drive reports the crypto erase failed")
+        );
+    }
+}
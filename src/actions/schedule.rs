@@ -0,0 +1,161 @@
+//! Time of the day a wipe is paused in, e.g. to leave shared storage alone during
+//! business hours.
+
+use anyhow::{Context, Result};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Window of the day from `start` to `end` (local time), ending on the next day if it
+/// ends earlier than it starts, like 22:00-06:00.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PauseWindow {
+    start: Duration,
+    end: Duration,
+}
+
+impl PauseWindow {
+    /// Time left until the end of the window, `None` if the time of the day is outside of it.
+    pub fn remaining(&self, time_of_day: Duration) -> Option<Duration> {
+        let inside = if self.start <= self.end {
+            time_of_day >= self.start && time_of_day < self.end
+        } else {
+            time_of_day >= self.start || time_of_day < self.end
+        };
+        match inside {
+            true if time_of_day < self.end => Some(self.end - time_of_day),
+            true => Some(DAY - time_of_day + self.end),
+            false => None,
+        }
+    }
+
+    /// The time of the day the window ends, like `18:00`.
+    pub fn end(&self) -> String {
+        format_time_of_day(self.end)
+    }
+}
+
+impl Display for PauseWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            format_time_of_day(self.start),
+            format_time_of_day(self.end)
+        )
+    }
+}
+
+impl std::str::FromStr for PauseWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow!("Use a range of the day like 08:00-18:00."))?;
+        let window = PauseWindow {
+            start: parse_time_of_day(start)?,
+            end: parse_time_of_day(end)?,
+        };
+        if window.start == window.end {
+            return Err(anyhow!("The window should not be empty."));
+        }
+        Ok(window)
+    }
+}
+
+fn parse_time_of_day(s: &str) -> Result<Duration> {
+    let (hours, minutes) = s
+        .trim()
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Use HH:MM for {}.", s))?;
+    let hours: u64 = hours.parse().context(format!("Invalid hours in {}", s))?;
+    let minutes: u64 = minutes
+        .parse()
+        .context(format!("Invalid minutes in {}", s))?;
+    if hours > 23 || minutes > 59 {
+        return Err(anyhow!("{} is not a time of the day.", s));
+    }
+    Ok(Duration::from_secs((hours * 60 + minutes) * 60))
+}
+
+fn format_time_of_day(time: Duration) -> String {
+    let minutes = time.as_secs() / 60;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Pause window of a wipe with the clock it's checked against.
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    pub window: PauseWindow,
+    clock: fn() -> Duration,
+}
+
+impl Schedule {
+    pub fn new(window: PauseWindow) -> Self {
+        Schedule {
+            window,
+            clock: local_time_of_day,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_clock(window: PauseWindow, clock: fn() -> Duration) -> Self {
+        Schedule { window, clock }
+    }
+
+    /// How long the wipe should stay paused from now, `None` if it can go on.
+    pub fn pause_left(&self) -> Option<Duration> {
+        self.window.remaining((self.clock)())
+    }
+}
+
+/// Time since the local midnight.
+#[cfg(unix)]
+pub fn local_time_of_day() -> Duration {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    Duration::from_secs((tm.tm_hour * 3600 + tm.tm_min * 60 + tm.tm_sec) as u64)
+}
+
+/// Time since the local midnight.
+#[cfg(windows)]
+pub fn local_time_of_day() -> Duration {
+    let mut time: winapi::um::minwinbase::SYSTEMTIME = unsafe { std::mem::zeroed() };
+    unsafe { winapi::um::sysinfoapi::GetLocalTime(&mut time) };
+    Duration::from_secs(time.wHour as u64 * 3600 + time.wMinute as u64 * 60 + time.wSecond as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(hours: u64, minutes: u64) -> Duration {
+        Duration::from_secs((hours * 60 + minutes) * 60)
+    }
+
+    #[test]
+    fn test_pause_window() {
+        let office: PauseWindow = "08:00-18:00".parse().unwrap();
+        assert_eq!(office.remaining(at(7, 59)), None);
+        assert_eq!(office.remaining(at(8, 0)), Some(at(10, 0)));
+        assert_eq!(office.remaining(at(17, 30)), Some(at(0, 30)));
+        assert_eq!(office.remaining(at(18, 0)), None);
+        assert_eq!(office.to_string(), "08:00-18:00");
+        assert_eq!(office.end(), "18:00");
+
+        let night: PauseWindow = "22:30-6:00".parse().unwrap();
+        assert_eq!(night.remaining(at(22, 0)), None);
+        assert_eq!(night.remaining(at(23, 30)), Some(at(6, 30)));
+        assert_eq!(night.remaining(at(5, 0)), Some(at(1, 0)));
+        assert_eq!(night.remaining(at(12, 0)), None);
+        assert_eq!(night.to_string(), "22:30-06:00");
+
+        assert!("08:00".parse::<PauseWindow>().is_err());
+        assert!("08:00-24:00".parse::<PauseWindow>().is_err());
+        assert!("8-18".parse::<PauseWindow>().is_err());
+        assert!("10:00-10:00".parse::<PauseWindow>().is_err());
+    }
+}
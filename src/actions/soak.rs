@@ -0,0 +1,245 @@
+use crate::actions::{
+    underlying_storage_error, Verify, WipeEvent, WipeEventReceiver, WipeRange, WipeState, WipeTask,
+};
+use crate::sanitization::mem::AlignedBuffer;
+use crate::sanitization::{Scheme, Stage};
+use crate::storage::{StorageAccess, StorageError};
+use anyhow::Result;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Long running health test which repeatedly reads the whole device and optionally
+/// writes and verifies a small region of it.
+#[derive(Debug)]
+pub struct SoakTask {
+    pub duration: Duration,
+    pub total_size: u64,
+    pub block_size: usize,
+    pub write_region: Option<WipeRange>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SoakPass {
+    pub pass: u32,
+    pub bytes_read: u64,
+    pub read_errors: u32,
+    pub elapsed: Duration,
+    pub complete: bool,
+    pub region_error: Option<String>,
+}
+
+impl SoakPass {
+    /// Average read throughput in bytes per second.
+    pub fn throughput(&self) -> u64 {
+        match self.elapsed.as_millis() {
+            0 => 0,
+            ms => (self.bytes_read as u128 * 1000 / ms) as u64,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SoakEvent {
+    Started,
+    PassStarted(u32),
+    Progress(u64),
+    ReadError(u64),
+    PassCompleted(SoakPass),
+    Completed(Option<Rc<anyhow::Error>>),
+}
+
+pub trait SoakEventReceiver {
+    fn handle(&mut self, task: &SoakTask, event: SoakEvent);
+}
+
+impl SoakTask {
+    pub fn new(duration: Duration, total_size: u64, block_size: usize) -> Self {
+        SoakTask {
+            duration,
+            total_size,
+            block_size,
+            write_region: None,
+        }
+    }
+
+    pub fn run(
+        &self,
+        access: &mut dyn StorageAccess,
+        frontend: &mut dyn SoakEventReceiver,
+    ) -> bool {
+        frontend.handle(self, SoakEvent::Started);
+
+        let deadline = Instant::now() + self.duration;
+        let mut pass = 0;
+
+        let result = loop {
+            if Instant::now() >= deadline {
+                break None;
+            }
+
+            pass += 1;
+            frontend.handle(self, SoakEvent::PassStarted(pass));
+
+            match self.scan(access, frontend, deadline) {
+                Ok(mut stats) => {
+                    stats.pass = pass;
+                    if let Some(region) = self.write_region {
+                        stats.region_error = self
+                            .exercise_region(access, region)
+                            .err()
+                            .map(|e| format!("{:#}", e));
+                    }
+                    frontend.handle(self, SoakEvent::PassCompleted(stats));
+                }
+                Err(err) => break Some(Rc::from(err)),
+            }
+        };
+
+        let success = result.is_none();
+        frontend.handle(self, SoakEvent::Completed(result));
+        success
+    }
+
+    fn scan(
+        &self,
+        access: &mut dyn StorageAccess,
+        frontend: &mut dyn SoakEventReceiver,
+        deadline: Instant,
+    ) -> Result<SoakPass> {
+        let mut buf = AlignedBuffer::new(self.block_size, self.block_size);
+        let mut stats = SoakPass::default();
+        let started = Instant::now();
+        let mut position = 0;
+        let mut need_seek = true;
+
+        frontend.handle(self, SoakEvent::Progress(position));
+
+        while position < self.total_size && Instant::now() < deadline {
+            let len = (self.total_size - position).min(self.block_size as u64) as usize;
+
+            let result = if need_seek {
+                access
+                    .seek(position)
+                    .and_then(|_| access.read(&mut buf.as_mut_slice()[..len]))
+            } else {
+                access.read(&mut buf.as_mut_slice()[..len])
+            };
+
+            match result {
+                Ok(0) => break,
+                Ok(n) => {
+                    position += n as u64;
+                    stats.bytes_read += n as u64;
+                    need_seek = false;
+                }
+                Err(err) => match underlying_storage_error(&err) {
+                    Some(StorageError::BadBlock) => {
+                        stats.read_errors += 1;
+                        frontend.handle(self, SoakEvent::ReadError(position));
+                        position += len as u64;
+                        need_seek = true;
+                    }
+                    _ => return Err(err),
+                },
+            }
+
+            frontend.handle(self, SoakEvent::Progress(position));
+        }
+
+        stats.elapsed = started.elapsed();
+        stats.complete = position >= self.total_size;
+        Ok(stats)
+    }
+
+    /// Writes random data to the region and reads it back using the wipe engine.
+    fn exercise_region(&self, access: &mut dyn StorageAccess, region: WipeRange) -> Result<()> {
+        let scheme = Scheme {
+            description: "Soak test region".to_string(),
+            stages: vec![Stage::random()],
+        };
+        let mut task = WipeTask::new(scheme, Verify::Last, self.total_size, self.block_size)?;
+        task.set_ranges(vec![region])?;
+
+        let mut outcome = RegionOutcome::default();
+        task.run(access, &mut WipeState::default(), &mut outcome);
+
+        match outcome.error {
+            Some(e) => Err(anyhow!(e)),
+            None if outcome.bad_blocks > 0 => Err(anyhow!(
+                "{} block(s) in the region are not accessible",
+                outcome.bad_blocks
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct RegionOutcome {
+    error: Option<String>,
+    bad_blocks: u32,
+}
+
+impl WipeEventReceiver for RegionOutcome {
+    fn handle(&mut self, _task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        if let WipeEvent::Completed(result) = event {
+            self.error = result.map(|e| format!("{:#}", e));
+            self.bad_blocks = state.bad_blocks.borrow().total_marked();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::memory::InMemoryStorage;
+    use assert_matches::*;
+
+    struct StubReceiver {
+        collected: Vec<SoakEvent>,
+    }
+
+    impl SoakEventReceiver for StubReceiver {
+        fn handle(&mut self, _task: &SoakTask, event: SoakEvent) {
+            self.collected.push(event);
+        }
+    }
+
+    #[test]
+    fn test_soak_counts_read_errors_and_checks_region() {
+        let mut storage = InMemoryStorage::new(10000);
+        storage.unreadable = vec![4096];
+        let mut receiver = StubReceiver {
+            collected: Vec::new(),
+        };
+
+        let mut task = SoakTask::new(Duration::from_millis(50), 10000, 2048);
+        task.write_region = Some(WipeRange::new(0, 2048));
+
+        assert!(task.run(&mut storage, &mut receiver));
+
+        let passes: Vec<_> = receiver
+            .collected
+            .iter()
+            .filter_map(|e| match e {
+                SoakEvent::PassCompleted(p) => Some(p),
+                _ => None,
+            })
+            .collect();
+
+        assert!(!passes.is_empty());
+        let first = passes[0];
+        assert_eq!(first.pass, 1);
+        assert!(first.complete);
+        assert_eq!(first.read_errors, 1);
+        assert_eq!(first.bytes_read, 10000 - 2048);
+        assert_eq!(first.region_error, None);
+
+        assert_matches!(receiver.collected.first(), Some(SoakEvent::Started));
+        assert_matches!(receiver.collected.last(), Some(SoakEvent::Completed(None)));
+
+        let data = storage.file.get_ref();
+        assert!(data[..2048].iter().any(|x| *x != 0xff));
+        assert!(data[2048..].iter().all(|x| *x == 0xff));
+    }
+}
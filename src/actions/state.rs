@@ -0,0 +1,670 @@
+//! The data directory keeping what outlives a run: the checkpoints, the bad block maps and
+//! the reports of the wipes, and a record of each wipe session (a job) to tell later how
+//! it ended, even after a reboot.
+
+use crate::actions::badblocks::BadBlockMap;
+use crate::actions::checkpoint::{CheckpointStore, DeviceFingerprint};
+use crate::actions::report::{digest_path, ReportRecorder};
+use crate::actions::{WipeEvent, WipeEventReceiver, WipeOutcome, WipeState, WipeTask};
+use crate::storage::StorageDetails;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+pub const DEFAULT_MAX_JOBS: usize = 64;
+
+/// Tells apart the jobs started by the same process within a millisecond.
+static JOBS_STARTED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone)]
+pub struct StateDir {
+    root: PathBuf,
+}
+
+impl StateDir {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        StateDir {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// `%LOCALAPPDATA%\lethe` on Windows, `$XDG_DATA_HOME/lethe` (`~/.local/share/lethe`)
+    /// elsewhere.
+    pub fn default_location() -> PathBuf {
+        let base = if cfg!(windows) {
+            std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+        } else {
+            std::env::var_os("XDG_DATA_HOME")
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local").join("share"))
+                })
+        };
+
+        base.unwrap_or_else(std::env::temp_dir).join("lethe")
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn checkpoints(&self) -> CheckpointStore {
+        CheckpointStore::new(self.root.join("checkpoints"))
+    }
+
+    pub fn jobs(&self) -> JobStore {
+        JobStore::new(self.root.join("jobs"))
+    }
+
+    /// Bad block map saved after a wipe of the device skipped some blocks.
+    pub fn badblocks_path(&self, fingerprint: &DeviceFingerprint) -> PathBuf {
+        self.root.join("badblocks").join(fingerprint.file_name())
+    }
+
+    pub fn report_path(&self, job_id: &str) -> PathBuf {
+        self.root.join("reports").join(format!("{}.json", job_id))
+    }
+
+    /// Tells for each of the jobs (listed the most recent first) whether it can be continued
+    /// from a checkpoint: it's the last job of the device, it didn't complete and the
+    /// checkpoint is still there.
+    pub fn resumable(&self, jobs: &[Job]) -> Vec<bool> {
+        let checkpoints = self.checkpoints();
+        let mut seen = Vec::new();
+        jobs.iter()
+            .map(|job| {
+                let latest = !seen.contains(&&job.fingerprint);
+                seen.push(&job.fingerprint);
+                latest
+                    && job.effective_status() != JobStatus::Completed
+                    && checkpoints.find(&job.fingerprint).ok().flatten().is_some()
+            })
+            .collect()
+    }
+
+    /// Removes the job along with its checkpoint if it's the one the job would be resumed
+    /// from, so the next wipe of the device starts over. A running job can't be deleted.
+    pub fn delete_job(&self, id: &str) -> Result<Job> {
+        let jobs = self.jobs().list()?;
+        let (job, resumable) = jobs
+            .iter()
+            .zip(self.resumable(&jobs))
+            .find(|(j, _)| j.id == id)
+            .ok_or_else(|| anyhow!("Unknown job {}", id))?;
+        if job.effective_status() == JobStatus::Running {
+            return Err(anyhow!("Job {} is still running (pid {})", id, job.pid));
+        }
+        if resumable {
+            self.checkpoints().remove(&job.fingerprint)?;
+        }
+        self.jobs().remove(job)?;
+        Ok(job.clone())
+    }
+
+    /// Moves the checkpoints of the earlier versions, kept in `$XDG_STATE_HOME/lethe`,
+    /// unless there are checkpoints here already.
+    pub fn migrate_checkpoints(&self) -> Result<()> {
+        let legacy = match legacy_checkpoints_location() {
+            Some(l) if l.is_dir() => l,
+            _ => return Ok(()),
+        };
+        let target = self.root.join("checkpoints");
+        if target.exists() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.root).context("Unable to create the data directory")?;
+        std::fs::rename(&legacy, &target).context(format!(
+            "Unable to move the checkpoints from {}",
+            legacy.display()
+        ))
+    }
+}
+
+impl Default for StateDir {
+    fn default() -> Self {
+        StateDir::new(StateDir::default_location())
+    }
+}
+
+fn legacy_checkpoints_location() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return None;
+    }
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local").join("state")))
+        .map(|base| base.join("lethe").join("checkpoints"))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    CompletedWithBadBlocks,
+    Failed,
+    Aborted,
+    /// Never recorded, a running job is reported as interrupted once its process is gone,
+    /// e.g. after a crash or a reboot.
+    Interrupted,
+}
+
+impl Display for JobStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Running => f.write_str("running"),
+            JobStatus::Completed => f.write_str("completed"),
+            JobStatus::CompletedWithBadBlocks => f.write_str("completed, bad blocks skipped"),
+            JobStatus::Failed => f.write_str("failed"),
+            JobStatus::Aborted => f.write_str("aborted"),
+            JobStatus::Interrupted => f.write_str("interrupted"),
+        }
+    }
+}
+
+impl From<WipeOutcome> for JobStatus {
+    fn from(outcome: WipeOutcome) -> Self {
+        match outcome {
+            WipeOutcome::Completed => JobStatus::Completed,
+            WipeOutcome::CompletedWithBadBlocks => JobStatus::CompletedWithBadBlocks,
+            WipeOutcome::Aborted => JobStatus::Aborted,
+            _ => JobStatus::Failed,
+        }
+    }
+}
+
+/// A wipe session of a device, saved as it goes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Job {
+    pub id: String,
+    pub fingerprint: DeviceFingerprint,
+    pub scheme: String,
+    pub pid: u32,
+    pub status: JobStatus,
+    /// Current stage, starting from 1.
+    pub stage: usize,
+    pub stages: usize,
+    pub at_verification: bool,
+    /// Bytes of the current stage done.
+    pub progress: u64,
+    pub total: u64,
+    pub bad_blocks: u32,
+    pub error: Option<String>,
+    pub report: Option<PathBuf>,
+    pub badblocks: Option<PathBuf>,
+    pub started: u64,
+    pub updated: u64,
+}
+
+impl Job {
+    pub fn new(fingerprint: DeviceFingerprint) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let pid = std::process::id();
+        Job {
+            id: format!(
+                "{}-{}-{}",
+                now.as_millis(),
+                pid,
+                JOBS_STARTED.fetch_add(1, Ordering::SeqCst)
+            ),
+            fingerprint,
+            scheme: String::new(),
+            pid,
+            status: JobStatus::Running,
+            stage: 0,
+            stages: 0,
+            at_verification: false,
+            progress: 0,
+            total: 0,
+            bad_blocks: 0,
+            error: None,
+            report: None,
+            badblocks: None,
+            started: now.as_secs(),
+            updated: now.as_secs(),
+        }
+    }
+
+    /// Whether the ID is in the `<millis>-<pid>-<counter>` format of the generated ones.
+    pub fn is_valid_id(id: &str) -> bool {
+        let parts: Vec<&str> = id.split('-').collect();
+        parts.len() == 3
+            && parts
+                .iter()
+                .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()))
+    }
+
+    /// The recorded status, unless the job is still recorded as running by a process
+    /// which is gone.
+    pub fn effective_status(&self) -> JobStatus {
+        match self.status {
+            JobStatus::Running if !is_process_running(self.pid) => JobStatus::Interrupted,
+            status => status,
+        }
+    }
+}
+
+pub struct JobStore {
+    root: PathBuf,
+}
+
+impl JobStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        JobStore {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Fails for anything but the IDs generated by [`Job::new`], so an ID given by the user
+    /// (or read from a tampered job) never points outside of the store.
+    fn path_for(&self, id: &str) -> Result<PathBuf> {
+        if !Job::is_valid_id(id) {
+            return Err(anyhow!("Invalid job ID {}", id));
+        }
+        Ok(self.root.join(format!("{}.json", id)))
+    }
+
+    /// Replaces the saved job atomically, like the checkpoints.
+    pub fn save(&self, job: &Job) -> Result<()> {
+        std::fs::create_dir_all(&self.root).context("Unable to create jobs directory")?;
+        let path = self.path_for(&job.id)?;
+        let tmp_path = path.with_extension("tmp");
+        let written = File::create(&tmp_path)
+            .map(BufWriter::new)
+            .context("Unable to create job")
+            .and_then(|mut writer| {
+                serde_json::to_writer(&mut writer, job).context("Unable to write job")?;
+                writer.flush().context("Unable to write job")
+            })
+            .and_then(|_| std::fs::rename(&tmp_path, &path).context("Unable to replace job"));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+        written
+    }
+
+    /// Returns all readable jobs, the most recently started first.
+    pub fn list(&self) -> Result<Vec<Job>> {
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(e) => e,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).context("Unable to read jobs directory"),
+        };
+        let mut jobs: Vec<Job> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().map(|e| e == "json").unwrap_or(false))
+            .filter_map(|p| {
+                File::open(p)
+                    .ok()
+                    .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            })
+            .filter(|j: &Job| Job::is_valid_id(&j.id))
+            .collect();
+        jobs.sort_by_key(|j| std::cmp::Reverse(j.started));
+        Ok(jobs)
+    }
+
+    pub fn find(&self, id: &str) -> Result<Option<Job>> {
+        if !Job::is_valid_id(id) {
+            return Ok(None);
+        }
+        let file = match File::open(self.path_for(id)?) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("Unable to open job"),
+        };
+        serde_json::from_reader(BufReader::new(file))
+            .map(Some)
+            .context("Corrupted job")
+    }
+
+    /// Removes the job with its report.
+    pub fn remove(&self, job: &Job) -> Result<()> {
+        if let Some(report) = &job.report {
+            let _ = std::fs::remove_file(report);
+            let _ = std::fs::remove_file(digest_path(report));
+        }
+        match std::fs::remove_file(self.path_for(&job.id)?) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e).context("Unable to remove job"),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes all but the `max_count` most recent jobs. Returns the number of removed jobs.
+    pub fn prune(&self, max_count: usize) -> Result<usize> {
+        let stale: Vec<Job> = self.list()?.into_iter().skip(max_count).collect();
+        for job in &stale {
+            self.remove(job)?;
+        }
+        Ok(stale.len())
+    }
+}
+
+#[cfg(unix)]
+fn is_process_running(pid: u32) -> bool {
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn is_process_running(pid: u32) -> bool {
+    use winapi::shared::minwindef::{DWORD, FALSE};
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::minwinbase::STILL_ACTIVE;
+    use winapi::um::processthreadsapi::{GetExitCodeProcess, OpenProcess};
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        if process.is_null() {
+            return false;
+        }
+        let mut code: DWORD = 0;
+        let alive = GetExitCodeProcess(process, &mut code) != 0 && code == STILL_ACTIVE;
+        CloseHandle(process);
+        alive
+    }
+}
+
+/// Keeps the job of a wipe up to date in the store. Once the wipe is completed, the
+/// report (if asked for with `with_report`) and the bad block map are saved next to it.
+pub struct JobRecorder {
+    state: StateDir,
+    job: Job,
+    report: Option<ReportRecorder>,
+    last_flush: Option<Instant>,
+    pruned: bool,
+}
+
+impl JobRecorder {
+    pub fn new(state: StateDir, fingerprint: DeviceFingerprint) -> Self {
+        JobRecorder {
+            state,
+            job: Job::new(fingerprint),
+            report: None,
+            last_flush: None,
+            pruned: false,
+        }
+    }
+
+    /// Goes on recording an earlier job, e.g. when it's resumed after an interruption.
+    pub fn resume(state: StateDir, job: Job) -> Self {
+        JobRecorder {
+            state,
+            job: Job {
+                pid: std::process::id(),
+                ..job
+            },
+            report: None,
+            last_flush: None,
+            pruned: true,
+        }
+    }
+
+    pub fn with_report(mut self, details: &StorageDetails) -> Self {
+        self.report = Some(ReportRecorder::new(&self.job.fingerprint.id, details));
+        self
+    }
+
+    pub fn job(&self) -> &Job {
+        &self.job
+    }
+
+    fn flush(&mut self) {
+        self.last_flush = Some(Instant::now());
+        self.job.updated = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if let Err(err) = self.state.jobs().save(&self.job) {
+            eprintln!("Unable to save the job. {:#}", err);
+        }
+    }
+
+    fn complete(&mut self, task: &WipeTask, state: &WipeState, outcome: WipeOutcome) {
+        self.job.status = outcome.into();
+        if self.job.bad_blocks > 0 {
+            let path = self.state.badblocks_path(&self.job.fingerprint);
+            let saved = path
+                .parent()
+                .map(std::fs::create_dir_all)
+                .transpose()
+                .context("Unable to create bad blocks directory")
+                .and_then(|_| {
+                    BadBlockMap::new(task.total_size, task.block_size, state).save(&path)
+                });
+            match saved {
+                Ok(()) => self.job.badblocks = Some(path),
+                Err(err) => eprintln!("{:#}", err),
+            }
+        }
+    }
+
+    fn save_report(&mut self, task: &WipeTask, state: &WipeState, event: &WipeEvent) {
+        let report = match self
+            .report
+            .as_mut()
+            .and_then(|r| r.record(task, state, event))
+        {
+            Some(r) => r,
+            None => return,
+        };
+        let path = self.state.report_path(&self.job.id);
+        let written = path
+            .parent()
+            .map(std::fs::create_dir_all)
+            .transpose()
+            .context("Unable to create reports directory")
+            .and_then(|_| report.write(&path));
+        match written {
+            Ok(_) => self.job.report = Some(path),
+            Err(err) => eprintln!("Unable to save the report. {:#}", err),
+        }
+    }
+}
+
+impl WipeEventReceiver for JobRecorder {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        self.save_report(task, state, &event);
+        self.job.stage = state.stage + 1;
+        self.job.stages = task.scheme.stages.len();
+        self.job.at_verification = state.at_verification;
+        self.job.progress = task.progress_at(state.position);
+        self.job.bad_blocks = state.bad_blocks.borrow().total_marked();
+
+        match event {
+            WipeEvent::Started => {
+                if !self.pruned {
+                    // pruned once per job, before it's saved for the first time
+                    self.pruned = true;
+                    if let Err(err) = self.state.jobs().prune(DEFAULT_MAX_JOBS - 1) {
+                        eprintln!("Unable to prune old jobs. {:#}", err);
+                    }
+                }
+                self.job.scheme = task.scheme.description.clone();
+                self.job.total = task.wiped_size();
+                self.job.status = JobStatus::Running;
+                self.job.error = None;
+                self.flush();
+            }
+            WipeEvent::Progress(_) => match self.last_flush {
+                Some(t) if t.elapsed() < DEFAULT_FLUSH_INTERVAL => {}
+                _ => self.flush(),
+            },
+            WipeEvent::Completed(None) => {
+                let outcome = if self.job.bad_blocks > 0 {
+                    WipeOutcome::CompletedWithBadBlocks
+                } else {
+                    WipeOutcome::Completed
+                };
+                self.complete(task, state, outcome);
+                self.flush();
+            }
+            WipeEvent::Completed(Some(err)) => {
+                self.complete(task, state, WipeOutcome::of_error(&err));
+                self.job.error = Some(format!("{:#}", err));
+                self.flush();
+            }
+            WipeEvent::Fatal(err) => {
+                self.job.status = JobStatus::Failed;
+                self.job.error = Some(format!("{:#}", err));
+                self.flush();
+            }
+            WipeEvent::StageStarted
+            | WipeEvent::StageCompleted(_)
+            | WipeEvent::Paused
+            | WipeEvent::PausedUntil(_)
+            | WipeEvent::Aborted => self.flush(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::checkpoint::Checkpoint;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+    use std::rc::Rc;
+
+    fn temp_state(name: &str) -> StateDir {
+        StateDir::new(std::env::temp_dir().join(format!(
+            "lethe-state-{}-{}",
+            name,
+            std::process::id()
+        )))
+    }
+
+    #[test]
+    fn test_job_is_recorded_with_report_and_bad_blocks() {
+        let state_dir = temp_state("job");
+        let fingerprint = DeviceFingerprint::new("/dev/sdz", 1000);
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::No, 1000, 100).unwrap();
+        let state = WipeState::default();
+
+        let mut recorder = JobRecorder::new(state_dir.clone(), fingerprint.clone())
+            .with_report(&StorageDetails::default());
+        recorder.handle(&task, &state, WipeEvent::Started);
+
+        let jobs = state_dir.jobs().list().unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].fingerprint, fingerprint);
+        assert_eq!(jobs[0].status, JobStatus::Running);
+        assert_eq!(jobs[0].effective_status(), JobStatus::Running);
+        assert_eq!(jobs[0].total, 1000);
+
+        state.bad_blocks.borrow_mut().mark(3);
+        recorder.handle(&task, &state, WipeEvent::Completed(None));
+
+        let job = &state_dir.jobs().list().unwrap()[0];
+        assert_eq!(job.status, JobStatus::CompletedWithBadBlocks);
+        assert_eq!(job.bad_blocks, 1);
+        assert_eq!(
+            job.badblocks.as_deref(),
+            Some(state_dir.badblocks_path(&fingerprint).as_path())
+        );
+        assert_eq!(
+            BadBlockMap::load(job.badblocks.as_ref().unwrap())
+                .unwrap()
+                .ranges
+                .len(),
+            1
+        );
+        assert!(job.report.as_ref().unwrap().exists());
+
+        let mut failed = JobRecorder::new(state_dir.clone(), fingerprint);
+        failed.handle(&task, &state, WipeEvent::Started);
+        failed.handle(
+            &task,
+            &state,
+            WipeEvent::Completed(Some(Rc::new(anyhow!("Mocked IO failure")))),
+        );
+        let jobs = state_dir.jobs().list().unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert!(jobs
+            .iter()
+            .any(|j| j.status == JobStatus::Failed
+                && j.error.as_deref() == Some("Mocked IO failure")));
+
+        assert_eq!(state_dir.jobs().prune(1).unwrap(), 1);
+        assert_eq!(state_dir.jobs().list().unwrap().len(), 1);
+
+        std::fs::remove_dir_all(state_dir.root()).unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_job_is_resumed_or_deleted() {
+        let state_dir = temp_state("resume");
+        let fingerprint = DeviceFingerprint::new("/dev/sdz", 1000);
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::No, 1000, 100).unwrap();
+        let state = WipeState {
+            position: 500,
+            ..Default::default()
+        };
+
+        let mut recorder = JobRecorder::new(state_dir.clone(), fingerprint.clone());
+        recorder.handle(&task, &state, WipeEvent::Started);
+        state_dir
+            .checkpoints()
+            .flush(&Checkpoint::capture(&fingerprint, &task, &state))
+            .unwrap();
+        let mut job = recorder.job().clone();
+        job.pid = u32::MAX / 2;
+        state_dir.jobs().save(&job).unwrap();
+
+        let jobs = state_dir.jobs().list().unwrap();
+        assert_eq!(jobs[0].effective_status(), JobStatus::Interrupted);
+        assert_eq!(state_dir.resumable(&jobs), vec![true]);
+
+        let mut resumed = JobRecorder::resume(state_dir.clone(), job.clone());
+        resumed.handle(&task, &state, WipeEvent::Started);
+        let found = state_dir.jobs().find(&job.id).unwrap().unwrap();
+        assert_eq!(found.effective_status(), JobStatus::Running);
+        assert_eq!(found.started, job.started);
+        assert!(state_dir.delete_job(&job.id).is_err());
+
+        state_dir.jobs().save(&job).unwrap();
+        assert_eq!(state_dir.delete_job(&job.id).unwrap().id, job.id);
+        assert!(state_dir.jobs().find(&job.id).unwrap().is_none());
+        assert!(state_dir
+            .checkpoints()
+            .find(&fingerprint)
+            .unwrap()
+            .is_none());
+        assert!(state_dir.delete_job(&job.id).is_err());
+
+        // IDs which aren't generated never reach outside of the store
+        let mut tampered = job.clone();
+        tampered.id = "../../checkpoints/1-2-3".to_string();
+        assert!(state_dir.jobs().save(&tampered).is_err());
+        assert!(state_dir.jobs().find(&tampered.id).unwrap().is_none());
+        assert!(state_dir.jobs().remove(&tampered).is_err());
+
+        std::fs::remove_dir_all(state_dir.root()).unwrap();
+    }
+
+    #[test]
+    fn test_running_job_of_gone_process_is_interrupted() {
+        let mut job = Job::new(DeviceFingerprint::new("/dev/sdz", 1000));
+        assert!(Job::is_valid_id(&job.id));
+        assert!(!Job::is_valid_id("1-2"));
+        assert!(!Job::is_valid_id("1-2-../3"));
+        assert_eq!(job.effective_status(), JobStatus::Running);
+        job.pid = u32::MAX / 2;
+        assert_eq!(job.effective_status(), JobStatus::Interrupted);
+        job.status = JobStatus::Completed;
+        assert_eq!(job.effective_status(), JobStatus::Completed);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::actions::{WipeEvent, WipeState, WipeTask};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Time spent writing or verifying a single stage, summed over all attempts.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTiming {
+    pub duration_ms: u64,
+    pub bytes: u64,
+    /// Average throughput in bytes per second.
+    pub throughput: u64,
+}
+
+impl PhaseTiming {
+    fn add(&mut self, elapsed: Duration, bytes: u64) {
+        self.duration_ms += elapsed.as_millis() as u64;
+        self.bytes += bytes;
+        self.throughput = match self.duration_ms {
+            0 => self.bytes,
+            ms => (self.bytes as u128 * 1000 / ms as u128) as u64,
+        };
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct StageTiming {
+    /// Number of the stage, starting from 1.
+    pub stage: usize,
+    pub description: String,
+    pub write: Option<PhaseTiming>,
+    pub verify: Option<PhaseTiming>,
+    pub retries: u32,
+    pub skipped: bool,
+}
+
+/// Collects the per stage durations from the wipe events, starting over with each run.
+#[derive(Debug, Default)]
+pub struct StageTimer {
+    stages: Vec<StageTiming>,
+    /// Stamp time and progress at the start of the current phase.
+    started: Option<(Duration, u64)>,
+}
+
+impl StageTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stages(&self) -> &[StageTiming] {
+        &self.stages
+    }
+
+    pub fn record(&mut self, task: &WipeTask, state: &WipeState, event: &WipeEvent) {
+        match event {
+            WipeEvent::Started => *self = StageTimer::new(),
+            WipeEvent::StageStarted => {
+                self.current(task, state);
+                self.started = Some((state.stamp.elapsed, task.progress_at(state.position)));
+            }
+            WipeEvent::StageSkipped => self.current(task, state).skipped = true,
+            WipeEvent::StageCompleted(_) => {
+                if let Some((started, progress)) = self.started.take() {
+                    let bytes = task.progress_at(state.position).saturating_sub(progress);
+                    let timing = self.current(task, state);
+                    let phase = if state.at_verification {
+                        &mut timing.verify
+                    } else {
+                        &mut timing.write
+                    };
+                    phase
+                        .get_or_insert_with(Default::default)
+                        .add(state.stamp.elapsed.saturating_sub(started), bytes);
+                }
+            }
+            WipeEvent::Retrying(_) => self.current(task, state).retries += 1,
+            _ => {}
+        }
+    }
+
+    fn current(&mut self, task: &WipeTask, state: &WipeState) -> &mut StageTiming {
+        let stage = state.stage + 1;
+        if self.stages.last().map(|s| s.stage) != Some(stage) {
+            self.stages.push(StageTiming {
+                stage,
+                description: task.scheme.stages[state.stage].to_string(),
+                ..Default::default()
+            });
+        }
+        self.stages.last_mut().unwrap()
+    }
+}
+
+/// Minimum time between throughput samples, shorter intervals are too noisy.
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time constant of the exponential smoothing applied to the current throughput.
+const SMOOTHING_WINDOW: Duration = Duration::from_secs(10);
+
+/// Measures the current (smoothed) and the average throughput of a stage.
+#[derive(Debug, Clone)]
+pub struct ThroughputMeter {
+    started: Instant,
+    start_progress: u64,
+    last_sample: (Instant, u64),
+    current: Option<f64>,
+}
+
+impl ThroughputMeter {
+    pub fn new(progress: u64) -> Self {
+        Self::started_at(progress, Instant::now())
+    }
+
+    fn started_at(progress: u64, now: Instant) -> Self {
+        ThroughputMeter {
+            started: now,
+            start_progress: progress,
+            last_sample: (now, progress),
+            current: None,
+        }
+    }
+
+    /// Records the number of bytes processed so far. Returns true if a new sample was taken.
+    pub fn update(&mut self, progress: u64) -> bool {
+        self.update_at(progress, Instant::now())
+    }
+
+    fn update_at(&mut self, progress: u64, now: Instant) -> bool {
+        let (last_time, last_progress) = self.last_sample;
+        let elapsed = now.saturating_duration_since(last_time);
+        if elapsed < MIN_SAMPLE_INTERVAL {
+            return false;
+        }
+
+        let rate = progress.saturating_sub(last_progress) as f64 / elapsed.as_secs_f64();
+        self.current = Some(match self.current {
+            None => rate,
+            Some(current) => {
+                let alpha = 1.0 - (-elapsed.as_secs_f64() / SMOOTHING_WINDOW.as_secs_f64()).exp();
+                current + alpha * (rate - current)
+            }
+        });
+        self.last_sample = (now, progress);
+        true
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Smoothed throughput in bytes per second, if enough samples were taken.
+    pub fn current(&self) -> Option<u64> {
+        self.current.map(|c| c as u64)
+    }
+
+    /// Average throughput since the start in bytes per second.
+    pub fn average(&self, progress: u64) -> u64 {
+        self.average_at(progress, Instant::now())
+    }
+
+    fn average_at(&self, progress: u64, now: Instant) -> u64 {
+        let processed = progress.saturating_sub(self.start_progress);
+        match now.saturating_duration_since(self.started).as_millis() {
+            0 => processed,
+            ms => (processed as u128 * 1000 / ms) as u64,
+        }
+    }
+
+    /// Expected time to process the remaining bytes at the current throughput.
+    pub fn eta(&self, remaining: u64) -> Option<Duration> {
+        match self.current {
+            Some(c) if c >= 1.0 => Some(Duration::from_secs_f64(remaining as f64 / c)),
+            _ => None,
+        }
+    }
+}
+
+/// Formats the duration as `HH:MM:SS`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_timer_collects_stages() {
+        let schemes = SchemeRepo::default();
+        let task = WipeTask::new(
+            schemes.find("random2x").unwrap().clone(),
+            Verify::Last,
+            4096,
+            1024,
+        )
+        .unwrap();
+        let mut timer = StageTimer::new();
+        let mut state = WipeState::default();
+
+        let run_phase = |timer: &mut StageTimer, state: &mut WipeState, ok: bool| {
+            state.position = 0;
+            timer.record(&task, state, &WipeEvent::StageStarted);
+            state.position = 4096;
+            state.stamp.elapsed += Duration::from_millis(500);
+            let result = if ok {
+                None
+            } else {
+                Some(Rc::new(anyhow!("Verification failed!")))
+            };
+            timer.record(&task, state, &WipeEvent::StageCompleted(result));
+        };
+
+        run_phase(&mut timer, &mut state, true);
+        state.stage = 1;
+        run_phase(&mut timer, &mut state, true);
+        state.at_verification = true;
+        run_phase(&mut timer, &mut state, false);
+        state.at_verification = false;
+        timer.record(&task, &state, &WipeEvent::Retrying(Duration::from_secs(3)));
+        run_phase(&mut timer, &mut state, true);
+        state.at_verification = true;
+        run_phase(&mut timer, &mut state, true);
+
+        let stages = timer.stages();
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].stage, 1);
+        assert_eq!(stages[0].write.map(|w| w.bytes), Some(4096));
+        assert_eq!(stages[0].write.map(|w| w.duration_ms), Some(500));
+        assert_eq!(stages[0].write.map(|w| w.throughput), Some(8192));
+        assert_eq!(stages[0].verify, None);
+        assert_eq!(stages[0].retries, 0);
+        assert_eq!(stages[1].write.map(|w| w.bytes), Some(8192));
+        assert_eq!(stages[1].verify.map(|w| w.bytes), Some(8192));
+        assert_eq!(stages[1].verify.map(|w| w.duration_ms), Some(1000));
+        assert_eq!(stages[1].retries, 1);
+
+        timer.record(&task, &state, &WipeEvent::Started);
+        assert!(timer.stages().is_empty());
+    }
+
+    #[test]
+    fn test_throughput_meter_smooths_rate() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut meter = ThroughputMeter::started_at(1000, start);
+
+        assert!(!meter.update_at(2000, start));
+        assert_eq!(meter.current(), None);
+        assert_eq!(meter.eta(1000), None);
+
+        assert!(meter.update_at(101000, at(1)));
+        assert_eq!(meter.current(), Some(100000));
+        assert_eq!(meter.eta(1000000), Some(Duration::from_secs(10)));
+
+        // a sudden drop only partially affects the current rate
+        assert!(meter.update_at(111000, at(2)));
+        let current = meter.current().unwrap();
+        assert!(current < 100000 && current > 50000);
+        assert_eq!(meter.average_at(111000, at(2)), 55000);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "00:00:00");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "01:02:05");
+    }
+}
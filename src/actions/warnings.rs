@@ -0,0 +1,296 @@
+use crate::actions::probe::{Signature, SignatureKind};
+use crate::actions::WipeTask;
+use crate::storage::hidden::HiddenAreas;
+use crate::storage::{DeviceUser, MediaType, StorageDetails};
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Worth knowing, the wipe is still effective.
+    Notice,
+    /// Some of the data may survive the wipe.
+    Caution,
+    /// The wipe may break the running system or other volumes.
+    Danger,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Notice => f.write_str("notice"),
+            Severity::Caution => f.write_str("caution"),
+            Severity::Danger => f.write_str("danger"),
+        }
+    }
+}
+
+/// Something about the device worth knowing before confirming the wipe. The code is
+/// stable and can be used to suppress the warning.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Warning {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+pub const BOOT_DEVICE: &str = "boot-device";
+pub const MOUNTED: &str = "mounted";
+pub const RAID_METADATA: &str = "raid-metadata";
+pub const FLASH_OVERWRITE: &str = "flash-overwrite";
+pub const MULTIPASS_FLASH: &str = "multipass-flash";
+pub const THIN_PROVISIONED: &str = "thin-provisioned";
+pub const HIDDEN_AREA: &str = "hidden-area";
+
+pub const CODES: &[&str] = &[
+    BOOT_DEVICE,
+    MOUNTED,
+    RAID_METADATA,
+    FLASH_OVERWRITE,
+    MULTIPASS_FLASH,
+    THIN_PROVISIONED,
+    HIDDEN_AREA,
+];
+
+/// What is known about the device before the wipe.
+pub struct Preflight<'a> {
+    pub details: &'a StorageDetails,
+    pub users: &'a [DeviceUser],
+    pub signatures: &'a [Signature],
+    /// HPA and DCO left in place for the wipe.
+    pub hidden_areas: Option<&'a HiddenAreas>,
+}
+
+impl Preflight<'_> {
+    /// Warnings for the wipe of the device, the most severe first.
+    pub fn warnings(&self, task: &WipeTask) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let mut warn = |code, severity, message: String| {
+            warnings.push(Warning {
+                code,
+                severity,
+                message,
+            })
+        };
+
+        if self.details.is_boot {
+            warn(
+                BOOT_DEVICE,
+                Severity::Danger,
+                "The system is booted from this device!".to_string(),
+            );
+        }
+
+        let mounted: Vec<_> = self
+            .details
+            .mount_point
+            .iter()
+            .cloned()
+            .chain(
+                self.users
+                    .iter()
+                    .filter(|u| u.usage.starts_with("mounted"))
+                    .map(|u| u.name.clone()),
+            )
+            .collect();
+        if !mounted.is_empty() {
+            warn(
+                MOUNTED,
+                Severity::Danger,
+                format!(
+                    "Filesystems on the device are mounted: {}",
+                    mounted.join(", ")
+                ),
+            );
+        }
+
+        let members: Vec<_> = self
+            .signatures
+            .iter()
+            .filter(|s| matches!(s.kind, SignatureKind::MdRaid | SignatureKind::Lvm))
+            .map(|s| s.kind.to_string())
+            .collect();
+        if !members.is_empty() {
+            warn(
+                RAID_METADATA,
+                Severity::Danger,
+                format!(
+                    "Found {}, the arrays or volume groups using it will be degraded or lost",
+                    members.join(", ")
+                ),
+            );
+        }
+
+        if self.details.thin_provisioned {
+            warn(
+                THIN_PROVISIONED,
+                Severity::Caution,
+                "The device is thin-provisioned, overwriting it may not reach the blocks which held the data".to_string(),
+            );
+        }
+
+        if let Some(hidden) = self.hidden_areas.filter(|h| !h.is_empty()) {
+            warn(
+                HIDDEN_AREA,
+                Severity::Caution,
+                format!(
+                    "The drive hides {} sectors in a host protected area (HPA) and {} behind a device configuration overlay (DCO), they won't be wiped unless unlocked with --hidden-areas",
+                    hidden.hpa_sectors(),
+                    hidden.dco_sectors()
+                ),
+            );
+        }
+
+        let passes = task
+            .scheme
+            .stages
+            .iter()
+            .filter(|s| s.is_verifiable())
+            .count();
+        let flash = match self.details.media_type {
+            MediaType::Unknown => self.details.trim_supported,
+            m => m.is_flash(),
+        };
+        if flash && passes > 0 && !task.dry_run {
+            warn(
+                FLASH_OVERWRITE,
+                Severity::Caution,
+                "The device is likely flash storage, its controller may keep copies of the data in remapped or spare blocks which can't be overwritten".to_string(),
+            );
+        }
+
+        if self.details.media_type.is_flash() && passes > 1 && !task.dry_run {
+            let alternative = if self.details.trim_supported {
+                "the drive's own secure erase or the random-trim scheme"
+            } else {
+                "the drive's own secure erase or a single pass scheme"
+            };
+            warn(
+                MULTIPASS_FLASH,
+                Severity::Notice,
+                format!(
+                    "The scheme makes {} passes meant for rotational disks, on {} media they only add wear, consider {}",
+                    passes, self.details.media_type, alternative
+                ),
+            );
+        }
+
+        warnings.sort_by_key(|w| std::cmp::Reverse(w.severity));
+        warnings
+    }
+}
+
+/// Removes the warnings with the given codes, failing on unknown codes so typos don't
+/// go unnoticed.
+pub fn suppress(warnings: Vec<Warning>, codes: &[&str]) -> anyhow::Result<Vec<Warning>> {
+    if let Some(unknown) = codes.iter().find(|c| !CODES.contains(c)) {
+        return Err(anyhow!(
+            "Unknown warning code {}, use one of {}",
+            unknown,
+            CODES.join(", ")
+        ));
+    }
+    Ok(warnings
+        .into_iter()
+        .filter(|w| !codes.contains(&w.code))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::{Verify, WipeRange};
+    use crate::sanitization::SchemeRepo;
+
+    #[test]
+    fn test_warnings_are_collected_and_suppressed() {
+        let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::No, 1 << 20, 4096).unwrap();
+        let details = StorageDetails {
+            trim_supported: true,
+            is_boot: true,
+            ..Default::default()
+        };
+        let users = vec![DeviceUser {
+            pid: None,
+            name: "/dev/sdx1".to_string(),
+            usage: "mounted at /mnt".to_string(),
+        }];
+        let signatures = vec![Signature {
+            kind: SignatureKind::MdRaid,
+            offset: 4096,
+            regions: vec![WipeRange::new(4096, 8192)],
+        }];
+        let preflight = Preflight {
+            details: &details,
+            users: &users,
+            signatures: &signatures,
+            hidden_areas: Some(&HiddenAreas {
+                visible: 900,
+                native: 1000,
+                real: None,
+            }),
+        };
+
+        let warnings = preflight.warnings(&task);
+        let codes: Vec<_> = warnings.iter().map(|w| w.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                BOOT_DEVICE,
+                MOUNTED,
+                RAID_METADATA,
+                HIDDEN_AREA,
+                FLASH_OVERWRITE
+            ]
+        );
+        assert_eq!(
+            warnings[1].message,
+            "Filesystems on the device are mounted: /dev/sdx1"
+        );
+
+        assert_eq!(
+            warnings[3].message,
+            "The drive hides 100 sectors in a host protected area (HPA) and 0 behind a device configuration overlay (DCO), they won't be wiped unless unlocked with --hidden-areas"
+        );
+
+        let left = suppress(warnings.clone(), &[FLASH_OVERWRITE, MOUNTED]).unwrap();
+        assert_eq!(left.len(), 3);
+        assert!(suppress(warnings, &["typo"]).is_err());
+    }
+
+    #[test]
+    fn test_multipass_schemes_are_flagged_on_flash() {
+        let schemes = SchemeRepo::default();
+        let task = |name| {
+            let scheme = schemes.find(name).unwrap().clone();
+            WipeTask::new(scheme, Verify::No, 1 << 20, 4096).unwrap()
+        };
+        let codes = |media_type, name| {
+            let details = StorageDetails {
+                media_type,
+                ..Default::default()
+            };
+            let preflight = Preflight {
+                details: &details,
+                users: &[],
+                signatures: &[],
+                hidden_areas: None,
+            };
+            let warnings = preflight.warnings(&task(name));
+            warnings.iter().map(|w| w.code).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            codes(MediaType::SolidState, "random2x"),
+            vec![FLASH_OVERWRITE, MULTIPASS_FLASH]
+        );
+        assert_eq!(
+            codes(MediaType::RemovableFlash, "zero"),
+            vec![FLASH_OVERWRITE]
+        );
+        assert!(codes(MediaType::Rotational, "random2x").is_empty());
+        assert!(codes(MediaType::Unknown, "random2x").is_empty());
+    }
+}
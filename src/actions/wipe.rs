@@ -1,17 +1,62 @@
+use crate::actions::cancel::Cancelled;
+use crate::actions::checkpoint::Checkpointer;
+use crate::actions::digest::{GroupCheck, GroupChecker, StageDigests};
+use crate::actions::duty::{Throttle, TokenBucket};
+use crate::actions::entropy;
+use crate::actions::journal::VerificationJournal;
 use crate::actions::marker::{BlockMarker, RoaringBlockMarker};
+use crate::actions::schedule::Schedule;
+use crate::actions::{
+    CancellationToken, DutyCycle, Operator, PauseToken, RetryBackoff, RetryScope, WipeRange,
+};
 use crate::sanitization::mem::*;
 use crate::sanitization::*;
-use crate::storage::{StorageAccess, StorageError};
+use crate::storage::{Geometry, QueuedWriteFailed, StorageAccess, StorageError};
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug)]
+/// Maximum number of bytes discarded with a single request, keeps the progress moving.
+const DISCARD_CHUNK_SIZE: u64 = 1 << 30;
+
+/// Size of the region around a mismatched block which is rewritten and verified again
+/// before falling back to retrying the whole stage.
+const REPAIR_WINDOW: u64 = 64 << 20;
+
+/// Writing stages shorter than this are not checked for completing too fast, small writes
+/// can legitimately end up in the device cache.
+const MIN_TIMED_WRITE_SIZE: u64 = 1 << 30;
+
+/// How often a paused wipe checks whether it's resumed or cancelled.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Buffer alignment until `WipeTask::align_to` sets the one of the device, enough for
+/// both 512-byte and 4K sectors.
+const DEFAULT_IO_ALIGNMENT: usize = 4096;
+
+/// Ranges are aligned to the smallest sectors until `WipeTask::align_to` checks them
+/// against the ones of the device.
+const MIN_SECTOR_SIZE: usize = 512;
+
+/// Faster than any storage device can sustain writing.
+const MAX_WRITE_THROUGHPUT: u64 = 32 << 30;
+
+/// How much faster than the first writing stage the following ones may complete.
+const MAX_WRITE_SPEEDUP: u64 = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum Verify {
     No,
     Last,
     All,
+    /// Last stage only, checking a pseudo-random subset of blocks selected by the seed.
+    Sample {
+        percent: u8,
+        seed: u64,
+    },
 }
 
 impl Display for Verify {
@@ -20,16 +65,78 @@ impl Display for Verify {
             Verify::No => f.write_str("No"),
             Verify::Last => f.write_str("Last stage only"),
             Verify::All => f.write_str("After each stage"),
+            Verify::Sample { percent, .. } => {
+                write!(f, "Last stage only, {}% of blocks sampled", percent)
+            }
+        }
+    }
+}
+
+impl Verify {
+    pub fn sample(percent: u8) -> Result<Verify> {
+        if percent == 0 || percent > 100 {
+            return Err(anyhow!("Sample size should be between 1 and 100 percent"));
+        }
+        Ok(Verify::Sample {
+            percent,
+            seed: rand::random(),
+        })
+    }
+
+    /// Whether the block is checked during verification. The selection only depends
+    /// on the block number and the seed, so it's stable across retries and resumes.
+    pub fn is_block_sampled(&self, block: u64) -> bool {
+        match self {
+            Verify::Sample { percent, seed } => {
+                // splitmix64 finalizer
+                let mut z = block ^ seed;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                z ^= z >> 31;
+                z % 100 < *percent as u64
+            }
+            _ => true,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WipeTask {
     pub scheme: Scheme,
     pub verify: Verify,
     pub total_size: u64,
     pub block_size: usize,
+    /// Memory alignment of the data buffers, direct I/O needs it to match the sectors.
+    /// Set with `align_to`.
+    pub io_alignment: usize,
+    pub operator: Option<Operator>,
+    pub ranges: Vec<WipeRange>,
+    pub duty_cycle: Option<DutyCycle>,
+    /// Most bytes written or read for verification per second.
+    pub rate_limit: Option<u64>,
+    /// Time of the day the wipe is paused in.
+    pub schedule: Option<Schedule>,
+    pub trim_supported: bool,
+    pub continue_on_mismatch: bool,
+    pub retry_backoff: RetryBackoff,
+    pub retry_scope: RetryScope,
+    pub cancellation: CancellationToken,
+    pub pause: PauseToken,
+    /// Goes through all the stages without writing or reading anything.
+    pub dry_run: bool,
+    pub estimated_duration: Option<Duration>,
+    /// Expected time of each stage, see `estimate_stages`.
+    pub estimated_stage_durations: Vec<Duration>,
+    /// Verifies against keyed digests of the written data instead of generating it again.
+    pub digest_verification: bool,
+    /// Random stages are verified by checking that the data read back looks random,
+    /// without generating it again, see `entropy::looks_random`.
+    pub entropy_verification: bool,
+    /// Only verifies the current content of the device, nothing is written.
+    pub verify_only: bool,
+    /// Writes failing with a bad block error are retried in halved pieces down to this size
+    /// before the block is marked as bad. Set with `set_min_block_size`.
+    pub min_block_size: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -37,8 +144,17 @@ pub struct WipeState {
     pub stage: usize,
     pub at_verification: bool,
     pub position: u64,
+    /// Retries left after write or read errors.
     pub retries_left: u32,
+    /// Retries left after the written data didn't match during verification.
+    pub verify_retries_left: u32,
     pub bad_blocks: Rc<RefCell<dyn BlockMarker>>,
+    pub mismatched_blocks: Rc<RefCell<dyn BlockMarker>>,
+    pub verified_blocks: u64,
+    /// Blocks verified so far in the current stage, kept only when running with checkpoints.
+    pub journal: VerificationJournal,
+    /// Stamp of the event being handled.
+    pub stamp: EventStamp,
 }
 
 pub struct WipeRun<'a> {
@@ -46,6 +162,17 @@ pub struct WipeRun<'a> {
     pub task: &'a WipeTask,
     pub state: &'a mut WipeState,
     pub frontend: &'a mut dyn WipeEventReceiver,
+    pub checkpointer: Option<&'a mut Checkpointer>,
+    throttle: Option<Throttle>,
+    pacing: Option<TokenBucket>,
+    digests: Option<StageDigests>,
+    /// Size of the writes, reduced from the block size after write errors.
+    write_size: usize,
+    started: Instant,
+    /// Throughput of the first writing stage, the following ones are expected to be similar.
+    write_throughput: Option<u64>,
+    /// Retries so far, sets the delay of the next one.
+    retries: u32,
 }
 
 impl Default for WipeState {
@@ -55,13 +182,31 @@ impl Default for WipeState {
             at_verification: false,
             position: 0,
             retries_left: 0,
+            verify_retries_left: 0,
             bad_blocks: Rc::new(RefCell::new(RoaringBlockMarker::new())),
+            mismatched_blocks: Rc::new(RefCell::new(RoaringBlockMarker::new())),
+            verified_blocks: 0,
+            journal: VerificationJournal::default(),
+            stamp: EventStamp::default(),
         }
     }
 }
 
 impl WipeTask {
+    /// A block size larger than the device is reduced to the smallest power of two
+    /// covering the device, so the single (partial) block doesn't need an oversized buffer.
     pub fn new(scheme: Scheme, verify: Verify, total_size: u64, block_size: usize) -> Result<Self> {
+        if total_size == 0 {
+            Err(anyhow!("Nothing to wipe, the device is empty."))?;
+        }
+        if block_size == 0 {
+            Err(anyhow!("Block size should be more than zero."))?;
+        }
+        let block_size = if block_size as u64 > total_size {
+            total_size.next_power_of_two() as usize
+        } else {
+            block_size
+        };
         if total_size / block_size as u64 > 1 << 32 {
             Err(anyhow!(
                 "Number of blocks in this device is more than 2^32. Try using a bigger block size."
@@ -72,38 +217,312 @@ impl WipeTask {
             verify,
             total_size,
             block_size,
+            io_alignment: block_size.min(DEFAULT_IO_ALIGNMENT),
+            operator: None,
+            ranges: vec![WipeRange::new(0, total_size)],
+            duty_cycle: None,
+            rate_limit: None,
+            schedule: None,
+            trim_supported: false,
+            continue_on_mismatch: false,
+            retry_backoff: RetryBackoff::default(),
+            retry_scope: RetryScope::Stage,
+            cancellation: CancellationToken::new(),
+            pause: PauseToken::new(),
+            dry_run: false,
+            estimated_duration: None,
+            estimated_stage_durations: Vec::new(),
+            digest_verification: false,
+            entropy_verification: false,
+            verify_only: false,
+            min_block_size: None,
         })
     }
+
+    /// Reads the whole device and compares it with the value without writing anything.
+    /// The blocks holding anything else are marked as mismatched.
+    pub fn blank_check(value: u8, total_size: u64, block_size: usize) -> Result<Self> {
+        let scheme = Scheme {
+            description: format!("Blank check (0x{:02x})", value),
+            stages: vec![Stage::constant(value)],
+        };
+        let mut task = WipeTask::new(scheme, Verify::All, total_size, block_size)?;
+        task.verify_only = true;
+        task.continue_on_mismatch = true;
+        Ok(task)
+    }
+
+    /// Limits the wipe to the given ranges instead of the whole device.
+    pub fn set_ranges(&mut self, ranges: Vec<WipeRange>) -> Result<()> {
+        self.ranges = WipeRange::normalize(ranges, self.total_size, MIN_SECTOR_SIZE)?;
+        Ok(())
+    }
+
+    /// Enables retrying failed writes with smaller pieces, down to the given size.
+    pub fn set_min_block_size(&mut self, min_block_size: usize) -> Result<()> {
+        if min_block_size == 0 || min_block_size > self.block_size {
+            return Err(anyhow!(
+                "Minimum block size should be between 1 and the block size ({})",
+                self.block_size
+            ));
+        }
+        self.min_block_size = Some(min_block_size);
+        Ok(())
+    }
+
+    /// Aligns the data buffers to the sectors of the device. Fails when the block size (or
+    /// the minimum block size) is not a multiple of the logical sector size, or a range
+    /// doesn't start and end at a sector, as direct I/O would reject such writes.
+    pub fn align_to(&mut self, geometry: &Geometry) -> Result<()> {
+        let sector_size = geometry.sector_size.max(1) as usize;
+        for (name, size) in [
+            ("Block size", Some(self.block_size)),
+            ("Minimum block size", self.min_block_size),
+        ] {
+            match size {
+                Some(s) if s % sector_size != 0 => {
+                    return Err(anyhow!(
+                        "{} {} is not a multiple of the {}-byte sectors of the device.",
+                        name,
+                        s,
+                        sector_size
+                    ))
+                }
+                _ => {}
+            }
+        }
+        if let Some(r) = self
+            .ranges
+            .iter()
+            .find(|r| !r.is_aligned(sector_size, self.total_size))
+        {
+            return Err(anyhow!(
+                "Range {} is not aligned to the {}-byte sectors of the device.",
+                r,
+                sector_size
+            ));
+        }
+        self.io_alignment = geometry
+            .physical_sector_size
+            .max(geometry.sector_size)
+            .max(1)
+            .next_power_of_two() as usize;
+        Ok(())
+    }
+
+    pub fn is_partial(&self) -> bool {
+        self.ranges != [WipeRange::new(0, self.total_size)]
+    }
+
+    /// Total number of bytes to be wiped in each stage.
+    pub fn wiped_size(&self) -> u64 {
+        self.ranges.iter().map(|r| r.len()).sum()
+    }
+
+    /// Number of blocks to be wiped in each stage, a partial block counts as a whole one.
+    pub fn total_blocks(&self) -> u64 {
+        self.wiped_size().div_ceil(self.block_size as u64)
+    }
+
+    /// Byte ranges covered by the given blocks, with adjacent blocks merged together.
+    pub fn block_ranges(&self, blocks: &[u32]) -> Vec<WipeRange> {
+        WipeRange::from_blocks(blocks, self.block_size, self.total_size)
+    }
+
+    /// Expected time to complete the wipe given the device read throughput in bytes per second.
+    /// Writing is assumed to be as fast as reading, discards are not accounted for.
+    pub fn estimate_duration(&self, throughput: u64) -> Duration {
+        self.estimate_stages(throughput, throughput).iter().sum()
+    }
+
+    /// Expected time of each stage, including its verification, given the device read and
+    /// write throughput in bytes per second. Discards are not accounted for.
+    pub fn estimate_stages(&self, read_throughput: u64, write_throughput: u64) -> Vec<Duration> {
+        let stages = &self.scheme.stages;
+        let last_verifiable = stages.iter().rposition(|s| s.is_verifiable());
+        let size = self.wiped_size() as f64;
+        stages
+            .iter()
+            .enumerate()
+            .map(|(i, stage)| {
+                if !stage.is_verifiable() {
+                    return Duration::ZERO;
+                }
+                let verified_percent = match self.verify {
+                    Verify::All => 100,
+                    Verify::Last if last_verifiable == Some(i) => 100,
+                    Verify::Sample { percent, .. } if last_verifiable == Some(i) => percent,
+                    _ => 0,
+                };
+                let write = if self.verify_only {
+                    0.0
+                } else {
+                    size / write_throughput.max(1) as f64
+                };
+                let verify = size * verified_percent as f64 / 100.0 / read_throughput.max(1) as f64;
+                Duration::from_secs_f64(write + verify)
+            })
+            .collect()
+    }
+
+    /// Number of bytes of a stage already processed when it reaches the given position.
+    pub fn progress_at(&self, position: u64) -> u64 {
+        self.ranges
+            .iter()
+            .map(|r| position.clamp(r.start, r.end) - r.start)
+            .sum()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum WipeEvent {
     Started,
     StageStarted,
+    StageSkipped,
     Progress(u64),
     MarkBlockAsBad(u64),
+    MarkBlockAsMismatched(u64),
+    /// Rewriting the region around a mismatched block.
+    Repairing(WipeRange),
+    /// Writes keep failing, continuing with smaller writes of this size.
+    BlockSizeReduced(usize),
     StageCompleted(Option<Rc<anyhow::Error>>),
-    Retrying,
+    /// Retrying the stage after the given delay.
+    Retrying(Duration),
     Completed(Option<Rc<anyhow::Error>>),
+    /// The written data is flushed and the wipe waits for `PauseToken::resume`, the state
+    /// holds the position it continues from.
+    Paused,
+    /// The written data is flushed and the wipe waits until the given time, as it's in the
+    /// pause window of its schedule.
+    PausedUntil(SystemTime),
+    Resumed,
+    /// The wipe was cancelled, the state holds the position it can be resumed from.
+    Aborted,
     Fatal(Rc<anyhow::Error>),
 }
 
+/// Order and time of a wipe event, set in the state right before the event is published.
+/// Receivers can rely on it instead of their own clocks, e.g. when the events are buffered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventStamp {
+    /// Starts from 1 and increases with every event of the run.
+    pub sequence: u64,
+    pub timestamp: SystemTime,
+    /// Monotonic time since the run started.
+    pub elapsed: Duration,
+}
+
+impl EventStamp {
+    pub fn timestamp_millis(&self) -> u64 {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+    }
+}
+
+impl Default for EventStamp {
+    fn default() -> Self {
+        EventStamp {
+            sequence: 0,
+            timestamp: UNIX_EPOCH,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
 pub trait WipeEventReceiver {
     fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) -> ();
 }
 
+/// How a wipe run ended, the details are in the `Completed` event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WipeOutcome {
+    Completed,
+    /// Completed, but some blocks were marked as bad and skipped.
+    CompletedWithBadBlocks,
+    /// Stopped by the user (or Ctrl+C).
+    Aborted,
+    /// The data read back doesn't match the one written.
+    VerificationFailed,
+    /// The device refused the access.
+    PermissionDenied,
+    /// The device is gone, e.g. disconnected during the wipe.
+    DeviceNotFound,
+    /// Read or write errors, or any other failure.
+    Failed,
+}
+
+impl WipeOutcome {
+    pub fn is_success(self) -> bool {
+        matches!(
+            self,
+            WipeOutcome::Completed | WipeOutcome::CompletedWithBadBlocks
+        )
+    }
+
+    pub(crate) fn of_error(error: &anyhow::Error) -> WipeOutcome {
+        if error.is::<Cancelled>() {
+            return WipeOutcome::Aborted;
+        }
+        if error.is::<VerificationMismatch>() {
+            return WipeOutcome::VerificationFailed;
+        }
+        match underlying_storage_error(error) {
+            Some(StorageError::PermissionDenied(_)) => WipeOutcome::PermissionDenied,
+            Some(StorageError::NotFound(_)) => WipeOutcome::DeviceNotFound,
+            _ => WipeOutcome::Failed,
+        }
+    }
+}
+
 impl WipeTask {
     pub fn run(
         self,
         access: &mut dyn StorageAccess,
         state: &mut WipeState,
         frontend: &mut dyn WipeEventReceiver,
-    ) -> bool {
+    ) -> WipeOutcome {
+        WipeRun {
+            access,
+            task: &self,
+            state,
+            frontend,
+            checkpointer: None,
+            throttle: self.duty_cycle.map(Throttle::new),
+            pacing: self.rate_limit.map(TokenBucket::new),
+            digests: None,
+            write_size: self.block_size,
+            started: Instant::now(),
+            write_throughput: None,
+            retries: 0,
+        }
+        .run()
+    }
+
+    /// Same as `run` but also keeps a checkpoint of the progress so that the wipe can be resumed.
+    /// The state may come from a previously saved checkpoint, in which case the wipe continues
+    /// from the recorded stage and position.
+    pub fn run_with_checkpoints(
+        self,
+        access: &mut dyn StorageAccess,
+        state: &mut WipeState,
+        frontend: &mut dyn WipeEventReceiver,
+        checkpointer: &mut Checkpointer,
+    ) -> WipeOutcome {
         WipeRun {
             access,
             task: &self,
             state,
             frontend,
+            checkpointer: Some(checkpointer),
+            throttle: self.duty_cycle.map(Throttle::new),
+            pacing: self.rate_limit.map(TokenBucket::new),
+            digests: None,
+            write_size: self.block_size,
+            started: Instant::now(),
+            write_throughput: None,
+            retries: 0,
         }
         .run()
     }
@@ -111,27 +530,123 @@ impl WipeTask {
 
 impl WipeRun<'_> {
     fn publish(&mut self, event: WipeEvent) {
+        self.state.stamp = EventStamp {
+            sequence: self.state.stamp.sequence + 1,
+            timestamp: SystemTime::now(),
+            elapsed: self.started.elapsed(),
+        };
+        if let Some(c) = &mut self.checkpointer {
+            if c.update(self.task, self.state, &event).is_err() {
+                // checkpoints are a convenience, failing to save one shouldn't stop the wipe
+                self.checkpointer = None;
+            }
+        }
         self.frontend.handle(self.task, self.state, event)
     }
 
-    fn build_stream(&self, stage: &Stage) -> SanitizationStream {
+    fn build_stream(&self, stage: &Stage, end: u64) -> SanitizationStream {
         stage.stream(
-            self.task.total_size,
+            end,
             self.task.block_size,
+            self.task.io_alignment,
             self.state.position,
         )
     }
 
-    fn advance(&mut self, bytes: usize) {
+    /// Ranges (or their parts) which are not yet processed in the current stage.
+    fn remaining_ranges(&self) -> Vec<WipeRange> {
+        self.task
+            .ranges
+            .iter()
+            .filter(|r| r.end > self.state.position)
+            .cloned()
+            .collect()
+    }
+
+    fn advance(&mut self, bytes: usize, end: u64) {
         self.state.position += bytes as u64;
-        if self.state.position > self.task.total_size {
-            self.state.position = self.task.total_size
+        if self.state.position > end {
+            self.state.position = end
         }
         self.publish(WipeEvent::Progress(self.state.position));
+
+        if let Some(t) = &mut self.throttle {
+            t.tick();
+        }
+        if let Some(p) = &mut self.pacing {
+            p.take(bytes as u64);
+        }
+    }
+
+    fn check_cancelled(&mut self) -> Result<()> {
+        if self.task.pause.is_paused() && !self.task.cancellation.is_cancelled() {
+            self.wait_while_paused()?;
+        }
+        if let Some(left) = self.task.schedule.and_then(|s| s.pause_left()) {
+            if !self.task.cancellation.is_cancelled() {
+                self.wait_for_schedule(left)?;
+            }
+        }
+        if self.task.cancellation.is_cancelled() {
+            Err(Cancelled)?;
+        }
+        Ok(())
+    }
+
+    fn wait_for_schedule(&mut self, left: Duration) -> Result<()> {
+        if !self.task.dry_run {
+            self.access.flush()?;
+        }
+        self.publish(WipeEvent::PausedUntil(SystemTime::now() + left));
+        // the window is checked again rather than waited out, the clock may be changed
+        while self.task.schedule.and_then(|s| s.pause_left()).is_some()
+            && !self.task.cancellation.is_cancelled()
+        {
+            std::thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+        if !self.task.cancellation.is_cancelled() {
+            self.publish(WipeEvent::Resumed);
+        }
+        Ok(())
+    }
+
+    fn wait_while_paused(&mut self) -> Result<()> {
+        if !self.task.dry_run {
+            self.access.flush()?;
+        }
+        self.publish(WipeEvent::Paused);
+        while self.task.pause.is_paused() && !self.task.cancellation.is_cancelled() {
+            std::thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+        if !self.task.cancellation.is_cancelled() {
+            self.publish(WipeEvent::Resumed);
+        }
+        Ok(())
+    }
+
+    /// Waits before a retry, the wait is cut short if the wipe is cancelled.
+    fn back_off(&mut self) {
+        let delay = self.task.retry_backoff.delay(self.retries);
+        self.retries += 1;
+        self.publish(WipeEvent::Retrying(delay));
+
+        let started = Instant::now();
+        while started.elapsed() < delay && !self.task.cancellation.is_cancelled() {
+            std::thread::sleep(PAUSE_POLL_INTERVAL.min(delay - started.elapsed()));
+        }
     }
 
-    fn at_the_end(&self) -> bool {
-        self.state.position >= self.task.total_size
+    /// Moves back to the queued write which failed after the position had moved past it,
+    /// so the retry (or a resume) writes it again.
+    fn rewind_to_failed_write(&mut self, err: &anyhow::Error) {
+        if let Some(failed) = err
+            .chain()
+            .find_map(|c| c.downcast_ref::<QueuedWriteFailed>())
+        {
+            let block_size = self.task.block_size as u64;
+            let block_start = failed.position / block_size * block_size;
+            self.state.position = self.state.position.min(block_start);
+        }
     }
 
     fn current_block_number(&self) -> u32 {
@@ -145,7 +660,7 @@ impl WipeRun<'_> {
             .is_marked(self.current_block_number())
     }
 
-    fn mark_bad_block(&mut self) -> () {
+    fn mark_bad_block(&mut self) {
         self.state
             .bad_blocks
             .borrow_mut()
@@ -153,19 +668,24 @@ impl WipeRun<'_> {
         self.publish(WipeEvent::MarkBlockAsBad(self.state.position));
     }
 
+    /// Marks the current block as bad if the error is caused by it, otherwise passes it on.
+    fn skip_bad_block(&mut self, err: anyhow::Error) -> Result<bool> {
+        match underlying_storage_error(&err) {
+            Some(StorageError::BadBlock) => {
+                self.mark_bad_block();
+                Ok(false)
+            }
+            _ => Err(err),
+        }
+    }
+
     fn try_seek(&mut self) -> Result<bool> {
         if self.is_at_bad_block() {
             return Ok(false);
         }
 
         if let Err(err) = self.access.seek(self.state.position) {
-            return match underlying_storage_error(&err) {
-                Some(StorageError::BadBlock) => {
-                    self.mark_bad_block();
-                    Ok(false)
-                }
-                _ => Err(err),
-            };
+            return self.skip_bad_block(err);
         }
 
         Ok(true)
@@ -176,26 +696,55 @@ impl WipeRun<'_> {
             return Ok(false);
         }
 
-        if let Err(err) = self.access.write(chunk) {
-            return match underlying_storage_error(&err) {
-                Some(StorageError::BadBlock) => {
-                    self.mark_bad_block();
-                    Ok(false)
+        if self.task.dry_run {
+            return Ok(true);
+        }
+
+        let mut offset = 0;
+        while offset < chunk.len() {
+            let piece = &chunk[offset..chunk.len().min(offset + self.write_size)];
+            let err = match self.access.write(piece) {
+                Ok(()) => {
+                    offset += piece.len();
+                    continue;
                 }
-                _ => Err(err),
+                Err(err) => err,
             };
+
+            let is_bad_block =
+                matches!(underlying_storage_error(&err), Some(StorageError::BadBlock));
+            if !is_bad_block || !self.reduce_write_size() {
+                return self.skip_bad_block(err);
+            }
+            if let Err(err) = self.access.seek(self.state.position + offset as u64) {
+                return self.skip_bad_block(err);
+            }
         }
         Ok(true)
     }
 
-    fn seek_to_the_next_safe_position(&mut self) -> Result<()> {
+    /// Halves the size of the writes unless it's already at the minimum.
+    /// Some devices (e.g. failing USB bridges) keep working with smaller writes.
+    fn reduce_write_size(&mut self) -> bool {
+        match self.task.min_block_size {
+            Some(min) if self.write_size / 2 >= min => {
+                self.write_size /= 2;
+                self.publish(WipeEvent::BlockSizeReduced(self.write_size));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn seek_to_the_next_safe_position(&mut self, end: u64) -> Result<()> {
         loop {
-            if self.at_the_end() {
+            if self.state.position >= end {
                 break;
             }
+            self.check_cancelled()?;
 
             if self.is_at_bad_block() || !self.try_seek()? {
-                self.advance(self.task.block_size);
+                self.advance(self.task.block_size, end);
                 continue;
             }
 
@@ -204,59 +753,120 @@ impl WipeRun<'_> {
         Ok(())
     }
 
-    fn run(&mut self) -> bool {
+    fn run(&mut self) -> WipeOutcome {
         self.publish(WipeEvent::Started);
 
         let stages = &self.task.scheme.stages;
+        let first_stage = self.state.stage;
+        let last_verifiable = stages.iter().rposition(|s| s.is_verifiable());
 
         let mut wipe_error = None;
+        // failing bridges may report a different capacity mid-run, checked between the stages
+        let geometry = self.access.geometry().ok().flatten();
 
-        for (i, stage) in stages.iter().enumerate() {
-            let have_to_verify = match self.task.verify {
-                Verify::No => false,
-                Verify::Last if i + 1 == stages.len() => true,
-                Verify::All => true,
-                _ => false,
-            };
+        for (i, stage) in stages.iter().enumerate().skip(first_stage) {
+            let have_to_verify = stage.is_verifiable()
+                && match self.task.verify {
+                    Verify::No => false,
+                    Verify::Last | Verify::Sample { .. } => last_verifiable == Some(i),
+                    Verify::All => true,
+                };
 
             self.state.stage = i;
-            self.state.position = 0;
-            self.state.at_verification = false;
+            if i != first_stage {
+                self.state.position = 0;
+                self.state.at_verification = false;
+                self.restart_verification();
+            }
+            if self.task.verify_only {
+                if !have_to_verify {
+                    continue;
+                }
+                self.state.at_verification = true;
+            }
 
-            let stage_error = loop {
-                let watermark = self.state.position;
+            let use_digests = self.task.digest_verification
+                && have_to_verify
+                && !self.task.dry_run
+                && !matches!(self.task.verify, Verify::Sample { .. });
+            self.digests = if use_digests {
+                Some(StageDigests::new(self.task.block_size))
+            } else {
+                None
+            };
 
-                self.publish(WipeEvent::StageStarted);
-                if let Err(err) = self.fill(stage) {
-                    let err_rc = Rc::from(err);
-                    self.publish(WipeEvent::StageCompleted(Some(Rc::clone(&err_rc))));
+            if let Err(err) = self
+                .check_cancelled()
+                .and_then(|_| self.check_geometry(geometry))
+            {
+                wipe_error = Some(Rc::from(err));
+                break;
+            }
 
-                    if self.state.retries_left > 0 {
-                        self.state.retries_left -= 1;
-                        self.publish(WipeEvent::Retrying);
-                        continue;
+            if let (Stage::Discard, false) = (stage, self.task.trim_supported) {
+                self.publish(WipeEvent::StageSkipped);
+                continue;
+            }
+
+            let stage_error = loop {
+                // a resumed verification starts over from where the stage did
+                let watermark = if self.state.at_verification {
+                    self.state.journal.start
+                } else {
+                    self.state.position
+                };
+
+                if !self.state.at_verification {
+                    self.publish(WipeEvent::StageStarted);
+                    let fill_started = Instant::now();
+                    let result = self
+                        .fill(stage)
+                        .and_then(|_| self.check_write_duration(stage, watermark, fill_started));
+                    if let Err(err) = result {
+                        if err.is::<Cancelled>() {
+                            break Some(Rc::from(err));
+                        }
+                        self.rewind_to_failed_write(&err);
+                        let err_rc = Rc::from(err);
+                        self.publish(WipeEvent::StageCompleted(Some(Rc::clone(&err_rc))));
+
+                        if self.state.retries_left > 0 {
+                            self.state.retries_left -= 1;
+                            self.back_off();
+                            continue;
+                        }
+
+                        break Some(err_rc);
                     }
+                    self.publish(WipeEvent::StageCompleted(None));
 
-                    break Some(err_rc);
-                }
-                self.publish(WipeEvent::StageCompleted(None));
+                    if !have_to_verify {
+                        break None;
+                    }
 
-                if !have_to_verify {
-                    break None;
+                    self.state.position = watermark;
+                    self.state.at_verification = true;
+                    self.restart_verification();
                 }
 
-                self.state.position = watermark;
-                self.state.at_verification = true;
-
                 self.publish(WipeEvent::StageStarted);
                 if let Err(err) = self.verify(stage) {
+                    if err.is::<Cancelled>() {
+                        break Some(Rc::from(err));
+                    }
+                    let mismatch = err.is::<VerificationMismatch>();
                     let err_rc = Rc::from(err);
                     self.publish(WipeEvent::StageCompleted(Some(Rc::clone(&err_rc))));
 
-                    if self.state.retries_left > 0 {
-                        self.state.retries_left -= 1;
+                    let retries_left = if mismatch {
+                        &mut self.state.verify_retries_left
+                    } else {
+                        &mut self.state.retries_left
+                    };
+                    if *retries_left > 0 && !self.task.verify_only {
+                        *retries_left -= 1;
                         self.state.at_verification = false;
-                        self.publish(WipeEvent::Retrying);
+                        self.back_off();
                         continue;
                     }
                     break Some(err_rc);
@@ -271,35 +881,146 @@ impl WipeRun<'_> {
             };
         }
 
-        let result = wipe_error.is_none();
+        if matches!(&wipe_error, Some(e) if e.is::<Cancelled>()) {
+            // the position saved on abort must not be ahead of the queued writes
+            if !self.task.dry_run && !self.state.at_verification {
+                if let Err(err) = self.access.flush() {
+                    self.rewind_to_failed_write(&err);
+                }
+            }
+            self.publish(WipeEvent::Aborted);
+            return WipeOutcome::Aborted;
+        }
+
+        if wipe_error.is_none() {
+            wipe_error = self.check_geometry(geometry).err().map(Rc::from);
+        }
+
+        let mismatched = self.state.mismatched_blocks.borrow().total_marked();
+        if wipe_error.is_none() && mismatched > 0 {
+            wipe_error = Some(Rc::new(anyhow!(
+                "Verification failed for {} block(s)",
+                mismatched
+            )));
+        }
+
+        let outcome = match &wipe_error {
+            None if self.state.bad_blocks.borrow().total_marked() > 0 => {
+                WipeOutcome::CompletedWithBadBlocks
+            }
+            None => WipeOutcome::Completed,
+            Some(_) if mismatched > 0 => WipeOutcome::VerificationFailed,
+            Some(e) => WipeOutcome::of_error(e),
+        };
         self.publish(WipeEvent::Completed(wipe_error));
 
-        result
+        outcome
+    }
+
+    /// Fails if the device now reports a different geometry than at the start of the run,
+    /// or a size smaller than the one of the task.
+    fn check_geometry(&mut self, initial: Option<Geometry>) -> Result<()> {
+        let initial = match initial {
+            Some(g) => g,
+            None => return Ok(()),
+        };
+        let current = match self.access.geometry() {
+            Ok(Some(g)) => g,
+            // the following reads and writes fail anyway if the device is gone
+            _ => return Ok(()),
+        };
+        if current != initial || current.size < self.task.total_size {
+            Err(GeometryChanged {
+                before: initial,
+                after: current,
+            })?;
+        }
+        Ok(())
     }
 
     fn fill(&mut self, stage: &Stage) -> Result<()> {
         self.publish(WipeEvent::Progress(self.state.position));
 
-        self.seek_to_the_next_safe_position()?;
+        if let Some(d) = &mut self.digests {
+            d.rewind(self.state.position);
+        }
+
+        for range in self.remaining_ranges() {
+            self.state.position = self.state.position.max(range.start);
+            match stage {
+                Stage::Discard => self.discard_range(range.end)?,
+                _ => self.fill_range(stage, range.end)?,
+            }
+            if let Some(d) = &mut self.digests {
+                d.finish();
+            }
+        }
+
+        self.access.flush()?;
+
+        Ok(())
+    }
+
+    /// Fails the stage if it was completed faster than the device can write, which means
+    /// the data didn't actually reach it (e.g. a wrongly resolved size or a caching layer).
+    fn check_write_duration(&mut self, stage: &Stage, from: u64, started: Instant) -> Result<()> {
+        if self.task.dry_run || !stage.is_verifiable() {
+            return Ok(());
+        }
+
+        let skipped =
+            self.state.bad_blocks.borrow().total_marked() as u64 * self.task.block_size as u64;
+        let written =
+            (self.task.wiped_size() - self.task.progress_at(from)).saturating_sub(skipped);
+        if written < MIN_TIMED_WRITE_SIZE {
+            return Ok(());
+        }
+
+        let throughput = check_write_throughput(written, started.elapsed(), self.write_throughput)?;
+        self.write_throughput.get_or_insert(throughput);
+        Ok(())
+    }
+
+    fn fill_range(&mut self, stage: &Stage, end: u64) -> Result<()> {
+        self.seek_to_the_next_safe_position(end)?;
 
-        if self.at_the_end() {
+        if self.state.position >= end {
             return Ok(());
         }
 
-        let mut stream = self.build_stream(stage);
+        let mut stream = self.build_stream(stage, end);
         let mut skip_next = false;
 
         while let Some(chunk) = stream.next() {
+            self.check_cancelled()?;
             if skip_next || !self.try_write(chunk)? {
-                self.advance(chunk.len());
+                self.advance(chunk.len(), end);
                 skip_next = !self.try_seek()?;
                 continue;
             }
 
-            self.advance(chunk.len());
+            if let Some(d) = &mut self.digests {
+                d.record(self.state.position, chunk);
+            }
+            if !self.task.dry_run && self.checkpointer.as_ref().is_some_and(|c| c.is_due()) {
+                // the saved position must not be ahead of the queued writes
+                self.access.flush()?;
+            }
+            self.advance(chunk.len(), end);
         }
 
-        self.access.flush()?;
+        Ok(())
+    }
+
+    fn discard_range(&mut self, end: u64) -> Result<()> {
+        while self.state.position < end {
+            self.check_cancelled()?;
+            let length = (end - self.state.position).min(DISCARD_CHUNK_SIZE);
+            if !self.task.dry_run {
+                self.access.discard(self.state.position, length)?;
+            }
+            self.advance(length as usize, end);
+        }
 
         Ok(())
     }
@@ -307,65 +1028,580 @@ impl WipeRun<'_> {
     fn verify(&mut self, stage: &Stage) -> Result<()> {
         self.publish(WipeEvent::Progress(self.state.position));
 
-        self.seek_to_the_next_safe_position()?;
+        let mut buf = AlignedBuffer::new(self.task.block_size, self.task.io_alignment);
+        self.check_journal(&mut buf)?;
+
+        for range in self.remaining_ranges() {
+            self.state.position = self.state.position.max(range.start);
+            if self.digests.is_some() {
+                self.verify_range_by_digests(stage, range, &mut buf)?;
+            } else if self.task.entropy_verification && matches!(stage, Stage::Random { .. }) {
+                self.verify_range_by_entropy(stage, range, &mut buf)?;
+            } else {
+                self.verify_range(stage, range, &mut buf)?;
+            }
+        }
 
-        if self.at_the_end() {
-            return Ok(());
+        Ok(())
+    }
+
+    /// Starts the verification of the stage over from the current position.
+    fn restart_verification(&mut self) {
+        self.state.verified_blocks = 0;
+        self.state.journal = VerificationJournal::new(self.state.position);
+    }
+
+    fn record_verified(&mut self, position: u64, data: &[u8]) {
+        self.state.verified_blocks += 1;
+        if self.checkpointer.is_some() {
+            self.state.journal.record(position, data);
+        }
+    }
+
+    /// Reads the last block recorded in the journal of a resumed verification again, and
+    /// starts the verification over if the device doesn't hold the same data anymore.
+    fn check_journal(&mut self, buf: &mut AlignedBuffer) -> Result<()> {
+        let last = match self.state.journal.last_block {
+            Some(b) if !self.task.dry_run => b,
+            _ => return Ok(()),
+        };
+        let matches = last.length <= self.task.block_size && {
+            let b = &mut buf.as_mut_slice()[..last.length];
+            self.access.seek(last.position)?;
+            self.access.read(b)?;
+            self.state.journal.matches_last_block(b)
+        };
+        if !matches {
+            self.state.position = self.state.journal.start;
+            self.restart_verification();
+            self.publish(WipeEvent::Progress(self.state.position));
         }
+        Ok(())
+    }
+
+    fn verify_range(
+        &mut self,
+        stage: &Stage,
+        range: WipeRange,
+        buf: &mut AlignedBuffer,
+    ) -> Result<()> {
+        let end = range.end;
+        self.seek_to_the_next_safe_position(end)?;
 
-        let mut stream = self.build_stream(stage);
+        if self.state.position >= end {
+            return Ok(());
+        }
 
-        let buf = AlignedBuffer::new(self.task.block_size, self.task.block_size);
+        let mut stream = self.build_stream(stage, end);
 
         while let Some(chunk) = stream.next() {
-            if self.is_at_bad_block() {
-                self.advance(chunk.len());
+            self.check_cancelled()?;
+            let sampled = self
+                .task
+                .verify
+                .is_block_sampled(self.current_block_number() as u64);
+
+            if self.is_at_bad_block() || !sampled {
+                self.advance(chunk.len(), end);
                 self.try_seek()?;
                 continue;
             }
 
+            if self.task.dry_run {
+                self.state.verified_blocks += 1;
+                self.advance(chunk.len(), end);
+                continue;
+            }
+
             let b = &mut buf.as_mut_slice()[..chunk.len()];
 
-            self.access.read(b)?;
+            self.access.read_back(b, chunk)?;
 
             if b != chunk {
-                Err(anyhow!("Verification failed!"))?;
+                let repaired = self.repair_region(stage, range)?;
+                if !repaired {
+                    if !self.task.continue_on_mismatch {
+                        Err(VerificationMismatch)?;
+                    }
+                    self.state
+                        .mismatched_blocks
+                        .borrow_mut()
+                        .mark(self.current_block_number());
+                    self.publish(WipeEvent::MarkBlockAsMismatched(self.state.position));
+                }
+                self.record_verified(self.state.position, if repaired { chunk } else { b });
+                self.advance(chunk.len(), end);
+                self.try_seek()?;
+                continue;
             }
+            self.record_verified(self.state.position, b);
 
-            self.advance(chunk.len());
+            self.advance(chunk.len(), end);
         }
 
         Ok(())
     }
-}
 
-// taken directly from https://docs.rs/anyhow/1.0.9/anyhow/struct.Error.html#example
-pub fn underlying_storage_error(error: &anyhow::Error) -> Option<&StorageError> {
-    for cause in error.chain() {
-        if let Some(storage_error) = cause.downcast_ref::<StorageError>() {
-            return Some(storage_error);
+    /// Reads the blocks back and checks that they look random, the data isn't generated.
+    fn verify_range_by_entropy(
+        &mut self,
+        stage: &Stage,
+        range: WipeRange,
+        buf: &mut AlignedBuffer,
+    ) -> Result<()> {
+        let end = range.end;
+        self.seek_to_the_next_safe_position(end)?;
+
+        while self.state.position < end {
+            self.check_cancelled()?;
+            let len = (self.task.block_size as u64).min(end - self.state.position) as usize;
+            let sampled = self
+                .task
+                .verify
+                .is_block_sampled(self.current_block_number() as u64);
+
+            if self.is_at_bad_block() || !sampled {
+                self.advance(len, end);
+                self.try_seek()?;
+                continue;
+            }
+
+            if !self.task.dry_run {
+                let b = &mut buf.as_mut_slice()[..len];
+                self.access.read(b)?;
+
+                if !entropy::looks_random(b) {
+                    if self.repair_region(stage, range)? {
+                        // the rewritten data isn't at hand to be recorded
+                        self.state.verified_blocks += 1;
+                    } else {
+                        if !self.task.continue_on_mismatch {
+                            Err(VerificationMismatch)?;
+                        }
+                        self.state
+                            .mismatched_blocks
+                            .borrow_mut()
+                            .mark(self.current_block_number());
+                        self.publish(WipeEvent::MarkBlockAsMismatched(self.state.position));
+                        self.record_verified(self.state.position, b);
+                    }
+                    self.advance(len, end);
+                    self.try_seek()?;
+                    continue;
+                }
+                self.record_verified(self.state.position, b);
+            } else {
+                self.state.verified_blocks += 1;
+            }
+
+            self.advance(len, end);
         }
-    }
-    None
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use anyhow::{Context, Result};
-    use assert_matches::*;
-    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
-    use WipeEvent::*;
+        Ok(())
+    }
 
-    #[test]
-    fn test_wipe_task_validation() {
-        let schemes = SchemeRepo::default();
-        let scheme = schemes.find("zero").unwrap();
+    /// Reads the blocks back and compares them with the stage data, group by group,
+    /// without generating the data again. Groups without a recorded digest (e.g. written
+    /// before the wipe was resumed) are compared with the generated data instead.
+    fn verify_range_by_digests(
+        &mut self,
+        stage: &Stage,
+        range: WipeRange,
+        buf: &mut AlignedBuffer,
+    ) -> Result<()> {
+        let end = range.end;
+        self.seek_to_the_next_safe_position(end)?;
+
+        let mut checker: Option<GroupChecker> = None;
+
+        while self.state.position < end {
+            self.check_cancelled()?;
+            let position = self.state.position;
+            let len = (self.task.block_size as u64).min(end - position) as usize;
 
-        assert!(WipeTask::new(scheme.clone(), Verify::No, 1 << 32, 1).is_ok());
+            if self.is_at_bad_block() {
+                self.advance(len, end);
+                self.try_seek()?;
+                continue;
+            }
+
+            let digests = self.digests.as_ref().unwrap();
+            if checker.as_ref().map(|c| c.group()) != Some(position / digests.group_size()) {
+                let next = digests.checker(position);
+                if let Some(c) = checker.replace(next) {
+                    self.check_group(stage, range, c)?;
+                }
+            }
+
+            let b = &mut buf.as_mut_slice()[..len];
+            self.access.read(b)?;
+            checker.as_mut().unwrap().update(position, b);
+            self.record_verified(position, b);
+
+            self.advance(len, end);
+        }
+
+        if let Some(c) = checker {
+            self.check_group(stage, range, c)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_group(
+        &mut self,
+        stage: &Stage,
+        range: WipeRange,
+        checker: GroupChecker,
+    ) -> Result<()> {
+        let region = WipeRange::new(checker.start(), checker.end());
+        let matches = match self.digests.as_ref().unwrap().check(checker) {
+            GroupCheck::Match => true,
+            GroupCheck::Mismatch => false,
+            GroupCheck::Unknown => self.compare_region(stage, region)?,
+        };
+        if matches {
+            return Ok(());
+        }
+
+        let position = self.state.position;
+        self.state.position = region.start;
+        if !self.repair_region(stage, range)? {
+            if !self.task.continue_on_mismatch {
+                Err(VerificationMismatch)?;
+            }
+            // the digests don't tell which blocks differ, so the whole group is reported
+            let block_size = self.task.block_size as u64;
+            for block in region.start / block_size..region.end.div_ceil(block_size) {
+                if !self.state.bad_blocks.borrow().is_marked(block as u32) {
+                    self.state.mismatched_blocks.borrow_mut().mark(block as u32);
+                    self.publish(WipeEvent::MarkBlockAsMismatched(block * block_size));
+                }
+            }
+        }
+        self.state.position = position;
+        self.try_seek()?;
+
+        Ok(())
+    }
+
+    /// Rewrites the region around the current (mismatched) block and verifies it again,
+    /// so a single bad write doesn't cost a retry of the whole stage. With the block retry
+    /// scope this is repeated while there are verification retries left.
+    /// Returns false if the region still doesn't match.
+    fn repair_region(&mut self, stage: &Stage, range: WipeRange) -> Result<bool> {
+        if self.task.verify_only {
+            return Ok(false);
+        }
+
+        let block_size = self.task.block_size as u64;
+        let position = self.state.position;
+        let region = WipeRange::new(
+            (position.saturating_sub(REPAIR_WINDOW) / block_size * block_size).max(range.start),
+            (position + block_size + REPAIR_WINDOW).min(range.end),
+        );
+
+        loop {
+            if self.rewrite_region(stage, region)? {
+                return Ok(true);
+            }
+            if self.task.retry_scope != RetryScope::Block || self.state.verify_retries_left == 0 {
+                return Ok(false);
+            }
+            self.state.verify_retries_left -= 1;
+            self.back_off();
+            self.check_cancelled()?;
+        }
+    }
+
+    fn rewrite_region(&mut self, stage: &Stage, region: WipeRange) -> Result<bool> {
+        self.publish(WipeEvent::Repairing(region));
+
+        let block_size = self.task.block_size as u64;
+        let mut stream = stage.stream(
+            region.end,
+            self.task.block_size,
+            self.task.io_alignment,
+            region.start,
+        );
+        let mut position = region.start;
+
+        while let Some(chunk) = stream.next() {
+            let block = (position / block_size) as u32;
+            if !self.state.bad_blocks.borrow().is_marked(block) {
+                self.access.seek(position)?;
+                for piece in chunk.chunks(self.write_size) {
+                    self.access.write(piece)?;
+                }
+            }
+            position += chunk.len() as u64;
+        }
+        self.access.flush()?;
+
+        self.compare_region(stage, region)
+    }
+
+    /// Compares the region with the generated stage data, skipping bad blocks.
+    fn compare_region(&mut self, stage: &Stage, region: WipeRange) -> Result<bool> {
+        let block_size = self.task.block_size as u64;
+        let mut buf = AlignedBuffer::new(self.task.block_size, self.task.io_alignment);
+        let mut stream = stage.stream(
+            region.end,
+            self.task.block_size,
+            self.task.io_alignment,
+            region.start,
+        );
+        let mut position = region.start;
+
+        while let Some(chunk) = stream.next() {
+            let block = (position / block_size) as u32;
+            if !self.state.bad_blocks.borrow().is_marked(block) {
+                self.access.seek(position)?;
+                let b = &mut buf.as_mut_slice()[..chunk.len()];
+                self.access.read_back(b, chunk)?;
+                if b != chunk {
+                    return Ok(false);
+                }
+            }
+            position += chunk.len() as u64;
+        }
+
+        Ok(true)
+    }
+}
+
+/// Written data doesn't match what was read back.
+#[derive(thiserror::Error, Debug)]
+#[error("Verification failed!")]
+pub struct VerificationMismatch;
+
+/// Throughput of writing the given number of bytes in the given time, failing if it's
+/// implausible for a storage device or much higher than the expected one.
+pub fn check_write_throughput(
+    written: u64,
+    elapsed: Duration,
+    expected: Option<u64>,
+) -> Result<u64> {
+    let millis = elapsed.as_millis().max(1);
+    let throughput = (written as u128 * 1000 / millis).min(u64::MAX as u128) as u64;
+    let limit = expected
+        .map(|e| {
+            e.saturating_mul(MAX_WRITE_SPEEDUP)
+                .min(MAX_WRITE_THROUGHPUT)
+        })
+        .unwrap_or(MAX_WRITE_THROUGHPUT);
+    if throughput > limit {
+        Err(ImplausibleWrite { written, elapsed })?;
+    }
+    Ok(throughput)
+}
+
+/// A stage was completed too fast for the data to be actually written.
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "Writing {written} bytes completed in {elapsed:?}, too fast for the data to reach the device. \
+     The device size may be resolved incorrectly or the writes may be cached."
+)]
+pub struct ImplausibleWrite {
+    pub written: u64,
+    pub elapsed: Duration,
+}
+
+/// The device reported a different size or sector size in the middle of the wipe.
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "Device geometry changed from {before} to {after}. The device or its USB bridge may be failing."
+)]
+pub struct GeometryChanged {
+    pub before: Geometry,
+    pub after: Geometry,
+}
+
+// taken directly from https://docs.rs/anyhow/1.0.9/anyhow/struct.Error.html#example
+pub fn underlying_storage_error(error: &anyhow::Error) -> Option<&StorageError> {
+    for cause in error.chain() {
+        if let Some(storage_error) = cause.downcast_ref::<StorageError>() {
+            return Some(storage_error);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::checkpoint::{CheckpointStore, DeviceFingerprint};
+    use crate::storage::memory::InMemoryStorage;
+    use assert_matches::*;
+    use WipeEvent::*;
+
+    #[test]
+    fn test_wipe_task_validation() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+
+        assert!(WipeTask::new(scheme.clone(), Verify::No, 1 << 32, 1).is_ok());
         assert!(WipeTask::new(scheme.clone(), Verify::No, 1 << 35, 8).is_ok());
         assert!(WipeTask::new(scheme.clone(), Verify::No, 1 << 33, 1).is_err());
         assert!(WipeTask::new(scheme.clone(), Verify::No, 1 << 36, 8).is_err());
+        assert!(WipeTask::new(scheme.clone(), Verify::No, 0, 4096).is_err());
+        assert!(WipeTask::new(scheme.clone(), Verify::No, 4096, 0).is_err());
+
+        let task = WipeTask::new(scheme.clone(), Verify::No, 1000, 1 << 20).unwrap();
+        assert_eq!(task.block_size, 1024);
+        let task = WipeTask::new(scheme.clone(), Verify::No, 16 << 20, 1 << 20).unwrap();
+        assert_eq!(task.block_size, 1 << 20);
+    }
+
+    #[test]
+    fn test_wipe_task_alignment() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let geometry = |sector_size, physical_sector_size| Geometry {
+            size: 16 << 20,
+            sector_size,
+            physical_sector_size,
+        };
+
+        let mut task = WipeTask::new(scheme.clone(), Verify::No, 16 << 20, 1 << 20).unwrap();
+        assert_eq!(task.io_alignment, DEFAULT_IO_ALIGNMENT);
+        task.align_to(&geometry(512, 512)).unwrap();
+        assert_eq!(task.io_alignment, 512);
+        task.align_to(&geometry(512, 4096)).unwrap();
+        assert_eq!(task.io_alignment, 4096);
+
+        let mut task = WipeTask::new(scheme.clone(), Verify::No, 16 << 20, 2048).unwrap();
+        assert!(task.align_to(&geometry(4096, 4096)).is_err());
+
+        let mut task = WipeTask::new(scheme.clone(), Verify::No, 16 << 20, 8192).unwrap();
+        task.set_min_block_size(512).unwrap();
+        assert!(task.align_to(&geometry(4096, 4096)).is_err());
+
+        // ranges have to be aligned to the sectors, not to the blocks
+        let mut task = WipeTask::new(scheme.clone(), Verify::No, 16 << 20, 4 << 20).unwrap();
+        task.set_ranges(vec![
+            WipeRange::new(0, 1 << 20),
+            WipeRange::new(15 << 20, 16 << 20),
+        ])
+        .unwrap();
+        task.align_to(&geometry(4096, 4096)).unwrap();
+        task.set_ranges(vec![WipeRange::new(512, 1 << 20)]).unwrap();
+        assert!(task.align_to(&geometry(512, 512)).is_ok());
+        assert!(task.align_to(&geometry(4096, 4096)).is_err());
+    }
+
+    #[test]
+    fn test_implausibly_fast_writes() {
+        let gb = 1u64 << 30;
+        let second = Duration::from_secs(1);
+
+        assert_eq!(check_write_throughput(gb, second, None).unwrap(), gb);
+        assert!(check_write_throughput(64 * gb, second, None).is_err());
+        assert!(check_write_throughput(gb, Duration::from_secs(0), None).is_err());
+
+        // following stages are compared to the first one
+        assert!(check_write_throughput(5 * gb, second, Some(gb)).is_ok());
+        let err = check_write_throughput(20 * gb, second, Some(gb)).unwrap_err();
+        assert!(err.is::<ImplausibleWrite>());
+    }
+
+    #[test]
+    fn test_wiping_fails_when_geometry_changes() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random2x").unwrap();
+        let geometry = |size| Geometry {
+            size,
+            sector_size: 512,
+            physical_sector_size: 512,
+        };
+
+        let mut storage = InMemoryStorage::new(100000);
+        storage.geometry = vec![geometry(100000)];
+        let task = WipeTask::new(scheme.clone(), Verify::No, 100000, 4096).unwrap();
+        assert!(task
+            .run(
+                &mut storage,
+                &mut WipeState::default(),
+                &mut StubReceiver::new()
+            )
+            .is_success());
+
+        let mut storage = InMemoryStorage::new(100000);
+        // at the start, before the first stage and before the second one
+        storage.geometry = vec![geometry(100000), geometry(100000), geometry(65536)];
+        let mut receiver = StubReceiver::new();
+        let task = WipeTask::new(scheme.clone(), Verify::No, 100000, 4096).unwrap();
+        assert!(!task
+            .run(&mut storage, &mut WipeState::default(), &mut receiver)
+            .is_success());
+
+        assert_eq!(storage.total_written, 100000);
+        match receiver.collected.last() {
+            Some((_, Completed(Some(err)))) => {
+                let err = err.downcast_ref::<GeometryChanged>().unwrap();
+                assert_eq!(err.after.size, 65536);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_wiping_target_smaller_than_block() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random2x").unwrap();
+        let mut storage = InMemoryStorage::new(1000);
+        let mut receiver = StubReceiver::new();
+
+        let task = WipeTask::new(scheme.clone(), Verify::All, 1000, 1 << 20).unwrap();
+        assert_eq!(task.block_size, 1024);
+        assert_eq!(task.total_blocks(), 1);
+        let mut state = WipeState::default();
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+
+        let progress: Vec<_> = receiver
+            .collected
+            .iter()
+            .filter_map(|(_, e)| match e {
+                Progress(p) => Some(*p),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(progress, vec![0, 1000, 0, 1000, 0, 1000, 0, 1000]);
+        assert_eq!(state.verified_blocks, 1);
+        assert_eq!(storage.total_written, 2000);
+
+        // resumed verification of the last stage
+        let task = WipeTask::new(scheme.clone(), Verify::Last, 1000, 1 << 20).unwrap();
+        let mut stream = task.scheme.stages[1].stream(1000, task.block_size, task.io_alignment, 0);
+        let chunk = stream.next().unwrap();
+        assert_eq!(chunk.len(), 1000);
+        storage.file.get_mut().clone_from_slice(chunk);
+        assert!(stream.next().is_none());
+
+        let mut state = WipeState {
+            stage: 1,
+            at_verification: true,
+            ..Default::default()
+        };
+        let mut receiver = StubReceiver::new();
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        assert_eq!(state.verified_blocks, 1);
+
+        // a bad block covers the whole target
+        let mut storage = InMemoryStorage::new(1000);
+        storage.fail_at(500);
+        let task = WipeTask::new(scheme.clone(), Verify::Last, 1000, 1 << 20).unwrap();
+        let mut state = WipeState::default();
+        let mut receiver = StubReceiver::new();
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        assert_eq!(state.bad_blocks.borrow().all_marked(), vec![0]);
+        assert_eq!(state.verified_blocks, 0);
     }
 
     #[test]
@@ -384,9 +1620,9 @@ mod test {
         )
         .unwrap();
         let mut state = WipeState::default();
-        let result = task.run(&mut storage, &mut state, &mut receiver);
+        let outcome = task.run(&mut storage, &mut state, &mut receiver);
 
-        assert!(result);
+        assert_eq!(outcome, WipeOutcome::Completed);
 
         let mut e = receiver.collected.iter();
         assert_matches!(e.next(), Some((_, Started)));
@@ -406,6 +1642,13 @@ mod test {
         assert_matches!(e.next(), Some((_, StageCompleted(None))));
         assert_matches!(e.next(), Some((_, Completed(None))));
 
+        let stamps: Vec<_> = receiver.collected.iter().map(|(s, _)| s.stamp).collect();
+        assert_eq!(stamps.first().unwrap().sequence, 1);
+        assert!(stamps.windows(2).all(|w| w[1].sequence == w[0].sequence + 1
+            && w[1].elapsed >= w[0].elapsed
+            && w[1].timestamp >= w[0].timestamp));
+        assert_eq!(state.stamp.sequence, stamps.len() as u64);
+
         assert_eq!(
             storage.file.get_ref().iter().filter(|x| **x != 0u8).count(),
             0
@@ -430,9 +1673,9 @@ mod test {
         )
         .unwrap();
         let mut state = WipeState::default();
-        let result = task.run(&mut storage, &mut state, &mut receiver);
+        let outcome = task.run(&mut storage, &mut state, &mut receiver);
 
-        assert!(!result);
+        assert_eq!(outcome, WipeOutcome::Failed);
 
         let mut e = receiver.collected.iter();
         assert_matches!(e.next(), Some((_, Started)));
@@ -448,6 +1691,35 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_wiping_rewrites_failed_queued_write() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let block_size = 32768;
+        let mut receiver = StubReceiver::new();
+
+        storage.queued_failures = vec![32768];
+
+        let task =
+            WipeTask::new(scheme.clone(), Verify::No, storage.size as u64, block_size).unwrap();
+        let mut state = WipeState {
+            retries_left: 1,
+            ..Default::default()
+        };
+        let outcome = task.run(&mut storage, &mut state, &mut receiver);
+
+        assert_eq!(outcome, WipeOutcome::Completed);
+        let retried_at = receiver
+            .collected
+            .iter()
+            .skip_while(|(_, e)| !matches!(e, Retrying(_)))
+            .map(|(s, _)| s.position)
+            .next();
+        assert_eq!(retried_at, Some(32768));
+        assert!(storage.file.get_ref().iter().all(|x| *x == 0u8));
+    }
+
     #[test]
     fn test_wiping_validation_failure_with_retries() {
         let schemes = SchemeRepo::default();
@@ -458,16 +1730,21 @@ mod test {
 
         storage.fail_after_any(150000);
 
-        let task = WipeTask::new(
+        let mut task = WipeTask::new(
             scheme.clone(),
             Verify::Last,
             storage.size as u64,
             block_size,
         )
         .unwrap();
-        let mut state = WipeState::default();
-        state.retries_left = 8;
-        let result = task.run(&mut storage, &mut state, &mut receiver);
+        task.retry_backoff = RetryBackoff::Fixed(Duration::ZERO);
+        let mut state = WipeState {
+            retries_left: 8,
+            ..Default::default()
+        };
+        let result = task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success();
 
         assert!(result);
 
@@ -484,7 +1761,7 @@ mod test {
         assert_matches!(e.next(), Some((_, Progress(0))));
         assert_matches!(e.next(), Some((_, Progress(32768))));
         assert_matches!(e.next(), Some((_, StageCompleted(Some(_)))));
-        assert_matches!(e.next(), Some((_, Retrying)));
+        assert_matches!(e.next(), Some((_, Retrying(_))));
         assert_matches!(e.next(), Some((ref s, StageStarted)) if !s.at_verification);
         assert_matches!(e.next(), Some((_, Progress(32768))));
         assert_matches!(e.next(), Some((_, Progress(65536))));
@@ -517,11 +1794,13 @@ mod test {
             block_size,
         )
         .unwrap();
-        let mut state = WipeState::default();
-        state.retries_left = 8;
-        let result = task.run(&mut storage, &mut state, &mut receiver);
+        let mut state = WipeState {
+            retries_left: 8,
+            ..Default::default()
+        };
+        let outcome = task.run(&mut storage, &mut state, &mut receiver);
 
-        assert!(result);
+        assert_eq!(outcome, WipeOutcome::CompletedWithBadBlocks);
 
         let mut e = receiver.collected.iter();
         assert_matches!(e.next(), Some((_, Started)));
@@ -543,6 +1822,65 @@ mod test {
         assert_matches!(e.next(), Some((_, Completed(None))));
     }
 
+    #[test]
+    fn test_wiping_reduces_write_size_after_failures() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        storage.max_write_size = Some(8192);
+        let mut receiver = StubReceiver::new();
+
+        let mut task = WipeTask::new(scheme.clone(), Verify::Last, 100000, 32768).unwrap();
+        assert!(task.set_min_block_size(65536).is_err());
+        assert!(task.set_min_block_size(0).is_err());
+        task.set_min_block_size(4096).unwrap();
+        let mut state = WipeState::default();
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+
+        let reduced: Vec<_> = receiver
+            .collected
+            .iter()
+            .filter_map(|(_, e)| match e {
+                BlockSizeReduced(size) => Some(*size),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reduced, vec![16384, 8192]);
+        assert_eq!(state.bad_blocks.borrow().total_marked(), 0);
+        assert_eq!(state.verified_blocks, 4);
+    }
+
+    #[test]
+    fn test_wiping_marks_bad_block_at_min_write_size() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        storage.fail_at(50000);
+        let mut receiver = StubReceiver::new();
+
+        let mut task = WipeTask::new(scheme.clone(), Verify::Last, 100000, 32768).unwrap();
+        task.set_min_block_size(8192).unwrap();
+        let mut state = WipeState::default();
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+
+        let mut e = receiver
+            .collected
+            .iter()
+            .filter(|(_, e)| !matches!(e, Progress(_)));
+        assert_matches!(e.next(), Some((_, Started)));
+        assert_matches!(e.next(), Some((_, StageStarted)));
+        assert_matches!(e.next(), Some((_, BlockSizeReduced(16384))));
+        assert_matches!(e.next(), Some((_, BlockSizeReduced(8192))));
+        assert_matches!(e.next(), Some((_, MarkBlockAsBad(32768))));
+        assert_matches!(e.next(), Some((_, StageCompleted(None))));
+        assert_eq!(state.bad_blocks.borrow().all_marked(), vec![1]);
+        assert_eq!(state.verified_blocks, 3);
+    }
+
     #[test]
     fn test_wiping_skip_bad_blocks_at_beginning() {
         let schemes = SchemeRepo::default();
@@ -561,9 +1899,13 @@ mod test {
             block_size,
         )
         .unwrap();
-        let mut state = WipeState::default();
-        state.retries_left = 8;
-        let result = task.run(&mut storage, &mut state, &mut receiver);
+        let mut state = WipeState {
+            retries_left: 8,
+            ..Default::default()
+        };
+        let result = task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success();
 
         assert!(result);
 
@@ -605,9 +1947,13 @@ mod test {
             block_size,
         )
         .unwrap();
-        let mut state = WipeState::default();
-        state.retries_left = 8;
-        let result = task.run(&mut storage, &mut state, &mut receiver);
+        let mut state = WipeState {
+            retries_left: 8,
+            ..Default::default()
+        };
+        let result = task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success();
 
         assert!(result);
 
@@ -651,9 +1997,13 @@ mod test {
             block_size,
         )
         .unwrap();
-        let mut state = WipeState::default();
-        state.retries_left = 8;
-        let result = task.run(&mut storage, &mut state, &mut receiver);
+        let mut state = WipeState {
+            retries_left: 8,
+            ..Default::default()
+        };
+        let result = task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success();
 
         assert!(result);
 
@@ -697,11 +2047,13 @@ mod test {
             block_size,
         )
         .unwrap();
-        let mut state = WipeState::default();
-        state.retries_left = 0;
-        let result = task.run(&mut storage, &mut state, &mut receiver);
+        let mut state = WipeState {
+            retries_left: 0,
+            ..Default::default()
+        };
+        let outcome = task.run(&mut storage, &mut state, &mut receiver);
 
-        assert!(!result);
+        assert_eq!(outcome, WipeOutcome::Failed);
 
         let mut e = receiver.collected.iter();
         assert_matches!(e.next(), Some((_, Started)));
@@ -719,106 +2071,917 @@ mod test {
         assert_matches!(e.next(), Some((_, Completed(Some(_)))));
     }
 
-    struct StubReceiver {
-        collected: Vec<(WipeState, WipeEvent)>,
-    }
+    #[test]
+    fn test_wiping_resume_from_checkpoint() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random2x").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let block_size = 32768;
+        let mut receiver = StubReceiver::new();
 
-    impl StubReceiver {
-        pub fn new() -> Self {
-            StubReceiver {
-                collected: Vec::new(),
-            }
+        let task = WipeTask::new(
+            scheme.clone(),
+            Verify::Last,
+            storage.size as u64,
+            block_size,
+        )
+        .unwrap();
+        let mut state = WipeState {
+            stage: 1,
+            at_verification: true,
+            position: 65536,
+            ..Default::default()
+        };
+
+        let mut stream = task.scheme.stages[1].stream(100000, block_size, task.io_alignment, 0);
+        let mut position = 0;
+        while let Some(chunk) = stream.next() {
+            storage.file.get_mut()[position..position + chunk.len()].clone_from_slice(chunk);
+            position += chunk.len();
         }
-    }
 
-    impl WipeEventReceiver for StubReceiver {
-        fn handle(&mut self, _task: &WipeTask, state: &WipeState, event: WipeEvent) -> () {
-            println!("{:?}", event);
-            self.collected.push((state.clone(), event));
-        }
-    }
+        let result = task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success();
 
-    struct InMemoryStorage {
-        file: Cursor<Vec<u8>>,
-        size: usize,
-        total_written: usize,
-        total_read: usize,
-        failures: Vec<usize>,
-        bad_blocks: Vec<u64>,
-    }
+        assert!(result);
 
-    impl InMemoryStorage {
-        fn new(size: usize) -> Self {
-            InMemoryStorage {
-                file: Cursor::new(vec![0xff; size]),
-                size,
-                total_written: 0,
-                total_read: 0,
-                failures: Vec::new(),
-                bad_blocks: Vec::new(),
-            }
-        }
+        let mut e = receiver.collected.iter();
+        assert_matches!(e.next(), Some((_, Started)));
+        assert_matches!(e.next(), Some((ref s, StageStarted)) if s.at_verification && s.stage == 1);
+        assert_matches!(e.next(), Some((_, Progress(65536))));
+        assert_matches!(e.next(), Some((_, Progress(98304))));
+        assert_matches!(e.next(), Some((_, Progress(100000))));
+        assert_matches!(e.next(), Some((_, StageCompleted(None))));
+        assert_matches!(e.next(), Some((_, Completed(None))));
+    }
 
-        fn fail_after_any(&mut self, amount: usize) -> () {
-            self.failures.push(amount);
-            self.failures.sort();
+    #[test]
+    fn test_wiping_resume_verification_from_journal() {
+        struct CancellingReceiver {
+            inner: StubReceiver,
         }
 
-        fn fail_at(&mut self, pos: u64) -> () {
-            self.bad_blocks.push(pos);
-            self.bad_blocks.sort();
+        impl WipeEventReceiver for CancellingReceiver {
+            fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+                if let Progress(65536) = event {
+                    if state.at_verification {
+                        task.cancellation.cancel();
+                    }
+                }
+                self.inner.handle(task, state, event);
+            }
         }
 
-        fn check_for_traps(&mut self, read_bytes: usize, write_bytes: usize) -> Result<()> {
-            let block_start = self.file.position();
-            let block_end = block_start + write_bytes as u64;
-            let is_bad_block = self
-                .bad_blocks
-                .iter()
-                .find(|b| block_start <= **b && block_end > **b)
-                .is_some();
-
-            if is_bad_block {
-                return Err(StorageError::BadBlock.into());
-            }
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let block_size = 32768;
+        let root = std::env::temp_dir().join(format!(
+            "lethe-test-verification-journal-{}",
+            std::process::id()
+        ));
+        let fingerprint = DeviceFingerprint::new("/dev/test", 100000);
+        let new_task = || WipeTask::new(scheme.clone(), Verify::Last, 100000, block_size).unwrap();
+        let new_checkpointer = || {
+            Checkpointer::new(
+                CheckpointStore::new(&root),
+                fingerprint.clone(),
+                Duration::ZERO,
+            )
+        };
+
+        let mut uninterrupted = WipeState::default();
+        let outcome = new_task().run_with_checkpoints(
+            &mut InMemoryStorage::new(100000),
+            &mut uninterrupted,
+            &mut StubReceiver::new(),
+            &mut new_checkpointer(),
+        );
+        assert_eq!(outcome, WipeOutcome::Completed);
+        assert_eq!(uninterrupted.journal.blocks, 4);
 
-            let old_total = self.total_read + self.total_written;
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = CancellingReceiver {
+            inner: StubReceiver::new(),
+        };
+        let outcome = new_task().run_with_checkpoints(
+            &mut storage,
+            &mut WipeState::default(),
+            &mut receiver,
+            &mut new_checkpointer(),
+        );
+        assert_eq!(outcome, WipeOutcome::Aborted);
+
+        let checkpoint = CheckpointStore::new(&root)
+            .find(&fingerprint)
+            .unwrap()
+            .unwrap();
+        assert!(checkpoint.at_verification);
+        assert_eq!(checkpoint.position, 65536);
+        assert_eq!(checkpoint.verified_blocks, 2);
+
+        // continues from the interrupted block and ends with the same journal
+        let (task, mut state) = checkpoint.restore().unwrap();
+        storage.total_written = 0;
+        storage.total_read = 0;
+        let mut receiver = StubReceiver::new();
+        let outcome = task.run_with_checkpoints(
+            &mut storage,
+            &mut state,
+            &mut receiver,
+            &mut new_checkpointer(),
+        );
+        assert_eq!(outcome, WipeOutcome::Completed);
+        assert_eq!(state.journal, uninterrupted.journal);
+        assert_eq!(state.verified_blocks, 4);
+        assert_eq!(storage.total_written, 0);
+        assert_eq!(storage.total_read, 32768 + 32768 + 1696);
+
+        // the device doesn't hold the verified data anymore, the verification starts over
+        let (task, mut state) = checkpoint.restore().unwrap();
+        storage.file.get_mut()[40000] = 0xff;
+        storage.total_read = 0;
+        let mut receiver = StubReceiver::new();
+        let outcome = task.run_with_checkpoints(
+            &mut storage,
+            &mut state,
+            &mut receiver,
+            &mut new_checkpointer(),
+        );
+        assert_eq!(outcome, WipeOutcome::Completed);
+        assert_eq!(state.journal, uninterrupted.journal);
+        assert_eq!(state.verified_blocks, 4);
+        assert!(receiver
+            .collected
+            .iter()
+            .any(|(s, e)| matches!(e, Progress(0)) && s.at_verification));
+        assert!(storage.file.get_ref().iter().all(|b| *b == 0));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 
-            self.total_read += read_bytes;
-            self.total_written += write_bytes;
+    #[test]
+    fn test_wiping_cancellation() {
+        struct CancellingReceiver {
+            inner: StubReceiver,
+            cancel_at: u64,
+        }
 
-            match self.failures.iter().find(|x| **x >= old_total) {
-                Some(v) if old_total + read_bytes + write_bytes > *v => {
-                    Err(anyhow!("Mocked IO failure"))
+        impl WipeEventReceiver for CancellingReceiver {
+            fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+                if let Progress(position) = event {
+                    if position == self.cancel_at {
+                        task.cancellation.cancel();
+                    }
                 }
-                _ => Ok(()),
+                self.inner.handle(task, state, event);
             }
         }
-    }
 
-    impl StorageAccess for InMemoryStorage {
-        fn position(&mut self) -> Result<u64> {
-            self.file.seek(SeekFrom::Current(0)).context("unexpected")
-        }
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let block_size = 32768;
+        let mut receiver = CancellingReceiver {
+            inner: StubReceiver::new(),
+            cancel_at: 65536,
+        };
 
-        fn seek(&mut self, position: u64) -> Result<u64> {
-            self.file
-                .seek(SeekFrom::Start(position))
-                .context("unexpected")
-        }
+        let task = WipeTask::new(
+            scheme.clone(),
+            Verify::Last,
+            storage.size as u64,
+            block_size,
+        )
+        .unwrap();
+        let mut state = WipeState::default();
+        let outcome = task.run(&mut storage, &mut state, &mut receiver);
 
-        fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
-            self.check_for_traps(buffer.len(), 0)?;
-            self.file.read(buffer).context("unexpected")
-        }
+        assert_eq!(outcome, WipeOutcome::Aborted);
 
-        fn write(&mut self, data: &[u8]) -> Result<()> {
-            self.check_for_traps(0, data.len())?;
-            self.file.write_all(data).context("unexpected")
-        }
+        let mut e = receiver.inner.collected.iter();
+        assert_matches!(e.next(), Some((_, Started)));
+        assert_matches!(e.next(), Some((_, StageStarted)));
+        assert_matches!(e.next(), Some((_, Progress(0))));
+        assert_matches!(e.next(), Some((_, Progress(32768))));
+        assert_matches!(e.next(), Some((_, Progress(65536))));
+        assert_matches!(e.next(), Some((ref s, Aborted)) if s.position == 65536 && !s.at_verification);
+        assert_matches!(e.next(), None);
+
+        assert_eq!(
+            storage.file.get_ref().iter().filter(|x| **x != 0u8).count(),
+            100000 - 65536
+        );
 
-        fn flush(&mut self) -> Result<()> {
-            Ok(())
+        let task = WipeTask::new(
+            scheme.clone(),
+            Verify::Last,
+            storage.size as u64,
+            block_size,
+        )
+        .unwrap();
+        let mut receiver = StubReceiver::new();
+
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        assert_matches!(receiver.collected.last(), Some((_, Completed(None))));
+        assert_eq!(
+            storage.file.get_ref().iter().filter(|x| **x != 0u8).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_wiping_pause() {
+        struct PausingReceiver {
+            inner: StubReceiver,
+            pause_at: u64,
+        }
+
+        impl WipeEventReceiver for PausingReceiver {
+            fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+                match event {
+                    Progress(position) if position == self.pause_at => task.pause.pause(),
+                    // another thread would normally resume it
+                    Paused => task.pause.resume(),
+                    _ => {}
+                }
+                self.inner.handle(task, state, event);
+            }
+        }
+
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = PausingReceiver {
+            inner: StubReceiver::new(),
+            pause_at: 32768,
+        };
+
+        let task = WipeTask::new(scheme.clone(), Verify::No, storage.size as u64, 32768).unwrap();
+        let mut state = WipeState::default();
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+
+        let mut e = receiver.inner.collected.iter();
+        assert_matches!(e.next(), Some((_, Started)));
+        assert_matches!(e.next(), Some((_, StageStarted)));
+        assert_matches!(e.next(), Some((_, Progress(0))));
+        assert_matches!(e.next(), Some((_, Progress(32768))));
+        assert_matches!(e.next(), Some((ref s, Paused)) if s.position == 32768);
+        assert_matches!(e.next(), Some((_, Resumed)));
+        assert_matches!(e.next(), Some((_, Progress(65536))));
+        assert_matches!(e.next(), Some((_, Progress(98304))));
+        assert_matches!(e.next(), Some((_, Progress(100000))));
+        assert_matches!(e.next(), Some((_, StageCompleted(None))));
+        assert_matches!(e.next(), Some((_, Completed(None))));
+
+        assert!(storage.file.get_ref().iter().all(|x| *x == 0u8));
+    }
+
+    #[test]
+    fn test_wiping_schedule() {
+        use crate::actions::schedule::PauseWindow;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        static NOW: AtomicU64 = AtomicU64::new(7 * 3600);
+
+        struct ClockReceiver {
+            inner: StubReceiver,
+        }
+
+        impl WipeEventReceiver for ClockReceiver {
+            fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+                match event {
+                    Progress(32768) => NOW.store(8 * 3600, Ordering::SeqCst),
+                    // time flies while the wipe waits
+                    PausedUntil(_) => NOW.store(18 * 3600, Ordering::SeqCst),
+                    _ => {}
+                }
+                self.inner.handle(task, state, event);
+            }
+        }
+
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = ClockReceiver {
+            inner: StubReceiver::new(),
+        };
+
+        let mut task =
+            WipeTask::new(scheme.clone(), Verify::No, storage.size as u64, 32768).unwrap();
+        task.schedule = Some(Schedule::with_clock(
+            "08:00-18:00".parse::<PauseWindow>().unwrap(),
+            || Duration::from_secs(NOW.load(Ordering::SeqCst)),
+        ));
+        let mut state = WipeState::default();
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+
+        let mut e = receiver.inner.collected.iter();
+        assert_matches!(e.next(), Some((_, Started)));
+        assert_matches!(e.next(), Some((_, StageStarted)));
+        assert_matches!(e.next(), Some((_, Progress(0))));
+        assert_matches!(e.next(), Some((_, Progress(32768))));
+        assert_matches!(e.next(), Some((ref s, PausedUntil(_))) if s.position == 32768);
+        assert_matches!(e.next(), Some((_, Resumed)));
+        assert_matches!(e.next(), Some((_, Progress(65536))));
+        assert!(storage.file.get_ref().iter().all(|x| *x == 0u8));
+    }
+
+    #[test]
+    fn test_wiping_ranges() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let block_size = 8192;
+        let mut receiver = StubReceiver::new();
+
+        let mut task = WipeTask::new(
+            scheme.clone(),
+            Verify::Last,
+            storage.size as u64,
+            block_size,
+        )
+        .unwrap();
+        task.set_ranges(vec![
+            WipeRange::new(90112, 100000),
+            WipeRange::new(0, 16384),
+        ])
+        .unwrap();
+
+        assert!(task.is_partial());
+        assert_eq!(task.wiped_size(), 16384 + 9888);
+        assert_eq!(task.progress_at(50000), 16384);
+
+        let mut state = WipeState::default();
+        let result = task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success();
+
+        assert!(result);
+
+        let mut e = receiver.collected.iter();
+        assert_matches!(e.next(), Some((_, Started)));
+        assert_matches!(e.next(), Some((ref s, StageStarted)) if !s.at_verification);
+        assert_matches!(e.next(), Some((_, Progress(0))));
+        assert_matches!(e.next(), Some((_, Progress(8192))));
+        assert_matches!(e.next(), Some((_, Progress(16384))));
+        assert_matches!(e.next(), Some((_, Progress(98304))));
+        assert_matches!(e.next(), Some((_, Progress(100000))));
+        assert_matches!(e.next(), Some((_, StageCompleted(None))));
+        assert_matches!(e.next(), Some((ref s, StageStarted)) if s.at_verification);
+        assert_matches!(e.next(), Some((_, Progress(0))));
+        assert_matches!(e.next(), Some((_, Progress(8192))));
+        assert_matches!(e.next(), Some((_, Progress(16384))));
+        assert_matches!(e.next(), Some((_, Progress(98304))));
+        assert_matches!(e.next(), Some((_, Progress(100000))));
+        assert_matches!(e.next(), Some((_, StageCompleted(None))));
+        assert_matches!(e.next(), Some((_, Completed(None))));
+
+        let data = storage.file.get_ref();
+        assert!(data[16384..90112].iter().all(|x| *x == 0xff));
+        assert!(data[..16384].iter().any(|x| *x != 0xff));
+        assert!(data[90112..].iter().any(|x| *x != 0xff));
+
+        // a range not aligned to the blocks ends with a shorter one
+        let mut storage = InMemoryStorage::new(100000);
+        let mut task =
+            WipeTask::new(scheme.clone(), Verify::All, storage.size as u64, block_size).unwrap();
+        task.set_ranges(vec![WipeRange::new(1024, 20480)]).unwrap();
+        let mut receiver = StubReceiver::new();
+        assert!(task
+            .run(&mut storage, &mut WipeState::default(), &mut receiver)
+            .is_success());
+        let data = storage.file.get_ref();
+        assert!(data[..1024].iter().all(|x| *x == 0xff));
+        assert!(data[1024..20480].iter().any(|x| *x != 0xff));
+        assert!(data[20480..].iter().all(|x| *x == 0xff));
+    }
+
+    #[test]
+    fn test_wiping_discard() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random-trim").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let block_size = 32768;
+        let mut receiver = StubReceiver::new();
+
+        let mut task = WipeTask::new(
+            scheme.clone(),
+            Verify::Last,
+            storage.size as u64,
+            block_size,
+        )
+        .unwrap();
+        task.trim_supported = true;
+
+        let mut state = WipeState::default();
+        let result = task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success();
+
+        assert!(result);
+
+        let mut e = receiver.collected.iter();
+        assert_matches!(e.next(), Some((_, Started)));
+        assert_matches!(e.next(), Some((ref s, StageStarted)) if s.stage == 0 && !s.at_verification);
+        assert_matches!(e.next(), Some((_, Progress(0))));
+        assert_matches!(e.nth(3), Some((_, Progress(100000))));
+        assert_matches!(e.next(), Some((_, StageCompleted(None))));
+        assert_matches!(e.next(), Some((ref s, StageStarted)) if s.stage == 0 && s.at_verification);
+        assert_matches!(e.next(), Some((_, Progress(0))));
+        assert_matches!(e.nth(3), Some((_, Progress(100000))));
+        assert_matches!(e.next(), Some((_, StageCompleted(None))));
+        assert_matches!(e.next(), Some((ref s, StageStarted)) if s.stage == 1 && !s.at_verification);
+        assert_matches!(e.next(), Some((_, Progress(0))));
+        assert_matches!(e.next(), Some((_, Progress(100000))));
+        assert_matches!(e.next(), Some((_, StageCompleted(None))));
+        assert_matches!(e.next(), Some((_, Completed(None))));
+
+        assert!(storage.file.get_ref().iter().all(|x| *x == 0));
+    }
+
+    #[test]
+    fn test_wiping_discard_skipped_when_not_supported() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random-trim").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let block_size = 32768;
+        let mut receiver = StubReceiver::new();
+
+        let task =
+            WipeTask::new(scheme.clone(), Verify::No, storage.size as u64, block_size).unwrap();
+
+        let mut state = WipeState::default();
+        let result = task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success();
+
+        assert!(result);
+
+        let mut e = receiver.collected.iter().skip(7);
+        assert_matches!(e.next(), Some((_, StageCompleted(None))));
+        assert_matches!(e.next(), Some((ref s, StageSkipped)) if s.stage == 1);
+        assert_matches!(e.next(), Some((_, Completed(None))));
+
+        assert!(storage.file.get_ref().iter().any(|x| *x != 0));
+    }
+
+    #[test]
+    fn test_block_sampling_is_stable() {
+        let verify = Verify::Sample {
+            percent: 10,
+            seed: 42,
+        };
+        let sampled: Vec<u64> = (0..10000).filter(|b| verify.is_block_sampled(*b)).collect();
+        assert!(sampled.len() > 800 && sampled.len() < 1200);
+        assert!(sampled.iter().all(|b| verify.is_block_sampled(*b)));
+        assert!((0..100).all(|b| Verify::Last.is_block_sampled(b)));
+    }
+
+    #[test]
+    fn test_wiping_dry_run() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random2x").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = StubReceiver::new();
+
+        let mut task =
+            WipeTask::new(scheme.clone(), Verify::All, storage.size as u64, 32768).unwrap();
+        task.dry_run = true;
+        let mut state = WipeState::default();
+
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        assert_matches!(receiver.collected.last(), Some((_, Completed(None))));
+        assert_eq!(state.verified_blocks, 4);
+        assert_eq!(storage.total_written, 0);
+        assert_eq!(storage.total_read, 0);
+        assert!(storage.file.get_ref().iter().all(|x| *x == 0xff));
+    }
+
+    #[test]
+    fn test_estimate_duration() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random2x").unwrap();
+        let estimate = |verify| {
+            WipeTask::new(scheme.clone(), verify, 1000000, 4096)
+                .unwrap()
+                .estimate_duration(1000)
+        };
+
+        assert_eq!(estimate(Verify::No), Duration::from_secs(2000));
+        assert_eq!(estimate(Verify::Last), Duration::from_secs(3000));
+        assert_eq!(estimate(Verify::All), Duration::from_secs(4000));
+        assert_eq!(
+            estimate(Verify::Sample {
+                percent: 10,
+                seed: 0
+            }),
+            Duration::from_secs(2100)
+        );
+    }
+
+    #[test]
+    fn test_estimate_stages() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random2x").unwrap();
+        let task = WipeTask::new(scheme.clone(), Verify::Last, 1000000, 4096).unwrap();
+
+        assert_eq!(
+            task.estimate_stages(2000, 1000),
+            vec![Duration::from_secs(1000), Duration::from_secs(1500)]
+        );
+    }
+
+    #[test]
+    fn test_wiping_sampled_verification() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random2x").unwrap();
+        let mut storage = InMemoryStorage::new(102400);
+        let block_size = 1024;
+        let mut receiver = StubReceiver::new();
+        let verify = Verify::Sample {
+            percent: 30,
+            seed: 7,
+        };
+
+        let task = WipeTask::new(scheme.clone(), verify, storage.size as u64, block_size).unwrap();
+        let mut state = WipeState::default();
+        let result = task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success();
+
+        assert!(result);
+
+        let expected = (0..100).filter(|b| verify.is_block_sampled(*b)).count() as u64;
+        assert!(expected > 0 && expected < 100);
+        assert_eq!(state.verified_blocks, expected);
+
+        let verifications = receiver
+            .collected
+            .iter()
+            .filter(|(s, e)| matches!(e, StageStarted) && s.at_verification)
+            .map(|(s, _)| s.stage)
+            .collect::<Vec<_>>();
+        assert_eq!(verifications, vec![1]);
+    }
+
+    #[test]
+    fn test_wiping_repairs_region_around_mismatch() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = StubReceiver::new();
+
+        storage.lose_writes_at(vec![32768]);
+
+        let task = WipeTask::new(scheme.clone(), Verify::Last, storage.size as u64, 32768).unwrap();
+        let mut state = WipeState {
+            verify_retries_left: 8,
+            ..Default::default()
+        };
+
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+
+        let repairs: Vec<_> = receiver
+            .collected
+            .iter()
+            .filter_map(|(_, e)| match e {
+                Repairing(r) => Some(*r),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(repairs, vec![WipeRange::new(0, 100000)]);
+        assert!(!receiver
+            .collected
+            .iter()
+            .any(|(_, e)| matches!(e, Retrying(_))));
+        assert_eq!(state.verified_blocks, 4);
+        assert_eq!(state.verify_retries_left, 8);
+        assert!(storage.file.get_ref()[32768..65536]
+            .iter()
+            .any(|x| *x != 0xff));
+    }
+
+    #[test]
+    fn test_wiping_block_retry_scope() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = StubReceiver::new();
+
+        // the first write and two rewrites of the block are lost
+        storage.lose_writes_at(vec![32768, 32768, 32768]);
+
+        let mut task =
+            WipeTask::new(scheme.clone(), Verify::Last, storage.size as u64, 32768).unwrap();
+        task.retry_scope = RetryScope::Block;
+        task.retry_backoff = RetryBackoff::Fixed(Duration::ZERO);
+        let mut state = WipeState {
+            verify_retries_left: 8,
+            ..Default::default()
+        };
+
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        let count =
+            |f: fn(&WipeEvent) -> bool| receiver.collected.iter().filter(|(_, e)| f(e)).count();
+        assert_eq!(count(|e| matches!(e, Repairing(_))), 3);
+        assert_eq!(count(|e| matches!(e, Retrying(_))), 2);
+        // the stage wasn't written again
+        assert_eq!(count(|e| matches!(e, StageStarted)), 2);
+        assert_eq!(state.verify_retries_left, 6);
+        assert!(storage.file.get_ref().iter().all(|x| *x == 0u8));
+    }
+
+    #[test]
+    fn test_wiping_mismatch_uses_verify_retries() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = StubReceiver::new();
+
+        storage.corrupt_at(vec![32768]);
+
+        let mut task =
+            WipeTask::new(scheme.clone(), Verify::Last, storage.size as u64, 32768).unwrap();
+        task.retry_backoff = RetryBackoff::Fixed(Duration::ZERO);
+        let mut state = WipeState {
+            retries_left: 8,
+            verify_retries_left: 2,
+            ..Default::default()
+        };
+
+        assert!(!task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        assert_eq!(
+            receiver
+                .collected
+                .iter()
+                .filter(|(_, e)| matches!(e, Retrying(_)))
+                .count(),
+            2
+        );
+        assert_eq!(state.retries_left, 8);
+        assert_eq!(state.verify_retries_left, 0);
+        assert_matches!(receiver.collected.last(), Some((_, Completed(Some(_)))));
+    }
+
+    #[test]
+    fn test_wiping_continue_on_mismatch() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let block_size = 8192;
+        let mut receiver = StubReceiver::new();
+
+        storage.corrupt_at(vec![16384, 24576, 81920]);
+
+        let mut task = WipeTask::new(
+            scheme.clone(),
+            Verify::Last,
+            storage.size as u64,
+            block_size,
+        )
+        .unwrap();
+        task.continue_on_mismatch = true;
+        let mut state = WipeState {
+            retries_left: 8,
+            ..Default::default()
+        };
+        let outcome = task.run(&mut storage, &mut state, &mut receiver);
+
+        assert_eq!(outcome, WipeOutcome::VerificationFailed);
+        assert!(!receiver
+            .collected
+            .iter()
+            .any(|(_, e)| matches!(e, Retrying(_))));
+
+        let mismatches: Vec<_> = receiver
+            .collected
+            .iter()
+            .filter_map(|(_, e)| match e {
+                MarkBlockAsMismatched(p) => Some(*p),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(mismatches, vec![16384, 24576, 81920]);
+        assert_matches!(receiver.collected.last(), Some((_, Completed(Some(_)))));
+        assert_eq!(state.verified_blocks, 13);
+
+        let task = WipeTask::new(scheme.clone(), Verify::Last, 100000, block_size).unwrap();
+        let blocks = state.mismatched_blocks.borrow().all_marked();
+        assert_eq!(
+            task.block_ranges(&blocks),
+            vec![WipeRange::new(16384, 32768), WipeRange::new(81920, 90112)]
+        );
+        assert_eq!(
+            task.block_ranges(&[12]),
+            vec![WipeRange::new(98304, 100000)]
+        );
+    }
+
+    #[test]
+    fn test_wiping_digest_verification() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random2x").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = StubReceiver::new();
+
+        storage.lose_writes_at(vec![8192]);
+
+        let mut task =
+            WipeTask::new(scheme.clone(), Verify::All, storage.size as u64, 8192).unwrap();
+        task.digest_verification = true;
+        let mut state = WipeState::default();
+
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        assert_eq!(state.verified_blocks, 13);
+        assert_eq!(
+            receiver
+                .collected
+                .iter()
+                .filter(|(_, e)| matches!(e, Repairing(_)))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_wiping_entropy_verification() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = StubReceiver::new();
+
+        storage.lose_writes_at(vec![8192]);
+
+        let mut task =
+            WipeTask::new(scheme.clone(), Verify::Last, storage.size as u64, 8192).unwrap();
+        task.entropy_verification = true;
+        let mut state = WipeState::default();
+
+        assert!(task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        assert_eq!(state.verified_blocks, 13);
+        assert_eq!(
+            receiver
+                .collected
+                .iter()
+                .filter(|(_, e)| matches!(e, Repairing(_)))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_wiping_digest_verification_marks_whole_group() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("random").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        let mut receiver = StubReceiver::new();
+
+        storage.corrupt_at(vec![16384]);
+
+        let mut task =
+            WipeTask::new(scheme.clone(), Verify::Last, storage.size as u64, 8192).unwrap();
+        task.digest_verification = true;
+        task.continue_on_mismatch = true;
+        let mut state = WipeState::default();
+
+        assert!(!task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        assert_eq!(state.mismatched_blocks.borrow().total_marked(), 13);
+        assert_matches!(receiver.collected.last(), Some((_, Completed(Some(_)))));
+    }
+
+    #[test]
+    fn test_verify_only() {
+        let schemes = SchemeRepo::default();
+        let scheme = schemes.find("zero").unwrap();
+        let mut storage = InMemoryStorage::new(100000);
+        storage.file.get_mut()[..50000].fill(0);
+
+        let mut task =
+            WipeTask::new(scheme.clone(), Verify::Last, storage.size as u64, 8192).unwrap();
+        task.verify_only = true;
+        task.continue_on_mismatch = true;
+        let mut state = WipeState {
+            retries_left: 8,
+            verify_retries_left: 8,
+            ..Default::default()
+        };
+        let mut receiver = StubReceiver::new();
+
+        assert!(!task
+            .run(&mut storage, &mut state, &mut receiver)
+            .is_success());
+        assert_eq!(storage.total_written, 0);
+        assert_eq!(state.verified_blocks, 13);
+        // blocks 6 (partially zeroed) to 12 don't match
+        assert_eq!(state.mismatched_blocks.borrow().total_marked(), 7);
+    }
+
+    #[test]
+    fn test_blank_check() {
+        let mut storage = InMemoryStorage::new(100000);
+        storage.file.get_mut().fill(0);
+
+        let task = WipeTask::blank_check(0, storage.size as u64, 8192).unwrap();
+        let outcome = task.run(
+            &mut storage,
+            &mut WipeState::default(),
+            &mut StubReceiver::new(),
+        );
+        assert_eq!(outcome, WipeOutcome::Completed);
+
+        storage.file.get_mut()[20000] = 1;
+        storage.file.get_mut()[99999] = 0xff;
+        let task = WipeTask::blank_check(0, storage.size as u64, 8192).unwrap();
+        let mut state = WipeState::default();
+        let outcome = task.run(&mut storage, &mut state, &mut StubReceiver::new());
+        assert_eq!(outcome, WipeOutcome::VerificationFailed);
+        assert_eq!(state.mismatched_blocks.borrow().all_marked(), vec![2, 12]);
+        assert_eq!(state.verified_blocks, 13);
+        assert_eq!(storage.total_written, 0);
+
+        storage.file.get_mut().fill(0xff);
+        let task = WipeTask::blank_check(0xff, storage.size as u64, 8192).unwrap();
+        assert!(task
+            .run(
+                &mut storage,
+                &mut WipeState::default(),
+                &mut StubReceiver::new()
+            )
+            .is_success());
+    }
+
+    #[test]
+    fn test_verify_only_deterministic_random() {
+        let schemes = SchemeRepo::default();
+        let mut storage = InMemoryStorage::new(100000);
+        let scheme = |seed: &[u8]| {
+            let mut scheme = schemes.find("random2x").unwrap().clone();
+            scheme.derive_seeds(seed);
+            scheme
+        };
+
+        let task = WipeTask::new(scheme(b"seed"), Verify::No, 100000, 8192).unwrap();
+        assert!(task
+            .run(
+                &mut storage,
+                &mut WipeState::default(),
+                &mut StubReceiver::new()
+            )
+            .is_success());
+        let written = storage.total_written;
+
+        for (seed, matches) in [(&b"seed"[..], true), (&b"other"[..], false)] {
+            let mut task = WipeTask::new(scheme(seed), Verify::Last, 100000, 8192).unwrap();
+            task.verify_only = true;
+            let result = task
+                .run(
+                    &mut storage,
+                    &mut WipeState::default(),
+                    &mut StubReceiver::new(),
+                )
+                .is_success();
+            assert_eq!(result, matches);
+        }
+        assert_eq!(storage.total_written, written);
+    }
+
+    struct StubReceiver {
+        collected: Vec<(WipeState, WipeEvent)>,
+    }
+
+    impl StubReceiver {
+        pub fn new() -> Self {
+            StubReceiver {
+                collected: Vec::new(),
+            }
+        }
+    }
+
+    impl WipeEventReceiver for StubReceiver {
+        fn handle(&mut self, _task: &WipeTask, state: &WipeState, event: WipeEvent) {
+            println!("{:?}", event);
+            self.collected.push((state.clone(), event));
         }
     }
 }
@@ -0,0 +1,76 @@
+//! Secure, cross-platform drive wiping.
+//!
+//! The library powers the `lethe` command line utility and can be embedded into other
+//! applications. The public API lives in [`core`]:
+//!
+//! - [`core::storage`] enumerates storage devices and provides raw access to them.
+//! - [`core::sanitization`] defines data sanitization schemes and the stages they consist of.
+//! - [`core::actions`] runs wipe tasks and reports their progress through events.
+//!
+//! ```
+//! use lethe::core::actions::{Verify, WipeEvent, WipeEventReceiver, WipeState, WipeTask};
+//! use lethe::core::sanitization::SchemeRepo;
+//! use lethe::core::storage::StorageAccess;
+//! use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+//!
+//! // any storage can be wiped as long as it implements `StorageAccess`,
+//! // real devices are accessed with `System::access`
+//! struct InMemory(Cursor<Vec<u8>>);
+//!
+//! impl StorageAccess for InMemory {
+//!     fn position(&mut self) -> anyhow::Result<u64> {
+//!         Ok(self.0.position())
+//!     }
+//!     fn seek(&mut self, position: u64) -> anyhow::Result<u64> {
+//!         Ok(self.0.seek(SeekFrom::Start(position))?)
+//!     }
+//!     fn read(&mut self, buffer: &mut [u8]) -> anyhow::Result<usize> {
+//!         Ok(self.0.read(buffer)?)
+//!     }
+//!     fn write(&mut self, data: &[u8]) -> anyhow::Result<()> {
+//!         Ok(self.0.write_all(data)?)
+//!     }
+//!     fn flush(&mut self) -> anyhow::Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! struct Progress;
+//!
+//! impl WipeEventReceiver for Progress {
+//!     fn handle(&mut self, _task: &WipeTask, _state: &WipeState, event: WipeEvent) {
+//!         if let WipeEvent::Completed(Some(err)) = event {
+//!             eprintln!("Wipe failed: {:#}", err);
+//!         }
+//!     }
+//! }
+//!
+//! let scheme = SchemeRepo::default().find("zero").unwrap().clone();
+//! let mut storage = InMemory(Cursor::new(vec![0xff; 65536]));
+//! let task = WipeTask::new(scheme, Verify::Last, 65536, 4096).unwrap();
+//!
+//! assert!(task
+//!     .run(&mut storage, &mut WipeState::default(), &mut Progress)
+//!     .is_success());
+//! assert!(storage.0.get_ref().iter().all(|b| *b == 0));
+//! ```
+
+#![recursion_limit = "256"]
+
+#[macro_use]
+extern crate anyhow;
+
+#[doc(hidden)]
+pub mod actions;
+#[doc(hidden)]
+pub mod sanitization;
+#[doc(hidden)]
+pub mod storage;
+
+pub mod core {
+    //! Stable entry points of the library.
+
+    pub use crate::actions;
+    pub use crate::sanitization;
+    pub use crate::storage;
+}
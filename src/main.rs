@@ -1,5 +1,4 @@
-#![recursion_limit = "256"]
-
+use std::ffi::OsString;
 use std::rc::Rc;
 
 #[macro_use]
@@ -16,34 +15,116 @@ use prettytable::{format, Table};
 use ::console::style;
 use indicatif::HumanBytes;
 
-mod storage;
+use lethe::core::{actions, sanitization, storage};
+
+use storage::sanitize::{
+    DriveSanitizer, SanitizeInterface, SanitizeMode, SanitizeSupport, Sanitizer,
+};
 use storage::*;
 
-mod sanitization;
 use sanitization::*;
 
-mod actions;
+use actions::checkpoint::*;
 use actions::*;
 
 mod ui;
 use ui::*;
 
-const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// How often the devices are listed again by `watch` when the system doesn't notify about
+/// attached devices.
+const WATCH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+fn main() {
+    if let Err(err) = run(std::env::args_os().collect()) {
+        if let Some(exitcode::Exit(code)) = err.downcast_ref() {
+            std::process::exit(*code);
+        }
+        eprintln!("Error: {:?}", err);
+        cli::ConsoleFrontend::print_error_hint(&err);
+        std::process::exit(exitcode::of_error(&err));
+    }
+}
 
-fn main() -> Result<()> {
+fn run(args: Vec<OsString>) -> Result<()> {
     let schemes = SchemeRepo::default();
     let scheme_keys: Vec<_> = schemes.all().keys().cloned().collect();
 
     let schemes_explanation = cli::ConsoleFrontend::explain_schemes(&schemes);
+    let topics_help = format!(
+        "Command or one of the topics: {}",
+        examples::TOPICS
+            .iter()
+            .map(|t| t.name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let default_retries = config::DEFAULT_RETRIES.to_string();
+    let default_inspect_sectors = inspect::DEFAULT_SECTORS.to_string();
 
-    let app = App::new("Lethe")
+    let cli = App::new("Lethe")
         .version(VERSION)
         .author("https://github.com/Kostassoid/lethe")
         .about("Secure disk wipe")
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .setting(AppSettings::UnifiedHelpMessage)
         .setting(AppSettings::VersionlessSubcommands)
+        .setting(AppSettings::DisableHelpSubcommand)
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("Output format"),
+        )
+        .arg(
+            Arg::with_name("lock-memory")
+                .long("lock-memory")
+                .global(true)
+                .help("Lock the data buffers in RAM so they are never swapped out"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .global(true)
+                .help("Show more details, like the buffer pool statistics after a wipe"),
+        )
+        .arg(
+            Arg::with_name("no-progress")
+                .long("no-progress")
+                .global(true)
+                .help("Print the stages as plain lines instead of progress bars, e.g. for logs"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .long("quiet")
+                .global(true)
+                .help("Print only the warnings, the stages and the final report, without progress bars (requires --yes)"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .global(true)
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Read the default options from this file instead of lethe.toml in the user config directory"),
+        )
         .subcommand(SubCommand::with_name("list").about("list available storage devices"))
+        .subcommand(
+            SubCommand::with_name("help")
+                .about("Prints help of a command or a topic")
+                .arg(Arg::with_name("topic").index(1).help(&topics_help)),
+        )
+        .subcommand(SubCommand::with_name("examples").about("Show examples of common commands"))
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("Show the default options in effect and where they are read from"),
+        )
         .subcommand(
             SubCommand::with_name("wipe")
                 .about("Wipe storage device")
@@ -52,152 +133,3030 @@ fn main() -> Result<()> {
                     Arg::with_name("device")
                         .long("device")
                         .short("d")
-                        .required(true)
+                        .required_unless("file")
                         .takes_value(true)
                         .index(1)
                         .help("Storage device ID"),
                 )
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .conflicts_with("device")
+                        .help("Wipe a regular file (like a disk image) instead of a storage device"),
+                )
+                .arg(
+                    Arg::with_name("file-after")
+                        .long("file-after")
+                        .takes_value(true)
+                        .possible_values(ImageFileDisposal::NAMES)
+                        .requires("file")
+                        .help("After a successful wipe, keep, truncate or delete the file"),
+                )
                 .arg(
                     Arg::with_name("scheme")
                         .long("scheme")
                         .short("s")
                         .takes_value(true)
                         .possible_values(&scheme_keys)
-                        .default_value("random2x")
+                        .default_value(config::DEFAULT_SCHEME)
                         .help("Data sanitization scheme"),
                 )
+                .arg(
+                    Arg::with_name("schemes")
+                        .long("schemes")
+                        .takes_value(true)
+                        .use_delimiter(true)
+                        .possible_values(&scheme_keys)
+                        .conflicts_with("scheme")
+                        .help("Run several schemes one after another in a single session (e.g. badblocks,random), progress is not saved"),
+                )
                 .arg(
                     Arg::with_name("verify")
                         .long("verify")
                         .short("v")
                         .takes_value(true)
-                        .possible_values(&["no", "last", "all"])
-                        .default_value("last")
-                        .help("Verify after completion"),
+                        .default_value(config::DEFAULT_VERIFY)
+                        .value_name("no|last|all|sample:PERCENT")
+                        .help("Verify after completion (sample checks only a part of the blocks)"),
+                )
+                .arg(
+                    Arg::with_name("verify-digests")
+                        .long("verify-digests")
+                        .help("Verify against keyed digests of the written data instead of generating it again"),
+                )
+                .arg(
+                    Arg::with_name("verify-entropy")
+                        .long("verify-entropy")
+                        .conflicts_with("verify-digests")
+                        .help("Verify random stages by checking that the data read back looks random instead of generating it again (weaker, but doesn't depend on the generator)"),
+                )
+                .arg(
+                    Arg::with_name("continue-on-mismatch")
+                        .long("continue-on-mismatch")
+                        .help("Keep verifying after a mismatch and list the mismatched ranges at the end instead of retrying the stage"),
                 )
                 .arg(
                     Arg::with_name("blocksize")
                         .long("blocksize")
                         .short("b")
                         .takes_value(true)
-                        .default_value("1m")
-                        .help("Block size"),
+                        .help("Block size [default: 4m for NVMe drives, 1m for others]"),
+                )
+                .arg(
+                    Arg::with_name("min-blocksize")
+                        .long("min-blocksize")
+                        .takes_value(true)
+                        .help("Retry failed writes with smaller blocks down to this size before skipping a block as bad"),
+                )
+                .arg(
+                    Arg::with_name("queue-depth")
+                        .long("queue-depth")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Number of writes kept in flight (Linux builds with the io-uring feature), 1 writes synchronously"),
                 )
                 .arg(
                     Arg::with_name("retries")
                         .long("retries")
                         .short("r")
                         .takes_value(true)
+                        .default_value(&default_retries)
+                        .help("Maximum number of retries after I/O errors"),
+                )
+                .arg(
+                    Arg::with_name("verify-retries")
+                        .long("verify-retries")
+                        .takes_value(true)
                         .default_value("8")
-                        .help("Maximum number of retries"),
+                        .help("Maximum number of retries after verification mismatches"),
+                )
+                .arg(
+                    Arg::with_name("retry-backoff")
+                        .long("retry-backoff")
+                        .takes_value(true)
+                        .value_name("fixed|exponential[:SECONDS]")
+                        .default_value("fixed:3")
+                        .help("Delay before each retry, exponential doubles it every time"),
+                )
+                .arg(
+                    Arg::with_name("retry-scope")
+                        .long("retry-scope")
+                        .takes_value(true)
+                        .possible_values(RetryScope::NAMES)
+                        .default_value("stage")
+                        .help("Rewrite the whole stage or only the region around a mismatched block after verification mismatches"),
+                )
+                .arg(
+                    Arg::with_name("ranges")
+                        .long("ranges")
+                        .takes_value(true)
+                        .value_name("FROM-TO,..")
+                        .help("Wipe only these byte ranges (e.g. 0-1M,100G-200G,END-1M)"),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .takes_value(true)
+                        .value_name("OFFSET")
+                        .conflicts_with("ranges")
+                        .help("Wipe starting from this offset (e.g. 1M or END-1G)"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .value_name("OFFSET")
+                        .conflicts_with_all(&["ranges", "length"])
+                        .help("Wipe up to this offset (e.g. 100G or END)"),
+                )
+                .arg(
+                    Arg::with_name("length")
+                        .long("length")
+                        .takes_value(true)
+                        .value_name("SIZE")
+                        .conflicts_with("ranges")
+                        .help("Wipe only this many bytes after the start offset"),
+                )
+                .arg(
+                    Arg::with_name("quick-size")
+                        .long("quick-size")
+                        .takes_value(true)
+                        .value_name("SIZE")
+                        .default_value("1M")
+                        .help("Size of the beginning and the end of the device wiped by the quick scheme"),
+                )
+                .arg(
+                    Arg::with_name("metadata-first")
+                        .long("metadata-first")
+                        .help("Shred the partition tables and filesystem metadata (MFT, inode tables, APFS container) before the wipe, so the data is unrecoverable early on even if the wipe is interrupted"),
+                )
+                .arg(
+                    Arg::with_name("badblocks-in")
+                        .long("badblocks-in")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Skip the bad blocks listed in this map (saved by --badblocks-out) from the start"),
+                )
+                .arg(
+                    Arg::with_name("badblocks-out")
+                        .long("badblocks-out")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Save the map of bad blocks found to this file at the end"),
+                )
+                .arg(
+                    Arg::with_name("duty")
+                        .long("duty")
+                        .takes_value(true)
+                        .value_name("PERCENT")
+                        .help("Pause between bursts of I/O to keep the device busy only this share of time"),
+                )
+                .arg(
+                    Arg::with_name("rate-limit")
+                        .long("rate-limit")
+                        .takes_value(true)
+                        .value_name("BYTES")
+                        .help("Limit the throughput of the wipe to this many bytes per second (e.g. 200M), leaving bandwidth to other workloads on shared storage"),
+                )
+                .arg(
+                    Arg::with_name("pause-between")
+                        .long("pause-between")
+                        .takes_value(true)
+                        .value_name("HH:MM-HH:MM")
+                        .help("Pause the wipe every day between these times (local time, e.g. 08:00-18:00), keeping the device open and the progress saved"),
+                )
+                .arg(
+                    Arg::with_name("deterministic")
+                        .long("deterministic")
+                        .takes_value(true)
+                        .value_name("MASTER-SEED")
+                        .help("Derive random stage seeds from this master seed, producing the same data on every run"),
                 )
                 .arg(
                     Arg::with_name("yes")
                         .long("yes")
                         .short("y")
                         .help("Automatically confirm"),
+                )
+                .arg(
+                    Arg::with_name("operator")
+                        .long("operator")
+                        .takes_value(true)
+                        .value_name("NAME <EMAIL>")
+                        .env("LETHE_OPERATOR")
+                        .help("Person performing the wipe, recorded in the output"),
+                )
+                .arg(
+                    Arg::with_name("report")
+                        .long("report")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Save an erasure report (JSON, or PDF for .pdf files) to this file"),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .conflicts_with("report")
+                        .help("Go through the whole wipe without writing anything and estimate its duration"),
+                )
+                .arg(
+                    Arg::with_name("no-estimate")
+                        .long("no-estimate")
+                        .help("Skip the short read benchmark estimating the wipe duration"),
+                )
+                .arg(
+                    Arg::with_name("suppress-warnings")
+                        .long("suppress-warnings")
+                        .takes_value(true)
+                        .use_delimiter(true)
+                        .value_name("CODE,..")
+                        .possible_values(warnings::CODES)
+                        .env("LETHE_SUPPRESS_WARNINGS")
+                        .help("Don't show these warnings before the confirmation"),
+                )
+                .arg(
+                    Arg::with_name("no-checkpoint")
+                        .long("no-checkpoint")
+                        .help("Don't save the progress to resume an interrupted wipe later"),
+                )
+                .arg(
+                    Arg::with_name("private")
+                        .long("private")
+                        .conflicts_with_all(&["report", "log-file", "detach", "evidence", "badblocks-out", "diagnostics"])
+                        .help("Leave no local traces: no checkpoints, logs or diagnostics, data buffers locked in RAM, and a final self-audit printed instead of stored"),
+                )
+                .arg(
+                    Arg::with_name("unmount")
+                        .long("unmount")
+                        .conflicts_with("dry-run")
+                        .help("Unmount all filesystems on the device before wiping it (Linux and macOS)"),
+                )
+                .arg(
+                    Arg::with_name("force-system")
+                        .long("force-system")
+                        .help("Allow wiping the device the running system is booted from"),
+                )
+                .arg(
+                    Arg::with_name("detach")
+                        .long("detach")
+                        .help("Keep wiping if the terminal is closed, logging to a file, and allow following the progress with the attach command (Linux and macOS)"),
+                )
+                .arg(
+                    Arg::with_name("log-file")
+                        .long("log-file")
+                        .alias("log")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Also append all wipe events with timestamps to this file"),
+                )
+                .arg(
+                    Arg::with_name("progress-fd")
+                        .long("progress-fd")
+                        .takes_value(true)
+                        .value_name("FD")
+                        .help("Also write the overall percent and status lines (zenity --progress format) to this inherited file descriptor, or a handle on Windows"),
+                )
+                .arg(
+                    Arg::with_name("log-format")
+                        .long("log-format")
+                        .takes_value(true)
+                        .possible_values(&["json", "text"])
+                        .default_value("json")
+                        .help("Format of the log file: JSON lines (same as with --json) or plain text"),
+                )
+                .arg(
+                    Arg::with_name("notify-url")
+                        .long("notify-url")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("URL")
+                        .help("POST the main events and the progress (every minute) as JSON to this URL (requires curl)"),
+                )
+                .arg(
+                    Arg::with_name("notify-cmd")
+                        .long("notify-cmd")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("COMMAND")
+                        .help("Run this shell command for the main events and the progress (every minute), with the event as JSON in the standard input"),
+                )
+                .arg(
+                    Arg::with_name("email-to")
+                        .long("email-to")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("ADDRESS")
+                        .help("Email the outcome of the wipe, bad blocks and looping retries to this address, through the SMTP server in the [email] table of the config (requires curl)"),
+                )
+                .arg(
+                    Arg::with_name("desktop-notify")
+                        .long("desktop-notify")
+                        .help("Show a desktop notification when a stage or the wipe is completed (uses notify-send, osascript or a PowerShell toast)"),
+                )
+                .arg(
+                    Arg::with_name("reverify-after")
+                        .long("reverify-after")
+                        .takes_value(true)
+                        .value_name("DURATION")
+                        .conflicts_with("dry-run")
+                        .help("After a successful wipe, wait this long (e.g. 30m or 1h) and verify a sample of the device again, catching drives which lose the written data"),
+                )
+                .arg(
+                    Arg::with_name("reverify-sample")
+                        .long("reverify-sample")
+                        .takes_value(true)
+                        .value_name("PERCENT")
+                        .default_value("10")
+                        .help("Percent of the blocks checked by the re-verification"),
+                )
+                .arg(
+                    Arg::with_name("after")
+                        .long("after")
+                        .takes_value(true)
+                        .possible_values(postwipe::PostWipeAction::NAMES)
+                        .default_value("none")
+                        .help("After a successful wipe, discard the whole device (trim), write an empty partition table (gpt, mbr) or spin the drive down (standby)"),
+                )
+                .arg(
+                    Arg::with_name("hidden-areas")
+                        .long("hidden-areas")
+                        .takes_value(true)
+                        .possible_values(hidden::HiddenAreaMode::NAMES)
+                        .default_value("report")
+                        .help("Capacity an ATA drive hides in a host protected area (HPA) or behind a device configuration overlay (DCO): only warn about it (report), remove the HPA for the wipe and restore it afterwards (unlock) or remove both for good (remove)"),
+                )
+                .arg(
+                    Arg::with_name("luks")
+                        .long("luks")
+                        .conflicts_with("dry-run")
+                        .help("After the wipe, erase both LUKS header copies, verify no header is left and close the crypt mappings"),
+                )
+                .arg(
+                    Arg::with_name("diagnostics")
+                        .long("diagnostics")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Save a diagnostics bundle (zip) to this file on failure"),
+                )
+                .arg(
+                    Arg::with_name("evidence")
+                        .long("evidence")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .conflicts_with("dry-run")
+                        .help("After the wipe, read back sectors sampled by a random nonce and save them to this archive (zip) for an independent check"),
+                )
+                .arg(
+                    Arg::with_name("label")
+                        .long("label")
+                        .conflicts_with_all(&["dry-run", "private"])
+                        .help("After a successful wipe, write a small signed label recording when and how the device was wiped (shown by the info command)"),
+                )
+                .arg(
+                    Arg::with_name("label-offset")
+                        .long("label-offset")
+                        .takes_value(true)
+                        .value_name("OFFSET")
+                        .requires("label")
+                        .help("Device offset of the label (e.g. 1m or end-4k), a multiple of 4096 [default: 0]"),
+                )
+                .arg(
+                    Arg::with_name("label-key")
+                        .long("label-key")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .requires("label")
+                        .help("File with the key signing the label, otherwise it's only protected by a checksum"),
                 ),
         )
-        .get_matches();
-
-    let storage_devices = System::get_storage_devices().unwrap_or_else(|err| {
-        eprintln!("Unable to enumerate storage devices. {:#}", err);
-
-        if cfg!(linux) {
-            let is_wsl = std::fs::read_to_string("/proc/version")
-                .map(|v| v.contains("Microsoft"))
-                .unwrap_or(false);
-
-            if is_wsl {
-                eprintln!("WSL is not supported.");
-            }
-        }
-
-        std::process::exit(1);
-    });
-
-    let ids = idshortcuts::IdShortcuts::from(storage_devices.iter().map(|r| r.id()).collect());
-
-    let frontend = cli::ConsoleFrontend::new();
-
-    match app.subcommand() {
-        ("list", _) => {
-            let mut t = Table::new();
-            t.set_format(*format::consts::FORMAT_CLEAN);
-            t.set_titles(row!["Device ID", "Short ID", "Size", "Type", "Mount Point",]);
-            for x in storage_devices {
-                t.add_row(row![
-                    style(x.id()).bold(),
-                    style(ids.get_short(x.id()).unwrap_or(&"".to_owned())).bold(),
-                    HumanBytes(x.details().size),
-                    x.details().storage_type,
-                    (x.details().mount_point)
-                        .as_ref()
-                        .unwrap_or(&"".to_string())
-                ]);
-            }
-            t.printstd();
-        }
-        ("wipe", Some(cmd)) => {
-            let device_id = cmd
-                .value_of("device")
-                .map(|id| ids.get(id))
-                .flatten()
-                .ok_or(anyhow!("Invalid device ID"))?;
-            let scheme_id = cmd.value_of("scheme").unwrap();
-            let verification = match cmd.value_of("verify").unwrap() {
-                "no" => Verify::No,
-                "last" => Verify::Last,
-                "all" => Verify::All,
-                _ => Verify::Last,
-            };
-            let block_size_arg = cmd.value_of("blocksize").unwrap();
-            let block_size = ui::args::parse_block_size(block_size_arg)
-                .context(format!("Invalid blocksize value: {}", block_size_arg))?;
-
-            let device = storage_devices
-                .iter()
-                .find(|d| d.id() == device_id)
-                .ok_or(anyhow!("Unknown device {}", device_id))?;
-            let scheme = schemes
-                .find(scheme_id)
-                .ok_or(anyhow!("Unknown scheme {}", scheme_id))?;
-
-            let retries = cmd
-                .value_of("retries")
-                .unwrap()
-                .parse()
-                .context("Invalid retries number value")?;
-
-            let task = WipeTask::new(
-                scheme.clone(),
-                verification,
-                device.details().size,
-                block_size,
-            )?;
-
-            let mut state = WipeState::default();
-            state.retries_left = retries;
-
-            let mut session = frontend.wipe_session(device_id, cmd.is_present("yes"));
-
-            match System::access(device) {
-                Ok(mut access) => {
-                    if !task.run(&mut access, &mut state, &mut session) {
-                        std::process::exit(1);
-                    }
-                }
-                Err(err) => {
-                    session.handle(&task, &state, WipeEvent::Fatal(Rc::from(err)));
-                    std::process::exit(1);
-                }
-            }
-        }
-        _ => {
-            println!("{}", app.usage());
-            std::process::exit(1)
-        }
-    }
-
+        .subcommand(
+            SubCommand::with_name("tui")
+                .about("Pick the device, the scheme and the verification interactively, then wipe as usual"),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Show the label left on a storage device by a wipe with --label")
+                .arg(
+                    Arg::with_name("device")
+                        .long("device")
+                        .short("d")
+                        .required_unless("file")
+                        .takes_value(true)
+                        .index(1)
+                        .help("Storage device ID"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .conflicts_with("device")
+                        .help("Read the label of a regular file (like a disk image) instead of a storage device"),
+                )
+                .arg(
+                    Arg::with_name("label-offset")
+                        .long("label-offset")
+                        .takes_value(true)
+                        .value_name("OFFSET")
+                        .default_value("0")
+                        .help("Device offset of the label"),
+                )
+                .arg(
+                    Arg::with_name("label-key")
+                        .long("label-key")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("File with the key the label was signed with"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("inspect")
+                .about("Show the first and the last sectors, the partition tables and filesystems of a storage device without writing anything")
+                .arg(
+                    Arg::with_name("device")
+                        .long("device")
+                        .short("d")
+                        .required_unless("file")
+                        .takes_value(true)
+                        .index(1)
+                        .help("Storage device ID"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .conflicts_with("device")
+                        .help("Inspect a regular file (like a disk image) instead of a storage device"),
+                )
+                .arg(
+                    Arg::with_name("sectors")
+                        .long("sectors")
+                        .takes_value(true)
+                        .value_name("COUNT")
+                        .default_value(&default_inspect_sectors)
+                        .help("Number of 512-byte sectors to dump from the start and the end"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Serve an HTTP API to list the devices and run wipes remotely")
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .takes_value(true)
+                        .value_name("ADDRESS")
+                        .default_value(server::DEFAULT_LISTEN_ADDRESS)
+                        .help("Address and port to listen on"),
+                )
+                .arg(
+                    Arg::with_name("token-file")
+                        .long("token-file")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("File with the token clients have to send, a random one is generated if not set"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Check that a storage device holds the data expected after a wipe without writing anything")
+                .arg(
+                    Arg::with_name("device")
+                        .long("device")
+                        .short("d")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1)
+                        .help("Storage device ID"),
+                )
+                .arg(
+                    Arg::with_name("scheme")
+                        .long("scheme")
+                        .short("s")
+                        .takes_value(true)
+                        .possible_values(&scheme_keys)
+                        .conflicts_with("pattern")
+                        .help("Expect the data of the last stage of this scheme"),
+                )
+                .arg(
+                    Arg::with_name("seed-file")
+                        .long("seed-file")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .requires("scheme")
+                        .help("File with the master seed of a --deterministic wipe, needed to verify random stages"),
+                )
+                .arg(
+                    Arg::with_name("entropy")
+                        .long("entropy")
+                        .conflicts_with_all(&["pattern", "seed-file"])
+                        .help("Check that the data looks random instead of comparing it, e.g. after a wipe with an unknown seed"),
+                )
+                .arg(
+                    Arg::with_name("pattern")
+                        .long("pattern")
+                        .takes_value(true)
+                        .value_name("HEX")
+                        .help("Expect this repeating byte pattern (e.g. 00, ff or 55aa) [default: 00]"),
+                )
+                .arg(
+                    Arg::with_name("blocksize")
+                        .long("blocksize")
+                        .short("b")
+                        .takes_value(true)
+                        .help("Block size [default: 4m for NVMe drives, 1m for others]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check-blank")
+                .about("Read the whole storage device and check that it holds nothing but zeroes (or another value)")
+                .arg(
+                    Arg::with_name("device")
+                        .long("device")
+                        .short("d")
+                        .required_unless("file")
+                        .takes_value(true)
+                        .index(1)
+                        .help("Storage device ID"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .conflicts_with("device")
+                        .help("Check a regular file (like a disk image) instead of a storage device"),
+                )
+                .arg(
+                    Arg::with_name("value")
+                        .long("value")
+                        .takes_value(true)
+                        .value_name("HEX")
+                        .default_value("00")
+                        .help("Byte value a blank device holds, e.g. ff for some flash media"),
+                )
+                .arg(
+                    Arg::with_name("blocksize")
+                        .long("blocksize")
+                        .short("b")
+                        .takes_value(true)
+                        .help("Block size [default: 4m for NVMe drives, 1m for others]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sanitize")
+                .about("Show whether the drive supports SANITIZE, or erase it with the drive's own SANITIZE command")
+                .arg(
+                    Arg::with_name("device")
+                        .required(true)
+                        .index(1)
+                        .help("Storage device ID"),
+                )
+                .arg(
+                    Arg::with_name("mode")
+                        .long("mode")
+                        .short("m")
+                        .takes_value(true)
+                        .possible_values(SanitizeMode::NAMES)
+                        .help("Sanitize the drive by changing its encryption key (crypto), erasing its blocks (block) or overwriting them (overwrite), only the support is shown if not given"),
+                )
+                .arg(
+                    Arg::with_name("unmount")
+                        .long("unmount")
+                        .help("Unmount all filesystems on the device before sanitizing it (Linux and macOS)"),
+                )
+                .arg(
+                    Arg::with_name("force-system")
+                        .long("force-system")
+                        .help("Allow sanitizing the device the running system is booted from"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Automatically confirm"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("resume")
+                .about("Continue an interrupted job from its checkpoint, or a wipe without one, detecting its progress from the data on the device")
+                .arg(
+                    Arg::with_name("device")
+                        .long("device")
+                        .short("d")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1)
+                        .help("Storage device ID, or the ID of an interrupted job (see `lethe jobs`) to continue from its checkpoint"),
+                )
+                .arg(
+                    Arg::with_name("scheme")
+                        .long("scheme")
+                        .short("s")
+                        .takes_value(true)
+                        .possible_values(&scheme_keys)
+                        .default_value("zero")
+                        .help("Data sanitization scheme of the interrupted wipe, its stages can't be random"),
+                )
+                .arg(
+                    Arg::with_name("verify")
+                        .long("verify")
+                        .short("v")
+                        .takes_value(true)
+                        .default_value("last")
+                        .value_name("no|last|all|sample:PERCENT")
+                        .help("Verify after completion (sample checks only a part of the blocks)"),
+                )
+                .arg(
+                    Arg::with_name("blocksize")
+                        .long("blocksize")
+                        .short("b")
+                        .takes_value(true)
+                        .help("Block size of the interrupted wipe [default: 4m for NVMe drives, 1m for others]"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Automatically confirm"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("attach")
+                .about("Follow the progress of a wipe started with --detach, e.g. from another terminal")
+                .arg(
+                    Arg::with_name("device")
+                        .long("device")
+                        .short("d")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1)
+                        .help("Storage device ID"),
+                ),
+        )
+        .subcommand(
+            batch_args(SubCommand::with_name("wipe-all"), &scheme_keys, &default_retries)
+                .about("Wipe every device matching the selection, e.g. a batch of USB sticks (the system drive is never selected)")
+                .arg(
+                    Arg::with_name("parallel")
+                        .long("parallel")
+                        .short("j")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Number of devices wiped at the same time"),
+                )
+                .arg(
+                    Arg::with_name("mirror")
+                        .long("mirror")
+                        .help("Write the same data to all the devices at once, generating it only once (the devices should be of the same size)"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Automatically confirm"),
+                ),
+        )
+        .subcommand(
+            batch_args(SubCommand::with_name("watch"), &scheme_keys, &default_retries)
+                .about("Wait for devices to be attached and wipe the ones matching the selection, until stopped with Ctrl+C")
+                .arg(
+                    Arg::with_name("auto")
+                        .long("auto")
+                        .help("Start wiping the attached devices right away, without it they are only reported. Only the removable ones are wiped unless --type is given"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Confirm wiping attached devices which aren't removable with --auto"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("wipe-free")
+                .about("Wipe the free space of a mounted filesystem, overwriting the deleted files")
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .index(1)
+                        .help("Directory on the filesystem (like its mount point)"),
+                )
+                .arg(
+                    Arg::with_name("scheme")
+                        .long("scheme")
+                        .short("s")
+                        .takes_value(true)
+                        .possible_values(&scheme_keys)
+                        .default_value("random")
+                        .help("Data sanitization scheme"),
+                )
+                .arg(
+                    Arg::with_name("blocksize")
+                        .long("blocksize")
+                        .short("b")
+                        .takes_value(true)
+                        .default_value("1m")
+                        .help("Block size"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Automatically confirm"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("soak")
+                .about("Run a long read test of a storage device (burn-in)")
+                .arg(
+                    Arg::with_name("device")
+                        .long("device")
+                        .short("d")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1)
+                        .help("Storage device ID"),
+                )
+                .arg(
+                    Arg::with_name("hours")
+                        .long("hours")
+                        .takes_value(true)
+                        .default_value("24")
+                        .help("Test duration in hours"),
+                )
+                .arg(
+                    Arg::with_name("blocksize")
+                        .long("blocksize")
+                        .short("b")
+                        .takes_value(true)
+                        .default_value("1m")
+                        .help("Block size"),
+                )
+                .arg(
+                    Arg::with_name("write-region")
+                        .long("write-region")
+                        .takes_value(true)
+                        .value_name("FROM-TO")
+                        .help("Also write and verify this byte range after each pass (destroys data in it)"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Automatically confirm"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("jobs")
+                .about("List the previous wipes and how they ended, including the interrupted ones")
+                .arg(
+                    Arg::with_name("incomplete")
+                        .long("incomplete")
+                        .help("Only list the wipes which didn't complete"),
+                )
+                .subcommand(
+                    SubCommand::with_name("delete")
+                        .about("Forget an interrupted wipe along with its checkpoint, so the next wipe of the device starts over")
+                        .arg(
+                            Arg::with_name("job")
+                                .required(true)
+                                .index(1)
+                                .value_name("JOB-ID")
+                                .help("Job ID as listed by `lethe jobs`"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("checkpoints")
+                .about("Manage saved progress of interrupted wipes")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("list").about("List saved checkpoints"))
+                .subcommand(
+                    SubCommand::with_name("prune")
+                        .about("Remove stale checkpoints")
+                        .arg(
+                            Arg::with_name("max-age")
+                                .long("max-age")
+                                .takes_value(true)
+                                .value_name("DAYS")
+                                .default_value("30")
+                                .help("Remove checkpoints older than this number of days"),
+                        )
+                        .arg(
+                            Arg::with_name("keep")
+                                .long("keep")
+                                .takes_value(true)
+                                .default_value("16")
+                                .help("Maximum number of most recent checkpoints to keep"),
+                        ),
+                ),
+        );
+
+    let app = match cli.clone().get_matches_from_safe(args) {
+        Ok(app) => app,
+        Err(err) if err.use_stderr() => {
+            eprintln!("{}", err.message);
+            return Err(exitcode::Exit(exitcode::INVALID_ARGUMENTS).into());
+        }
+        Err(err) => err.exit(),
+    };
+
+    let config_path = app
+        .value_of_os("config")
+        .map(std::path::PathBuf::from)
+        .or_else(config::Config::default_location);
+    let config = match &config_path {
+        Some(path) => config::Config::load(path)?,
+        None => config::Config::default(),
+    };
+
+    let state_dir = actions::state::StateDir::default();
+    if let Err(err) = state_dir.migrate_checkpoints() {
+        eprintln!("{:#}", err);
+    }
+
+    match app.subcommand() {
+        ("help", Some(cmd)) => return print_help(cli, cmd.value_of("topic")),
+        ("jobs", Some(cmd)) => {
+            if let ("delete", Some(delete)) = cmd.subcommand() {
+                let job = state_dir.delete_job(delete.value_of("job").unwrap())?;
+                if app.value_of("output") != Some("json") {
+                    println!("Deleted job {} of {}.", job.id, job.fingerprint.id);
+                }
+                return Ok(());
+            }
+            let all = state_dir.jobs().list()?;
+            let (jobs, resumable): (Vec<_>, Vec<_>) = all
+                .iter()
+                .cloned()
+                .zip(state_dir.resumable(&all))
+                .filter(|(job, _)| {
+                    !cmd.is_present("incomplete")
+                        || !matches!(
+                            job.effective_status(),
+                            actions::state::JobStatus::Completed
+                                | actions::state::JobStatus::CompletedWithBadBlocks
+                        )
+                })
+                .unzip();
+            if app.value_of("output") == Some("json") {
+                json::JsonFrontend::print_jobs(&jobs, &resumable);
+            } else if jobs.is_empty() && !all.is_empty() {
+                println!("No incomplete wipes.");
+            } else {
+                cli::ConsoleFrontend::print_jobs(&jobs, &resumable, &state_dir);
+            }
+            return Ok(());
+        }
+        ("config", _) => {
+            if app.value_of("output") == Some("json") {
+                json::JsonFrontend::print_config(config_path.as_deref(), &config);
+            } else {
+                cli::ConsoleFrontend::print_config(config_path.as_deref(), &config);
+            }
+            return Ok(());
+        }
+        ("examples", _) => {
+            for topic in examples::TOPICS {
+                println!("{}\n", style(topic.title).bold());
+                cli::ConsoleFrontend::print_examples(topic, examples::Platform::current(), false);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let mut registry = StorageRegistry::default();
+    registry.register(Box::new(ImageFiles))?;
+    registry.set_filter(config.device_filter()?);
+    let storage_devices = match registry.devices() {
+        Ok(devices) => devices,
+        Err(err) => {
+            eprintln!("Unable to enumerate storage devices. {:#}", err);
+
+            if cfg!(target_os = "linux") {
+                let is_wsl = std::fs::read_to_string("/proc/version")
+                    .map(|v| v.contains("Microsoft"))
+                    .unwrap_or(false);
+
+                if is_wsl {
+                    eprintln!("WSL is not supported.");
+                }
+            }
+
+            return Err(exitcode::Exit(exitcode::of_error(&err)).into());
+        }
+    };
+
+    let ids = idshortcuts::IdShortcuts::from(storage_devices.iter().map(|r| r.id()).collect())
+        .with_aliases(storage_devices.iter().flat_map(|r| {
+            r.details()
+                .aliases
+                .iter()
+                .map(move |a| (a.as_str(), r.id()))
+        }));
+
+    let json_output = app.value_of("output") == Some("json");
+    let quiet = app.is_present("quiet");
+
+    let frontend = cli::ConsoleFrontend::new().with_mode(if quiet {
+        cli::ConsoleMode::Quiet
+    } else if app.is_present("no-progress") {
+        cli::ConsoleMode::NoProgress
+    } else {
+        cli::ConsoleMode::Interactive
+    });
+
+    sanitization::mem::set_memory_locking(app.is_present("lock-memory"));
+
+    match app.subcommand() {
+        ("list", _) if json_output => {
+            json::JsonFrontend::print_devices(&storage_devices, &ids);
+        }
+        ("list", Some(cmd)) => {
+            let verbose = app.is_present("verbose") || cmd.is_present("verbose");
+            let mut t = Table::new();
+            t.set_format(*format::consts::FORMAT_CLEAN);
+            let mut titles = row![
+                "Device ID",
+                "Short ID",
+                "Alias",
+                "Size",
+                "Sectors",
+                "Type",
+                "Filesystem",
+                "Label",
+                "Mount Point",
+                "Boot",
+            ];
+            if verbose {
+                for title in &["Media", "Bus", "Model", "Serial", "Firmware"] {
+                    titles.add_cell(cell!(title));
+                }
+            }
+            t.set_titles(titles);
+            for (depth, x) in device_tree(&storage_devices) {
+                let mut row = row![
+                    style(format!("{}{}", "  ".repeat(depth), x.id())).bold(),
+                    style(ids.get_short(x.id()).unwrap_or(&"".to_owned())).bold(),
+                    x.details()
+                        .aliases
+                        .first()
+                        .and_then(|a| a.rsplit('/').next())
+                        .unwrap_or(""),
+                    HumanBytes(x.details().size),
+                    x.details().describe_sectors().unwrap_or_default(),
+                    x.details().storage_type,
+                    x.details().filesystem.as_deref().unwrap_or(""),
+                    x.details().label.as_deref().unwrap_or(""),
+                    (x.details().mount_point)
+                        .as_ref()
+                        .unwrap_or(&"".to_string()),
+                    if x.details().is_boot {
+                        style("yes").red().bold()
+                    } else {
+                        style("")
+                    }
+                ];
+                if verbose {
+                    row.add_cell(cell!(match x.details().media_type {
+                        MediaType::Unknown => String::new(),
+                        m => m.to_string(),
+                    }));
+                    row.add_cell(cell!(match x.details().bus_type {
+                        BusType::Unknown => String::new(),
+                        b => b.to_string(),
+                    }));
+                    for value in &[
+                        &x.details().model,
+                        &x.details().serial,
+                        &x.details().firmware,
+                    ] {
+                        row.add_cell(cell!(value.as_deref().unwrap_or("")));
+                    }
+                }
+                t.add_row(row);
+            }
+            t.printstd();
+        }
+        ("wipe", Some(cmd)) => {
+            require_yes(cmd, json_output, quiet)?;
+
+            let image = cmd.value_of("file").map(ImageFile::open).transpose()?;
+            let device: &dyn StorageRef = match &image {
+                Some(image) => image,
+                None => {
+                    let device_id = cmd
+                        .value_of("device")
+                        .and_then(|id| ids.get(id))
+                        .ok_or_else(|| {
+                            exitcode::UnknownDevice(
+                                cmd.value_of("device").unwrap_or("").to_string(),
+                            )
+                        })?;
+                    storage_devices
+                        .iter()
+                        .find(|d| d.id() == device_id)
+                        .ok_or_else(|| exitcode::UnknownDevice(device_id.to_string()))?
+                }
+            };
+            let device_id = device.id();
+
+            let hidden_mode: hidden::HiddenAreaMode =
+                cmd.value_of("hidden-areas").unwrap().parse()?;
+            let hidden_areas = match &image {
+                Some(_) => None,
+                None => hidden::HiddenAreas::read(device).filter(|h| !h.is_empty()),
+            };
+            // asked now, but done only once the wipe is confirmed
+            let mut unlock = None;
+            if let Some(hidden) = hidden_areas {
+                if (hidden_mode == hidden::HiddenAreaMode::Remove
+                    || (hidden_mode == hidden::HiddenAreaMode::Unlock && hidden.hpa_sectors() > 0))
+                    && !cmd.is_present("dry-run")
+                {
+                    let permanent = hidden_mode == hidden::HiddenAreaMode::Remove;
+                    if !permanent && cmd.value_of("after") == Some("gpt") {
+                        return Err(anyhow!(
+                            "The backup GPT header would be hidden when the HPA is restored, remove the HPA for good with --hidden-areas remove"
+                        ));
+                    }
+                    if cmd.is_present("yes")
+                        || cli::ConsoleFrontend::ask_to_unlock_hidden_areas(&hidden, permanent)
+                    {
+                        unlock = Some((hidden, permanent));
+                    }
+                }
+            }
+            let (hidden_areas, device_size) = match unlock {
+                Some((hidden, permanent)) if hidden.visible > 0 => (
+                    Some(hidden.after_unlock(permanent)).filter(|h| !h.is_empty()),
+                    device.details().size / hidden.visible * hidden.unlocked_max(permanent),
+                ),
+                _ => (hidden_areas, device.details().size),
+            };
+
+            let scheme_ids: Vec<_> = match cmd.values_of("schemes") {
+                Some(ids) => ids.collect(),
+                None => vec![config::Config::value_of(cmd, "scheme", &config.scheme).unwrap()],
+            };
+            let scheme_id = scheme_ids[0];
+            let multiple_schemes = scheme_ids.len() > 1;
+            let verify_arg = config::Config::value_of(cmd, "verify", &config.verify).unwrap();
+            let verification = ui::args::parse_verify(verify_arg).context(
+                exitcode::InvalidArgument(format!("Invalid verify value: {}", verify_arg)),
+            )?;
+            let block_size = match config::Config::value_of(cmd, "blocksize", &config.blocksize) {
+                Some(b) => ui::args::parse_block_size(b).context(exitcode::InvalidArgument(
+                    format!("Invalid blocksize value: {}", b),
+                ))?,
+                None => device.details().preferred_block_size(),
+            };
+            let geometry = registry
+                .access(device)
+                .and_then(|mut access| access.geometry())
+                .ok()
+                .flatten();
+            let block_size = match &geometry {
+                Some(g) => {
+                    let aligned = ui::args::align_block_size(block_size, g);
+                    if aligned != block_size && !json_output {
+                        cli::ConsoleFrontend::print_block_size_aligned(aligned, g);
+                    }
+                    aligned
+                }
+                None => block_size,
+            };
+
+            let find_scheme = |id: &str| -> Result<Scheme> {
+                let mut scheme = schemes
+                    .find(id)
+                    .ok_or(anyhow!("Unknown scheme {}", id))?
+                    .clone();
+                if let Some(master_seed) = cmd.value_of("deterministic") {
+                    scheme.derive_seeds(master_seed.as_bytes());
+                }
+                Ok(scheme)
+            };
+            let scheme = find_scheme(scheme_id)?;
+
+            let min_block_size = cmd
+                .value_of("min-blocksize")
+                .map(|b| {
+                    ui::args::parse_block_size(b).context(exitcode::InvalidArgument(format!(
+                        "Invalid min-blocksize value: {}",
+                        b
+                    )))
+                })
+                .transpose()?;
+
+            let queue_depth_arg = cmd.value_of("queue-depth").unwrap();
+            let queue_depth: usize = queue_depth_arg
+                .parse()
+                .ok()
+                .filter(|d| (1..=256).contains(d))
+                .ok_or_else(|| anyhow!("Use a number from 1 to 256."))
+                .context(exitcode::InvalidArgument(format!(
+                    "Invalid queue-depth value: {}",
+                    queue_depth_arg
+                )))?;
+            registry.set_queue_depth(queue_depth);
+
+            let retries =
+                match config.retries {
+                    Some(r) if cmd.occurrences_of("retries") == 0 => r,
+                    _ => cmd.value_of("retries").unwrap().parse().context(
+                        exitcode::InvalidArgument("Invalid retries number value".to_string()),
+                    )?,
+                };
+
+            let verify_retries = cmd.value_of("verify-retries").unwrap().parse().context(
+                exitcode::InvalidArgument("Invalid verify retries number value".to_string()),
+            )?;
+
+            let retry_backoff_arg = cmd.value_of("retry-backoff").unwrap();
+            let retry_backoff = RetryBackoff::parse(retry_backoff_arg).context(format!(
+                "Invalid retry-backoff value: {}",
+                retry_backoff_arg
+            ))?;
+            let retry_scope: RetryScope = cmd.value_of("retry-scope").unwrap().parse()?;
+
+            let smtp =
+                match &config.email {
+                    _ if !cmd.is_present("email-to") => None,
+                    Some(smtp) => {
+                        smtp.validate().context(exitcode::InvalidArgument(
+                            "Invalid email settings in the config".to_string(),
+                        ))?;
+                        Some(smtp.clone())
+                    }
+                    None => return Err(exitcode::InvalidArgument(
+                        "--email-to requires the SMTP server in the [email] table of the config"
+                            .to_string(),
+                    )
+                    .into()),
+                };
+
+            let operator = cmd
+                .value_of("operator")
+                .map(|o| {
+                    Operator::parse(o).context(exitcode::InvalidArgument(format!(
+                        "Invalid operator value: {}",
+                        o
+                    )))
+                })
+                .transpose()?;
+
+            let duty_cycle = cmd
+                .value_of("duty")
+                .map(|d| {
+                    d.parse::<u8>()
+                        .map_err(anyhow::Error::from)
+                        .and_then(DutyCycle::new)
+                        .context(exitcode::InvalidArgument(format!(
+                            "Invalid duty value: {}",
+                            d
+                        )))
+                })
+                .transpose()?;
+
+            let rate_limit = cmd
+                .value_of("rate-limit")
+                .map(|r| {
+                    ui::args::parse_size(r)
+                        .and_then(|r| match r {
+                            0 => Err(anyhow!("Should be more than zero.")),
+                            r => Ok(r),
+                        })
+                        .context(exitcode::InvalidArgument(format!(
+                            "Invalid rate-limit value: {}",
+                            r
+                        )))
+                })
+                .transpose()?;
+
+            let schedule = cmd
+                .value_of("pause-between")
+                .map(|w| {
+                    w.parse::<actions::schedule::PauseWindow>()
+                        .map(actions::schedule::Schedule::new)
+                        .context(exitcode::InvalidArgument(format!(
+                            "Invalid pause-between value: {}",
+                            w
+                        )))
+                })
+                .transpose()?;
+
+            let quick_size_arg = cmd.value_of("quick-size").unwrap();
+            let quick_size = ui::args::parse_size(quick_size_arg)
+                .and_then(|s| match s {
+                    0 => Err(anyhow!("Should be more than zero.")),
+                    s => Ok(s),
+                })
+                .context(exitcode::InvalidArgument(format!(
+                    "Invalid quick-size value: {}",
+                    quick_size_arg
+                )))?;
+
+            let ranges = cmd
+                .value_of("ranges")
+                .map(|r| {
+                    ui::args::parse_ranges(r, device_size).context(exitcode::InvalidArgument(
+                        format!("Invalid ranges value: {}", r),
+                    ))
+                })
+                .transpose()?
+                .or(
+                    match (
+                        cmd.value_of("from"),
+                        cmd.value_of("to"),
+                        cmd.value_of("length"),
+                    ) {
+                        (None, None, None) => None,
+                        (from, to, length) => {
+                            Some(vec![ui::args::parse_window(from, to, length, device_size)?])
+                        }
+                    },
+                );
+
+            if cmd.is_present("verify-digests") {
+                if let Verify::Sample { .. } = verification {
+                    return Err(anyhow!(
+                        "Digest verification can't be combined with sampled verification"
+                    ));
+                }
+            }
+
+            if let Some(s) = scheme_ids.iter().find(|s| is_targeted(s)) {
+                if multiple_schemes {
+                    return Err(anyhow!(
+                        "The {} scheme can't be combined with other schemes",
+                        s
+                    ));
+                }
+            }
+
+            if is_targeted(scheme_id) && ranges.is_some() {
+                return Err(anyhow!(
+                    "Ranges can't be combined with the {} scheme",
+                    scheme_id
+                ));
+            }
+
+            if cmd.is_present("metadata-first") && (is_targeted(scheme_id) || ranges.is_some()) {
+                return Err(exitcode::InvalidArgument(format!(
+                    "--metadata-first can't be combined with ranges or the {} scheme",
+                    scheme_id
+                ))
+                .into());
+            }
+
+            if cmd.occurrences_of("quick-size") > 0 && scheme_id != QUICK_SCHEME {
+                return Err(exitcode::InvalidArgument(format!(
+                    "--quick-size applies only to the {} scheme",
+                    QUICK_SCHEME
+                ))
+                .into());
+            }
+
+            let dry_run = cmd.is_present("dry-run");
+            let private = cmd.is_present("private");
+            let no_checkpoint = private || cmd.is_present("no-checkpoint");
+            if private {
+                sanitization::mem::set_memory_locking(true);
+            }
+            let fingerprint =
+                DeviceFingerprint::new(device_id, device_size).with_identity(device.details());
+            let checkpoints = state_dir.checkpoints();
+
+            if !no_checkpoint {
+                match checkpoints.prune(&RetentionPolicy::default()) {
+                    Ok(pruned) => {
+                        cli::ConsoleFrontend::print_unreadable_checkpoints(&pruned.unreadable)
+                    }
+                    Err(err) => eprintln!("Unable to prune stale checkpoints. {:#}", err),
+                }
+            }
+
+            let resumed = if no_checkpoint {
+                // not even looked up, the store is left untouched
+                None
+            } else {
+                match checkpoints.find(&fingerprint) {
+                    _ if dry_run || multiple_schemes => None,
+                    Ok(Some(c)) if cmd.is_present("yes") => {
+                        let mut requested =
+                            WipeTask::new(scheme.clone(), verification, device_size, block_size)?;
+                        match &ranges {
+                            Some(r) => requested.set_ranges(r.clone())?,
+                            // found by probing the device, never given explicitly
+                            None if is_targeted(scheme_id) => requested.ranges = c.ranges.clone(),
+                            None => {}
+                        }
+                        if let Some(param) = c.mismatch(&requested) {
+                            return Err(exitcode::InvalidArgument(format!(
+                                "The unfinished wipe of {} used a different {}, resume it without --yes or forget it with `lethe jobs delete`",
+                                device_id, param
+                            ))
+                            .into());
+                        }
+                        Some(c.restore()?)
+                    }
+                    Ok(Some(c)) if cli::ConsoleFrontend::ask_to_resume(&c) => Some(c.restore()?),
+                    Ok(Some(_)) => {
+                        checkpoints.remove(&fingerprint)?;
+                        None
+                    }
+                    Ok(None) => None,
+                    Err(err) => {
+                        eprintln!("Ignoring unreadable checkpoint. {:#}", err);
+                        None
+                    }
+                }
+            };
+
+            let mut destroyed = Vec::new();
+            let resuming = resumed.is_some();
+            let (mut task, mut state) = match resumed {
+                Some(r) => r,
+                None => {
+                    let mut task = WipeTask::new(scheme, verification, device_size, block_size)?;
+                    let ranges = match ranges {
+                        None if is_targeted(scheme_id) => {
+                            let mut access = registry.access(device)?;
+                            let signatures = probe::probe(&mut access, device.details().size)?;
+                            let (ranges, edge_size) = if scheme_id == QUICK_SCHEME {
+                                let ranges = probe::quick_ranges(
+                                    &signatures,
+                                    device_size,
+                                    quick_size,
+                                    block_size,
+                                )?;
+                                (ranges, Some(quick_size.min(device_size)))
+                            } else if signatures.is_empty() {
+                                return Err(anyhow!(
+                                    "No partition tables or filesystems found on {}",
+                                    device_id
+                                ));
+                            } else {
+                                let ranges =
+                                    probe::metadata_ranges(&signatures, device_size, block_size)?;
+                                (ranges, None)
+                            };
+                            if json_output {
+                                json::JsonFrontend::print_targeted_wipe(&signatures, &ranges);
+                            } else {
+                                cli::ConsoleFrontend::print_targeted_wipe(
+                                    &signatures,
+                                    edge_size,
+                                    &ranges,
+                                );
+                            }
+                            destroyed = signatures;
+                            Some(ranges)
+                        }
+                        r => r,
+                    };
+                    if let Some(r) = ranges {
+                        task.set_ranges(r)?;
+                    }
+                    (task, WipeState::default())
+                }
+            };
+            // the checkpoint may have been saved by a run of another scheme
+            let scheme_names = if resuming {
+                resumed_scheme_name(&schemes, &task.scheme)
+            } else {
+                scheme_ids.join(", ")
+            };
+            task.operator = operator;
+            task.duty_cycle = duty_cycle;
+            task.rate_limit = rate_limit;
+            task.schedule = schedule;
+            task.trim_supported = device.details().trim_supported;
+            task.continue_on_mismatch = cmd.is_present("continue-on-mismatch");
+            task.retry_backoff = retry_backoff;
+            task.retry_scope = retry_scope;
+            task.dry_run = dry_run;
+            task.digest_verification = cmd.is_present("verify-digests");
+            task.entropy_verification = cmd.is_present("verify-entropy");
+            if let Some(b) = min_block_size {
+                task.set_min_block_size(b)?;
+            }
+            if let Some(g) = &geometry {
+                task.align_to(g)?;
+            }
+            state.retries_left = retries;
+            state.verify_retries_left = verify_retries;
+
+            if let Some(path) = cmd.value_of("badblocks-in") {
+                let marked = badblocks::BadBlockMap::load(path)
+                    .and_then(|m| m.apply(&task, &mut state))
+                    .context(format!("Unable to use the bad block map {}", path))?;
+                if !json_output {
+                    println!("Skipping {} known bad block(s).", marked);
+                }
+            }
+
+            registry.check_for_wipe(device, dry_run || cmd.is_present("force-system"))?;
+
+            let after: postwipe::PostWipeAction = cmd.value_of("after").unwrap().parse()?;
+            let file_after: Option<ImageFileDisposal> =
+                cmd.value_of("file-after").map(|a| a.parse()).transpose()?;
+
+            let label_target = if cmd.is_present("label") {
+                let offset_arg = cmd.value_of("label-offset").unwrap_or("0");
+                let offset = ui::args::parse_offset(offset_arg, device_size)
+                    .and_then(|o| label::check_offset(o, device_size).map(|_| o))
+                    .context(exitcode::InvalidArgument(format!(
+                        "Invalid label-offset value: {}",
+                        offset_arg
+                    )))?;
+                match after {
+                    postwipe::PostWipeAction::Trim => {
+                        return Err(anyhow!(
+                            "The label can't be combined with discarding the device after the wipe"
+                        ))
+                    }
+                    postwipe::PostWipeAction::Gpt | postwipe::PostWipeAction::Mbr
+                        if label::overlaps_partition_table(offset, device_size) =>
+                    {
+                        return Err(anyhow!(
+                            "The label would be overwritten by the partition table, move it with --label-offset"
+                        ))
+                    }
+                    _ => {}
+                }
+                let key = cmd
+                    .value_of("label-key")
+                    .map(|path| {
+                        std::fs::read(path)
+                            .context(format!("Unable to read the label key {}", path))
+                    })
+                    .transpose()?;
+                Some((offset, key))
+            } else {
+                None
+            };
+
+            let reverify_after = cmd
+                .value_of("reverify-after")
+                .map(|d| {
+                    ui::args::parse_duration(d).context(exitcode::InvalidArgument(format!(
+                        "Invalid reverify-after value: {}",
+                        d
+                    )))
+                })
+                .transpose()?;
+            let reverify_sample = cmd.value_of("reverify-sample").unwrap();
+            let reverify_sample: u8 =
+                reverify_sample
+                    .trim_end_matches('%')
+                    .parse()
+                    .context(format!(
+                        "Invalid reverify-sample value: {}",
+                        reverify_sample
+                    ))?;
+
+            let luks_volumes = if cmd.is_present("luks") {
+                let volumes =
+                    luks::find_volumes(&mut registry.access(device)?, device.details().size)?;
+                if volumes.is_empty() {
+                    return Err(anyhow!("No LUKS headers found on {}", device_id));
+                }
+                Some(volumes)
+            } else {
+                None
+            };
+
+            // the following schemes are run with the same settings
+            let mut tasks = vec![task];
+            for id in &scheme_ids[1..] {
+                let mut next = tasks[0].clone();
+                next.scheme = find_scheme(id)?;
+                tasks.push(next);
+            }
+
+            // already done if the wipe is resumed, it's never checkpointed
+            let mut metadata_pass = None;
+            if cmd.is_present("metadata-first") && !resuming {
+                let signatures =
+                    probe::probe(&mut registry.access(device)?, device.details().size)?;
+                if signatures.is_empty() {
+                    if !json_output {
+                        println!("No partition tables or filesystems found to shred first.");
+                    }
+                } else {
+                    let ranges =
+                        probe::metadata_ranges(&signatures, device_size, tasks[0].block_size)?;
+                    if json_output {
+                        json::JsonFrontend::print_targeted_wipe(&signatures, &ranges);
+                    } else {
+                        cli::ConsoleFrontend::print_targeted_wipe(&signatures, None, &ranges);
+                    }
+                    let mut pass = tasks[0].clone();
+                    pass.scheme = find_scheme(METADATA_SCHEME)?;
+                    pass.verify = Verify::No;
+                    pass.set_ranges(ranges)?;
+                    metadata_pass = Some(pass);
+                    destroyed = signatures;
+                }
+            }
+
+            if !cmd.is_present("no-estimate") {
+                // nothing may be written before the wipe is confirmed, so the writes are assumed
+                // to be as fast as the reads until the first stage shows the real throughput
+                // the sectors to be unlocked can't be read yet
+                let total_size = device.details().size.min(tasks[0].total_size);
+                let throughput = registry.access(device).and_then(|mut access| {
+                    benchmark::read_throughput(
+                        &mut access,
+                        total_size,
+                        tasks[0].block_size,
+                        tasks[0].io_alignment,
+                        benchmark::DEFAULT_BENCHMARK_DURATION,
+                    )
+                });
+                match throughput {
+                    Ok(read) => {
+                        let read = rate_limit.map_or(read, |r| read.min(r));
+                        for task in tasks.iter_mut().chain(metadata_pass.iter_mut()) {
+                            task.estimated_stage_durations = task.estimate_stages(read, read);
+                            task.estimated_duration =
+                                Some(task.estimated_stage_durations.iter().sum());
+                        }
+                    }
+                    Err(err) => eprintln!("Unable to estimate the wipe duration. {:#}", err),
+                }
+            }
+
+            if reverify_after.is_some() {
+                let last_scheme = &tasks[tasks.len() - 1].scheme;
+                if !last_scheme.stages.iter().any(|s| s.is_verifiable()) {
+                    return Err(anyhow!(
+                        "The {} scheme has nothing to re-verify",
+                        scheme_ids[scheme_ids.len() - 1]
+                    ));
+                }
+            }
+
+            let signatures = registry
+                .access(device)
+                .and_then(|mut access| probe::probe(&mut access, device.details().size))
+                .unwrap_or_default();
+            let users = registry.platform()?.device_users(device_id);
+            let suppressed: Vec<_> = cmd
+                .values_of("suppress-warnings")
+                .into_iter()
+                .flatten()
+                .collect();
+            let preflight_warnings = warnings::suppress(
+                warnings::Preflight {
+                    details: device.details(),
+                    users: &users,
+                    signatures: &signatures,
+                    hidden_areas: hidden_areas.as_ref(),
+                }
+                .warnings(&tasks[0]),
+                &suppressed,
+            )?;
+            if json_output && !preflight_warnings.is_empty() {
+                json::JsonFrontend::print_warnings(&preflight_warnings);
+            }
+
+            let mut checkpointer =
+                Checkpointer::new(checkpoints, fingerprint.clone(), DEFAULT_FLUSH_INTERVAL);
+
+            let mut session: Box<dyn WipeEventReceiver> = if json_output {
+                Box::new(json::JsonFrontend::wipe_session(device_id))
+            } else {
+                let sensor_id = device_id.to_string();
+                let short_id = match &image {
+                    Some(image) => image
+                        .path()
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned()),
+                    None => ids.get_short(device_id).cloned(),
+                };
+                let mut console = frontend
+                    .wipe_session(
+                        device_id,
+                        device.details(),
+                        cmd.is_present("yes"),
+                        cmd.value_of("diagnostics"),
+                    )
+                    .with_short_id(short_id.as_deref().unwrap_or(device_id))
+                    .with_temperature(Box::new(move || System::temperature(&sensor_id)))
+                    .with_warnings(preflight_warnings);
+                if private {
+                    console = console.without_diagnostics();
+                }
+                if !console.confirm(metadata_pass.as_ref().unwrap_or(&tasks[0]), &state) {
+                    println!("Aborted.");
+                    return Ok(());
+                }
+                Box::new(console)
+            };
+
+            // only done once the wipe is confirmed
+            let unmounted = registry.prepare_for_wipe(
+                device,
+                dry_run || cmd.is_present("force-system"),
+                cmd.is_present("unmount"),
+            )?;
+            if !unmounted.is_empty() {
+                if json_output {
+                    json::JsonFrontend::print_unmounted(&unmounted);
+                } else {
+                    cli::ConsoleFrontend::print_unmounted(&unmounted);
+                }
+            }
+
+            let unlocked_devices;
+            let (device, _hidden_area_guard): (&dyn StorageRef, _) = match unlock {
+                Some((hidden, permanent)) => {
+                    hidden.unlock(device_id, permanent)?;
+                    let guard = if permanent {
+                        None
+                    } else {
+                        Some(HiddenAreaGuard {
+                            hidden,
+                            device_id,
+                            json_output,
+                        })
+                    };
+                    unlocked_devices = registry.devices()?;
+                    let device = unlocked_devices
+                        .iter()
+                        .find(|d| d.id() == device_id)
+                        .ok_or_else(|| exitcode::UnknownDevice(device_id.to_string()))?
+                        .as_ref();
+                    if json_output {
+                        json::JsonFrontend::print_hidden_areas_unlocked(
+                            &hidden,
+                            permanent,
+                            device.details().size,
+                        );
+                    } else {
+                        cli::ConsoleFrontend::print_hidden_areas_unlocked(
+                            &hidden,
+                            permanent,
+                            device.details().size,
+                        );
+                    }
+                    if device.details().size != device_size {
+                        return Err(anyhow!(
+                            "{} has {} bytes after unlocking the hidden areas instead of the expected {}",
+                            device_id,
+                            device.details().size,
+                            device_size
+                        ));
+                    }
+                    (device, guard)
+                }
+                None => (device, None),
+            };
+
+            let evidence_nonce = cmd.value_of("evidence").map(|_| evidence::generate_nonce());
+
+            if cmd.is_present("detach") {
+                #[cfg(unix)]
+                {
+                    control::survive_hangup();
+                    let detachable = control::DetachableReceiver::new(session, device_id)?;
+                    if !json_output {
+                        println!(
+                            "If the terminal is closed, the output goes to {}. Follow the progress with `lethe attach {}`.",
+                            detachable.log_path().to_string_lossy(),
+                            device_id
+                        );
+                    }
+                    session = Box::new(detachable);
+                }
+                #[cfg(not(unix))]
+                return Err(anyhow!("Detaching is not supported on this platform"));
+            }
+
+            if let Some(path) = cmd.value_of("log-file") {
+                let log: Box<dyn WipeEventReceiver> = match cmd.value_of("log-format") {
+                    Some("text") => Box::new(eventlog::TextLogReceiver::open(device_id, path)?),
+                    _ => Box::new(json::JsonFrontend::wipe_log(device_id, path)?),
+                };
+                session = Box::new(FanOutReceiver::new(vec![session]).with(log));
+            }
+
+            if let Some(fd) = cmd.value_of("progress-fd") {
+                let fd = fd.parse().context(exitcode::InvalidArgument(format!(
+                    "Invalid progress-fd value: {}",
+                    fd
+                )))?;
+                let progress = ui::progress::ProgressFdReceiver::open(fd)?;
+                session = Box::new(FanOutReceiver::new(vec![session]).with(Box::new(progress)));
+            }
+
+            let notify_targets: Vec<_> = cmd
+                .values_of("notify-url")
+                .into_iter()
+                .flatten()
+                .map(|u| notify::NotifyTarget::Url(u.to_string()))
+                .chain(
+                    cmd.values_of("notify-cmd")
+                        .into_iter()
+                        .flatten()
+                        .map(|c| notify::NotifyTarget::Command(c.to_string())),
+                )
+                .collect();
+            if !notify_targets.is_empty() {
+                session = Box::new(notify::NotifyingReceiver::new(
+                    session,
+                    device_id,
+                    notify_targets,
+                ));
+            }
+
+            if let (Some(to), Some(smtp)) = (cmd.values_of("email-to"), smtp) {
+                session = Box::new(email::EmailNotifier::new(
+                    session,
+                    device_id,
+                    smtp,
+                    to.map(|t| t.to_string()).collect(),
+                ));
+            }
+
+            if cmd.is_present("desktop-notify") {
+                session = Box::new(desktop::DesktopNotifier::new(session, device_id));
+            }
+
+            if let Some(path) = cmd.value_of("report") {
+                let mut recorder = report::ReportRecorder::new(device_id, device.details())
+                    .with_destroyed(&destroyed);
+                if let Some(nonce) = &evidence_nonce {
+                    recorder = recorder.with_evidence_nonce(&evidence::hex(nonce));
+                }
+                session = Box::new(
+                    report::ReportingReceiver::new(session, recorder, path)
+                        .with_runs(scheme_ids.len() + metadata_pass.iter().count()),
+                );
+            }
+
+            if !private && !dry_run {
+                let job = actions::state::JobRecorder::new(state_dir.clone(), fingerprint)
+                    .with_report(device.details());
+                session = Box::new(FanOutReceiver::new(vec![session]).with(Box::new(job)));
+            }
+
+            // the other tasks are clones sharing the same token
+            tasks[0].cancellation.cancel_on_interrupt();
+
+            match registry.access(device) {
+                Ok(mut access) => {
+                    let (total_size, block_size) = (tasks[0].total_size, tasks[0].block_size);
+                    let last_task = tasks[tasks.len() - 1].clone();
+                    let mut results = Vec::new();
+                    let mut outcome = WipeOutcome::Completed;
+                    let shredding = metadata_pass.is_some();
+                    for (i, task) in metadata_pass.into_iter().chain(tasks).enumerate() {
+                        let is_metadata_pass = shredding && i == 0;
+                        if i > 0 {
+                            // blocks found bad by the previous schemes are skipped right away
+                            state = WipeState {
+                                retries_left: retries,
+                                verify_retries_left: verify_retries,
+                                bad_blocks: Rc::clone(&state.bad_blocks),
+                                ..Default::default()
+                            };
+                        }
+                        outcome =
+                            if dry_run || multiple_schemes || no_checkpoint || is_metadata_pass {
+                                task.run(&mut access, &mut state, session.as_mut())
+                            } else {
+                                task.run_with_checkpoints(
+                                    &mut access,
+                                    &mut state,
+                                    session.as_mut(),
+                                    &mut checkpointer,
+                                )
+                            };
+                        if !is_metadata_pass {
+                            results.push(outcome.is_success());
+                        }
+                        if !outcome.is_success() {
+                            break;
+                        }
+                    }
+                    let success = outcome.is_success() && results.iter().all(|r| *r);
+
+                    if app.is_present("verbose") {
+                        let stats = sanitization::mem::pool_stats();
+                        if json_output {
+                            json::JsonFrontend::print_pool_stats(&stats);
+                        } else {
+                            cli::ConsoleFrontend::print_pool_stats(&stats);
+                        }
+                    }
+
+                    if multiple_schemes && !json_output {
+                        cli::ConsoleFrontend::print_scheme_results(&scheme_ids, &results);
+                    }
+
+                    if private {
+                        let audit = privacy::SelfAudit::run(
+                            device_id,
+                            &state_dir,
+                            &DeviceFingerprint::new(device_id, device.details().size),
+                        );
+                        if json_output {
+                            json::JsonFrontend::print_self_audit(&audit);
+                        } else {
+                            cli::ConsoleFrontend::print_self_audit(&audit);
+                        }
+                    }
+
+                    if let Some(path) = cmd.value_of("badblocks-out") {
+                        let map = badblocks::BadBlockMap::new(total_size, block_size, &state);
+                        if let Err(err) = map.save(path) {
+                            eprintln!("{:#}", err);
+                        }
+                    }
+
+                    if !success {
+                        return Err(exitcode::Exit(exitcode::of_outcome(outcome)).into());
+                    }
+
+                    if let (Some(path), Some(nonce)) = (cmd.value_of("evidence"), evidence_nonce) {
+                        let evidence = evidence::Evidence::collect(
+                            &mut access,
+                            device_id,
+                            &last_task,
+                            &state,
+                            &nonce,
+                            evidence::DEFAULT_SAMPLES,
+                        )?;
+                        let digest = evidence.write(path)?;
+                        eprintln!(
+                            "Evidence archive saved to {} (digest in {}).",
+                            path,
+                            digest.to_string_lossy()
+                        );
+                        if !evidence.is_consistent() {
+                            eprintln!(
+                                "Some of the sampled sectors don't hold the data written by the last stage."
+                            );
+                            return Err(exitcode::Exit(exitcode::VERIFICATION_FAILED).into());
+                        }
+                    }
+
+                    if let Some(delay) = reverify_after {
+                        if json_output {
+                            json::JsonFrontend::print_reverification(delay, reverify_sample);
+                        } else {
+                            cli::ConsoleFrontend::print_reverification(delay, reverify_sample);
+                        }
+                        std::thread::sleep(delay);
+
+                        let mut task = last_task.clone();
+                        task.verify = Verify::sample(reverify_sample)?;
+                        task.verify_only = true;
+                        task.continue_on_mismatch = true;
+                        let mut state = WipeState {
+                            bad_blocks: Rc::clone(&state.bad_blocks),
+                            ..Default::default()
+                        };
+                        let outcome = task.run(&mut access, &mut state, session.as_mut());
+                        if !outcome.is_success() {
+                            return Err(exitcode::Exit(exitcode::of_outcome(outcome)).into());
+                        }
+                    }
+
+                    if let Some(volumes) = luks_volumes {
+                        let mut erasure =
+                            luks::erase_headers(&mut access, device.details().size, &volumes)?;
+                        match registry.platform()?.close_crypt_mappings(device_id) {
+                            Ok(closed) => erasure.closed_mappings = closed,
+                            Err(err) => eprintln!("Unable to close crypt mappings. {:#}", err),
+                        }
+                        if json_output {
+                            json::JsonFrontend::print_luks_erasure(&erasure);
+                        } else {
+                            cli::ConsoleFrontend::print_luks_erasure(&erasure);
+                        }
+                        if !erasure.is_unrecoverable() {
+                            return Err(exitcode::Exit(exitcode::FAILED).into());
+                        }
+                    }
+
+                    if let Some((offset, key)) = &label_target {
+                        let wipe_label = label::WipeLabel {
+                            tool: format!("lethe {}", VERSION),
+                            wiped_at: report::format_timestamp(std::time::SystemTime::now()),
+                            scheme: scheme_names.clone(),
+                            verify: last_task.verify.to_string(),
+                            operator: last_task.operator.as_ref().map(|o| o.to_string()),
+                            keyed: key.is_some(),
+                        };
+                        wipe_label.write(&mut access, *offset, key.as_deref().unwrap_or(&[]))?;
+                        if json_output {
+                            json::JsonFrontend::print_label_written(*offset);
+                        } else {
+                            cli::ConsoleFrontend::print_label_written(*offset);
+                        }
+                    }
+
+                    if !dry_run && after != postwipe::PostWipeAction::None {
+                        after
+                            .apply(registry.platform()?, device, &mut access)
+                            .context("Post-wipe action failed")?;
+                        if json_output {
+                            json::JsonFrontend::print_post_wipe(cmd.value_of("after").unwrap());
+                        } else {
+                            cli::ConsoleFrontend::print_post_wipe(after);
+                        }
+                    }
+
+                    if let (Some(image), Some(disposal), false) = (&image, file_after, dry_run) {
+                        drop(access);
+                        disposal.apply(image)?;
+                        if json_output {
+                            json::JsonFrontend::print_post_wipe(
+                                cmd.value_of("file-after").unwrap(),
+                            );
+                        } else {
+                            cli::ConsoleFrontend::print_post_wipe(disposal);
+                        }
+                    }
+
+                    if outcome == WipeOutcome::CompletedWithBadBlocks {
+                        return Err(exitcode::Exit(exitcode::BAD_BLOCKS_SKIPPED).into());
+                    }
+                }
+                Err(err) => {
+                    let code = exitcode::of_error(&err);
+                    session.handle(&tasks[0], &state, WipeEvent::Fatal(Rc::from(err)));
+                    return Err(exitcode::Exit(code).into());
+                }
+            }
+        }
+        ("verify", Some(cmd)) => {
+            let device_id = cmd
+                .value_of("device")
+                .and_then(|id| ids.get(id))
+                .ok_or_else(|| {
+                    exitcode::UnknownDevice(cmd.value_of("device").unwrap_or("").to_string())
+                })?;
+            let device = storage_devices
+                .iter()
+                .find(|d| d.id() == device_id)
+                .ok_or_else(|| exitcode::UnknownDevice(device_id.to_string()))?;
+            let block_size = match cmd.value_of("blocksize") {
+                Some(b) => ui::args::parse_block_size(b).context(exitcode::InvalidArgument(
+                    format!("Invalid blocksize value: {}", b),
+                ))?,
+                None => device.details().preferred_block_size(),
+            };
+
+            let scheme = match cmd.value_of("scheme") {
+                Some(scheme_id) if is_targeted(scheme_id) => {
+                    return Err(anyhow!("The {} scheme can't be verified", scheme_id))
+                }
+                Some(scheme_id) => {
+                    let mut scheme = schemes
+                        .find(scheme_id)
+                        .ok_or(anyhow!("Unknown scheme {}", scheme_id))?
+                        .clone();
+                    match cmd.value_of("seed-file") {
+                        Some(path) => {
+                            let master_seed = std::fs::read_to_string(path)
+                                .context(format!("Unable to read the seed file {}", path))?;
+                            scheme.derive_seeds(master_seed.trim_end().as_bytes());
+                        }
+                        None => match scheme.stages.iter().rev().find(|s| s.is_verifiable()) {
+                            Some(Stage::Random { .. }) if !cmd.is_present("entropy") => {
+                                return Err(anyhow!(
+                                    "The last stage of {} is random, use --seed-file with the master seed of a deterministic wipe",
+                                    scheme_id
+                                ))
+                            }
+                            Some(_) => {}
+                            None => {
+                                return Err(anyhow!("The {} scheme has nothing to verify", scheme_id))
+                            }
+                        },
+                    }
+                    scheme
+                }
+                None if cmd.is_present("entropy") => Scheme {
+                    description: "Random data".to_string(),
+                    stages: vec![Stage::random()],
+                },
+                None => {
+                    let pattern_arg = cmd.value_of("pattern").unwrap_or("00");
+                    let invalid = || {
+                        exitcode::InvalidArgument(format!("Invalid pattern value: {}", pattern_arg))
+                    };
+                    let pattern = ui::args::parse_pattern(pattern_arg).context(invalid())?;
+                    let stage = Stage::pattern(&pattern).ok_or_else(invalid)?;
+                    Scheme {
+                        description: format!("Pattern {}", pattern_arg),
+                        stages: vec![stage],
+                    }
+                }
+            };
+            let mut task = WipeTask::new(scheme, Verify::Last, device.details().size, block_size)?;
+            task.verify_only = true;
+            task.continue_on_mismatch = true;
+            task.entropy_verification = cmd.is_present("entropy");
+
+            let mut session: Box<dyn WipeEventReceiver> = if json_output {
+                Box::new(json::JsonFrontend::wipe_session(device_id))
+            } else {
+                Box::new(frontend.wipe_session(device_id, device.details(), true, None))
+            };
+
+            let mut access = registry.access(device)?;
+            if let Some(g) = access.geometry().ok().flatten() {
+                task.align_to(&g)?;
+            }
+            let outcome = task.run(&mut access, &mut WipeState::default(), session.as_mut());
+            if !outcome.is_success() {
+                return Err(exitcode::Exit(exitcode::of_outcome(outcome)).into());
+            }
+        }
+        ("sanitize", Some(cmd)) => {
+            let device_id = cmd
+                .value_of("device")
+                .and_then(|id| ids.get(id))
+                .ok_or_else(|| {
+                    exitcode::UnknownDevice(cmd.value_of("device").unwrap_or("").to_string())
+                })?;
+            let device = storage_devices
+                .iter()
+                .find(|d| d.id() == device_id)
+                .ok_or_else(|| exitcode::UnknownDevice(device_id.to_string()))?;
+            let support = SanitizeSupport::read(device_id, device.details().bus_type);
+
+            let mode: SanitizeMode = match cmd.value_of("mode") {
+                Some(m) => m.parse().context(exitcode::InvalidArgument(format!(
+                    "Invalid mode value: {}",
+                    m
+                )))?,
+                None => {
+                    let sanitizer = support.as_ref().map(|s| DriveSanitizer {
+                        device_id: device_id.to_string(),
+                        interface: s.interface,
+                    });
+                    let status = sanitizer.and_then(|s| s.status().ok());
+                    if json_output {
+                        json::JsonFrontend::print_sanitize_support(
+                            device_id,
+                            support.as_ref(),
+                            status,
+                        );
+                    } else {
+                        cli::ConsoleFrontend::print_sanitize_support(
+                            device_id,
+                            support.as_ref(),
+                            status,
+                        );
+                    }
+                    return Ok(());
+                }
+            };
+            require_yes(cmd, json_output, quiet)?;
+            let support = match support {
+                Some(s) if s.supports(mode) => s,
+                Some(s) if s.modes.is_empty() => {
+                    return Err(anyhow!("{} doesn't support SANITIZE", device_id))
+                }
+                Some(_) => {
+                    return Err(anyhow!(
+                        "{} doesn't support the SANITIZE {}",
+                        device_id,
+                        mode
+                    ))
+                }
+                None => {
+                    return Err(anyhow!(
+                        "Unable to query the SANITIZE support of {} with {}",
+                        device_id,
+                        match SanitizeInterface::of(device.details().bus_type) {
+                            SanitizeInterface::Nvme => "nvme-cli",
+                            SanitizeInterface::Ata => "hdparm",
+                        }
+                    ))
+                }
+            };
+
+            registry.check_for_wipe(device.as_ref(), cmd.is_present("force-system"))?;
+
+            let task = actions::sanitize::SanitizeTask::new(device_id, mode);
+            let sanitizer = DriveSanitizer {
+                device_id: device_id.to_string(),
+                interface: support.interface,
+            };
+            let mut session: Box<dyn actions::sanitize::SanitizeEventReceiver> = if json_output {
+                Box::new(json::JsonSanitizeSession)
+            } else {
+                let short_id = ids.get_short(device_id).cloned();
+                let mut console = frontend.sanitize_session(
+                    short_id.as_deref().unwrap_or(device_id),
+                    device.details(),
+                    cmd.is_present("yes"),
+                );
+                if !console.confirm(&task) {
+                    println!("Aborted.");
+                    return Ok(());
+                }
+                Box::new(console)
+            };
+
+            // only done once the operation is confirmed
+            let unmounted = registry.prepare_for_wipe(
+                device.as_ref(),
+                cmd.is_present("force-system"),
+                cmd.is_present("unmount"),
+            )?;
+            if !unmounted.is_empty() {
+                if json_output {
+                    json::JsonFrontend::print_unmounted(&unmounted);
+                } else {
+                    cli::ConsoleFrontend::print_unmounted(&unmounted);
+                }
+            }
+            if !task.run(&sanitizer, session.as_mut()) {
+                return Err(exitcode::Exit(exitcode::FAILED).into());
+            }
+        }
+        ("check-blank", Some(cmd)) => {
+            let image = cmd.value_of("file").map(ImageFile::open).transpose()?;
+            let device: &dyn StorageRef = match &image {
+                Some(image) => image,
+                None => {
+                    let device_id = cmd
+                        .value_of("device")
+                        .and_then(|id| ids.get(id))
+                        .ok_or_else(|| {
+                            exitcode::UnknownDevice(
+                                cmd.value_of("device").unwrap_or("").to_string(),
+                            )
+                        })?;
+                    storage_devices
+                        .iter()
+                        .find(|d| d.id() == device_id)
+                        .ok_or_else(|| exitcode::UnknownDevice(device_id.to_string()))?
+                }
+            };
+            let device_id = device.id();
+            let block_size = match cmd.value_of("blocksize") {
+                Some(b) => ui::args::parse_block_size(b).context(exitcode::InvalidArgument(
+                    format!("Invalid blocksize value: {}", b),
+                ))?,
+                None => device.details().preferred_block_size(),
+            };
+            let value_arg = cmd.value_of("value").unwrap();
+            let value = match ui::args::parse_pattern(value_arg).ok().as_deref() {
+                Some(&[value]) => value,
+                _ => Err(exitcode::InvalidArgument(format!(
+                    "Invalid value {}, expected a single hex byte like 00 or ff",
+                    value_arg
+                )))?,
+            };
+
+            let mut task = WipeTask::blank_check(value, device.details().size, block_size)?;
+            let mut session: Box<dyn WipeEventReceiver> = if json_output {
+                Box::new(json::JsonFrontend::wipe_session(device_id))
+            } else {
+                Box::new(frontend.blank_check_session(device_id, device.details(), value))
+            };
+
+            let mut access = registry.access(device)?;
+            if let Some(g) = access.geometry().ok().flatten() {
+                task.align_to(&g)?;
+            }
+            let outcome = task.run(&mut access, &mut WipeState::default(), session.as_mut());
+            if !outcome.is_success() {
+                return Err(exitcode::Exit(exitcode::of_outcome(outcome)).into());
+            }
+        }
+        ("serve", Some(cmd)) => {
+            let token = match cmd.value_of("token-file") {
+                Some(path) => std::fs::read_to_string(path)
+                    .context(format!("Unable to read the token {}", path))?
+                    .trim()
+                    .to_string(),
+                None => match std::env::var("LETHE_SERVE_TOKEN") {
+                    Ok(t) if !t.trim().is_empty() => t.trim().to_string(),
+                    _ => {
+                        let token = evidence::hex(&rand::random::<[u8; 16]>());
+                        eprintln!("Token: {}", token);
+                        token
+                    }
+                },
+            };
+            if token.is_empty() {
+                return Err(anyhow!("The token is empty"));
+            }
+            let address = cmd.value_of("listen").unwrap();
+            eprintln!("Listening on {}", address);
+            server::Server::new(&token, config).serve(address)?;
+        }
+        ("tui", _) => {
+            if json_output {
+                return Err(anyhow!("The interactive mode doesn't support JSON output"));
+            }
+            let choice = match tui::choose(&storage_devices, &ids, &schemes)? {
+                Some(c) => c,
+                None => return Ok(()),
+            };
+
+            // the regular wipe command does the checks, the confirmation and the progress
+            let mut args: Vec<OsString> = vec![
+                "lethe".into(),
+                "wipe".into(),
+                choice.device_id.into(),
+                "--scheme".into(),
+                choice.scheme.into(),
+                "--verify".into(),
+                choice.verify.into(),
+            ];
+            for flag in &["lock-memory", "verbose"] {
+                if app.is_present(flag) {
+                    args.push(format!("--{}", flag).into());
+                }
+            }
+            return run(args);
+        }
+        ("info", Some(cmd)) => {
+            let image = cmd.value_of("file").map(ImageFile::open).transpose()?;
+            let device: &dyn StorageRef = match &image {
+                Some(image) => image,
+                None => {
+                    let device_id = cmd
+                        .value_of("device")
+                        .and_then(|id| ids.get(id))
+                        .ok_or_else(|| {
+                            exitcode::UnknownDevice(
+                                cmd.value_of("device").unwrap_or("").to_string(),
+                            )
+                        })?;
+                    storage_devices
+                        .iter()
+                        .find(|d| d.id() == device_id)
+                        .ok_or_else(|| exitcode::UnknownDevice(device_id.to_string()))?
+                }
+            };
+            let device_id = device.id();
+            let offset_arg = cmd.value_of("label-offset").unwrap();
+            let offset = ui::args::parse_offset(offset_arg, device.details().size)
+                .and_then(|o| label::check_offset(o, device.details().size).map(|_| o))
+                .context(exitcode::InvalidArgument(format!(
+                    "Invalid label-offset value: {}",
+                    offset_arg
+                )))?;
+            let key = cmd
+                .value_of("label-key")
+                .map(|path| {
+                    std::fs::read(path).context(format!("Unable to read the label key {}", path))
+                })
+                .transpose()?;
+
+            let mut access = registry.access(device)?;
+            let found = label::WipeLabel::read(&mut access, offset, key.as_deref())?;
+            if json_output {
+                json::JsonFrontend::print_label(device_id, found.as_ref());
+            } else {
+                cli::ConsoleFrontend::print_label(device_id, found.as_ref());
+            }
+            if let Some((_, label::LabelSignature::Invalid)) = found {
+                return Err(exitcode::Exit(exitcode::FAILED).into());
+            }
+        }
+        ("inspect", Some(cmd)) => {
+            let image = cmd.value_of("file").map(ImageFile::open).transpose()?;
+            let device: &dyn StorageRef = match &image {
+                Some(image) => image,
+                None => {
+                    let device_id = cmd
+                        .value_of("device")
+                        .and_then(|id| ids.get(id))
+                        .ok_or_else(|| {
+                            exitcode::UnknownDevice(
+                                cmd.value_of("device").unwrap_or("").to_string(),
+                            )
+                        })?;
+                    storage_devices
+                        .iter()
+                        .find(|d| d.id() == device_id)
+                        .ok_or_else(|| exitcode::UnknownDevice(device_id.to_string()))?
+                }
+            };
+            let sectors_arg = cmd.value_of("sectors").unwrap();
+            let sectors = sectors_arg
+                .parse::<usize>()
+                .ok()
+                .filter(|s| *s > 0)
+                .ok_or_else(|| {
+                    exitcode::InvalidArgument(format!("Invalid sectors value: {}", sectors_arg))
+                })?;
+
+            let mut access = registry.access(device)?;
+            let inspection = inspect::inspect(&mut access, device.details().size, sectors)?;
+            if json_output {
+                json::JsonFrontend::print_inspection(device.id(), &inspection);
+            } else {
+                cli::ConsoleFrontend::print_inspection(device.id(), &inspection);
+            }
+        }
+        ("resume", Some(cmd))
+            if state_dir
+                .jobs()
+                .find(cmd.value_of("device").unwrap_or(""))
+                .ok()
+                .flatten()
+                .is_some() =>
+        {
+            require_yes(cmd, json_output, quiet)?;
+            let jobs = state_dir.jobs().list()?;
+            let (job, resumable) = jobs
+                .iter()
+                .zip(state_dir.resumable(&jobs))
+                .find(|(j, _)| Some(j.id.as_str()) == cmd.value_of("device"))
+                .ok_or_else(|| anyhow!("Unknown job {}", cmd.value_of("device").unwrap()))?;
+            if job.effective_status() == actions::state::JobStatus::Running {
+                return Err(anyhow!("Job {} is still running (pid {})", job.id, job.pid));
+            }
+            if !resumable {
+                return Err(anyhow!(
+                    "Job {} can't be resumed, it's either complete or there's no checkpoint left",
+                    job.id
+                ));
+            }
+            let checkpoint = state_dir
+                .checkpoints()
+                .find(&job.fingerprint)?
+                .ok_or_else(|| anyhow!("No checkpoint of job {}", job.id))?;
+
+            let image = match job
+                .fingerprint
+                .id
+                .strip_prefix(IMAGE_FILE_SCHEME)
+                .and_then(|p| p.strip_prefix("://"))
+            {
+                Some(path) => Some(ImageFile::open(path)?),
+                None => None,
+            };
+            let device: &dyn StorageRef = match &image {
+                Some(image) => image,
+                None => storage_devices
+                    .iter()
+                    .find(|d| d.id() == job.fingerprint.id)
+                    .ok_or_else(|| exitcode::UnknownDevice(job.fingerprint.id.clone()))?,
+            };
+            let device_id = device.id();
+            if device.details().size != job.fingerprint.size {
+                return Err(anyhow!(
+                    "The size of {} has changed since job {} was interrupted",
+                    device_id,
+                    job.id
+                ));
+            }
+            let attached = DeviceFingerprint::new(device_id, device.details().size)
+                .with_identity(device.details());
+            if !job.fingerprint.matches(&attached) {
+                return Err(anyhow!(
+                    "{} is not the drive job {} was interrupted on, its serial number or model differ",
+                    device_id,
+                    job.id
+                ));
+            }
+
+            let (mut task, mut state) = checkpoint.restore()?;
+            task.trim_supported = device.details().trim_supported;
+            state.retries_left = config::DEFAULT_RETRIES;
+            state.verify_retries_left = config::DEFAULT_RETRIES;
+
+            registry.prepare_for_wipe(device, false, false)?;
+            let mut access = registry.access(device)?;
+            if let Some(g) = access.geometry().ok().flatten() {
+                task.align_to(&g)?;
+            }
+
+            let session: Box<dyn WipeEventReceiver> = if json_output {
+                Box::new(json::JsonFrontend::wipe_session(device_id))
+            } else {
+                Box::new(
+                    frontend
+                        .wipe_session(device_id, device.details(), cmd.is_present("yes"), None)
+                        .with_short_id(ids.get_short(device_id).unwrap_or(&device_id.to_string())),
+                )
+            };
+            let recorder = actions::state::JobRecorder::resume(state_dir.clone(), job.clone())
+                .with_report(device.details());
+            let mut session = FanOutReceiver::new(vec![session]).with(Box::new(recorder));
+            let mut checkpointer = Checkpointer::new(
+                state_dir.checkpoints(),
+                job.fingerprint.clone(),
+                DEFAULT_FLUSH_INTERVAL,
+            );
+            task.cancellation.cancel_on_interrupt();
+            let outcome =
+                task.run_with_checkpoints(&mut access, &mut state, &mut session, &mut checkpointer);
+            if !outcome.is_success() {
+                return Err(exitcode::Exit(exitcode::of_outcome(outcome)).into());
+            }
+            if outcome == WipeOutcome::CompletedWithBadBlocks {
+                return Err(exitcode::Exit(exitcode::BAD_BLOCKS_SKIPPED).into());
+            }
+        }
+        ("resume", Some(cmd)) => {
+            require_yes(cmd, json_output, quiet)?;
+            let device_id = cmd
+                .value_of("device")
+                .and_then(|id| ids.get(id))
+                .ok_or_else(|| {
+                    exitcode::UnknownDevice(cmd.value_of("device").unwrap_or("").to_string())
+                })?;
+            let device = storage_devices
+                .iter()
+                .find(|d| d.id() == device_id)
+                .ok_or_else(|| exitcode::UnknownDevice(device_id.to_string()))?;
+            let scheme_id = cmd.value_of("scheme").unwrap();
+            if is_targeted(scheme_id) {
+                return Err(anyhow!("The {} scheme can't be resumed", scheme_id));
+            }
+            let scheme = schemes
+                .find(scheme_id)
+                .ok_or(anyhow!("Unknown scheme {}", scheme_id))?
+                .clone();
+            let verify_arg = cmd.value_of("verify").unwrap();
+            let verification = ui::args::parse_verify(verify_arg).context(
+                exitcode::InvalidArgument(format!("Invalid verify value: {}", verify_arg)),
+            )?;
+            let block_size = match cmd.value_of("blocksize") {
+                Some(b) => ui::args::parse_block_size(b).context(exitcode::InvalidArgument(
+                    format!("Invalid blocksize value: {}", b),
+                ))?,
+                None => device.details().preferred_block_size(),
+            };
+
+            let mut task = WipeTask::new(scheme, verification, device.details().size, block_size)?;
+            task.trim_supported = device.details().trim_supported;
+
+            registry.prepare_for_wipe(device, false, false)?;
+            let mut access = registry.access(device)?;
+            if let Some(g) = access.geometry().ok().flatten() {
+                task.align_to(&g)?;
+            }
+            let mut state = resume::detect_progress(&mut access, &task)
+                .context(format!("Unable to detect the progress of {}", scheme_id))?;
+            state.retries_left = config::DEFAULT_RETRIES;
+            state.verify_retries_left = config::DEFAULT_RETRIES;
+            if json_output {
+                json::JsonFrontend::print_detected_progress(&state);
+            } else {
+                cli::ConsoleFrontend::print_detected_progress(&task, &state);
+            }
+
+            let mut session: Box<dyn WipeEventReceiver> = if json_output {
+                Box::new(json::JsonFrontend::wipe_session(device_id))
+            } else {
+                Box::new(
+                    frontend
+                        .wipe_session(device_id, device.details(), cmd.is_present("yes"), None)
+                        .with_short_id(ids.get_short(device_id).unwrap_or(&device_id.to_string())),
+                )
+            };
+            let mut checkpointer = Checkpointer::new(
+                state_dir.checkpoints(),
+                DeviceFingerprint::new(device_id, device.details().size)
+                    .with_identity(device.details()),
+                DEFAULT_FLUSH_INTERVAL,
+            );
+            task.cancellation.cancel_on_interrupt();
+            let outcome = task.run_with_checkpoints(
+                &mut access,
+                &mut state,
+                session.as_mut(),
+                &mut checkpointer,
+            );
+            if outcome != WipeOutcome::Completed {
+                return Err(exitcode::Exit(exitcode::of_outcome(outcome)).into());
+            }
+        }
+        ("attach", Some(cmd)) => {
+            let device_id = cmd
+                .value_of("device")
+                .and_then(|id| ids.get(id))
+                .ok_or_else(|| {
+                    exitcode::UnknownDevice(cmd.value_of("device").unwrap_or("").to_string())
+                })?;
+            #[cfg(unix)]
+            control::attach(device_id)?;
+            #[cfg(not(unix))]
+            return Err(anyhow!(
+                "Attaching to {} is not supported on this platform",
+                device_id
+            ));
+        }
+        ("soak", Some(cmd)) => {
+            let device_id = cmd
+                .value_of("device")
+                .and_then(|id| ids.get(id))
+                .ok_or_else(|| {
+                    exitcode::UnknownDevice(cmd.value_of("device").unwrap_or("").to_string())
+                })?;
+            let device = storage_devices
+                .iter()
+                .find(|d| d.id() == device_id)
+                .ok_or_else(|| exitcode::UnknownDevice(device_id.to_string()))?;
+
+            let hours: f64 = cmd
+                .value_of("hours")
+                .unwrap()
+                .parse()
+                .context(exitcode::InvalidArgument("Invalid hours value".to_string()))?;
+            if hours.is_nan() || hours <= 0.0 {
+                return Err(anyhow!("Invalid hours value"));
+            }
+            let block_size_arg = cmd.value_of("blocksize").unwrap();
+            let block_size = ui::args::parse_block_size(block_size_arg).context(
+                exitcode::InvalidArgument(format!("Invalid blocksize value: {}", block_size_arg)),
+            )?;
+
+            let mut task = soak::SoakTask::new(
+                std::time::Duration::from_secs_f64(hours * 60.0 * 60.0),
+                device.details().size,
+                block_size,
+            );
+
+            if let Some(r) = cmd.value_of("write-region") {
+                let region = match ui::args::parse_ranges(r, device.details().size)
+                    .and_then(|ranges| {
+                        WipeRange::normalize(ranges, device.details().size, block_size)
+                    })
+                    .context(exitcode::InvalidArgument(format!(
+                        "Invalid write-region value: {}",
+                        r
+                    )))?[..]
+                {
+                    [region] => region,
+                    _ => return Err(anyhow!("Write region must be a single range")),
+                };
+                require_yes(cmd, json_output, quiet)?;
+                task.write_region = Some(region);
+            }
+
+            let sensor_id = device_id.to_string();
+            let temperature = Box::new(move || System::temperature(&sensor_id));
+            let mut session: Box<dyn soak::SoakEventReceiver> = if json_output {
+                Box::new(json::JsonFrontend::soak_session(device_id, temperature))
+            } else {
+                Box::new(frontend.soak_session(device_id, cmd.is_present("yes"), temperature))
+            };
+
+            let mut access = registry.access(device)?;
+            if !task.run(&mut access, session.as_mut()) {
+                return Err(exitcode::Exit(exitcode::FAILED).into());
+            }
+        }
+        ("wipe-all", Some(cmd)) => {
+            require_yes(cmd, json_output, quiet)?;
+
+            let (selector, batch_wipe) = batch_settings(cmd, &config, &schemes)?;
+            let parallel_arg = cmd.value_of("parallel").unwrap();
+            let parallel: usize =
+                parallel_arg
+                    .parse()
+                    .ok()
+                    .filter(|p| *p > 0)
+                    .ok_or_else(|| {
+                        exitcode::InvalidArgument(format!(
+                            "Invalid parallel value: {}",
+                            parallel_arg
+                        ))
+                    })?;
+
+            if cmd.is_present("mirror") && cmd.occurrences_of("parallel") > 0 {
+                return Err(exitcode::InvalidArgument(
+                    "--parallel can't be used with --mirror".to_string(),
+                )
+                .into());
+            }
+
+            let selected = selector.select(&storage_devices);
+            if selected.is_empty() {
+                return Err(anyhow!("No devices match the selection"));
+            }
+            if !json_output
+                && !cli::ConsoleFrontend::confirm_batch(&selected, cmd.is_present("yes"))
+            {
+                println!("Aborted.");
+                return Ok(());
+            }
+            let device_ids: Vec<String> = selected.iter().map(|d| d.id().to_string()).collect();
+
+            batch_wipe.cancellation.cancel_on_interrupt();
+            let results = if cmd.is_present("mirror") {
+                let session_id = device_ids.join(", ");
+                batch_wipe.run_mirrored(
+                    &device_ids,
+                    batch_session(&session_id, json_output).as_mut(),
+                )?
+            } else {
+                batch::run_batch(&device_ids, parallel, |device_id| {
+                    batch_wipe.run(device_id, batch_session(device_id, json_output).as_mut())
+                })
+            };
+
+            if json_output {
+                json::JsonFrontend::print_batch_results(&results);
+            } else {
+                cli::ConsoleFrontend::print_batch_results(&results);
+            }
+
+            // the first failure decides the exit code, skipped bad blocks only if nothing failed
+            let failed = results
+                .iter()
+                .find(|r| !r.outcome.is_success() || r.error.is_some());
+            match failed {
+                Some(r) if r.outcome.is_success() => {
+                    return Err(exitcode::Exit(exitcode::FAILED).into())
+                }
+                Some(r) => return Err(exitcode::Exit(exitcode::of_outcome(r.outcome)).into()),
+                None if results
+                    .iter()
+                    .any(|r| r.outcome == WipeOutcome::CompletedWithBadBlocks) =>
+                {
+                    return Err(exitcode::Exit(exitcode::BAD_BLOCKS_SKIPPED).into())
+                }
+                None => {}
+            }
+        }
+        ("watch", Some(cmd)) => {
+            let (selector, batch_wipe) = batch_settings(cmd, &config, &schemes)?;
+            let auto = cmd.is_present("auto");
+            let selector = if auto {
+                selector.removable_by_default()
+            } else {
+                selector
+            };
+            if auto
+                && selector.storage_type != Some(StorageType::Removable)
+                && !cmd.is_present("yes")
+            {
+                return Err(exitcode::InvalidArgument(
+                    "Wiping devices which aren't removable as soon as they are attached requires --yes"
+                        .to_string(),
+                )
+                .into());
+            }
+            let cancellation = batch_wipe.cancellation.clone();
+            cancellation.cancel_on_interrupt();
+
+            let mut monitor = DeviceMonitor::new();
+            let mut watcher = batch::AttachWatcher::new(selector, &storage_devices);
+            if !json_output {
+                cli::ConsoleFrontend::print_watching(auto, monitor.is_notified());
+            }
+
+            let (results, finished) = std::sync::mpsc::channel();
+            let mut running = 0;
+            while !cancellation.is_cancelled() {
+                monitor.wait(WATCH_POLL_INTERVAL);
+
+                for result in finished.try_iter() {
+                    running -= 1;
+                    print_watch_result(&result, json_output);
+                }
+
+                let devices = match registry.devices() {
+                    Ok(d) => d,
+                    Err(err) => {
+                        eprintln!("Unable to enumerate storage devices. {:#}", err);
+                        continue;
+                    }
+                };
+                for device_id in watcher.poll(&devices) {
+                    if json_output {
+                        json::JsonFrontend::print_device_attached(&device_id, auto);
+                    } else {
+                        cli::ConsoleFrontend::print_device_attached(&device_id, auto);
+                    }
+                    if !auto {
+                        continue;
+                    }
+
+                    let batch_wipe = batch_wipe.clone();
+                    let results = results.clone();
+                    running += 1;
+                    std::thread::spawn(move || {
+                        let started = std::time::Instant::now();
+                        let result = batch_wipe
+                            .run(&device_id, batch_session(&device_id, json_output).as_mut());
+                        let _ = results.send(batch::BatchResult::new(&device_id, started, result));
+                    });
+                }
+            }
+
+            // the running wipes see the cancellation and stop after the current block
+            for result in finished.iter().take(running) {
+                print_watch_result(&result, json_output);
+            }
+            return Err(exitcode::Exit(INTERRUPTED_EXIT_CODE).into());
+        }
+        ("wipe-free", Some(cmd)) => {
+            require_yes(cmd, json_output, quiet)?;
+            let scheme_id = cmd.value_of("scheme").unwrap();
+            if is_targeted(scheme_id) {
+                return Err(anyhow!(
+                    "The {} scheme can't be used for free space",
+                    scheme_id
+                ));
+            }
+            let scheme = schemes
+                .find(scheme_id)
+                .ok_or(anyhow!("Unknown scheme {}", scheme_id))?
+                .clone();
+            let block_size_arg = cmd.value_of("blocksize").unwrap();
+            let block_size = ui::args::parse_block_size(block_size_arg).context(
+                exitcode::InvalidArgument(format!("Invalid blocksize value: {}", block_size_arg)),
+            )?;
+
+            let task =
+                freespace::FreeSpaceTask::new(scheme, cmd.value_of("path").unwrap(), block_size)?;
+            let mut session: Box<dyn freespace::FreeSpaceEventReceiver> = if json_output {
+                Box::new(json::JsonFreeSpaceSession)
+            } else {
+                Box::new(frontend.free_space_session(cmd.is_present("yes")))
+            };
+            if !task.run(session.as_mut()) {
+                return Err(exitcode::Exit(exitcode::FAILED).into());
+            }
+        }
+        ("checkpoints", Some(cmd)) => match cmd.subcommand() {
+            ("list", _) => {
+                let checkpoints = state_dir.checkpoints();
+                cli::ConsoleFrontend::print_checkpoints(&checkpoints.list()?);
+            }
+            ("prune", Some(prune_cmd)) => {
+                let max_age_days: u64 = prune_cmd.value_of("max-age").unwrap().parse().context(
+                    exitcode::InvalidArgument("Invalid max-age value".to_string()),
+                )?;
+                let keep: usize = prune_cmd
+                    .value_of("keep")
+                    .unwrap()
+                    .parse()
+                    .context(exitcode::InvalidArgument("Invalid keep value".to_string()))?;
+
+                let checkpoints = state_dir.checkpoints();
+                let pruned = checkpoints.prune(&RetentionPolicy {
+                    max_age: Some(std::time::Duration::from_secs(max_age_days * 24 * 60 * 60)),
+                    max_count: Some(keep),
+                })?;
+                cli::ConsoleFrontend::print_unreadable_checkpoints(&pruned.unreadable);
+                println!("Removed {} checkpoint(s).", pruned.removed);
+            }
+            _ => unreachable!(),
+        },
+        _ => {
+            println!("{}", app.usage());
+            return Err(exitcode::Exit(exitcode::INVALID_ARGUMENTS).into());
+        }
+    }
+
+    Ok(())
+}
+
+/// JSON output and the quiet mode don't show the confirmation, so it has to be given up
+/// front.
+fn require_yes(cmd: &clap::ArgMatches, json_output: bool, quiet: bool) -> Result<()> {
+    if (json_output || quiet) && !cmd.is_present("yes") {
+        return Err(
+            exitcode::InvalidArgument("JSON output and --quiet require --yes".to_string()).into(),
+        );
+    }
+    Ok(())
+}
+
+/// Name of the scheme a resumed task runs, or its description if it's not in the repo.
+fn resumed_scheme_name(schemes: &SchemeRepo, scheme: &Scheme) -> String {
+    let info = scheme.describe("");
+    schemes
+        .all()
+        .iter()
+        .find(|(_, s)| s.describe("") == info)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| scheme.description.clone())
+}
+
+/// Hides the HPA again when the wipe is over, however it ends, after it was unlocked for the
+/// wipe. Otherwise the drive keeps it removed until it's powered off.
+struct HiddenAreaGuard<'a> {
+    hidden: hidden::HiddenAreas,
+    device_id: &'a str,
+    json_output: bool,
+}
+
+impl Drop for HiddenAreaGuard<'_> {
+    fn drop(&mut self) {
+        match self.hidden.restore(self.device_id) {
+            Ok(()) if self.json_output => {
+                json::JsonFrontend::print_hidden_areas_restored(&self.hidden)
+            }
+            Ok(()) => cli::ConsoleFrontend::print_hidden_areas_restored(&self.hidden),
+            Err(err) => eprintln!(
+                "{:#}. The HPA is hidden again when the drive is powered off.",
+                err
+            ),
+        }
+    }
+}
+
+/// Device selection and wipe settings of `wipe-all` and `watch`, the defaults come from
+/// the config.
+fn batch_settings(
+    cmd: &clap::ArgMatches,
+    config: &config::Config,
+    schemes: &SchemeRepo,
+) -> Result<(batch::DeviceSelector, batch::BatchWipe)> {
+    let parse_size = |name: &str| {
+        cmd.value_of(name)
+            .map(|s| {
+                ui::args::parse_size(s).context(exitcode::InvalidArgument(format!(
+                    "Invalid {} value: {}",
+                    name, s
+                )))
+            })
+            .transpose()
+    };
+    let selector = batch::DeviceSelector {
+        storage_type: cmd.value_of("type").map(|t| t.parse()).transpose()?,
+        min_size: parse_size("min-size")?,
+        max_size: parse_size("max-size")?,
+    };
+
+    let scheme_id = config::Config::value_of(cmd, "scheme", &config.scheme).unwrap();
+    if is_targeted(scheme_id) {
+        return Err(anyhow!(
+            "The {} scheme can only be applied to a single device",
+            scheme_id
+        ));
+    }
+    let scheme = schemes
+        .find(scheme_id)
+        .ok_or(anyhow!("Unknown scheme {}", scheme_id))?
+        .clone();
+    let verify_arg = config::Config::value_of(cmd, "verify", &config.verify).unwrap();
+    let verification = ui::args::parse_verify(verify_arg).context(exitcode::InvalidArgument(
+        format!("Invalid verify value: {}", verify_arg),
+    ))?;
+    let block_size = config::Config::value_of(cmd, "blocksize", &config.blocksize)
+        .map(|b| {
+            ui::args::parse_block_size(b).context(exitcode::InvalidArgument(format!(
+                "Invalid blocksize value: {}",
+                b
+            )))
+        })
+        .transpose()?;
+    let retries: u32 = match config.retries {
+        Some(r) if cmd.occurrences_of("retries") == 0 => r,
+        _ => cmd
+            .value_of("retries")
+            .unwrap()
+            .parse()
+            .context(exitcode::InvalidArgument(
+                "Invalid retries number value".to_string(),
+            ))?,
+    };
+    Ok((
+        selector,
+        batch::BatchWipe {
+            scheme,
+            verify: verification,
+            block_size,
+            retries,
+            dry_run: cmd.is_present("dry-run"),
+            unmount: cmd.is_present("unmount"),
+            filter: config.device_filter()?,
+            cancellation: CancellationToken::new(),
+        },
+    ))
+}
+
+fn batch_session(device_id: &str, json_output: bool) -> Box<dyn WipeEventReceiver> {
+    if json_output {
+        Box::new(json::JsonFrontend::wipe_session(device_id))
+    } else {
+        Box::new(cli::ConsoleFrontend::batch_session(device_id))
+    }
+}
+
+fn print_watch_result(result: &batch::BatchResult, json_output: bool) {
+    if json_output {
+        json::JsonFrontend::print_batch_results(std::slice::from_ref(result));
+    } else {
+        cli::ConsoleFrontend::print_batch_results(std::slice::from_ref(result));
+    }
+}
+
+/// Selection and wipe settings shared by `wipe-all` and `watch`.
+fn batch_args<'a>(
+    cmd: App<'a, 'a>,
+    scheme_keys: &'a [&'a str],
+    default_retries: &'a str,
+) -> App<'a, 'a> {
+    cmd.arg(
+        Arg::with_name("type")
+            .long("type")
+            .takes_value(true)
+            .possible_values(&[
+                "removable",
+                "fixed",
+                "partition",
+                "cd",
+                "network",
+                "raid",
+                "other",
+            ])
+            .help("Only devices of this type [default: all drives, without their partitions]"),
+    )
+    .arg(
+        Arg::with_name("min-size")
+            .long("min-size")
+            .takes_value(true)
+            .value_name("SIZE")
+            .help("Only devices of at least this size (e.g. 16g)"),
+    )
+    .arg(
+        Arg::with_name("max-size")
+            .long("max-size")
+            .takes_value(true)
+            .value_name("SIZE")
+            .help("Only devices of at most this size"),
+    )
+    .arg(
+        Arg::with_name("scheme")
+            .long("scheme")
+            .short("s")
+            .takes_value(true)
+            .possible_values(scheme_keys)
+            .default_value(config::DEFAULT_SCHEME)
+            .help("Data sanitization scheme"),
+    )
+    .arg(
+        Arg::with_name("verify")
+            .long("verify")
+            .short("v")
+            .takes_value(true)
+            .default_value(config::DEFAULT_VERIFY)
+            .value_name("no|last|all|sample:PERCENT")
+            .help("Verify after completion (sample checks only a part of the blocks)"),
+    )
+    .arg(
+        Arg::with_name("blocksize")
+            .long("blocksize")
+            .short("b")
+            .takes_value(true)
+            .help("Block size [default: 4m for NVMe drives, 1m for others]"),
+    )
+    .arg(
+        Arg::with_name("retries")
+            .long("retries")
+            .short("r")
+            .takes_value(true)
+            .default_value(default_retries)
+            .help("Maximum number of retries after I/O errors"),
+    )
+    .arg(
+        Arg::with_name("dry-run")
+            .long("dry-run")
+            .help("Go through the wipes without writing anything"),
+    )
+    .arg(
+        Arg::with_name("unmount")
+            .long("unmount")
+            .conflicts_with("dry-run")
+            .help("Unmount the filesystems on the devices before wiping"),
+    )
+}
+
+fn print_help(mut cli: App, topic: Option<&str>) -> Result<()> {
+    match topic {
+        None => {
+            cli.print_help()?;
+            println!();
+        }
+        Some(t) => match examples::find_topic(t) {
+            Some(topic) => cli::ConsoleFrontend::print_topic(topic, examples::Platform::current()),
+            None => match cli.get_matches_from_safe(vec!["lethe", t, "--help"]) {
+                Err(err) if err.kind == clap::ErrorKind::HelpDisplayed => err.exit(),
+                _ => return Err(anyhow!("Unknown command or help topic {}", t)),
+            },
+        },
+    }
     Ok(())
 }
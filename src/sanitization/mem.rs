@@ -1,34 +1,220 @@
-use std::ptr::slice_from_raw_parts_mut;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::ptr::{slice_from_raw_parts, slice_from_raw_parts_mut};
+use std::sync::atomic::{compiler_fence, AtomicBool, Ordering};
+use std::sync::Once;
 
+static LOCK_BUFFERS: AtomicBool = AtomicBool::new(false);
+static LOCK_FAILURE: Once = Once::new();
+
+/// Maximum number of released buffers kept for reuse.
+const MAX_POOLED_BUFFERS: usize = 4;
+/// Maximum total size of the released buffers kept for reuse.
+const MAX_POOLED_BYTES: usize = 256 * 1024 * 1024;
+
+thread_local! {
+    static POOL: RefCell<BufferPool> = RefCell::new(BufferPool::default());
+}
+
+/// Keeps the buffers allocated afterwards in RAM so the data never reaches the swap.
+pub fn set_memory_locking(enabled: bool) {
+    LOCK_BUFFERS.store(enabled, Ordering::SeqCst);
+}
+
+/// Overwrites the data with zeroes in a way the compiler can't optimize away.
+pub fn zeroize(data: &mut [u8]) {
+    for b in data.iter_mut() {
+        unsafe { std::ptr::write_volatile(b, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+fn lock_memory(ptr: *mut u8, size: usize) -> std::io::Result<()> {
+    match unsafe { libc::mlock(ptr as *const libc::c_void, size) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(unix)]
+fn unlock_memory(ptr: *mut u8, size: usize) {
+    unsafe { libc::munlock(ptr as *const libc::c_void, size) };
+}
+
+#[cfg(windows)]
+fn lock_memory(ptr: *mut u8, size: usize) -> std::io::Result<()> {
+    match unsafe { winapi::um::memoryapi::VirtualLock(ptr as _, size) } {
+        0 => Err(std::io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(windows)]
+fn unlock_memory(ptr: *mut u8, size: usize) {
+    unsafe { winapi::um::memoryapi::VirtualUnlock(ptr as _, size) };
+}
+
+/// Counters of the buffer pool of the current thread.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct PoolStats {
+    /// Buffers allocated from the system.
+    pub allocations: u64,
+    /// Buffers taken from the pool instead.
+    pub reuses: u64,
+    /// Size of the buffers waiting in the pool.
+    pub pooled_bytes: usize,
+}
+
+/// Counters of the buffer pool of the current thread, which keeps a few of the released
+/// buffers so the following stages and verifications don't allocate them again.
+pub fn pool_stats() -> PoolStats {
+    POOL.with(|p| p.borrow().stats)
+}
+
+#[derive(Default)]
+struct BufferPool {
+    buffers: Vec<Allocation>,
+    stats: PoolStats,
+}
+
+impl BufferPool {
+    fn take(&mut self, layout: std::alloc::Layout) -> Option<Allocation> {
+        let i = self.buffers.iter().position(|b| b.layout == layout)?;
+        self.stats.reuses += 1;
+        self.stats.pooled_bytes -= layout.size();
+        Some(self.buffers.swap_remove(i))
+    }
+
+    /// Keeps the memory if there is still room, returns false otherwise.
+    fn put(&mut self, allocation: Allocation) -> bool {
+        let size = allocation.layout.size();
+        if self.buffers.len() >= MAX_POOLED_BUFFERS
+            || self.stats.pooled_bytes + size > MAX_POOLED_BYTES
+        {
+            return false;
+        }
+        self.stats.pooled_bytes += size;
+        self.buffers.push(allocation);
+        true
+    }
+}
+
+impl Drop for BufferPool {
+    fn drop(&mut self) {
+        for a in self.buffers.drain(..) {
+            a.release();
+        }
+    }
+}
+
+/// Memory of a buffer, owned by either the buffer or the pool.
+#[derive(Clone, Copy)]
+struct Allocation {
+    ptr: *mut u8,
+    layout: std::alloc::Layout,
+    locked: bool,
+}
+
+impl Allocation {
+    fn new(layout: std::alloc::Layout) -> Self {
+        let size = layout.size();
+        unsafe {
+            let ptr = std::alloc::alloc(layout);
+
+            let locked = LOCK_BUFFERS.load(Ordering::SeqCst)
+                && match lock_memory(ptr, size) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        LOCK_FAILURE.call_once(|| {
+                            eprintln!(
+                                "Unable to lock buffer memory ({}). Check the locked memory limit.",
+                                err
+                            )
+                        });
+                        false
+                    }
+                };
+
+            Allocation {
+                ptr,
+                layout,
+                locked,
+            }
+        }
+    }
+
+    fn release(self) {
+        if self.locked {
+            unlock_memory(self.ptr, self.layout.size());
+        }
+        unsafe { std::alloc::dealloc(self.ptr, self.layout) }
+    }
+}
+
+/// Memory block with the given alignment as required for direct I/O. Released buffers are
+/// zeroed and go back to the pool of the thread, the content of a new one isn't initialized.
 pub(crate) struct AlignedBuffer {
     ptr: *mut u8,
     layout: std::alloc::Layout,
+    locked: bool,
 }
 
+// the buffer owns its allocation, it can be filled on one thread and used on another
+unsafe impl Send for AlignedBuffer {}
+// the memory is only changed through a mutable reference
+unsafe impl Sync for AlignedBuffer {}
+
 impl AlignedBuffer {
     pub(crate) fn new(size: usize, align: usize) -> Self {
-        unsafe {
-            let buf_layout = std::alloc::Layout::from_size_align_unchecked(size, align);
-            let buf_ptr = std::alloc::alloc(buf_layout);
-            AlignedBuffer {
-                ptr: buf_ptr,
-                layout: buf_layout,
-            }
+        let layout = std::alloc::Layout::from_size_align(size, align).unwrap();
+        let pooled = POOL
+            .try_with(|p| {
+                let mut pool = p.borrow_mut();
+                let pooled = pool.take(layout);
+                if pooled.is_none() {
+                    pool.stats.allocations += 1;
+                }
+                pooled
+            })
+            .ok()
+            .flatten();
+        let a = pooled.unwrap_or_else(|| Allocation::new(layout));
+        AlignedBuffer {
+            ptr: a.ptr,
+            layout: a.layout,
+            locked: a.locked,
         }
     }
 
-    pub(crate) fn fill(&mut self, value: u8) -> () {
+    pub(crate) fn fill(&mut self, value: u8) {
         unsafe { self.ptr.write_bytes(value, self.layout.size()) }
     }
 
-    pub(crate) fn as_mut_slice(&self) -> &mut [u8] {
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        unsafe { &*slice_from_raw_parts(self.ptr, self.layout.size()) }
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [u8] {
         unsafe { &mut *slice_from_raw_parts_mut(self.ptr, self.layout.size()) }
     }
 }
 
 impl Drop for AlignedBuffer {
     fn drop(&mut self) {
-        unsafe { std::alloc::dealloc(self.ptr as *mut u8, self.layout) }
+        zeroize(self.as_mut_slice());
+        let allocation = Allocation {
+            ptr: self.ptr,
+            layout: self.layout,
+            locked: self.locked,
+        };
+        // the pool is gone when the thread is exiting
+        let pooled = POOL
+            .try_with(|p| p.borrow_mut().put(allocation))
+            .unwrap_or(false);
+        if !pooled {
+            allocation.release();
+        }
     }
 }
 
@@ -43,7 +229,7 @@ mod test {
 
         let buf = AlignedBuffer::new(size, align);
 
-        assert_eq!(buf.as_mut_slice().len(), size);
+        assert_eq!(buf.as_slice().len(), size);
         assert_eq!(buf.ptr as usize % align, 0);
     }
 
@@ -52,9 +238,41 @@ mod test {
         let mut buf = AlignedBuffer::new(1024, 1024);
 
         buf.fill(0xff);
-        assert_eq!(buf.as_mut_slice().iter().filter(|x| **x != 0xff).count(), 0);
+        assert_eq!(buf.as_slice().iter().filter(|x| **x != 0xff).count(), 0);
+
+        buf.fill(0x11);
+        assert_eq!(buf.as_slice().iter().filter(|x| **x != 0x11).count(), 0);
+    }
+
+    #[test]
+    fn test_released_buffers_are_reused() {
+        let before = pool_stats();
+        let mut buf = AlignedBuffer::new(8192, 4096);
+        buf.fill(0x11);
+        let ptr = buf.ptr;
+        drop(buf);
+
+        let buf = AlignedBuffer::new(8192, 4096);
+        assert_eq!(buf.ptr, ptr);
+        assert!(buf.as_slice().iter().all(|x| *x == 0));
+        let other = AlignedBuffer::new(8192, 8192);
+        assert_ne!(other.ptr, ptr);
+
+        let after = pool_stats();
+        assert_eq!(after.reuses - before.reuses, 1);
+        assert_eq!(after.allocations - before.allocations, 2);
+    }
+
+    #[test]
+    fn test_zeroize() {
+        let mut data = vec![0xaa; 100];
+        zeroize(&mut data);
+        assert!(data.iter().all(|x| *x == 0));
 
+        set_memory_locking(true);
+        let mut buf = AlignedBuffer::new(4096, 4096);
+        set_memory_locking(false);
         buf.fill(0x11);
-        assert_eq!(buf.as_mut_slice().iter().filter(|x| **x != 0x11).count(), 0);
+        assert_eq!(buf.as_slice()[4095], 0x11);
     }
 }
@@ -1,16 +1,83 @@
+//! Data sanitization schemes.
+//!
+//! A [`Scheme`] is a sequence of [`Stage`]s, each producing a stream of data to be written
+//! over the whole device. [`SchemeRepo`] holds the predefined schemes.
+
 pub mod stage;
 pub use stage::*;
 
 pub mod mem;
 
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 
-#[derive(Debug, Clone)]
+/// Scheme which is applied only to the metadata regions found by the signature prober.
+pub const METADATA_SCHEME: &str = "metadata";
+
+/// Scheme which is applied to the beginning and the end of the device along with the metadata
+/// regions found by the signature prober.
+pub const QUICK_SCHEME: &str = "quick";
+
+/// Whether the scheme only covers the regions picked after probing the device, so it can't
+/// be resumed, verified on its own or applied to anything but a single device.
+pub fn is_targeted(name: &str) -> bool {
+    name == METADATA_SCHEME || name == QUICK_SCHEME
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Scheme {
     pub description: String,
     pub stages: Vec<Stage>,
 }
 
+/// Serializable description of a scheme for rendering scheme pickers and such.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SchemeInfo {
+    pub name: String,
+    pub description: String,
+    /// Number of stages writing data, discarding doesn't count.
+    pub passes: usize,
+    pub stages: Vec<StageInfo>,
+}
+
+impl Scheme {
+    pub fn describe(&self, name: &str) -> SchemeInfo {
+        SchemeInfo {
+            name: name.to_string(),
+            description: self.description.clone(),
+            passes: self.stages.iter().filter(|s| s.is_verifiable()).count(),
+            stages: self.stages.iter().map(|s| s.describe()).collect(),
+        }
+    }
+
+    /// Replaces the seeds of all random stages with ones derived from the master seed,
+    /// so the same scheme produces byte-identical data on any machine.
+    pub fn derive_seeds(&mut self, master_seed: &[u8]) {
+        for (index, stage) in self.stages.iter_mut().enumerate() {
+            if let Stage::Random { .. } = stage {
+                *stage = Stage::random_with_seed(derive_stage_seed(master_seed, index as u32));
+            }
+        }
+    }
+}
+
+/// Seed of the stage at the given index: `SHA-256("lethe-stage-seed" || len(master) || master || index)`,
+/// with the length and the index encoded as little-endian integers.
+fn derive_stage_seed(master_seed: &[u8], index: u32) -> [u8; stage::RANDOM_SEED_SIZE] {
+    let mut digest = Sha256::new()
+        .chain(b"lethe-stage-seed")
+        .chain((master_seed.len() as u64).to_le_bytes())
+        .chain(master_seed)
+        .chain(index.to_le_bytes())
+        .finalize();
+
+    let mut seed = [0; stage::RANDOM_SEED_SIZE];
+    seed.copy_from_slice(&digest);
+    mem::zeroize(&mut digest);
+    seed
+}
+
 pub struct SchemeRepo {
     schemes: BTreeMap<&'static str, Scheme>,
 }
@@ -20,7 +87,62 @@ impl SchemeRepo {
         SchemeRepo { schemes }
     }
 
-    pub fn default() -> SchemeRepo {
+    fn gutmann_stages() -> Vec<Stage> {
+        let patterns: [&[u8]; 27] = [
+            &[0x55],
+            &[0xaa],
+            &[0x92, 0x49, 0x24],
+            &[0x49, 0x24, 0x92],
+            &[0x24, 0x92, 0x49],
+            &[0x00],
+            &[0x11],
+            &[0x22],
+            &[0x33],
+            &[0x44],
+            &[0x55],
+            &[0x66],
+            &[0x77],
+            &[0x88],
+            &[0x99],
+            &[0xaa],
+            &[0xbb],
+            &[0xcc],
+            &[0xdd],
+            &[0xee],
+            &[0xff],
+            &[0x92, 0x49, 0x24],
+            &[0x49, 0x24, 0x92],
+            &[0x24, 0x92, 0x49],
+            &[0x6d, 0xb6, 0xdb],
+            &[0xb6, 0xdb, 0x6d],
+            &[0xdb, 0x6d, 0xb6],
+        ];
+
+        let mut stages: Vec<Stage> = (0..4).map(|_| Stage::random()).collect();
+        stages.extend(patterns.iter().filter_map(|p| Stage::pattern(p)));
+        stages.extend((0..4).map(|_| Stage::random()));
+        stages
+    }
+
+    pub fn all(&self) -> &BTreeMap<&'static str, Scheme> {
+        &self.schemes
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Scheme> {
+        self.schemes.get(name)
+    }
+
+    /// Descriptions of all the schemes, ordered by name.
+    pub fn describe_all(&self) -> Vec<SchemeInfo> {
+        self.schemes
+            .iter()
+            .map(|(name, scheme)| scheme.describe(name))
+            .collect()
+    }
+}
+
+impl Default for SchemeRepo {
+    fn default() -> SchemeRepo {
         let mut schemes = BTreeMap::new();
 
         schemes.insert(
@@ -39,6 +161,33 @@ impl SchemeRepo {
             },
         );
 
+        schemes.insert(
+            "random-trim",
+            Scheme {
+                description: "Single random fill followed by discard (TRIM) on SSDs".to_string(),
+                stages: vec![Stage::random(), Stage::discard()],
+            },
+        );
+
+        schemes.insert(
+            METADATA_SCHEME,
+            Scheme {
+                description: "Partition tables and filesystem headers only (single random fill)"
+                    .to_string(),
+                stages: vec![Stage::random()],
+            },
+        );
+
+        schemes.insert(
+            QUICK_SCHEME,
+            Scheme {
+                description:
+                    "First and last megabytes and filesystem headers only (single random fill)"
+                        .to_string(),
+                stages: vec![Stage::random()],
+            },
+        );
+
         schemes.insert(
             "random2x",
             Scheme {
@@ -92,21 +241,38 @@ impl SchemeRepo {
             },
         );
 
-        Self::new(schemes)
-    }
+        schemes.insert(
+            "schneier",
+            Scheme {
+                description: "Bruce Schneier's algorithm (Bruce-7)".to_string(),
+                stages: vec![
+                    Stage::one(),
+                    Stage::zero(),
+                    Stage::random(),
+                    Stage::random(),
+                    Stage::random(),
+                    Stage::random(),
+                    Stage::random(),
+                ],
+            },
+        );
 
-    pub fn all(&self) -> &BTreeMap<&'static str, Scheme> {
-        &self.schemes
-    }
+        schemes.insert(
+            "gutmann",
+            Scheme {
+                description: "Peter Gutmann's method".to_string(),
+                stages: SchemeRepo::gutmann_stages(),
+            },
+        );
 
-    pub fn find(&self, name: &str) -> Option<&Scheme> {
-        self.schemes.get(name)
+        Self::new(schemes)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use assert_matches::*;
 
     #[test]
     fn test_scheme_find() {
@@ -117,4 +283,61 @@ mod test {
         let scheme = repo.find("random");
         assert!(scheme.is_some());
     }
+
+    #[test]
+    fn test_scheme_gutmann() {
+        let repo = SchemeRepo::default();
+        let scheme = repo.find("gutmann").unwrap();
+
+        assert_eq!(scheme.stages.len(), 35);
+        assert_matches!(scheme.stages[0], Stage::Random { .. });
+        assert_matches!(scheme.stages[6], Stage::Pattern { ref bytes } if bytes == &[0x92, 0x49, 0x24]);
+        assert_matches!(scheme.stages[24], Stage::Fill { value: 0xff });
+        assert_matches!(scheme.stages[34], Stage::Random { .. });
+    }
+
+    #[test]
+    fn test_describe_schemes() {
+        let repo = SchemeRepo::default();
+        let all = repo.describe_all();
+        assert_eq!(all.len(), repo.all().len());
+
+        let trim = all.iter().find(|s| s.name == "random-trim").unwrap();
+        assert_eq!(trim.passes, 1);
+        assert_eq!(trim.stages[1].kind, StageKind::Discard);
+        assert!(!trim.stages[1].verifiable);
+
+        let json = serde_json::to_value(repo.find("gutmann").unwrap().describe("gutmann")).unwrap();
+        assert_eq!(json["passes"], 35);
+        assert_eq!(json["stages"][0]["kind"], "random");
+        assert!(json["stages"][0].get("seed").is_none());
+        assert_eq!(
+            json["stages"][6],
+            serde_json::json!({
+                "kind": "pattern",
+                "bytes": [0x92, 0x49, 0x24],
+                "description": "fill with pattern 0x92 0x49 0x24",
+                "verifiable": true
+            })
+        );
+    }
+
+    #[test]
+    fn test_derive_seeds() {
+        let repo = SchemeRepo::default();
+        let seeds = |master: &[u8]| {
+            let mut scheme = repo.find("gutmann").unwrap().clone();
+            scheme.derive_seeds(master);
+            serde_json::to_value(&scheme.stages).unwrap()
+        };
+
+        let derived = seeds(b"master");
+        assert_eq!(derived, seeds(b"master"));
+        assert_ne!(derived, seeds(b"other"));
+        assert_ne!(derived[0], derived[1]);
+        assert_eq!(
+            derived[4],
+            serde_json::to_value(Stage::pattern(&[0x55]).unwrap()).unwrap()
+        );
+    }
 }
@@ -3,26 +3,97 @@ use rand::SeedableRng;
 pub use streaming_iterator::StreamingIterator;
 
 use super::mem::*;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread::JoinHandle;
 
-const RANDOM_SEED_SIZE: usize = 32;
+pub(crate) const RANDOM_SEED_SIZE: usize = 32;
 type RandomGenerator = rand_chacha::ChaCha8Rng;
 
-#[derive(Debug, Clone)]
+/// Number of blocks a pipelined stream generates ahead of the consumer.
+const PIPELINE_DEPTH: usize = 4;
+
+/// Set to reveal random seeds in debug output.
+const REVEAL_SEEDS_VAR: &str = "LETHE_REVEAL_SEEDS";
+
+#[derive(Serialize, Deserialize, Clone)]
 pub enum Stage {
-    Fill { value: u8 },
-    Random { seed: [u8; RANDOM_SEED_SIZE] },
+    Fill {
+        value: u8,
+    },
+    Pattern {
+        bytes: Vec<u8>,
+    },
+    Random {
+        seed: [u8; RANDOM_SEED_SIZE],
+    },
+    /// Marks all blocks as unused (TRIM / UNMAP) instead of writing to them.
+    Discard,
 }
 
 impl Display for Stage {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Stage::Fill { value } => f.write_str(&format!("fill with {:#04X}", value)),
+            Stage::Pattern { bytes } => f.write_str(&format!(
+                "fill with pattern {}",
+                bytes
+                    .iter()
+                    .map(|b| format!("{:#04X}", b))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )),
             Stage::Random { seed: _seed } => f.write_str("random fill"),
+            Stage::Discard => f.write_str("discard (TRIM)"),
         }
     }
 }
 
+impl std::fmt::Debug for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stage::Fill { value } => f.debug_struct("Fill").field("value", value).finish(),
+            Stage::Pattern { bytes } => f.debug_struct("Pattern").field("bytes", bytes).finish(),
+            Stage::Random { seed } if std::env::var_os(REVEAL_SEEDS_VAR).is_some() => {
+                f.debug_struct("Random").field("seed", seed).finish()
+            }
+            Stage::Random { .. } => f
+                .debug_struct("Random")
+                .field("seed", &format_args!("<hidden>"))
+                .finish(),
+            Stage::Discard => f.write_str("Discard"),
+        }
+    }
+}
+
+impl Drop for Stage {
+    fn drop(&mut self) {
+        if let Stage::Random { seed } = self {
+            zeroize(seed);
+        }
+    }
+}
+
+/// Serializable description of a stage, without the seeds of random stages.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StageInfo {
+    #[serde(flatten)]
+    pub kind: StageKind,
+    /// Human readable summary, e.g. "fill with 0xFF".
+    pub description: String,
+    pub verifiable: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StageKind {
+    Fill { value: u8 },
+    Pattern { bytes: Vec<u8> },
+    Random,
+    Discard,
+}
+
 struct StreamState {
     total_size: u64,
     block_size: usize,
@@ -33,9 +104,92 @@ struct StreamState {
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 enum StreamKind {
     Fill,
-    Random { gen: RandomGenerator },
+    Pattern {
+        bytes: Vec<u8>,
+        phase: Option<usize>,
+    },
+    Random {
+        gen: RandomGenerator,
+    },
+    /// Random data generated on another thread while the consumer does the I/O.
+    Pipelined(Pipeline),
+}
+
+#[derive(Debug)]
+struct Pipeline {
+    filled: Option<Receiver<AlignedBuffer>>,
+    free: Option<SyncSender<AlignedBuffer>>,
+    generator: Option<JoinHandle<()>>,
+}
+
+impl Pipeline {
+    /// Returns `None` if the thread can't be started.
+    fn start(
+        seed: &[u8; RANDOM_SEED_SIZE],
+        block_size: usize,
+        io_alignment: usize,
+        start_from: u64,
+    ) -> Option<Pipeline> {
+        let (free, free_rx) = sync_channel::<AlignedBuffer>(PIPELINE_DEPTH);
+        let (filled_tx, filled) = sync_channel(PIPELINE_DEPTH);
+        for _ in 0..PIPELINE_DEPTH {
+            free.send(AlignedBuffer::new(block_size, io_alignment))
+                .ok()?;
+        }
+
+        let mut gen = RandomGenerator::from_seed(*seed);
+        gen.set_word_pos((start_from >> 2) as u128);
+        let generator = std::thread::Builder::new()
+            .name("lethe-generator".to_string())
+            .spawn(move || {
+                // stops once the stream is dropped and the channels are closed
+                while let Ok(mut buf) = free_rx.recv() {
+                    gen.fill_bytes(buf.as_mut_slice());
+                    if filled_tx.send(buf).is_err() {
+                        break;
+                    }
+                }
+                unsafe {
+                    std::ptr::write_volatile(
+                        &mut gen,
+                        RandomGenerator::from_seed([0; RANDOM_SEED_SIZE]),
+                    )
+                };
+            })
+            .ok()?;
+
+        Some(Pipeline {
+            filled: Some(filled),
+            free: Some(free),
+            generator: Some(generator),
+        })
+    }
+
+    /// Swaps the buffer for the next generated one, the old one is filled again.
+    fn next(&mut self, buf: &mut AlignedBuffer) {
+        let next = self
+            .filled
+            .as_ref()
+            .and_then(|f| f.recv().ok())
+            .expect("The generator thread stopped");
+        let used = std::mem::replace(buf, next);
+        if let Some(free) = &self.free {
+            let _ = free.send(used);
+        }
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.free.take();
+        self.filled.take();
+        if let Some(g) = self.generator.take() {
+            let _ = g.join();
+        }
+    }
 }
 
 pub struct SanitizationStream {
@@ -56,6 +210,17 @@ impl Stage {
         Self::constant(0xff)
     }
 
+    /// Returns `None` for an empty pattern, which has nothing to fill the blocks with.
+    pub fn pattern(bytes: &[u8]) -> Option<Stage> {
+        match bytes {
+            [] => None,
+            [value] => Some(Self::constant(*value)),
+            _ => Some(Stage::Pattern {
+                bytes: bytes.to_vec(),
+            }),
+        }
+    }
+
     pub fn random_with_seed(seed: [u8; RANDOM_SEED_SIZE]) -> Stage {
         Stage::Random { seed }
     }
@@ -63,26 +228,77 @@ impl Stage {
     pub fn random() -> Stage {
         let mut seed: [u8; RANDOM_SEED_SIZE] = [0; RANDOM_SEED_SIZE];
         rand::thread_rng().fill_bytes(&mut seed[..]);
-        Stage::random_with_seed(seed)
+        let stage = Stage::random_with_seed(seed);
+        zeroize(&mut seed);
+        stage
     }
 
+    pub fn discard() -> Stage {
+        Stage::Discard
+    }
+
+    /// Discarded blocks have no defined content so the stage can't be verified.
+    pub fn is_verifiable(&self) -> bool {
+        !matches!(self, Stage::Discard)
+    }
+
+    pub fn describe(&self) -> StageInfo {
+        let kind = match self {
+            Stage::Fill { value } => StageKind::Fill { value: *value },
+            Stage::Pattern { bytes } => StageKind::Pattern {
+                bytes: bytes.clone(),
+            },
+            Stage::Random { .. } => StageKind::Random,
+            Stage::Discard => StageKind::Discard,
+        };
+        StageInfo {
+            kind,
+            description: self.to_string(),
+            verifiable: self.is_verifiable(),
+        }
+    }
+
+    /// Data of the stage from `start_from` up to `total_size` in blocks, in buffers aligned
+    /// to `io_alignment` so they can be written to the device directly.
     pub fn stream(
         &self,
         total_size: u64,
         block_size: usize,
+        io_alignment: usize,
         start_from: u64,
     ) -> SanitizationStream {
-        let mut buf = AlignedBuffer::new(block_size, block_size);
+        let mut buf = AlignedBuffer::new(block_size, io_alignment);
 
         let kind = match self {
             Stage::Fill { value } => {
                 buf.fill(*value);
                 StreamKind::Fill
             }
+            Stage::Discard => {
+                buf.fill(0);
+                StreamKind::Fill
+            }
+            Stage::Pattern { bytes } => StreamKind::Pattern {
+                bytes: bytes.clone(),
+                phase: None,
+            },
             Stage::Random { seed } => {
-                let mut gen = RandomGenerator::from_seed(*seed);
-                gen.set_word_pos((start_from >> 2) as u128);
-                StreamKind::Random { gen }
+                // generating a few blocks isn't worth a thread
+                let pipeline = if total_size.saturating_sub(start_from)
+                    > (block_size * PIPELINE_DEPTH) as u64
+                {
+                    Pipeline::start(seed, block_size, io_alignment, start_from)
+                } else {
+                    None
+                };
+                match pipeline {
+                    Some(pipeline) => StreamKind::Pipelined(pipeline),
+                    None => {
+                        let mut gen = RandomGenerator::from_seed(*seed);
+                        gen.set_word_pos((start_from >> 2) as u128);
+                        StreamKind::Random { gen }
+                    }
+                }
             }
         };
 
@@ -98,6 +314,17 @@ impl Stage {
     }
 }
 
+impl Drop for SanitizationStream {
+    fn drop(&mut self) {
+        // replaces the generator state derived from the seed
+        if let StreamKind::Random { gen } = &mut self.kind {
+            unsafe {
+                std::ptr::write_volatile(gen, RandomGenerator::from_seed([0; RANDOM_SEED_SIZE]))
+            };
+        }
+    }
+}
+
 impl StreamingIterator for SanitizationStream {
     type Item = [u8];
 
@@ -110,7 +337,20 @@ impl StreamingIterator for SanitizationStream {
 
             match &mut self.kind {
                 StreamKind::Fill => (),
+                StreamKind::Pattern { bytes, phase } => {
+                    // the pattern is aligned to the absolute position so it stays continuous
+                    // across blocks which are not a multiple of its length
+                    let current = (self.state.position % bytes.len() as u64) as usize;
+                    if *phase != Some(current) {
+                        let buf = self.state.buf.as_mut_slice();
+                        for (i, b) in buf.iter_mut().enumerate() {
+                            *b = bytes[(current + i) % bytes.len()];
+                        }
+                        *phase = Some(current);
+                    }
+                }
                 StreamKind::Random { gen } => gen.fill_bytes(self.state.buf.as_mut_slice()),
+                StreamKind::Pipelined(pipeline) => pipeline.next(&mut self.state.buf),
             };
 
             self.state.current_block_size = chunk_size;
@@ -122,7 +362,7 @@ impl StreamingIterator for SanitizationStream {
 
     fn get(&self) -> Option<&Self::Item> {
         if !self.state.eof {
-            Some(&self.state.buf.as_mut_slice()[..self.state.current_block_size as usize])
+            Some(&self.state.buf.as_slice()[..self.state.current_block_size])
         } else {
             None
         }
@@ -132,6 +372,7 @@ impl StreamingIterator for SanitizationStream {
 #[cfg(test)]
 mod test {
     use super::*;
+    use assert_matches::*;
 
     const TEST_SIZE: u64 = 10245;
     const TEST_BLOCK: usize = 256;
@@ -150,6 +391,28 @@ mod test {
         assert_eq!(data1, data2);
     }
 
+    #[test]
+    fn test_stage_pattern_behaves() {
+        let pattern = [0x92, 0x49, 0x24];
+        let mut stage = Stage::pattern(&pattern).unwrap();
+
+        let mut data = create_test_vec();
+        fill(&mut data, &mut stage);
+
+        for (i, b) in data.iter().enumerate() {
+            assert_eq!(*b, pattern[i % pattern.len()]);
+        }
+
+        let mut stream = stage.stream(TEST_SIZE, TEST_BLOCK, TEST_BLOCK, 1000);
+        assert_eq!(stream.next().unwrap()[..3], [0x49, 0x24, 0x92]);
+    }
+
+    #[test]
+    fn test_stage_pattern_single_byte_is_constant() {
+        assert_matches!(Stage::pattern(&[0x55]), Some(Stage::Fill { value: 0x55 }));
+        assert_matches!(Stage::pattern(&[]), None);
+    }
+
     #[test]
     fn test_stage_random_behaves() {
         let mut data1 = create_test_vec();
@@ -179,6 +442,43 @@ mod test {
         assert_ne!(data3, data2);
     }
 
+    #[test]
+    fn test_stage_random_pipelined() {
+        let seed = [13; 32];
+        let mut stream =
+            Stage::random_with_seed(seed).stream(TEST_SIZE, TEST_BLOCK, TEST_BLOCK, 1024);
+        assert_matches!(stream.kind, StreamKind::Pipelined(_));
+
+        let mut gen = RandomGenerator::from_seed(seed);
+        gen.set_word_pos(1024 >> 2);
+        let mut expected = vec![0u8; TEST_BLOCK];
+        let mut position = 1024;
+        while let Some(chunk) = stream.next() {
+            gen.fill_bytes(&mut expected);
+            assert_eq!(chunk, &expected[..chunk.len()]);
+            position += chunk.len() as u64;
+        }
+        assert_eq!(position, TEST_SIZE);
+
+        let short = Stage::random_with_seed(seed).stream(
+            TEST_SIZE,
+            TEST_BLOCK,
+            TEST_BLOCK,
+            TEST_SIZE - 100,
+        );
+        assert_matches!(short.kind, StreamKind::Random { .. });
+    }
+
+    #[test]
+    fn test_stream_buffers_are_aligned() {
+        for stage in [Stage::Fill { value: 0 }, Stage::random()] {
+            let mut stream = stage.stream(1 << 20, 8192, 4096, 0);
+            while let Some(chunk) = stream.next() {
+                assert_eq!(chunk.as_ptr() as usize % 4096, 0);
+            }
+        }
+    }
+
     #[test]
     fn test_stage_random_entropy() {
         let mut data = create_test_vec();
@@ -192,12 +492,29 @@ mod test {
         assert!(stage_entropy > 0.9);
     }
 
+    #[test]
+    fn test_stage_debug_hides_seed() {
+        let stage = Stage::random_with_seed([13; 32]);
+        assert_eq!(format!("{:?}", stage), "Random { seed: <hidden> }");
+        assert_eq!(
+            format!("{:?}", Stage::constant(0xaa)),
+            "Fill { value: 170 }"
+        );
+    }
+
+    #[test]
+    fn test_stage_discard_is_not_verifiable() {
+        assert!(!Stage::discard().is_verifiable());
+        assert!(Stage::zero().is_verifiable());
+        assert_eq!(Stage::discard().to_string(), "discard (TRIM)");
+    }
+
     fn create_test_vec() -> Vec<u8> {
         (0..TEST_SIZE).map(|x| (x % 256) as u8).collect()
     }
 
-    fn fill(v: &mut Vec<u8>, stage: &mut Stage) -> () {
-        let mut stream = stage.stream(TEST_SIZE, TEST_BLOCK, 0);
+    fn fill(v: &mut [u8], stage: &mut Stage) {
+        let mut stream = stage.stream(TEST_SIZE, TEST_BLOCK, TEST_BLOCK, 0);
 
         let mut position = 0;
         while let Some(chunk) = stream.next() {
@@ -0,0 +1,249 @@
+//! Devices a registry keeps away from the user, like the boot drive of a wipe station.
+
+use crate::storage::StorageRef;
+use anyhow::Result;
+use regex::Regex;
+
+/// Selects devices by a glob (`*` and `?`) on the device ID or one of its aliases, or on
+/// the serial number or the label with the `serial:` and `label:` prefixes.
+#[derive(Debug, Clone)]
+pub enum DevicePattern {
+    Path(Regex),
+    Serial(Regex),
+    Label(Regex),
+}
+
+impl DevicePattern {
+    pub fn parse(pattern: &str) -> Result<DevicePattern> {
+        let (make, glob): (fn(Regex) -> DevicePattern, _) =
+            if let Some(serial) = pattern.strip_prefix("serial:") {
+                (DevicePattern::Serial, serial)
+            } else if let Some(label) = pattern.strip_prefix("label:") {
+                (DevicePattern::Label, label)
+            } else {
+                (DevicePattern::Path, pattern)
+            };
+
+        if glob.is_empty() {
+            return Err(anyhow!("Empty device pattern: {}", pattern));
+        }
+        Ok(make(glob_to_regex(glob)?))
+    }
+
+    pub fn matches(&self, device: &dyn StorageRef) -> bool {
+        let details = device.details();
+        match self {
+            DevicePattern::Path(r) => {
+                r.is_match(device.id()) || details.aliases.iter().any(|a| r.is_match(a))
+            }
+            DevicePattern::Serial(r) => details.serial.as_deref().is_some_and(|s| r.is_match(s)),
+            DevicePattern::Label(r) => details.label.as_deref().is_some_and(|l| r.is_match(l)),
+        }
+    }
+}
+
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Ok(Regex::new(&pattern)?)
+}
+
+/// Excluded devices, and the only allowed ones if any are given. Partitions and mappings
+/// follow the device they are on, so excluding a drive excludes all of its partitions.
+/// Exclusion wins over the allowlist.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceFilter {
+    pub exclude: Vec<DevicePattern>,
+    pub allow: Vec<DevicePattern>,
+}
+
+impl DeviceFilter {
+    pub fn is_empty(&self) -> bool {
+        self.exclude.is_empty() && self.allow.is_empty()
+    }
+
+    /// Whether the device itself matches one of the excluded patterns.
+    pub fn excludes(&self, device: &dyn StorageRef) -> bool {
+        self.exclude.iter().any(|p| p.matches(device))
+    }
+
+    /// Whether the device is on the allowlist and not excluded, an empty allowlist allows
+    /// nothing.
+    pub fn allows_explicitly(&self, device: &dyn StorageRef) -> bool {
+        !self.excludes(device) && self.allow.iter().any(|p| p.matches(device))
+    }
+
+    /// Keeps the devices which are not excluded, nor on an excluded device, and are allowed
+    /// (or on an allowed device) when there is an allowlist.
+    pub fn apply(&self, devices: Vec<Box<dyn StorageRef>>) -> Vec<Box<dyn StorageRef>> {
+        if self.is_empty() {
+            return devices;
+        }
+
+        let keep: Vec<bool> = devices
+            .iter()
+            .map(|d| {
+                let lineage = lineage(&devices, d.as_ref());
+                !lineage.iter().any(|d| self.excludes(*d))
+                    && (self.allow.is_empty()
+                        || lineage
+                            .iter()
+                            .any(|d| self.allow.iter().any(|p| p.matches(*d))))
+            })
+            .collect();
+
+        devices
+            .into_iter()
+            .zip(keep)
+            .filter(|(_, keep)| *keep)
+            .map(|(d, _)| d)
+            .collect()
+    }
+}
+
+/// The device followed by the devices it is on, as far as they are listed.
+fn lineage<'a>(
+    devices: &'a [Box<dyn StorageRef>],
+    device: &'a dyn StorageRef,
+) -> Vec<&'a dyn StorageRef> {
+    let mut lineage = vec![device];
+    let mut parent = device.details().parent.as_deref();
+    while let Some(id) = parent {
+        match devices.iter().find(|d| d.id() == id) {
+            // a loop in the parents would never end otherwise
+            Some(d) if lineage.len() <= devices.len() => {
+                lineage.push(d.as_ref());
+                parent = d.details().parent.as_deref();
+            }
+            _ => break,
+        }
+    }
+    lineage
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::platform::MockDevice;
+    use crate::storage::StorageDetails;
+
+    fn device(
+        id: &str,
+        parent: Option<&str>,
+        serial: Option<&str>,
+        label: Option<&str>,
+    ) -> Box<dyn StorageRef> {
+        Box::new(MockDevice {
+            id: id.to_string(),
+            details: StorageDetails {
+                parent: parent.map(String::from),
+                serial: serial.map(String::from),
+                label: label.map(String::from),
+                aliases: vec![format!("/dev/disk/by-id/ata-{}", serial.unwrap_or("none"))],
+                ..Default::default()
+            },
+            users: Vec::new(),
+            mounts: Vec::new(),
+        })
+    }
+
+    fn devices() -> Vec<Box<dyn StorageRef>> {
+        vec![
+            device("/dev/sda", None, Some("S4EVNF0M123"), None),
+            device("/dev/sda1", Some("/dev/sda"), None, Some("EFI")),
+            device("/dev/sda2", Some("/dev/sda"), None, Some("root")),
+            device("/dev/dm-0", Some("/dev/sda2"), None, None),
+            device("/dev/sdb", None, Some("WD-123"), None),
+            device("/dev/sdb1", Some("/dev/sdb"), None, Some("EFI")),
+            device("/dev/sdc", None, Some("WD-456"), None),
+        ]
+    }
+
+    fn filter(exclude: &[&str], allow: &[&str]) -> DeviceFilter {
+        DeviceFilter {
+            exclude: exclude
+                .iter()
+                .map(|p| DevicePattern::parse(p).unwrap())
+                .collect(),
+            allow: allow
+                .iter()
+                .map(|p| DevicePattern::parse(p).unwrap())
+                .collect(),
+        }
+    }
+
+    fn ids(filter: &DeviceFilter) -> Vec<String> {
+        filter
+            .apply(devices())
+            .iter()
+            .map(|d| d.id().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_device_pattern() {
+        let devices = devices();
+        let matches = |pattern: &str| -> Vec<&str> {
+            let pattern = DevicePattern::parse(pattern).unwrap();
+            devices
+                .iter()
+                .filter(|d| pattern.matches(d.as_ref()))
+                .map(|d| d.id())
+                .collect()
+        };
+
+        assert_eq!(matches("/dev/sda"), vec!["/dev/sda"]);
+        assert_eq!(
+            matches("/dev/sd?"),
+            vec!["/dev/sda", "/dev/sdb", "/dev/sdc"]
+        );
+        assert_eq!(
+            matches("/dev/disk/by-id/ata-WD-*"),
+            vec!["/dev/sdb", "/dev/sdc"]
+        );
+        assert_eq!(matches("serial:S4EV*"), vec!["/dev/sda"]);
+        assert_eq!(matches("label:EFI"), vec!["/dev/sda1", "/dev/sdb1"]);
+        assert!(matches("serial:/dev/sda").is_empty());
+        assert!(matches("/dev/sd.").is_empty());
+
+        assert!(DevicePattern::parse("serial:").is_err());
+        assert!(DevicePattern::parse("").is_err());
+    }
+
+    #[test]
+    fn test_device_filter() {
+        assert_eq!(ids(&DeviceFilter::default()).len(), 7);
+
+        // partitions and mappings go with their drive
+        assert_eq!(
+            ids(&filter(&["serial:S4EVNF0M123"], &[])),
+            vec!["/dev/sdb", "/dev/sdb1", "/dev/sdc"]
+        );
+        assert_eq!(
+            ids(&filter(&["label:EFI"], &[])),
+            vec!["/dev/sda", "/dev/sda2", "/dev/dm-0", "/dev/sdb", "/dev/sdc"]
+        );
+
+        assert_eq!(
+            ids(&filter(&[], &["/dev/sdb"])),
+            vec!["/dev/sdb", "/dev/sdb1"]
+        );
+        assert_eq!(
+            ids(&filter(&["label:EFI"], &["serial:WD-*"])),
+            vec!["/dev/sdb", "/dev/sdc"]
+        );
+
+        let devices = devices();
+        let sdb = devices.iter().find(|d| d.id() == "/dev/sdb").unwrap();
+        assert!(!DeviceFilter::default().allows_explicitly(sdb.as_ref()));
+        assert!(filter(&[], &["/dev/sd?"]).allows_explicitly(sdb.as_ref()));
+        assert!(!filter(&["/dev/sdb"], &["/dev/sd?"]).allows_explicitly(sdb.as_ref()));
+    }
+}
@@ -0,0 +1,143 @@
+//! Capacity an ATA drive hides from the system in a Host Protected Area (HPA) or behind a
+//! Device Configuration Overlay (DCO), which a wipe of the visible sectors doesn't reach.
+
+use crate::storage::{BusType, StorageRef, System};
+use anyhow::{Context, Result};
+
+/// Sector counts of the drive, in logical sectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HiddenAreas {
+    /// Sectors the system sees.
+    pub visible: u64,
+    /// Sectors up to the end of the HPA (READ NATIVE MAX ADDRESS).
+    pub native: u64,
+    /// Sectors up to the end of the DCO (DEVICE CONFIGURATION IDENTIFY), if the drive
+    /// supports it.
+    pub real: Option<u64>,
+}
+
+impl HiddenAreas {
+    /// Queries the sector counts from the drive with ATA commands, only ATA drives are
+    /// asked and only where the platform supports it (Linux).
+    pub fn read(device: &dyn StorageRef) -> Option<HiddenAreas> {
+        match device.details().bus_type {
+            BusType::Ata => System::hidden_areas(device.id()),
+            _ => None,
+        }
+    }
+
+    pub fn hpa_sectors(&self) -> u64 {
+        self.native.saturating_sub(self.visible)
+    }
+
+    pub fn dco_sectors(&self) -> u64 {
+        self.real
+            .map(|r| r.saturating_sub(self.native))
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hpa_sectors() == 0 && self.dco_sectors() == 0
+    }
+
+    /// Sectors the system sees after the unlock.
+    pub fn unlocked_max(&self, permanent: bool) -> u64 {
+        match self.real {
+            Some(real) if permanent && real > self.native => real,
+            _ => self.native,
+        }
+    }
+
+    /// What is left hidden after the unlock.
+    pub fn after_unlock(&self, permanent: bool) -> HiddenAreas {
+        let max = self.unlocked_max(permanent);
+        HiddenAreas {
+            visible: max,
+            native: max,
+            real: self.real,
+        }
+    }
+
+    /// Makes the hidden sectors visible. A temporary unlock only removes the HPA until the
+    /// drive is powered off, as the DCO can't be brought back once removed. A permanent one
+    /// removes the DCO too.
+    pub fn unlock(&self, device_id: &str, permanent: bool) -> Result<()> {
+        let max = self.unlocked_max(permanent);
+        if permanent && self.dco_sectors() > 0 {
+            System::restore_dco(device_id).context("Unable to remove the DCO")?;
+        }
+        if max > self.visible {
+            System::set_max_sectors(device_id, max, permanent)
+                .context("Unable to remove the HPA")?;
+        }
+        Ok(())
+    }
+
+    /// Hides the sectors of the HPA again after a temporary unlock.
+    pub fn restore(&self, device_id: &str) -> Result<()> {
+        System::set_max_sectors(device_id, self.visible, false).context("Unable to restore the HPA")
+    }
+}
+
+/// What is done with the hidden areas found on the drive before the wipe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HiddenAreaMode {
+    /// Only warns about them.
+    Report,
+    /// Removes the HPA until the wipe is done.
+    Unlock,
+    /// Removes the HPA and the DCO for good.
+    Remove,
+}
+
+impl HiddenAreaMode {
+    pub const NAMES: &'static [&'static str] = &["report", "unlock", "remove"];
+}
+
+impl std::str::FromStr for HiddenAreaMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "report" => Ok(HiddenAreaMode::Report),
+            "unlock" => Ok(HiddenAreaMode::Unlock),
+            "remove" => Ok(HiddenAreaMode::Remove),
+            _ => Err(anyhow!("Use one of {}.", HiddenAreaMode::NAMES.join(", "))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hidden_sectors() {
+        let areas = HiddenAreas {
+            visible: 900,
+            native: 950,
+            real: Some(1000),
+        };
+        assert_eq!(areas.hpa_sectors(), 50);
+        assert_eq!(areas.dco_sectors(), 50);
+        assert!(!areas.is_empty());
+        assert_eq!(areas.unlocked_max(false), 950);
+        assert_eq!(areas.unlocked_max(true), 1000);
+        assert_eq!(areas.after_unlock(false).dco_sectors(), 50);
+        assert_eq!(areas.after_unlock(false).hpa_sectors(), 0);
+        assert!(areas.after_unlock(true).is_empty());
+
+        let plain = HiddenAreas {
+            visible: 1000,
+            native: 1000,
+            real: None,
+        };
+        assert_eq!(plain.dco_sectors(), 0);
+        assert!(plain.is_empty());
+        assert_eq!(
+            "unlock".parse::<HiddenAreaMode>().unwrap(),
+            HiddenAreaMode::Unlock
+        );
+        assert!("keep".parse::<HiddenAreaMode>().is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::storage::{
+    Geometry, StorageAccess, StorageDetails, StorageError, StorageProvider, StorageRef, StorageType,
+};
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Scheme of the image file ids, like `file:///tmp/disk.img`.
+pub const IMAGE_FILE_SCHEME: &str = "file";
+
+const IMAGE_SECTOR_SIZE: u32 = 512;
+
+/// Regular file (like a disk or ISO image) wiped in place. Unlike the devices of the
+/// system, it's accessed through the page cache, which is flushed after each stage.
+#[derive(Debug, Clone)]
+pub struct ImageFile {
+    id: String,
+    path: PathBuf,
+    details: StorageDetails,
+}
+
+impl ImageFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ImageFile> {
+        let path = path.as_ref();
+        let metadata = std::fs::metadata(path)
+            .context(format!("Unable to access {}", path.to_string_lossy()))?;
+        if !metadata.is_file() {
+            return Err(anyhow!("{} is not a regular file", path.to_string_lossy()));
+        }
+        let path = path.canonicalize()?;
+        Ok(ImageFile {
+            id: format!("{}://{}", IMAGE_FILE_SCHEME, path.to_string_lossy()),
+            path,
+            details: StorageDetails {
+                size: metadata.len(),
+                block_size: IMAGE_SECTOR_SIZE as usize,
+                logical_sector_size: Some(IMAGE_SECTOR_SIZE),
+                physical_sector_size: Some(IMAGE_SECTOR_SIZE),
+                storage_type: StorageType::File,
+                ..Default::default()
+            },
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl StorageRef for ImageFile {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn details(&self) -> &StorageDetails {
+        &self.details
+    }
+}
+
+/// Opens the image files, they are given explicitly so nothing is enumerated.
+pub struct ImageFiles;
+
+impl StorageProvider for ImageFiles {
+    fn scheme(&self) -> &str {
+        IMAGE_FILE_SCHEME
+    }
+
+    fn devices(&self) -> Result<Vec<Box<dyn StorageRef>>> {
+        Ok(Vec::new())
+    }
+
+    fn access(&self, device: &dyn StorageRef) -> Result<Box<dyn StorageAccess>> {
+        let path = &device.id()[IMAGE_FILE_SCHEME.len() + 3..];
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .context(format!("Unable to open image file {}", path))?;
+        Ok(Box::new(ImageFileAccess { file }))
+    }
+}
+
+struct ImageFileAccess {
+    file: File,
+}
+
+impl StorageAccess for ImageFileAccess {
+    fn position(&mut self) -> Result<u64> {
+        Ok(self.file.stream_position().map_err(StorageError::from)?)
+    }
+
+    fn seek(&mut self, position: u64) -> Result<u64> {
+        Ok(self
+            .file
+            .seek(SeekFrom::Start(position))
+            .map_err(StorageError::from)?)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        self.file
+            .read(buffer)
+            .map_err(StorageError::from)
+            .context("Can't read from the image file")
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.file
+            .write_all(data)
+            .map_err(StorageError::from)
+            .context("Writing to the image file failed")
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file
+            .sync_data()
+            .map_err(StorageError::from)
+            .context("Unable to flush the image file")
+    }
+
+    fn geometry(&mut self) -> Result<Option<Geometry>> {
+        Ok(Some(Geometry {
+            size: self.file.metadata()?.len(),
+            sector_size: IMAGE_SECTOR_SIZE,
+            physical_sector_size: IMAGE_SECTOR_SIZE,
+        }))
+    }
+}
+
+/// What happens to the image file after a successful wipe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFileDisposal {
+    Keep,
+    Truncate,
+    Delete,
+}
+
+impl ImageFileDisposal {
+    pub const NAMES: &'static [&'static str] = &["keep", "truncate", "delete"];
+
+    pub fn apply(&self, image: &ImageFile) -> Result<()> {
+        match self {
+            ImageFileDisposal::Keep => Ok(()),
+            ImageFileDisposal::Truncate => OpenOptions::new()
+                .write(true)
+                .open(image.path())
+                .and_then(|f| f.set_len(0))
+                .context(format!(
+                    "Unable to truncate {}",
+                    image.path().to_string_lossy()
+                )),
+            ImageFileDisposal::Delete => std::fs::remove_file(image.path()).context(format!(
+                "Unable to delete {}",
+                image.path().to_string_lossy()
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ImageFileDisposal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageFileDisposal::Keep => f.write_str("kept the file"),
+            ImageFileDisposal::Truncate => f.write_str("truncated the file"),
+            ImageFileDisposal::Delete => f.write_str("deleted the file"),
+        }
+    }
+}
+
+impl std::str::FromStr for ImageFileDisposal {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "keep" => Ok(ImageFileDisposal::Keep),
+            "truncate" => Ok(ImageFileDisposal::Truncate),
+            "delete" => Ok(ImageFileDisposal::Delete),
+            _ => Err(anyhow!(
+                "Use one of {}.",
+                ImageFileDisposal::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_image_file_is_wiped_and_deleted() {
+        let path = std::env::temp_dir().join(format!("lethe-image-{}.img", std::process::id()));
+        std::fs::write(&path, vec![0xaa; 8192]).unwrap();
+
+        let image = ImageFile::open(&path).unwrap();
+        assert_eq!(image.details().size, 8192);
+        assert!(image.id().starts_with("file://"));
+
+        let mut access = ImageFiles.access(&image).unwrap();
+        access.seek(4096).unwrap();
+        access.write(&[0; 4096]).unwrap();
+        access.flush().unwrap();
+        assert_eq!(access.geometry().unwrap().unwrap().size, 8192);
+        drop(access);
+
+        let data = std::fs::read(&path).unwrap();
+        assert!(data[..4096].iter().all(|b| *b == 0xaa));
+        assert!(data[4096..].iter().all(|b| *b == 0));
+
+        ImageFileDisposal::Truncate.apply(&image).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        ImageFileDisposal::Delete.apply(&image).unwrap();
+        assert!(!path.exists());
+
+        assert!(ImageFile::open(std::env::temp_dir()).is_err());
+        assert!("shred".parse::<ImageFileDisposal>().is_err());
+    }
+}
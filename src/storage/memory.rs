@@ -0,0 +1,170 @@
+//! Storage kept in memory for the tests, with the failures of a real device injectable.
+
+use crate::storage::{Geometry, QueuedWriteFailed, StorageAccess, StorageError};
+use anyhow::{Context, Result};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+pub struct InMemoryStorage {
+    pub file: Cursor<Vec<u8>>,
+    pub size: usize,
+    pub total_written: usize,
+    pub total_read: usize,
+    failures: Vec<usize>,
+    bad_blocks: Vec<u64>,
+    /// Reads starting at these positions fail as if hitting a bad block.
+    pub unreadable: Vec<u64>,
+    corrupted: Vec<u64>,
+    lost_writes: Vec<u64>,
+    /// The first write starting at each of these positions is dropped and reported as a
+    /// failed queued write by the next write or flush.
+    pub queued_failures: Vec<u64>,
+    failed_queued_write: Option<u64>,
+    /// Writes larger than this fail as if hitting a bad block.
+    pub max_write_size: Option<usize>,
+    /// Answers to the geometry queries in order, the last one is repeated.
+    pub geometry: Vec<Geometry>,
+}
+
+impl InMemoryStorage {
+    /// Storage of the given size filled with `0xff`.
+    pub fn new(size: usize) -> Self {
+        Self::with_data(vec![0xff; size])
+    }
+
+    pub fn with_data(data: Vec<u8>) -> Self {
+        InMemoryStorage {
+            size: data.len(),
+            file: Cursor::new(data),
+            total_written: 0,
+            total_read: 0,
+            failures: Vec::new(),
+            bad_blocks: Vec::new(),
+            unreadable: Vec::new(),
+            corrupted: Vec::new(),
+            lost_writes: Vec::new(),
+            queued_failures: Vec::new(),
+            failed_queued_write: None,
+            max_write_size: None,
+            geometry: Vec::new(),
+        }
+    }
+
+    /// Fails the access crossing the given number of bytes read and written in total.
+    pub fn fail_after_any(&mut self, amount: usize) {
+        self.failures.push(amount);
+        self.failures.sort();
+    }
+
+    /// Writes covering this position fail as if hitting a bad block.
+    pub fn fail_at(&mut self, pos: u64) {
+        self.bad_blocks.push(pos);
+        self.bad_blocks.sort();
+    }
+
+    /// Reads starting at these positions return altered data.
+    pub fn corrupt_at(&mut self, positions: Vec<u64>) {
+        self.corrupted = positions;
+    }
+
+    /// The first write starting at each of these positions is silently dropped.
+    pub fn lose_writes_at(&mut self, positions: Vec<u64>) {
+        self.lost_writes = positions;
+    }
+
+    fn report_failed_queued_write(&mut self) -> Result<()> {
+        match self.failed_queued_write.take() {
+            Some(position) => Err(QueuedWriteFailed {
+                position,
+                source: StorageError::Other(std::io::Error::other("Mocked IO failure")),
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    fn check_for_traps(&mut self, read_bytes: usize, write_bytes: usize) -> Result<()> {
+        let block_start = self.file.position();
+        let block_end = block_start + write_bytes as u64;
+        let is_bad_block = self
+            .bad_blocks
+            .iter()
+            .any(|b| block_start <= *b && block_end > *b)
+            || (read_bytes > 0 && self.unreadable.contains(&block_start));
+
+        let too_large = self.max_write_size.is_some_and(|m| write_bytes > m);
+
+        if is_bad_block || too_large {
+            return Err(StorageError::BadBlock.into());
+        }
+
+        let old_total = self.total_read + self.total_written;
+
+        self.total_read += read_bytes;
+        self.total_written += write_bytes;
+
+        match self.failures.iter().find(|x| **x >= old_total) {
+            Some(v) if old_total + read_bytes + write_bytes > *v => {
+                Err(anyhow!("Mocked IO failure"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl StorageAccess for InMemoryStorage {
+    fn position(&mut self) -> Result<u64> {
+        self.file.stream_position().context("unexpected")
+    }
+
+    fn seek(&mut self, position: u64) -> Result<u64> {
+        self.file
+            .seek(SeekFrom::Start(position))
+            .context("unexpected")
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        self.check_for_traps(buffer.len(), 0)?;
+        let corrupted = self.corrupted.contains(&self.file.position());
+        let read = self.file.read(buffer).context("unexpected")?;
+        if corrupted {
+            buffer[0] ^= 0xff;
+        }
+        Ok(read)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.report_failed_queued_write()?;
+        self.check_for_traps(0, data.len())?;
+        let position = self.file.position();
+        if let Some(i) = self.lost_writes.iter().position(|p| *p == position) {
+            self.lost_writes.remove(i);
+            self.file.set_position(position + data.len() as u64);
+            return Ok(());
+        }
+        if let Some(i) = self.queued_failures.iter().position(|p| *p == position) {
+            self.queued_failures.remove(i);
+            self.failed_queued_write = Some(position);
+            self.file.set_position(position + data.len() as u64);
+            return Ok(());
+        }
+        self.file.write_all(data).context("unexpected")
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.report_failed_queued_write()
+    }
+
+    fn discard(&mut self, position: u64, length: u64) -> Result<()> {
+        let range = position as usize..(position + length) as usize;
+        self.file.get_mut()[range].fill(0);
+        Ok(())
+    }
+
+    fn geometry(&mut self) -> Result<Option<Geometry>> {
+        Ok(match self.geometry.len() {
+            0 => None,
+            1 => Some(self.geometry[0]),
+            _ => Some(self.geometry.remove(0)),
+        })
+    }
+}
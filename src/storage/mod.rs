@@ -1,3 +1,10 @@
+//! Storage device enumeration and raw access.
+//!
+//! [`System`] lists the devices available on the current platform and opens them for
+//! wiping, the opened device is accessed through the [`StorageAccess`] trait. Devices
+//! which are not attached to the system can be added with a [`StorageProvider`] registered
+//! in the [`StorageRegistry`].
+
 #[cfg(unix)]
 use self::nix::*;
 #[cfg(unix)]
@@ -6,6 +13,23 @@ mod nix;
 #[cfg(windows)]
 mod windows;
 
+mod filter;
+pub mod hidden;
+mod image;
+#[cfg(test)]
+pub(crate) mod memory;
+mod monitor;
+pub mod platform;
+mod registry;
+pub mod sanitize;
+pub mod smart;
+
+pub use filter::{DeviceFilter, DevicePattern};
+pub use image::{ImageFile, ImageFileDisposal, ImageFiles, IMAGE_FILE_SCHEME};
+pub use monitor::DeviceMonitor;
+pub use platform::Platform;
+pub use registry::{StorageProvider, StorageRegistry};
+
 use anyhow::Result;
 use thiserror::Error;
 
@@ -13,20 +37,80 @@ use thiserror::Error;
 pub enum StorageError {
     #[error("bad block")]
     BadBlock,
+    /// The device is in use, e.g. mounted, locked or opened exclusively by another process.
+    #[error("device is busy")]
+    Busy(#[source] std::io::Error),
+    #[error("permission denied")]
+    PermissionDenied(#[source] std::io::Error),
+    #[error("device not found")]
+    NotFound(#[source] std::io::Error),
+    /// The device doesn't support the way it's opened (like direct I/O) or accessed.
+    #[error("not supported by the device")]
+    Unsupported(#[source] std::io::Error),
     #[error("other i/o error")]
     Other(#[from] std::io::Error),
 }
 
+/// A write which was queued and failed after `write` had returned, so the position of the
+/// access is already past it. The data from `position` on has to be written again.
+#[derive(Error, Debug)]
+#[error("queued write at {position} failed")]
+pub struct QueuedWriteFailed {
+    pub position: u64,
+    #[source]
+    pub source: StorageError,
+}
+
 pub trait StorageAccess {
+    #[allow(dead_code)]
     fn position(&mut self) -> Result<u64>;
     fn seek(&mut self, position: u64) -> Result<u64>;
     fn read(&mut self, buffer: &mut [u8]) -> Result<usize>;
     fn write(&mut self, data: &[u8]) -> Result<()>;
     fn flush(&mut self) -> Result<()>;
+
+    /// Reads the data back for verification. The caller compares it with the expected data,
+    /// which is only given to the storages writing to several devices, to check each of them.
+    fn read_back(&mut self, buffer: &mut [u8], _expected: &[u8]) -> Result<usize> {
+        self.read(buffer)
+    }
+
+    /// Tells the device the given byte range is no longer in use (TRIM / UNMAP).
+    fn discard(&mut self, _position: u64, _length: u64) -> Result<()> {
+        Err(anyhow!("Discard is not supported by the storage"))
+    }
+
+    /// Size and sector size currently reported by the device, if it has any.
+    fn geometry(&mut self) -> Result<Option<Geometry>> {
+        Ok(None)
+    }
 }
 
-#[derive(Clone, Debug)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geometry {
+    pub size: u64,
+    /// Logical sector size.
+    pub sector_size: u32,
+    /// Physical sector size, the same as the logical one when the device doesn't report it.
+    pub physical_sector_size: u32,
+}
+
+impl std::fmt::Display for Geometry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} bytes with {}-byte sectors",
+            self.size, self.sector_size
+        )?;
+        if self.physical_sector_size != self.sector_size {
+            write!(f, " ({}-byte physical)", self.physical_sector_size)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code, clippy::upper_case_acronyms)]
 pub enum StorageType {
     Unknown,
     File,
@@ -45,12 +129,119 @@ impl std::fmt::Display for StorageType {
     }
 }
 
+impl std::str::FromStr for StorageType {
+    type Err = anyhow::Error;
+
+    /// Same names as displayed, in any case.
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            StorageType::File,
+            StorageType::Partition,
+            StorageType::Fixed,
+            StorageType::Removable,
+            StorageType::CD,
+            StorageType::Network,
+            StorageType::RAID,
+            StorageType::Other,
+        ]
+        .iter()
+        .find(|t| t.to_string().eq_ignore_ascii_case(s))
+        .copied()
+        .ok_or_else(|| anyhow!("Unknown storage type {}", s))
+    }
+}
+
+/// Kind of the medium holding the data, as far as the system can tell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaType {
+    Unknown,
+    /// Spinning disk, overwriting reaches the blocks which held the data.
+    Rotational,
+    /// SSD or other flash behind a controller remapping the blocks.
+    SolidState,
+    /// USB sticks, memory cards and similar flash media.
+    RemovableFlash,
+}
+
+impl MediaType {
+    pub fn is_flash(&self) -> bool {
+        matches!(self, MediaType::SolidState | MediaType::RemovableFlash)
+    }
+}
+
+impl std::fmt::Display for MediaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MediaType::Unknown => f.write_str("unknown"),
+            MediaType::Rotational => f.write_str("rotational"),
+            MediaType::SolidState => f.write_str("solid state"),
+            MediaType::RemovableFlash => f.write_str("removable flash"),
+        }
+    }
+}
+
+/// Interface the drive is attached with, as far as the system can tell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BusType {
+    Unknown,
+    Ata,
+    Nvme,
+    Usb,
+    Scsi,
+    Other,
+}
+
+impl std::fmt::Display for BusType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BusType::Unknown => f.write_str("unknown"),
+            BusType::Ata => f.write_str("ATA"),
+            BusType::Nvme => f.write_str("NVMe"),
+            BusType::Usb => f.write_str("USB"),
+            BusType::Scsi => f.write_str("SCSI"),
+            BusType::Other => f.write_str("other"),
+        }
+    }
+}
+
+/// Block size of a wipe when neither the user nor the device suggests another one.
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+/// NVMe drives need larger writes to keep all their queues busy.
+const NVME_BLOCK_SIZE: usize = 4 << 20;
+
 #[derive(Debug, Clone)]
 pub struct StorageDetails {
     pub size: u64,
+    #[allow(dead_code)]
     pub block_size: usize,
+    /// Logical sector size, the unit of addressing, if known.
+    pub logical_sector_size: Option<u32>,
+    /// Physical sector size, the unit the drive writes in, if known.
+    pub physical_sector_size: Option<u32>,
     pub storage_type: StorageType,
+    pub media_type: MediaType,
+    pub bus_type: BusType,
     pub mount_point: Option<String>,
+    /// Volume label of the filesystem on the device, if any.
+    pub label: Option<String>,
+    /// Type of the filesystem (or other content like LUKS) as named by the system.
+    pub filesystem: Option<String>,
+    pub serial: Option<String>,
+    /// Model name reported by the drive.
+    pub model: Option<String>,
+    /// Firmware revision reported by the drive.
+    pub firmware: Option<String>,
+    /// Stable alternative paths of the device (like `/dev/disk/by-id` links), most stable first.
+    pub aliases: Vec<String>,
+    /// Id of the device this one is a part of, like the drive of a partition.
+    pub parent: Option<String>,
+    pub trim_supported: bool,
+    /// Space is allocated from a shared pool on write (like an LVM thin volume), so the
+    /// blocks written are not necessarily the ones which held the data.
+    pub thin_provisioned: bool,
+    /// Holds the volume the running system was booted from.
+    pub is_boot: bool,
 }
 
 impl Default for StorageDetails {
@@ -58,15 +249,234 @@ impl Default for StorageDetails {
         StorageDetails {
             size: 0,
             block_size: 0,
+            logical_sector_size: None,
+            physical_sector_size: None,
             storage_type: StorageType::Unknown,
+            media_type: MediaType::Unknown,
+            bus_type: BusType::Unknown,
             mount_point: None,
+            label: None,
+            filesystem: None,
+            serial: None,
+            model: None,
+            firmware: None,
+            aliases: Vec::new(),
+            parent: None,
+            trim_supported: false,
+            thin_provisioned: false,
+            is_boot: false,
         }
     }
 }
 
+impl StorageDetails {
+    /// Block size to wipe the device with unless given explicitly: larger for NVMe drives,
+    /// never smaller than the physical sector.
+    pub fn preferred_block_size(&self) -> usize {
+        let block_size = match self.bus_type {
+            BusType::Nvme => NVME_BLOCK_SIZE,
+            _ => DEFAULT_BLOCK_SIZE,
+        };
+        let sector_size = self
+            .physical_sector_size
+            .or(self.logical_sector_size)
+            .unwrap_or(1) as usize;
+        block_size.max(sector_size)
+    }
+
+    /// Sector sizes as `logical/physical` in bytes, a single number when they are the same.
+    pub fn describe_sectors(&self) -> Option<String> {
+        match (self.logical_sector_size, self.physical_sector_size) {
+            (Some(l), Some(p)) if l != p => Some(format!("{}/{}", l, p)),
+            (Some(s), _) | (None, Some(s)) => Some(s.to_string()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Another process which holds the device, or a partition of it, busy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceUser {
+    pub pid: Option<u32>,
+    pub name: String,
+    pub usage: String,
+}
+
+impl std::fmt::Display for DeviceUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pid {
+            Some(pid) => write!(f, "{} (pid {}): {}", self.name, pid, self.usage),
+            None => write!(f, "{}: {}", self.name, self.usage),
+        }
+    }
+}
+
+impl<T: StorageAccess + ?Sized> StorageAccess for Box<T> {
+    fn position(&mut self) -> Result<u64> {
+        (**self).position()
+    }
+
+    fn seek(&mut self, position: u64) -> Result<u64> {
+        (**self).seek(position)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        (**self).read(buffer)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        (**self).write(data)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+
+    fn discard(&mut self, position: u64, length: u64) -> Result<()> {
+        (**self).discard(position, length)
+    }
+
+    fn geometry(&mut self) -> Result<Option<Geometry>> {
+        (**self).geometry()
+    }
+}
+
 pub trait StorageRef {
     fn id(&self) -> &str;
     fn details(&self) -> &StorageDetails;
 }
 
+impl<T: StorageRef + ?Sized> StorageRef for Box<T> {
+    fn id(&self) -> &str {
+        (**self).id()
+    }
+
+    fn details(&self) -> &StorageDetails {
+        (**self).details()
+    }
+}
+
+/// Devices ordered so that each one is followed by its children (like the partitions of a
+/// drive), with their nesting depth. Devices whose parent isn't listed are at the top.
+pub fn device_tree<T: StorageRef>(devices: &[T]) -> Vec<(usize, &T)> {
+    fn add_children<'a, T: StorageRef>(
+        devices: &'a [T],
+        parent: &str,
+        depth: usize,
+        tree: &mut Vec<(usize, &'a T)>,
+    ) {
+        // a chain can't be longer than the list, unless the parents make a loop
+        if depth > devices.len() {
+            return;
+        }
+        for d in devices
+            .iter()
+            .filter(|d| d.details().parent.as_deref() == Some(parent))
+        {
+            tree.push((depth, d));
+            add_children(devices, d.id(), depth + 1, tree);
+        }
+    }
+
+    let mut tree = Vec::new();
+    for d in devices.iter().filter(|d| match &d.details().parent {
+        Some(p) => !devices.iter().any(|x| x.id() == p),
+        None => true,
+    }) {
+        tree.push((0, d));
+        add_children(devices, d.id(), 1, &mut tree);
+    }
+    tree
+}
+
 pub struct System {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_preferred_block_size() {
+        let details = |bus_type, physical_sector_size| StorageDetails {
+            logical_sector_size: Some(512),
+            physical_sector_size,
+            bus_type,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            details(BusType::Nvme, Some(4096)).preferred_block_size(),
+            4 << 20
+        );
+        assert_eq!(
+            details(BusType::Usb, Some(512)).preferred_block_size(),
+            DEFAULT_BLOCK_SIZE
+        );
+        assert_eq!(
+            details(BusType::Unknown, None).preferred_block_size(),
+            DEFAULT_BLOCK_SIZE
+        );
+        assert_eq!(
+            details(BusType::Ata, Some(4 << 20)).preferred_block_size(),
+            4 << 20
+        );
+
+        assert_eq!(
+            details(BusType::Ata, Some(4096)).describe_sectors(),
+            Some("512/4096".to_string())
+        );
+        assert_eq!(
+            details(BusType::Ata, Some(512)).describe_sectors(),
+            Some("512".to_string())
+        );
+    }
+
+    #[test]
+    fn test_device_tree() {
+        struct Device(&'static str, StorageDetails);
+
+        impl StorageRef for Device {
+            fn id(&self) -> &str {
+                self.0
+            }
+
+            fn details(&self) -> &StorageDetails {
+                &self.1
+            }
+        }
+
+        let device = |id, parent: Option<&str>| {
+            Device(
+                id,
+                StorageDetails {
+                    parent: parent.map(|p| p.to_string()),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let devices = vec![
+            device("dm-0", Some("sda2")),
+            device("sda1", Some("sda")),
+            device("sda", None),
+            device("sda2", Some("sda")),
+            device("sdb", None),
+            device("sdc1", Some("sdc")),
+        ];
+        let tree: Vec<(usize, &str)> = device_tree(&devices)
+            .into_iter()
+            .map(|(depth, d)| (depth, d.id()))
+            .collect();
+        assert_eq!(
+            tree,
+            vec![
+                (0, "sda"),
+                (1, "sda1"),
+                (1, "sda2"),
+                (2, "dm-0"),
+                (0, "sdb"),
+                (0, "sdc1"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+/// Wakes up the caller when devices may have been attached or detached, so it lists them
+/// again. Linux is notified by the kernel. The other platforms (and Linux when the
+/// notifications are unavailable) are polled, waiting simply takes the whole timeout.
+pub struct DeviceMonitor {
+    #[cfg(target_os = "linux")]
+    uevents: Option<super::nix::UeventSocket>,
+}
+
+impl DeviceMonitor {
+    pub fn new() -> DeviceMonitor {
+        DeviceMonitor {
+            #[cfg(target_os = "linux")]
+            uevents: super::nix::UeventSocket::open().ok(),
+        }
+    }
+
+    /// Whether the changes are reported by the system rather than polled.
+    pub fn is_notified(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            self.uevents.is_some()
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            false
+        }
+    }
+
+    /// Waits for a change, at most for `timeout`. Returns false after the timeout.
+    pub fn wait(&mut self, timeout: Duration) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(uevents) = &mut self.uevents {
+                return uevents.wait(timeout);
+            }
+        }
+        std::thread::sleep(timeout);
+        false
+    }
+}
+
+impl Default for DeviceMonitor {
+    fn default() -> Self {
+        DeviceMonitor::new()
+    }
+}
@@ -0,0 +1,382 @@
+//! ATA commands sent to the drive through the SCSI generic driver (SG_IO) with the
+//! ATA PASS-THROUGH (16) command, which the kernel's libata translates.
+
+use crate::storage::hidden::HiddenAreas;
+use ::nix::*;
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+const SECTOR_SIZE: usize = 512;
+const TIMEOUT_MS: u32 = 15000;
+
+const SG_DXFER_NONE: libc::c_int = -1;
+const SG_DXFER_FROM_DEV: libc::c_int = -3;
+const CHECK_CONDITION: u8 = 0x02;
+
+const IDENTIFY_DEVICE: u8 = 0xec;
+const READ_NATIVE_MAX_ADDRESS: u8 = 0xf8;
+const READ_NATIVE_MAX_ADDRESS_EXT: u8 = 0x27;
+const SET_MAX_ADDRESS: u8 = 0xf9;
+const SET_MAX_ADDRESS_EXT: u8 = 0x37;
+const DEVICE_CONFIGURATION: u8 = 0xb1;
+const DCO_RESTORE: u16 = 0xc0;
+const DCO_IDENTIFY: u16 = 0xc2;
+
+/// `sg_io_hdr` from `<scsi/sg.h>`.
+#[repr(C)]
+struct SgIoHdr {
+    interface_id: libc::c_int,
+    dxfer_direction: libc::c_int,
+    cmd_len: libc::c_uchar,
+    mx_sb_len: libc::c_uchar,
+    iovec_count: libc::c_ushort,
+    dxfer_len: libc::c_uint,
+    dxferp: *mut libc::c_void,
+    cmdp: *const libc::c_uchar,
+    sbp: *mut libc::c_uchar,
+    timeout: libc::c_uint,
+    flags: libc::c_uint,
+    pack_id: libc::c_int,
+    usr_ptr: *mut libc::c_void,
+    status: libc::c_uchar,
+    masked_status: libc::c_uchar,
+    msg_status: libc::c_uchar,
+    sb_len_wr: libc::c_uchar,
+    host_status: libc::c_ushort,
+    driver_status: libc::c_ushort,
+    resid: libc::c_int,
+    duration: libc::c_uint,
+    info: libc::c_uint,
+}
+
+ioctl_readwrite_bad!(sg_io, 0x2285, SgIoHdr); // SG_IO
+
+/// Registers of an ATA command, the 48-bit form is used when `extend` is set.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Registers {
+    command: u8,
+    features: u16,
+    count: u16,
+    lba: u64,
+    extend: bool,
+}
+
+impl Registers {
+    /// ATA PASS-THROUGH (16) command block, asking for the registers back in the sense data
+    /// when there's no data to transfer.
+    fn cdb(&self, data_in: bool) -> [u8; 16] {
+        let (protocol, flags) = match data_in {
+            // PIO data-in, the length is in the count field in blocks from the device
+            true => (4, 0x0e),
+            // non-data, check condition to return the registers
+            false => (3, 0x20),
+        };
+        let lba = self.lba.to_be_bytes();
+        [
+            0x85,
+            (protocol << 1) | self.extend as u8,
+            flags,
+            (self.features >> 8) as u8,
+            self.features as u8,
+            (self.count >> 8) as u8,
+            self.count as u8,
+            lba[4],
+            lba[7],
+            lba[3],
+            lba[6],
+            lba[2],
+            lba[5],
+            // LBA mode, and the top bits of a 28-bit address
+            0x40 | if self.extend { 0 } else { lba[4] & 0x0f },
+            self.command,
+            0,
+        ]
+    }
+}
+
+/// Address the drive returned in the ATA Status Return descriptor of the sense data, fails
+/// if the drive reported an error.
+fn parse_returned_lba(sense: &[u8], extend: bool) -> Result<u64> {
+    if sense.first().map(|c| c & 0x7f) != Some(0x72) {
+        return Err(anyhow!("Unexpected sense data format"));
+    }
+    let end = sense
+        .len()
+        .min(8 + sense.get(7).copied().unwrap_or(0) as usize);
+    let descriptors = sense.get(8..end).unwrap_or(&[]);
+    let mut offset = 0;
+    while offset + 2 <= descriptors.len() {
+        let length = descriptors[offset + 1] as usize + 2;
+        let d = match descriptors.get(offset..offset + length) {
+            Some(d) if d[0] == 0x09 && length >= 14 => d,
+            _ => {
+                offset += length;
+                continue;
+            }
+        };
+        if d[13] & 0x01 != 0 {
+            return Err(anyhow!(
+                "The drive aborted the command (error {:#04x})",
+                d[3]
+            ));
+        }
+        let lba = if extend {
+            u64::from_be_bytes([0, 0, d[10], d[8], d[6], d[11], d[9], d[7]])
+        } else {
+            u64::from_be_bytes([0, 0, 0, 0, d[12] & 0x0f, d[11], d[9], d[7]])
+        };
+        return Ok(lba);
+    }
+    Err(anyhow!("The drive didn't return the registers"))
+}
+
+/// Reads a little-endian number from the consecutive 16-bit words of an identify page.
+fn words(page: &[u8], first: usize, count: usize) -> u64 {
+    (0..count).rev().fold(0, |n, i| {
+        let o = (first + i) * 2;
+        (n << 16) | u16::from_le_bytes([page[o], page[o + 1]]) as u64
+    })
+}
+
+struct AtaDevice {
+    file: File,
+}
+
+impl AtaDevice {
+    fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path.as_ref())
+            .context("Unable to open the drive")?;
+        Ok(AtaDevice { file })
+    }
+
+    fn execute(&self, registers: Registers, data: Option<&mut [u8]>) -> Result<[u8; 32]> {
+        let cdb = registers.cdb(data.is_some());
+        let mut sense = [0u8; 32];
+        let (direction, buffer, length) = match data {
+            Some(d) => (SG_DXFER_FROM_DEV, d.as_mut_ptr(), d.len()),
+            None => (SG_DXFER_NONE, std::ptr::null_mut(), 0),
+        };
+        let mut hdr = SgIoHdr {
+            interface_id: b'S' as libc::c_int,
+            dxfer_direction: direction,
+            cmd_len: cdb.len() as u8,
+            mx_sb_len: sense.len() as u8,
+            iovec_count: 0,
+            dxfer_len: length as libc::c_uint,
+            dxferp: buffer as *mut libc::c_void,
+            cmdp: cdb.as_ptr(),
+            sbp: sense.as_mut_ptr(),
+            timeout: TIMEOUT_MS,
+            flags: 0,
+            pack_id: 0,
+            usr_ptr: std::ptr::null_mut(),
+            status: 0,
+            masked_status: 0,
+            msg_status: 0,
+            sb_len_wr: 0,
+            host_status: 0,
+            driver_status: 0,
+            resid: 0,
+            duration: 0,
+            info: 0,
+        };
+        unsafe { sg_io(self.file.as_raw_fd(), &mut hdr) }
+            .map_err(|e| anyhow!("SG_IO failed: {}", e))?;
+
+        if hdr.host_status != 0 {
+            return Err(anyhow!(
+                "The command failed (host status {})",
+                hdr.host_status
+            ));
+        }
+        match hdr.status {
+            0 => Ok(sense),
+            // the registers are returned with a check condition
+            CHECK_CONDITION => {
+                parse_returned_lba(&sense, registers.extend)?;
+                Ok(sense)
+            }
+            s => Err(anyhow!("The command failed (status {:#04x})", s)),
+        }
+    }
+
+    fn identify(&self, registers: Registers) -> Result<Vec<u8>> {
+        let mut page = vec![0u8; SECTOR_SIZE];
+        self.execute(registers, Some(&mut page))?;
+        Ok(page)
+    }
+
+    fn read_native_max(&self, extend: bool) -> Result<u64> {
+        let registers = Registers {
+            command: match extend {
+                true => READ_NATIVE_MAX_ADDRESS_EXT,
+                false => READ_NATIVE_MAX_ADDRESS,
+            },
+            extend,
+            ..Default::default()
+        };
+        let sense = self.execute(registers, None)?;
+        parse_returned_lba(&sense, extend).map(|lba| lba + 1)
+    }
+}
+
+/// Capacity the drive reports to the system, and whether it takes 48-bit addresses, from
+/// the IDENTIFY DEVICE data.
+fn parse_identify(page: &[u8]) -> (u64, bool) {
+    let extend = words(page, 83, 1) & (1 << 10) != 0;
+    let sectors = match extend {
+        true => words(page, 100, 4),
+        false => words(page, 60, 2),
+    };
+    (sectors, extend)
+}
+
+/// Sector counts of the drive including the ones in its HPA and behind its DCO.
+pub fn read_hidden_areas<P: AsRef<Path>>(path: P) -> Option<HiddenAreas> {
+    let device = AtaDevice::open(path).ok()?;
+    let (visible, extend) = parse_identify(
+        &device
+            .identify(Registers {
+                command: IDENTIFY_DEVICE,
+                count: 1,
+                ..Default::default()
+            })
+            .ok()?,
+    );
+    let native = device.read_native_max(extend).ok()?;
+    let real = device
+        .identify(Registers {
+            command: DEVICE_CONFIGURATION,
+            features: DCO_IDENTIFY,
+            count: 1,
+            ..Default::default()
+        })
+        .ok()
+        .map(|page| words(&page, 3, 4) + 1);
+    Some(HiddenAreas {
+        visible,
+        native,
+        real,
+    })
+}
+
+/// Sets the number of sectors the drive shows with SET MAX ADDRESS, keeping it over power
+/// cycles when permanent, and has the kernel read the capacity again.
+pub fn set_max_sectors<P: AsRef<Path>>(path: P, sectors: u64, permanent: bool) -> Result<()> {
+    let device = AtaDevice::open(&path)?;
+    let (_, extend) = parse_identify(&device.identify(Registers {
+        command: IDENTIFY_DEVICE,
+        count: 1,
+        ..Default::default()
+    })?);
+    // the drive only takes the command right after READ NATIVE MAX ADDRESS
+    device.read_native_max(extend)?;
+    device.execute(
+        Registers {
+            command: match extend {
+                true => SET_MAX_ADDRESS_EXT,
+                false => SET_MAX_ADDRESS,
+            },
+            count: permanent as u16,
+            lba: sectors - 1,
+            extend,
+            ..Default::default()
+        },
+        None,
+    )?;
+    rescan(path)
+}
+
+/// Removes the DCO with DEVICE CONFIGURATION RESTORE.
+pub fn restore_dco<P: AsRef<Path>>(path: P) -> Result<()> {
+    AtaDevice::open(path)?.execute(
+        Registers {
+            command: DEVICE_CONFIGURATION,
+            features: DCO_RESTORE,
+            ..Default::default()
+        },
+        None,
+    )?;
+    Ok(())
+}
+
+/// Makes the kernel read the capacity of the drive again.
+fn rescan<P: AsRef<Path>>(path: P) -> Result<()> {
+    let name = path
+        .as_ref()
+        .file_name()
+        .ok_or_else(|| anyhow!("Unexpected device path {}", path.as_ref().display()))?;
+    std::fs::write(
+        Path::new("/sys/class/block")
+            .join(name)
+            .join("device/rescan"),
+        "1",
+    )
+    .context(format!("Unable to rescan {}", path.as_ref().display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pass_through_command() {
+        let set_max = Registers {
+            command: SET_MAX_ADDRESS_EXT,
+            count: 1,
+            lba: 0x0000_3a38_6030,
+            extend: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            set_max.cdb(false),
+            [0x85, 0x07, 0x20, 0, 0, 0, 1, 0x3a, 0x30, 0, 0x60, 0, 0x38, 0x40, 0x37, 0]
+        );
+
+        let identify = Registers {
+            command: IDENTIFY_DEVICE,
+            count: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            identify.cdb(true),
+            [0x85, 0x08, 0x0e, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0x40, 0xec, 0]
+        );
+    }
+
+    #[test]
+    fn test_returned_lba() {
+        let mut sense = [0u8; 32];
+        sense[0] = 0x72;
+        sense[7] = 14;
+        sense[8..22].copy_from_slice(&[
+            0x09, 0x0c, 0x01, 0, 0, 0, 0x3a, 0x2f, 0, 0x60, 0, 0x38, 0x40, 0x50,
+        ]);
+        assert_eq!(parse_returned_lba(&sense, true).unwrap(), 0x3a38_602f);
+        assert_eq!(parse_returned_lba(&sense, false).unwrap(), 0x0038_602f);
+
+        // the error bit of the status
+        sense[21] = 0x51;
+        assert!(parse_returned_lba(&sense, true).is_err());
+        // fixed format sense data
+        sense[0] = 0x70;
+        assert!(parse_returned_lba(&sense, true).is_err());
+    }
+
+    #[test]
+    fn test_identify() {
+        let mut page = [0u8; SECTOR_SIZE];
+        page[120..124].copy_from_slice(&0x0fff_ffffu32.to_le_bytes());
+        assert_eq!(parse_identify(&page), (0x0fff_ffff, false));
+
+        page[167] = 1 << 2; // word 83 bit 10
+        page[200..208].copy_from_slice(&976773168u64.to_le_bytes());
+        assert_eq!(parse_identify(&page), (976773168, true));
+    }
+}
@@ -1,12 +1,14 @@
 use crate::storage::*;
 use ::nix::*;
 use anyhow::{Context, Result};
-use regex::Regex;
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::BufRead;
 use std::io::BufReader;
 use std::os::unix::io::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+pub use super::ata::{read_hidden_areas, restore_dco, set_max_sectors};
 
 impl System {
     pub fn get_storage_devices() -> Result<Vec<impl StorageRef>> {
@@ -24,6 +26,7 @@ pub fn open_file_direct<P: AsRef<Path>>(file_path: P, write_access: bool) -> Res
         .truncate(false)
         .custom_flags(libc::O_DIRECT /* | libc::O_DSYNC*/) // should be enough in linux 2.6+
         .open(file_path.as_ref())
+        .map_err(StorageError::from_open)
         .context(format!(
             "Unable to open file-device {}",
             file_path.as_ref().to_str().unwrap_or("?")
@@ -41,9 +44,68 @@ pub fn get_block_device_size(fd: RawFd) -> u64 {
     }
 }
 
-#[allow(dead_code)]
-pub fn is_trim_supported(_fd: RawFd) -> bool {
-    false
+pub fn query_geometry(fd: RawFd) -> Result<Geometry> {
+    ioctl_read!(linux_get_block_size, 0x12, 114, u64); // BLKGETSIZE64
+    ioctl_read_bad!(
+        linux_get_sector_size,
+        request_code_none!(0x12, 104),
+        libc::c_int
+    ); // BLKSSZGET
+    ioctl_read_bad!(
+        linux_get_physical_sector_size,
+        request_code_none!(0x12, 123),
+        libc::c_uint
+    ); // BLKPBSZGET
+
+    unsafe {
+        let mut size: u64 = 0;
+        let mut sector_size: libc::c_int = 0;
+        let mut physical_sector_size: libc::c_uint = 0;
+        linux_get_block_size(fd, &mut size).context("Unable to get the device size")?;
+        linux_get_sector_size(fd, &mut sector_size)
+            .context("Unable to get the device sector size")?;
+        if linux_get_physical_sector_size(fd, &mut physical_sector_size).is_err() {
+            physical_sector_size = sector_size as u32;
+        }
+        Ok(Geometry {
+            size,
+            sector_size: sector_size as u32,
+            physical_sector_size,
+        })
+    }
+}
+
+pub fn is_trim_supported(fd: RawFd) -> bool {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } < 0 {
+        return false;
+    }
+
+    let (major, minor) = (libc::major(stat.st_rdev), libc::minor(stat.st_rdev));
+    let mut sys_path = match std::fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)) {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+
+    if sys_path.join("partition").exists() {
+        sys_path.pop();
+    }
+
+    std::fs::read_to_string(sys_path.join("queue/discard_max_bytes"))
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(|max| max > 0)
+        .unwrap_or(false)
+}
+
+pub fn discard(fd: RawFd, position: u64, length: u64) -> Result<()> {
+    ioctl_write_ptr_bad!(blk_discard, request_code_none!(0x12, 119), [u64; 2]); // BLKDISCARD
+
+    let range: [u64; 2] = [position, length];
+    unsafe { blk_discard(fd, &range) }
+        .map(|_| ())
+        .map_err(|_| StorageError::from(std::io::Error::last_os_error()))
+        .context("Unable to discard blocks")
 }
 
 pub fn resolve_storage_type<P: AsRef<Path>>(path: P) -> Result<StorageType> {
@@ -90,27 +152,1233 @@ pub fn resolve_mount_point<P: AsRef<Path>>(path: P) -> Result<Option<String>> {
     Ok(None)
 }
 
-pub fn get_storage_devices() -> Result<Vec<FileRef>> {
-    let partitions_file = File::open("/proc/partitions")?;
-    let buf = BufReader::new(partitions_file);
-    let name_regex = Regex::new(r"\s+(?P<name>\w+)$").unwrap();
-    let refs = buf
+/// Decodes the `\xNN` escapes used by udev for unsafe characters in the property values.
+fn decode_udev_value(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while !rest.is_empty() {
+        let escaped = match rest {
+            [b'\\', b'x', h, l, ..] => std::str::from_utf8(&[*h, *l])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(b) => {
+                bytes.push(b);
+                rest = &rest[4..];
+            }
+            None => {
+                bytes.push(rest[0]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Filesystem label and type from a udev database entry (the same values `blkid` reports).
+pub fn parse_udev_filesystem(data: &str) -> (Option<String>, Option<String>) {
+    let property = |name: &str| {
+        let prefix = format!("E:{}=", name);
+        data.lines()
+            .find_map(|l| l.strip_prefix(prefix.as_str()))
+            .filter(|v| !v.is_empty())
+    };
+
+    let label = property("ID_FS_LABEL_ENC")
+        .map(decode_udev_value)
+        .or_else(|| property("ID_FS_LABEL").map(String::from));
+    let filesystem = property("ID_FS_TYPE").map(String::from);
+    (label, filesystem)
+}
+
+/// Label and type of the filesystem on the device. Without udev the type of a mounted
+/// filesystem is still known from the mount table.
+pub fn resolve_filesystem<P: AsRef<Path>>(path: P) -> (Option<String>, Option<String>) {
+    let udev = path
+        .as_ref()
+        .file_name()
+        .and_then(|n| {
+            std::fs::read_to_string(Path::new("/sys/class/block").join(n).join("dev")).ok()
+        })
+        .and_then(|dev| std::fs::read_to_string(format!("/run/udev/data/b{}", dev.trim())).ok());
+
+    match udev.map(|d| parse_udev_filesystem(&d)) {
+        Some((label, Some(filesystem))) => (label, Some(filesystem)),
+        _ => (None, resolve_mounted_filesystem(path)),
+    }
+}
+
+fn resolve_mounted_filesystem<P: AsRef<Path>>(path: P) -> Option<String> {
+    let s = path.as_ref().to_str()?;
+    std::fs::read_to_string("/etc/mtab")
+        .ok()?
         .lines()
-        .filter_map(|io_line| {
-            let line = io_line.unwrap();
-            name_regex
-                .captures(line.as_str())
-                .map(|c| format!("/dev/{}", &c["name"]))
+        .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .find(|p| p.len() > 2 && p[0] == s)
+        .map(|p| p[2].to_string())
+}
+
+/// First non-empty of the sysfs attributes of the disk (the parent of a partition).
+fn read_disk_attribute<P: AsRef<Path>>(path: P, files: &[&str]) -> Option<String> {
+    let name = path.as_ref().file_name()?;
+    let mut sys_path = std::fs::canonicalize(Path::new("/sys/class/block").join(name)).ok()?;
+
+    if sys_path.join("partition").exists() {
+        sys_path.pop();
+    }
+
+    files
+        .iter()
+        .filter_map(|f| std::fs::read_to_string(sys_path.join(f)).ok())
+        .map(|s| s.trim().to_string())
+        .find(|s| !s.is_empty())
+}
+
+pub fn resolve_serial<P: AsRef<Path>>(path: P) -> Option<String> {
+    read_disk_attribute(path, &["serial", "device/serial"])
+}
+
+pub fn resolve_model<P: AsRef<Path>>(path: P) -> Option<String> {
+    read_disk_attribute(path, &["device/model", "device/name"])
+}
+
+pub fn resolve_firmware<P: AsRef<Path>>(path: P) -> Option<String> {
+    read_disk_attribute(path, &["device/firmware_rev", "device/rev", "device/fwrev"])
+}
+
+pub fn resolve_media_type<P: AsRef<Path>>(path: P) -> MediaType {
+    let rotational = read_disk_attribute(&path, &["queue/rotational"]);
+    let removable = read_disk_attribute(&path, &["removable"]);
+    match (rotational.as_deref(), removable.as_deref()) {
+        (Some("1"), _) => MediaType::Rotational,
+        (Some("0"), Some("1")) => MediaType::RemovableFlash,
+        (Some("0"), _) => MediaType::SolidState,
+        _ => MediaType::Unknown,
+    }
+}
+
+pub fn resolve_bus_type<P: AsRef<Path>>(path: P) -> BusType {
+    path.as_ref()
+        .file_name()
+        .and_then(|name| std::fs::canonicalize(Path::new("/sys/class/block").join(name)).ok())
+        .map(|p| bus_type_of_sys_path(&p.to_string_lossy()))
+        .unwrap_or(BusType::Unknown)
+}
+
+/// The sysfs path of a block device goes through its controllers, like
+/// `/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0/block/sdb`.
+fn bus_type_of_sys_path(sys_path: &str) -> BusType {
+    let has = |segment: &str| {
+        sys_path
+            .split('/')
+            .any(|s| s.starts_with(segment) && s[segment.len()..].starts_with(char::is_numeric))
+    };
+    if has("usb") {
+        BusType::Usb
+    } else if has("nvme") {
+        BusType::Nvme
+    } else if has("ata") {
+        BusType::Ata
+    } else if has("host") {
+        BusType::Scsi
+    } else if sys_path.starts_with("/sys/devices/virtual/") {
+        BusType::Unknown
+    } else {
+        BusType::Other
+    }
+}
+
+/// Reads the drive temperature exposed by the kernel hwmon drivers (drivetemp, nvme).
+pub fn resolve_temperature<P: AsRef<Path>>(path: P) -> Option<f32> {
+    let name = path.as_ref().file_name()?;
+    let mut sys_path = std::fs::canonicalize(Path::new("/sys/class/block").join(name)).ok()?;
+
+    if sys_path.join("partition").exists() {
+        sys_path.pop();
+    }
+
+    let device = sys_path.join("device");
+    let mut candidates: Vec<_> = std::fs::read_dir(device.join("hwmon"))
+        .into_iter()
+        .chain(std::fs::read_dir(&device))
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("hwmon") && n.len() > 5)
+                .unwrap_or(false)
+        })
+        .collect();
+    candidates.sort();
+
+    candidates
+        .iter()
+        .filter_map(|p| std::fs::read_to_string(p.join("temp1_input")).ok())
+        .filter_map(|s| s.trim().parse::<f32>().ok())
+        .map(|millidegrees| millidegrees / 1000.0)
+        .next()
+}
+
+/// Device numbers (`major:minor`) of the device, its partitions and the devices built on top
+/// of them (device mapper, md).
+fn resolve_related_devices(name: &str) -> Vec<String> {
+    let sys_path = Path::new("/sys/class/block").join(name);
+    let mut devices: Vec<String> = std::fs::read_to_string(sys_path.join("dev"))
+        .map(|d| vec![d.trim().to_string()])
+        .unwrap_or_default();
+
+    let children = std::fs::read_dir(&sys_path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.path().join("partition").exists())
+        .chain(
+            std::fs::read_dir(sys_path.join("holders"))
+                .into_iter()
+                .flatten()
+                .flatten(),
+        )
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    for c in children {
+        devices.extend(resolve_related_devices(&c));
+    }
+    devices
+}
+
+/// Mount points of the given devices found in a `/proc/<pid>/mountinfo` listing.
+pub fn find_mounts(mountinfo: &str, devices: &[String]) -> Vec<String> {
+    mountinfo
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .filter(|p| p.len() > 4 && devices.iter().any(|d| d == p[2]))
+        .map(|p| p[4].to_string())
+        .collect()
+}
+
+/// Inhibitor locks (as listed by `systemd-inhibit --list`) which mention the device.
+/// The listing is a table with the columns aligned to the header.
+pub fn find_inhibitors(listing: &str, device_name: &str) -> Vec<DeviceUser> {
+    let mut lines = listing.lines();
+    let header = match lines.next() {
+        Some(h) => h,
+        None => return Vec::new(),
+    };
+
+    let columns: Vec<(usize, &str)> = header
+        .char_indices()
+        .filter(|(i, c)| !c.is_whitespace() && (*i == 0 || header[..*i].ends_with(' ')))
+        .map(|(i, _)| (i, header[i..].split_whitespace().next().unwrap_or("")))
+        .collect();
+
+    let field = |line: &str, name: &str| -> String {
+        let index = match columns.iter().position(|(_, n)| *n == name) {
+            Some(i) => i,
+            None => return String::new(),
+        };
+        let start = columns[index].0.min(line.len());
+        let end = columns
+            .get(index + 1)
+            .map(|c| c.0.min(line.len()))
+            .unwrap_or(line.len());
+        line.get(start..end).unwrap_or("").trim().to_string()
+    };
+
+    lines
+        .filter(|l| l.contains(device_name))
+        .map(|l| DeviceUser {
+            pid: field(l, "PID").parse().ok(),
+            name: field(l, "WHO"),
+            usage: format!(
+                "holds {} inhibitor lock ({})",
+                field(l, "WHAT"),
+                field(l, "WHY")
+            ),
+        })
+        .collect()
+}
+
+/// The device and its partitions which the kernel doesn't let open exclusively, as they are
+/// held by a mapping (LUKS, LVM), a RAID array or used as swap. Mounted filesystems are left
+/// out, those are reported with their mount points.
+fn resolve_kernel_claims(name: &str) -> Vec<DeviceUser> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let sys_block = Path::new("/sys/class/block");
+    let names = std::iter::once(name.to_string()).chain(
+        std::fs::read_dir(sys_block.join(name))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.path().join("partition").exists())
+            .map(|e| e.file_name().to_string_lossy().into_owned()),
+    );
+
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").unwrap_or_default();
+    let swaps = std::fs::read_to_string("/proc/swaps").unwrap_or_default();
+
+    names
+        .filter_map(|n| {
+            let path = device_path(&n);
+            let claimed = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_EXCL)
+                .open(&path)
+                .err()
+                .and_then(|e| e.raw_os_error())
+                == Some(libc::EBUSY);
+            if !claimed {
+                return None;
+            }
+
+            let sys_path = sys_block.join(&n);
+            let dev = std::fs::read_to_string(sys_path.join("dev")).unwrap_or_default();
+            if !find_mounts(&mountinfo, &[dev.trim().to_string()]).is_empty() {
+                return None;
+            }
+
+            // device mapper holders are shown with their names, like the volumes in /dev/mapper
+            let holders: Vec<String> = std::fs::read_dir(sys_path.join("holders"))
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|e| {
+                    std::fs::read_to_string(e.path().join("dm").join("name"))
+                        .map(|n| n.trim().to_string())
+                        .unwrap_or_else(|_| e.file_name().to_string_lossy().into_owned())
+                })
+                .collect();
+            let swapped = swaps
+                .lines()
+                .skip(1)
+                .filter_map(|l| l.split_whitespace().next())
+                .any(|s| std::fs::canonicalize(s).ok().as_ref() == Some(&path));
+
+            let usage = if !holders.is_empty() {
+                format!("holds {} for {}", path.display(), holders.join(", "))
+            } else if swapped {
+                format!("uses {} as a swap area", path.display())
+            } else {
+                format!("has {} claimed exclusively", path.display())
+            };
+            Some(DeviceUser {
+                pid: None,
+                name: "kernel".to_string(),
+                usage,
+            })
+        })
+        .collect()
+}
+
+fn process_name(pid: &str) -> String {
+    std::fs::read_to_string(format!("/proc/{}/comm", pid))
+        .map(|n| n.trim().to_string())
+        .unwrap_or_else(|_| "?".to_string())
+}
+
+pub fn resolve_device_users<P: AsRef<Path>>(path: P) -> Vec<DeviceUser> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let name = match std::fs::canonicalize(&path)
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+    {
+        Some(n) => n,
+        None => return Vec::new(),
+    };
+
+    let devices = resolve_related_devices(&name);
+    if devices.is_empty() {
+        return Vec::new();
+    }
+
+    let own_pid = std::process::id().to_string();
+    let mut users = Vec::new();
+    let mut namespaces = Vec::new();
+
+    let pids = std::fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|p| p.chars().all(|c| c.is_ascii_digit()) && *p != own_pid);
+
+    for pid in pids {
+        let proc_path = Path::new("/proc").join(&pid);
+
+        let open_devices: Vec<String> = std::fs::read_dir(proc_path.join("fd"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|fd| std::fs::metadata(fd.path()).ok())
+            .filter(|m| m.file_type().is_block_device())
+            .map(|m| {
+                let rdev = m.rdev();
+                format!("{}:{}", libc::major(rdev), libc::minor(rdev))
+            })
+            .filter(|d| devices.contains(d))
+            .collect();
+
+        if !open_devices.is_empty() {
+            users.push(DeviceUser {
+                pid: pid.parse().ok(),
+                name: process_name(&pid),
+                usage: "has the device open".to_string(),
+            });
+        }
+
+        // mounts are shared by all processes in a namespace, report them once
+        let namespace = std::fs::read_link(proc_path.join("ns/mnt")).ok();
+        if namespace.is_none() || namespaces.contains(&namespace) {
+            continue;
+        }
+        namespaces.push(namespace);
+
+        if let Ok(mountinfo) = std::fs::read_to_string(proc_path.join("mountinfo")) {
+            for mount_point in find_mounts(&mountinfo, &devices) {
+                users.push(DeviceUser {
+                    pid: pid.parse().ok(),
+                    name: process_name(&pid),
+                    usage: format!("mounted at {} in its mount namespace", mount_point),
+                });
+            }
+        }
+    }
+
+    users.extend(resolve_kernel_claims(&name));
+
+    if let Ok(output) = std::process::Command::new("systemd-inhibit")
+        .args(["--list", "--no-pager"])
+        .output()
+    {
+        users.extend(find_inhibitors(
+            &String::from_utf8_lossy(&output.stdout),
+            &name,
+        ));
+    }
+
+    users
+}
+
+/// Names of the dm-crypt mappings built on top of the device or its partitions.
+fn resolve_crypt_mappings(name: &str) -> Vec<String> {
+    let sys_path = Path::new("/sys/class/block").join(name);
+    let children = std::fs::read_dir(&sys_path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.path().join("partition").exists())
+        .chain(
+            std::fs::read_dir(sys_path.join("holders"))
+                .into_iter()
+                .flatten()
+                .flatten(),
+        )
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    let mut mappings = Vec::new();
+    for c in children {
+        // mappings stacked on top have to be closed first
+        mappings.extend(resolve_crypt_mappings(&c));
+        let dm = Path::new("/sys/class/block").join(&c).join("dm");
+        let is_crypt = std::fs::read_to_string(dm.join("uuid"))
+            .map(|u| u.starts_with("CRYPT-"))
+            .unwrap_or(false);
+        if is_crypt {
+            if let Ok(n) = std::fs::read_to_string(dm.join("name")) {
+                mappings.push(n.trim().to_string());
+            }
+        }
+    }
+    mappings
+}
+
+pub fn close_crypt_mappings<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+    let name = match std::fs::canonicalize(&path)
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+    {
+        Some(n) => n,
+        None => return Ok(Vec::new()),
+    };
+
+    let mappings = resolve_crypt_mappings(&name);
+    for m in &mappings {
+        let output = std::process::Command::new("cryptsetup")
+            .args(["close", m])
+            .output()
+            .context("Unable to run cryptsetup")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Unable to close {}: {}",
+                m,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    Ok(mappings)
+}
+
+/// Unmounts the filesystems of the device, its partitions and the devices built on top
+/// of them, nested mounts first. Returns the mount points.
+pub fn unmount_filesystems<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+    let name = match std::fs::canonicalize(&path)
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+    {
+        Some(n) => n,
+        None => return Ok(Vec::new()),
+    };
+
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+        .context("Unable to read the mount table")?;
+    let mut mount_points = find_mounts(&mountinfo, &resolve_related_devices(&name));
+    mount_points.sort();
+    mount_points.dedup();
+    mount_points.sort_by_key(|m| std::cmp::Reverse(m.len()));
+
+    for m in &mount_points {
+        let output = std::process::Command::new("umount")
+            .arg(m)
+            .output()
+            .context("Unable to run umount")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Unable to unmount {}: {}",
+                m,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    Ok(mount_points)
+}
+
+/// Sends STANDBY IMMEDIATE to an ATA drive with `hdparm`.
+pub fn standby<P: AsRef<Path>>(path: P) -> Result<()> {
+    let output = std::process::Command::new("hdparm")
+        .arg("-y")
+        .arg(path.as_ref())
+        .output()
+        .context("Unable to run hdparm")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Unable to put the drive to standby: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+const EFI_GLOBAL_VARIABLE_GUID: &str = "8be4df61-93ca-11d2-aa0d-00e098032b8c";
+
+fn read_efi_variable(name: &str) -> Option<Vec<u8>> {
+    let path = format!(
+        "/sys/firmware/efi/efivars/{}-{}",
+        name, EFI_GLOBAL_VARIABLE_GUID
+    );
+    // the first 4 bytes are the variable attributes
+    std::fs::read(path)
+        .ok()
+        .filter(|d| d.len() > 4)
+        .map(|d| d[4..].to_vec())
+}
+
+/// Extracts the partition UUID from the first hard drive node of an EFI load option
+/// (in the same format as `/dev/disk/by-partuuid` links).
+pub fn parse_load_option_partuuid(option: &[u8]) -> Option<String> {
+    let le16 = |d: &[u8], o: usize| d.get(o..o + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+
+    let path_list_length = le16(option, 4)? as usize;
+    let mut offset = 6;
+    while le16(option, offset)? != 0 {
+        offset += 2;
+    }
+    offset += 2;
+
+    let path_list = option.get(offset..offset + path_list_length)?;
+    let mut node = 0;
+    while node + 4 <= path_list.len() {
+        let (node_type, node_subtype) = (path_list[node], path_list[node + 1]);
+        let length = le16(path_list, node + 2)? as usize;
+        if node_type == 0x7f || length < 4 {
+            break;
+        }
+
+        // media device path, hard drive
+        if node_type == 0x04 && node_subtype == 0x01 && length >= 42 {
+            let hard_drive = path_list.get(node..node + length)?;
+            let signature = &hard_drive[24..40];
+            return match hard_drive[41] {
+                0x02 => Some(format_guid(signature)),
+                0x01 => Some(format!(
+                    "{:08x}-{:02x}",
+                    u32::from_le_bytes([signature[0], signature[1], signature[2], signature[3]]),
+                    hard_drive[4]
+                )),
+                _ => None,
+            };
+        }
+        node += length;
+    }
+    None
+}
+
+fn format_guid(b: &[u8]) -> String {
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{}",
+        b[3],
+        b[2],
+        b[1],
+        b[0],
+        b[5],
+        b[4],
+        b[7],
+        b[6],
+        b[8],
+        b[9],
+        b[10..16]
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect::<String>()
+    )
+}
+
+/// Partition holding the EFI system partition the system was booted from.
+fn resolve_efi_boot_partition() -> Option<PathBuf> {
+    let current = read_efi_variable("BootCurrent")?;
+    let number = u16::from_le_bytes([*current.first()?, *current.get(1)?]);
+    let option = read_efi_variable(&format!("Boot{:04X}", number))?;
+    let partuuid = parse_load_option_partuuid(&option)?;
+    std::fs::canonicalize(Path::new("/dev/disk/by-partuuid").join(partuuid)).ok()
+}
+
+/// Block devices backing the given one, following device mapper and md layers.
+fn resolve_backing_devices(name: &str) -> Vec<String> {
+    let slaves = Path::new("/sys/class/block").join(name).join("slaves");
+    let backing: Vec<String> = std::fs::read_dir(slaves)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+
+    if backing.is_empty() {
+        vec![name.to_string()]
+    } else {
+        backing
+            .iter()
+            .flat_map(|b| resolve_backing_devices(b))
+            .collect()
+    }
+}
+
+/// Device a mapping (like a LUKS or LVM volume) is built on, if it's built on just one.
+fn resolve_single_backing_device(name: &str) -> Option<String> {
+    let slaves = Path::new("/sys/class/block").join(name).join("slaves");
+    let mut backing = std::fs::read_dir(slaves).ok()?.flatten();
+    match (backing.next(), backing.next()) {
+        (Some(b), None) => Some(b.file_name().to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+/// Device mapper volumes backed by a thin pool.
+pub fn resolve_thin_provisioned<P: AsRef<Path>>(path: P) -> bool {
+    let name = match path.as_ref().file_name() {
+        Some(n) => n.to_string_lossy().into_owned(),
+        None => return false,
+    };
+    let slaves = Path::new("/sys/class/block").join(name).join("slaves");
+    std::fs::read_dir(slaves)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(|e| {
+            std::fs::read_to_string(e.path().join("dm").join("name"))
+                .map(|n| n.trim_end().ends_with("-tpool"))
+                .unwrap_or(false)
         })
+}
+
+fn resolve_parent_disk(name: &str) -> Option<String> {
+    let sys_path = std::fs::canonicalize(Path::new("/sys/class/block").join(name)).ok()?;
+    if sys_path.join("partition").exists() {
+        sys_path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+    } else {
+        None
+    }
+}
+
+/// Devices of the root and boot filesystems found in a `/proc/mounts` listing and of the
+/// swap areas in use found in a `/proc/swaps` listing.
+pub fn find_system_volumes<'a>(mounts: &'a str, swaps: &'a str) -> Vec<&'a str> {
+    let mounted = mounts
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .filter(|p| p.len() > 1 && ["/", "/boot", "/boot/efi", "/efi"].contains(&p[1]))
+        .map(|p| p[0]);
+    let swapped = swaps
+        .lines()
         .skip(1)
-        .flat_map(FileRef::new)
+        .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .filter(|p| p.len() > 1 && p[1] == "partition")
+        .map(|p| p[0]);
+    mounted.chain(swapped).collect()
+}
+
+/// Names of the block devices (partitions and their disks) holding the currently booted
+/// EFI system partition, the root and boot filesystems and the swap of the running system.
+pub fn resolve_boot_devices() -> Vec<String> {
+    let mut volumes: Vec<PathBuf> = resolve_efi_boot_partition().into_iter().collect();
+
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let swaps = std::fs::read_to_string("/proc/swaps").unwrap_or_default();
+    volumes.extend(
+        find_system_volumes(&mounts, &swaps)
+            .into_iter()
+            .filter_map(|v| std::fs::canonicalize(v).ok()),
+    );
+
+    let mut names: Vec<String> = volumes
+        .iter()
+        .filter_map(|v| v.file_name())
+        .flat_map(|n| resolve_backing_devices(&n.to_string_lossy()))
+        .flat_map(|n| {
+            let parent = resolve_parent_disk(&n);
+            std::iter::once(n).chain(parent)
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Lower is more stable: WWN and EUI are tied to the drive itself, other ids usually include
+/// the model and the serial, and the paths change when the drive is moved to another port.
+fn alias_rank(alias: &str) -> u8 {
+    let name = alias.rsplit('/').next().unwrap_or(alias);
+    if alias.contains("/by-path/") {
+        3
+    } else if name.starts_with("wwn-") || name.starts_with("nvme-eui.") {
+        0
+    } else if name.starts_with("ata-") || name.starts_with("nvme-") || name.starts_with("scsi-") {
+        1
+    } else {
+        2
+    }
+}
+
+pub fn sort_aliases(aliases: &mut [String]) {
+    aliases.sort_by(|a, b| {
+        alias_rank(a)
+            .cmp(&alias_rank(b))
+            .then_with(|| a.len().cmp(&b.len()))
+            .then_with(|| a.cmp(b))
+    });
+}
+
+/// Links in `/dev/disk/by-id` and `/dev/disk/by-path` grouped by the devices they point to.
+fn resolve_disk_aliases() -> HashMap<PathBuf, Vec<String>> {
+    let mut aliases: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    for dir in &["/dev/disk/by-id", "/dev/disk/by-path"] {
+        for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+            if let Ok(target) = std::fs::canonicalize(entry.path()) {
+                aliases
+                    .entry(target)
+                    .or_default()
+                    .push(entry.path().to_string_lossy().into_owned());
+            }
+        }
+    }
+    for a in aliases.values_mut() {
+        sort_aliases(a);
+    }
+    aliases
+}
+
+/// Block device found in sysfs, with the disk it's a partition of.
+#[derive(Debug, PartialEq)]
+pub struct BlockDevice {
+    pub name: String,
+    pub parent: Option<String>,
+}
+
+/// Names split into text and numbers, so `sdb2` goes before `sdb10` when sorted.
+fn natural_key(name: &str) -> Vec<(String, u64)> {
+    let mut key = Vec::new();
+    let mut rest = name;
+    while !rest.is_empty() {
+        let text_end = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (text, tail) = rest.split_at(text_end);
+        let number_end = tail
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (number, tail) = tail.split_at(number_end);
+        key.push((text.to_string(), number.parse().unwrap_or(0)));
+        rest = tail;
+    }
+    key
+}
+
+fn read_sys_number(path: &Path) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Disks found in `/sys/block` (including NVMe namespaces, MMC boot areas, loop and device
+/// mapper devices), each followed by its partitions. Drives without media and detached loop
+/// devices have no size and are skipped.
+pub fn list_block_devices<P: AsRef<Path>>(sys_block: P) -> Vec<BlockDevice> {
+    let sized = |path: &Path| read_sys_number(&path.join("size")).unwrap_or(0) > 0;
+    let file_name = |path: &Path| {
+        path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+
+    let mut disks: Vec<PathBuf> = std::fs::read_dir(sys_block.as_ref())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| sized(p))
+        .collect();
+    disks.sort_by_key(|d| natural_key(&file_name(d)));
+
+    let mut devices = Vec::new();
+    for disk in disks {
+        let name = file_name(&disk);
+        let mut partitions: Vec<(u64, String)> = std::fs::read_dir(&disk)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| sized(p))
+            .filter_map(|p| read_sys_number(&p.join("partition")).map(|n| (n, file_name(&p))))
+            .collect();
+        partitions.sort();
+
+        devices.push(BlockDevice {
+            name: name.clone(),
+            parent: None,
+        });
+        devices.extend(partitions.into_iter().map(|(_, p)| BlockDevice {
+            name: p,
+            parent: Some(name.clone()),
+        }));
+    }
+    devices
+}
+
+/// Path of the device node, sysfs replaces the slashes of nested names (like `cciss/c0d0`).
+fn device_path(name: &str) -> PathBuf {
+    Path::new("/dev").join(name.replace('!', "/"))
+}
+
+pub fn get_storage_devices() -> Result<Vec<FileRef>> {
+    let mut refs = list_block_devices("/sys/block")
+        .into_iter()
+        .flat_map(|d| {
+            let BlockDevice { name, parent } = d;
+            FileRef::new(device_path(&name)).map(|mut r| {
+                if parent.is_some() {
+                    r.details.storage_type = StorageType::Partition;
+                }
+                r.details.parent = parent
+                    .or_else(|| resolve_single_backing_device(&name))
+                    .map(|p| device_path(&p).to_string_lossy().into_owned());
+                r
+            })
+        })
         .collect::<Vec<_>>();
 
+    let boot_devices = resolve_boot_devices();
+    for r in refs.iter_mut() {
+        let name = r.path.file_name().map(|n| n.to_string_lossy().into_owned());
+        r.details.is_boot = name.map(|n| boot_devices.contains(&n)).unwrap_or(false);
+    }
+
+    let mut aliases = resolve_disk_aliases();
+    for r in refs.iter_mut() {
+        r.details.aliases = aliases.remove(&r.path).unwrap_or_default();
+    }
+
     Ok(refs)
 }
 
 pub fn enrich_storage_details<P: AsRef<Path>>(path: P, details: &mut StorageDetails) -> Result<()> {
     details.mount_point = resolve_mount_point(&path).unwrap_or(None);
     details.storage_type = resolve_storage_type(&path).unwrap_or(StorageType::Unknown);
+    details.serial = resolve_serial(&path);
+    details.model = resolve_model(&path);
+    details.firmware = resolve_firmware(&path);
+    details.media_type = resolve_media_type(&path);
+    details.bus_type = resolve_bus_type(&path);
+    details.thin_provisioned = resolve_thin_provisioned(&path);
+    let (label, filesystem) = resolve_filesystem(&path);
+    details.label = label;
+    details.filesystem = filesystem;
     Ok(())
 }
+
+/// Kernel uevents of block devices being added or removed, the notifications udev is
+/// built on. Only tells that something changed, the devices are listed again afterwards.
+pub struct UeventSocket {
+    fd: RawFd,
+}
+
+impl UeventSocket {
+    pub fn open() -> Result<UeventSocket> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error()).context("Unable to open uevent socket");
+        }
+
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        // the group of the kernel events (udev rebroadcasts them in another one)
+        addr.nl_groups = 1;
+        let bound = unsafe {
+            libc::bind(
+                fd,
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if bound < 0 {
+            let err = std::io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err).context("Unable to listen for uevents");
+        }
+        Ok(UeventSocket { fd })
+    }
+
+    /// Waits for a block device to be added or removed, returns false after the timeout.
+    pub fn wait(&mut self, timeout: std::time::Duration) -> bool {
+        let deadline = std::time::Instant::now() + timeout;
+        let mut buffer = [0u8; 8192];
+        loop {
+            let left = deadline.saturating_duration_since(std::time::Instant::now());
+            if left.as_millis() == 0 {
+                return false;
+            }
+            let mut pfd = libc::pollfd {
+                fd: self.fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // interrupted by a signal (like Ctrl+C) counts as a timeout
+            if unsafe { libc::poll(&mut pfd, 1, left.as_millis() as libc::c_int) } <= 0 {
+                return false;
+            }
+            let read = unsafe {
+                libc::recv(
+                    self.fd,
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                    0,
+                )
+            };
+            if read <= 0 {
+                return false;
+            }
+            if is_block_device_change(&buffer[..read as usize]) {
+                return true;
+            }
+        }
+    }
+}
+
+impl Drop for UeventSocket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Parses a uevent message, `ACTION@DEVPATH` followed by `KEY=VALUE` fields, all ending
+/// with a zero byte.
+fn is_block_device_change(message: &[u8]) -> bool {
+    let mut fields = message
+        .split(|&b| b == 0)
+        .filter_map(|f| std::str::from_utf8(f).ok());
+    let mut action = None;
+    let mut subsystem = None;
+    fields.next();
+    for field in fields {
+        if let Some(a) = field.strip_prefix("ACTION=") {
+            action = Some(a);
+        } else if let Some(s) = field.strip_prefix("SUBSYSTEM=") {
+            subsystem = Some(s);
+        }
+    }
+    subsystem == Some("block") && matches!(action, Some("add") | Some("remove") | Some("change"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn load_option(device_path: &[u8]) -> Vec<u8> {
+        let mut option = vec![1, 0, 0, 0];
+        option.extend_from_slice(&(device_path.len() as u16).to_le_bytes());
+        for c in "Linux".encode_utf16().chain(std::iter::once(0)) {
+            option.extend_from_slice(&c.to_le_bytes());
+        }
+        option.extend_from_slice(device_path);
+        option
+    }
+
+    fn hard_drive_node(partition: u32, signature: [u8; 16], signature_type: u8) -> Vec<u8> {
+        let mut node = vec![0x04, 0x01, 42, 0];
+        node.extend_from_slice(&partition.to_le_bytes());
+        node.extend_from_slice(&2048u64.to_le_bytes());
+        node.extend_from_slice(&1048576u64.to_le_bytes());
+        node.extend_from_slice(&signature);
+        node.push(if signature_type == 0x02 { 0x02 } else { 0x01 });
+        node.push(signature_type);
+        node
+    }
+
+    #[test]
+    fn test_find_mounts_and_inhibitors() {
+        let mountinfo = "\
+22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw
+45 22 8:17 / /mnt/backup rw,relatime shared:30 - ext4 /dev/sdb1 rw
+46 22 0:45 / /tmp rw shared:31 - tmpfs tmpfs rw
+";
+        let devices = vec!["8:16".to_string(), "8:17".to_string()];
+        assert_eq!(find_mounts(mountinfo, &devices), vec!["/mnt/backup"]);
+
+        let listing = "\
+WHO          UID USER PID  COMM         WHAT           WHY                                 MODE
+ModemManager 0   root 1021 ModemManager sleep          ModemManager needs to reset devices delay
+udisksd      0   root 1150 udisksd      sleep:shutdown Formatting /dev/sdb                 block
+
+2 inhibitors listed.
+";
+        assert_eq!(
+            find_inhibitors(listing, "sdb"),
+            vec![DeviceUser {
+                pid: Some(1150),
+                name: "udisksd".to_string(),
+                usage: "holds sleep:shutdown inhibitor lock (Formatting /dev/sdb)".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_system_volumes() {
+        let mounts = "\
+/dev/mapper/root / ext4 rw,relatime 0 0
+/dev/nvme0n1p1 /boot/efi vfat rw,relatime 0 0
+/dev/sdb1 /mnt/backup ext4 rw,relatime 0 0
+tmpfs /tmp tmpfs rw 0 0
+";
+        let swaps = "\
+Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority
+/dev/nvme0n1p3                          partition\t8388604\t\t0\t\t-2
+/swapfile                               file\t\t1048572\t\t0\t\t-3
+";
+        assert_eq!(
+            find_system_volumes(mounts, swaps),
+            vec!["/dev/mapper/root", "/dev/nvme0n1p1", "/dev/nvme0n1p3"]
+        );
+    }
+
+    #[test]
+    fn test_sort_aliases() {
+        let mut aliases = vec![
+            "/dev/disk/by-path/pci-0000:00:17.0-ata-1".to_string(),
+            "/dev/disk/by-id/ata-Samsung_SSD_860_EVO_1TB_S3Z9NB0K".to_string(),
+            "/dev/disk/by-id/usb-Generic_Flash_Disk_8F4A-0:0".to_string(),
+            "/dev/disk/by-id/wwn-0x5002538e40a1b2c3".to_string(),
+        ];
+        sort_aliases(&mut aliases);
+        assert_eq!(
+            aliases,
+            vec![
+                "/dev/disk/by-id/wwn-0x5002538e40a1b2c3",
+                "/dev/disk/by-id/ata-Samsung_SSD_860_EVO_1TB_S3Z9NB0K",
+                "/dev/disk/by-id/usb-Generic_Flash_Disk_8F4A-0:0",
+                "/dev/disk/by-path/pci-0000:00:17.0-ata-1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_udev_filesystem() {
+        let data = "\
+S:disk/by-label/My\\x20Data
+E:ID_FS_LABEL=My_Data
+E:ID_FS_LABEL_ENC=My\\x20Data\\xc3\\xa9
+E:ID_FS_TYPE=ext4
+E:ID_FS_USAGE=filesystem
+";
+        assert_eq!(
+            parse_udev_filesystem(data),
+            (Some("My Dataé".to_string()), Some("ext4".to_string()))
+        );
+
+        let data = "E:ID_FS_LABEL=backup\nE:ID_FS_TYPE=ntfs\n";
+        assert_eq!(
+            parse_udev_filesystem(data),
+            (Some("backup".to_string()), Some("ntfs".to_string()))
+        );
+
+        let data = "E:ID_PART_TABLE_TYPE=gpt\nE:ID_FS_TYPE=\n";
+        assert_eq!(parse_udev_filesystem(data), (None, None));
+    }
+
+    #[test]
+    fn test_bus_type_of_sys_path() {
+        assert_eq!(
+            bus_type_of_sys_path("/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0/block/sdb"),
+            BusType::Usb
+        );
+        assert_eq!(
+            bus_type_of_sys_path(
+                "/sys/devices/pci0000:00/0000:00:1d.0/0000:3d:00.0/nvme/nvme0/nvme0n1"
+            ),
+            BusType::Nvme
+        );
+        assert_eq!(
+            bus_type_of_sys_path("/sys/devices/pci0000:00/0000:00:17.0/ata1/host0/target0:0:0/0:0:0:0/block/sda/sda1"),
+            BusType::Ata
+        );
+        assert_eq!(
+            bus_type_of_sys_path("/sys/devices/virtual/block/loop0"),
+            BusType::Unknown
+        );
+        assert_eq!(
+            bus_type_of_sys_path("/sys/devices/pci0000:00/0000:00:04.0/virtio1/block/vda"),
+            BusType::Other
+        );
+    }
+
+    #[test]
+    fn test_uevent_parsing() {
+        let message = |fields: &[&str]| fields.join("\0").into_bytes();
+
+        assert!(is_block_device_change(&message(&[
+            "add@/devices/pci0000:00/usb1/1-1/host6/target6:0:0/6:0:0:0/block/sdc",
+            "ACTION=add",
+            "DEVPATH=/devices/pci0000:00/usb1/1-1/host6/target6:0:0/6:0:0:0/block/sdc",
+            "SUBSYSTEM=block",
+            "DEVNAME=sdc",
+            "DEVTYPE=disk",
+            "SEQNUM=4242",
+        ])));
+        assert!(is_block_device_change(&message(&[
+            "remove@/devices/virtual/block/loop0",
+            "ACTION=remove",
+            "SUBSYSTEM=block",
+        ])));
+        assert!(!is_block_device_change(&message(&[
+            "add@/devices/pci0000:00/usb1/1-1",
+            "ACTION=add",
+            "SUBSYSTEM=usb",
+        ])));
+        // udev's own messages start with a binary header
+        assert!(!is_block_device_change(b"libudev\0\xfe\xed\xca\xfe"));
+    }
+
+    #[test]
+    fn test_list_block_devices() {
+        let root = std::env::temp_dir().join(format!("lethe-sys-block-{}", std::process::id()));
+        let add = |path: &str, size: u64, partition: Option<u64>| {
+            let dir = root.join(path);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("size"), format!("{}\n", size)).unwrap();
+            if let Some(n) = partition {
+                std::fs::write(dir.join("partition"), format!("{}\n", n)).unwrap();
+            }
+        };
+        add("sdb", 1000, None);
+        add("sdb/sdb10", 100, Some(10));
+        add("sdb/sdb2", 100, Some(2));
+        add("nvme0n1", 1000, None);
+        add("nvme0n1/nvme0n1p1", 100, Some(1));
+        add("nvme0n10", 1000, None);
+        add("mmcblk0", 1000, None);
+        add("mmcblk0/mmcblk0p1", 100, Some(1));
+        add("mmcblk0boot0", 100, None);
+        add("dm-0", 500, None);
+        add("loop0", 0, None);
+        add("sr0", 0, None);
+        std::fs::create_dir_all(root.join("sdb/queue")).unwrap();
+
+        let devices = list_block_devices(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let listed: Vec<(&str, Option<&str>)> = devices
+            .iter()
+            .map(|d| (d.name.as_str(), d.parent.as_deref()))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("dm-0", None),
+                ("mmcblk0", None),
+                ("mmcblk0p1", Some("mmcblk0")),
+                ("mmcblk0boot0", None),
+                ("nvme0n1", None),
+                ("nvme0n1p1", Some("nvme0n1")),
+                ("nvme0n10", None),
+                ("sdb", None),
+                ("sdb2", Some("sdb")),
+                ("sdb10", Some("sdb")),
+            ]
+        );
+        assert_eq!(device_path("cciss!c0d0"), Path::new("/dev/cciss/c0d0"));
+    }
+
+    #[test]
+    fn test_parse_load_option_partuuid() {
+        let guid = [
+            0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e,
+            0xc9, 0x3b,
+        ];
+        let mut path = vec![0x02, 0x01, 0x0c, 0, 0xd0, 0x41, 0x03, 0x0a, 0, 0, 0, 0];
+        path.extend(hard_drive_node(1, guid, 0x02));
+        path.extend_from_slice(&[0x7f, 0xff, 4, 0]);
+
+        assert_eq!(
+            parse_load_option_partuuid(&load_option(&path)),
+            Some("c12a7328-f81f-11d2-ba4b-00a0c93ec93b".to_string())
+        );
+
+        let mut mbr = [0u8; 16];
+        mbr[..4].copy_from_slice(&0x1234abcdu32.to_le_bytes());
+        let path = hard_drive_node(2, mbr, 0x01);
+        assert_eq!(
+            parse_load_option_partuuid(&load_option(&path)),
+            Some("1234abcd-02".to_string())
+        );
+
+        assert_eq!(
+            parse_load_option_partuuid(&load_option(&[0x7f, 0xff, 4, 0])),
+            None
+        );
+        assert_eq!(parse_load_option_partuuid(&[1, 0]), None);
+
+        // the hard drive node is cut short
+        let path = hard_drive_node(1, guid, 0x02);
+        assert_eq!(parse_load_option_partuuid(&load_option(&path[..30])), None);
+    }
+}
@@ -1,6 +1,6 @@
 //extern crate IOKit_sys as iokit;
 use ::nix::*;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use regex::Regex;
 use std::collections::HashMap;
 use std::fs::read_dir;
@@ -24,7 +24,12 @@ pub fn open_file_direct<P: AsRef<Path>>(file_path: P, write_access: bool) -> Res
         .write(write_access)
         .read(true)
         .truncate(false)
-        .open(file_path.as_ref())?;
+        .open(file_path.as_ref())
+        .map_err(StorageError::from_open)
+        .context(format!(
+            "Unable to open file-device {}",
+            file_path.as_ref().to_str().unwrap_or("?")
+        ))?;
 
     unsafe {
         let fd = file.as_raw_fd();
@@ -35,19 +40,31 @@ pub fn open_file_direct<P: AsRef<Path>>(file_path: P, write_access: bool) -> Res
 }
 
 pub fn get_block_device_size(fd: libc::c_int) -> u64 {
+    query_geometry(fd).unwrap().size
+}
+
+pub fn query_geometry(fd: RawFd) -> Result<Geometry> {
     ioctl_read!(dk_get_block_size, b'd', 24, u32); // DKIOCGETBLOCKSIZE
     ioctl_read!(dk_get_block_count, b'd', 25, u64); // DKIOCGETBLOCKCOUNT
+    ioctl_read!(dk_get_physical_block_size, b'd', 77, u32); // DKIOCGETPHYSICALBLOCKSIZE
 
     unsafe {
         let mut block_size: u32 = std::mem::zeroed();
         let mut block_count: u64 = std::mem::zeroed();
-        dk_get_block_size(fd, &mut block_size).unwrap();
-        dk_get_block_count(fd, &mut block_count).unwrap();
-        (block_size as u64) * block_count
+        let mut physical_block_size: u32 = std::mem::zeroed();
+        dk_get_block_size(fd, &mut block_size).context("Unable to get the device block size")?;
+        dk_get_block_count(fd, &mut block_count).context("Unable to get the device block count")?;
+        if dk_get_physical_block_size(fd, &mut physical_block_size).is_err() {
+            physical_block_size = block_size;
+        }
+        Ok(Geometry {
+            size: (block_size as u64) * block_count,
+            sector_size: block_size,
+            physical_sector_size: physical_block_size,
+        })
     }
 }
 
-#[allow(dead_code)]
 pub fn is_trim_supported(fd: RawFd) -> bool {
     ioctl_read!(dk_get_features, b'd', 76, u32); // DKIOCGETFEATURES
 
@@ -59,12 +76,207 @@ pub fn is_trim_supported(fd: RawFd) -> bool {
     }
 }
 
+#[repr(C)]
+struct DkExtent {
+    offset: u64,
+    length: u64,
+}
+
+#[repr(C)]
+struct DkUnmap {
+    extents: *mut DkExtent,
+    extents_count: u32,
+    options: u32,
+}
+
+pub fn discard(fd: RawFd, position: u64, length: u64) -> Result<()> {
+    ioctl_write_ptr!(dk_unmap, b'd', 31, DkUnmap); // DKIOCUNMAP
+
+    let mut extent = DkExtent {
+        offset: position,
+        length,
+    };
+    let unmap = DkUnmap {
+        extents: &mut extent,
+        extents_count: 1,
+        options: 0,
+    };
+
+    unsafe { dk_unmap(fd, &unmap) }
+        .map(|_| ())
+        .map_err(|_| StorageError::from(std::io::Error::last_os_error()))
+        .context("Unable to discard blocks")
+}
+
+pub fn resolve_temperature<P: AsRef<Path>>(_path: P) -> Option<f32> {
+    None
+}
+
+pub fn resolve_device_users<P: AsRef<Path>>(_path: P) -> Vec<DeviceUser> {
+    Vec::new()
+}
+
+pub fn close_crypt_mappings<P: AsRef<Path>>(_path: P) -> Result<Vec<String>> {
+    Ok(Vec::new())
+}
+
+pub fn standby<P: AsRef<Path>>(_path: P) -> Result<()> {
+    Err(anyhow!("Standby is not supported on this platform"))
+}
+
+pub fn read_hidden_areas<P: AsRef<Path>>(_path: P) -> Option<hidden::HiddenAreas> {
+    None
+}
+
+pub fn set_max_sectors<P: AsRef<Path>>(_path: P, _sectors: u64, _permanent: bool) -> Result<()> {
+    Err(anyhow!("Hidden areas are not supported on this platform"))
+}
+
+pub fn restore_dco<P: AsRef<Path>>(_path: P) -> Result<()> {
+    Err(anyhow!("Hidden areas are not supported on this platform"))
+}
+
+/// Unmounts the volumes of a whole disk with `diskutil unmountDisk`, or the single volume
+/// of a partition with `diskutil unmount`.
+pub fn unmount_filesystems<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+    let name = get_bsd_device_name(&path)?;
+    let info = get_diskutils_info(format!("/dev/{}", name))?;
+    let whole = info.get("Whole").map(|w| w == "Yes").unwrap_or(true);
+    if !whole && info.get("Mounted").map(|m| m != "Yes").unwrap_or(true) {
+        return Ok(Vec::new());
+    }
+
+    let output = Command::new("/usr/sbin/diskutil")
+        .arg(if whole { "unmountDisk" } else { "unmount" })
+        .arg(format!("/dev/{}", name))
+        .output()
+        .context("Unable to run diskutil")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Unable to unmount {}: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(vec![format!("/dev/{}", name)])
+}
+
 pub fn get_storage_devices() -> Result<Vec<FileRef>> {
-    discover_file_based_devices(
+    let mut refs = discover_file_based_devices(
         "/dev",
         |p| p.to_str().unwrap().contains("/dev/rdisk"),
         |_m| true,
-    )
+    )?;
+
+    let boot_devices = resolve_boot_devices();
+    let identities = resolve_drive_identities();
+    for r in refs.iter_mut() {
+        let name = match get_bsd_device_name(&r.path) {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+        r.details.is_boot = boot_devices.contains(&name);
+        r.details.parent = whole_disk_name(&name)
+            .filter(|n| *n != name)
+            .map(|n| format!("/dev/r{}", n));
+        if let Some(identity) = whole_disk_name(&name).and_then(|n| identities.get(&n)) {
+            if identity.model.is_some() {
+                r.details.model = identity.model.clone();
+            }
+            r.details.serial = identity.serial.clone();
+            r.details.firmware = identity.firmware.clone();
+        }
+    }
+
+    Ok(refs)
+}
+
+/// Model, serial number and firmware revision of a drive.
+#[derive(Debug, Default, PartialEq)]
+struct DriveIdentity {
+    model: Option<String>,
+    serial: Option<String>,
+    firmware: Option<String>,
+}
+
+/// Identities of the SATA and NVMe drives by their BSD names, as diskutil doesn't report
+/// serial numbers and firmware revisions.
+fn resolve_drive_identities() -> HashMap<String, DriveIdentity> {
+    Command::new("/usr/sbin/system_profiler")
+        .args(&["-json", "SPSerialATADataType", "SPNVMeDataType"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| serde_json::from_slice(&o.stdout).ok())
+        .map(|json| {
+            let mut identities = HashMap::new();
+            collect_drive_identities(&json, &mut identities);
+            identities
+        })
+        .unwrap_or_default()
+}
+
+/// Finds the drive entries (objects with a BSD name) at any depth of the system_profiler output.
+fn collect_drive_identities(
+    value: &serde_json::Value,
+    identities: &mut HashMap<String, DriveIdentity>,
+) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(name) = map.get("bsd_name").and_then(|n| n.as_str()) {
+                let field = |key: &str| {
+                    map.get(key)
+                        .and_then(|v| v.as_str())
+                        .map(|v| v.trim().to_string())
+                        .filter(|v| !v.is_empty())
+                };
+                identities.insert(
+                    name.to_string(),
+                    DriveIdentity {
+                        model: field("device_model"),
+                        serial: field("device_serial"),
+                        firmware: field("device_revision"),
+                    },
+                );
+            }
+            map.values()
+                .for_each(|v| collect_drive_identities(v, identities));
+        }
+        serde_json::Value::Array(items) => items
+            .iter()
+            .for_each(|v| collect_drive_identities(v, identities)),
+        _ => {}
+    }
+}
+
+/// BSD name of the whole disk a partition (like `disk0s1`) belongs to.
+fn whole_disk_name(name: &str) -> Option<String> {
+    Regex::new(r"^disk\d+")
+        .unwrap()
+        .find(name)
+        .map(|m| m.as_str().to_string())
+}
+
+/// BSD names of the devices holding the root volume, including the physical stores
+/// of an APFS container and their whole disks.
+fn resolve_boot_devices() -> Vec<String> {
+    let mut names = Vec::new();
+    let root = match get_diskutils_info("/") {
+        Ok(r) => r,
+        Err(_) => return names,
+    };
+
+    names.extend(root.get("Device Identifier").cloned());
+    names.extend(root.get("Part of Whole").cloned());
+
+    if let Some(store) = root.get("APFS Physical Store") {
+        names.push(store.clone());
+        if let Ok(info) = get_diskutils_info(format!("/dev/{}", store)) {
+            names.extend(info.get("Part of Whole").cloned());
+        }
+    }
+
+    names
 }
 
 fn discover_file_based_devices<P: AsRef<Path>>(
@@ -123,6 +335,35 @@ pub fn enrich_storage_details<P: AsRef<Path>>(path: P, details: &mut StorageDeta
     let du = get_diskutils_info(path)?;
 
     details.mount_point = du.get("Mount Point").map(|s| s.to_owned());
+    details.label = du
+        .get("Volume Name")
+        .filter(|n| !n.is_empty() && !n.starts_with("Not applicable"))
+        .cloned();
+    details.filesystem = du
+        .get("File System Personality")
+        .or_else(|| du.get("Type (Bundle)"))
+        .cloned();
+    details.model = du
+        .get("Device / Media Name")
+        .filter(|n| !n.is_empty())
+        .cloned();
+
+    let removable = du.get("Removable Media").map(|m| m == "Removable") == Some(true);
+    details.media_type = match du.get("Solid State").map(|s| s.as_str()) {
+        Some("Yes") if removable => MediaType::RemovableFlash,
+        Some("Yes") => MediaType::SolidState,
+        Some("No") => MediaType::Rotational,
+        _ => MediaType::Unknown,
+    };
+
+    details.bus_type = match du.get("Protocol").map(|p| p.as_str()) {
+        Some("USB") => BusType::Usb,
+        Some("PCI-Express") | Some("Apple Fabric") => BusType::Nvme,
+        Some("SATA") | Some("ATA") => BusType::Ata,
+        Some("SAS") | Some("SCSI") => BusType::Scsi,
+        Some(_) => BusType::Other,
+        None => BusType::Unknown,
+    };
 
     if du.get("Whole").unwrap_or(&String::from("Yes")) == "No" {
         details.storage_type = StorageType::Partition;
@@ -159,4 +400,38 @@ mod test {
 
         assert!(get_bsd_device_name("").is_err());
     }
+
+    #[test]
+    fn test_drive_identities_are_collected() {
+        let json: serde_json::Value = serde_json::from_str(
+            r#"{
+                "SPNVMeDataType": [{
+                    "_name": "Apple SSD Controller",
+                    "_items": [{
+                        "_name": "APPLE SSD AP0512Q",
+                        "bsd_name": "disk0",
+                        "device_model": "APPLE SSD AP0512Q",
+                        "device_revision": "387.100.",
+                        "device_serial": " 0ba0123456789abc "
+                    }]
+                }],
+                "SPSerialATADataType": []
+            }"#,
+        )
+        .unwrap();
+        let mut identities = HashMap::new();
+        collect_drive_identities(&json, &mut identities);
+        assert_eq!(
+            identities.get("disk0"),
+            Some(&DriveIdentity {
+                model: Some("APPLE SSD AP0512Q".to_owned()),
+                serial: Some("0ba0123456789abc".to_owned()),
+                firmware: Some("387.100.".to_owned()),
+            })
+        );
+
+        assert_eq!(whole_disk_name("disk0s1"), Some("disk0".to_owned()));
+        assert_eq!(whole_disk_name("disk12"), Some("disk12".to_owned()));
+        assert_eq!(whole_disk_name("rdisk1"), None);
+    }
 }
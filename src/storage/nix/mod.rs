@@ -9,10 +9,17 @@ use std::io::SeekFrom;
 use std::os::unix::io::*;
 use std::path::{Path, PathBuf};
 
+#[cfg(target_os = "linux")]
+mod ata;
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "linux")]
 use linux as os;
+#[cfg(target_os = "linux")]
+pub use linux::UeventSocket;
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
 
 #[cfg(target_os = "macos")]
 mod macos;
@@ -49,6 +56,21 @@ impl StorageError {
             _ => StorageError::Other(err),
         }
     }
+
+    fn from_open(err: std::io::Error) -> StorageError {
+        match err.raw_os_error() {
+            Some(libc::EBUSY) | Some(libc::ETXTBSY) => StorageError::Busy(err),
+            Some(libc::EACCES) | Some(libc::EPERM) | Some(libc::EROFS) => {
+                StorageError::PermissionDenied(err)
+            }
+            Some(libc::ENOENT) | Some(libc::ENXIO) | Some(libc::ENODEV) => {
+                StorageError::NotFound(err)
+            }
+            // O_DIRECT is rejected with EINVAL by the filesystems which don't support it
+            Some(libc::EINVAL) | Some(libc::EOPNOTSUPP) => StorageError::Unsupported(err),
+            _ => StorageError::Other(err),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -66,38 +88,50 @@ impl FileAccess {
 impl StorageAccess for FileAccess {
     fn position(&mut self) -> Result<u64> {
         self.file
-            .seek(SeekFrom::Current(0))
-            .map_err(|e| StorageError::from(e))
+            .stream_position()
+            .map_err(StorageError::from)
             .context("Seek failed or not supported for the storage")
     }
 
     fn seek(&mut self, position: u64) -> Result<u64> {
         self.file
             .seek(SeekFrom::Start(position))
-            .map_err(|e| StorageError::from(e))
+            .map_err(StorageError::from)
             .context("Seek failed or not supported for the storage")
     }
 
     fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
         self.file
             .read(buffer)
-            .map_err(|e| StorageError::from(e))
+            .map_err(StorageError::from)
             .context("Can't read from the storage")
     }
 
     fn write(&mut self, data: &[u8]) -> Result<()> {
         self.file
             .write_all(data)
-            .map_err(|e| StorageError::from(e))
+            .map_err(StorageError::from)
             .context("Writing to storage failed")
     }
 
     fn flush(&mut self) -> Result<()> {
         self.file
             .flush()
-            .map_err(|e| StorageError::from(e))
+            .map_err(StorageError::from)
             .context("Unable to flush data to the storage")
     }
+
+    fn discard(&mut self, position: u64, length: u64) -> Result<()> {
+        os::discard(self.file.as_raw_fd(), position, length)
+    }
+
+    fn geometry(&mut self) -> Result<Option<Geometry>> {
+        let stat = ::nix::sys::stat::fstat(self.file.as_raw_fd())?;
+        match resolve_file_type(stat.st_mode) {
+            FileType::Block | FileType::Raw => Ok(Some(os::query_geometry(self.file.as_raw_fd())?)),
+            _ => Ok(None),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -129,11 +163,21 @@ impl FileRef {
 
         let size = resolve_storage_size(&file_type, &stat, fd);
 
+        let geometry = match file_type {
+            FileType::Block | FileType::Raw => os::query_geometry(fd).ok(),
+            _ => None,
+        };
+
         let mut details = StorageDetails {
             size,
             block_size: stat.st_blksize as usize,
-            storage_type: StorageType::Unknown,
-            mount_point: None,
+            logical_sector_size: geometry.map(|g| g.sector_size),
+            physical_sector_size: geometry.map(|g| g.physical_sector_size),
+            trim_supported: match file_type {
+                FileType::Block | FileType::Raw => os::is_trim_supported(fd),
+                _ => false,
+            },
+            ..Default::default()
         };
 
         os::enrich_storage_details(path, &mut details)?;
@@ -154,6 +198,90 @@ impl StorageRef for FileRef {
 
 impl System {
     pub fn access(storage_ref: &dyn StorageRef) -> Result<impl StorageAccess> {
-        FileAccess::new(&storage_ref.id())
+        FileAccess::new(storage_ref.id())
+    }
+
+    /// Access keeping up to `queue_depth` writes in flight where supported (io_uring on
+    /// Linux), the synchronous one otherwise.
+    pub fn access_queued(
+        storage_ref: &dyn StorageRef,
+        queue_depth: usize,
+    ) -> Result<Box<dyn StorageAccess>> {
+        let access = FileAccess::new(storage_ref.id())?;
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        {
+            if queue_depth > 1 {
+                // falls back when the kernel is too old or io_uring is disabled
+                if let Ok(file) = access.file.try_clone() {
+                    if let Ok(queued) = uring::UringAccess::new(FileAccess { file }, queue_depth) {
+                        return Ok(Box::new(queued));
+                    }
+                }
+            }
+        }
+        let _ = queue_depth;
+        Ok(Box::new(access))
+    }
+
+    pub fn temperature(device_id: &str) -> Option<f32> {
+        os::resolve_temperature(device_id)
+    }
+
+    /// Other processes which have the device open, mounted or locked.
+    pub fn device_users(device_id: &str) -> Vec<DeviceUser> {
+        os::resolve_device_users(device_id)
+    }
+
+    /// Closes the dm-crypt mappings opened on top of the device or its partitions and
+    /// returns their names.
+    pub fn close_crypt_mappings(device_id: &str) -> Result<Vec<String>> {
+        os::close_crypt_mappings(device_id)
+    }
+
+    /// Unmounts all filesystems on the device and its partitions and returns what
+    /// was unmounted.
+    pub fn unmount_filesystems(device_id: &str) -> Result<Vec<String>> {
+        os::unmount_filesystems(device_id)
+    }
+
+    pub fn standby(device_id: &str) -> Result<()> {
+        os::standby(device_id)
+    }
+
+    /// Sector counts of an ATA drive including the ones hidden in its HPA and DCO, if the
+    /// drive can be queried.
+    pub fn hidden_areas(device_id: &str) -> Option<hidden::HiddenAreas> {
+        os::read_hidden_areas(device_id)
+    }
+
+    /// Sets the number of sectors the drive shows, for good when permanent or until it's
+    /// powered off otherwise.
+    pub fn set_max_sectors(device_id: &str, sectors: u64, permanent: bool) -> Result<()> {
+        os::set_max_sectors(device_id, sectors, permanent)
+    }
+
+    /// Removes the DCO of the drive.
+    pub fn restore_dco(device_id: &str) -> Result<()> {
+        os::restore_dco(device_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::underlying_storage_error;
+
+    #[test]
+    fn test_open_errors_are_classified() {
+        let err = os::open_file_direct("/dev/lethe-missing-device", false).unwrap_err();
+        assert!(matches!(
+            underlying_storage_error(&err),
+            Some(StorageError::NotFound(_))
+        ));
+
+        let busy = StorageError::from_open(std::io::Error::from_raw_os_error(libc::EBUSY));
+        assert!(matches!(busy, StorageError::Busy(_)));
+        let denied = StorageError::from_open(std::io::Error::from_raw_os_error(libc::EACCES));
+        assert_eq!(denied.to_string(), "permission denied");
     }
 }
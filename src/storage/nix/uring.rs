@@ -0,0 +1,303 @@
+//! Queued writes with io_uring, so the device gets the next blocks while the previous
+//! ones are still being written.
+
+use super::FileAccess;
+use crate::sanitization::mem::AlignedBuffer;
+use crate::storage::*;
+use anyhow::{Context, Result};
+use io_uring::{opcode, types, IoUring};
+use std::io::{Error, ErrorKind, Seek};
+use std::os::unix::io::*;
+
+/// Direct I/O needs the buffers aligned to the logical sector size, a page is enough for
+/// all of them.
+const BUFFER_ALIGNMENT: usize = 4096;
+
+pub const MAX_QUEUE_DEPTH: usize = 256;
+
+/// Copy of the written data, owned by the queue until the kernel is done with it.
+struct Slot {
+    buffer: Option<AlignedBuffer>,
+    position: u64,
+    length: usize,
+}
+
+/// Keeps up to `queue_depth` writes in flight. The data is copied into the buffers of the
+/// queue, so the caller can reuse its buffer right away. Reads, flushes and drops wait
+/// for the queued writes first.
+///
+/// A queued write can only fail after `write` has returned, which makes it impossible to
+/// tell the bad block. So a failure is reported as `QueuedWriteFailed` with the position of
+/// the write, for the retry to start over from there, and the queue switches to synchronous
+/// writes to find and skip the bad block as usual.
+pub struct UringAccess {
+    inner: FileAccess,
+    ring: IoUring,
+    queue_depth: usize,
+    position: u64,
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    in_flight: usize,
+    failure: Option<(u64, Error)>,
+}
+
+impl UringAccess {
+    pub fn new(mut inner: FileAccess, queue_depth: usize) -> Result<UringAccess> {
+        let queue_depth = queue_depth.clamp(1, MAX_QUEUE_DEPTH);
+        let ring = IoUring::new(queue_depth as u32).context("Unable to set up io_uring")?;
+        let position = inner.file.stream_position()?;
+        Ok(UringAccess {
+            inner,
+            ring,
+            queue_depth,
+            position,
+            slots: (0..queue_depth)
+                .map(|_| Slot {
+                    buffer: None,
+                    position: 0,
+                    length: 0,
+                })
+                .collect(),
+            free: (0..queue_depth).rev().collect(),
+            in_flight: 0,
+            failure: None,
+        })
+    }
+
+    /// Waits for at least `count` of the queued writes to complete.
+    fn reap(&mut self, count: usize) -> std::io::Result<()> {
+        loop {
+            match self.ring.submit_and_wait(count) {
+                Ok(_) => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        for cqe in self.ring.completion() {
+            let i = cqe.user_data() as usize;
+            let slot = &self.slots[i];
+            let result = cqe.result();
+            if self.failure.is_none() {
+                if result < 0 {
+                    self.failure = Some((slot.position, Error::from_raw_os_error(-result)));
+                } else if (result as usize) < slot.length {
+                    self.failure = Some((
+                        slot.position,
+                        Error::new(ErrorKind::WriteZero, "failed to write the whole block"),
+                    ));
+                }
+            }
+            self.free.push(i);
+            self.in_flight -= 1;
+        }
+        Ok(())
+    }
+
+    fn drain(&mut self) -> std::io::Result<()> {
+        while self.in_flight > 0 {
+            self.reap(1)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for all queued writes and reports the first one which failed.
+    fn complete_writes(&mut self) -> Result<()> {
+        self.drain()
+            .map_err(StorageError::Other)
+            .context("Waiting for the queued writes failed")?;
+        match self.failure.take() {
+            Some((position, err)) => {
+                self.queue_depth = 1;
+                Err(QueuedWriteFailed {
+                    position,
+                    source: StorageError::Other(err),
+                }
+                .into())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl StorageAccess for UringAccess {
+    fn position(&mut self) -> Result<u64> {
+        Ok(self.position)
+    }
+
+    fn seek(&mut self, position: u64) -> Result<u64> {
+        // the queued writes have their own offsets
+        self.position = position;
+        Ok(position)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        self.complete_writes()?;
+
+        let read = opcode::Read::new(
+            types::Fd(self.inner.file.as_raw_fd()),
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+        )
+        .offset(self.position)
+        .build()
+        .user_data(u64::MAX);
+        unsafe { self.ring.submission().push(&read) }
+            .map_err(|_| anyhow!("The submission queue is full"))?;
+
+        // the buffer is borrowed only for this call, so the read is waited for right away
+        let result = loop {
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    return Err(StorageError::Other(e)).context("Can't read from the storage")
+                }
+            }
+            if let Some(cqe) = self.ring.completion().next() {
+                break cqe.result();
+            }
+        };
+
+        if result < 0 {
+            return Err(StorageError::from(Error::from_raw_os_error(-result)))
+                .context("Can't read from the storage");
+        }
+        self.position += result as u64;
+        Ok(result as usize)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        if self.queue_depth == 1 {
+            // synchronous writes report the errors of the block itself
+            self.complete_writes()?;
+            self.inner.seek(self.position)?;
+            self.inner.write(data)?;
+            self.position += data.len() as u64;
+            return Ok(());
+        }
+
+        if self.failure.is_some() {
+            return self.complete_writes();
+        }
+        if self.free.is_empty() {
+            self.reap(1)
+                .map_err(StorageError::Other)
+                .context("Waiting for the queued writes failed")?;
+            if self.failure.is_some() {
+                return self.complete_writes();
+            }
+        }
+
+        let i = self.free.pop().unwrap();
+        let slot = &mut self.slots[i];
+        if slot.buffer.as_ref().map(|b| b.as_slice().len()) < Some(data.len()) {
+            let size = data.len().div_ceil(BUFFER_ALIGNMENT) * BUFFER_ALIGNMENT;
+            slot.buffer = Some(AlignedBuffer::new(size, BUFFER_ALIGNMENT));
+        }
+        let buffer = slot.buffer.as_mut().unwrap().as_mut_slice();
+        buffer[..data.len()].copy_from_slice(data);
+        slot.position = self.position;
+        slot.length = data.len();
+
+        let write = opcode::Write::new(
+            types::Fd(self.inner.file.as_raw_fd()),
+            buffer.as_ptr(),
+            data.len() as u32,
+        )
+        .offset(self.position)
+        .build()
+        .user_data(i as u64);
+        // the queue has a free entry for each free slot
+        unsafe { self.ring.submission().push(&write) }
+            .map_err(|_| anyhow!("The submission queue is full"))?;
+        // once pushed the write stays queued and is submitted by the next wait even if the
+        // submission fails now, so it's counted right away
+        let position = self.position;
+        self.in_flight += 1;
+        self.position += data.len() as u64;
+
+        if let Err(err) = self.ring.submit() {
+            // the retry starts over from this write
+            if self.failure.is_none() {
+                self.failure = Some((position, err));
+            }
+            return self.complete_writes();
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.complete_writes()?;
+        self.inner.flush()
+    }
+
+    fn discard(&mut self, position: u64, length: u64) -> Result<()> {
+        self.complete_writes()?;
+        self.inner.discard(position, length)
+    }
+
+    fn geometry(&mut self) -> Result<Option<Geometry>> {
+        self.inner.geometry()
+    }
+}
+
+impl Drop for UringAccess {
+    fn drop(&mut self) {
+        if self.drain().is_err() {
+            // the kernel may still write from the buffers, so they can't be released
+            for s in &mut self.slots {
+                std::mem::forget(s.buffer.take());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    fn temp_access(name: &str, size: u64) -> (std::path::PathBuf, FileAccess) {
+        let path = std::env::temp_dir().join(name);
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        file.set_len(size).unwrap();
+        (path, FileAccess { file })
+    }
+
+    #[test]
+    fn test_uring_queued_writes() {
+        let block = 4096;
+        let (path, file) = temp_access("lethe_test_uring_queued_writes", 16 * block as u64);
+        let mut access = match UringAccess::new(file, 4) {
+            Ok(a) => a,
+            // io_uring can be disabled in the kernel or by a sandbox
+            Err(_) => return,
+        };
+
+        for i in 0..16u8 {
+            access.write(&vec![i; block]).unwrap();
+        }
+        access.flush().unwrap();
+
+        access.seek(5 * block as u64).unwrap();
+        let mut buffer = vec![0u8; block];
+        assert_eq!(access.read(&mut buffer).unwrap(), block);
+        assert!(buffer.iter().all(|&b| b == 5));
+        assert_eq!(access.position().unwrap(), 6 * block as u64);
+
+        drop(access);
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(content
+            .chunks(block)
+            .enumerate()
+            .all(|(i, c)| c.iter().all(|&b| b == i as u8)));
+    }
+}
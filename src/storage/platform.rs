@@ -0,0 +1,224 @@
+#[cfg(test)]
+use crate::storage::{memory::InMemoryStorage, StorageDetails};
+use crate::storage::{DeviceUser, StorageAccess, StorageRef, System};
+use anyhow::Result;
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::collections::HashMap;
+#[cfg(test)]
+use std::rc::Rc;
+
+/// Metadata queries and operations on the devices attached to the system. Implemented by
+/// [`System`] for the current platform, and by [`MockPlatform`] so the logic built on top
+/// of it can be tested anywhere.
+pub trait Platform {
+    fn devices(&self) -> Result<Vec<Box<dyn StorageRef>>>;
+    fn access(&self, device: &dyn StorageRef) -> Result<Box<dyn StorageAccess>>;
+    /// Access keeping up to `queue_depth` writes in flight, where the platform supports it.
+    fn access_queued(
+        &self,
+        device: &dyn StorageRef,
+        _queue_depth: usize,
+    ) -> Result<Box<dyn StorageAccess>> {
+        self.access(device)
+    }
+    fn temperature(&self, device_id: &str) -> Option<f32>;
+    /// Other processes which have the device open, mounted or locked.
+    fn device_users(&self, device_id: &str) -> Vec<DeviceUser>;
+    /// Unmounts all filesystems on the device and its partitions and returns what
+    /// was unmounted.
+    fn unmount_filesystems(&self, device_id: &str) -> Result<Vec<String>>;
+    fn close_crypt_mappings(&self, device_id: &str) -> Result<Vec<String>>;
+    /// Spins the drive down right away.
+    fn standby(&self, device_id: &str) -> Result<()>;
+}
+
+impl Platform for System {
+    fn devices(&self) -> Result<Vec<Box<dyn StorageRef>>> {
+        Ok(System::get_storage_devices()?
+            .into_iter()
+            .map(|d| Box::new(d) as Box<dyn StorageRef>)
+            .collect())
+    }
+
+    fn access(&self, device: &dyn StorageRef) -> Result<Box<dyn StorageAccess>> {
+        Ok(Box::new(System::access(device)?))
+    }
+
+    fn access_queued(
+        &self,
+        device: &dyn StorageRef,
+        queue_depth: usize,
+    ) -> Result<Box<dyn StorageAccess>> {
+        System::access_queued(device, queue_depth)
+    }
+
+    fn temperature(&self, device_id: &str) -> Option<f32> {
+        System::temperature(device_id)
+    }
+
+    fn device_users(&self, device_id: &str) -> Vec<DeviceUser> {
+        System::device_users(device_id)
+    }
+
+    fn unmount_filesystems(&self, device_id: &str) -> Result<Vec<String>> {
+        System::unmount_filesystems(device_id)
+    }
+
+    fn close_crypt_mappings(&self, device_id: &str) -> Result<Vec<String>> {
+        System::close_crypt_mappings(device_id)
+    }
+
+    fn standby(&self, device_id: &str) -> Result<()> {
+        System::standby(device_id)
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub struct MockDevice {
+    pub id: String,
+    pub details: StorageDetails,
+    pub users: Vec<DeviceUser>,
+    /// Mount points unmounted by `unmount_filesystems`.
+    pub mounts: Vec<String>,
+}
+
+#[cfg(test)]
+impl StorageRef for MockDevice {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn details(&self) -> &StorageDetails {
+        &self.details
+    }
+}
+
+#[cfg(test)]
+/// Simulated platform with the given devices, each backed by zeroed memory. The data
+/// written and the unmounted filesystems are kept for the following calls.
+#[derive(Default)]
+pub struct MockPlatform {
+    devices: RefCell<Vec<MockDevice>>,
+    data: Rc<RefCell<HashMap<String, Vec<u8>>>>,
+}
+
+#[cfg(test)]
+impl MockPlatform {
+    pub fn new(devices: Vec<MockDevice>) -> Self {
+        MockPlatform {
+            devices: RefCell::new(devices),
+            data: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    /// Current content of the device.
+    pub fn data(&self, device_id: &str) -> Option<Vec<u8>> {
+        self.data.borrow().get(device_id).cloned().or_else(|| {
+            self.find(device_id)
+                .map(|d| vec![0; d.details.size as usize])
+        })
+    }
+
+    fn find(&self, device_id: &str) -> Option<MockDevice> {
+        self.devices
+            .borrow()
+            .iter()
+            .find(|d| d.id == device_id)
+            .cloned()
+    }
+
+    fn find_mut<R>(&self, device_id: &str, f: impl FnOnce(&mut MockDevice) -> R) -> Result<R> {
+        self.devices
+            .borrow_mut()
+            .iter_mut()
+            .find(|d| d.id == device_id)
+            .map(f)
+            .ok_or_else(|| anyhow!("Unknown device {}", device_id))
+    }
+}
+
+#[cfg(test)]
+impl Platform for MockPlatform {
+    fn devices(&self) -> Result<Vec<Box<dyn StorageRef>>> {
+        Ok(self
+            .devices
+            .borrow()
+            .iter()
+            .map(|d| Box::new(d.clone()) as Box<dyn StorageRef>)
+            .collect())
+    }
+
+    fn access(&self, device: &dyn StorageRef) -> Result<Box<dyn StorageAccess>> {
+        let data = self
+            .data(device.id())
+            .ok_or_else(|| anyhow!("Unknown device {}", device.id()))?;
+        Ok(Box::new(MockAccess {
+            id: device.id().to_string(),
+            storage: InMemoryStorage::with_data(data),
+            saved: Rc::clone(&self.data),
+        }))
+    }
+
+    fn temperature(&self, _device_id: &str) -> Option<f32> {
+        None
+    }
+
+    fn device_users(&self, device_id: &str) -> Vec<DeviceUser> {
+        self.find(device_id).map(|d| d.users).unwrap_or_default()
+    }
+
+    fn unmount_filesystems(&self, device_id: &str) -> Result<Vec<String>> {
+        self.find_mut(device_id, |d| {
+            d.details.mount_point = None;
+            d.users.retain(|u| !u.usage.starts_with("mounted"));
+            std::mem::take(&mut d.mounts)
+        })
+    }
+
+    fn close_crypt_mappings(&self, _device_id: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn standby(&self, device_id: &str) -> Result<()> {
+        self.find(device_id)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("Unknown device {}", device_id))
+    }
+}
+
+#[cfg(test)]
+struct MockAccess {
+    id: String,
+    storage: InMemoryStorage,
+    saved: Rc<RefCell<HashMap<String, Vec<u8>>>>,
+}
+
+#[cfg(test)]
+impl StorageAccess for MockAccess {
+    fn position(&mut self) -> Result<u64> {
+        self.storage.position()
+    }
+
+    fn seek(&mut self, position: u64) -> Result<u64> {
+        self.storage.seek(position)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        self.storage.read(buffer)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.storage.write(data)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.storage.flush()?;
+        self.saved
+            .borrow_mut()
+            .insert(self.id.clone(), self.storage.file.get_ref().clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,387 @@
+use crate::storage::{DeviceFilter, Platform, StorageAccess, StorageRef, System};
+use anyhow::{Context, Result};
+
+/// Source of storage devices which are not attached to the system directly, e.g. network
+/// volumes or custom lab hardware. Devices of a provider are identified by URI-style ids
+/// starting with its scheme, like `iscsi://target/lun0`.
+pub trait StorageProvider {
+    fn scheme(&self) -> &str;
+    fn devices(&self) -> Result<Vec<Box<dyn StorageRef>>>;
+    fn access(&self, device: &dyn StorageRef) -> Result<Box<dyn StorageAccess>>;
+}
+
+/// Enumerates and opens the devices of the platform and of the registered providers.
+pub struct StorageRegistry {
+    platform: Option<Box<dyn Platform>>,
+    providers: Vec<Box<dyn StorageProvider>>,
+    queue_depth: usize,
+    filter: DeviceFilter,
+}
+
+impl StorageRegistry {
+    /// Registry of the given providers only, without the system devices.
+    pub fn new(providers: Vec<Box<dyn StorageProvider>>) -> Result<StorageRegistry> {
+        let mut registry = StorageRegistry {
+            platform: None,
+            providers: Vec::new(),
+            queue_depth: 1,
+            filter: DeviceFilter::default(),
+        };
+        for p in providers {
+            registry.register(p)?;
+        }
+        Ok(registry)
+    }
+
+    /// Registry of the devices of the given platform, e.g. a simulated one.
+    pub fn with_platform(platform: Box<dyn Platform>) -> StorageRegistry {
+        StorageRegistry {
+            platform: Some(platform),
+            providers: Vec::new(),
+            queue_depth: 1,
+            filter: DeviceFilter::default(),
+        }
+    }
+
+    /// Number of writes kept in flight on the platform devices, 1 (the default) writes
+    /// synchronously.
+    pub fn set_queue_depth(&mut self, queue_depth: usize) {
+        self.queue_depth = queue_depth.max(1);
+    }
+
+    /// Hides the excluded (or not allowed) devices from `devices` and refuses to wipe
+    /// the excluded ones.
+    pub fn set_filter(&mut self, filter: DeviceFilter) {
+        self.filter = filter;
+    }
+
+    pub fn register(&mut self, provider: Box<dyn StorageProvider>) -> Result<()> {
+        let scheme = provider.scheme();
+        if scheme.is_empty()
+            || !scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        {
+            return Err(anyhow!("Invalid storage provider scheme: {}", scheme));
+        }
+        if self.find_provider(scheme).is_some() {
+            return Err(anyhow!(
+                "Storage provider for {} is already registered",
+                scheme
+            ));
+        }
+        self.providers.push(provider);
+        Ok(())
+    }
+
+    /// Platform devices followed by the devices of the registered providers.
+    pub fn devices(&self) -> Result<Vec<Box<dyn StorageRef>>> {
+        let mut devices: Vec<Box<dyn StorageRef>> = match &self.platform {
+            Some(p) => p.devices()?,
+            None => Vec::new(),
+        };
+
+        for p in &self.providers {
+            for d in p.devices()? {
+                if uri_scheme(d.id()) != Some(p.scheme()) {
+                    return Err(anyhow!(
+                        "Device id {} should start with {}://",
+                        d.id(),
+                        p.scheme()
+                    ));
+                }
+                devices.push(d);
+            }
+        }
+
+        Ok(self.filter.apply(devices))
+    }
+
+    pub fn access(&self, device: &dyn StorageRef) -> Result<Box<dyn StorageAccess>> {
+        match uri_scheme(device.id()) {
+            Some(scheme) => self
+                .find_provider(scheme)
+                .ok_or_else(|| anyhow!("No storage provider for {}", device.id()))?
+                .access(device),
+            None if self.queue_depth > 1 => {
+                self.platform()?.access_queued(device, self.queue_depth)
+            }
+            None => self.platform()?.access(device),
+        }
+    }
+
+    pub fn platform(&self) -> Result<&dyn Platform> {
+        self.platform
+            .as_deref()
+            .ok_or_else(|| anyhow!("No platform devices in the registry"))
+    }
+
+    /// Checks that the device is allowed to be wiped, without changing anything. Done before
+    /// the wipe is confirmed, `prepare_for_wipe` checks it again.
+    pub fn check_for_wipe(&self, device: &dyn StorageRef, force_system: bool) -> Result<()> {
+        let id = device.id();
+        if self.filter.excludes(device) {
+            return Err(anyhow!(
+                "{} is excluded from wiping by the configuration",
+                id
+            ));
+        }
+        if device.details().is_boot && !force_system {
+            return Err(anyhow!(
+                "{} holds the running system, wiping it would make the system unusable. \
+                 Use --force-system to wipe it anyway.",
+                id
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the device can be wiped safely, unmounting its filesystems first if
+    /// requested. Returns the unmounted filesystems.
+    pub fn prepare_for_wipe(
+        &self,
+        device: &dyn StorageRef,
+        force_system: bool,
+        unmount: bool,
+    ) -> Result<Vec<String>> {
+        self.check_for_wipe(device, force_system)?;
+
+        let id = device.id();
+        let platform = match (uri_scheme(id), &self.platform) {
+            (None, Some(p)) => p.as_ref(),
+            _ => return Ok(Vec::new()),
+        };
+
+        let unmounted = if unmount {
+            platform
+                .unmount_filesystems(id)
+                .context(format!("Unable to unmount the filesystems on {}", id))?
+        } else {
+            Vec::new()
+        };
+
+        let users = platform.device_users(id);
+        if !users.is_empty() {
+            let hint = if !unmount && users.iter().any(|u| u.usage.starts_with("mounted")) {
+                "\nUse --unmount to unmount its filesystems before wiping."
+            } else {
+                ""
+            };
+            return Err(anyhow!(
+                "{} is in use by other processes:\n{}{}",
+                id,
+                users
+                    .iter()
+                    .map(|u| format!("  {}", u))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                hint
+            ));
+        }
+
+        Ok(unmounted)
+    }
+
+    fn find_provider(&self, scheme: &str) -> Option<&dyn StorageProvider> {
+        self.providers
+            .iter()
+            .find(|p| p.scheme() == scheme)
+            .map(|p| p.as_ref())
+    }
+}
+
+impl Default for StorageRegistry {
+    fn default() -> Self {
+        StorageRegistry::with_platform(Box::new(System {}))
+    }
+}
+
+fn uri_scheme(id: &str) -> Option<&str> {
+    id.find("://").map(|i| &id[..i])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::memory::InMemoryStorage;
+    use crate::storage::platform::{MockDevice, MockPlatform};
+    use crate::storage::{DevicePattern, DeviceUser, StorageDetails};
+
+    struct MemoryRef {
+        id: String,
+        details: StorageDetails,
+    }
+
+    impl StorageRef for MemoryRef {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn details(&self) -> &StorageDetails {
+            &self.details
+        }
+    }
+
+    struct MemoryProvider {
+        scheme: &'static str,
+        ids: Vec<&'static str>,
+    }
+
+    impl StorageProvider for MemoryProvider {
+        fn scheme(&self) -> &str {
+            self.scheme
+        }
+
+        fn devices(&self) -> Result<Vec<Box<dyn StorageRef>>> {
+            Ok(self
+                .ids
+                .iter()
+                .map(|id| {
+                    Box::new(MemoryRef {
+                        id: id.to_string(),
+                        details: StorageDetails {
+                            size: 1024,
+                            ..Default::default()
+                        },
+                    }) as Box<dyn StorageRef>
+                })
+                .collect())
+        }
+
+        fn access(&self, device: &dyn StorageRef) -> Result<Box<dyn StorageAccess>> {
+            let size = device.details().size as usize;
+            Ok(Box::new(InMemoryStorage::with_data(vec![0; size])))
+        }
+    }
+
+    #[test]
+    fn test_registry_routes_by_scheme() {
+        let registry = StorageRegistry::new(vec![Box::new(MemoryProvider {
+            scheme: "mem",
+            ids: vec!["mem://a", "mem://b"],
+        })])
+        .unwrap();
+
+        let devices = registry.devices().unwrap();
+        let ids: Vec<_> = devices.iter().map(|d| d.id()).collect();
+        assert_eq!(ids, vec!["mem://a", "mem://b"]);
+
+        let mut access = registry.access(devices[1].as_ref()).unwrap();
+        access.write(&[1, 2, 3]).unwrap();
+        access.seek(0).unwrap();
+        let mut buf = [0; 3];
+        access.read(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3]);
+
+        let unknown = MemoryRef {
+            id: "nbd://host/export".to_string(),
+            details: StorageDetails::default(),
+        };
+        assert!(registry.access(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_registry_with_mock_platform() {
+        let device = |id: &str, is_boot, users: Vec<&str>, mounts: Vec<&str>| MockDevice {
+            id: id.to_string(),
+            details: StorageDetails {
+                size: 4096,
+                is_boot,
+                ..Default::default()
+            },
+            users: users
+                .into_iter()
+                .map(|u| DeviceUser {
+                    pid: None,
+                    name: "kernel".to_string(),
+                    usage: u.to_string(),
+                })
+                .collect(),
+            mounts: mounts.into_iter().map(String::from).collect(),
+        };
+        let platform = MockPlatform::new(vec![
+            device("/dev/sda", true, vec!["mounted at /"], vec!["/"]),
+            device("/dev/sdb", false, vec!["mounted at /mnt"], vec!["/mnt"]),
+            device("/dev/sdc", false, vec!["holds a swap area"], vec![]),
+        ]);
+        let mut registry = StorageRegistry::with_platform(Box::new(platform));
+        registry
+            .register(Box::new(MemoryProvider {
+                scheme: "mem",
+                ids: vec!["mem://a"],
+            }))
+            .unwrap();
+
+        let devices = registry.devices().unwrap();
+        let ids: Vec<_> = devices.iter().map(|d| d.id()).collect();
+        assert_eq!(ids, vec!["/dev/sda", "/dev/sdb", "/dev/sdc", "mem://a"]);
+
+        // the system drive isn't touched without forcing
+        let err = registry
+            .prepare_for_wipe(devices[0].as_ref(), false, true)
+            .unwrap_err();
+        assert!(err.to_string().contains("--force-system"));
+        assert!(registry.check_for_wipe(devices[0].as_ref(), false).is_err());
+        assert!(registry.check_for_wipe(devices[1].as_ref(), false).is_ok());
+        assert_eq!(
+            registry.platform().unwrap().device_users("/dev/sda").len(),
+            1
+        );
+
+        let err = registry
+            .prepare_for_wipe(devices[1].as_ref(), false, false)
+            .unwrap_err();
+        assert!(err.to_string().contains("--unmount"));
+        assert_eq!(
+            registry
+                .prepare_for_wipe(devices[1].as_ref(), false, true)
+                .unwrap(),
+            vec!["/mnt"]
+        );
+        assert!(registry
+            .prepare_for_wipe(devices[2].as_ref(), false, true)
+            .is_err());
+        assert!(registry
+            .prepare_for_wipe(devices[3].as_ref(), false, false)
+            .is_ok());
+
+        let mut access = registry.access(devices[1].as_ref()).unwrap();
+        access.write(&[1, 2, 3]).unwrap();
+        access.flush().unwrap();
+        let mut access = registry.access(devices[1].as_ref()).unwrap();
+        let mut buf = [0; 4];
+        access.read(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 0]);
+
+        registry.set_filter(DeviceFilter {
+            exclude: vec![DevicePattern::parse("mem://*").unwrap()],
+            allow: Vec::new(),
+        });
+        let ids: Vec<_> = registry
+            .devices()
+            .unwrap()
+            .iter()
+            .map(|d| d.id().to_string())
+            .collect();
+        assert_eq!(ids, vec!["/dev/sda", "/dev/sdb", "/dev/sdc"]);
+        let err = registry
+            .prepare_for_wipe(devices[3].as_ref(), false, false)
+            .unwrap_err();
+        assert!(err.to_string().contains("excluded"));
+    }
+
+    #[test]
+    fn test_registry_validation() {
+        let provider =
+            |scheme, ids| Box::new(MemoryProvider { scheme, ids }) as Box<dyn StorageProvider>;
+
+        assert!(StorageRegistry::new(vec![provider("", vec![])]).is_err());
+        assert!(StorageRegistry::new(vec![provider("a/b", vec![])]).is_err());
+        assert!(
+            StorageRegistry::new(vec![provider("mem", vec![]), provider("mem", vec![])]).is_err()
+        );
+
+        let registry = StorageRegistry::new(vec![provider("mem", vec!["/dev/sda"])]).unwrap();
+        assert!(registry.devices().is_err());
+    }
+}
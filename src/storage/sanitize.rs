@@ -0,0 +1,331 @@
+//! The SANITIZE commands of NVMe and ATA drives, which erase all the data including the
+//! caches and the spare blocks a regular overwrite can't reach.
+
+use crate::storage::BusType;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SanitizeMode {
+    /// Changes the media encryption key, the fastest.
+    Crypto,
+    /// Erases all the blocks of the media.
+    Block,
+    /// Overwrites all the blocks of the media with zeroes.
+    Overwrite,
+}
+
+impl SanitizeMode {
+    pub const NAMES: &'static [&'static str] = &["crypto", "block", "overwrite"];
+}
+
+impl std::fmt::Display for SanitizeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SanitizeMode::Crypto => f.write_str("crypto erase"),
+            SanitizeMode::Block => f.write_str("block erase"),
+            SanitizeMode::Overwrite => f.write_str("overwrite"),
+        }
+    }
+}
+
+impl std::str::FromStr for SanitizeMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "crypto" => Ok(SanitizeMode::Crypto),
+            "block" => Ok(SanitizeMode::Block),
+            "overwrite" => Ok(SanitizeMode::Overwrite),
+            _ => Err(anyhow!("Use one of {}.", SanitizeMode::NAMES.join(", "))),
+        }
+    }
+}
+
+/// Tool the commands are sent with: `nvme` (nvme-cli) or `hdparm`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SanitizeInterface {
+    Nvme,
+    Ata,
+}
+
+impl SanitizeInterface {
+    /// NVMe drives use their own commands, anything else is tried as ATA (which includes
+    /// SATA drives behind most USB bridges).
+    pub fn of(bus_type: BusType) -> SanitizeInterface {
+        match bus_type {
+            BusType::Nvme => SanitizeInterface::Nvme,
+            _ => SanitizeInterface::Ata,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizeSupport {
+    pub interface: SanitizeInterface,
+    /// Empty if the drive doesn't support SANITIZE at all.
+    pub modes: Vec<SanitizeMode>,
+}
+
+impl SanitizeSupport {
+    /// Reads the capabilities of the drive, `None` if the tool isn't installed or doesn't
+    /// recognize the drive.
+    pub fn read(device_id: &str, bus_type: BusType) -> Option<SanitizeSupport> {
+        let interface = SanitizeInterface::of(bus_type);
+        let modes = match interface {
+            SanitizeInterface::Nvme => {
+                Self::parse_nvme_sanicap(&run("nvme", &["id-ctrl", device_id]).ok()?)?
+            }
+            SanitizeInterface::Ata => {
+                Self::parse_ata_identify(&run("hdparm", &["-I", device_id]).ok()?)?
+            }
+        };
+        Some(SanitizeSupport { interface, modes })
+    }
+
+    /// Parses the `sanicap : 0x3` line of `nvme id-ctrl`.
+    pub fn parse_nvme_sanicap(output: &str) -> Option<Vec<SanitizeMode>> {
+        let regex = Regex::new(r"(?m)^sanicap\s*:\s*0x(?P<caps>[0-9a-fA-F]+)").unwrap();
+        let caps = u32::from_str_radix(&regex.captures(output)?["caps"], 16).ok()?;
+        Some(
+            [
+                (0, SanitizeMode::Crypto),
+                (1, SanitizeMode::Block),
+                (2, SanitizeMode::Overwrite),
+            ]
+            .iter()
+            .filter(|(bit, _)| caps & (1 << bit) != 0)
+            .map(|(_, mode)| *mode)
+            .collect(),
+        )
+    }
+
+    /// Parses the supported commands listed by `hdparm -I`.
+    pub fn parse_ata_identify(output: &str) -> Option<Vec<SanitizeMode>> {
+        if !output.contains("Commands/features:") {
+            return None;
+        }
+        if !output.contains("SANITIZE feature set") {
+            return Some(Vec::new());
+        }
+        Some(
+            [
+                ("CRYPTO_SCRAMBLE_EXT command", SanitizeMode::Crypto),
+                ("BLOCK_ERASE_EXT command", SanitizeMode::Block),
+                ("OVERWRITE_EXT command", SanitizeMode::Overwrite),
+            ]
+            .iter()
+            .filter(|(command, _)| output.contains(command))
+            .map(|(_, mode)| *mode)
+            .collect(),
+        )
+    }
+
+    pub fn supports(&self, mode: SanitizeMode) -> bool {
+        self.modes.contains(&mode)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SanitizeStatus {
+    /// No sanitize operation was run, or its result isn't reported.
+    Idle,
+    /// Done fraction, from 0 to 1.
+    InProgress(f32),
+    Completed,
+    Failed,
+}
+
+impl SanitizeStatus {
+    /// Parses the `(SPROG)` and `(SSTAT)` lines of `nvme sanitize-log`.
+    pub fn parse_nvme_log(output: &str) -> Option<SanitizeStatus> {
+        let progress = Regex::new(r"\(SPROG\)\s*:\s*(?P<value>\d+)").unwrap();
+        let status = Regex::new(r"\(SSTAT\)\s*:\s*0x(?P<value>[0-9a-fA-F]+)").unwrap();
+        let status = u16::from_str_radix(&status.captures(output)?["value"], 16).ok()?;
+        Some(match status & 0x7 {
+            1 | 4 => SanitizeStatus::Completed,
+            2 => {
+                let done: u32 = progress.captures(output)?["value"].parse().ok()?;
+                SanitizeStatus::InProgress(done as f32 / 65536.0)
+            }
+            3 => SanitizeStatus::Failed,
+            _ => SanitizeStatus::Idle,
+        })
+    }
+
+    /// Parses the `State:` and `Progress:` lines of `hdparm --sanitize-status`.
+    pub fn parse_ata_status(output: &str) -> Option<SanitizeStatus> {
+        let state = Regex::new(r"State:\s*(?P<state>SD\d)").unwrap();
+        let progress = Regex::new(r"Progress:\s*0x(?P<value>[0-9a-fA-F]+)").unwrap();
+        Some(match &state.captures(output)?["state"] {
+            "SD2" => {
+                let done = progress
+                    .captures(output)
+                    .and_then(|c| u32::from_str_radix(&c["value"], 16).ok())
+                    .unwrap_or(0);
+                SanitizeStatus::InProgress(done as f32 / 65536.0)
+            }
+            _ if output.contains("Completed Without Error") => SanitizeStatus::Completed,
+            _ if output.to_lowercase().contains("failed") => SanitizeStatus::Failed,
+            _ => SanitizeStatus::Idle,
+        })
+    }
+}
+
+/// The drive sanitize operations are sent to, implemented by [`DriveSanitizer`] and by
+/// test doubles.
+pub trait Sanitizer {
+    fn start(&self, mode: SanitizeMode) -> Result<()>;
+    fn status(&self) -> Result<SanitizeStatus>;
+}
+
+/// Sends the commands to the drive with `nvme` or `hdparm`.
+pub struct DriveSanitizer {
+    pub device_id: String,
+    pub interface: SanitizeInterface,
+}
+
+impl Sanitizer for DriveSanitizer {
+    fn start(&self, mode: SanitizeMode) -> Result<()> {
+        let device = self.device_id.as_str();
+        match self.interface {
+            SanitizeInterface::Nvme => {
+                let action = match mode {
+                    SanitizeMode::Block => "--sanact=2",
+                    SanitizeMode::Overwrite => "--sanact=3",
+                    SanitizeMode::Crypto => "--sanact=4",
+                };
+                run("nvme", &["sanitize", device, action])
+            }
+            SanitizeInterface::Ata => {
+                let mut args = vec!["--yes-i-know-what-i-am-doing"];
+                match mode {
+                    SanitizeMode::Crypto => args.push("--sanitize-crypto-scramble"),
+                    SanitizeMode::Block => args.push("--sanitize-block-erase"),
+                    SanitizeMode::Overwrite => {
+                        args.extend(&["--sanitize-overwrite", "hex:00000000"])
+                    }
+                }
+                args.push(device);
+                run("hdparm", &args)
+            }
+        }
+        .map(|_| ())
+        .context(format!("Unable to start the {}", mode))
+    }
+
+    fn status(&self) -> Result<SanitizeStatus> {
+        let device = self.device_id.as_str();
+        let status = match self.interface {
+            SanitizeInterface::Nvme => {
+                SanitizeStatus::parse_nvme_log(&run("nvme", &["sanitize-log", device])?)
+            }
+            SanitizeInterface::Ata => {
+                SanitizeStatus::parse_ata_status(&run("hdparm", &["--sanitize-status", device])?)
+            }
+        };
+        status.ok_or_else(|| anyhow!("Unexpected sanitize status of {}", device))
+    }
+}
+
+fn run(tool: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(tool)
+        .args(args)
+        .output()
+        .context(format!("Unable to run {}", tool))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} failed: {}",
+            tool,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_support() {
+        let nvme = "\
+NVME Identify Controller:
+vid       : 0x144d
+sanicap   : 0x60000003
+hmminds   : 0
+";
+        assert_eq!(
+            SanitizeSupport::parse_nvme_sanicap(nvme),
+            Some(vec![SanitizeMode::Crypto, SanitizeMode::Block])
+        );
+        assert_eq!(SanitizeSupport::parse_nvme_sanicap("sanicap   : 0\n"), None);
+        assert_eq!(
+            SanitizeSupport::parse_nvme_sanicap("sanicap   : 0x0\n"),
+            Some(vec![])
+        );
+
+        let ata = "\
+Commands/features:
+\tEnabled\tSupported:
+\t   *\tSMART feature set
+\t   *\tSANITIZE feature set
+\t   *\tCRYPTO_SCRAMBLE_EXT command
+\t   *\tBLOCK_ERASE_EXT command
+";
+        assert_eq!(
+            SanitizeSupport::parse_ata_identify(ata),
+            Some(vec![SanitizeMode::Crypto, SanitizeMode::Block])
+        );
+        assert_eq!(
+            SanitizeSupport::parse_ata_identify("Commands/features:\n\t   *\tSMART feature set\n"),
+            Some(vec![])
+        );
+        assert_eq!(SanitizeSupport::parse_ata_identify(""), None);
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let log = |sprog: u32, sstat: u16| {
+            format!(
+                "Sanitize Progress                      (SPROG) :  {}\nSanitize Status                        (SSTAT) :  {:#x}\n",
+                sprog, sstat
+            )
+        };
+        assert_eq!(
+            SanitizeStatus::parse_nvme_log(&log(16384, 0x2)),
+            Some(SanitizeStatus::InProgress(0.25))
+        );
+        assert_eq!(
+            SanitizeStatus::parse_nvme_log(&log(65535, 0x101)),
+            Some(SanitizeStatus::Completed)
+        );
+        assert_eq!(
+            SanitizeStatus::parse_nvme_log(&log(65535, 0x3)),
+            Some(SanitizeStatus::Failed)
+        );
+        assert_eq!(
+            SanitizeStatus::parse_nvme_log(&log(65535, 0x0)),
+            Some(SanitizeStatus::Idle)
+        );
+
+        assert_eq!(
+            SanitizeStatus::parse_ata_status(
+                "Sanitize status:\n    State:    SD2 Sanitize operation In Process\n    Progress: 0x8000(50%)\n"
+            ),
+            Some(SanitizeStatus::InProgress(0.5))
+        );
+        assert_eq!(
+            SanitizeStatus::parse_ata_status(
+                "Sanitize status:\n    State:    SD0 Sanitize Idle\n    Last Sanitize Operation Completed Without Error\n"
+            ),
+            Some(SanitizeStatus::Completed)
+        );
+        assert_eq!(
+            SanitizeStatus::parse_ata_status("    State:    SD0 Sanitize Idle\n"),
+            Some(SanitizeStatus::Idle)
+        );
+        assert_eq!(SanitizeStatus::parse_ata_status("SG_IO: bad sense"), None);
+    }
+}
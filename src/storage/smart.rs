@@ -0,0 +1,96 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::process::Command;
+
+/// Raw SMART attribute values as reported by `smartctl`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SmartAttributes {
+    pub values: BTreeMap<String, u64>,
+}
+
+impl SmartAttributes {
+    /// Reads the attributes using `smartctl` if it's installed and the device supports SMART.
+    pub fn read(device_id: &str) -> Option<SmartAttributes> {
+        let output = Command::new("smartctl")
+            .arg("-A")
+            .arg(device_id)
+            .output()
+            .ok()?;
+
+        let attributes = Self::parse(&String::from_utf8_lossy(&output.stdout));
+        if attributes.values.is_empty() {
+            None
+        } else {
+            Some(attributes)
+        }
+    }
+
+    /// Parses both the ATA attributes table and the NVMe health log formats.
+    pub fn parse(output: &str) -> SmartAttributes {
+        let ata_regex =
+            Regex::new(r"^\s*\d+\s+(?P<name>\S+)\s+0x[0-9a-fA-F]+(\s+\S+){6}\s+(?P<raw>\d+)")
+                .unwrap();
+        let nvme_regex = Regex::new(r"^(?P<name>[A-Za-z][\w /-]*):\s+(?P<raw>[\d,]+)\b").unwrap();
+
+        let values = output
+            .lines()
+            .filter_map(|l| ata_regex.captures(l).or_else(|| nvme_regex.captures(l)))
+            .filter_map(|c| {
+                let raw = c["raw"].replace(',', "").parse().ok()?;
+                Some((c["name"].trim().to_string(), raw))
+            })
+            .collect();
+
+        SmartAttributes { values }
+    }
+
+    /// Attributes which changed since the earlier snapshot, with their old and new values.
+    pub fn changes_since(&self, earlier: &SmartAttributes) -> Vec<(String, u64, u64)> {
+        self.values
+            .iter()
+            .filter_map(|(k, v)| match earlier.values.get(k) {
+                Some(old) if old != v => Some((k.clone(), *old, *v)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_ata_and_nvme() {
+        let ata = "\
+ID# ATTRIBUTE_NAME          FLAG     VALUE WORST THRESH TYPE      UPDATED  WHEN_FAILED RAW_VALUE
+  5 Reallocated_Sector_Ct   0x0033   100   100   010    Pre-fail  Always       -       0
+194 Temperature_Celsius     0x0022   067   052   000    Old_age   Always       -       33 (Min/Max 20/48)
+";
+        let nvme = "\
+Critical Warning:                   0x00
+Temperature:                        35 Celsius
+Data Units Written:                 1,234,567 [632 GB]
+Media and Data Integrity Errors:    0
+";
+        let a = SmartAttributes::parse(ata);
+        assert_eq!(a.values.get("Reallocated_Sector_Ct"), Some(&0));
+        assert_eq!(a.values.get("Temperature_Celsius"), Some(&33));
+
+        let n = SmartAttributes::parse(nvme);
+        assert_eq!(n.values.get("Temperature"), Some(&35));
+        assert_eq!(n.values.get("Data Units Written"), Some(&1234567));
+        assert_eq!(n.values.get("Media and Data Integrity Errors"), Some(&0));
+        assert_eq!(n.values.get("Critical Warning"), None);
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let before = SmartAttributes::parse("Temperature: 35 Celsius\nPower Cycles: 10\n");
+        let after = SmartAttributes::parse("Temperature: 41 Celsius\nPower Cycles: 10\n");
+        assert_eq!(
+            after.changes_since(&before),
+            vec![("Temperature".to_string(), 35, 41)]
+        );
+    }
+}
@@ -1,12 +1,16 @@
 #![cfg(windows)]
-use crate::storage::{StorageAccess, StorageError};
+use crate::storage::{Geometry, StorageAccess, StorageError};
 use anyhow::{Context, Result};
 use std::{io, mem, ptr};
 use widestring::WideCString;
 use winapi::_core::ptr::null_mut;
 use winapi::shared::minwindef::{DWORD, LPVOID};
+use winapi::shared::ntdef::{LONGLONG, ULONGLONG};
 use winapi::shared::winerror::{
-    ERROR_CRC, ERROR_READ_FAULT, ERROR_SECTOR_NOT_FOUND, ERROR_SEEK, ERROR_WRITE_FAULT,
+    ERROR_ACCESS_DENIED, ERROR_BUSY, ERROR_CRC, ERROR_FILE_NOT_FOUND, ERROR_INVALID_FUNCTION,
+    ERROR_LOCK_VIOLATION, ERROR_NOT_READY, ERROR_NOT_SUPPORTED, ERROR_PATH_NOT_FOUND,
+    ERROR_PRIVILEGE_NOT_HELD, ERROR_READ_FAULT, ERROR_SECTOR_NOT_FOUND, ERROR_SEEK,
+    ERROR_SHARING_VIOLATION, ERROR_WRITE_FAULT,
 };
 use winapi::um::fileapi::*;
 use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
@@ -21,6 +25,35 @@ use winapi::um::winnt::{
     LARGE_INTEGER,
 };
 
+const DEVICE_DSM_ACTION_TRIM: DWORD = 1;
+
+winapi::STRUCT! {
+    #[allow(non_snake_case)]
+    struct DEVICE_MANAGE_DATA_SET_ATTRIBUTES {
+        Size: DWORD,
+        Action: DWORD,
+        Flags: DWORD,
+        ParameterBlockOffset: DWORD,
+        ParameterBlockLength: DWORD,
+        DataSetRangesOffset: DWORD,
+        DataSetRangesLength: DWORD,
+    }
+}
+
+winapi::STRUCT! {
+    #[allow(non_snake_case)]
+    struct DEVICE_DATA_SET_RANGE {
+        StartingOffset: LONGLONG,
+        LengthInBytes: ULONGLONG,
+    }
+}
+
+#[repr(C)]
+struct TrimRequest {
+    attributes: DEVICE_MANAGE_DATA_SET_ATTRIBUTES,
+    range: DEVICE_DATA_SET_RANGE,
+}
+
 pub struct DeviceFile {
     is_locked: bool,
     pub handle: HANDLE,
@@ -56,7 +89,7 @@ impl DeviceFile {
             );
 
             if handle == INVALID_HANDLE_VALUE {
-                return Err(io::Error::last_os_error())
+                return Err(StorageError::from_open(io::Error::last_os_error()))
                     .context(format!("Cannot open device {}.", path));
             }
 
@@ -74,10 +107,28 @@ impl DeviceFile {
                     null_mut(),
                 ) == 0
                 {
-                    return Err(io::Error::last_os_error())
-                        .context(format!("Cannot lock device {}.", path));
+                    // a volume in use can't be locked, which is reported as access denied
+                    let err = match io::Error::last_os_error() {
+                        e if e.raw_os_error() == Some(ERROR_ACCESS_DENIED as i32) => {
+                            Some(StorageError::Busy(e))
+                        }
+                        // RAW disks without any volumes have nothing to lock
+                        e if matches!(
+                            e.raw_os_error().map(|c| c as DWORD),
+                            Some(ERROR_INVALID_FUNCTION) | Some(ERROR_NOT_SUPPORTED)
+                        ) =>
+                        {
+                            None
+                        }
+                        e => Some(StorageError::from_open(e)),
+                    };
+                    if let Some(err) = err {
+                        CloseHandle(handle);
+                        return Err(err).context(format!("Cannot lock device {}.", path));
+                    }
+                } else {
+                    is_locked = true;
                 }
-                is_locked = true;
             }
 
             Ok(DeviceFile { handle, is_locked })
@@ -100,6 +151,24 @@ impl StorageError {
             _ => StorageError::Other(err),
         }
     }
+
+    fn from_open(err: std::io::Error) -> StorageError {
+        match err.raw_os_error().map(|c| c as DWORD) {
+            Some(ERROR_SHARING_VIOLATION) | Some(ERROR_LOCK_VIOLATION) | Some(ERROR_BUSY) => {
+                StorageError::Busy(err)
+            }
+            Some(ERROR_ACCESS_DENIED) | Some(ERROR_PRIVILEGE_NOT_HELD) => {
+                StorageError::PermissionDenied(err)
+            }
+            Some(ERROR_FILE_NOT_FOUND) | Some(ERROR_PATH_NOT_FOUND) | Some(ERROR_NOT_READY) => {
+                StorageError::NotFound(err)
+            }
+            Some(ERROR_NOT_SUPPORTED) | Some(ERROR_INVALID_FUNCTION) => {
+                StorageError::Unsupported(err)
+            }
+            _ => StorageError::Other(err),
+        }
+    }
 }
 
 impl Drop for DeviceFile {
@@ -202,4 +271,87 @@ impl StorageAccess for DeviceFile {
             Ok(())
         }
     }
+
+    fn discard(&mut self, position: u64, length: u64) -> Result<()> {
+        let mut request = TrimRequest {
+            attributes: DEVICE_MANAGE_DATA_SET_ATTRIBUTES {
+                Size: mem::size_of::<DEVICE_MANAGE_DATA_SET_ATTRIBUTES>() as DWORD,
+                Action: DEVICE_DSM_ACTION_TRIM,
+                Flags: 0,
+                ParameterBlockOffset: 0,
+                ParameterBlockLength: 0,
+                DataSetRangesOffset: mem::size_of::<DEVICE_MANAGE_DATA_SET_ATTRIBUTES>() as DWORD,
+                DataSetRangesLength: mem::size_of::<DEVICE_DATA_SET_RANGE>() as DWORD,
+            },
+            range: DEVICE_DATA_SET_RANGE {
+                StartingOffset: position as LONGLONG,
+                LengthInBytes: length,
+            },
+        };
+
+        unsafe {
+            let mut returned: DWORD = 0;
+            if DeviceIoControl(
+                self.handle,
+                winioctl::IOCTL_STORAGE_MANAGE_DATA_SET_ATTRIBUTES,
+                &mut request as *mut _ as LPVOID,
+                mem::size_of::<TrimRequest>() as DWORD,
+                null_mut(),
+                0,
+                &mut returned,
+                null_mut(),
+            ) == 0
+            {
+                return Err(StorageError::from(io::Error::last_os_error()))
+                    .context("Unable to discard blocks.");
+            }
+            Ok(())
+        }
+    }
+
+    fn geometry(&mut self) -> Result<Option<Geometry>> {
+        unsafe {
+            let mut returned: DWORD = 0;
+            let mut length: winioctl::GET_LENGTH_INFORMATION = mem::zeroed();
+            if DeviceIoControl(
+                self.handle,
+                winioctl::IOCTL_DISK_GET_LENGTH_INFO,
+                null_mut(),
+                0,
+                &mut length as *mut _ as LPVOID,
+                mem::size_of::<winioctl::GET_LENGTH_INFORMATION>() as DWORD,
+                &mut returned,
+                null_mut(),
+            ) == 0
+            {
+                return Err(io::Error::last_os_error()).context("Unable to get the device size.");
+            }
+
+            let mut geometry: winioctl::DISK_GEOMETRY = mem::zeroed();
+            if DeviceIoControl(
+                self.handle,
+                winioctl::IOCTL_DISK_GET_DRIVE_GEOMETRY,
+                null_mut(),
+                0,
+                &mut geometry as *mut _ as LPVOID,
+                mem::size_of::<winioctl::DISK_GEOMETRY>() as DWORD,
+                &mut returned,
+                null_mut(),
+            ) == 0
+            {
+                return Err(io::Error::last_os_error())
+                    .context("Unable to get the device geometry.");
+            }
+
+            let physical_sector_size = super::meta::get_alignment_descriptor(self)
+                .map(|a| a.BytesPerPhysicalSector)
+                .unwrap_or(geometry.BytesPerSector);
+
+            Ok(Some(Geometry {
+                size: *length.Length.QuadPart() as u64,
+                sector_size: geometry.BytesPerSector,
+                physical_sector_size,
+            }))
+        }
+    }
 }
@@ -1,5 +1,6 @@
 extern crate winapi;
 
+use std::convert::TryInto;
 use std::slice;
 use std::{io, mem, ptr};
 
@@ -7,12 +8,15 @@ use anyhow::{Context, Result};
 use libc;
 use widestring::WideCString;
 use winapi::_core::ptr::null_mut;
+use winapi::shared::guiddef::GUID;
 use winapi::shared::minwindef::*;
 use winapi::um::handleapi::INVALID_HANDLE_VALUE;
 use winapi::um::setupapi::*;
-use winapi::um::winioctl::GUID_DEVINTERFACE_DISK;
-use winapi::um::winnt::{PVOID, WCHAR};
-use winapi::um::{fileapi, ioapiset, winioctl};
+use winapi::um::winioctl::{
+    GUID_DEVINTERFACE_CDROM, GUID_DEVINTERFACE_DISK, GUID_DEVINTERFACE_FLOPPY,
+};
+use winapi::um::winnt::{BOOLEAN, PVOID, WCHAR};
+use winapi::um::{fileapi, ioapiset, sysinfoapi, winioctl};
 
 use windows::access::*;
 
@@ -103,8 +107,30 @@ impl Drop for DeviceInterfaceDetailData {
     }
 }
 
+/// Device interface classes listed as storage devices. Most drives, including card readers
+/// and USB sticks, are disks, optical and floppy drives have classes of their own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DeviceClass {
+    Disk,
+    CdRom,
+    Floppy,
+}
+
+impl DeviceClass {
+    const ALL: [DeviceClass; 3] = [DeviceClass::Disk, DeviceClass::CdRom, DeviceClass::Floppy];
+
+    fn guid(self) -> &'static GUID {
+        match self {
+            DeviceClass::Disk => &GUID_DEVINTERFACE_DISK,
+            DeviceClass::CdRom => &GUID_DEVINTERFACE_CDROM,
+            DeviceClass::Floppy => &GUID_DEVINTERFACE_FLOPPY,
+        }
+    }
+}
+
 pub struct DiskDeviceEnumerator {
-    device_info_list: HDEVINFO,
+    classes: Vec<(DeviceClass, HDEVINFO)>,
+    class_index: usize,
     device_index: DWORD,
     volumes: Vec<(String, Vec<VolumeExtent>)>,
 }
@@ -113,46 +139,48 @@ impl DiskDeviceEnumerator {
     pub fn new() -> Result<Self> {
         let volumes = get_volumes()?;
 
-        let device_info_list = unsafe {
-            SetupDiGetClassDevsW(
-                &GUID_DEVINTERFACE_DISK,
-                ptr::null(),
-                ptr::null_mut(),
-                DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
-            )
-        };
-        if device_info_list == INVALID_HANDLE_VALUE {
-            return Err(anyhow!("Unable to initialize disk device enumeration"));
+        let mut classes = Vec::new();
+        for class in DeviceClass::ALL.iter() {
+            let device_info_list = unsafe {
+                SetupDiGetClassDevsW(
+                    class.guid(),
+                    ptr::null(),
+                    ptr::null_mut(),
+                    DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
+                )
+            };
+            if device_info_list == INVALID_HANDLE_VALUE {
+                // the disks come first, nothing to clean up yet
+                if *class == DeviceClass::Disk {
+                    return Err(anyhow!("Unable to initialize disk device enumeration"));
+                }
+                continue;
+            }
+            classes.push((*class, device_info_list));
         }
 
         Ok(DiskDeviceEnumerator {
-            device_info_list,
+            classes,
+            class_index: 0,
             device_index: 0,
             volumes,
         })
     }
-}
-
-impl Drop for DiskDeviceEnumerator {
-    fn drop(&mut self) {
-        unsafe {
-            SetupDiDestroyDeviceInfoList(self.device_info_list);
-        }
-    }
-}
-
-impl Iterator for DiskDeviceEnumerator {
-    type Item = Vec<DiskDeviceInfo>;
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Path of the next device interface of the class, `None` after the last one.
+    fn next_interface_path(
+        &mut self,
+        class: DeviceClass,
+        device_info_list: HDEVINFO,
+    ) -> Option<String> {
         let mut device_interface_data: SP_DEVICE_INTERFACE_DATA = unsafe { mem::zeroed() };
         device_interface_data.cbSize = mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as UINT;
 
         let result = unsafe {
             SetupDiEnumDeviceInterfaces(
-                self.device_info_list,
+                device_info_list,
                 ptr::null_mut(),
-                &GUID_DEVINTERFACE_DISK,
+                class.guid(),
                 self.device_index,
                 &mut device_interface_data,
             )
@@ -168,7 +196,7 @@ impl Iterator for DiskDeviceEnumerator {
 
         unsafe {
             SetupDiGetDeviceInterfaceDetailW(
-                self.device_info_list,
+                device_info_list,
                 &mut device_interface_data,
                 ptr::null_mut(),
                 0,
@@ -181,11 +209,11 @@ impl Iterator for DiskDeviceEnumerator {
             return None;
         }
 
-        let interface_details = DeviceInterfaceDetailData::new(required_size as usize).unwrap(); //todo: handle errors
+        let interface_details = DeviceInterfaceDetailData::new(required_size as usize).ok()?;
 
         unsafe {
             SetupDiGetDeviceInterfaceDetailW(
-                self.device_info_list,
+                device_info_list,
                 &mut device_interface_data,
                 interface_details.get(),
                 required_size,
@@ -194,17 +222,85 @@ impl Iterator for DiskDeviceEnumerator {
             )
         };
 
-        let device = DeviceFile::open(interface_details.path().as_str(), false).unwrap();
-        let device_number = get_device_number(&device).unwrap();
+        Some(interface_details.path())
+    }
+}
+
+impl Drop for DiskDeviceEnumerator {
+    fn drop(&mut self) {
+        for (_, device_info_list) in &self.classes {
+            unsafe {
+                SetupDiDestroyDeviceInfoList(*device_info_list);
+            }
+        }
+    }
+}
+
+impl Iterator for DiskDeviceEnumerator {
+    type Item = Vec<DiskDeviceInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (class, device_info_list) = *self.classes.get(self.class_index)?;
+            let path = match self.next_interface_path(class, device_info_list) {
+                Some(p) => p,
+                None => {
+                    self.class_index += 1;
+                    self.device_index = 0;
+                    continue;
+                }
+            };
 
-        PhysicalDrive::from_device_number(device_number)
-            .and_then(|x| x.get_storage_list(&self.volumes))
-            //.map_err(|e| println!("Error: {:?}", e)) //todo: figure out how to propagate errors
-            .ok()
-            .or_else(|| self.next()) // skip
+            let device_number =
+                DeviceFile::open(path.as_str(), false).and_then(|d| get_device_number(&d));
+            let devices = match class {
+                DeviceClass::Disk => device_number
+                    .and_then(PhysicalDrive::from_device_number)
+                    .and_then(|x| x.get_storage_list(&self.volumes)),
+                DeviceClass::CdRom => device_number
+                    .and_then(|n| get_media_drive(format!("\\\\.\\CdRom{}", n), StorageType::CD)),
+                // floppy drives have no DOS device name of their own
+                DeviceClass::Floppy => get_media_drive(path, StorageType::Removable),
+            };
+
+            //todo: figure out how to propagate errors
+            if let Ok(d) = devices {
+                return Some(d);
+            }
+        }
     }
 }
 
+/// Drive without partitions, like an optical or a floppy drive. Empty drives are skipped,
+/// as there is nothing to wipe.
+fn get_media_drive(id: String, storage_type: StorageType) -> Result<Vec<DiskDeviceInfo>> {
+    let device = DeviceFile::open(id.as_str(), false)?;
+    let geometry = get_drive_geometry(&device)?;
+    let size = unsafe { *geometry.DiskSize.QuadPart() as u64 };
+    if size == 0 {
+        return Err(anyhow!("No media in {}", id));
+    }
+
+    let sector_size = geometry.Geometry.BytesPerSector;
+    let identity = get_device_identity(&device).unwrap_or_default();
+
+    Ok(vec![DiskDeviceInfo {
+        id,
+        details: StorageDetails {
+            size,
+            block_size: sector_size as usize,
+            logical_sector_size: Some(sector_size),
+            physical_sector_size: Some(sector_size),
+            storage_type,
+            bus_type: identity.bus_type.unwrap_or(BusType::Unknown),
+            model: identity.model,
+            serial: identity.serial,
+            firmware: identity.firmware,
+            ..Default::default()
+        },
+    }])
+}
+
 impl PhysicalDrive {
     fn from_device_number(device_number: u32) -> Result<Self> {
         let disk_path = format!("\\\\.\\PhysicalDrive{}", device_number);
@@ -221,9 +317,11 @@ impl PhysicalDrive {
         volumes: &Vec<(String, Vec<VolumeExtent>)>,
     ) -> Result<Vec<DiskDeviceInfo>> {
         let geometry = get_drive_geometry(&self.device)?;
-        let bytes_per_sector = get_alignment_descriptor(&self.device)
-            .map(|a| a.BytesPerPhysicalSector as usize)
-            .unwrap_or(geometry.Geometry.BytesPerSector as usize);
+        let logical_sector_size = geometry.Geometry.BytesPerSector;
+        let physical_sector_size = get_alignment_descriptor(&self.device)
+            .map(|a| a.BytesPerPhysicalSector)
+            .unwrap_or(logical_sector_size);
+        let bytes_per_sector = physical_sector_size as usize;
 
         let storage_type = match geometry.Geometry.MediaType {
             winioctl::RemovableMedia => StorageType::Removable,
@@ -231,26 +329,52 @@ impl PhysicalDrive {
             _ => StorageType::Other,
         };
 
-        let drive_details = StorageDetails {
+        let identity = get_device_identity(&self.device).unwrap_or_default();
+
+        let media_type = match get_seek_penalty_descriptor(&self.device) {
+            Ok(d) if d.IncursSeekPenalty != 0 => MediaType::Rotational,
+            Ok(_) if matches!(storage_type, StorageType::Removable) => MediaType::RemovableFlash,
+            Ok(_) => MediaType::SolidState,
+            Err(_) => MediaType::Unknown,
+        };
+
+        let mut drive_details = StorageDetails {
             size: unsafe { *geometry.DiskSize.QuadPart() as u64 },
             block_size: bytes_per_sector,
+            logical_sector_size: Some(logical_sector_size),
+            physical_sector_size: Some(physical_sector_size),
             storage_type,
-            mount_point: None,
+            media_type,
+            bus_type: identity.bus_type.unwrap_or(BusType::Unknown),
+            model: identity.model,
+            serial: identity.serial,
+            firmware: identity.firmware,
+            trim_supported: get_trim_descriptor(&self.device)
+                .map(|t| t.TrimEnabled != 0)
+                .unwrap_or(false),
+            ..Default::default()
         };
 
-        let layout = get_drive_layout(&self.device)?;
+        // disks which were never initialized (RAW) have no layout, the drive itself is
+        // still listed and can be wiped
+        let layout = get_drive_layout(&self.device).ok();
+        let system_drive = get_system_drive();
 
         let mut devices: Vec<DiskDeviceInfo> = Vec::new();
-
-        let partitions = unsafe {
-            slice::from_raw_parts(
-                layout.info.PartitionEntry.as_ptr(),
-                layout.info.PartitionCount as usize,
-            )
+        // partitions the firmware boots from, which are part of the system if the drive is
+        let mut system_partitions: Vec<usize> = Vec::new();
+
+        let partitions = match &layout {
+            Some(layout) => unsafe {
+                slice::from_raw_parts(
+                    layout.info.PartitionEntry.as_ptr(),
+                    layout.info.PartitionCount as usize,
+                )
+            },
+            None => &[],
         };
 
-        for i in 0..layout.info.PartitionCount {
-            let x = partitions[i as usize];
+        for &x in partitions {
             let l = unsafe { *x.PartitionLength.QuadPart() };
 
             match x.PartitionStyle {
@@ -282,17 +406,46 @@ impl PhysicalDrive {
                 })
                 .map(|v| v.0.clone());
 
+            let is_boot = mount_point.is_some() && mount_point == system_drive;
+            if is_system_partition(&x) {
+                system_partitions.push(devices.len());
+            }
+            let (label, filesystem) = mount_point
+                .as_deref()
+                .and_then(get_volume_information)
+                .unwrap_or((None, None));
+
             devices.push(DiskDeviceInfo {
                 id: partition_path,
                 details: StorageDetails {
                     size: l as u64,
                     block_size: drive_details.block_size,
+                    logical_sector_size: drive_details.logical_sector_size,
+                    physical_sector_size: drive_details.physical_sector_size,
+                    bus_type: drive_details.bus_type,
                     storage_type: StorageType::Partition,
                     mount_point,
+                    label,
+                    filesystem,
+                    trim_supported: drive_details.trim_supported,
+                    media_type: drive_details.media_type,
+                    model: drive_details.model.clone(),
+                    serial: drive_details.serial.clone(),
+                    firmware: drive_details.firmware.clone(),
+                    parent: Some(self.path.to_string()),
+                    is_boot,
+                    ..Default::default()
                 },
             })
         }
 
+        drive_details.is_boot = devices.iter().any(|d| d.details.is_boot);
+        if drive_details.is_boot {
+            for i in system_partitions {
+                devices[i].details.is_boot = true;
+            }
+        }
+
         devices.push(DiskDeviceInfo {
             id: self.path.to_string(),
             details: drive_details,
@@ -302,28 +455,48 @@ impl PhysicalDrive {
     }
 }
 
-fn get_drive_layout(device: &DeviceFile) -> Result<&mut Layout> {
-    const LAYOUT_BUFFER_SIZE: usize = std::mem::size_of::<Layout>();
-    let mut layout_buffer: [BYTE; LAYOUT_BUFFER_SIZE] = [0; LAYOUT_BUFFER_SIZE];
+/// EFI system partition (GPT) or the active partition of an MBR drive.
+fn is_system_partition(partition: &winioctl::PARTITION_INFORMATION_EX) -> bool {
+    const EFI_SYSTEM_PARTITION: (u32, u16, u16, [u8; 8]) = (
+        0xC12A7328,
+        0xF81F,
+        0x11D2,
+        [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B],
+    );
+
+    match partition.PartitionStyle {
+        winioctl::PARTITION_STYLE_MBR => unsafe {
+            let mbr = partition.u.Mbr();
+            mbr.BootIndicator != 0 || mbr.PartitionType == 0xEF
+        },
+        winioctl::PARTITION_STYLE_GPT => unsafe {
+            let t = &partition.u.Gpt().PartitionType;
+            (t.Data1, t.Data2, t.Data3, t.Data4) == EFI_SYSTEM_PARTITION
+        },
+        _ => false,
+    }
+}
+
+fn get_drive_layout(device: &DeviceFile) -> Result<Box<Layout>> {
+    // owned by the caller, the partition entries are read after this returns
+    let mut layout: Box<Layout> = Box::new(unsafe { mem::zeroed() });
     let mut bytes: DWORD = 0;
     unsafe {
-        let layout: &mut Layout = std::mem::transmute(layout_buffer.as_mut_ptr());
-
         if ioapiset::DeviceIoControl(
             device.handle,
             winioctl::IOCTL_DISK_GET_DRIVE_LAYOUT_EX,
             std::ptr::null_mut(),
             0,
-            layout_buffer.as_mut_ptr() as PVOID,
-            LAYOUT_BUFFER_SIZE as DWORD,
+            layout.as_mut() as *mut Layout as PVOID,
+            mem::size_of::<Layout>() as DWORD,
             &mut bytes,
             std::ptr::null_mut(),
         ) == 0
         {
             return Err(io::Error::last_os_error()).context("Unable to get device layout.");
         }
-        Ok(layout)
     }
+    Ok(layout)
 }
 
 fn get_volume_extents(device: &DeviceFile) -> Result<Vec<VolumeExtent>> {
@@ -398,7 +571,11 @@ fn get_volumes() -> Result<Vec<(String, Vec<VolumeExtent>)>> {
                 Ok(x) => x,
                 _ => continue,
             };
-            let device = DeviceFile::open(volume_path.as_str(), false)?;
+            // volumes without media (like empty card readers) can't be opened
+            let device = match DeviceFile::open(volume_path.as_str(), false) {
+                Ok(d) => d,
+                _ => continue,
+            };
             match get_volume_extents(&device) {
                 Ok(e) => volumes.push((device_path, e)),
                 _ => {}
@@ -409,6 +586,48 @@ fn get_volumes() -> Result<Vec<(String, Vec<VolumeExtent>)>> {
     Ok(volumes)
 }
 
+/// Label and filesystem name of the volume mounted at the given root (like `D:\`).
+fn get_volume_information(root: &str) -> Option<(Option<String>, Option<String>)> {
+    const MAX_PATH: usize = 261;
+    let mut label: [WCHAR; MAX_PATH] = [0; MAX_PATH];
+    let mut filesystem: [WCHAR; MAX_PATH] = [0; MAX_PATH];
+    unsafe {
+        if fileapi::GetVolumeInformationW(
+            WideCString::from_str(root).ok()?.as_ptr(),
+            label.as_mut_ptr(),
+            MAX_PATH as DWORD,
+            null_mut(),
+            null_mut(),
+            null_mut(),
+            filesystem.as_mut_ptr(),
+            MAX_PATH as DWORD,
+        ) == 0
+        {
+            return None;
+        }
+    }
+
+    let to_string = |buffer: &[WCHAR]| {
+        let s = unsafe { WideCString::from_ptr_str(buffer.as_ptr()) }.to_string_lossy();
+        Some(s).filter(|s| !s.is_empty())
+    };
+    Some((to_string(&label), to_string(&filesystem)))
+}
+
+/// Drive letter root (like `C:\`) of the volume Windows is running from.
+fn get_system_drive() -> Option<String> {
+    const MAX_PATH: usize = 1024;
+    let mut buffer: [WCHAR; MAX_PATH] = [0; MAX_PATH];
+    let len =
+        unsafe { sysinfoapi::GetSystemWindowsDirectoryW(buffer.as_mut_ptr(), MAX_PATH as UINT) };
+    if len == 0 || len as usize >= MAX_PATH {
+        return None;
+    }
+
+    let dir = String::from_utf16_lossy(&buffer[..len as usize]);
+    dir.get(..3).map(|d| d.to_uppercase())
+}
+
 fn get_device_number(device: &DeviceFile) -> Result<DWORD> {
     let mut dev_number = StorageDeviceNumber {
         device_type: 0,
@@ -484,7 +703,9 @@ winapi::STRUCT! {
     }
 }
 
-fn get_alignment_descriptor(device: &DeviceFile) -> Result<STORAGE_ACCESS_ALIGNMENT_DESCRIPTOR> {
+pub(super) fn get_alignment_descriptor(
+    device: &DeviceFile,
+) -> Result<STORAGE_ACCESS_ALIGNMENT_DESCRIPTOR> {
     let mut query = winioctl::STORAGE_PROPERTY_QUERY {
         PropertyId: winioctl::StorageAccessAlignmentProperty,
         QueryType: winioctl::PropertyStandardQuery,
@@ -511,3 +732,156 @@ fn get_alignment_descriptor(device: &DeviceFile) -> Result<STORAGE_ACCESS_ALIGNM
 
     Ok(alignment)
 }
+
+winapi::STRUCT! {
+    #[allow(non_snake_case)]
+    #[derive(Debug)]
+    struct DEVICE_TRIM_DESCRIPTOR {
+        Version: ULONG,
+        Size: ULONG,
+        TrimEnabled: BOOLEAN,
+    }
+}
+
+fn get_trim_descriptor(device: &DeviceFile) -> Result<DEVICE_TRIM_DESCRIPTOR> {
+    let mut query = winioctl::STORAGE_PROPERTY_QUERY {
+        PropertyId: winioctl::StorageDeviceTrimProperty,
+        QueryType: winioctl::PropertyStandardQuery,
+        AdditionalParameters: [0],
+    };
+
+    let mut trim: DEVICE_TRIM_DESCRIPTOR = unsafe { mem::zeroed() };
+    let mut bytes: DWORD = 0;
+    unsafe {
+        if ioapiset::DeviceIoControl(
+            device.handle,
+            winioctl::IOCTL_STORAGE_QUERY_PROPERTY,
+            &mut query as *mut _ as PVOID,
+            mem::size_of_val(&query) as DWORD,
+            &mut trim as *mut _ as PVOID,
+            mem::size_of_val(&trim) as DWORD,
+            &mut bytes,
+            ptr::null_mut(),
+        ) == 0
+        {
+            return Err(io::Error::last_os_error()).context("Unable to get TRIM support info.");
+        }
+    }
+
+    Ok(trim)
+}
+
+winapi::STRUCT! {
+    #[allow(non_snake_case)]
+    #[derive(Debug)]
+    struct DEVICE_SEEK_PENALTY_DESCRIPTOR {
+        Version: ULONG,
+        Size: ULONG,
+        IncursSeekPenalty: BOOLEAN,
+    }
+}
+
+fn get_seek_penalty_descriptor(device: &DeviceFile) -> Result<DEVICE_SEEK_PENALTY_DESCRIPTOR> {
+    let mut query = winioctl::STORAGE_PROPERTY_QUERY {
+        PropertyId: winioctl::StorageDeviceSeekPenaltyProperty,
+        QueryType: winioctl::PropertyStandardQuery,
+        AdditionalParameters: [0],
+    };
+
+    let mut penalty: DEVICE_SEEK_PENALTY_DESCRIPTOR = unsafe { mem::zeroed() };
+    let mut bytes: DWORD = 0;
+    unsafe {
+        if ioapiset::DeviceIoControl(
+            device.handle,
+            winioctl::IOCTL_STORAGE_QUERY_PROPERTY,
+            &mut query as *mut _ as PVOID,
+            mem::size_of_val(&query) as DWORD,
+            &mut penalty as *mut _ as PVOID,
+            mem::size_of_val(&penalty) as DWORD,
+            &mut bytes,
+            ptr::null_mut(),
+        ) == 0
+        {
+            return Err(io::Error::last_os_error()).context("Unable to get seek penalty info.");
+        }
+    }
+
+    Ok(penalty)
+}
+
+/// Model, serial number and firmware revision reported by the drive.
+#[derive(Default)]
+struct DeviceIdentity {
+    model: Option<String>,
+    serial: Option<String>,
+    firmware: Option<String>,
+    bus_type: Option<BusType>,
+}
+
+fn get_device_identity(device: &DeviceFile) -> Result<DeviceIdentity> {
+    // STORAGE_DEVICE_DESCRIPTOR followed by the strings it points to
+    const DESCRIPTOR_BUFFER_SIZE: usize = 1024;
+    const VENDOR_ID_OFFSET: usize = 12;
+    const PRODUCT_ID_OFFSET: usize = 16;
+    const PRODUCT_REVISION_OFFSET: usize = 20;
+    const SERIAL_NUMBER_OFFSET: usize = 24;
+    const BUS_TYPE_OFFSET: usize = 28;
+
+    let mut query = winioctl::STORAGE_PROPERTY_QUERY {
+        PropertyId: winioctl::StorageDeviceProperty,
+        QueryType: winioctl::PropertyStandardQuery,
+        AdditionalParameters: [0],
+    };
+
+    let mut buffer: [BYTE; DESCRIPTOR_BUFFER_SIZE] = [0; DESCRIPTOR_BUFFER_SIZE];
+    let mut bytes: DWORD = 0;
+    unsafe {
+        if ioapiset::DeviceIoControl(
+            device.handle,
+            winioctl::IOCTL_STORAGE_QUERY_PROPERTY,
+            &mut query as *mut _ as PVOID,
+            mem::size_of_val(&query) as DWORD,
+            buffer.as_mut_ptr() as PVOID,
+            DESCRIPTOR_BUFFER_SIZE as DWORD,
+            &mut bytes,
+            ptr::null_mut(),
+        ) == 0
+        {
+            return Err(io::Error::last_os_error()).context("Unable to get device descriptor.");
+        }
+    }
+
+    let descriptor = &buffer[..(bytes as usize).min(DESCRIPTOR_BUFFER_SIZE)];
+    // the fields hold offsets of null-terminated strings, zero if there's no string
+    let field = |at: usize| -> Option<String> {
+        let offset = u32::from_le_bytes(descriptor.get(at..at + 4)?.try_into().ok()?) as usize;
+        let data = descriptor.get(offset..).filter(|_| offset != 0)?;
+        let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+        Some(String::from_utf8_lossy(&data[..end]).trim().to_string()).filter(|s| !s.is_empty())
+    };
+
+    let model = match (field(VENDOR_ID_OFFSET), field(PRODUCT_ID_OFFSET)) {
+        (Some(vendor), Some(product)) => Some(format!("{} {}", vendor, product)),
+        (vendor, product) => product.or(vendor),
+    };
+
+    // STORAGE_BUS_TYPE
+    let bus_type = descriptor
+        .get(BUS_TYPE_OFFSET..BUS_TYPE_OFFSET + 4)
+        .and_then(|b| b.try_into().ok())
+        .map(|b| match u32::from_le_bytes(b) {
+            0 => BusType::Unknown,
+            1 | 10 => BusType::Scsi, // SCSI, SAS
+            3 | 11 => BusType::Ata,  // ATA, SATA
+            7 => BusType::Usb,
+            17 => BusType::Nvme,
+            _ => BusType::Other,
+        });
+
+    Ok(DeviceIdentity {
+        model,
+        serial: field(SERIAL_NUMBER_OFFSET),
+        firmware: field(PRODUCT_REVISION_OFFSET),
+        bus_type,
+    })
+}
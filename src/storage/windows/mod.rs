@@ -34,6 +34,46 @@ impl System {
     pub fn access(storage_ref: &dyn StorageRef) -> Result<impl StorageAccess> {
         DeviceFile::open(storage_ref.id(), true)
     }
+
+    /// Writes are not queued on Windows yet.
+    pub fn access_queued(
+        storage_ref: &dyn StorageRef,
+        _queue_depth: usize,
+    ) -> Result<Box<dyn StorageAccess>> {
+        Ok(Box::new(System::access(storage_ref)?))
+    }
+
+    pub fn temperature(_device_id: &str) -> Option<f32> {
+        None
+    }
+
+    pub fn device_users(_device_id: &str) -> Vec<DeviceUser> {
+        Vec::new()
+    }
+
+    pub fn close_crypt_mappings(_device_id: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    pub fn unmount_filesystems(_device_id: &str) -> Result<Vec<String>> {
+        Err(anyhow!("Unmounting is not supported on this platform"))
+    }
+
+    pub fn standby(_device_id: &str) -> Result<()> {
+        Err(anyhow!("Standby is not supported on this platform"))
+    }
+
+    pub fn hidden_areas(_device_id: &str) -> Option<hidden::HiddenAreas> {
+        None
+    }
+
+    pub fn set_max_sectors(_device_id: &str, _sectors: u64, _permanent: bool) -> Result<()> {
+        Err(anyhow!("Hidden areas are not supported on this platform"))
+    }
+
+    pub fn restore_dco(_device_id: &str) -> Result<()> {
+        Err(anyhow!("Hidden areas are not supported on this platform"))
+    }
 }
 
 impl StorageRef for DiskDeviceInfo {
@@ -1,5 +1,8 @@
+use crate::actions::{Verify, WipeRange};
+use crate::storage::Geometry;
 use anyhow::{Context, Result};
 use regex::Regex;
+use std::time::Duration;
 
 pub fn parse_block_size(s: &str) -> Result<usize> {
     let block_size_regex = Regex::new(r"^(?i)(\d+) *(([km])b?)?$").unwrap();
@@ -14,9 +17,11 @@ pub fn parse_block_size(s: &str) -> Result<usize> {
                 _ => 1,
             };
 
-            let bytes_length = (units * unit_size) as usize;
-            if bytes_length & (bytes_length - 1) == 0 {
-                Ok((units * unit_size) as usize)
+            let bytes_length = units * unit_size;
+            if bytes_length == 0 {
+                Err(anyhow!("Should be more than zero."))
+            } else if bytes_length & (bytes_length - 1) == 0 {
+                Ok(units * unit_size)
             } else {
                 Err(anyhow!("Should be a power of two."))
             }
@@ -27,6 +32,162 @@ pub fn parse_block_size(s: &str) -> Result<usize> {
     }
 }
 
+/// Rounds the block size up to a multiple of the physical sector size of the device,
+/// smaller or misaligned blocks are rejected or slow with direct I/O.
+pub fn align_block_size(block_size: usize, geometry: &Geometry) -> usize {
+    let sector_size = geometry
+        .physical_sector_size
+        .max(geometry.sector_size)
+        .max(1) as usize;
+    block_size.div_ceil(sector_size) * sector_size
+}
+
+/// Parses a byte size with an optional binary scale (e.g. 512, 64k, 100G or 2T).
+pub fn parse_size(s: &str) -> Result<u64> {
+    let size_regex = Regex::new(r"^(?i)(\d+) *(([kmgt])b?)?$").unwrap();
+
+    match size_regex.captures(s) {
+        Some(groups) => {
+            let units = groups[1].parse::<u64>().context("Not a number.")?;
+            let shift = match groups.get(3).map(|m| m.as_str().to_uppercase()) {
+                Some(ref u) if u == "K" => 10,
+                Some(ref u) if u == "M" => 20,
+                Some(ref u) if u == "G" => 30,
+                Some(ref u) if u == "T" => 40,
+                _ => 0,
+            };
+
+            units
+                .checked_mul(1 << shift)
+                .ok_or_else(|| anyhow!("Size is too big."))
+        }
+        _ => Err(anyhow!(
+            "Use a number of bytes with optional scale (e.g. 4096, 128k, 2M or 100G)."
+        )),
+    }
+}
+
+/// Parses a device offset: a size, `END` (the end of the device) or `END-SIZE`.
+pub fn parse_offset(s: &str, total_size: u64) -> Result<u64> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("end") {
+        return Ok(total_size);
+    }
+    match s.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("end-") => total_size
+            .checked_sub(parse_size(&s[4..])?)
+            .ok_or_else(|| anyhow!("{} is before the start of the device.", s)),
+        _ => parse_size(s),
+    }
+}
+
+/// Builds a single range from the optional start offset and either the end offset or
+/// the length. The range defaults to the whole device.
+pub fn parse_window(
+    from: Option<&str>,
+    to: Option<&str>,
+    length: Option<&str>,
+    total_size: u64,
+) -> Result<WipeRange> {
+    let start = match from {
+        Some(f) => parse_offset(f, total_size).context(format!("Invalid start {}", f))?,
+        None => 0,
+    };
+    let end = match (to, length) {
+        (Some(_), Some(_)) => return Err(anyhow!("Use either the end or the length, not both.")),
+        (Some(t), None) => parse_offset(t, total_size).context(format!("Invalid end {}", t))?,
+        (None, Some(l)) => start
+            .checked_add(parse_size(l).context(format!("Invalid length {}", l))?)
+            .ok_or_else(|| anyhow!("Length is too big."))?,
+        (None, None) => total_size,
+    };
+    Ok(WipeRange::new(start, end))
+}
+
+/// Parses a comma separated list of byte ranges, e.g. `0-1M,100G-200G,END-1M`.
+///
+/// Each range is `FROM-TO` where both ends are either a size, `END` (the end of the device)
+/// or `END-SIZE`. When `TO` is omitted the range extends to the end of the device, so
+/// `END-1M` stands for the last megabyte.
+pub fn parse_ranges(s: &str, total_size: u64) -> Result<Vec<WipeRange>> {
+    let range_regex =
+        Regex::new(r"^(?i)(?P<from>end(-[^-]+)?|[^-]+)(-(?P<to>end(-[^-]+)?|[^-]+))?$").unwrap();
+
+    let parse_offset = |v: &str| parse_offset(v, total_size);
+
+    s.split(',')
+        .map(|r| {
+            let r = r.trim();
+            let groups = range_regex.captures(r).ok_or_else(|| {
+                anyhow!("Invalid range {}. Use FROM-TO (e.g. 0-1M or END-1M).", r)
+            })?;
+
+            let start = parse_offset(&groups["from"]).context(format!("Invalid range {}", r))?;
+            let end = match groups.name("to") {
+                Some(to) => parse_offset(to.as_str()).context(format!("Invalid range {}", r))?,
+                None => total_size,
+            };
+
+            Ok(WipeRange::new(start, end))
+        })
+        .collect()
+}
+
+/// Parses a duration given in seconds or with a unit, e.g. `90`, `30m`, `1h` or `2d`.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let duration_regex = Regex::new(r"^(\d+) *([smhd])?$").unwrap();
+    match duration_regex.captures(&s.to_lowercase()) {
+        Some(groups) => {
+            let units = groups[1].parse::<u64>().context("Not a number.")?;
+            let unit_secs = match groups.get(2).map(|m| m.as_str()) {
+                Some("m") => 60,
+                Some("h") => 3600,
+                Some("d") => 86400,
+                _ => 1,
+            };
+            units
+                .checked_mul(unit_secs)
+                .map(Duration::from_secs)
+                .ok_or_else(|| anyhow!("Duration is too long."))
+        }
+        None => Err(anyhow!(
+            "Use a number of seconds with optional unit (e.g. 90, 30m, 1h or 2d)."
+        )),
+    }
+}
+
+/// Parses a verification mode: `no`, `last`, `all` or `sample:PERCENT`.
+pub fn parse_verify(s: &str) -> Result<Verify> {
+    match s {
+        "no" => Ok(Verify::No),
+        "last" => Ok(Verify::Last),
+        "all" => Ok(Verify::All),
+        _ => match s.strip_prefix("sample:") {
+            Some(p) => Verify::sample(
+                p.trim_end_matches('%')
+                    .parse()
+                    .context("Sample size should be a number of percent.")?,
+            ),
+            None => Err(anyhow!("Use one of no, last, all or sample:PERCENT.")),
+        },
+    }
+}
+
+/// Parses a byte pattern given as hex digits, e.g. `00` or `55aa`.
+pub fn parse_pattern(s: &str) -> Result<Vec<u8>> {
+    let s = s.trim_start_matches("0x");
+    if s.is_empty() || !s.len().is_multiple_of(2) {
+        return Err(anyhow!("Pattern should be an even number of hex digits."));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&s[i..i + 2], 16)
+                .context("Pattern should be an even number of hex digits.")
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -44,6 +205,30 @@ mod test {
         assert_eq!(parse_block_size("2M").unwrap(), m2);
     }
 
+    #[test]
+    fn test_block_size_alignment() {
+        let geometry = |sector_size, physical_sector_size| Geometry {
+            size: 1 << 30,
+            sector_size,
+            physical_sector_size,
+        };
+        assert_eq!(align_block_size(1 << 20, &geometry(512, 4096)), 1 << 20);
+        assert_eq!(align_block_size(512, &geometry(512, 4096)), 4096);
+        assert_eq!(align_block_size(1024, &geometry(4096, 4096)), 4096);
+        assert_eq!(align_block_size(512, &geometry(512, 512)), 512);
+    }
+
+    #[test]
+    fn test_duration_parser() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("1H").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::from_secs(172800));
+        assert_matches!(parse_duration(""), Err(_));
+        assert_matches!(parse_duration("1w"), Err(_));
+        assert_matches!(parse_duration("-1h"), Err(_));
+    }
+
     #[test]
     fn test_block_size_parser_bad() {
         assert_matches!(parse_block_size(""), Err(_));
@@ -51,5 +236,85 @@ mod test {
         assert_matches!(parse_block_size("-128k"), Err(_));
         assert_matches!(parse_block_size("4096.000"), Err(_));
         assert_matches!(parse_block_size("4095"), Err(_));
+        assert_matches!(parse_block_size("0"), Err(_));
+    }
+
+    #[test]
+    fn test_size_parser() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("64k").unwrap(), 64 << 10);
+        assert_eq!(parse_size("100G").unwrap(), 100 << 30);
+        assert_eq!(parse_size("2TB").unwrap(), 2 << 40);
+        assert_matches!(parse_size("1.5G"), Err(_));
+        assert_matches!(parse_size("99999999999T"), Err(_));
+    }
+
+    #[test]
+    fn test_ranges_parser() {
+        let total = 500 << 30;
+        assert_eq!(
+            parse_ranges("0-1M,100G-200G,END-1M", total).unwrap(),
+            vec![
+                WipeRange::new(0, 1 << 20),
+                WipeRange::new(100 << 30, 200 << 30),
+                WipeRange::new(total - (1 << 20), total)
+            ]
+        );
+        assert_eq!(
+            parse_ranges("end-2m-end-1m, 1G-END", total).unwrap(),
+            vec![
+                WipeRange::new(total - (2 << 20), total - (1 << 20)),
+                WipeRange::new(1 << 30, total)
+            ]
+        );
+        assert_matches!(parse_ranges("", total), Err(_));
+        assert_matches!(parse_ranges("1M-xxx", total), Err(_));
+        assert_matches!(parse_ranges("END-600G", total), Err(_));
+    }
+
+    #[test]
+    fn test_pattern_parser() {
+        assert_eq!(parse_pattern("00").unwrap(), vec![0]);
+        assert_eq!(parse_pattern("0x55AA").unwrap(), vec![0x55, 0xaa]);
+        assert_matches!(parse_pattern(""), Err(_));
+        assert_matches!(parse_pattern("0x"), Err(_));
+        assert_matches!(parse_pattern("5"), Err(_));
+        assert_matches!(parse_pattern("zz"), Err(_));
+    }
+
+    #[test]
+    fn test_window_parser() {
+        let total = 500 << 30;
+        assert_eq!(
+            parse_window(Some("1M"), None, Some("4M"), total).unwrap(),
+            WipeRange::new(1 << 20, 5 << 20)
+        );
+        assert_eq!(
+            parse_window(None, Some("END-1G"), None, total).unwrap(),
+            WipeRange::new(0, total - (1 << 30))
+        );
+        assert_eq!(
+            parse_window(Some("100G"), None, None, total).unwrap(),
+            WipeRange::new(100 << 30, total)
+        );
+        assert_matches!(parse_window(None, Some("1M"), Some("1M"), total), Err(_));
+        assert_matches!(parse_window(Some("x"), None, None, total), Err(_));
+    }
+
+    #[test]
+    fn test_verify_parser() {
+        assert_matches!(parse_verify("no"), Ok(Verify::No));
+        assert_matches!(parse_verify("all"), Ok(Verify::All));
+        assert_matches!(
+            parse_verify("sample:10"),
+            Ok(Verify::Sample { percent: 10, .. })
+        );
+        assert_matches!(
+            parse_verify("sample:25%"),
+            Ok(Verify::Sample { percent: 25, .. })
+        );
+        assert_matches!(parse_verify("sample:0"), Err(_));
+        assert_matches!(parse_verify("sample:x"), Err(_));
+        assert_matches!(parse_verify("some"), Err(_));
     }
 }
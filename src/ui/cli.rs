@@ -1,34 +1,694 @@
 use std::io::ErrorKind;
-use std::time::Instant;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use console::style;
 use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressStyle};
 
-use crate::actions::{WipeEvent, WipeEventReceiver, WipeState, WipeTask};
+use crate::actions::batch::BatchResult;
+use crate::actions::checkpoint::Checkpoint;
+use crate::actions::diagnostics::{DiagnosticsRecorder, DEFAULT_EVENTS_CAPACITY};
+use crate::actions::freespace::{FreeSpaceEvent, FreeSpaceEventReceiver, FreeSpaceTask};
+use crate::actions::inspect::{self, Inspection};
+use crate::actions::label::{LabelSignature, WipeLabel};
+use crate::actions::luks::LuksErasure;
+use crate::actions::privacy::SelfAudit;
+use crate::actions::probe::Signature;
+use crate::actions::sanitize::{SanitizeEvent, SanitizeEventReceiver, SanitizeTask};
+use crate::actions::soak::{SoakEvent, SoakEventReceiver, SoakTask};
+use crate::actions::state::{Job, JobStatus, StateDir};
+use crate::actions::timing::{PhaseTiming, StageTimer, ThroughputMeter};
+use crate::actions::warnings::{Severity, Warning};
+use crate::actions::{
+    underlying_storage_error, Verify, WipeEvent, WipeEventReceiver, WipeOutcome, WipeRange,
+    WipeState, WipeTask,
+};
+use crate::sanitization::mem::PoolStats;
 use crate::sanitization::{Scheme, SchemeRepo};
 use crate::stage::Stage;
+use crate::storage::hidden::HiddenAreas;
+use crate::storage::sanitize::{SanitizeStatus, SanitizeSupport};
+use crate::storage::smart::SmartAttributes;
+use crate::storage::{Geometry, StorageDetails, StorageError, StorageRef};
+use crate::ui::config::Config;
+use crate::ui::examples::{Platform, Topic};
+use crate::ui::keys::KeyControl;
 use prettytable::format::FormatBuilder;
 use prettytable::Table;
-use std::thread::sleep;
 
-const RETRY_BACKOFF_SECONDS: u32 = 3;
+const WIPE_PROGRESS_TEMPLATE: &str =
+    "[{elapsed_precise}] {bar:40.cyan/blue} {bytes:>7}/{total_bytes:7} {msg}";
+const SOAK_PROGRESS_TEMPLATE: &str =
+    "[{elapsed_precise}] {bar:40.cyan/blue} {bytes:>7}/{total_bytes:7} ({eta} left) {msg}";
+const SANITIZE_PROGRESS_TEMPLATE: &str = "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>3}% {msg}";
+const TEMPERATURE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
 
-pub struct ConsoleFrontend {}
+/// How the console sessions show the progress, see `--no-progress` and `--quiet`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleMode {
+    /// Progress bars and keyboard controls.
+    Interactive,
+    /// Plain lines for the stages instead of the progress bars, for logs.
+    NoProgress,
+    /// Same as `NoProgress`, without the details shown before the start.
+    Quiet,
+}
+
+pub struct ConsoleFrontend {
+    mode: ConsoleMode,
+}
 
 impl ConsoleFrontend {
     pub fn new() -> Self {
-        ConsoleFrontend {}
+        ConsoleFrontend {
+            mode: ConsoleMode::Interactive,
+        }
     }
 
-    pub fn wipe_session(self, device_id: &str, auto_confirm: bool) -> ConsoleWipeSession {
+    pub fn with_mode(mut self, mode: ConsoleMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn wipe_session(
+        self,
+        device_id: &str,
+        details: &StorageDetails,
+        auto_confirm: bool,
+        diagnostics_path: Option<&str>,
+    ) -> ConsoleWipeSession {
         ConsoleWipeSession {
+            mode: self.mode,
             device_id: String::from(device_id),
+            short_id: String::from(device_id),
+            details: details.clone(),
             auto_confirm,
+            confirmed: false,
+            introduced: false,
+            diagnostics: DiagnosticsRecorder::new(device_id, details, DEFAULT_EVENTS_CAPACITY),
+            diagnostics_path: diagnostics_path.map(String::from),
+            diagnostics_offered: true,
+            warnings: Vec::new(),
             pb: None,
             session_started: None,
-            stage_started: None,
+            meter: None,
+            timer: StageTimer::new(),
+            temperature: None,
+            temperature_checked: None,
+            last_temperature: None,
+            keys: None,
+            paused_until: None,
+        }
+    }
+
+    pub fn soak_session(
+        self,
+        device_id: &str,
+        auto_confirm: bool,
+        temperature: Box<dyn Fn() -> Option<f32>>,
+    ) -> ConsoleSoakSession {
+        ConsoleSoakSession {
+            mode: self.mode,
+            device_id: String::from(device_id),
+            auto_confirm,
+            temperature,
+            pb: None,
+            smart: None,
+            passes: 0,
+            bytes_read: 0,
+            read_time: std::time::Duration::from_secs(0),
+            read_errors: 0,
+            region_failures: 0,
+            temperatures: Vec::new(),
+        }
+    }
+
+    pub fn sanitize_session(
+        self,
+        short_id: &str,
+        details: &StorageDetails,
+        auto_confirm: bool,
+    ) -> ConsoleSanitizeSession {
+        ConsoleSanitizeSession {
+            mode: self.mode,
+            short_id: short_id.to_string(),
+            details: details.clone(),
+            auto_confirm,
+            confirmed: false,
+            pb: None,
+        }
+    }
+
+    pub fn free_space_session(self, auto_confirm: bool) -> ConsoleFreeSpaceSession {
+        ConsoleFreeSpaceSession {
+            mode: self.mode,
+            auto_confirm,
+            pb: None,
+            length: 0,
+        }
+    }
+
+    pub fn print_checkpoints(checkpoints: &[Checkpoint]) {
+        let mut t = Table::new();
+        t.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+        t.set_titles(row![
+            "Device ID",
+            "Size",
+            "Scheme",
+            "Stage",
+            "Position",
+            "Saved"
+        ]);
+        for c in checkpoints {
+            t.add_row(row![
+                c.fingerprint.id,
+                HumanBytes(c.fingerprint.size),
+                c.scheme.description,
+                format!(
+                    "{}/{}{}",
+                    c.stage + 1,
+                    c.scheme.stages.len(),
+                    if c.at_verification { " (verify)" } else { "" }
+                ),
+                HumanBytes(c.position),
+                format!("{} ago", HumanDuration(checkpoint_age(c)))
+            ]);
+        }
+        t.printstd();
+    }
+
+    /// Checkpoints kept by the pruning as they couldn't be read.
+    pub fn print_unreadable_checkpoints(paths: &[PathBuf]) {
+        for path in paths {
+            eprintln!("Skipping unreadable checkpoint {}.", path.display());
+        }
+    }
+
+    pub fn print_jobs(jobs: &[Job], resumable: &[bool], state: &StateDir) {
+        if jobs.is_empty() {
+            println!("No wipes recorded in {}.", state.root().display());
+            return;
+        }
+        let mut t = Table::new();
+        t.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+        t.set_titles(row![
+            "Job ID",
+            "Device ID",
+            "Size",
+            "Scheme",
+            "Updated",
+            "Status",
+            "Progress",
+            "Resumable"
+        ]);
+        for (job, resumable) in jobs.iter().zip(resumable) {
+            let status = job.effective_status();
+            let styled = match status {
+                JobStatus::Completed => style(status.to_string()).green(),
+                JobStatus::Running => style(status.to_string()),
+                JobStatus::Failed => style(status.to_string()).red(),
+                _ => style(status.to_string()).yellow(),
+            };
+            let progress = match status {
+                JobStatus::Completed | JobStatus::CompletedWithBadBlocks => String::new(),
+                _ => format!(
+                    "Stage {}/{}{} at {:.1}%",
+                    job.stage,
+                    job.stages,
+                    if job.at_verification { " (verify)" } else { "" },
+                    job.progress as f64 * 100.0 / job.total.max(1) as f64
+                ),
+            };
+            t.add_row(row![
+                job.id,
+                job.fingerprint.id,
+                HumanBytes(job.fingerprint.size),
+                job.scheme,
+                format!("{} ago", HumanDuration(seconds_since(job.updated))),
+                styled,
+                progress,
+                if *resumable { "yes" } else { "" }
+            ]);
+        }
+        t.printstd();
+        if resumable.iter().any(|r| *r) {
+            println!("\nContinue a resumable job with `lethe resume <job-id>`.");
+        }
+        println!(
+            "\nReports and bad block maps are kept in {}.",
+            state.root().display()
+        );
+    }
+
+    pub fn print_config(path: Option<&std::path::Path>, config: &Config) {
+        match path {
+            Some(p) if p.exists() => println!("Config file: {}\n", p.display()),
+            Some(p) => println!("Config file: {} (not found)\n", p.display()),
+            None => println!("Config file: none\n"),
+        }
+
+        let effective = config.effective();
+        let mut t = Table::new();
+        t.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+        t.add_row(row!["scheme", effective.scheme.unwrap_or_default()]);
+        t.add_row(row![
+            "blocksize",
+            effective
+                .blocksize
+                .unwrap_or_else(|| "4m for NVMe drives, 1m for others".to_string())
+        ]);
+        t.add_row(row!["retries", effective.retries.unwrap_or_default()]);
+        t.add_row(row!["verify", effective.verify.unwrap_or_default()]);
+        t.add_row(row!["exclude", effective.exclude.join(", ")]);
+        t.add_row(row!["allow", effective.allow.join(", ")]);
+        t.add_row(row![
+            "email",
+            effective
+                .email
+                .map(|e| format!("{} via {}", e.from, e.server))
+                .unwrap_or_default()
+        ]);
+        t.printstd();
+    }
+
+    /// Shows the devices selected by `wipe-all` and asks to confirm wiping all of them.
+    pub fn confirm_batch<T: StorageRef>(devices: &[&T], auto_confirm: bool) -> bool {
+        println!("Wiping {} device(s):", devices.len());
+        let mut t = Table::new();
+        let indent_table_format = FormatBuilder::new().padding(4, 1).build();
+        t.set_format(indent_table_format);
+        for d in devices {
+            t.add_row(row![
+                d.id(),
+                HumanBytes(d.details().size),
+                d.details().storage_type,
+                d.details().model.as_deref().unwrap_or("")
+            ]);
+        }
+        print!("{}", t);
+
+        auto_confirm || ask_for_confirmation()
+    }
+
+    pub fn print_watching(auto: bool, notified: bool) {
+        println!(
+            "Waiting for devices to be attached{}. Press Ctrl+C to stop{}.",
+            if notified {
+                ""
+            } else {
+                " (the device list is checked every few seconds)"
+            },
+            if auto {
+                ", the running wipes are aborted"
+            } else {
+                ""
+            }
+        );
+        if !auto {
+            println!("The attached devices are only reported, use --auto to wipe them.");
         }
     }
 
+    pub fn print_device_attached(device_id: &str, wiping: bool) {
+        if wiping {
+            println!("{}: attached, wiping", style(device_id).bold());
+        } else {
+            println!("{}: attached", style(device_id).bold());
+        }
+    }
+
+    pub fn blank_check_session(
+        self,
+        device_id: &str,
+        details: &StorageDetails,
+        value: u8,
+    ) -> BlankCheckSession {
+        BlankCheckSession {
+            inner: self.wipe_session(device_id, details, true, None),
+            value,
+        }
+    }
+
+    pub fn batch_session(device_id: &str) -> BatchWipeSession {
+        BatchWipeSession {
+            device_id: device_id.to_string(),
+        }
+    }
+
+    pub fn print_batch_results(results: &[BatchResult]) {
+        let mut t = Table::new();
+        t.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+        t.set_titles(row!["Device ID", "Result", "Time"]);
+        for r in results {
+            let result = match (r.outcome, &r.error) {
+                (_, Some(err)) => style(format!("Failed: {}", err)).red(),
+                (WipeOutcome::Completed, _) => style("Completed".to_string()).green(),
+                (WipeOutcome::CompletedWithBadBlocks, _) => {
+                    style("Completed, bad blocks skipped".to_string()).yellow()
+                }
+                (WipeOutcome::Aborted, _) => style("Aborted".to_string()).yellow(),
+                (WipeOutcome::VerificationFailed, _) => {
+                    style("Verification failed".to_string()).red()
+                }
+                (WipeOutcome::PermissionDenied, _) => style("Permission denied".to_string()).red(),
+                (WipeOutcome::DeviceNotFound, _) => style("Device not found".to_string()).red(),
+                (WipeOutcome::Failed, _) => style("Failed".to_string()).red(),
+            };
+            t.add_row(row![r.device_id, result, HumanDuration(r.elapsed)]);
+        }
+        t.printstd();
+    }
+
+    pub fn print_signatures(signatures: &[Signature]) {
+        let mut t = Table::new();
+        let indent_table_format = FormatBuilder::new().padding(4, 1).build();
+        t.set_format(indent_table_format);
+        for s in signatures {
+            t.add_row(row![s.kind, format!("at {}", s.offset)]);
+        }
+        print!("Found metadata structures:\n{}", t);
+    }
+
+    /// The structures a targeted scheme is about to wipe, along with the beginning and the end
+    /// of the device for the quick scheme.
+    pub fn print_targeted_wipe(
+        signatures: &[Signature],
+        edge_size: Option<u64>,
+        ranges: &[WipeRange],
+    ) {
+        if let Some(edge_size) = edge_size {
+            println!("Wiping the first and the last {}.", HumanBytes(edge_size));
+        }
+        if signatures.is_empty() {
+            println!("No partition tables or filesystems found.");
+        } else {
+            ConsoleFrontend::print_signatures(signatures);
+        }
+        println!(
+            "{} in {} range(s) to be wiped.",
+            HumanBytes(ranges.iter().map(|r| r.len()).sum()),
+            ranges.len()
+        );
+    }
+
+    pub fn print_inspection(device_id: &str, inspection: &Inspection) {
+        let sectors = |data: &[u8]| data.len().div_ceil(inspect::SECTOR_SIZE);
+        println!(
+            "First {} sector(s) of {}:",
+            sectors(&inspection.head),
+            device_id
+        );
+        for line in inspect::hexdump(&inspection.head, 0) {
+            println!("    {}", line);
+        }
+        println!("Last {} sector(s):", sectors(&inspection.tail));
+        for line in inspect::hexdump(&inspection.tail, inspection.tail_offset) {
+            println!("    {}", line);
+        }
+
+        if inspection.signatures.is_empty() {
+            println!("No partition tables or filesystems found.");
+        } else {
+            ConsoleFrontend::print_signatures(&inspection.signatures);
+        }
+        println!(
+            "Content of {} sampled block(s): {}.",
+            inspection.samples, inspection.content
+        );
+        if inspection.looks_blank() {
+            println!("{}", style("The device looks blank already.").green());
+        } else {
+            println!("{}", style("The device holds data.").yellow());
+        }
+    }
+
+    pub fn print_sanitize_support(
+        device_id: &str,
+        support: Option<&SanitizeSupport>,
+        status: Option<SanitizeStatus>,
+    ) {
+        match support {
+            None => println!(
+                "Unable to tell whether {} supports SANITIZE, it's queried with nvme-cli on NVMe drives and hdparm on the others.",
+                device_id
+            ),
+            Some(s) if s.modes.is_empty() => {
+                println!("{} doesn't support SANITIZE.", device_id)
+            }
+            Some(s) => println!(
+                "{} supports SANITIZE: {}.",
+                device_id,
+                s.modes
+                    .iter()
+                    .map(|m| m.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+        match status {
+            Some(SanitizeStatus::InProgress(done)) => println!(
+                "A sanitize operation is in progress ({:.0}% done).",
+                done * 100.0
+            ),
+            Some(SanitizeStatus::Completed) => {
+                println!("The last sanitize operation completed successfully.")
+            }
+            Some(SanitizeStatus::Failed) => {
+                println!("{}", style("The last sanitize operation failed.").red())
+            }
+            Some(SanitizeStatus::Idle) | None => {}
+        }
+    }
+
+    pub fn print_post_wipe(action: impl std::fmt::Display) {
+        println!("After the wipe: {}.", action);
+    }
+
+    pub fn print_self_audit(audit: &SelfAudit) {
+        println!("Self-audit, nothing of it is stored:");
+        for t in &audit.touched {
+            println!("    Wrote to {}", t);
+        }
+        for t in &audit.traces {
+            if t.exists {
+                println!(
+                    "    {} {} is left by an earlier wipe",
+                    style(format!("Found {}", t.kind)).yellow(),
+                    t.path.to_string_lossy()
+                );
+            } else {
+                println!("    No {} at {}", t.kind, t.path.to_string_lossy());
+            }
+        }
+    }
+
+    pub fn print_label_written(offset: u64) {
+        println!("Wrote the wipe label at offset {}.", offset);
+    }
+
+    pub fn print_label(device_id: &str, label: Option<&(WipeLabel, LabelSignature)>) {
+        let (label, signature) = match label {
+            Some(l) => l,
+            None => {
+                println!("No wipe label found on {}.", device_id);
+                return;
+            }
+        };
+        let operator = label
+            .operator
+            .as_ref()
+            .map(|o| format!(" by {}", o))
+            .unwrap_or_default();
+        println!(
+            "Wiped on {} with {}{} using {} (verification: {}).",
+            label.wiped_at, label.scheme, operator, label.tool, label.verify
+        );
+        match signature {
+            LabelSignature::Valid if label.keyed => {
+                println!("{}", style("The label signature is valid.").green())
+            }
+            LabelSignature::Valid => println!("The label isn't signed, its checksum is valid."),
+            LabelSignature::Invalid => println!(
+                "{}",
+                style("The label signature is invalid, it was altered or signed with another key.")
+                    .red()
+                    .bold()
+            ),
+            LabelSignature::Unchecked => println!(
+                "{}",
+                style("The label is signed, use --label-key to check the signature.").yellow()
+            ),
+        }
+    }
+
+    pub fn print_reverification(delay: Duration, percent: u8) {
+        println!(
+            "Waiting {} before verifying {}% of the blocks again.",
+            HumanDuration(delay),
+            percent
+        );
+    }
+
+    pub fn print_block_size_aligned(block_size: usize, geometry: &Geometry) {
+        println!(
+            "{}",
+            style(format!(
+                "Block size raised to {} to match the {}-byte physical sectors of the device.",
+                block_size,
+                geometry.physical_sector_size.max(geometry.sector_size)
+            ))
+            .yellow()
+        );
+    }
+
+    pub fn print_hidden_areas_unlocked(hidden: &HiddenAreas, permanent: bool, size: u64) {
+        let removed = if permanent && hidden.dco_sectors() > 0 {
+            format!(
+                "Removed the HPA ({} sectors) and the DCO ({} sectors) for good",
+                hidden.hpa_sectors(),
+                hidden.dco_sectors()
+            )
+        } else if permanent {
+            format!(
+                "Removed the HPA ({} sectors) for good",
+                hidden.hpa_sectors()
+            )
+        } else {
+            format!(
+                "Unlocked the HPA ({} sectors) until the wipe is done",
+                hidden.hpa_sectors()
+            )
+        };
+        println!(
+            "{}",
+            style(format!(
+                "{}, the device size is now {}.",
+                removed,
+                HumanBytes(size)
+            ))
+            .yellow()
+        );
+    }
+
+    pub fn print_hidden_areas_restored(hidden: &HiddenAreas) {
+        println!(
+            "Restored the HPA, {} sectors are hidden again.",
+            hidden.hpa_sectors()
+        );
+    }
+
+    pub fn print_unmounted(mount_points: &[String]) {
+        for m in mount_points {
+            println!("Unmounted {}.", m);
+        }
+    }
+
+    pub fn print_luks_erasure(erasure: &LuksErasure) {
+        for m in &erasure.closed_mappings {
+            println!("Closed crypt mapping {}.", m);
+        }
+        if erasure.is_unrecoverable() {
+            println!(
+                "{}",
+                style("LUKS headers erased, the container is cryptographically unrecoverable.")
+                    .green()
+                    .bold()
+            );
+        } else {
+            let offsets: Vec<_> = erasure
+                .remaining_headers
+                .iter()
+                .map(|o| o.to_string())
+                .collect();
+            println!(
+                "{} {}",
+                style("LUKS header magic is still present at offsets")
+                    .red()
+                    .bold(),
+                offsets.join(", ")
+            );
+        }
+    }
+
+    pub fn print_detected_progress(task: &WipeTask, state: &WipeState) {
+        if state.position == 0 && state.stage == 0 {
+            println!("No progress detected, the wipe starts from the beginning.");
+        } else {
+            println!(
+                "Detected progress: stage {}/{} ({}) written up to {}.",
+                state.stage + 1,
+                task.scheme.stages.len(),
+                task.scheme.stages[state.stage],
+                HumanBytes(state.position)
+            );
+        }
+    }
+
+    /// Suggests what to do about an error opening or accessing the device, if it's clear.
+    pub fn print_error_hint(err: &anyhow::Error) {
+        let hint = match underlying_storage_error(err) {
+            Some(StorageError::Busy(_)) => {
+                "The device is in use. Unmount its filesystems (or use --unmount), close the programs using it and try again."
+            }
+            Some(StorageError::PermissionDenied(_)) if cfg!(windows) => {
+                "Run lethe from an elevated (administrator) command prompt."
+            }
+            Some(StorageError::PermissionDenied(_)) => {
+                "Run lethe as root, e.g. with sudo. Read-only devices can't be wiped."
+            }
+            Some(StorageError::NotFound(_)) => {
+                "The device may have been disconnected or renamed, check the available devices with the list command."
+            }
+            Some(StorageError::Unsupported(_)) => {
+                "The device can't be opened for direct access, make sure it's a disk or a partition and not a file on a filesystem."
+            }
+            _ => return,
+        };
+        eprintln!("{} {}", style("Hint:").yellow().bold(), hint);
+    }
+
+    pub fn ask_to_resume(checkpoint: &Checkpoint) -> bool {
+        use std::io::prelude::*;
+
+        println!(
+            "Found an unfinished wipe of this device ({}) saved {} ago, stopped at stage {}/{}{} ({}).",
+            checkpoint.scheme.description,
+            HumanDuration(checkpoint_age(checkpoint)),
+            checkpoint.stage + 1,
+            checkpoint.scheme.stages.len(),
+            if checkpoint.at_verification {
+                " verification"
+            } else {
+                ""
+            },
+            HumanBytes(checkpoint.position)
+        );
+        print!("Resume it? (type 'yes' to resume, anything else to start over): ");
+        std::io::stdout().flush().unwrap();
+
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer).is_ok() && answer.trim() == "yes"
+    }
+
+    pub fn ask_to_unlock_hidden_areas(hidden: &HiddenAreas, permanent: bool) -> bool {
+        use std::io::prelude::*;
+
+        println!(
+            "The drive hides {} sectors in a host protected area (HPA) and {} behind a device configuration overlay (DCO).",
+            hidden.hpa_sectors(),
+            hidden.dco_sectors()
+        );
+        if permanent {
+            print!("Remove them for good before the wipe? (type 'yes' to remove, anything else to wipe the visible sectors only): ");
+        } else {
+            print!("Unlock the HPA for the wipe? (type 'yes' to unlock, anything else to wipe the visible sectors only): ");
+        }
+        std::io::stdout().flush().unwrap();
+
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer).is_ok() && answer.trim() == "yes"
+    }
+
     pub fn explain_schemes(schemes: &SchemeRepo) -> String {
         let mut t = Table::new();
         let indent_table_format = FormatBuilder::new().padding(4, 1).build();
@@ -39,6 +699,53 @@ impl ConsoleFrontend {
         format!("Data sanitization schemes:\n{}", t)
     }
 
+    /// Summary of the schemes run in a single session, a failed one stops the rest.
+    pub fn print_pool_stats(stats: &PoolStats) {
+        println!(
+            "Buffers: {} allocated, {} reused, {} kept for reuse.",
+            stats.allocations,
+            stats.reuses,
+            HumanBytes(stats.pooled_bytes as u64)
+        );
+    }
+
+    pub fn print_scheme_results(scheme_ids: &[&str], results: &[bool]) {
+        let mut t = Table::new();
+        let indent_table_format = FormatBuilder::new().padding(4, 1).build();
+        t.set_format(indent_table_format);
+        for (i, id) in scheme_ids.iter().enumerate() {
+            let result = match results.get(i) {
+                Some(true) => style("completed").green(),
+                Some(false) => style("failed").red(),
+                None => style("not run"),
+            };
+            t.add_row(row![format!("{}. {}", i + 1, id), result]);
+        }
+        print!("\nSchemes:\n{}", t);
+    }
+
+    pub fn print_topic(topic: &Topic, platform: Platform) {
+        println!("{}\n", style(topic.title).bold());
+        println!("{}\n", textwrap(topic.text, 80));
+        Self::print_examples(topic, platform, true);
+    }
+
+    /// Prints the examples of the topic which apply to the platform, or all of them
+    /// (with the platform noted) if `all_platforms` is set.
+    pub fn print_examples(topic: &Topic, platform: Platform, all_platforms: bool) {
+        for e in topic
+            .examples
+            .iter()
+            .filter(|e| all_platforms || e.applies_to(platform))
+        {
+            match e.platform {
+                Some(p) if p != platform => println!("    # {} ({})", e.description, p),
+                _ => println!("    # {}", e.description),
+            }
+            println!("    {}\n", style(e.command(platform)).bold());
+        }
+    }
+
     fn describe_scheme(scheme: &Scheme) -> String {
         let mut s = String::new();
 
@@ -59,96 +766,428 @@ impl ConsoleFrontend {
 }
 
 pub struct ConsoleWipeSession {
+    mode: ConsoleMode,
     device_id: String,
+    /// Typed to confirm the wipe, see `confirmation_codes`.
+    short_id: String,
+    details: StorageDetails,
     auto_confirm: bool,
+    /// Set once the wipe is confirmed, the following runs of the session don't ask again.
+    confirmed: bool,
+    /// The task was shown by `confirm`, so the first run doesn't show it again.
+    introduced: bool,
+    diagnostics: DiagnosticsRecorder,
+    diagnostics_path: Option<String>,
+    diagnostics_offered: bool,
+    warnings: Vec<Warning>,
     pb: Option<ProgressBar>,
     session_started: Option<Instant>,
-    stage_started: Option<Instant>,
+    meter: Option<ThroughputMeter>,
+    timer: StageTimer,
+    temperature: Option<Box<dyn Fn() -> Option<f32>>>,
+    temperature_checked: Option<Instant>,
+    last_temperature: Option<f32>,
+    /// Reads the pause, resume and abort keys while the wipe runs.
+    keys: Option<KeyControl>,
+    /// End of the pause window the wipe is waiting out, like `18:00`.
+    paused_until: Option<String>,
+}
+
+impl ConsoleWipeSession {
+    /// Asks to type the short device ID (as shown by the list command) to confirm the wipe
+    /// instead of the full one.
+    pub fn with_short_id(mut self, short_id: &str) -> Self {
+        self.short_id = short_id.to_string();
+        self
+    }
+
+    /// Shows the drive temperature (if available) next to the progress bar.
+    pub fn with_temperature(mut self, temperature: Box<dyn Fn() -> Option<f32>>) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Shows the warnings in one block before asking for confirmation.
+    pub fn with_warnings(mut self, warnings: Vec<Warning>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Never offers to save a diagnostics bundle after a failure.
+    pub fn without_diagnostics(mut self) -> Self {
+        self.diagnostics_offered = false;
+        self
+    }
+
+    /// Shows what is about to be wiped and asks to confirm it. Called before anything is
+    /// done to the device (like unmounting it), the wipe then starts without asking again.
+    /// Returns false if the wipe is declined.
+    pub fn confirm(&mut self, task: &WipeTask, state: &WipeState) -> bool {
+        self.introduce(task, state);
+        self.introduced = true;
+        self.confirmed = self.ask_to_confirm(task);
+        self.confirmed
+    }
+
+    fn ask_to_confirm(&self, task: &WipeTask) -> bool {
+        if self.auto_confirm || self.confirmed || task.verify_only {
+            return true;
+        }
+        // nothing is written in a dry run, so it's not worth typing the ID
+        if task.dry_run {
+            ask_for_confirmation()
+        } else {
+            ask_for_device_confirmation(&confirmation_codes(&self.short_id, &self.details))
+        }
+    }
+
+    /// Shows the device, the task and the warnings.
+    fn introduce(&self, task: &WipeTask, state: &WipeState) {
+        let mut t = Table::new();
+        let indent_table_format = FormatBuilder::new().padding(4, 1).build();
+        t.set_format(indent_table_format);
+        t.add_row(row!["Device", self.device_id]);
+        if let Some(model) = &self.details.model {
+            t.add_row(row!["Model", model]);
+        }
+        if let Some(serial) = &self.details.serial {
+            t.add_row(row!["Serial", serial]);
+        }
+        match (&self.details.label, &self.details.filesystem) {
+            (Some(label), Some(fs)) => {
+                t.add_row(row!["Label", format!("{} ({})", label, fs)]);
+            }
+            (Some(label), None) => {
+                t.add_row(row!["Label", label]);
+            }
+            (None, Some(fs)) => {
+                t.add_row(row!["Filesystem", fs]);
+            }
+            (None, None) => {}
+        }
+        if task.dry_run {
+            t.add_row(row![
+                "Mode",
+                style("Dry run, nothing will be written").yellow()
+            ]);
+        }
+        if task.verify_only {
+            t.add_row(row!["Mode", "Verification only, nothing will be written"]);
+        }
+        t.add_row(row!["Size", HumanBytes(task.total_size)]);
+        if task.is_partial() {
+            t.add_row(row![
+                "Ranges",
+                format!(
+                    "{}({} total)",
+                    describe_ranges(task),
+                    HumanBytes(task.wiped_size())
+                )
+            ]);
+        }
+        t.add_row(row![
+            "Scheme",
+            ConsoleFrontend::describe_scheme(&task.scheme)
+        ]);
+        t.add_row(row!["Block size", HumanBytes(task.block_size as u64)]);
+        t.add_row(row![
+            "Verification",
+            if task.digest_verification {
+                format!("{} (against digests)", task.verify)
+            } else {
+                task.verify.to_string()
+            }
+        ]);
+        if task.scheme.stages.iter().any(|s| !s.is_verifiable()) {
+            t.add_row(row![
+                "TRIM",
+                if task.trim_supported {
+                    "Supported"
+                } else {
+                    "Not supported (discard stages will be skipped)"
+                }
+            ]);
+        }
+        if let Some(duty) = task.duty_cycle {
+            t.add_row(row!["Duty cycle", format!("{}%", duty.percent())]);
+        }
+        if let Some(rate) = task.rate_limit {
+            t.add_row(row!["Rate limit", format!("{}/s", HumanBytes(rate))]);
+        }
+        if let Some(schedule) = &task.schedule {
+            t.add_row(row!["Paused between", schedule.window]);
+        }
+        if state.stage > 0 || state.position > 0 {
+            t.add_row(row![
+                "Resume from",
+                format!(
+                    "Stage {}{} at {}",
+                    state.stage + 1,
+                    if state.at_verification {
+                        " (verification)"
+                    } else {
+                        ""
+                    },
+                    HumanBytes(state.position)
+                )
+            ]);
+        }
+        if let Some(operator) = &task.operator {
+            t.add_row(row!["Operator", operator]);
+        }
+        if let Some(estimate) = task.estimated_duration {
+            let mut lines = vec![HumanDuration(estimate).to_string()];
+            if task.estimated_stage_durations.len() > 1 {
+                for (i, (stage, d)) in task
+                    .scheme
+                    .stages
+                    .iter()
+                    .zip(&task.estimated_stage_durations)
+                    .enumerate()
+                {
+                    lines.push(format!("  {}. {}: {}", i + 1, stage, HumanDuration(*d)));
+                }
+            }
+            t.add_row(row!["Estimated time", lines.join("\n")]);
+        }
+        if self.mode == ConsoleMode::Quiet {
+            // the stages and the final report are still shown
+        } else if task.verify_only {
+            print!("Verifying:\n{}", t);
+        } else {
+            print!("Wiping:\n{}", t);
+        }
+
+        if !self.warnings.is_empty() {
+            println!("Warnings:");
+            for w in &self.warnings {
+                let line = format!("    {} ({})", w.message, w.code);
+                match w.severity {
+                    Severity::Danger => println!("{}", style(line).red().bold()),
+                    Severity::Caution => println!("{}", style(line).yellow()),
+                    Severity::Notice => println!("{}", line),
+                }
+            }
+        }
+    }
+
+    /// Prints above the progress bar, or as a plain line without one.
+    fn println(&self, line: String) {
+        match &self.pb {
+            Some(pb) => pb.println(line),
+            None => println!("{}", line),
+        }
+    }
+
+    fn update_temperature(&mut self) -> bool {
+        let due = self
+            .temperature_checked
+            .map(|t| t.elapsed() >= TEMPERATURE_CHECK_INTERVAL)
+            .unwrap_or(true);
+
+        if let (true, Some(sensor)) = (due, &self.temperature) {
+            self.temperature_checked = Some(Instant::now());
+            self.last_temperature = sensor();
+            return true;
+        }
+        false
+    }
+
+    /// Shows the current and average throughput, the time left and the temperature.
+    fn update_message(&self, task: &WipeTask, state: &WipeState) {
+        let pb = match &self.pb {
+            Some(pb) => pb,
+            None => return,
+        };
+
+        let progress = task.progress_at(state.position);
+        let mut message = String::from(if state.at_verification {
+            "Checking"
+        } else {
+            "Writing"
+        });
+
+        if let Some(meter) = &self.meter {
+            if let Some(current) = meter.current() {
+                message.push_str(&format!(
+                    " {}/s (avg {}/s)",
+                    HumanBytes(current),
+                    HumanBytes(meter.average(progress))
+                ));
+                if let Some(rate) = task.rate_limit {
+                    message.push_str(&format!(" [limited to {}/s]", HumanBytes(rate)));
+                }
+            }
+            if let Some(eta) = meter.eta(task.wiped_size().saturating_sub(progress)) {
+                message.push_str(&format!(", {} left", HumanDuration(eta)));
+            }
+        }
+
+        if let Some(t) = self.last_temperature {
+            message.push_str(&format!(" {:.0}°C", t));
+        }
+        if task.pause.is_paused() {
+            message.push_str(" [paused]");
+        }
+        if let Some(until) = &self.paused_until {
+            message.push_str(&format!(" [paused until {}]", until));
+        }
+
+        pb.set_message(&message);
+    }
 }
 
 impl WipeEventReceiver for ConsoleWipeSession {
-    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) -> () {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        self.diagnostics.record(state, &event);
+        self.timer.record(task, state, &event);
+
+        if let WipeEvent::Completed(_) | WipeEvent::Aborted | WipeEvent::Fatal(_) = event {
+            // gives the terminal back before the diagnostics prompt
+            self.keys = None;
+        }
+
         match event {
             WipeEvent::Started => {
-                let mut t = Table::new();
-                let indent_table_format = FormatBuilder::new().padding(4, 1).build();
-                t.set_format(indent_table_format);
-                t.add_row(row!["Device", self.device_id]);
-                t.add_row(row!["Size", HumanBytes(task.total_size)]);
-                t.add_row(row![
-                    "Scheme",
-                    ConsoleFrontend::describe_scheme(&task.scheme)
-                ]);
-                t.add_row(row!["Block size", HumanBytes(task.block_size as u64)]);
-                t.add_row(row!["Verification", task.verify]);
-                print!("Wiping:\n{}", t);
+                if !std::mem::take(&mut self.introduced) {
+                    self.introduce(task, state);
+                }
 
-                if !self.auto_confirm && !ask_for_confirmation() {
-                    println!("Aborted.");
-                    std::process::exit(0);
+                if !self.confirmed && !self.ask_to_confirm(task) {
+                    exit_aborted();
                 }
+                self.confirmed = true;
                 self.session_started = Some(Instant::now());
+
+                // the confirmation above reads whole lines, so the keys are taken over after it
+                if self.mode == ConsoleMode::Interactive {
+                    self.keys = KeyControl::start(task.cancellation.clone(), task.pause.clone());
+                }
+                if self.keys.is_some() {
+                    println!(
+                        "{}",
+                        style("Press p to pause, r to resume, q to abort.").dim()
+                    );
+                }
             }
             WipeEvent::StageStarted => {
                 let stage_num = format!("Stage {}/{}", state.stage + 1, task.scheme.stages.len());
                 let stage = &task.scheme.stages[state.stage];
 
-                let stage_description = match stage {
-                    Stage::Fill { value } => format!("Value Fill ({:02x})", value),
-                    Stage::Random { seed: _seed } => String::from("Random Fill"),
-                };
-
-                let pb = create_progress_bar(task.total_size);
+                let stage_description = describe_stage(stage);
 
-                if !state.at_verification {
-                    pb.println(format!("\n{}: Performing {}", stage_num, stage_description));
-                } else {
-                    pb.println(format!("\n{}: Verifying {}", stage_num, stage_description));
-                }
+                self.pb = match self.mode {
+                    ConsoleMode::Interactive => Some(create_progress_bar(
+                        task.wiped_size(),
+                        WIPE_PROGRESS_TEMPLATE,
+                    )),
+                    _ => None,
+                };
 
                 if !state.at_verification {
-                    pb.set_message("Writing");
+                    self.println(format!("\n{}: Performing {}", stage_num, stage_description));
                 } else {
-                    pb.set_message("Checking");
+                    self.println(format!("\n{}: Verifying {}", stage_num, stage_description));
                 }
 
-                self.pb = Some(pb);
-                self.stage_started = Some(Instant::now());
+                self.meter = Some(ThroughputMeter::new(task.progress_at(state.position)));
+                self.temperature_checked = None;
+                self.update_temperature();
+                self.update_message(task, state);
+            }
+            WipeEvent::StageSkipped => {
+                println!(
+                    "\nStage {}/{}: Skipping {}, not supported by the device",
+                    state.stage + 1,
+                    task.scheme.stages.len(),
+                    describe_stage(&task.scheme.stages[state.stage])
+                );
             }
             WipeEvent::Progress(position) => {
                 if let Some(pb) = &self.pb {
-                    pb.set_position(position);
+                    pb.set_position(task.progress_at(position));
+                }
+                let sampled = self
+                    .meter
+                    .as_mut()
+                    .map(|m| m.update(task.progress_at(position)))
+                    .unwrap_or(false);
+                if self.update_temperature() || sampled {
+                    self.update_message(task, state);
                 }
             }
             WipeEvent::MarkBlockAsBad(block) => {
-                if let Some(pb) = &self.pb {
-                    pb.println(format!("Unable to access block at {}. Skipping.", block));
-                }
+                self.println(format!("Unable to access block at {}. Skipping.", block));
+            }
+            WipeEvent::MarkBlockAsMismatched(position) => {
+                self.println(format!(
+                    "Block at {} doesn't match the written data. Continuing.",
+                    position
+                ));
+            }
+            WipeEvent::Repairing(region) => {
+                self.println(format!(
+                    "Block at {} doesn't match the written data. Rewriting {}.",
+                    state.position, region
+                ));
+            }
+            WipeEvent::BlockSizeReduced(write_size) => {
+                self.println(format!(
+                    "Writes at {} keep failing. Continuing with {} writes.",
+                    state.position,
+                    HumanBytes(write_size as u64)
+                ));
             }
             WipeEvent::StageCompleted(result) => {
-                if let Some(pb) = &self.pb {
-                    match result {
-                        None => {
-                            if let Some(s) = self.stage_started {
-                                let elapsed = HumanDuration(s.elapsed());
-                                pb.println(format!("✔ Completed in {}", elapsed));
-                            } else {
-                                pb.println("✔ Completed");
-                            }
-                        }
-                        Some(err) => {
-                            pb.println(format!("❌ FAILED! {:#}", err));
-                        }
-                    }
+                let line = match (result, &self.meter) {
+                    (None, Some(m)) => format!(
+                        "✔ Completed in {} ({}/s)",
+                        HumanDuration(m.elapsed()),
+                        HumanBytes(m.average(task.progress_at(state.position)))
+                    ),
+                    (None, None) => "✔ Completed".to_string(),
+                    (Some(err), _) => format!("❌ FAILED! {:#}", err),
+                };
+                self.println(line);
+                if let Some(pb) = self.pb.take() {
                     pb.finish_and_clear();
                 }
             }
-            WipeEvent::Retrying => {
+            WipeEvent::Retrying(delay) => {
                 eprintln!(
-                    "Retrying previous stage at {} in {} seconds.",
-                    state.position, RETRY_BACKOFF_SECONDS
+                    "Retrying {} at {} in {} ({} write and {} verification retries left).",
+                    // only the retries of the block scope stay at the verification
+                    if state.at_verification {
+                        "the region"
+                    } else {
+                        "previous stage"
+                    },
+                    state.position,
+                    HumanDuration(delay),
+                    state.retries_left,
+                    state.verify_retries_left
                 );
-                sleep(std::time::Duration::from_secs(RETRY_BACKOFF_SECONDS as u64));
+            }
+            WipeEvent::Paused => {
+                self.println(format!(
+                    "Paused at {}. Press r to resume or q to abort.",
+                    state.position
+                ));
+                self.update_message(task, state);
+            }
+            WipeEvent::PausedUntil(_) => {
+                let until = task.schedule.map(|s| s.window.end()).unwrap_or_default();
+                self.println(format!(
+                    "Paused at {} until {} (outside of the wipe schedule). Press q to abort.",
+                    state.position, until
+                ));
+                self.paused_until = Some(until);
+                self.update_message(task, state);
+            }
+            WipeEvent::Resumed => {
+                self.paused_until = None;
+                self.println("Resumed.".to_string());
+                self.update_message(task, state);
             }
             WipeEvent::Completed(result) => match result {
                 None => {
@@ -156,24 +1195,55 @@ impl WipeEventReceiver for ConsoleWipeSession {
                         let elapsed = HumanDuration(s.elapsed());
                         println!("✔ Total time: {}", elapsed);
                     }
-                    let total_blocks = task.total_size / task.block_size as u64;
+                    let total_blocks = task.total_blocks();
                     let bad_blocks = state.bad_blocks.borrow_mut().total_marked();
 
                     let mut t = Table::new();
                     let indent_table_format = FormatBuilder::new().padding(4, 1).build();
                     t.set_format(indent_table_format);
+                    if let Some(operator) = &task.operator {
+                        t.add_row(row!["Operator", operator]);
+                    }
                     t.add_row(row!["Total device size", HumanBytes(task.total_size)]);
+                    if task.is_partial() {
+                        t.add_row(row!["Wiped", HumanBytes(task.wiped_size())]);
+                    }
                     t.add_row(row!["Total blocks", total_blocks]);
+                    if let Verify::Sample { percent, .. } = task.verify {
+                        t.add_row(row![
+                            "Verified blocks",
+                            format!("{} ({}% sample)", state.verified_blocks, percent)
+                        ]);
+                    }
                     t.add_row(row![
                         "Skipped blocks",
                         format!(
                             "{} ({}%)",
                             bad_blocks,
-                            bad_blocks * 100 / total_blocks as u32
+                            (bad_blocks as u64 * 100)
+                                .checked_div(total_blocks)
+                                .unwrap_or(0)
                         )
                     ]);
 
                     print!("{}", t);
+                    self.print_stage_timings();
+
+                    if task.dry_run {
+                        println!("Dry run completed, nothing was written.");
+                    }
+                }
+                Some(e) if state.mismatched_blocks.borrow().total_marked() > 0 => {
+                    let blocks = state.mismatched_blocks.borrow().all_marked();
+                    eprintln!("❌ {:#}", e);
+                    eprintln!(
+                        "Mismatched ranges (can be wiped again with --ranges):\n{}",
+                        task.block_ranges(&blocks)
+                            .iter()
+                            .map(|r| format!("{}-{}", r.start, r.end))
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    );
                 }
                 Some(e) => {
                     eprintln!("❌ Unexpected error: {:#}", e);
@@ -183,15 +1253,537 @@ impl WipeEventReceiver for ConsoleWipeSession {
                             eprintln!("Make sure the drive is not mounted.")
                         }
                     };
+
+                    self.offer_diagnostics(task, &e);
                 }
             },
+            WipeEvent::Aborted => {
+                if let Some(pb) = self.pb.take() {
+                    pb.finish_and_clear();
+                }
+                eprintln!(
+                    "\nAborted at stage {}/{}{}, position {}. Run the same command again to resume.",
+                    state.stage + 1,
+                    task.scheme.stages.len(),
+                    if state.at_verification {
+                        " (verification)"
+                    } else {
+                        ""
+                    },
+                    state.position
+                );
+            }
             WipeEvent::Fatal(err) => {
                 eprintln!("❌ Fatal: {:#}", err);
+                ConsoleFrontend::print_error_hint(&err);
+                self.offer_diagnostics(task, &err);
+            }
+        }
+    }
+}
+
+fn describe_stage(stage: &Stage) -> String {
+    match stage {
+        Stage::Fill { value } => format!("Value Fill ({:02x})", value),
+        Stage::Pattern { bytes } => format!(
+            "Pattern Fill ({})",
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        Stage::Random { seed: _seed } => String::from("Random Fill"),
+        Stage::Discard => String::from("Discard (TRIM)"),
+    }
+}
+
+fn describe_phase(phase: Option<PhaseTiming>) -> String {
+    match phase {
+        Some(p) => format!(
+            "{} ({}/s)",
+            HumanDuration(p.duration()),
+            HumanBytes(p.throughput)
+        ),
+        None => "-".to_string(),
+    }
+}
+
+impl ConsoleWipeSession {
+    fn print_stage_timings(&self) {
+        let mut t = Table::new();
+        let indent_table_format = FormatBuilder::new().padding(4, 1).build();
+        t.set_format(indent_table_format);
+        t.set_titles(row!["Stage", "Write", "Verify", "Retries"]);
+        for s in self.timer.stages() {
+            if s.skipped {
+                t.add_row(row![
+                    format!("{}. {}", s.stage, s.description),
+                    "skipped",
+                    "-",
+                    "-"
+                ]);
+            } else {
+                t.add_row(row![
+                    format!("{}. {}", s.stage, s.description),
+                    describe_phase(s.write),
+                    describe_phase(s.verify),
+                    s.retries
+                ]);
+            }
+        }
+        print!("{}", t);
+    }
+
+    fn offer_diagnostics(&self, task: &WipeTask, failure: &anyhow::Error) {
+        if !self.diagnostics_offered {
+            return;
+        }
+        let path = match &self.diagnostics_path {
+            Some(p) => p.clone(),
+            None if !self.auto_confirm && ask_for_diagnostics() => format!(
+                "lethe-diagnostics-{}.zip",
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0)
+            ),
+            None => return,
+        };
+
+        match self.diagnostics.write_bundle(&path, Some(task), failure) {
+            Ok(()) => eprintln!(
+                "Diagnostics bundle saved to {}. Please attach it to an issue at https://github.com/Kostassoid/lethe/issues",
+                path
+            ),
+            Err(e) => eprintln!("Unable to save diagnostics bundle. {:#}", e),
+        }
+    }
+}
+
+pub struct ConsoleSoakSession {
+    mode: ConsoleMode,
+    device_id: String,
+    auto_confirm: bool,
+    temperature: Box<dyn Fn() -> Option<f32>>,
+    pb: Option<ProgressBar>,
+    smart: Option<SmartAttributes>,
+    passes: u32,
+    bytes_read: u64,
+    read_time: std::time::Duration,
+    read_errors: u32,
+    region_failures: u32,
+    temperatures: Vec<f32>,
+}
+
+impl SoakEventReceiver for ConsoleSoakSession {
+    fn handle(&mut self, task: &SoakTask, event: SoakEvent) {
+        match event {
+            SoakEvent::Started => {
+                let mut t = Table::new();
+                let indent_table_format = FormatBuilder::new().padding(4, 1).build();
+                t.set_format(indent_table_format);
+                t.add_row(row!["Device", self.device_id]);
+                t.add_row(row!["Size", HumanBytes(task.total_size)]);
+                t.add_row(row!["Duration", HumanDuration(task.duration)]);
+                t.add_row(row!["Block size", HumanBytes(task.block_size as u64)]);
+                if let Some(r) = task.write_region {
+                    t.add_row(row![
+                        "Write region",
+                        format!("{} - {} ({})", r.start, r.end, HumanBytes(r.len()))
+                    ]);
+                }
+                if self.mode != ConsoleMode::Quiet {
+                    print!("Soak testing:\n{}", t);
+                }
+
+                if task.write_region.is_some() {
+                    println!("Data in the write region will be destroyed.");
+                    if !self.auto_confirm && !ask_for_confirmation() {
+                        exit_aborted();
+                    }
+                }
+
+                self.smart = SmartAttributes::read(&self.device_id);
+                if let Some(t) = (self.temperature)() {
+                    self.temperatures.push(t);
+                }
+            }
+            SoakEvent::PassStarted(pass) => {
+                if self.mode == ConsoleMode::Interactive {
+                    let pb = create_progress_bar(task.total_size, SOAK_PROGRESS_TEMPLATE);
+                    pb.set_message(&format!("Pass {}", pass));
+                    self.pb = Some(pb);
+                }
+            }
+            SoakEvent::Progress(position) => {
+                if let Some(pb) = &self.pb {
+                    pb.set_position(position);
+                }
+            }
+            SoakEvent::ReadError(position) => {
+                let line = format!("Unable to read block at {}.", position);
+                match &self.pb {
+                    Some(pb) => pb.println(line),
+                    None => println!("{}", line),
+                }
+            }
+            SoakEvent::PassCompleted(pass) => {
+                let temperature = (self.temperature)();
+                if let Some(t) = temperature {
+                    self.temperatures.push(t);
+                }
+
+                self.passes += 1;
+                self.bytes_read += pass.bytes_read;
+                self.read_time += pass.elapsed;
+                self.read_errors += pass.read_errors;
+                if pass.region_error.is_some() {
+                    self.region_failures += 1;
+                }
+
+                let mut line = format!(
+                    "[{}] Pass {}{}: read {} in {} ({}/s), {} read error(s)",
+                    crate::actions::report::format_timestamp(SystemTime::now()),
+                    pass.pass,
+                    if pass.complete { "" } else { " (partial)" },
+                    HumanBytes(pass.bytes_read),
+                    HumanDuration(pass.elapsed),
+                    HumanBytes(pass.throughput()),
+                    pass.read_errors
+                );
+                if task.write_region.is_some() {
+                    match &pass.region_error {
+                        None => line.push_str(", region verified"),
+                        Some(e) => line.push_str(&format!(", region FAILED: {}", e)),
+                    }
+                }
+                if let Some(t) = temperature {
+                    line.push_str(&format!(", {:.1}°C", t));
+                }
+
+                match self.pb.take() {
+                    Some(pb) => {
+                        pb.println(line);
+                        pb.finish_and_clear();
+                    }
+                    None => println!("{}", line),
+                }
+            }
+            SoakEvent::Completed(result) => {
+                if let Some(e) = result {
+                    eprintln!("❌ Unexpected error: {:#}", e);
+                }
+
+                let mut t = Table::new();
+                let indent_table_format = FormatBuilder::new().padding(4, 1).build();
+                t.set_format(indent_table_format);
+                t.add_row(row!["Passes", self.passes]);
+                t.add_row(row!["Total read", HumanBytes(self.bytes_read)]);
+                if let Some(ms) = std::num::NonZeroU128::new(self.read_time.as_millis()) {
+                    let throughput = (self.bytes_read as u128 * 1000 / ms.get()) as u64;
+                    t.add_row(row![
+                        "Average throughput",
+                        format!("{}/s", HumanBytes(throughput))
+                    ]);
+                }
+                t.add_row(row!["Read errors", self.read_errors]);
+                if task.write_region.is_some() {
+                    t.add_row(row!["Region failures", self.region_failures]);
+                }
+                if !self.temperatures.is_empty() {
+                    let min = self.temperatures.iter().cloned().fold(f32::MAX, f32::min);
+                    let max = self.temperatures.iter().cloned().fold(f32::MIN, f32::max);
+                    t.add_row(row!["Temperature", format!("{:.1}°C - {:.1}°C", min, max)]);
+                }
+                if let (Some(before), Some(after)) =
+                    (&self.smart, SmartAttributes::read(&self.device_id))
+                {
+                    let changes = after.changes_since(before);
+                    if changes.is_empty() {
+                        t.add_row(row!["SMART changes", "none"]);
+                    }
+                    for (name, old, new) in changes {
+                        t.add_row(row![
+                            "SMART changes",
+                            format!("{}: {} -> {}", name, old, new)
+                        ]);
+                    }
+                }
+                print!("{}", t);
+            }
+        }
+    }
+}
+
+pub struct ConsoleSanitizeSession {
+    mode: ConsoleMode,
+    short_id: String,
+    details: StorageDetails,
+    auto_confirm: bool,
+    confirmed: bool,
+    pb: Option<ProgressBar>,
+}
+
+impl ConsoleSanitizeSession {
+    /// Shows the drive and the operation and asks to confirm it. Called before anything is
+    /// done to the drive (like unmounting it). Returns false if the operation is declined.
+    pub fn confirm(&mut self, task: &SanitizeTask) -> bool {
+        let mut t = Table::new();
+        let indent_table_format = FormatBuilder::new().padding(4, 1).build();
+        t.set_format(indent_table_format);
+        t.add_row(row!["Device ID", task.device_id]);
+        t.add_row(row!["Size", HumanBytes(self.details.size)]);
+        if let Some(model) = &self.details.model {
+            t.add_row(row!["Model", model]);
+        }
+        t.add_row(row!["Operation", task.mode]);
+        if self.mode != ConsoleMode::Quiet {
+            print!("Sanitizing:\n{}", t);
+            println!(
+                "The drive erases all of its data by itself, including the caches and the spare blocks. Once started, it can't be cancelled and goes on after a power cycle."
+            );
+        }
+
+        self.confirmed = self.auto_confirm
+            || ask_for_device_confirmation(&confirmation_codes(&self.short_id, &self.details));
+        self.confirmed
+    }
+}
+
+impl SanitizeEventReceiver for ConsoleSanitizeSession {
+    fn handle(&mut self, task: &SanitizeTask, event: SanitizeEvent) {
+        match event {
+            SanitizeEvent::Started => {
+                if !self.confirmed && !self.confirm(task) {
+                    exit_aborted();
+                }
+                if self.mode == ConsoleMode::Interactive {
+                    let pb = create_progress_bar(100, SANITIZE_PROGRESS_TEMPLATE);
+                    pb.set_message(&task.mode.to_string());
+                    self.pb = Some(pb);
+                }
+            }
+            SanitizeEvent::Progress(done) => {
+                if let Some(pb) = &self.pb {
+                    pb.set_position((done * 100.0) as u64);
+                }
+            }
+            SanitizeEvent::Completed(result) => {
+                if let Some(pb) = self.pb.take() {
+                    pb.finish_and_clear();
+                }
+                match result {
+                    None => println!("✔ The drive is sanitized ({}).", task.mode),
+                    Some(e) => eprintln!("❌ {:#}", e),
+                }
+            }
+        }
+    }
+}
+
+pub struct ConsoleFreeSpaceSession {
+    mode: ConsoleMode,
+    auto_confirm: bool,
+    pb: Option<ProgressBar>,
+    length: u64,
+}
+
+impl FreeSpaceEventReceiver for ConsoleFreeSpaceSession {
+    fn handle(&mut self, task: &FreeSpaceTask, event: FreeSpaceEvent) {
+        let stages = task.scheme.stages.len();
+        match event {
+            FreeSpaceEvent::Started => {
+                let mut t = Table::new();
+                let indent_table_format = FormatBuilder::new().padding(4, 1).build();
+                t.set_format(indent_table_format);
+                t.add_row(row!["Directory", task.directory.to_string_lossy()]);
+                if let Some(free) = task.free_space {
+                    t.add_row(row!["Free space", HumanBytes(free)]);
+                }
+                t.add_row(row!["Scheme", task.scheme.description]);
+                t.add_row(row!["Block size", HumanBytes(task.block_size as u64)]);
+                if self.mode != ConsoleMode::Quiet {
+                    print!("Wiping free space:\n{}", t);
+                    println!(
+                        "The filesystem will be filled up completely for a while, other programs writing to it may fail."
+                    );
+                }
+
+                if !self.auto_confirm && !ask_for_confirmation() {
+                    exit_aborted();
+                }
+            }
+            FreeSpaceEvent::StageStarted(i) => {
+                self.length = task.free_space.unwrap_or(0);
+                let message = format!("Stage {}/{}: {}", i + 1, stages, task.scheme.stages[i]);
+                if self.mode == ConsoleMode::Interactive {
+                    let pb = create_progress_bar(self.length, WIPE_PROGRESS_TEMPLATE);
+                    pb.set_message(&message);
+                    self.pb = Some(pb);
+                } else {
+                    println!("{}", message);
+                }
+            }
+            FreeSpaceEvent::StageSkipped(i) => {
+                println!(
+                    "Stage {}/{}: {} can't be applied to files, skipped.",
+                    i + 1,
+                    stages,
+                    task.scheme.stages[i]
+                );
+            }
+            FreeSpaceEvent::Progress(written) => {
+                if let Some(pb) = &self.pb {
+                    // the free space changes as other programs write to the filesystem
+                    if written > self.length {
+                        self.length = written;
+                        pb.set_length(written);
+                    }
+                    pb.set_position(written);
+                }
+            }
+            FreeSpaceEvent::StageCompleted(written) => {
+                if let Some(pb) = self.pb.take() {
+                    pb.finish_and_clear();
+                }
+                println!("Overwrote {} of free space.", HumanBytes(written));
+            }
+            FreeSpaceEvent::Completed(result) => {
+                if let Some(pb) = self.pb.take() {
+                    pb.finish_and_clear();
+                }
+                match result {
+                    None => println!("✔ Free space wiped, the temporary files are removed."),
+                    Some(e) => eprintln!("❌ Unexpected error: {:#}", e),
+                }
+            }
+        }
+    }
+}
+
+fn checkpoint_age(checkpoint: &Checkpoint) -> std::time::Duration {
+    seconds_since(checkpoint.updated)
+}
+
+/// Time passed since the given UNIX time (in seconds).
+fn seconds_since(time: u64) -> std::time::Duration {
+    let age = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs().saturating_sub(time))
+        .unwrap_or(0);
+    std::time::Duration::from_secs(age)
+}
+
+fn describe_ranges(task: &WipeTask) -> String {
+    let mut s = String::new();
+    for r in &task.ranges {
+        s.push_str(&format!(
+            "{} - {} ({})\n",
+            r.start,
+            r.end,
+            HumanBytes(r.len())
+        ));
+    }
+    s
+}
+
+/// Prints the stages and the result as lines starting with the device ID, for the wipes
+/// of `wipe-all` which may run side by side.
+pub struct BatchWipeSession {
+    device_id: String,
+}
+
+impl WipeEventReceiver for BatchWipeSession {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        let stage_num = || format!("Stage {}/{}", state.stage + 1, task.scheme.stages.len());
+        match event {
+            WipeEvent::StageStarted => println!(
+                "{}: {}: {} {}",
+                self.device_id,
+                stage_num(),
+                if state.at_verification {
+                    "Verifying"
+                } else {
+                    "Performing"
+                },
+                describe_stage(&task.scheme.stages[state.stage])
+            ),
+            WipeEvent::StageSkipped => println!(
+                "{}: {}: Skipping {}, not supported by the device",
+                self.device_id,
+                stage_num(),
+                describe_stage(&task.scheme.stages[state.stage])
+            ),
+            WipeEvent::MarkBlockAsBad(block) => println!(
+                "{}: Unable to access block at {}. Skipping.",
+                self.device_id, block
+            ),
+            WipeEvent::StageCompleted(Some(err)) => {
+                eprintln!("{}: {}: ❌ FAILED! {:#}", self.device_id, stage_num(), err)
+            }
+            WipeEvent::Retrying(delay) => eprintln!(
+                "{}: Retrying at {} in {}.",
+                self.device_id,
+                state.position,
+                HumanDuration(delay)
+            ),
+            WipeEvent::Completed(None) if task.dry_run => {
+                println!(
+                    "{}: ✔ Dry run completed, nothing was written.",
+                    self.device_id
+                )
             }
+            WipeEvent::Completed(None) => println!("{}: ✔ Completed", self.device_id),
+            WipeEvent::Completed(Some(err)) => eprintln!("{}: ❌ {:#}", self.device_id, err),
+            WipeEvent::Aborted => eprintln!(
+                "{}: Aborted at {}, position {}.",
+                self.device_id,
+                stage_num(),
+                state.position
+            ),
+            WipeEvent::Fatal(err) => eprintln!("{}: ❌ {:#}", self.device_id, err),
+            _ => {}
         }
     }
 }
 
+/// Progress of `check-blank`, the blocks which are not blank are listed as ranges at the end
+/// instead of one by one.
+pub struct BlankCheckSession {
+    inner: ConsoleWipeSession,
+    value: u8,
+}
+
+impl WipeEventReceiver for BlankCheckSession {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        match event {
+            WipeEvent::MarkBlockAsMismatched(_) => {}
+            WipeEvent::Completed(None) => {
+                self.inner.handle(task, state, event);
+                println!(
+                    "{}",
+                    style(format!(
+                        "The device is blank, all the bytes are 0x{:02x}.",
+                        self.value
+                    ))
+                    .green()
+                );
+            }
+            _ => self.inner.handle(task, state, event),
+        }
+    }
+}
+
+/// Ends lethe after a declined confirmation. The process exits without unwinding, so the
+/// terminal taken over for the keyboard control of another wipe is restored first.
+fn exit_aborted() -> ! {
+    println!("Aborted.");
+    #[cfg(unix)]
+    crate::actions::restore_terminal();
+    std::process::exit(0);
+}
+
 fn ask_for_confirmation() -> bool {
     use std::io::prelude::*;
 
@@ -202,12 +1794,73 @@ fn ask_for_confirmation() -> bool {
     std::io::stdin().read_line(&mut confirm).is_ok() && confirm.trim() == "yes"
 }
 
-fn create_progress_bar(size: u64) -> ProgressBar {
+/// Answers accepted to confirm a wipe of the device: its short ID, or the last
+/// 4 characters of its serial number, so a wrong device is noticed before it's wiped.
+fn confirmation_codes(short_id: &str, details: &StorageDetails) -> Vec<String> {
+    let mut codes = vec![short_id.to_string()];
+    if let Some(serial) = &details.serial {
+        let chars: Vec<_> = serial.chars().collect();
+        if chars.len() >= 4 {
+            codes.push(chars[chars.len() - 4..].iter().collect());
+        }
+    }
+    codes
+}
+
+fn ask_for_device_confirmation(codes: &[String]) -> bool {
+    use std::io::prelude::*;
+
+    if codes.len() > 1 {
+        print!(
+            "Are you sure? (type the device ID '{}' or the last 4 characters of its serial number to confirm): ",
+            codes[0]
+        );
+    } else {
+        print!(
+            "Are you sure? (type the device ID '{}' to confirm): ",
+            codes[0]
+        );
+    }
+    std::io::stdout().flush().unwrap();
+
+    let mut confirm = String::new();
+    std::io::stdin().read_line(&mut confirm).is_ok()
+        && codes.iter().any(|c| c.eq_ignore_ascii_case(confirm.trim()))
+}
+
+fn ask_for_diagnostics() -> bool {
+    use std::io::prelude::*;
+
+    print!("Save a diagnostics bundle for a bug report? (y/N): ");
+    std::io::stdout().flush().unwrap();
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok() && answer.trim().eq_ignore_ascii_case("y")
+}
+
+fn create_progress_bar(size: u64, template: &str) -> ProgressBar {
     let pb = ProgressBar::new(size);
 
-    pb.set_style(ProgressStyle::default_bar()
-        .template("[{elapsed_precise}] {bar:40.cyan/blue} {bytes:>7}/{total_bytes:7} ({eta} left) {msg}")
-        .progress_chars("█▉▊▋▌▍▎▏  "));
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(template)
+            .progress_chars("█▉▊▋▌▍▎▏  "),
+    );
 
     pb
 }
+
+/// Wraps the text at word boundaries so that the lines are at most `width` long.
+fn textwrap(text: &str, width: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,203 @@
+//! Default options read from `lethe.toml`, so a wipe station can keep its settings in one
+//! place instead of repeating them on every command line. Options given on the command
+//! line always win.
+
+use crate::actions::email::SmtpSettings;
+use crate::storage::{DeviceFilter, DevicePattern};
+use crate::ui::exitcode::InvalidArgument;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = "lethe.toml";
+
+pub const DEFAULT_SCHEME: &str = "random2x";
+pub const DEFAULT_VERIFY: &str = "last";
+pub const DEFAULT_RETRIES: u32 = 8;
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Default sanitization scheme of `wipe`.
+    pub scheme: Option<String>,
+    /// Default block size of `wipe`, in the same format as `--blocksize`.
+    pub blocksize: Option<String>,
+    /// Default maximum number of retries after I/O errors.
+    pub retries: Option<u32>,
+    /// Default verification mode, in the same format as `--verify`.
+    pub verify: Option<String>,
+    /// Devices hidden from the list and refused by `wipe`, as path globs or `serial:` and
+    /// `label:` patterns.
+    pub exclude: Vec<String>,
+    /// If given, only these devices (and their partitions) are listed.
+    pub allow: Vec<String>,
+    /// SMTP server for `--email-to`.
+    pub email: Option<SmtpSettings>,
+}
+
+impl Config {
+    /// `lethe.toml` in the user config directory (`$XDG_CONFIG_HOME/lethe` or
+    /// `~/.config/lethe`, `%APPDATA%\lethe` on Windows).
+    pub fn default_location() -> Option<PathBuf> {
+        let base = if cfg!(windows) {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        };
+
+        base.map(|b| b.join("lethe").join(FILE_NAME))
+    }
+
+    /// Reads the config file, a missing file gives the built-in defaults.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config> {
+        let path = path.as_ref();
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => {
+                return Err(err).context(format!("Unable to read {}", path.display()));
+            }
+        };
+        Config::parse(&content).context(InvalidArgument(format!(
+            "Invalid config file {}",
+            path.display()
+        )))
+    }
+
+    pub fn parse(content: &str) -> Result<Config> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// The settings `wipe` runs with when no options are given, the block size is left
+    /// empty as it depends on the device.
+    pub fn effective(&self) -> Config {
+        Config {
+            scheme: self
+                .scheme
+                .clone()
+                .or_else(|| Some(DEFAULT_SCHEME.to_string())),
+            blocksize: self.blocksize.clone(),
+            retries: self.retries.or(Some(DEFAULT_RETRIES)),
+            verify: self
+                .verify
+                .clone()
+                .or_else(|| Some(DEFAULT_VERIFY.to_string())),
+            exclude: self.exclude.clone(),
+            allow: self.allow.clone(),
+            email: self.email.clone(),
+        }
+    }
+
+    /// Patterns of the excluded and allowed devices, see `DevicePattern` for the syntax.
+    pub fn device_filter(&self) -> Result<DeviceFilter> {
+        let parse = |patterns: &[String]| -> Result<Vec<DevicePattern>> {
+            patterns
+                .iter()
+                .map(|p| {
+                    DevicePattern::parse(p).context(InvalidArgument(format!(
+                        "Invalid device pattern in the config: {}",
+                        p
+                    )))
+                })
+                .collect()
+        };
+        Ok(DeviceFilter {
+            exclude: parse(&self.exclude)?,
+            allow: parse(&self.allow)?,
+        })
+    }
+
+    /// Uses the configured value when the option wasn't given on the command line.
+    pub fn value_of<'a>(
+        cmd: &'a clap::ArgMatches,
+        name: &str,
+        configured: &'a Option<String>,
+    ) -> Option<&'a str> {
+        match configured {
+            Some(value) if cmd.occurrences_of(name) == 0 => Some(value.as_str()),
+            _ => cmd.value_of(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config_parse() {
+        let config = Config::parse(
+            r#"
+            scheme = "zero"
+            blocksize = "4m"
+            retries = 2
+            verify = "all"
+            exclude = ["/dev/sda", "serial:S4EV*", "label:SYSTEM"]
+            allow = ["/dev/sd?"]
+
+            [email]
+            server = "smtp://mail.example.com:587"
+            from = "lethe@example.com"
+            username = "lethe"
+            password = "secret"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.scheme.as_deref(), Some("zero"));
+        assert_eq!(config.blocksize.as_deref(), Some("4m"));
+        assert_eq!(config.retries, Some(2));
+        assert_eq!(config.verify.as_deref(), Some("all"));
+        let filter = config.device_filter().unwrap();
+        assert_eq!(filter.exclude.len(), 3);
+        assert_eq!(filter.allow.len(), 1);
+        let email = config.email.as_ref().unwrap();
+        assert_eq!(email.from, "lethe@example.com");
+        assert_eq!(email.password.as_deref(), Some("secret"));
+        assert!(!email.allow_plaintext);
+        assert!(!serde_json::to_string(&config).unwrap().contains("secret"));
+        assert!(Config::parse("exclude = [\"label:\"]")
+            .unwrap()
+            .device_filter()
+            .is_err());
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("schema = \"zero\"").is_err());
+        assert!(Config::parse("retries = \"many\"").is_err());
+    }
+
+    #[test]
+    fn test_config_missing_file() {
+        let path = std::env::temp_dir().join("lethe_test_config_missing.toml");
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_config_value_of() {
+        let app = clap::App::new("test").arg(
+            clap::Arg::with_name("scheme")
+                .long("scheme")
+                .takes_value(true)
+                .default_value("random2x"),
+        );
+        let configured = Some("zero".to_string());
+
+        let defaults = app.clone().get_matches_from(vec!["test"]);
+        assert_eq!(
+            Config::value_of(&defaults, "scheme", &configured),
+            Some("zero")
+        );
+        assert_eq!(
+            Config::value_of(&defaults, "scheme", &None),
+            Some("random2x")
+        );
+
+        let explicit = app.get_matches_from(vec!["test", "--scheme", "dod"]);
+        assert_eq!(
+            Config::value_of(&explicit, "scheme", &configured),
+            Some("dod")
+        );
+    }
+}
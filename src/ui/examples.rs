@@ -0,0 +1,248 @@
+use std::fmt::{Display, Formatter};
+
+/// Placeholder for a device ID in the example commands.
+const DEVICE: &str = "{device}";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Platform {
+    Linux,
+    MacOs,
+    Windows,
+}
+
+impl Platform {
+    pub fn current() -> Platform {
+        if cfg!(windows) {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::MacOs
+        } else {
+            Platform::Linux
+        }
+    }
+
+    /// Typical ID of a whole (non-system) drive as shown by `lethe list`.
+    fn sample_device(&self) -> &'static str {
+        match self {
+            Platform::Linux => "/dev/sdb",
+            Platform::MacOs => "/dev/rdisk2",
+            Platform::Windows => "\\\\.\\PhysicalDrive1",
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Platform::Linux => f.write_str("Linux"),
+            Platform::MacOs => f.write_str("macOS"),
+            Platform::Windows => f.write_str("Windows"),
+        }
+    }
+}
+
+pub struct Example {
+    pub description: &'static str,
+    command: &'static str,
+    /// Only applies to this platform, when set.
+    pub platform: Option<Platform>,
+}
+
+impl Example {
+    const fn any(description: &'static str, command: &'static str) -> Example {
+        Example {
+            description,
+            command,
+            platform: None,
+        }
+    }
+
+    const fn only(platform: Platform, description: &'static str, command: &'static str) -> Example {
+        Example {
+            description,
+            command,
+            platform: Some(platform),
+        }
+    }
+
+    pub fn applies_to(&self, platform: Platform) -> bool {
+        self.platform.is_none_or(|p| p == platform)
+    }
+
+    /// The command with the device IDs of the given platform (or of the one the example is for).
+    pub fn command(&self, platform: Platform) -> String {
+        let platform = self.platform.unwrap_or(platform);
+        self.command.replace(DEVICE, platform.sample_device())
+    }
+}
+
+pub struct Topic {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub text: &'static str,
+    pub examples: &'static [Example],
+}
+
+pub static TOPICS: &[Topic] = &[
+    Topic {
+        name: "selection",
+        title: "Selecting a device",
+        text: "Devices are selected by the Device ID (or the shorter Short ID) shown by the list \
+               command. Pick the whole drive rather than a partition to wipe all of its data. \
+               Drives holding the running system are marked in the list.",
+        examples: &[
+            Example::any("List the available devices", "lethe list"),
+            Example::any("Wipe a device", "lethe wipe {device}"),
+            Example::any(
+                "Look at the first sectors and the filesystems of a device before wiping it",
+                "lethe inspect {device}",
+            ),
+            Example::any(
+                "Wipe only the first and the last megabyte",
+                "lethe wipe --ranges 0-1M,END-1M {device}",
+            ),
+            Example::any(
+                "Wipe all removable drives of 16GB or more, two at a time",
+                "lethe wipe-all --type=removable --min-size=16G --scheme=zero --parallel=2 --yes",
+            ),
+            Example::any(
+                "Write the same random data to a batch of identical drives at once",
+                "lethe wipe-all --type=removable --scheme=random --mirror",
+            ),
+            Example::any(
+                "Wipe each removable drive as soon as it's plugged in",
+                "lethe watch --type=removable --scheme=zero --auto",
+            ),
+            Example::only(
+                Platform::MacOs,
+                "Use the raw device (rdiskN) instead of diskN for much faster access",
+                "lethe wipe /dev/rdisk2",
+            ),
+        ],
+    },
+    Topic {
+        name: "schemes",
+        title: "Data sanitization schemes",
+        text: "A scheme is a sequence of stages, each overwriting the device once. The schemes \
+               are listed at the end of `lethe help wipe`. By default only the last stage is \
+               verified.",
+        examples: &[
+            Example::any(
+                "Single pass of zeroes without verification (fastest)",
+                "lethe wipe --scheme=zero --verify=no {device}",
+            ),
+            Example::any(
+                "Two random passes verifying each of them",
+                "lethe wipe --scheme=random2x --verify=all {device}",
+            ),
+            Example::any(
+                "Run several schemes back-to-back, e.g. to test the media",
+                "lethe wipe --schemes=badblocks,random {device}",
+            ),
+            Example::any(
+                "Wipe only partition tables and filesystem headers",
+                "lethe wipe --scheme=metadata {device}",
+            ),
+            Example::any(
+                "Quickly destroy the partition tables and filesystems, along with the first and last 16M",
+                "lethe wipe --scheme=quick --quick-size=16M {device}",
+            ),
+            Example::any(
+                "Shred the filesystem metadata before a long wipe",
+                "lethe wipe --metadata-first --scheme=random2x {device}",
+            ),
+            Example::any(
+                "Check that a device wiped earlier is filled with zeroes",
+                "lethe verify {device}",
+            ),
+            Example::any(
+                "Check the last random stage of a wipe made with --deterministic",
+                "lethe verify --scheme=random --seed-file seed.txt {device}",
+            ),
+            Example::any(
+                "Check that a new or returned drive is blank and list the ranges holding data",
+                "lethe check-blank {device}",
+            ),
+            Example::only(
+                Platform::Linux,
+                "Erase a modern drive with its own SANITIZE command, spare blocks included",
+                "lethe sanitize --mode=crypto {device}",
+            ),
+        ],
+    },
+    Topic {
+        name: "windows-paths",
+        title: "Device paths on Windows",
+        text: "Windows drives are accessed as \\\\.\\PhysicalDriveN, where N is the disk number \
+               shown by `lethe list` (or Disk Management). Drive letters like C: can't be wiped. \
+               The backslashes don't need to be escaped, neither in the command prompt nor in \
+               PowerShell (where the path can also be quoted). \
+               The commands have to be run from an elevated (Administrator) prompt.",
+        examples: &[
+            Example::only(
+                Platform::Windows,
+                "Wipe a drive from the command prompt",
+                "lethe wipe \\\\.\\PhysicalDrive1",
+            ),
+            Example::only(
+                Platform::Windows,
+                "Wipe a drive from PowerShell",
+                "lethe wipe '\\\\.\\PhysicalDrive1'",
+            ),
+        ],
+    },
+    Topic {
+        name: "resume",
+        title: "Resuming interrupted wipes",
+        text: "The progress of a wipe is saved periodically. Running the same wipe command again \
+               offers to continue from the last saved position, `--yes` continues automatically. \
+               Saved progress older than 30 days is removed.",
+        examples: &[
+            Example::any("Resume without asking", "lethe wipe --yes {device}"),
+            Example::any("List the interrupted wipes", "lethe jobs --incomplete"),
+            Example::any("Continue an interrupted wipe", "lethe resume <job-id>"),
+            Example::any("List the saved progress", "lethe checkpoints list"),
+            Example::any(
+                "Remove the saved progress older than a week",
+                "lethe checkpoints prune --max-age 7",
+            ),
+        ],
+    },
+    Topic {
+        name: "exit-codes",
+        title: "Exit codes",
+        text: "0 - completed; 1 - failed (I/O or other errors); 2 - invalid arguments; \
+               3 - device not found; 4 - permission denied; 5 - verification failed; \
+               6 - completed, but bad blocks were skipped; 130 - aborted by the user (Ctrl+C).",
+        examples: &[Example::any(
+            "Wipe from a script, without questions and with the events as JSON",
+            "lethe wipe --yes --output=json {device}",
+        )],
+    },
+];
+
+pub fn find_topic(name: &str) -> Option<&'static Topic> {
+    TOPICS.iter().find(|t| t.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_examples_render_platform_paths() {
+        let wipe = &find_topic("selection").unwrap().examples[1];
+        assert_eq!(wipe.command(Platform::Linux), "lethe wipe /dev/sdb");
+        assert_eq!(
+            wipe.command(Platform::Windows),
+            "lethe wipe \\\\.\\PhysicalDrive1"
+        );
+
+        let topic = find_topic("windows-paths").unwrap();
+        assert!(topic
+            .examples
+            .iter()
+            .all(|e| !e.applies_to(Platform::Linux)));
+        assert!(find_topic("unknown").is_none());
+    }
+}
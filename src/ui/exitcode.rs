@@ -0,0 +1,90 @@
+//! Exit codes telling the scripts running lethe why it failed. Ctrl+C exits with
+//! `INTERRUPTED_EXIT_CODE` (130), like other programs stopped with SIGINT.
+
+use crate::actions::{underlying_storage_error, WipeOutcome, INTERRUPTED_EXIT_CODE};
+use crate::storage::StorageError;
+
+pub const FAILED: i32 = 1;
+pub const INVALID_ARGUMENTS: i32 = 2;
+pub const DEVICE_NOT_FOUND: i32 = 3;
+pub const PERMISSION_DENIED: i32 = 4;
+pub const VERIFICATION_FAILED: i32 = 5;
+pub const BAD_BLOCKS_SKIPPED: i32 = 6;
+
+/// A value given on the command line which can't be used.
+#[derive(thiserror::Error, Debug)]
+#[error("{0}")]
+pub struct InvalidArgument(pub String);
+
+/// The device given on the command line isn't one of the listed devices.
+#[derive(thiserror::Error, Debug)]
+#[error("Unknown device {0}")]
+pub struct UnknownDevice(pub String);
+
+/// Ends lethe with the exit code after the outcome (like a failed wipe) was reported, so
+/// everything held on the way (like an unlocked HPA) is released first.
+#[derive(thiserror::Error, Debug)]
+#[error("Exited with code {0}")]
+pub struct Exit(pub i32);
+
+/// Exit code of a finished wipe (or verification), zero if it succeeded.
+pub fn of_outcome(outcome: WipeOutcome) -> i32 {
+    match outcome {
+        WipeOutcome::Completed => 0,
+        WipeOutcome::CompletedWithBadBlocks => BAD_BLOCKS_SKIPPED,
+        WipeOutcome::Aborted => INTERRUPTED_EXIT_CODE,
+        WipeOutcome::VerificationFailed => VERIFICATION_FAILED,
+        WipeOutcome::PermissionDenied => PERMISSION_DENIED,
+        WipeOutcome::DeviceNotFound => DEVICE_NOT_FOUND,
+        WipeOutcome::Failed => FAILED,
+    }
+}
+
+/// Exit code of an error which stopped lethe before (or instead of) running a wipe.
+pub fn of_error(err: &anyhow::Error) -> i32 {
+    if let Some(Exit(code)) = err.downcast_ref() {
+        return *code;
+    }
+    if err.downcast_ref::<InvalidArgument>().is_some() {
+        return INVALID_ARGUMENTS;
+    }
+    if err.downcast_ref::<UnknownDevice>().is_some() {
+        return DEVICE_NOT_FOUND;
+    }
+    match underlying_storage_error(err) {
+        Some(StorageError::NotFound(_)) => DEVICE_NOT_FOUND,
+        Some(StorageError::PermissionDenied(_)) => PERMISSION_DENIED,
+        _ => FAILED,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_code_of_error() {
+        let invalid: anyhow::Result<()> = Err(anyhow!("not a number"))
+            .context(InvalidArgument("Invalid blocksize value: x".to_string()));
+        assert_eq!(of_error(&invalid.unwrap_err()), INVALID_ARGUMENTS);
+
+        let unknown = anyhow::Error::new(UnknownDevice("/dev/sdx".to_string()));
+        assert_eq!(of_error(&unknown), DEVICE_NOT_FOUND);
+        assert_eq!(unknown.to_string(), "Unknown device /dev/sdx");
+
+        let denied: anyhow::Result<()> = Err(StorageError::PermissionDenied(
+            std::io::ErrorKind::PermissionDenied.into(),
+        ))
+        .context("Unable to open file-device /dev/sda");
+        assert_eq!(of_error(&denied.unwrap_err()), PERMISSION_DENIED);
+
+        assert_eq!(of_error(&anyhow!("Post-wipe action failed")), FAILED);
+        assert_eq!(
+            of_error(&Exit(VERIFICATION_FAILED).into()),
+            VERIFICATION_FAILED
+        );
+        assert_eq!(of_outcome(WipeOutcome::Completed), 0);
+        assert_eq!(of_outcome(WipeOutcome::Aborted), INTERRUPTED_EXIT_CODE);
+    }
+}
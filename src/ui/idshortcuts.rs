@@ -50,15 +50,31 @@ impl Tree {
 
 pub struct IdShortcuts {
     inner: HashMap<String, String>,
+    aliases: HashMap<String, String>,
 }
 
 impl IdShortcuts {
     pub fn from(ids: HashSet<&str>) -> IdShortcuts {
         IdShortcuts {
             inner: Self::build_map_from(ids.into_iter().collect()),
+            aliases: HashMap::new(),
         }
     }
 
+    /// Also accepts the aliases (as full paths or just the file names) of the ids.
+    pub fn with_aliases<'a>(
+        mut self,
+        aliases: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Self {
+        for (alias, id) in aliases {
+            if let Some(name) = alias.rsplit('/').next() {
+                self.aliases.insert(name.to_owned(), id.to_owned());
+            }
+            self.aliases.insert(alias.to_owned(), id.to_owned());
+        }
+        self
+    }
+
     #[allow(dead_code)]
     pub fn keys(&self) -> Vec<&str> {
         self.inner.keys().map(|s| s.as_ref()).collect()
@@ -73,6 +89,7 @@ impl IdShortcuts {
             .iter()
             .find(|kv| kv.0 == key || kv.1 == key)
             .map(|kv| kv.1)
+            .or_else(|| self.aliases.get(key))
     }
 
     fn build_map_from(ids: Vec<&str>) -> HashMap<String, String> {
@@ -113,9 +130,7 @@ mod test {
 
     #[test]
     fn test_no_intersection() {
-        let ids = IdShortcuts::from(HashSet::from_iter(
-            vec!["abc", "def", "ghi"].iter().cloned(),
-        ));
+        let ids = IdShortcuts::from(HashSet::from_iter(["abc", "def", "ghi"].iter().cloned()));
 
         let mut sorted = ids.keys();
         sorted.sort();
@@ -125,7 +140,7 @@ mod test {
     #[test]
     fn test_last_part() {
         let ids = IdShortcuts::from(HashSet::from_iter(
-            vec!["abc1", "abc2", "abc123"].iter().cloned(),
+            ["abc1", "abc2", "abc123"].iter().cloned(),
         ));
 
         let mut sorted = ids.keys();
@@ -136,7 +151,7 @@ mod test {
     #[test]
     fn test_normal() {
         let ids = IdShortcuts::from(HashSet::from_iter(
-            vec!["abc", "acd", "abd", "bac", "bad"].iter().cloned(),
+            ["abc", "acd", "abd", "bac", "bad"].iter().cloned(),
         ));
 
         let mut sorted = ids.keys();
@@ -147,9 +162,7 @@ mod test {
     #[test]
     fn test_sub_prefixes() {
         let ids = IdShortcuts::from(HashSet::from_iter(
-            vec!["abc", "abc1", "abc2", "abc23", "abc123"]
-                .iter()
-                .cloned(),
+            ["abc", "abc1", "abc2", "abc23", "abc123"].iter().cloned(),
         ));
 
         let mut sorted = ids.keys();
@@ -196,7 +209,7 @@ mod test {
     #[test]
     fn test_real_nix() {
         let ids = IdShortcuts::from(HashSet::from_iter(
-            vec![
+            [
                 "/dev/rdisk0s1",
                 "/dev/rdisk0s3",
                 "/dev/rdisk0s4",
@@ -215,4 +228,18 @@ mod test {
         assert_eq!("/dev/rdisk0s3", ids.get("03").unwrap());
         assert_eq!("/dev/rdisk0s3", ids.get("/dev/rdisk0s3").unwrap());
     }
+
+    #[test]
+    fn test_aliases() {
+        let ids = IdShortcuts::from(HashSet::from_iter(["/dev/sda", "/dev/sdb"].iter().cloned()))
+            .with_aliases(vec![("/dev/disk/by-id/wwn-0x5002538e40a1b2c3", "/dev/sdb")]);
+
+        assert_eq!("/dev/sda", ids.get("a").unwrap());
+        assert_eq!(
+            "/dev/sdb",
+            ids.get("/dev/disk/by-id/wwn-0x5002538e40a1b2c3").unwrap()
+        );
+        assert_eq!("/dev/sdb", ids.get("wwn-0x5002538e40a1b2c3").unwrap());
+        assert!(ids.get("wwn-0x0").is_none());
+    }
 }
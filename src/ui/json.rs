@@ -0,0 +1,957 @@
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::actions::batch::BatchResult;
+use crate::actions::eventlog::os_error_code;
+use crate::actions::evidence::hex;
+use crate::actions::freespace::{FreeSpaceEvent, FreeSpaceEventReceiver, FreeSpaceTask};
+use crate::actions::inspect::Inspection;
+use crate::actions::label::{LabelSignature, WipeLabel};
+use crate::actions::luks::LuksErasure;
+use crate::actions::privacy::SelfAudit;
+use crate::actions::probe::Signature;
+use crate::actions::sanitize::{SanitizeEvent, SanitizeEventReceiver, SanitizeTask};
+use crate::actions::soak::{SoakEvent, SoakEventReceiver, SoakTask};
+use crate::actions::state::Job;
+use crate::actions::timing::ThroughputMeter;
+use crate::actions::warnings::Warning;
+use crate::actions::{EventStamp, WipeEvent, WipeEventReceiver, WipeRange, WipeState, WipeTask};
+use crate::sanitization::mem::PoolStats;
+use crate::storage::hidden::HiddenAreas;
+use crate::storage::sanitize::{SanitizeStatus, SanitizeSupport};
+use crate::storage::smart::SmartAttributes;
+use crate::storage::StorageRef;
+use crate::ui::config::Config;
+use crate::ui::idshortcuts::IdShortcuts;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+struct DeviceEntry<'a> {
+    id: &'a str,
+    short_id: Option<&'a str>,
+    size: u64,
+    block_size: usize,
+    logical_sector_size: Option<u32>,
+    physical_sector_size: Option<u32>,
+    storage_type: String,
+    media_type: String,
+    bus_type: String,
+    serial: Option<&'a str>,
+    model: Option<&'a str>,
+    firmware: Option<&'a str>,
+    aliases: &'a [String],
+    parent: Option<&'a str>,
+    mount_point: Option<&'a str>,
+    label: Option<&'a str>,
+    filesystem: Option<&'a str>,
+    trim_supported: bool,
+    boot: bool,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum JsonEvent<'a> {
+    Started {
+        device: &'a str,
+        size: u64,
+        block_size: usize,
+        scheme: &'a str,
+        stages: usize,
+        verify: String,
+        operator: Option<String>,
+        ranges: &'a [WipeRange],
+        wiped_size: u64,
+        duty_cycle: Option<u8>,
+        rate_limit: Option<u64>,
+        dry_run: bool,
+        estimated_duration_secs: Option<u64>,
+        estimated_stage_durations_secs: Vec<u64>,
+        digest_verification: bool,
+        verify_only: bool,
+    },
+    StageStarted {
+        stage: usize,
+        stages: usize,
+        verifying: bool,
+        description: String,
+    },
+    StageSkipped {
+        stage: usize,
+        stages: usize,
+        description: String,
+    },
+    Progress {
+        stage: usize,
+        verifying: bool,
+        position: u64,
+        done: u64,
+        total: u64,
+        throughput: Option<u64>,
+        average_throughput: u64,
+        eta_secs: Option<u64>,
+    },
+    BadBlock {
+        position: u64,
+    },
+    Mismatch {
+        position: u64,
+    },
+    Repairing {
+        position: u64,
+        region: WipeRange,
+    },
+    BlockSizeReduced {
+        position: u64,
+        write_size: usize,
+    },
+    StageCompleted {
+        stage: usize,
+        verifying: bool,
+        error: Option<String>,
+        os_error: Option<i32>,
+        throughput: u64,
+    },
+    Retrying {
+        position: u64,
+        retries_left: u32,
+        verify_retries_left: u32,
+        delay_secs: u64,
+    },
+    Completed {
+        error: Option<String>,
+        os_error: Option<i32>,
+        bad_blocks: u32,
+        verified_blocks: u64,
+        mismatched_ranges: Vec<WipeRange>,
+    },
+    Paused {
+        stage: usize,
+        verifying: bool,
+        position: u64,
+    },
+    PausedUntil {
+        stage: usize,
+        verifying: bool,
+        position: u64,
+        until: String,
+    },
+    Resumed,
+    Aborted {
+        stage: usize,
+        verifying: bool,
+        position: u64,
+    },
+    Fatal {
+        error: String,
+        os_error: Option<i32>,
+    },
+}
+
+#[derive(Serialize)]
+struct SmartChange {
+    name: String,
+    before: u64,
+    after: u64,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum JsonSoakEvent<'a> {
+    Started {
+        device: &'a str,
+        size: u64,
+        duration_secs: u64,
+        block_size: usize,
+        write_region: Option<WipeRange>,
+    },
+    ReadError {
+        position: u64,
+    },
+    PassCompleted {
+        pass: u32,
+        bytes_read: u64,
+        seconds: f64,
+        throughput: u64,
+        read_errors: u32,
+        complete: bool,
+        region_error: Option<String>,
+        temperature: Option<f32>,
+    },
+    Completed {
+        error: Option<String>,
+        smart_changes: Option<Vec<SmartChange>>,
+    },
+}
+
+pub struct JsonFrontend {}
+
+impl JsonFrontend {
+    /// Settings shown by `config`, with the built-in defaults filled in.
+    pub fn print_config(path: Option<&std::path::Path>, config: &Config) {
+        #[derive(Serialize)]
+        struct ConfigEntry<'a> {
+            path: Option<String>,
+            found: bool,
+            settings: &'a Config,
+        }
+
+        let settings = config.effective();
+        let entry = ConfigEntry {
+            path: path.map(|p| p.display().to_string()),
+            found: path.map(|p| p.exists()).unwrap_or(false),
+            settings: &settings,
+        };
+        println!("{}", serde_json::to_string(&entry).unwrap());
+    }
+
+    /// Jobs as printed by `jobs`, a single JSON array.
+    pub fn print_jobs(jobs: &[Job], resumable: &[bool]) {
+        #[derive(Serialize)]
+        struct JobEntry {
+            #[serde(flatten)]
+            job: Job,
+            resumable: bool,
+        }
+
+        let entries: Vec<_> = jobs
+            .iter()
+            .zip(resumable)
+            .map(|(job, resumable)| JobEntry {
+                job: Job {
+                    status: job.effective_status(),
+                    ..job.clone()
+                },
+                resumable: *resumable,
+            })
+            .collect();
+        println!("{}", serde_json::to_string(&entries).unwrap());
+    }
+
+    pub fn print_devices<R: StorageRef>(devices: &[R], ids: &IdShortcuts) {
+        println!("{}", JsonFrontend::devices(devices, ids));
+    }
+
+    /// Devices as printed by `list`, a single JSON array.
+    pub fn devices<R: StorageRef>(devices: &[R], ids: &IdShortcuts) -> String {
+        let entries: Vec<_> = devices
+            .iter()
+            .map(|d| DeviceEntry {
+                id: d.id(),
+                short_id: ids.get_short(d.id()).map(|s| s.as_str()),
+                size: d.details().size,
+                block_size: d.details().block_size,
+                logical_sector_size: d.details().logical_sector_size,
+                physical_sector_size: d.details().physical_sector_size,
+                storage_type: d.details().storage_type.to_string(),
+                media_type: d.details().media_type.to_string(),
+                bus_type: d.details().bus_type.to_string(),
+                serial: d.details().serial.as_deref(),
+                model: d.details().model.as_deref(),
+                firmware: d.details().firmware.as_deref(),
+                aliases: &d.details().aliases,
+                parent: d.details().parent.as_deref(),
+                mount_point: d.details().mount_point.as_deref(),
+                label: d.details().label.as_deref(),
+                filesystem: d.details().filesystem.as_deref(),
+                trim_supported: d.details().trim_supported,
+                boot: d.details().is_boot,
+            })
+            .collect();
+
+        serde_json::to_string(&entries).unwrap()
+    }
+
+    pub fn print_post_wipe(action: &str) {
+        #[derive(Serialize)]
+        struct PostWipe<'a> {
+            event: &'static str,
+            action: &'a str,
+        }
+
+        emit(&PostWipe {
+            event: "post_wipe",
+            action,
+        });
+    }
+
+    pub fn print_batch_results(results: &[BatchResult]) {
+        #[derive(Serialize)]
+        struct DeviceResult<'a> {
+            device: &'a str,
+            outcome: String,
+            success: bool,
+            error: Option<&'a str>,
+            elapsed_ms: u64,
+        }
+
+        #[derive(Serialize)]
+        struct BatchCompleted<'a> {
+            event: &'static str,
+            results: Vec<DeviceResult<'a>>,
+        }
+
+        emit(&BatchCompleted {
+            event: "batch_completed",
+            results: results
+                .iter()
+                .map(|r| DeviceResult {
+                    device: &r.device_id,
+                    outcome: format!("{:?}", r.outcome),
+                    success: r.outcome.is_success() && r.error.is_none(),
+                    error: r.error.as_deref(),
+                    elapsed_ms: r.elapsed.as_millis() as u64,
+                })
+                .collect(),
+        });
+    }
+
+    pub fn print_device_attached(device_id: &str, wiping: bool) {
+        #[derive(Serialize)]
+        struct DeviceAttached<'a> {
+            event: &'static str,
+            device: &'a str,
+            wiping: bool,
+        }
+
+        emit(&DeviceAttached {
+            event: "device_attached",
+            device: device_id,
+            wiping,
+        });
+    }
+
+    pub fn print_label_written(offset: u64) {
+        #[derive(Serialize)]
+        struct LabelWritten {
+            event: &'static str,
+            offset: u64,
+        }
+
+        emit(&LabelWritten {
+            event: "label_written",
+            offset,
+        });
+    }
+
+    pub fn print_label(device_id: &str, label: Option<&(WipeLabel, LabelSignature)>) {
+        #[derive(Serialize)]
+        struct Label<'a> {
+            event: &'static str,
+            device: &'a str,
+            label: Option<&'a WipeLabel>,
+            signature: Option<&'static str>,
+        }
+
+        emit(&Label {
+            event: "label",
+            device: device_id,
+            label: label.map(|(l, _)| l),
+            signature: label.map(|(_, s)| match s {
+                LabelSignature::Valid => "valid",
+                LabelSignature::Invalid => "invalid",
+                LabelSignature::Unchecked => "unchecked",
+            }),
+        });
+    }
+
+    pub fn print_sanitize_support(
+        device_id: &str,
+        support: Option<&SanitizeSupport>,
+        status: Option<SanitizeStatus>,
+    ) {
+        #[derive(Serialize)]
+        struct Support<'a> {
+            event: &'static str,
+            device: &'a str,
+            supported: Option<bool>,
+            modes: Vec<String>,
+            status: Option<&'static str>,
+            progress: Option<f32>,
+        }
+
+        emit(&Support {
+            event: "sanitize_support",
+            device: device_id,
+            supported: support.map(|s| !s.modes.is_empty()),
+            modes: support
+                .map(|s| s.modes.iter().map(|m| m.to_string()).collect())
+                .unwrap_or_default(),
+            status: status.map(|s| match s {
+                SanitizeStatus::Idle => "idle",
+                SanitizeStatus::InProgress(_) => "in_progress",
+                SanitizeStatus::Completed => "completed",
+                SanitizeStatus::Failed => "failed",
+            }),
+            progress: match status {
+                Some(SanitizeStatus::InProgress(done)) => Some(done),
+                _ => None,
+            },
+        });
+    }
+
+    pub fn print_inspection(device_id: &str, inspection: &Inspection) {
+        #[derive(Serialize)]
+        struct InspectionEvent<'a> {
+            event: &'static str,
+            device: &'a str,
+            head: String,
+            tail_offset: u64,
+            tail: String,
+            structures: Vec<Structure>,
+            content: String,
+            samples: usize,
+            looks_blank: bool,
+        }
+
+        emit(&InspectionEvent {
+            event: "inspection",
+            device: device_id,
+            head: hex(&inspection.head),
+            tail_offset: inspection.tail_offset,
+            tail: hex(&inspection.tail),
+            structures: Structure::all(&inspection.signatures),
+            content: inspection.content.to_string(),
+            samples: inspection.samples,
+            looks_blank: inspection.looks_blank(),
+        });
+    }
+
+    /// The structures and ranges a targeted scheme is about to wipe.
+    pub fn print_targeted_wipe(signatures: &[Signature], ranges: &[WipeRange]) {
+        #[derive(Serialize)]
+        struct TargetedWipe<'a> {
+            event: &'static str,
+            structures: Vec<Structure>,
+            ranges: &'a [WipeRange],
+            size: u64,
+        }
+
+        emit(&TargetedWipe {
+            event: "targeted_wipe",
+            structures: Structure::all(signatures),
+            ranges,
+            size: ranges.iter().map(|r| r.len()).sum(),
+        });
+    }
+
+    pub fn print_warnings(warnings: &[Warning]) {
+        #[derive(Serialize)]
+        struct Warnings<'a> {
+            event: &'static str,
+            warnings: &'a [Warning],
+        }
+
+        emit(&Warnings {
+            event: "warnings",
+            warnings,
+        });
+    }
+
+    pub fn print_self_audit(audit: &SelfAudit) {
+        #[derive(Serialize)]
+        struct Trace<'a> {
+            kind: &'a str,
+            path: String,
+            exists: bool,
+        }
+
+        #[derive(Serialize)]
+        struct Audit<'a> {
+            event: &'static str,
+            touched: &'a [String],
+            traces: Vec<Trace<'a>>,
+        }
+
+        emit(&Audit {
+            event: "self_audit",
+            touched: &audit.touched,
+            traces: audit
+                .traces
+                .iter()
+                .map(|t| Trace {
+                    kind: t.kind,
+                    path: t.path.to_string_lossy().into_owned(),
+                    exists: t.exists,
+                })
+                .collect(),
+        });
+    }
+
+    pub fn print_reverification(delay: Duration, percent: u8) {
+        #[derive(Serialize)]
+        struct Reverification {
+            event: &'static str,
+            delay_secs: u64,
+            sample_percent: u8,
+        }
+
+        emit(&Reverification {
+            event: "reverification_scheduled",
+            delay_secs: delay.as_secs(),
+            sample_percent: percent,
+        });
+    }
+
+    pub fn print_pool_stats(stats: &PoolStats) {
+        #[derive(Serialize)]
+        struct BufferPool<'a> {
+            event: &'static str,
+            #[serde(flatten)]
+            stats: &'a PoolStats,
+        }
+
+        emit(&BufferPool {
+            event: "buffer_pool",
+            stats,
+        });
+    }
+
+    pub fn print_hidden_areas_unlocked(hidden: &HiddenAreas, permanent: bool, size: u64) {
+        #[derive(Serialize)]
+        struct HiddenAreasUnlocked {
+            event: &'static str,
+            hpa_sectors: u64,
+            dco_sectors: u64,
+            permanent: bool,
+            size: u64,
+        }
+
+        emit(&HiddenAreasUnlocked {
+            event: "hidden_areas_unlocked",
+            hpa_sectors: hidden.hpa_sectors(),
+            dco_sectors: if permanent { hidden.dco_sectors() } else { 0 },
+            permanent,
+            size,
+        });
+    }
+
+    pub fn print_hidden_areas_restored(hidden: &HiddenAreas) {
+        #[derive(Serialize)]
+        struct HiddenAreasRestored {
+            event: &'static str,
+            hpa_sectors: u64,
+        }
+
+        emit(&HiddenAreasRestored {
+            event: "hidden_areas_restored",
+            hpa_sectors: hidden.hpa_sectors(),
+        });
+    }
+
+    pub fn print_unmounted(mount_points: &[String]) {
+        #[derive(Serialize)]
+        struct Unmounted<'a> {
+            event: &'static str,
+            mount_points: &'a [String],
+        }
+
+        emit(&Unmounted {
+            event: "unmounted",
+            mount_points,
+        });
+    }
+
+    pub fn print_detected_progress(state: &WipeState) {
+        #[derive(Serialize)]
+        struct DetectedProgress {
+            event: &'static str,
+            stage: usize,
+            position: u64,
+        }
+
+        emit(&DetectedProgress {
+            event: "detected_progress",
+            stage: state.stage,
+            position: state.position,
+        });
+    }
+
+    pub fn print_luks_erasure(erasure: &LuksErasure) {
+        #[derive(Serialize)]
+        struct LuksErased<'a> {
+            event: &'static str,
+            #[serde(flatten)]
+            erasure: &'a LuksErasure,
+            unrecoverable: bool,
+        }
+
+        emit(&LuksErased {
+            event: "luks_erased",
+            erasure,
+            unrecoverable: erasure.is_unrecoverable(),
+        });
+    }
+
+    pub fn soak_session(
+        device_id: &str,
+        temperature: Box<dyn Fn() -> Option<f32>>,
+    ) -> JsonSoakSession {
+        JsonSoakSession {
+            device_id: device_id.to_string(),
+            temperature,
+            smart: None,
+        }
+    }
+
+    pub fn wipe_session(device_id: &str) -> JsonWipeSession {
+        JsonWipeSession::new(device_id, Box::new(std::io::stdout()))
+    }
+
+    /// Same events as `wipe_session` but written to the given output.
+    pub fn wipe_stream(device_id: &str, out: Box<dyn Write>) -> JsonWipeSession {
+        JsonWipeSession::new(device_id, out)
+    }
+
+    /// Same events as `wipe_session` but appended to the file.
+    pub fn wipe_log(device_id: &str, path: &str) -> Result<JsonWipeSession> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!("Unable to open log file {}", path))?;
+        Ok(JsonWipeSession::new(device_id, Box::new(file)))
+    }
+}
+
+/// Emits newline-delimited JSON events to stdout (or a log file), one per line.
+pub struct JsonWipeSession {
+    device_id: String,
+    last_progress: Option<Instant>,
+    meter: ThroughputMeter,
+    stamp: EventStamp,
+    out: RefCell<Box<dyn Write>>,
+}
+
+/// Wipe event with the order and the time it was published at.
+#[derive(Serialize)]
+struct StampedEvent<'a> {
+    #[serde(flatten)]
+    event: &'a JsonEvent<'a>,
+    seq: u64,
+    timestamp_ms: u64,
+    elapsed_ms: u64,
+}
+
+impl JsonWipeSession {
+    fn new(device_id: &str, out: Box<dyn Write>) -> Self {
+        JsonWipeSession {
+            device_id: device_id.to_string(),
+            last_progress: None,
+            meter: ThroughputMeter::new(0),
+            stamp: EventStamp::default(),
+            out: RefCell::new(out),
+        }
+    }
+
+    fn emit(&self, event: &JsonEvent) {
+        let stamped = StampedEvent {
+            event,
+            seq: self.stamp.sequence,
+            timestamp_ms: self.stamp.timestamp_millis(),
+            elapsed_ms: self.stamp.elapsed.as_millis() as u64,
+        };
+        let mut out = self.out.borrow_mut();
+        let _ = writeln!(out, "{}", serde_json::to_string(&stamped).unwrap());
+        let _ = out.flush();
+    }
+}
+
+/// Partition table or header found by the signature prober.
+#[derive(Serialize)]
+struct Structure {
+    kind: String,
+    offset: u64,
+}
+
+impl Structure {
+    fn all(signatures: &[Signature]) -> Vec<Structure> {
+        signatures
+            .iter()
+            .map(|s| Structure {
+                kind: s.kind.to_string(),
+                offset: s.offset,
+            })
+            .collect()
+    }
+}
+
+fn emit<T: Serialize>(event: &T) {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let _ = writeln!(out, "{}", serde_json::to_string(event).unwrap());
+    let _ = out.flush();
+}
+
+impl WipeEventReceiver for JsonWipeSession {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        self.stamp = state.stamp;
+        match event {
+            WipeEvent::Started => self.emit(&JsonEvent::Started {
+                device: &self.device_id,
+                size: task.total_size,
+                block_size: task.block_size,
+                scheme: &task.scheme.description,
+                stages: task.scheme.stages.len(),
+                verify: task.verify.to_string(),
+                operator: task.operator.as_ref().map(|o| o.to_string()),
+                ranges: &task.ranges,
+                wiped_size: task.wiped_size(),
+                duty_cycle: task.duty_cycle.map(|d| d.percent()),
+                rate_limit: task.rate_limit,
+                dry_run: task.dry_run,
+                estimated_duration_secs: task.estimated_duration.map(|d| d.as_secs()),
+                estimated_stage_durations_secs: task
+                    .estimated_stage_durations
+                    .iter()
+                    .map(|d| d.as_secs())
+                    .collect(),
+                digest_verification: task.digest_verification,
+                verify_only: task.verify_only,
+            }),
+            WipeEvent::StageStarted => {
+                self.last_progress = None;
+                self.meter = ThroughputMeter::new(task.progress_at(state.position));
+                self.emit(&JsonEvent::StageStarted {
+                    stage: state.stage + 1,
+                    stages: task.scheme.stages.len(),
+                    verifying: state.at_verification,
+                    description: task.scheme.stages[state.stage].to_string(),
+                })
+            }
+            WipeEvent::StageSkipped => self.emit(&JsonEvent::StageSkipped {
+                stage: state.stage + 1,
+                stages: task.scheme.stages.len(),
+                description: task.scheme.stages[state.stage].to_string(),
+            }),
+            WipeEvent::Progress(position) => {
+                let due = match self.last_progress {
+                    Some(t) => t.elapsed() >= PROGRESS_INTERVAL,
+                    None => true,
+                };
+                let done = task.progress_at(position);
+                self.meter.update(done);
+                if due || done >= task.wiped_size() {
+                    self.last_progress = Some(Instant::now());
+                    self.emit(&JsonEvent::Progress {
+                        stage: state.stage + 1,
+                        verifying: state.at_verification,
+                        position,
+                        done,
+                        total: task.wiped_size(),
+                        throughput: self.meter.current(),
+                        average_throughput: self.meter.average(done),
+                        eta_secs: self
+                            .meter
+                            .eta(task.wiped_size().saturating_sub(done))
+                            .map(|d| d.as_secs()),
+                    });
+                }
+            }
+            WipeEvent::MarkBlockAsBad(position) => self.emit(&JsonEvent::BadBlock { position }),
+            WipeEvent::MarkBlockAsMismatched(position) => {
+                self.emit(&JsonEvent::Mismatch { position })
+            }
+            WipeEvent::Repairing(region) => self.emit(&JsonEvent::Repairing {
+                position: state.position,
+                region,
+            }),
+            WipeEvent::BlockSizeReduced(write_size) => self.emit(&JsonEvent::BlockSizeReduced {
+                position: state.position,
+                write_size,
+            }),
+            WipeEvent::StageCompleted(result) => self.emit(&JsonEvent::StageCompleted {
+                stage: state.stage + 1,
+                verifying: state.at_verification,
+                os_error: result.as_ref().and_then(|e| os_error_code(e)),
+                error: result.map(|e| format!("{:#}", e)),
+                throughput: self.meter.average(task.progress_at(state.position)),
+            }),
+            WipeEvent::Retrying(delay) => self.emit(&JsonEvent::Retrying {
+                position: state.position,
+                retries_left: state.retries_left,
+                verify_retries_left: state.verify_retries_left,
+                delay_secs: delay.as_secs(),
+            }),
+            WipeEvent::Completed(result) => self.emit(&JsonEvent::Completed {
+                os_error: result.as_ref().and_then(|e| os_error_code(e)),
+                error: result.map(|e| format!("{:#}", e)),
+                bad_blocks: state.bad_blocks.borrow().total_marked(),
+                verified_blocks: state.verified_blocks,
+                mismatched_ranges: task
+                    .block_ranges(&state.mismatched_blocks.borrow().all_marked()),
+            }),
+            WipeEvent::Paused => self.emit(&JsonEvent::Paused {
+                stage: state.stage + 1,
+                verifying: state.at_verification,
+                position: state.position,
+            }),
+            WipeEvent::PausedUntil(until) => self.emit(&JsonEvent::PausedUntil {
+                stage: state.stage + 1,
+                verifying: state.at_verification,
+                position: state.position,
+                until: crate::actions::report::format_timestamp(until),
+            }),
+            WipeEvent::Resumed => self.emit(&JsonEvent::Resumed),
+            WipeEvent::Aborted => self.emit(&JsonEvent::Aborted {
+                stage: state.stage + 1,
+                verifying: state.at_verification,
+                position: state.position,
+            }),
+            WipeEvent::Fatal(err) => self.emit(&JsonEvent::Fatal {
+                error: format!("{:#}", err),
+                os_error: os_error_code(&err),
+            }),
+        }
+    }
+}
+
+/// Emits soak test results as newline-delimited JSON events.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum JsonFreeSpaceEvent<'a> {
+    Started {
+        directory: String,
+        free_space: Option<u64>,
+        scheme: &'a str,
+        block_size: usize,
+    },
+    StageStarted {
+        stage: usize,
+    },
+    StageSkipped {
+        stage: usize,
+    },
+    StageCompleted {
+        written: u64,
+    },
+    Completed {
+        error: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum JsonSanitizeEvent<'a> {
+    Started {
+        device: &'a str,
+        mode: String,
+    },
+    Progress {
+        device: &'a str,
+        progress: f32,
+    },
+    Completed {
+        device: &'a str,
+        error: Option<String>,
+    },
+}
+
+pub struct JsonSanitizeSession;
+
+impl SanitizeEventReceiver for JsonSanitizeSession {
+    fn handle(&mut self, task: &SanitizeTask, event: SanitizeEvent) {
+        let device = task.device_id.as_str();
+        match event {
+            SanitizeEvent::Started => emit(&JsonSanitizeEvent::Started {
+                device,
+                mode: task.mode.to_string(),
+            }),
+            SanitizeEvent::Progress(progress) => {
+                emit(&JsonSanitizeEvent::Progress { device, progress })
+            }
+            SanitizeEvent::Completed(result) => emit(&JsonSanitizeEvent::Completed {
+                device,
+                error: result.map(|e| format!("{:#}", e)),
+            }),
+        }
+    }
+}
+
+pub struct JsonFreeSpaceSession;
+
+impl FreeSpaceEventReceiver for JsonFreeSpaceSession {
+    fn handle(&mut self, task: &FreeSpaceTask, event: FreeSpaceEvent) {
+        match event {
+            FreeSpaceEvent::Started => emit(&JsonFreeSpaceEvent::Started {
+                directory: task.directory.to_string_lossy().into_owned(),
+                free_space: task.free_space,
+                scheme: &task.scheme.description,
+                block_size: task.block_size,
+            }),
+            FreeSpaceEvent::StageStarted(i) => {
+                emit(&JsonFreeSpaceEvent::StageStarted { stage: i + 1 })
+            }
+            FreeSpaceEvent::StageSkipped(i) => {
+                emit(&JsonFreeSpaceEvent::StageSkipped { stage: i + 1 })
+            }
+            FreeSpaceEvent::StageCompleted(written) => {
+                emit(&JsonFreeSpaceEvent::StageCompleted { written })
+            }
+            FreeSpaceEvent::Completed(result) => emit(&JsonFreeSpaceEvent::Completed {
+                error: result.map(|e| format!("{:#}", e)),
+            }),
+            FreeSpaceEvent::Progress(_) => {}
+        }
+    }
+}
+
+pub struct JsonSoakSession {
+    device_id: String,
+    temperature: Box<dyn Fn() -> Option<f32>>,
+    smart: Option<SmartAttributes>,
+}
+
+impl SoakEventReceiver for JsonSoakSession {
+    fn handle(&mut self, task: &SoakTask, event: SoakEvent) {
+        match event {
+            SoakEvent::Started => {
+                self.smart = SmartAttributes::read(&self.device_id);
+                emit(&JsonSoakEvent::Started {
+                    device: &self.device_id,
+                    size: task.total_size,
+                    duration_secs: task.duration.as_secs(),
+                    block_size: task.block_size,
+                    write_region: task.write_region,
+                })
+            }
+            SoakEvent::ReadError(position) => emit(&JsonSoakEvent::ReadError { position }),
+            SoakEvent::PassCompleted(pass) => emit(&JsonSoakEvent::PassCompleted {
+                pass: pass.pass,
+                bytes_read: pass.bytes_read,
+                seconds: pass.elapsed.as_secs_f64(),
+                throughput: pass.throughput(),
+                read_errors: pass.read_errors,
+                complete: pass.complete,
+                region_error: pass.region_error,
+                temperature: (self.temperature)(),
+            }),
+            SoakEvent::Completed(result) => {
+                let smart_changes = match (&self.smart, SmartAttributes::read(&self.device_id)) {
+                    (Some(before), Some(after)) => Some(
+                        after
+                            .changes_since(before)
+                            .into_iter()
+                            .map(|(name, before, after)| SmartChange {
+                                name,
+                                before,
+                                after,
+                            })
+                            .collect(),
+                    ),
+                    _ => None,
+                };
+                emit(&JsonSoakEvent::Completed {
+                    error: result.map(|e| format!("{:#}", e)),
+                    smart_changes,
+                })
+            }
+            SoakEvent::PassStarted(_) | SoakEvent::Progress(_) => {}
+        }
+    }
+}
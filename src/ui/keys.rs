@@ -0,0 +1,152 @@
+//! Keyboard control of a running wipe: `p` pauses, `r` resumes and `q` aborts it.
+
+use crate::actions::{CancellationToken, PauseToken};
+
+/// What a key press does to the wipe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyCommand {
+    Pause,
+    Resume,
+    Abort,
+}
+
+impl KeyCommand {
+    pub fn from_key(key: u8) -> Option<Self> {
+        match key.to_ascii_lowercase() {
+            b'p' => Some(KeyCommand::Pause),
+            b'r' => Some(KeyCommand::Resume),
+            b'q' => Some(KeyCommand::Abort),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, cancellation: &CancellationToken, pause: &PauseToken) {
+        match self {
+            KeyCommand::Pause => pause.pause(),
+            KeyCommand::Resume => pause.resume(),
+            KeyCommand::Abort => cancellation.cancel(),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use self::unix::KeyControl;
+
+#[cfg(unix)]
+mod unix {
+    use super::KeyCommand;
+    use crate::actions::{restore_terminal_on_interrupt, CancellationToken, PauseToken};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::JoinHandle;
+
+    /// Reads the keys on a background thread with the terminal switched to non-canonical
+    /// mode, so they take effect without Enter. The terminal is restored when dropped.
+    pub struct KeyControl {
+        stop: Arc<AtomicBool>,
+        reader: Option<JoinHandle<()>>,
+        saved: libc::termios,
+    }
+
+    impl KeyControl {
+        /// Returns `None` if the standard input is not a terminal.
+        pub fn start(cancellation: CancellationToken, pause: PauseToken) -> Option<Self> {
+            let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+            if unsafe { libc::isatty(libc::STDIN_FILENO) } != 1
+                || unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0
+            {
+                return None;
+            }
+
+            let mut raw = saved;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+            // reads return after a tenth of a second even without input, see `reader`
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 1;
+            if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+                return None;
+            }
+            restore_terminal_on_interrupt(Some(saved));
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let reader = {
+                let stop = Arc::clone(&stop);
+                std::thread::spawn(move || {
+                    let mut key = 0u8;
+                    while !stop.load(Ordering::SeqCst) {
+                        let read = unsafe {
+                            libc::read(
+                                libc::STDIN_FILENO,
+                                &mut key as *mut u8 as *mut libc::c_void,
+                                1,
+                            )
+                        };
+                        match read {
+                            1 => {
+                                if let Some(c) = KeyCommand::from_key(key) {
+                                    c.apply(&cancellation, &pause);
+                                }
+                            }
+                            0 => {}
+                            // the terminal is gone (e.g. after a hangup)
+                            _ => break,
+                        }
+                    }
+                })
+            };
+
+            Some(KeyControl {
+                stop,
+                reader: Some(reader),
+                saved,
+            })
+        }
+    }
+
+    impl Drop for KeyControl {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::SeqCst);
+            if let Some(r) = self.reader.take() {
+                let _ = r.join();
+            }
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved) };
+            restore_terminal_on_interrupt(None);
+        }
+    }
+}
+
+/// Keyboard control is not supported on Windows yet.
+#[cfg(windows)]
+pub struct KeyControl;
+
+#[cfg(windows)]
+impl KeyControl {
+    pub fn start(_cancellation: CancellationToken, _pause: PauseToken) -> Option<Self> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_key_commands() {
+        let cancellation = CancellationToken::new();
+        let pause = PauseToken::new();
+
+        KeyCommand::from_key(b'p')
+            .unwrap()
+            .apply(&cancellation, &pause);
+        assert!(pause.is_paused());
+        KeyCommand::from_key(b'R')
+            .unwrap()
+            .apply(&cancellation, &pause);
+        assert!(!pause.is_paused());
+        assert_eq!(KeyCommand::from_key(b'x'), None);
+        KeyCommand::from_key(b'q')
+            .unwrap()
+            .apply(&cancellation, &pause);
+        assert!(cancellation.is_cancelled());
+    }
+}
@@ -1,3 +1,11 @@
 pub mod args;
 pub mod cli;
+pub mod config;
+pub mod examples;
+pub mod exitcode;
 pub mod idshortcuts;
+pub mod json;
+pub mod keys;
+pub mod progress;
+pub mod server;
+pub mod tui;
@@ -0,0 +1,153 @@
+use crate::actions::eventlog;
+use crate::actions::{WipeEvent, WipeEventReceiver, WipeState, WipeTask};
+use anyhow::{Context, Result};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Write;
+
+/// Writes the progress of a wipe to a file descriptor inherited from a GUI wrapper, in the
+/// line format of `zenity --progress` and similar tools: a number sets the overall percent,
+/// a line starting with `#` sets the status text.
+pub struct ProgressFdReceiver {
+    out: Box<dyn Write>,
+    last_percent: Option<u8>,
+}
+
+impl ProgressFdReceiver {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        ProgressFdReceiver {
+            out,
+            last_percent: None,
+        }
+    }
+
+    /// Takes over the descriptor (a handle on Windows), it's closed when the wipe is over.
+    pub fn open(fd: i64) -> Result<Self> {
+        Ok(ProgressFdReceiver::new(Box::new(file_from_fd(fd)?)))
+    }
+
+    fn write_line(&mut self, line: &str) {
+        // the wrapper may be gone already, that's no reason to stop the wipe
+        let _ = writeln!(self.out, "{}", line).and_then(|_| self.out.flush());
+    }
+}
+
+#[cfg(unix)]
+fn file_from_fd(fd: i64) -> Result<File> {
+    use std::os::unix::io::FromRawFd;
+    let fd = i32::try_from(fd).context("Invalid file descriptor")?;
+    if fd <= 2 || unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+        return Err(anyhow!("File descriptor {} is not open", fd));
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(windows)]
+fn file_from_fd(handle: i64) -> Result<File> {
+    use std::os::windows::io::{FromRawHandle, RawHandle};
+    if handle <= 0 {
+        return Err(anyhow!("Invalid handle {}", handle));
+    }
+    Ok(unsafe { File::from_raw_handle(handle as isize as RawHandle) })
+}
+
+/// Overall progress of the wipe, each stage weighted by the amount of data it writes and
+/// verifies.
+fn overall_percent(task: &WipeTask, state: &WipeState, position: u64) -> u8 {
+    let weights: Vec<f64> = task
+        .estimate_stages(1, 1)
+        .iter()
+        .map(|d| d.as_secs_f64())
+        .collect();
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return 0;
+    }
+
+    let size = task.wiped_size() as f64;
+    let write = if task.verify_only { 0.0 } else { size };
+    let fraction = task.progress_at(position) as f64 / size.max(1.0);
+    let within = if state.at_verification {
+        write + (weights[state.stage] - write) * fraction
+    } else {
+        write * fraction
+    };
+    let done: f64 = weights[..state.stage].iter().sum::<f64>() + within;
+    (done * 100.0 / total).clamp(0.0, 100.0) as u8
+}
+
+impl WipeEventReceiver for ProgressFdReceiver {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        let percent = match event {
+            WipeEvent::Progress(position) => Some(overall_percent(task, state, position)),
+            WipeEvent::Completed(None) => Some(100),
+            _ => None,
+        };
+        if let Some(percent) = percent {
+            if self.last_percent != Some(percent) {
+                self.last_percent = Some(percent);
+                self.write_line(&percent.to_string());
+            }
+        }
+
+        if let WipeEvent::Progress(_) = event {
+            return;
+        }
+        if let Some(line) = eventlog::describe(task, state, &event) {
+            self.write_line(&format!("# {}", line));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Verify;
+    use crate::sanitization::SchemeRepo;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_progress_lines_cover_all_stages() {
+        let scheme = SchemeRepo::default().find("random2x").unwrap().clone();
+        let task = WipeTask::new(scheme, Verify::Last, 1000, 100).unwrap();
+        let buffer = SharedBuffer::default();
+        let mut receiver = ProgressFdReceiver::new(Box::new(buffer.clone()));
+
+        let mut state = WipeState::default();
+        receiver.handle(&task, &state, WipeEvent::Started);
+        receiver.handle(&task, &state, WipeEvent::Progress(500));
+        receiver.handle(&task, &state, WipeEvent::Progress(501));
+        state.stage = 1;
+        receiver.handle(&task, &state, WipeEvent::Progress(1000));
+        state.at_verification = true;
+        receiver.handle(&task, &state, WipeEvent::Progress(500));
+        receiver.handle(&task, &state, WipeEvent::Completed(None));
+
+        let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            vec![
+                "# Wiping with Double random fill",
+                "16",
+                "66",
+                "83",
+                "100",
+                "# Wipe completed"
+            ]
+        );
+    }
+}
@@ -0,0 +1,808 @@
+//! Server mode for headless wipe stations: a small HTTP API to list the devices, start and
+//! cancel wipes, follow their progress and fetch their reports.
+//!
+//! | Request                     | Response                                             |
+//! |-----------------------------|------------------------------------------------------|
+//! | `GET /devices`              | devices, as `list` prints them with `--output=json`  |
+//! | `GET /wipes`                | all wipes started by the server                      |
+//! | `POST /wipes`               | starts a wipe, see [`WipeRequest`]                   |
+//! | `GET /wipes/{id}`           | status of the wipe                                   |
+//! | `GET /wipes/{id}/events`    | server-sent events, one per `--output=json` line     |
+//! | `POST /wipes/{id}/cancel`   | stops the wipe                                       |
+//! | `GET /wipes/{id}/report`    | erasure report once the wipe is over                 |
+//!
+//! Every request has to carry the token as `Authorization: Bearer <token>`. Each wipe runs
+//! on its own worker thread. Image files on the server are only wiped when they are on the
+//! `allow` list of the config.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::actions::report::ReportRecorder;
+use crate::actions::{
+    CancellationToken, Operator, Verify, WipeEvent, WipeEventReceiver, WipeState, WipeTask,
+};
+use crate::sanitization::{is_targeted, Scheme, SchemeRepo};
+use crate::storage::{ImageFile, ImageFiles, StorageRef, StorageRegistry, IMAGE_FILE_SCHEME};
+use crate::ui::config::Config;
+use crate::ui::idshortcuts::IdShortcuts;
+use crate::ui::json::{JsonFrontend, JsonWipeSession};
+
+pub const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8484";
+
+const MAX_HEADERS: usize = 100;
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// A comment is sent to idle event streams this often, so closed connections are noticed.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const DEFAULT_RETRIES: u32 = 8;
+
+/// Body of `POST /wipes`, the fields have the same meaning and defaults as the options
+/// of the wipe command.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WipeRequest {
+    /// Device ID, or a `file://` URI of a regular file.
+    pub device: String,
+    #[serde(default = "default_scheme")]
+    pub scheme: String,
+    #[serde(default = "default_verify")]
+    pub verify: String,
+    /// The preferred block size of the device when not given.
+    pub blocksize: Option<String>,
+    pub operator: Option<String>,
+}
+
+fn default_scheme() -> String {
+    "random2x".to_string()
+}
+
+fn default_verify() -> String {
+    "last".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+struct Job {
+    id: u64,
+    device: String,
+    scheme: String,
+    status: JobStatus,
+    error: Option<String>,
+    /// JSON events of the wipe in the order they were published.
+    events: Vec<String>,
+    report: Option<String>,
+    cancellation: CancellationToken,
+}
+
+#[derive(Serialize)]
+struct JobSummary<'a> {
+    id: u64,
+    device: &'a str,
+    scheme: &'a str,
+    status: JobStatus,
+    error: Option<&'a str>,
+    events: usize,
+}
+
+impl Job {
+    fn summary(&self) -> JobSummary<'_> {
+        JobSummary {
+            id: self.id,
+            device: &self.device,
+            scheme: &self.scheme,
+            status: self.status,
+            error: self.error.as_deref(),
+            events: self.events.len(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Jobs {
+    last_id: u64,
+    jobs: Vec<Job>,
+}
+
+impl Jobs {
+    fn get(&mut self, id: u64) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|j| j.id == id)
+    }
+}
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercase.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Response {
+    Json(u16, String),
+    /// The connection is kept open for the events of the wipe.
+    Events(u64),
+}
+
+fn error(status: u16, message: &str) -> Response {
+    #[derive(Serialize)]
+    struct Error<'a> {
+        error: &'a str,
+    }
+
+    Response::Json(
+        status,
+        serde_json::to_string(&Error { error: message }).unwrap(),
+    )
+}
+
+/// State shared by the connections and the wipe workers.
+pub struct Server {
+    token: String,
+    schemes: SchemeRepo,
+    /// Only the excluded and allowed devices are taken from the config, the requests give all
+    /// options.
+    config: Config,
+    jobs: Mutex<Jobs>,
+    /// Notified whenever a wipe publishes an event or ends.
+    changed: Condvar,
+}
+
+impl Server {
+    pub fn new(token: &str, config: Config) -> Arc<Server> {
+        Arc::new(Server {
+            token: token.to_string(),
+            schemes: SchemeRepo::default(),
+            config,
+            jobs: Mutex::new(Jobs::default()),
+            changed: Condvar::new(),
+        })
+    }
+
+    /// Accepts the connections until the process is stopped.
+    pub fn serve(self: &Arc<Self>, address: &str) -> Result<()> {
+        let listener =
+            TcpListener::bind(address).context(format!("Unable to listen on {}", address))?;
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(err) => {
+                    eprintln!("Unable to accept a connection. {}", err);
+                    continue;
+                }
+            };
+            let server = Arc::clone(self);
+            std::thread::spawn(move || {
+                if let Err(err) = server.handle_connection(stream) {
+                    eprintln!("Connection failed. {:#}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, Jobs> {
+        // a worker panicking with the lock doesn't leave the jobs inconsistent
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn handle_connection(self: &Arc<Self>, mut stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let request = match read_request(&mut BufReader::new(&stream)) {
+            Ok(r) => r,
+            Err(err) => {
+                return write_response(&mut stream, &error(400, &format!("{:#}", err)))
+                    .map_err(anyhow::Error::from)
+            }
+        };
+        match self.route(&request) {
+            Response::Events(id) => self.stream_events(&mut stream, id)?,
+            response => write_response(&mut stream, &response)?,
+        }
+        Ok(())
+    }
+
+    pub fn route(self: &Arc<Self>, request: &Request) -> Response {
+        let authorized = request
+            .headers
+            .get("authorization")
+            .and_then(|a| a.trim().strip_prefix("Bearer "))
+            .map(|t| constant_time_eq(t.trim().as_bytes(), self.token.as_bytes()))
+            .unwrap_or(false);
+        if !authorized {
+            return error(401, "Missing or invalid token");
+        }
+
+        let segments: Vec<_> = request
+            .path
+            .split('?')
+            .next()
+            .unwrap_or("")
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect();
+        let job_id = |s: &str| s.parse::<u64>().ok();
+
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["devices"]) => list_devices(&self.config)
+                .map(|d| Response::Json(200, d))
+                .unwrap_or_else(|err| error(500, &format!("{:#}", err))),
+            ("GET", ["wipes"]) => {
+                let jobs = self.jobs();
+                let summaries: Vec<_> = jobs.jobs.iter().map(|j| j.summary()).collect();
+                Response::Json(200, serde_json::to_string(&summaries).unwrap())
+            }
+            ("POST", ["wipes"]) => match serde_json::from_slice(&request.body) {
+                Ok(wipe) => self.start(wipe),
+                Err(err) => error(400, &format!("Invalid wipe request: {}", err)),
+            },
+            ("GET", ["wipes", id]) => match job_id(id).and_then(|id| {
+                self.jobs()
+                    .get(id)
+                    .map(|j| serde_json::to_string(&j.summary()).unwrap())
+            }) {
+                Some(summary) => Response::Json(200, summary),
+                None => error(404, "No such wipe"),
+            },
+            ("GET", ["wipes", id, "events"]) => match job_id(id) {
+                Some(id) if self.jobs().get(id).is_some() => Response::Events(id),
+                _ => error(404, "No such wipe"),
+            },
+            ("POST", ["wipes", id, "cancel"]) => {
+                let mut jobs = self.jobs();
+                match job_id(id).and_then(|id| jobs.get(id)) {
+                    Some(job) if job.status == JobStatus::Running => {
+                        job.cancellation.cancel();
+                        Response::Json(202, "{}".to_string())
+                    }
+                    Some(_) => error(409, "The wipe is already over"),
+                    None => error(404, "No such wipe"),
+                }
+            }
+            ("GET", ["wipes", id, "report"]) => {
+                match job_id(id).and_then(|id| self.jobs().get(id).map(|j| j.report.clone())) {
+                    Some(Some(report)) => Response::Json(200, report),
+                    Some(None) => error(404, "The wipe has no report yet"),
+                    None => error(404, "No such wipe"),
+                }
+            }
+            (_, ["devices"])
+            | (_, ["wipes"])
+            | (_, ["wipes", _])
+            | (_, ["wipes", _, "events"])
+            | (_, ["wipes", _, "cancel"])
+            | (_, ["wipes", _, "report"]) => error(405, "Method not allowed"),
+            _ => error(404, "Not found"),
+        }
+    }
+
+    /// Opens the image file given as a `file://` URI, if it's explicitly allowed. The files
+    /// of the server are off limits otherwise, including the ones of the system.
+    fn open_image(&self, device_id: &str) -> Result<Option<ImageFile>> {
+        let path = match device_id
+            .strip_prefix(IMAGE_FILE_SCHEME)
+            .and_then(|p| p.strip_prefix("://"))
+        {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let image = ImageFile::open(path)?;
+        if !self.config.device_filter()?.allows_explicitly(&image) {
+            return Err(ImageNotAllowed(image.id().to_string()).into());
+        }
+        Ok(Some(image))
+    }
+
+    fn start(self: &Arc<Self>, wipe: WipeRequest) -> Response {
+        if let Err(err) = self.open_image(&wipe.device) {
+            let status = if err.is::<ImageNotAllowed>() {
+                403
+            } else {
+                400
+            };
+            return error(status, &format!("{:#}", err));
+        }
+        let setup = (|| -> Result<(Scheme, Verify, Option<usize>, Option<Operator>)> {
+            if is_targeted(&wipe.scheme) {
+                return Err(anyhow!(
+                    "The {} scheme can't be used by the server",
+                    wipe.scheme
+                ));
+            }
+            let scheme = self
+                .schemes
+                .find(&wipe.scheme)
+                .ok_or_else(|| anyhow!("Unknown scheme {}", wipe.scheme))?
+                .clone();
+            let verify = crate::ui::args::parse_verify(&wipe.verify)
+                .context(format!("Invalid verify value: {}", wipe.verify))?;
+            let block_size = wipe
+                .blocksize
+                .as_deref()
+                .map(|b| {
+                    crate::ui::args::parse_block_size(b)
+                        .context(format!("Invalid blocksize value: {}", b))
+                })
+                .transpose()?;
+            let operator = wipe
+                .operator
+                .as_deref()
+                .map(|o| Operator::parse(o).context(format!("Invalid operator value: {}", o)))
+                .transpose()?;
+            Ok((scheme, verify, block_size, operator))
+        })();
+        let (scheme, verify, block_size, operator) = match setup {
+            Ok(s) => s,
+            Err(err) => return error(400, &format!("{:#}", err)),
+        };
+
+        let cancellation = CancellationToken::new();
+        let id = {
+            let mut jobs = self.jobs();
+            let busy = jobs
+                .jobs
+                .iter()
+                .any(|j| j.device == wipe.device && j.status == JobStatus::Running);
+            if busy {
+                return error(409, &format!("{} is already being wiped", wipe.device));
+            }
+            jobs.last_id += 1;
+            let id = jobs.last_id;
+            jobs.jobs.push(Job {
+                id,
+                device: wipe.device.clone(),
+                scheme: wipe.scheme.clone(),
+                status: JobStatus::Running,
+                error: None,
+                events: Vec::new(),
+                report: None,
+                cancellation: cancellation.clone(),
+            });
+            id
+        };
+
+        let server = Arc::clone(self);
+        std::thread::spawn(move || {
+            let result = server.run_wipe(
+                id,
+                &wipe.device,
+                |task| {
+                    task.scheme = scheme;
+                    task.verify = verify;
+                    task.operator = operator;
+                    task.cancellation = cancellation.clone();
+                },
+                block_size,
+            );
+
+            let mut jobs = server.jobs();
+            if let Some(job) = jobs.get(id) {
+                job.status = match &result {
+                    Ok(true) => JobStatus::Completed,
+                    _ if job.cancellation.is_cancelled() => JobStatus::Cancelled,
+                    _ => JobStatus::Failed,
+                };
+                job.error = result.err().map(|e| format!("{:#}", e));
+            }
+            server.changed.notify_all();
+        });
+
+        Response::Json(201, format!("{{\"id\":{}}}", id))
+    }
+
+    /// Runs the wipe on the current thread, returns whether it succeeded.
+    fn run_wipe(
+        self: &Arc<Self>,
+        id: u64,
+        device_id: &str,
+        configure: impl FnOnce(&mut WipeTask),
+        block_size: Option<usize>,
+    ) -> Result<bool> {
+        let mut registry = StorageRegistry::default();
+        registry.register(Box::new(ImageFiles))?;
+        registry.set_filter(self.config.device_filter()?);
+
+        let image = self.open_image(device_id)?;
+        let devices;
+        let device: &dyn StorageRef = match &image {
+            Some(image) => image,
+            None => {
+                devices = registry.devices()?;
+                devices
+                    .iter()
+                    .find(|d| d.id() == device_id)
+                    .ok_or_else(|| anyhow!("Unknown device {}", device_id))?
+                    .as_ref()
+            }
+        };
+        registry.prepare_for_wipe(device, false, false)?;
+
+        let mut task = WipeTask::new(
+            Scheme {
+                description: String::new(),
+                stages: Vec::new(),
+            },
+            Verify::No,
+            device.details().size,
+            block_size.unwrap_or_else(|| device.details().preferred_block_size()),
+        )?;
+        configure(&mut task);
+        task.trim_supported = device.details().trim_supported;
+
+        let mut state = WipeState {
+            retries_left: DEFAULT_RETRIES,
+            verify_retries_left: DEFAULT_RETRIES,
+            ..Default::default()
+        };
+        let mut receiver = JobReceiver {
+            session: JsonFrontend::wipe_stream(
+                device.id(),
+                Box::new(EventSink {
+                    server: Arc::clone(self),
+                    id,
+                    pending: Vec::new(),
+                }),
+            ),
+            recorder: ReportRecorder::new(device.id(), device.details()),
+            server: Arc::clone(self),
+            id,
+        };
+        let mut access = registry.access(device)?;
+        if let Some(g) = access.geometry().ok().flatten() {
+            task.align_to(&g)?;
+        }
+        Ok(task
+            .run(&mut access, &mut state, &mut receiver)
+            .is_success())
+    }
+
+    fn stream_events(&self, stream: &mut TcpStream, id: u64) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+        )?;
+        let mut sent = 0;
+        loop {
+            let (lines, over) = {
+                let mut jobs = self.jobs();
+                let pending = |jobs: &mut Jobs| {
+                    jobs.get(id)
+                        .map(|j| j.events.len() > sent || j.status != JobStatus::Running)
+                        .unwrap_or(true)
+                };
+                if !pending(&mut jobs) {
+                    jobs = self
+                        .changed
+                        .wait_timeout(jobs, KEEPALIVE_INTERVAL)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+                match jobs.get(id) {
+                    Some(job) => (
+                        job.events[sent..].to_vec(),
+                        job.status != JobStatus::Running,
+                    ),
+                    None => (Vec::new(), true),
+                }
+            };
+
+            if lines.is_empty() && !over {
+                write!(stream, ": keepalive\n\n")?;
+            }
+            for line in &lines {
+                write!(stream, "data: {}\n\n", line)?;
+            }
+            stream.flush()?;
+            sent += lines.len();
+            if over {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Keeps the events and the report of a wipe in its job.
+struct JobReceiver {
+    session: JsonWipeSession,
+    recorder: ReportRecorder,
+    server: Arc<Server>,
+    id: u64,
+}
+
+impl WipeEventReceiver for JobReceiver {
+    fn handle(&mut self, task: &WipeTask, state: &WipeState, event: WipeEvent) {
+        if let Some(report) = self.recorder.record(task, state, &event) {
+            if let Some(job) = self.server.jobs().get(self.id) {
+                job.report = serde_json::to_string_pretty(&report).ok();
+            }
+        }
+        self.session.handle(task, state, event);
+    }
+}
+
+/// Output of the JSON session of a wipe, each line becomes an event of its job.
+struct EventSink {
+    server: Arc<Server>,
+    id: u64,
+    pending: Vec<u8>,
+}
+
+impl Write for EventSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+        if !lines.is_empty() {
+            if let Some(job) = self.server.jobs().get(self.id) {
+                job.events.extend(lines);
+            }
+            self.server.changed.notify_all();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn list_devices(config: &Config) -> Result<String> {
+    let mut registry = StorageRegistry::default();
+    registry.register(Box::new(ImageFiles))?;
+    registry.set_filter(config.device_filter()?);
+    let devices = registry.devices()?;
+    let ids: HashSet<_> = devices.iter().map(|d| d.id()).collect();
+    Ok(JsonFrontend::devices(&devices, &IdShortcuts::from(ids)))
+}
+
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(m), Some(p), Some(v)) if v.starts_with("HTTP/1.") => (m.to_string(), p.to_string()),
+        _ => return Err(anyhow!("Malformed request line")),
+    };
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("Unexpected end of request"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(anyhow!("Too many headers"));
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Malformed header"))?;
+        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+
+    let length = match headers.get("content-length") {
+        Some(l) => l.parse::<usize>().context("Invalid Content-Length")?,
+        None => 0,
+    };
+    if length > MAX_BODY_SIZE {
+        return Err(anyhow!("Request body is too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Request {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+/// Image file a request asked to wipe without it being allowed in the config.
+#[derive(thiserror::Error, Debug)]
+#[error("{0} is not on the allow list of the config")]
+struct ImageNotAllowed(String);
+
+/// Compares the digests of the values, so the time it takes tells nothing about the
+/// position of the first differing byte, nor about the length of the expected value.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    Sha256::digest(a)
+        .iter()
+        .zip(Sha256::digest(b).iter())
+        .fold(0, |diff, (x, y)| diff | (x ^ y))
+        == 0
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+    let (status, body) = match response {
+        Response::Json(status, body) => (*status, body.as_str()),
+        Response::Events(_) => unreachable!("events are streamed"),
+    };
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Instant;
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        let raw = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer secret\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        );
+        read_request(&mut raw.as_bytes()).unwrap()
+    }
+
+    fn status(response: &Response) -> u16 {
+        match response {
+            Response::Json(status, _) => *status,
+            Response::Events(_) => 200,
+        }
+    }
+
+    #[test]
+    fn test_request_parsing() {
+        let r = request("POST", "/wipes", "{}");
+        assert_eq!(r.method, "POST");
+        assert_eq!(r.path, "/wipes");
+        assert_eq!(r.headers.get("authorization").unwrap(), "Bearer secret");
+        assert_eq!(r.body, b"{}");
+
+        assert!(read_request(&mut "GET /\r\n\r\n".as_bytes()).is_err());
+        assert!(read_request(&mut "GET / HTTP/1.1\r\nHost".as_bytes()).is_err());
+        let too_large = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", 1 << 20);
+        assert!(read_request(&mut too_large.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_routes_are_authorized_and_validated() {
+        let server = Server::new("secret", Config::default());
+
+        let mut unauthorized = request("GET", "/wipes", "");
+        unauthorized
+            .headers
+            .insert("authorization".to_string(), "Bearer nope".to_string());
+        assert_eq!(status(&server.route(&unauthorized)), 401);
+        unauthorized
+            .headers
+            .insert("authorization".to_string(), "secret".to_string());
+        assert_eq!(status(&server.route(&unauthorized)), 401);
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+
+        assert_eq!(
+            server.route(&request("GET", "/wipes", "")),
+            Response::Json(200, "[]".to_string())
+        );
+        assert_eq!(status(&server.route(&request("GET", "/wipes/1", ""))), 404);
+        assert_eq!(status(&server.route(&request("GET", "/nothing", ""))), 404);
+        assert_eq!(status(&server.route(&request("DELETE", "/wipes", ""))), 405);
+        assert_eq!(
+            status(&server.route(&request("POST", "/wipes", "not json"))),
+            400
+        );
+        assert_eq!(
+            status(&server.route(&request(
+                "POST",
+                "/wipes",
+                r#"{"device":"/dev/x","scheme":"nope"}"#
+            ))),
+            400
+        );
+        assert_eq!(
+            status(&server.route(&request(
+                "POST",
+                "/wipes",
+                r#"{"device":"/dev/x","verify":"twice"}"#
+            ))),
+            400
+        );
+    }
+
+    #[test]
+    fn test_wipe_runs_on_worker() {
+        let path = std::env::temp_dir().join(format!("lethe-serve-{}.img", std::process::id()));
+        std::fs::write(&path, vec![0xffu8; 1 << 20]).unwrap();
+        let device = format!("file://{}", path.canonicalize().unwrap().to_string_lossy());
+
+        let body = format!(
+            r#"{{"device":"{}","scheme":"zero","blocksize":"64k","operator":"Jane"}}"#,
+            device
+        );
+        let server = Server::new("secret", Config::default());
+        assert_eq!(
+            status(&server.route(&request("POST", "/wipes", &body))),
+            403
+        );
+
+        let config = Config {
+            allow: vec![device.clone()],
+            ..Default::default()
+        };
+        let server = Server::new("secret", config);
+        assert_eq!(
+            server.route(&request("POST", "/wipes", &body)),
+            Response::Json(201, r#"{"id":1}"#.to_string())
+        );
+
+        let started = Instant::now();
+        while server.jobs().get(1).unwrap().status == JobStatus::Running {
+            assert!(started.elapsed() < Duration::from_secs(30));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        {
+            let mut jobs = server.jobs();
+            let job = jobs.get(1).unwrap();
+            assert_eq!(job.status, JobStatus::Completed, "{:?}", job.error);
+            assert!(job.events.first().unwrap().contains(r#""event":"started""#));
+            assert!(job
+                .events
+                .last()
+                .unwrap()
+                .contains(r#""event":"completed""#));
+        }
+        assert!(std::fs::read(&path).unwrap().iter().all(|b| *b == 0));
+
+        match server.route(&request("GET", "/wipes/1/report", "")) {
+            Response::Json(200, report) => assert!(report.contains(r#""operator": "Jane""#)),
+            r => panic!("unexpected response {:?}", r),
+        }
+        assert_eq!(
+            status(&server.route(&request("POST", "/wipes/1/cancel", ""))),
+            409
+        );
+        assert_eq!(
+            server.route(&request("GET", "/wipes/1/events", "")),
+            Response::Events(1)
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
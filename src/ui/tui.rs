@@ -0,0 +1,273 @@
+//! Interactive mode: the device, the scheme and the verification are picked with the
+//! arrow keys and handed over to the regular wipe command.
+
+use anyhow::Result;
+use console::{style, Key, Term};
+use indicatif::HumanBytes;
+use std::io;
+
+use crate::sanitization::SchemeRepo;
+use crate::storage::{BusType, MediaType, StorageRef};
+use crate::ui::idshortcuts::IdShortcuts;
+
+const VERIFY_MODES: &[(&str, &str)] = &[
+    ("last", "Read back and check the last stage"),
+    ("all", "Read back and check after each stage"),
+    ("sample:10", "Check 10% of the blocks of the last stage"),
+    ("no", "Don't verify"),
+];
+
+/// What was picked for the wipe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Choice {
+    pub device_id: String,
+    pub scheme: String,
+    pub verify: String,
+}
+
+/// Effect of a key pressed while picking from a list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Moved,
+    Picked(usize),
+    Cancelled,
+    Ignored,
+}
+
+/// Highlighted item of a non-empty list, wrapping around at both ends.
+struct Selection {
+    selected: usize,
+    len: usize,
+}
+
+impl Selection {
+    fn handle(&mut self, key: Key) -> Outcome {
+        match key {
+            Key::ArrowUp | Key::Char('k') => {
+                self.selected = (self.selected + self.len - 1) % self.len;
+                Outcome::Moved
+            }
+            Key::ArrowDown | Key::Char('j') => {
+                self.selected = (self.selected + 1) % self.len;
+                Outcome::Moved
+            }
+            Key::Enter => Outcome::Picked(self.selected),
+            Key::Escape | Key::Char('q') => Outcome::Cancelled,
+            _ => Outcome::Ignored,
+        }
+    }
+}
+
+/// Walks through the pickers, returns `None` if any of them is cancelled.
+pub fn choose<R: StorageRef>(
+    devices: &[R],
+    ids: &IdShortcuts,
+    schemes: &SchemeRepo,
+) -> Result<Option<Choice>> {
+    let term = Term::stdout();
+    if !term.is_term() {
+        return Err(anyhow!("The interactive mode needs a terminal"));
+    }
+    if devices.is_empty() {
+        return Err(anyhow!("No storage devices found"));
+    }
+
+    let id_width = devices.iter().map(|d| d.id().len()).max().unwrap_or(0);
+    let rows: Vec<_> = devices
+        .iter()
+        .map(|d| {
+            let details = d.details();
+            let short = ids.get_short(d.id()).map(|s| s.as_str()).unwrap_or("");
+            let name = details
+                .model
+                .as_deref()
+                .or(details.label.as_deref())
+                .unwrap_or("");
+            format!(
+                "{:<w$}  {:<4} {:>10}  {:<10} {}{}",
+                d.id(),
+                short,
+                HumanBytes(details.size).to_string(),
+                details.storage_type.to_string(),
+                name,
+                if details.is_boot { "  [boot]" } else { "" },
+                w = id_width
+            )
+        })
+        .collect();
+    let device = match pick(&term, "Device to wipe", &rows, |i| {
+        describe_device(&devices[i])
+    })? {
+        Some(i) => &devices[i],
+        None => return Ok(None),
+    };
+
+    let infos = schemes.describe_all();
+    let name_width = infos.iter().map(|s| s.name.len()).max().unwrap_or(0);
+    let rows: Vec<_> = infos
+        .iter()
+        .map(|s| format!("{:<w$}  {}", s.name, s.description, w = name_width))
+        .collect();
+    let flash = device.details().media_type.is_flash();
+    let scheme = match pick(&term, "Scheme", &rows, |i| {
+        let info = &infos[i];
+        let stages: Vec<_> = info.stages.iter().map(|s| s.description.as_str()).collect();
+        let mut lines = vec![
+            format!("Passes: {}", info.passes),
+            format!("Stages: {}", stages.join(", ")),
+        ];
+        if flash && info.passes > 1 {
+            lines.push(format!(
+                "{}",
+                style("Extra passes only add wear on flash media").yellow()
+            ));
+        }
+        lines
+    })? {
+        Some(i) => infos[i].name.clone(),
+        None => return Ok(None),
+    };
+
+    let rows: Vec<_> = VERIFY_MODES
+        .iter()
+        .map(|(mode, description)| format!("{:<9}  {}", mode, description))
+        .collect();
+    let verify = match pick(&term, "Verification", &rows, |_| Vec::new())? {
+        Some(i) => VERIFY_MODES[i].0,
+        None => return Ok(None),
+    };
+
+    Ok(Some(Choice {
+        device_id: device.id().to_string(),
+        scheme,
+        verify: verify.to_string(),
+    }))
+}
+
+fn describe_device<R: StorageRef>(device: &R) -> Vec<String> {
+    let details = device.details();
+    let mut lines = Vec::new();
+    let mut add = |name: &str, value: Option<String>| {
+        if let Some(v) = value {
+            lines.push(format!("{:<12}{}", name, v));
+        }
+    };
+    add("Model", details.model.clone());
+    add("Serial", details.serial.clone());
+    add("Firmware", details.firmware.clone());
+    add(
+        "Media",
+        Some(details.media_type)
+            .filter(|m| *m != MediaType::Unknown)
+            .map(|m| m.to_string()),
+    );
+    add(
+        "Bus",
+        Some(details.bus_type)
+            .filter(|b| *b != BusType::Unknown)
+            .map(|b| b.to_string()),
+    );
+    add("Sectors", details.describe_sectors());
+    add(
+        "Filesystem",
+        match (&details.label, &details.filesystem) {
+            (Some(label), Some(fs)) => Some(format!("{} ({})", label, fs)),
+            (label, fs) => label.clone().or_else(|| fs.clone()),
+        },
+    );
+    add("Mount point", details.mount_point.clone());
+    add("Alias", details.aliases.first().cloned());
+    if details.is_boot {
+        lines.push(format!(
+            "{}",
+            style("The system is booted from this device!").red().bold()
+        ));
+    }
+    lines
+}
+
+/// Shows the items with the highlighted one followed by its details and waits for the
+/// choice. Returns `None` if cancelled. The list is erased once done.
+fn pick(
+    term: &Term,
+    title: &str,
+    items: &[String],
+    details: impl Fn(usize) -> Vec<String>,
+) -> io::Result<Option<usize>> {
+    // wrapped lines would break erasing the list
+    let width = term.size().1.saturating_sub(4).max(20) as usize;
+    let fit = |s: &str| s.chars().take(width).collect::<String>();
+
+    let mut selection = Selection {
+        selected: 0,
+        len: items.len(),
+    };
+    let mut drawn = 0;
+    loop {
+        if drawn > 0 {
+            term.clear_last_lines(drawn)?;
+        }
+
+        let mut lines = vec![style(title).bold().to_string()];
+        for (i, item) in items.iter().enumerate() {
+            if i == selection.selected {
+                lines.push(format!(
+                    "{}",
+                    style(format!("> {}", fit(item))).cyan().bold()
+                ));
+            } else {
+                lines.push(format!("  {}", fit(item)));
+            }
+        }
+        let details = details(selection.selected);
+        if !details.is_empty() {
+            lines.push(String::new());
+            lines.extend(details.iter().map(|l| format!("    {}", fit(l))));
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "{}",
+            style("Up/down to select, Enter to confirm, Esc to cancel").dim()
+        ));
+
+        for l in &lines {
+            term.write_line(l)?;
+        }
+        drawn = lines.len();
+
+        match selection.handle(term.read_key()?) {
+            Outcome::Picked(i) => {
+                term.clear_last_lines(drawn)?;
+                term.write_line(&format!("{}: {}", title, fit(items[i].trim_end())))?;
+                return Ok(Some(i));
+            }
+            Outcome::Cancelled => {
+                term.clear_last_lines(drawn)?;
+                return Ok(None);
+            }
+            Outcome::Moved | Outcome::Ignored => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_selection_wraps_around() {
+        let mut selection = Selection {
+            selected: 0,
+            len: 3,
+        };
+
+        assert_eq!(selection.handle(Key::ArrowUp), Outcome::Moved);
+        assert_eq!(selection.selected, 2);
+        assert_eq!(selection.handle(Key::ArrowDown), Outcome::Moved);
+        assert_eq!(selection.selected, 0);
+        assert_eq!(selection.handle(Key::Char('j')), Outcome::Moved);
+        assert_eq!(selection.handle(Key::Char('x')), Outcome::Ignored);
+        assert_eq!(selection.handle(Key::Enter), Outcome::Picked(1));
+        assert_eq!(selection.handle(Key::Escape), Outcome::Cancelled);
+    }
+}